@@ -0,0 +1,125 @@
+// Compares projecting the records on the async task with projecting them
+// on the blocking thread pool through the RecordComputeQueue. Each record
+// waits on a simulated exchange round trip like the materialize task does
+// when it confirms a record.
+//
+//     cargo bench --bench record_compute
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use arrow::array::{Float64Array, Int64Array, RecordBatch};
+use arrow::datatypes::{DataType, Field, Schema};
+use sqlparser::ast::{SelectItem, SetExpr, Statement};
+use sqlparser::dialect::GenericDialect;
+use sqlparser::parser::Parser;
+
+use chapterhouseqe::handlers::operator_handler::operators::{project_record, RecordComputeQueue};
+
+const NUM_RECORDS: usize = 64;
+const ROWS_PER_RECORD: usize = 100_000;
+const ROUND_TRIP: Duration = Duration::from_millis(2);
+const MAX_IN_FLIGHT: usize = 8;
+
+fn build_record(offset: usize) -> Result<Arc<RecordBatch>> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("price", DataType::Float64, false),
+    ]));
+    let ids: Vec<i64> = (0..ROWS_PER_RECORD)
+        .map(|idx| (offset + idx) as i64)
+        .collect();
+    let prices: Vec<f64> = ids.iter().map(|id| *id as f64 * 0.25).collect();
+    Ok(Arc::new(RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int64Array::from(ids)),
+            Arc::new(Float64Array::from(prices)),
+        ],
+    )?))
+}
+
+fn build_fields() -> Result<Vec<SelectItem>> {
+    let query = "select id * 3 + 7, price * price - id, (id % 13) * price, \
+        price / 2.5 + id * 4 from t";
+    let statement = Parser::parse_sql(&GenericDialect {}, query)?.remove(0);
+    match statement {
+        Statement::Query(query) => match *query.body {
+            SetExpr::Select(select) => Ok(select.projection),
+            _ => Err(anyhow::Error::msg("expected a select")),
+        },
+        _ => Err(anyhow::Error::msg("expected a query")),
+    }
+}
+
+async fn project_inline(records: &[Arc<RecordBatch>], fields: &[SelectItem]) -> Result<usize> {
+    let fields = fields.to_vec();
+    let mut rows = 0;
+    for record in records {
+        let proj_rec = project_record(&fields, record.clone(), &vec![])?;
+        rows += proj_rec.num_rows();
+        tokio::time::sleep(ROUND_TRIP).await;
+    }
+    Ok(rows)
+}
+
+async fn project_queued(records: &[Arc<RecordBatch>], fields: &[SelectItem]) -> Result<usize> {
+    let mut queue: RecordComputeQueue<RecordBatch> = RecordComputeQueue::new(MAX_IN_FLIGHT);
+    let mut rows = 0;
+    let mut records = records.iter();
+    loop {
+        if !queue.is_full() {
+            if let Some(record) = records.next() {
+                let fields = fields.to_vec();
+                let record = record.clone();
+                queue.push(move || project_record(&fields, record, &vec![]));
+                continue;
+            }
+        }
+        match queue.next().await {
+            Some(res) => {
+                rows += res?.num_rows();
+                tokio::time::sleep(ROUND_TRIP).await;
+            }
+            None => break,
+        }
+    }
+    Ok(rows)
+}
+
+fn report(name: &str, rows: usize, elapsed: Duration) {
+    println!(
+        "{:<8} {:>8.1} ms {:>12.0} rows/s",
+        name,
+        elapsed.as_secs_f64() * 1000.0,
+        rows as f64 / elapsed.as_secs_f64()
+    );
+}
+
+fn main() -> Result<()> {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_all()
+        .build()?;
+
+    let records = (0..NUM_RECORDS)
+        .map(|idx| build_record(idx * ROWS_PER_RECORD))
+        .collect::<Result<Vec<Arc<RecordBatch>>>>()?;
+    let fields = build_fields()?;
+
+    rt.block_on(async {
+        // warm up
+        project_inline(&records[..4], &fields).await?;
+
+        let start = Instant::now();
+        let rows = project_inline(&records, &fields).await?;
+        report("inline", rows, start.elapsed());
+
+        let start = Instant::now();
+        let rows = project_queued(&records, &fields).await?;
+        report("queued", rows, start.elapsed());
+
+        Ok(())
+    })
+}
@@ -1,22 +1,177 @@
+use std::io::{IsTerminal, Read};
+use std::path::{Path, PathBuf};
+
 use anyhow::{Context, Result};
-use chapterhouseqe::client::AsyncQueryClient;
-use tracing::info;
+use chapterhouseqe::client::{parse_sql_queries, AsyncQueryClient};
+use chapterhouseqe::handlers::message_handler::messages;
+use clap::Parser;
+use thiserror::Error;
 use tracing_subscriber;
 
+#[derive(Debug, Error)]
+enum ClientError {
+    #[error(
+        "the queries are read from stdin but stdin is a terminal; pipe the sql into the client"
+    )]
+    StdinIsATerminal,
+}
+
+/// Runs the queries in a sql file without a user interface
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    /// Address of the worker the queries are sent to
+    #[arg(short, long, default_value_t = String::from("127.0.0.1:7000"))]
+    address: String,
+
+    /// File containing the queries separated by semicolons; - reads stdin
+    #[arg(long)]
+    sql_file: String,
+
+    /// Directory the result of each query is written to as csv
+    #[arg(short, long, default_value_t = String::from("results/"))]
+    output: String,
+
+    /// Token used to authenticate with the worker
+    #[arg(long)]
+    auth_token: Option<String>,
+}
+
+struct QueryOutcome {
+    query_idx: usize,
+    query_id: Option<u128>,
+    status: String,
+    num_rows: usize,
+    output_file: Option<PathBuf>,
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     tracing_subscriber::fmt::init();
 
-    let address = "127.0.0.1:7000";
-    let client = AsyncQueryClient::new(address.to_string());
+    let args = Args::parse();
 
-    let query = "select * from read_files('simple/*.parquet');";
-    let run_query_resp = client
-        .run_query(query.to_string())
-        .await
-        .context("failed initiating a query run")?;
+    let mut client = AsyncQueryClient::new(args.address.clone());
+    if let Some(auth_token) = args.auth_token {
+        client.set_auth_token(auth_token);
+    }
 
-    info!("run_query_resp: {:?}", run_query_resp);
+    let sql = read_sql(&args.sql_file)?;
+    std::fs::create_dir_all(&args.output)
+        .with_context(|| format!("failed creating the output directory {}", args.output))?;
+
+    let mut outcomes = Vec::new();
+    for (query_idx, query) in parse_sql_queries(&sql).into_iter().enumerate() {
+        let output_file = Path::new(&args.output).join(format!("query_{}.csv", query_idx));
+        outcomes.push(execute_query(&client, query_idx, query, output_file).await);
+    }
+
+    print_summary(&outcomes);
 
     Ok(())
 }
+
+fn read_sql(sql_file: &str) -> Result<String> {
+    if sql_file != "-" {
+        return std::fs::read_to_string(sql_file)
+            .with_context(|| format!("failed reading the sql file {}", sql_file));
+    }
+
+    // reading a terminal would wait for input the user doesn't know to give
+    let mut stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        return Err(ClientError::StdinIsATerminal.into());
+    }
+    let mut sql = String::new();
+    stdin
+        .read_to_string(&mut sql)
+        .context("failed reading the sql from stdin")?;
+    Ok(sql)
+}
+
+// A failed query is reported in the summary instead of stopping the
+// remaining queries.
+async fn execute_query(
+    client: &AsyncQueryClient,
+    query_idx: usize,
+    query: String,
+    output_file: PathBuf,
+) -> QueryOutcome {
+    let mut outcome = QueryOutcome {
+        query_idx,
+        query_id: None,
+        status: String::new(),
+        num_rows: 0,
+        output_file: None,
+    };
+
+    let query_id = match client.run_query(query).await {
+        Ok(messages::query::RunQueryResp::Created { query_id }) => query_id,
+        Ok(messages::query::RunQueryResp::Explained { plan }) => {
+            println!("query {} plan:\n{}", query_idx, plan);
+            outcome.status = "explained".to_string();
+            return outcome;
+        }
+        Ok(messages::query::RunQueryResp::NotCreated) => {
+            outcome.status = "not created".to_string();
+            return outcome;
+        }
+        Err(err) => {
+            outcome.status = format!("error: {:#}", err);
+            return outcome;
+        }
+    };
+    outcome.query_id = Some(query_id);
+
+    let res = write_query_results(client, query_id, &output_file).await;
+    match res {
+        Ok(num_rows) => {
+            outcome.status = "complete".to_string();
+            outcome.num_rows = num_rows;
+            outcome.output_file = Some(output_file);
+        }
+        Err(err) => outcome.status = format!("error: {:#}", err),
+    }
+    outcome
+}
+
+async fn write_query_results(
+    client: &AsyncQueryClient,
+    query_id: u128,
+    output_file: &Path,
+) -> Result<usize> {
+    client.wait_for_query_results(query_id).await?;
+    let records = client.collect_records(query_id).await?;
+
+    let file = std::fs::File::create(output_file)
+        .with_context(|| format!("failed creating the output file {}", output_file.display()))?;
+    let mut csv_writer = arrow::csv::WriterBuilder::new()
+        .with_header(true)
+        .build(file);
+    for record in &records {
+        csv_writer.write(record.as_ref())?;
+    }
+
+    Ok(records.iter().map(|record| record.num_rows()).sum())
+}
+
+fn print_summary(outcomes: &[QueryOutcome]) {
+    println!(
+        "{:<6} {:<36} {:<10} {:<30} {}",
+        "query", "query id", "rows", "output", "status"
+    );
+    for outcome in outcomes {
+        let query_id = match outcome.query_id {
+            Some(query_id) => uuid::Uuid::from_u128(query_id).to_string(),
+            None => "-".to_string(),
+        };
+        let output_file = match &outcome.output_file {
+            Some(output_file) => output_file.display().to_string(),
+            None => "-".to_string(),
+        };
+        println!(
+            "{:<6} {:<36} {:<10} {:<30} {}",
+            outcome.query_idx, query_id, outcome.num_rows, output_file, outcome.status
+        );
+    }
+}
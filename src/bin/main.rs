@@ -1,10 +1,12 @@
-use std::collections::HashMap;
-
 use clap;
 use tracing_subscriber;
 
 use chapterhouseqe::{
-    handlers::operator_handler::{operators, TotalOperatorCompute},
+    handlers::message_handler::TlsConfig,
+    handlers::operator_handler::{
+        operators, TotalOperatorCompute, DEFAULT_IDENTIFY_EXCHANGE_MAX_ATTEMPTS,
+    },
+    planner::{DataFormat, PlannerConfig},
     worker::{QueryWorker, QueryWorkerConfig},
 };
 
@@ -21,6 +23,74 @@ struct Args {
     // Addresses to connect to
     #[arg(short, long)]
     connect_to_addresses: Vec<String>,
+
+    /// Token workers and client connections must provide; auth is disabled
+    /// when not set
+    #[arg(long)]
+    auth_token: Option<String>,
+
+    /// Instances of each producer which can run in parallel
+    #[arg(long, default_value_t = 1)]
+    producer_instances: usize,
+
+    /// Format of the query result files; parquet, csv or jsonl
+    #[arg(long, default_value_t = DataFormat::Parquet.file_extension().to_string())]
+    output_format: String,
+
+    /// Max mebibytes buffered while reassembling large messages
+    #[arg(long, default_value_t = 256)]
+    max_reassembly_mib: usize,
+
+    /// Pem certificate chain of the worker; enables tls with the key and ca
+    #[arg(long)]
+    tls_cert: Option<String>,
+
+    /// Pem private key of the worker
+    #[arg(long)]
+    tls_key: Option<String>,
+
+    /// Pem certificate authority used to verify other workers and clients
+    #[arg(long)]
+    tls_ca: Option<String>,
+
+    /// Times a task asks the query handler for an exchange before giving up
+    #[arg(long, default_value_t = DEFAULT_IDENTIFY_EXCHANGE_MAX_ATTEMPTS)]
+    identify_exchange_max_attempts: u32,
+
+    /// Persist the query state so queries survive a restart
+    #[arg(long, default_value_t = false)]
+    persist_query_state: bool,
+
+    /// Local directory storing the tables and query results, for example
+    /// ./sample_data; /tmp/chapterhouse is used when not set
+    #[arg(long)]
+    storage_root: Option<String>,
+
+    /// Mebibytes of recently read storage byte ranges to cache; the cache
+    /// is disabled when not set
+    #[arg(long)]
+    read_cache_mib: Option<usize>,
+
+    /// S3 bucket storing the tables and query results; replaces the
+    /// storage root when set
+    #[arg(long)]
+    s3_bucket: Option<String>,
+
+    /// Region of the S3 bucket
+    #[arg(long)]
+    s3_region: Option<String>,
+
+    /// Endpoint of an S3 compatible service such as MinIO
+    #[arg(long)]
+    s3_endpoint: Option<String>,
+
+    /// S3 access key; read from AWS_ACCESS_KEY_ID when not set
+    #[arg(long)]
+    s3_access_key: Option<String>,
+
+    /// S3 secret key; read from AWS_SECRET_ACCESS_KEY when not set
+    #[arg(long)]
+    s3_secret_key: Option<String>,
 }
 
 fn main() {
@@ -32,15 +102,14 @@ fn main() {
         .init();
 
     let mut conn_reg = operators::ConnectionRegistry::new();
-    conn_reg.add_connection(
-        "default".to_string(),
-        opendal::Scheme::Fs,
-        vec![("root".to_string(), "./sample_data".to_string())]
-            .into_iter()
-            .collect::<HashMap<String, String>>(),
-    );
+    if let Some(storage_root) = args.storage_root {
+        conn_reg.register_fs("default".to_string(), storage_root);
+    }
+    if let Some(read_cache_mib) = args.read_cache_mib {
+        conn_reg.set_read_cache_mib(read_cache_mib);
+    }
 
-    let mut worker = QueryWorker::new(QueryWorkerConfig::new(
+    let mut config = QueryWorkerConfig::new(
         format!("127.0.0.1:{}", args.port),
         args.connect_to_addresses,
         TotalOperatorCompute {
@@ -49,7 +118,50 @@ fn main() {
             cpu_in_thousandths: 4_000,
         },
         conn_reg,
-    ));
+    );
+    if let Some(bucket) = args.s3_bucket {
+        config.set_s3_config(operators::S3Config {
+            bucket,
+            region: args.s3_region,
+            endpoint: args.s3_endpoint,
+            access_key: args.s3_access_key,
+            secret_key: args.s3_secret_key,
+        });
+    }
+    if let Some(auth_token) = args.auth_token {
+        config.set_auth_token(auth_token);
+    }
+    config.set_max_reassembly_bytes(args.max_reassembly_mib * 1024 * 1024);
+    config.set_persist_query_state(args.persist_query_state);
+    config.set_identify_exchange_max_attempts(args.identify_exchange_max_attempts);
+    match (args.tls_cert, args.tls_key, args.tls_ca) {
+        (Some(cert_path), Some(key_path), Some(ca_path)) => {
+            config.set_tls_config(TlsConfig {
+                cert_path,
+                key_path,
+                ca_path,
+            });
+        }
+        (None, None, None) => (),
+        _ => {
+            println!("error: --tls-cert, --tls-key and --tls-ca must be set together");
+            return;
+        }
+    }
+    let output_data_format = match args.output_format.parse::<DataFormat>() {
+        Ok(data_format) => data_format,
+        Err(err) => {
+            println!("error: {}", err);
+            return;
+        }
+    };
+    config.set_planner_config(PlannerConfig {
+        default_producer_instances: args.producer_instances,
+        output_data_format,
+        ..PlannerConfig::default()
+    });
+
+    let mut worker = QueryWorker::new(config);
 
     match worker.start() {
         Ok(_) => return,
@@ -1,3 +1,6 @@
+use std::sync::Arc;
+use std::time::Duration;
+
 use anyhow::{Context, Result};
 use bytes::BytesMut;
 use thiserror::Error;
@@ -5,7 +8,13 @@ use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 use uuid::Uuid;
 
-use crate::handlers::message_handler::{messages, MessageRegistry};
+use crate::handlers::message_handler::{
+    is_heartbeat, is_incomplete_msg_error, messages, ConnectionStream, MessageRegistry, Tls,
+    TlsConfig,
+};
+
+// how often the worker is asked whether the query results were written
+const QUERY_RESULTS_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 #[derive(Debug, Error)]
 pub enum AsyncQueryClientError {
@@ -15,12 +24,28 @@ pub enum AsyncQueryClientError {
     ConnectionResetByPeer,
     #[error("expected message but received none")]
     ExpectedMessageButReceivedNone,
+    #[error("connection rejected by the worker: {0}")]
+    ConnectionRejected(String),
+    #[error("query not found: {0}")]
+    QueryNotFound(u128),
+    #[error("query failed: {0}")]
+    QueryFailed(String),
+    #[error("unexpected query results message: {0}")]
+    UnexpectedQueryResultsMessage(String),
+    #[error("query results not ready: {0}")]
+    QueryResultsNotReady(u128),
+    #[error("schema of file {0} differs from the first file")]
+    QueryResultsSchemaMismatch(u64),
 }
 
 #[derive(Debug)]
 pub struct AsyncQueryClient {
     address: String,
     msg_reg: MessageRegistry,
+    auth_token: Option<String>,
+    tls: Option<Tls>,
+    query_timeout: Option<Duration>,
+    query_priority: messages::query::QueryPriority,
 }
 
 impl AsyncQueryClient {
@@ -28,17 +53,49 @@ impl AsyncQueryClient {
         AsyncQueryClient {
             address,
             msg_reg: MessageRegistry::new(),
+            auth_token: None,
+            tls: None,
+            query_timeout: None,
+            query_priority: messages::query::QueryPriority::default(),
         }
     }
 
+    pub fn set_auth_token(&mut self, token: String) -> &Self {
+        self.auth_token = Some(token);
+        self
+    }
+
+    // Queries which haven't finished within the timeout are failed by the
+    // worker.
+    pub fn set_query_timeout(&mut self, timeout: Duration) -> &Self {
+        self.query_timeout = Some(timeout);
+        self
+    }
+
+    // The priority of the queries run by this client.
+    pub fn set_query_priority(&mut self, priority: messages::query::QueryPriority) -> &Self {
+        self.query_priority = priority;
+        self
+    }
+
+    // Required when the worker uses tls; the client presents the
+    // certificate to the worker.
+    pub fn set_tls_config(&mut self, tls_config: TlsConfig) -> Result<&Self> {
+        self.tls = Some(Tls::new(&tls_config)?);
+        Ok(self)
+    }
+
     pub async fn run_query(&self, query: String) -> Result<messages::query::RunQueryResp> {
         let (ref mut stream, connection_id) = self
             .create_connection()
             .await
             .context("connection failed")?;
 
-        let ref mut run_query =
-            messages::message::Message::new(Box::new(messages::query::RunQuery::new(query)));
+        let mut run_query = messages::query::RunQuery::new(query).set_priority(self.query_priority);
+        if let Some(timeout) = self.query_timeout {
+            run_query = run_query.set_timeout(timeout);
+        }
+        let ref mut run_query = messages::message::Message::new(Box::new(run_query));
         self.send_msg(stream, run_query, connection_id)
             .await
             .context("failed to send query")?;
@@ -47,8 +104,273 @@ impl AsyncQueryClient {
         Ok(query_resp)
     }
 
-    async fn create_connection(&self) -> Result<(TcpStream, u128)> {
-        let mut stream = TcpStream::connect(self.address.clone()).await?;
+    pub async fn cancel_query(&self, query_id: u128) -> Result<messages::query::CancelQueryResp> {
+        let (ref mut stream, connection_id) = self
+            .create_connection()
+            .await
+            .context("connection failed")?;
+
+        let cancel_query = &mut messages::message::Message::new(Box::new(
+            messages::query::CancelQuery::new(query_id),
+        ));
+        self.send_msg(stream, cancel_query, connection_id)
+            .await
+            .context("failed to send the cancel query request")?;
+
+        let cancel_resp: messages::query::CancelQueryResp = self.expect_msg(stream).await?;
+        Ok(cancel_resp)
+    }
+
+    pub async fn update_query_priority(
+        &self,
+        query_id: u128,
+        priority: messages::query::QueryPriority,
+    ) -> Result<messages::query::SetQueryPriorityResp> {
+        let (ref mut stream, connection_id) = self
+            .create_connection()
+            .await
+            .context("connection failed")?;
+
+        let set_priority = &mut messages::message::Message::new(Box::new(
+            messages::query::SetQueryPriority::new(query_id, priority),
+        ));
+        self.send_msg(stream, set_priority, connection_id)
+            .await
+            .context("failed to send the set query priority request")?;
+
+        let priority_resp: messages::query::SetQueryPriorityResp = self.expect_msg(stream).await?;
+        Ok(priority_resp)
+    }
+
+    pub async fn list_queries(
+        &self,
+        status: Option<messages::query::QueryStatus>,
+    ) -> Result<messages::query::ListQueriesResp> {
+        let (ref mut stream, connection_id) = self
+            .create_connection()
+            .await
+            .context("connection failed")?;
+
+        let list_queries = &mut messages::message::Message::new(Box::new(
+            messages::query::ListQueries::new(status),
+        ));
+        self.send_msg(stream, list_queries, connection_id)
+            .await
+            .context("failed to send the list queries request")?;
+
+        let list_resp: messages::query::ListQueriesResp = self.expect_msg(stream).await?;
+        Ok(list_resp)
+    }
+
+    pub async fn get_query_status(
+        &self,
+        query_id: u128,
+    ) -> Result<messages::query::GetQueryStatusResp> {
+        let (ref mut stream, connection_id) = self
+            .create_connection()
+            .await
+            .context("connection failed")?;
+
+        let get_status = &mut messages::message::Message::new(Box::new(
+            messages::query::GetQueryStatus::new(query_id),
+        ));
+        self.send_msg(stream, get_status, connection_id)
+            .await
+            .context("failed to send the get query status request")?;
+
+        let status_resp: messages::query::GetQueryStatusResp = self.expect_msg(stream).await?;
+        Ok(status_resp)
+    }
+
+    pub async fn get_query_metrics(
+        &self,
+        query_id: u128,
+    ) -> Result<messages::query::GetQueryMetricsResp> {
+        let (ref mut stream, connection_id) = self
+            .create_connection()
+            .await
+            .context("connection failed")?;
+
+        let get_metrics = &mut messages::message::Message::new(Box::new(
+            messages::query::GetQueryMetrics::new(query_id),
+        ));
+        self.send_msg(stream, get_metrics, connection_id)
+            .await
+            .context("failed to send the get query metrics request")?;
+
+        let metrics_resp: messages::query::GetQueryMetricsResp = self.expect_msg(stream).await?;
+        Ok(metrics_resp)
+    }
+
+    pub async fn get_query_diagnostics(
+        &self,
+        query_id: u128,
+    ) -> Result<messages::query::GetQueryDiagnosticsResp> {
+        let (ref mut stream, connection_id) = self
+            .create_connection()
+            .await
+            .context("connection failed")?;
+
+        let ref mut get_diagnostics = messages::message::Message::new(Box::new(
+            messages::query::GetQueryDiagnostics::new(query_id),
+        ));
+        self.send_msg(stream, get_diagnostics, connection_id)
+            .await
+            .context("failed to send the get query diagnostics request")?;
+
+        let diagnostics_resp: messages::query::GetQueryDiagnosticsResp =
+            self.expect_msg(stream).await?;
+        Ok(diagnostics_resp)
+    }
+
+    pub async fn get_query_plan(
+        &self,
+        query_id: u128,
+    ) -> Result<messages::query::GetQueryPlanResp> {
+        let (ref mut stream, connection_id) = self
+            .create_connection()
+            .await
+            .context("connection failed")?;
+
+        let get_plan = &mut messages::message::Message::new(Box::new(
+            messages::query::GetQueryPlan::new(query_id),
+        ));
+        self.send_msg(stream, get_plan, connection_id)
+            .await
+            .context("failed to send the get query plan request")?;
+
+        let plan_resp: messages::query::GetQueryPlanResp = self.expect_msg(stream).await?;
+        Ok(plan_resp)
+    }
+
+    // Returns None when the query has already finished; its results can
+    // then be read with get_query_data.
+    pub async fn subscribe_query_results(
+        &self,
+        query_id: u128,
+    ) -> Result<Option<QueryResultsSubscription<'_>>> {
+        let (mut stream, connection_id) = self
+            .create_connection()
+            .await
+            .context("connection failed")?;
+
+        let subscribe = &mut messages::message::Message::new(Box::new(
+            messages::query::SubscribeQueryResults::new(query_id),
+        ));
+        self.send_msg(&mut stream, subscribe, connection_id)
+            .await
+            .context("failed to send the subscribe query results request")?;
+
+        let mut subscription = QueryResultsSubscription {
+            client: self,
+            stream,
+            buf: BytesMut::new(),
+        };
+        match subscription.next_results().await? {
+            messages::query::QueryResults::Subscribed => Ok(Some(subscription)),
+            messages::query::QueryResults::AlreadyFinished => Ok(None),
+            messages::query::QueryResults::QueryNotFound => {
+                Err(AsyncQueryClientError::QueryNotFound(query_id).into())
+            }
+            messages::query::QueryResults::Error(err) => {
+                Err(AsyncQueryClientError::QueryFailed(err).into())
+            }
+            results => Err(
+                AsyncQueryClientError::UnexpectedQueryResultsMessage(format!("{:?}", results))
+                    .into(),
+            ),
+        }
+    }
+
+    pub async fn get_query_data(
+        &self,
+        query_id: u128,
+        file_idx: u64,
+    ) -> Result<messages::query::GetQueryDataResp> {
+        let (ref mut stream, connection_id) = self
+            .create_connection()
+            .await
+            .context("connection failed")?;
+
+        let get_data = &mut messages::message::Message::new(Box::new(
+            messages::query::GetQueryData::new(query_id, file_idx),
+        ));
+        self.send_msg(stream, get_data, connection_id)
+            .await
+            .context("failed to send the get query data request")?;
+
+        let data_resp: messages::query::GetQueryDataResp = self.expect_msg(stream).await?;
+        Ok(data_resp)
+    }
+
+    // Waits until the query has written its results; errors if the query
+    // fails first.
+    pub async fn wait_for_query_results(&self, query_id: u128) -> Result<()> {
+        loop {
+            match self.get_query_status(query_id).await? {
+                messages::query::GetQueryStatusResp::Status(
+                    messages::query::QueryStatus::Error(err),
+                ) => return Err(AsyncQueryClientError::QueryFailed(err).into()),
+                messages::query::GetQueryStatusResp::Status(
+                    messages::query::QueryStatus::Cancelled,
+                ) => {
+                    return Err(
+                        AsyncQueryClientError::QueryFailed("query cancelled".to_string()).into(),
+                    )
+                }
+                messages::query::GetQueryStatusResp::QueryNotFound => {
+                    return Err(AsyncQueryClientError::QueryNotFound(query_id).into())
+                }
+                _ => (),
+            }
+
+            match self.get_query_data(query_id, 0).await? {
+                messages::query::GetQueryDataResp::QueryResultsNotReady => (),
+                _ => return Ok(()),
+            }
+            tokio::time::sleep(QUERY_RESULTS_POLL_INTERVAL).await;
+        }
+    }
+
+    // Reads the record from each of the result files.
+    pub async fn collect_records(
+        &self,
+        query_id: u128,
+    ) -> Result<Vec<Arc<arrow::array::RecordBatch>>> {
+        let mut records = Vec::new();
+        let mut file_idx = 0;
+        loop {
+            match self.get_query_data(query_id, file_idx).await? {
+                messages::query::GetQueryDataResp::Record { record, .. } => {
+                    records.push(record);
+                    file_idx += 1;
+                }
+                messages::query::GetQueryDataResp::ReachedEndOfFiles => break,
+                messages::query::GetQueryDataResp::QueryResultsNotReady => {
+                    return Err(AsyncQueryClientError::QueryResultsNotReady(query_id).into());
+                }
+                messages::query::GetQueryDataResp::Error(err) => {
+                    return Err(AsyncQueryClientError::QueryFailed(err).into());
+                }
+            }
+        }
+        Ok(records)
+    }
+
+    // Reads all of the result files and encodes the records as a single
+    // arrow ipc stream. The stream is empty when the query didn't produce
+    // any files.
+    pub async fn collect_ipc(&self, query_id: u128) -> Result<Vec<u8>> {
+        let records = self.collect_records(query_id).await?;
+        records_to_ipc(&records)
+    }
+
+    async fn create_connection(&self) -> Result<(Box<dyn ConnectionStream>, u128)> {
+        let tcp_stream = TcpStream::connect(self.address.clone()).await?;
+        let mut stream = match &self.tls {
+            Some(tls) => tls.connect(&self.address, tcp_stream).await?,
+            None => Box::new(tcp_stream) as Box<dyn ConnectionStream>,
+        };
         let connection_id = Uuid::new_v4().as_u128();
         self.identify(&mut stream, connection_id.clone())
             .await
@@ -56,24 +378,35 @@ impl AsyncQueryClient {
         Ok((stream, connection_id))
     }
 
-    async fn identify(&self, stream: &mut TcpStream, connection_id: u128) -> Result<()> {
+    async fn identify(
+        &self,
+        stream: &mut Box<dyn ConnectionStream>,
+        connection_id: u128,
+    ) -> Result<()> {
         let ref mut identify =
             messages::message::Message::new(Box::new(messages::common::Identify::Connection {
                 id: connection_id,
+                token: self.auth_token.clone(),
+                max_header_version: messages::message::HEADER_VERSION,
             }));
         self.send_msg(stream, identify, connection_id).await?;
 
-        let _: messages::common::Identify = self
+        let identify_resp: messages::common::Identify = self
             .expect_msg(stream)
             .await
             .context("failed to receive response identification from the worker")?;
 
-        Ok(())
+        match identify_resp {
+            messages::common::Identify::Rejected { reason } => {
+                Err(AsyncQueryClientError::ConnectionRejected(reason).into())
+            }
+            _ => Ok(()),
+        }
     }
 
     async fn expect_msg<T: messages::message::SendableMessage>(
         &self,
-        stream: &mut TcpStream,
+        stream: &mut Box<dyn ConnectionStream>,
     ) -> Result<T> {
         let msg = self.read_msg(stream).await?;
         match msg {
@@ -82,18 +415,37 @@ impl AsyncQueryClient {
         }
     }
 
-    async fn read_msg(&self, stream: &mut TcpStream) -> Result<Option<messages::message::Message>> {
-        let ref mut buf = BytesMut::new();
+    async fn read_msg(
+        &self,
+        stream: &mut Box<dyn ConnectionStream>,
+    ) -> Result<Option<messages::message::Message>> {
+        self.read_buffered_msg(stream, &mut BytesMut::new()).await
+    }
+
+    // The buffer keeps any data read past the message for the next read.
+    async fn read_buffered_msg(
+        &self,
+        stream: &mut Box<dyn ConnectionStream>,
+        buf: &mut BytesMut,
+    ) -> Result<Option<messages::message::Message>> {
         loop {
-            if let Ok(msg) = self.msg_reg.build_msg(buf) {
-                if let Some(msg) = msg {
+            match self.msg_reg.build_msg(buf) {
+                Ok(Some(msg)) => {
+                    // workers ping idle connections
+                    if is_heartbeat(&msg) {
+                        self.reply_to_heartbeat(stream, &msg).await?;
+                        continue;
+                    }
                     return Ok(Some(msg));
                 }
-                continue;
+                // a fragment was buffered; the rest may already be read
+                Ok(None) => continue,
+                Err(err) if is_incomplete_msg_error(&err) => (),
+                Err(err) => return Err(err.context("failed decoding a message from the worker")),
             }
 
             // end the conneciton if the other system has sent too much data
-            if buf.len() > 1024 * 1024 * 10 {
+            if buf.len() > 2 * messages::message::SerializedMessage::max_frame_len() {
                 return Err(AsyncQueryClientError::BufferReachedMaxSize.into());
             }
 
@@ -112,9 +464,22 @@ impl AsyncQueryClient {
         }
     }
 
+    async fn reply_to_heartbeat(
+        &self,
+        stream: &mut Box<dyn ConnectionStream>,
+        msg: &messages::message::Message,
+    ) -> Result<()> {
+        if let Ok(messages::common::Ping::Ping) = self.msg_reg.try_cast_msg(msg) {
+            let pong = messages::message::Message::new(Box::new(messages::common::Ping::Pong))
+                .set_request_id(msg.request_id);
+            stream.write_all(&pong.to_bytes()?[..]).await?;
+        }
+        Ok(())
+    }
+
     async fn send_msg(
         &self,
-        stream: &mut TcpStream,
+        stream: &mut Box<dyn ConnectionStream>,
         msg: &mut messages::message::Message,
         connection_id: u128,
     ) -> Result<()> {
@@ -124,3 +489,63 @@ impl AsyncQueryClient {
         Ok(())
     }
 }
+
+pub fn records_to_ipc(records: &[Arc<arrow::array::RecordBatch>]) -> Result<Vec<u8>> {
+    let schema = match records.first() {
+        Some(record) => record.schema(),
+        None => return Ok(Vec::new()),
+    };
+
+    let mut data_buf = Vec::new();
+    {
+        let mut record_writer = arrow::ipc::writer::StreamWriter::try_new(&mut data_buf, &schema)?;
+        for (file_idx, record) in records.iter().enumerate() {
+            if record.schema() != schema {
+                return Err(
+                    AsyncQueryClientError::QueryResultsSchemaMismatch(file_idx as u64).into(),
+                );
+            }
+            record_writer.write(record)?;
+        }
+        record_writer.finish()?;
+    }
+    Ok(data_buf)
+}
+
+//////////////////////////////////////////////////////
+// Query Results Subscription
+
+// Reads the records pushed by the worker as the query produces them.
+pub struct QueryResultsSubscription<'a> {
+    client: &'a AsyncQueryClient,
+    stream: Box<dyn ConnectionStream>,
+    buf: BytesMut,
+}
+
+impl QueryResultsSubscription<'_> {
+    // Returns None once the query has produced all of its records.
+    pub async fn next_record(&mut self) -> Result<Option<Arc<arrow::array::RecordBatch>>> {
+        match self.next_results().await? {
+            messages::query::QueryResults::Record { record } => Ok(Some(record)),
+            messages::query::QueryResults::End => Ok(None),
+            messages::query::QueryResults::Error(err) => {
+                Err(AsyncQueryClientError::QueryFailed(err).into())
+            }
+            results => Err(
+                AsyncQueryClientError::UnexpectedQueryResultsMessage(format!("{:?}", results))
+                    .into(),
+            ),
+        }
+    }
+
+    async fn next_results(&mut self) -> Result<messages::query::QueryResults> {
+        let msg = self
+            .client
+            .read_buffered_msg(&mut self.stream, &mut self.buf)
+            .await?;
+        match msg {
+            Some(msg) => Ok(self.client.msg_reg.try_cast_msg_owned(msg)?),
+            None => Err(AsyncQueryClientError::ExpectedMessageButReceivedNone.into()),
+        }
+    }
+}
@@ -1,5 +1,13 @@
 mod async_query_client;
 mod query_client;
+mod sql_file;
+#[cfg(test)]
+mod test_async_query_client;
+#[cfg(test)]
+mod test_query_client;
+#[cfg(test)]
+mod test_sql_file;
 
-pub use async_query_client::AsyncQueryClient;
+pub use async_query_client::{AsyncQueryClient, QueryResultsSubscription};
 pub use query_client::QueryClient;
+pub use sql_file::parse_sql_queries;
@@ -1,18 +1,50 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use bytes::BytesMut;
 use core::str;
 use std::{
     io::{Read, Write},
     net::TcpStream,
+    sync::Arc,
+    time::Duration,
 };
+use thiserror::Error;
 use tracing::info;
 use uuid::Uuid;
 
 use crate::handlers::message_handler::messages;
 use crate::handlers::message_handler::messages::message::{Message, SendableMessage};
+use crate::handlers::message_handler::{is_heartbeat, is_incomplete_msg_error, MessageRegistry};
+
+// how often the worker is asked whether the query has completed
+const QUERY_RESULTS_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Error)]
+pub enum QueryClientError {
+    #[error("buffer reach max size")]
+    BufferReachedMaxSize,
+    #[error("connection reset by peer")]
+    ConnectionResetByPeer,
+    #[error("expected message but received none")]
+    ExpectedMessageButReceivedNone,
+    #[error("connection rejected by the worker: {0}")]
+    ConnectionRejected(String),
+    #[error("query was not created")]
+    QueryNotCreated,
+    #[error("query was explained instead of run")]
+    QueryWasExplained,
+    #[error("query failed: {0}")]
+    QueryFailed(String),
+    #[error("query not found: {0}")]
+    QueryNotFound(u128),
+    #[error("query results not ready: {0}")]
+    QueryResultsNotReady(u128),
+}
 
 pub struct QueryClient {
     address: String,
     stream: TcpStream,
+    msg_reg: MessageRegistry,
+    auth_token: Option<String>,
 }
 
 impl QueryClient {
@@ -20,9 +52,16 @@ impl QueryClient {
         Ok(QueryClient {
             address: address.clone(),
             stream: TcpStream::connect(address)?,
+            msg_reg: MessageRegistry::new(),
+            auth_token: None,
         })
     }
 
+    pub fn set_auth_token(&mut self, token: String) -> &Self {
+        self.auth_token = Some(token);
+        self
+    }
+
     pub fn new_msg(&self, msg: Box<dyn SendableMessage>) -> Message {
         Message::new(msg)
             .set_sent_from_connection_id(Uuid::new_v4().as_u128())
@@ -50,4 +89,210 @@ impl QueryClient {
 
         Ok(())
     }
+
+    // Runs the query and blocks until it has completed. The results are
+    // only read once the query is complete so none of the files are
+    // missed. The client doesn't support tls.
+    pub fn run_query_blocking(&self, query: String) -> Result<QueryResultIterator<'_>> {
+        let run_query_resp: messages::query::RunQueryResp = self
+            .request(Box::new(messages::query::RunQuery::new(query)))
+            .context("failed to run the query")?;
+        let query_id = match run_query_resp {
+            messages::query::RunQueryResp::Created { query_id } => query_id,
+            messages::query::RunQueryResp::Explained { .. } => {
+                return Err(QueryClientError::QueryWasExplained.into());
+            }
+            messages::query::RunQueryResp::NotCreated => {
+                return Err(QueryClientError::QueryNotCreated.into());
+            }
+        };
+
+        self.wait_for_query_to_complete(query_id)?;
+        Ok(QueryResultIterator {
+            client: self,
+            query_id,
+            file_idx: 0,
+            finished: false,
+        })
+    }
+
+    fn wait_for_query_to_complete(&self, query_id: u128) -> Result<()> {
+        loop {
+            let status_resp: messages::query::GetQueryStatusResp =
+                self.request(Box::new(messages::query::GetQueryStatus::new(query_id)))?;
+            match status_resp {
+                messages::query::GetQueryStatusResp::Status(
+                    messages::query::QueryStatus::Complete,
+                ) => return Ok(()),
+                messages::query::GetQueryStatusResp::Status(
+                    messages::query::QueryStatus::Error(err),
+                ) => return Err(QueryClientError::QueryFailed(err).into()),
+                messages::query::GetQueryStatusResp::Status(
+                    messages::query::QueryStatus::Cancelled,
+                ) => {
+                    return Err(QueryClientError::QueryFailed("query cancelled".to_string()).into())
+                }
+                messages::query::GetQueryStatusResp::QueryNotFound => {
+                    return Err(QueryClientError::QueryNotFound(query_id).into())
+                }
+                messages::query::GetQueryStatusResp::Status(_) => (),
+            }
+            std::thread::sleep(QUERY_RESULTS_POLL_INTERVAL);
+        }
+    }
+
+    pub fn get_query_data(
+        &self,
+        query_id: u128,
+        file_idx: u64,
+    ) -> Result<messages::query::GetQueryDataResp> {
+        self.request(Box::new(messages::query::GetQueryData::new(
+            query_id, file_idx,
+        )))
+        .context("failed to get the query data")
+    }
+
+    // Each request uses its own connection.
+    fn request<T: SendableMessage>(&self, msg: Box<dyn SendableMessage>) -> Result<T> {
+        let (mut stream, connection_id) = self.create_connection().context("connection failed")?;
+        self.send_msg(&mut stream, &mut Message::new(msg), connection_id)?;
+        self.expect_msg(&mut stream)
+    }
+
+    fn create_connection(&self) -> Result<(TcpStream, u128)> {
+        let mut stream = TcpStream::connect(self.address.clone())?;
+        let connection_id = Uuid::new_v4().as_u128();
+
+        let identify = &mut Message::new(Box::new(messages::common::Identify::Connection {
+            id: connection_id,
+            token: self.auth_token.clone(),
+            max_header_version: messages::message::HEADER_VERSION,
+        }));
+        self.send_msg(&mut stream, identify, connection_id)?;
+
+        let identify_resp: messages::common::Identify = self
+            .expect_msg(&mut stream)
+            .context("failed to receive response identification from the worker")?;
+        match identify_resp {
+            messages::common::Identify::Rejected { reason } => {
+                Err(QueryClientError::ConnectionRejected(reason).into())
+            }
+            _ => Ok((stream, connection_id)),
+        }
+    }
+
+    fn expect_msg<T: SendableMessage>(&self, stream: &mut TcpStream) -> Result<T> {
+        match self.read_msg(stream)? {
+            Some(msg) => Ok(self.msg_reg.try_cast_msg_owned(msg)?),
+            None => Err(QueryClientError::ExpectedMessageButReceivedNone.into()),
+        }
+    }
+
+    fn read_msg(&self, stream: &mut TcpStream) -> Result<Option<Message>> {
+        let buf = &mut BytesMut::new();
+        let read_buf = &mut [0u8; 4096];
+        loop {
+            match self.msg_reg.build_msg(buf) {
+                Ok(Some(msg)) => {
+                    // workers ping idle connections
+                    if is_heartbeat(&msg) {
+                        self.reply_to_heartbeat(stream, &msg)?;
+                        continue;
+                    }
+                    return Ok(Some(msg));
+                }
+                // a fragment was buffered; the rest may already be read
+                Ok(None) => continue,
+                Err(err) if is_incomplete_msg_error(&err) => (),
+                Err(err) => return Err(err.context("failed decoding a message from the worker")),
+            }
+
+            // end the conneciton if the other system has sent too much data
+            if buf.len() > 2 * messages::message::SerializedMessage::max_frame_len() {
+                return Err(QueryClientError::BufferReachedMaxSize.into());
+            }
+
+            let size = stream.read(&mut read_buf[..])?;
+            if size == 0 {
+                if buf.is_empty() {
+                    return Ok(None);
+                } else {
+                    return Err(QueryClientError::ConnectionResetByPeer.into());
+                }
+            }
+            buf.extend_from_slice(&read_buf[..size]);
+        }
+    }
+
+    fn reply_to_heartbeat(&self, stream: &mut TcpStream, msg: &Message) -> Result<()> {
+        if let Ok(messages::common::Ping::Ping) = self.msg_reg.try_cast_msg(msg) {
+            let pong =
+                Message::new(Box::new(messages::common::Ping::Pong)).set_request_id(msg.request_id);
+            stream.write_all(&pong.to_bytes()?[..])?;
+        }
+        Ok(())
+    }
+
+    fn send_msg(
+        &self,
+        stream: &mut TcpStream,
+        msg: &mut Message,
+        connection_id: u128,
+    ) -> Result<()> {
+        msg.set_sent_from_connection_id(connection_id);
+        stream.write_all(&msg.to_bytes()?[..])?;
+        Ok(())
+    }
+}
+
+//////////////////////////////////////////////////////
+// Query Result Iterator
+
+// Reads the results of a completed query one file at a time. The
+// iteration ends when all of the files have been read or on the first
+// error.
+pub struct QueryResultIterator<'a> {
+    client: &'a QueryClient,
+    query_id: u128,
+    file_idx: u64,
+    finished: bool,
+}
+
+impl Iterator for QueryResultIterator<'_> {
+    type Item = Result<Arc<arrow::array::RecordBatch>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let resp = match self.client.get_query_data(self.query_id, self.file_idx) {
+            Ok(resp) => resp,
+            Err(err) => {
+                self.finished = true;
+                return Some(Err(err));
+            }
+        };
+
+        match resp {
+            messages::query::GetQueryDataResp::Record { record, .. } => {
+                self.file_idx += 1;
+                Some(Ok(record))
+            }
+            messages::query::GetQueryDataResp::ReachedEndOfFiles => {
+                self.finished = true;
+                None
+            }
+            messages::query::GetQueryDataResp::QueryResultsNotReady => {
+                self.finished = true;
+                Some(Err(
+                    QueryClientError::QueryResultsNotReady(self.query_id).into()
+                ))
+            }
+            messages::query::GetQueryDataResp::Error(err) => {
+                self.finished = true;
+                Some(Err(QueryClientError::QueryFailed(err).into()))
+            }
+        }
+    }
 }
@@ -0,0 +1,82 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SqlTokenState {
+    Code,
+    LineComment,
+    BlockComment,
+    Quoted(char),
+}
+
+// Splits the contents of a sql file into its statements. Semicolons in
+// comments and quoted strings don't end a statement, and statements
+// which only contain comments are skipped.
+pub fn parse_sql_queries(sql: &str) -> Vec<String> {
+    let mut queries = Vec::new();
+    let mut query = String::new();
+    let mut query_has_code = false;
+    let mut state = SqlTokenState::Code;
+
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        match state {
+            SqlTokenState::Code => match c {
+                ';' => {
+                    if query_has_code {
+                        queries.push(format!("{};", query.trim()));
+                    }
+                    query.clear();
+                    query_has_code = false;
+                    continue;
+                }
+                '-' if chars.peek() == Some(&'-') => {
+                    state = SqlTokenState::LineComment;
+                    continue;
+                }
+                '/' if chars.peek() == Some(&'*') => {
+                    query.push(c);
+                    query.push(chars.next().unwrap());
+                    state = SqlTokenState::BlockComment;
+                    continue;
+                }
+                '\'' | '"' => {
+                    state = SqlTokenState::Quoted(c);
+                    query_has_code = true;
+                }
+                _ if !c.is_whitespace() => query_has_code = true,
+                _ => (),
+            },
+            // line comments are dropped so the semicolon added to the
+            // last statement isn't commented out
+            SqlTokenState::LineComment => {
+                if c != '\n' {
+                    continue;
+                }
+                state = SqlTokenState::Code;
+            }
+            SqlTokenState::BlockComment => {
+                if c == '*' && chars.peek() == Some(&'/') {
+                    query.push(c);
+                    query.push(chars.next().unwrap());
+                    state = SqlTokenState::Code;
+                    continue;
+                }
+            }
+            // a doubled quote is an escaped quote and the string continues
+            SqlTokenState::Quoted(quote) => {
+                if c == quote {
+                    if chars.peek() == Some(&quote) {
+                        query.push(c);
+                        query.push(chars.next().unwrap());
+                        continue;
+                    }
+                    state = SqlTokenState::Code;
+                }
+            }
+        }
+        query.push(c);
+    }
+
+    if query_has_code {
+        queries.push(format!("{};", query.trim()));
+    }
+    queries
+}
@@ -0,0 +1,49 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::{Int64Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+
+use super::async_query_client::{records_to_ipc, AsyncQueryClientError};
+
+fn id_record(ids: Vec<i64>) -> Result<Arc<RecordBatch>> {
+    let schema = Schema::new(vec![Field::new("id", DataType::Int64, false)]);
+    Ok(Arc::new(RecordBatch::try_new(
+        Arc::new(schema),
+        vec![Arc::new(Int64Array::from(ids))],
+    )?))
+}
+
+#[test]
+fn test_records_are_encoded_as_a_single_ipc_stream() -> Result<()> {
+    let records = vec![id_record(vec![1, 2])?, id_record(vec![3])?];
+
+    let data = records_to_ipc(&records)?;
+    let reader = arrow::ipc::reader::StreamReader::try_new(std::io::Cursor::new(data), None)?;
+    let read_records = reader.collect::<Result<Vec<RecordBatch>, _>>()?;
+    assert_eq!(read_records.len(), 2);
+    assert_eq!(read_records[0], *records[0]);
+    assert_eq!(read_records[1], *records[1]);
+
+    assert!(records_to_ipc(&[])?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_records_with_different_schemas_are_rejected() -> Result<()> {
+    let schema = Schema::new(vec![Field::new("name", DataType::Utf8, false)]);
+    let name_record = Arc::new(RecordBatch::try_new(
+        Arc::new(schema),
+        vec![Arc::new(StringArray::from(vec!["a"]))],
+    )?);
+
+    let err = records_to_ipc(&[id_record(vec![1])?, name_record])
+        .expect_err("expected a schema mismatch");
+    assert!(matches!(
+        err.downcast_ref::<AsyncQueryClientError>(),
+        Some(AsyncQueryClientError::QueryResultsSchemaMismatch(1))
+    ));
+
+    Ok(())
+}
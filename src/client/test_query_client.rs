@@ -0,0 +1,191 @@
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use arrow::array::{ArrayRef, Int64Array, RecordBatch};
+use bytes::BytesMut;
+
+use crate::handlers::message_handler::messages;
+use crate::handlers::message_handler::messages::message::{
+    Message, MessageName, SendableMessage, HEADER_VERSION,
+};
+use crate::handlers::message_handler::MessageRegistry;
+
+use super::query_client::QueryClient;
+
+enum Reply {
+    Msg(Box<dyn SendableMessage>),
+    // the registered message id is overwritten so the reply can't be
+    // decoded
+    Undecodable(Box<dyn SendableMessage>),
+}
+
+// Answers each request of the client with the next reply and records the
+// name of each request. The client connects once when it's created and
+// then uses a new connection for each request.
+fn start_worker(replies: Vec<Reply>) -> Result<(String, Arc<Mutex<Vec<MessageName>>>)> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let address = listener.local_addr()?.to_string();
+    let replies = Arc::new(Mutex::new(VecDeque::from(replies)));
+    let requests = Arc::new(Mutex::new(Vec::new()));
+
+    let worker_requests = requests.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let (replies, requests) = (replies.clone(), worker_requests.clone());
+            std::thread::spawn(move || {
+                if let Ok(stream) = stream {
+                    let _ = answer_connection(stream, replies, requests);
+                }
+            });
+        }
+    });
+
+    Ok((address, requests))
+}
+
+fn answer_connection(
+    mut stream: TcpStream,
+    replies: Arc<Mutex<VecDeque<Reply>>>,
+    requests: Arc<Mutex<Vec<MessageName>>>,
+) -> Result<()> {
+    let msg_reg = MessageRegistry::new();
+    let buf = &mut BytesMut::new();
+
+    let identify = read_msg(&msg_reg, &mut stream, buf)?;
+    let identify_resp = identify.reply(Box::new(messages::common::Identify::Connection {
+        id: 0,
+        token: None,
+        max_header_version: HEADER_VERSION,
+    }));
+    stream.write_all(&identify_resp.to_bytes()?[..])?;
+
+    let request = read_msg(&msg_reg, &mut stream, buf)?;
+    requests.lock().unwrap().push(request.msg.msg_name());
+    let reply = replies
+        .lock()
+        .unwrap()
+        .pop_front()
+        .expect("expected a reply");
+    match reply {
+        Reply::Msg(msg) => {
+            stream.write_all(&request.reply(msg).to_bytes()?[..])?;
+        }
+        Reply::Undecodable(msg) => {
+            let mut data = request.reply(msg).to_bytes()?;
+            data[14..16].copy_from_slice(&u16::MAX.to_be_bytes());
+            stream.write_all(&data[..])?;
+        }
+    }
+
+    // hold the connection open until the client closes it
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(5)))?;
+    let _ = stream.read(&mut [0u8; 1]);
+    Ok(())
+}
+
+fn read_msg(
+    msg_reg: &MessageRegistry,
+    stream: &mut TcpStream,
+    buf: &mut BytesMut,
+) -> Result<Message> {
+    let read_buf = &mut [0u8; 4096];
+    loop {
+        if let Ok(Some(msg)) = msg_reg.build_msg(buf) {
+            return Ok(msg);
+        }
+        let size = stream.read(&mut read_buf[..])?;
+        if size == 0 {
+            return Err(anyhow::anyhow!("connection closed"));
+        }
+        buf.extend_from_slice(&read_buf[..size]);
+    }
+}
+
+fn id_record(ids: Vec<i64>) -> Result<RecordBatch> {
+    Ok(RecordBatch::try_from_iter(vec![(
+        "id",
+        Arc::new(Int64Array::from(ids)) as ArrayRef,
+    )])?)
+}
+
+#[test]
+fn test_results_are_only_read_once_the_query_is_complete() -> Result<()> {
+    let record = id_record(vec![1, 2, 3])?;
+    let (address, requests) = start_worker(vec![
+        Reply::Msg(Box::new(messages::query::RunQueryResp::Created {
+            query_id: 7,
+        })),
+        Reply::Msg(Box::new(messages::query::GetQueryStatusResp::Status(
+            messages::query::QueryStatus::Running,
+        ))),
+        Reply::Msg(Box::new(messages::query::GetQueryStatusResp::Status(
+            messages::query::QueryStatus::Complete,
+        ))),
+        Reply::Msg(Box::new(messages::query::GetQueryDataResp::Record {
+            file_idx: 0,
+            record: Arc::new(record.clone()),
+        })),
+        Reply::Msg(Box::new(
+            messages::query::GetQueryDataResp::ReachedEndOfFiles,
+        )),
+    ])?;
+
+    let client = QueryClient::new(address)?;
+    let records = client
+        .run_query_blocking("select * from data".to_string())?
+        .collect::<Result<Vec<Arc<RecordBatch>>>>()?;
+    assert_eq!(records, vec![Arc::new(record)]);
+
+    assert_eq!(
+        *requests.lock().unwrap(),
+        vec![
+            MessageName::RunQuery,
+            MessageName::GetQueryStatus,
+            MessageName::GetQueryStatus,
+            MessageName::GetQueryData,
+            MessageName::GetQueryData,
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_results_which_are_not_ready_are_an_error() -> Result<()> {
+    let (address, _) = start_worker(vec![
+        Reply::Msg(Box::new(messages::query::RunQueryResp::Created {
+            query_id: 7,
+        })),
+        Reply::Msg(Box::new(messages::query::GetQueryStatusResp::Status(
+            messages::query::QueryStatus::Complete,
+        ))),
+        Reply::Msg(Box::new(
+            messages::query::GetQueryDataResp::QueryResultsNotReady,
+        )),
+    ])?;
+
+    let client = QueryClient::new(address)?;
+    let mut results = client.run_query_blocking("select * from data".to_string())?;
+    assert!(matches!(results.next(), Some(Err(_))));
+    assert!(results.next().is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_messages_which_can_not_be_decoded_are_an_error() -> Result<()> {
+    let (address, _) = start_worker(vec![Reply::Undecodable(Box::new(
+        messages::query::GetQueryDataResp::ReachedEndOfFiles,
+    ))])?;
+
+    let client = QueryClient::new(address)?;
+    let err = client
+        .get_query_data(7, 0)
+        .expect_err("expected the reply to fail decoding");
+    assert!(format!("{:#}", err).contains("failed decoding a message from the worker"));
+
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use super::sql_file::parse_sql_queries;
+
+#[test]
+fn test_parse_sql_queries() {
+    struct TestCase {
+        case_name: String,
+        sql: String,
+        expected_queries: Vec<String>,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            case_name: "multiple statements".to_string(),
+            sql: "select 1;\nselect 2;\n\n".to_string(),
+            expected_queries: vec!["select 1;".to_string(), "select 2;".to_string()],
+        },
+        TestCase {
+            case_name: "last statement without a semicolon".to_string(),
+            sql: "select 1; select 2".to_string(),
+            expected_queries: vec!["select 1;".to_string(), "select 2;".to_string()],
+        },
+        TestCase {
+            case_name: "trailing line comment with a semicolon".to_string(),
+            sql: "select 1; -- trailing; comment".to_string(),
+            expected_queries: vec!["select 1;".to_string()],
+        },
+        TestCase {
+            case_name: "line comment inside a statement".to_string(),
+            sql: "select id -- the id; not the name\nfrom a;".to_string(),
+            expected_queries: vec!["select id \nfrom a;".to_string()],
+        },
+        TestCase {
+            case_name: "line comment after the last statement".to_string(),
+            sql: "select 1 -- no semicolon".to_string(),
+            expected_queries: vec!["select 1;".to_string()],
+        },
+        TestCase {
+            case_name: "block comment with a semicolon".to_string(),
+            sql: "select /* one; two */ 1; select 2;".to_string(),
+            expected_queries: vec![
+                "select /* one; two */ 1;".to_string(),
+                "select 2;".to_string(),
+            ],
+        },
+        TestCase {
+            case_name: "block comment only".to_string(),
+            sql: "/* select 1; */ select 2;".to_string(),
+            expected_queries: vec!["/* select 1; */ select 2;".to_string()],
+        },
+        TestCase {
+            case_name: "quoted strings with semicolons".to_string(),
+            sql: "select * from read_files('a;b.parquet') where name = 'it''s; here';".to_string(),
+            expected_queries: vec![
+                "select * from read_files('a;b.parquet') where name = 'it''s; here';".to_string(),
+            ],
+        },
+        TestCase {
+            case_name: "comment markers inside quotes".to_string(),
+            sql: "select '--', \"/*\"; select 2;".to_string(),
+            expected_queries: vec!["select '--', \"/*\";".to_string(), "select 2;".to_string()],
+        },
+    ];
+
+    for test_case in test_cases {
+        println!("test case: {}", test_case.case_name);
+        assert_eq!(
+            parse_sql_queries(&test_case.sql),
+            test_case.expected_queries
+        );
+    }
+}
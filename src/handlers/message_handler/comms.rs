@@ -1,8 +1,11 @@
 use std::collections::VecDeque;
+use std::time::Duration;
 
 use anyhow::Result;
+use rand::Rng;
 use thiserror::Error;
 use tokio::sync::mpsc;
+use tracing::debug;
 
 use super::messages::message::{Message, MessageName};
 
@@ -92,16 +95,45 @@ impl Pipe {
         self
     }
 
+    // The request is sent again after a backoff when it fails until the
+    // retry policy runs out of attempts. A closed channel isn't retried.
     pub async fn send_request(&mut self, req: Request) -> Result<Message> {
-        let request_id = req.msg.request_id.clone();
-        self.send(req.msg).await?;
-        let msg = self.recv_request(&request_id, req.timeout).await?;
+        let mut attempt: u32 = 0;
+        loop {
+            let err = match self.send_request_attempt(&req).await {
+                Ok(msg) => return Ok(msg),
+                Err(err) => err,
+            };
+            attempt += 1;
+            let channel_closed = matches!(
+                err.downcast_ref::<PipeError>(),
+                Some(PipeError::ChannelClosed)
+            );
+            if channel_closed || attempt >= req.retry_policy.max_attempts {
+                return Err(err);
+            }
+
+            let backoff = req.retry_policy.backoff(attempt - 1);
+            debug!(
+                request_id = req.msg.request_id,
+                attempt = attempt,
+                backoff_in_millis = backoff.as_millis() as u64,
+                "request failed; retrying after backoff: {}",
+                err
+            );
+            tokio::time::sleep(backoff).await;
+        }
+    }
+
+    async fn send_request_attempt(&mut self, req: &Request) -> Result<Message> {
+        self.send(req.msg.clone()).await?;
+        let msg = self.recv_request(&req.msg, req.timeout).await?;
         if let Some(msg) = msg {
             if msg.msg.msg_name() == req.expect_response_msg_name {
                 Ok(msg)
             } else {
                 Err(PipeError::RequestReceivedUnexpectedMessageName(
-                    req.expect_response_msg_name,
+                    req.expect_response_msg_name.clone(),
                     msg.msg.msg_name(),
                 )
                 .into())
@@ -145,9 +177,19 @@ impl Pipe {
         self.receiver.recv().await
     }
 
+    // Replies are matched to the request by the msg_id they reply to.
+    // Messages without an in reply to id, from peers which don't set it,
+    // are matched by the request id.
+    fn is_reply_to(msg: &Message, req_msg: &Message) -> bool {
+        match msg.in_reply_to {
+            Some(in_reply_to) => in_reply_to == req_msg.msg_id,
+            None => msg.request_id == req_msg.request_id,
+        }
+    }
+
     pub async fn recv_request(
         &mut self,
-        request_id: &u128,
+        req_msg: &Message,
         max_wait: chrono::Duration,
     ) -> Result<Option<Message>> {
         if self.msg_queue.len() > 0 {
@@ -155,7 +197,7 @@ impl Pipe {
                 .msg_queue
                 .iter()
                 .enumerate()
-                .find(|(_, msg)| msg.request_id == *request_id)
+                .find(|(_, msg)| Self::is_reply_to(msg, req_msg))
                 .map(|(idx, _)| idx);
             if let Some(next_msg_idx) = next_msg_idx {
                 let msg = self.msg_queue.remove(next_msg_idx);
@@ -169,7 +211,7 @@ impl Pipe {
             loop {
                 match self.receiver.recv().await {
                     Some(msg) => {
-                        if msg.request_id == *request_id {
+                        if Self::is_reply_to(&msg, req_msg) {
                             return Ok(Some(msg));
                         } else if self.msg_queue.len() < self.max_msg_queue_length {
                             self.msg_queue.push_back(msg);
@@ -188,11 +230,17 @@ impl Pipe {
         })
         .await
         .unwrap_or(Err(PipeError::TimedOutWaitingForMessageWithRequestId(
-            request_id.clone(),
+            req_msg.request_id,
         )
         .into()))
     }
 
+    // Messages sent with the cloned sender don't have the sent from ids
+    // of the pipe set on them.
+    pub fn sender(&self) -> mpsc::Sender<Message> {
+        self.sender.clone()
+    }
+
     pub fn close_receiver(&mut self) {
         self.receiver.close();
     }
@@ -205,5 +253,48 @@ impl Pipe {
 pub struct Request {
     pub msg: Message,
     pub expect_response_msg_name: MessageName,
+    // time to wait for the response of each attempt
     pub timeout: chrono::Duration,
+    pub retry_policy: RetryPolicy,
+}
+
+// The backoff doubles after each failed attempt up to the max backoff
+// and is randomly scaled by up to the jitter fraction in either direction
+// so requests which failed together don't retry together.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_backoff: Duration,
+    pub max_backoff: Duration,
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 1,
+            base_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            jitter: 0.2,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn with_max_attempts(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            ..RetryPolicy::default()
+        }
+    }
+
+    // Backoff after the failed attempt; the first attempt is 0.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let backoff = self
+            .base_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        backoff.mul_f64(rand::thread_rng().gen_range((1.0 - jitter)..=(1.0 + jitter)))
+    }
 }
@@ -1,19 +1,25 @@
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Result;
 use bytes::BytesMut;
 use thiserror::Error;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::net::TcpStream;
 use tokio::sync::mpsc;
+use tokio::time::Instant;
 use tokio_util::sync::CancellationToken;
-use tracing::info;
+use tracing::{info, warn};
 use uuid::Uuid;
 
 use crate::handlers::message_handler::messages;
-use crate::handlers::message_handler::messages::message::{Message, SerializedMessageError};
+use crate::handlers::message_handler::messages::message::{
+    Message, MessageName, SerializedMessage, SerializedMessageError,
+};
 
+use super::connection_stats::ConnectionStats;
 use super::message_registry::MessageRegistry;
+use super::tls::Tls;
 use super::Pipe;
 
 #[derive(Debug, Error)]
@@ -24,6 +30,48 @@ pub enum ConnectionError {
     BufferReachedMaxSize,
     #[error("timed out waiting for connections to close")]
     TimedOutWaitingForConnectionsToClose,
+    #[error("connection stream was already used")]
+    StreamAlreadyUsed,
+    #[error("timed out waiting for heartbeat pong")]
+    HeartbeatTimedOut,
+}
+
+// A ping is sent after the connection has received nothing for the
+// interval and the connection is closed if nothing is received within the
+// timeout of the ping.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Heartbeat {
+    pub interval: Duration,
+    pub timeout: Duration,
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Heartbeat {
+            interval: Duration::from_secs(15),
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+// Heartbeats are pings without any routing; they are answered by the
+// connection and never passed to the router.
+pub fn is_heartbeat(msg: &Message) -> bool {
+    msg.msg.msg_name() == MessageName::Ping
+        && msg.route_to_worker_id.is_none()
+        && msg.route_to_operation_id.is_none()
+        && msg.route_to_connection_id.is_none()
+}
+
+// A plaintext or tls stream.
+pub trait ConnectionStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> ConnectionStream for T {}
+
+enum ConnectionTls {
+    Accept(Arc<Tls>),
+    // the address the connection was made to
+    Connect(Arc<Tls>, String),
 }
 
 pub struct ConnectionComm {
@@ -49,13 +97,17 @@ impl ConnectionComm {
 pub struct Connection {
     pub worker_id: u128,
     pub stream_id: u128,
-    stream: TcpStream,
+    stream: Option<TcpStream>,
+    tls: Option<ConnectionTls>,
     pipe: Pipe,
     msg_reg: Arc<MessageRegistry>,
     buf: BytesMut,
     pub connection_ct: CancellationToken,
     send_identification_msg: bool,
+    auth_token: Option<String>,
     is_inbound: bool,
+    heartbeat: Option<Heartbeat>,
+    stats: Arc<ConnectionStats>,
 }
 
 impl Connection {
@@ -70,13 +122,17 @@ impl Connection {
         let conn = Connection {
             worker_id,
             stream_id: Uuid::new_v4().as_u128(),
-            stream,
+            stream: Some(stream),
+            tls: None,
             pipe,
             msg_reg,
             buf: BytesMut::with_capacity(4096),
             connection_ct: CancellationToken::new(),
             send_identification_msg: false,
+            auth_token: None,
             is_inbound,
+            heartbeat: None,
+            stats: Arc::new(ConnectionStats::new()),
         };
         let comm = ConnectionComm::new(conn.connection_ct.clone(), conn.stream_id, sender_to_conn);
         (conn, comm)
@@ -87,25 +143,99 @@ impl Connection {
         self
     }
 
+    // Sent in the identification message when the worker connects to
+    // another worker which requires the token.
+    pub fn set_auth_token(&mut self, token: Option<String>) -> &Self {
+        self.auth_token = token;
+        self
+    }
+
+    pub fn set_heartbeat(&mut self, heartbeat: Heartbeat) -> &Self {
+        self.heartbeat = Some(heartbeat);
+        self
+    }
+
+    pub fn set_stats(&mut self, stats: Arc<ConnectionStats>) -> &Self {
+        self.stats = stats;
+        self
+    }
+
+    // Run a tls handshake as the server before any messages are read.
+    pub fn set_tls_accept(&mut self, tls: Arc<Tls>) -> &Self {
+        self.tls = Some(ConnectionTls::Accept(tls));
+        self
+    }
+
+    // Run a tls handshake as the client of the address before any
+    // messages are sent.
+    pub fn set_tls_connect(&mut self, tls: Arc<Tls>, address: String) -> &Self {
+        self.tls = Some(ConnectionTls::Connect(tls, address));
+        self
+    }
+
     pub async fn async_main(&mut self, ct: CancellationToken) -> Result<()> {
+        let tcp_stream = match self.stream.take() {
+            Some(tcp_stream) => tcp_stream,
+            None => return Err(ConnectionError::StreamAlreadyUsed.into()),
+        };
         info!(
             stream_id = self.stream_id,
-            ip = self.stream.peer_addr()?.to_string(),
+            ip = tcp_stream.peer_addr()?.to_string(),
             "new connection",
         );
+        let _active_connection = self.stats.track_connection();
+
+        let stream_res = match &self.tls {
+            Some(ConnectionTls::Accept(tls)) => tls.accept(tcp_stream).await,
+            Some(ConnectionTls::Connect(tls, address)) => tls.connect(address, tcp_stream).await,
+            None => Ok(Box::new(tcp_stream) as Box<dyn ConnectionStream>),
+        };
+        let mut stream = match stream_res {
+            Ok(stream) => stream,
+            Err(err) => {
+                self.pipe.close_receiver();
+                return Err(err);
+            }
+        };
 
         if self.send_identification_msg {
             let identity_msg = Message::new(Box::new(messages::common::Identify::Worker {
                 id: self.worker_id.clone(),
+                token: self.auth_token.clone(),
+                max_header_version: messages::message::HEADER_VERSION,
             }))
             .set_sent_from_worker_id(self.worker_id.clone());
-            self.stream.write_all(&identity_msg.to_bytes()?[..]).await?;
+            stream.write_all(&identity_msg.to_bytes()?[..]).await?;
         }
 
+        // reads and writes use separate halves of the stream so a slow
+        // write doesn't stop the connection from reading
+        let (mut reader, mut writer) = tokio::io::split(stream);
+        let mut write_buf = BytesMut::new();
+        let mut close_after_write = false;
+        let mut last_msg_at = Instant::now();
+        let mut ping_sent_at: Option<Instant> = None;
+
         loop {
             match self.msg_reg.build_msg(&mut self.buf) {
                 Ok(msg) => {
                     if let Some(mut msg) = msg {
+                        self.stats.record_msg_parsed();
+                        // any message shows the peer is alive
+                        last_msg_at = Instant::now();
+                        ping_sent_at = None;
+                        if is_heartbeat(&msg) {
+                            if let Ok(messages::common::Ping::Ping) =
+                                self.msg_reg.try_cast_msg::<messages::common::Ping>(&msg)
+                            {
+                                let pong = Message::new(Box::new(messages::common::Ping::Pong))
+                                    .set_request_id(msg.request_id)
+                                    .set_sent_from_worker_id(self.worker_id);
+                                write_buf.extend_from_slice(&pong.to_bytes()?[..]);
+                            }
+                            continue;
+                        }
+
                         if self.is_inbound {
                             msg = msg.set_inbound_stream_id(self.stream_id);
                         } else {
@@ -120,22 +250,32 @@ impl Connection {
                     match ser_msg_err {
                         Some(SerializedMessageError::Incomplete) => (),
                         _ => {
+                            self.stats.record_parse_error();
                             info!("error: {}", err);
                         }
                     }
                 }
             }
 
-            // end the conneciton if the other system has sent too much data
-            if self.buf.len() > 1024 * 1024 * 10 {
+            // end the conneciton if the other system has sent too much data;
+            // large messages are sent as fragments so the buffer only needs
+            // to hold a single frame
+            if self.buf.len() > 2 * SerializedMessage::max_frame_len() {
                 self.pipe.close_receiver();
                 return Err(ConnectionError::BufferReachedMaxSize.into());
             }
 
+            let heartbeat_deadline = match (&self.heartbeat, ping_sent_at) {
+                (Some(heartbeat), Some(ping_sent_at)) => ping_sent_at + heartbeat.timeout,
+                (Some(heartbeat), None) => last_msg_at + heartbeat.interval,
+                (None, _) => last_msg_at,
+            };
+
             tokio::select! {
-                read_res = self.stream.read_buf(&mut self.buf) => {
+                read_res = reader.read_buf(&mut self.buf) => {
                     match read_res {
                         Ok(size) => {
+                            self.stats.record_bytes_read(size);
                             if size == 0 {
                                 self.pipe.close_receiver();
                                 if self.buf.is_empty() {
@@ -151,9 +291,33 @@ impl Connection {
                         },
                     }
                 },
-                Some(msg) = self.pipe.recv() => {
-                    let msg_bytes = msg.to_bytes()?;
-                    self.stream.write_all(&msg_bytes[..]).await?;
+                write_res = writer.write_buf(&mut write_buf), if !write_buf.is_empty() => {
+                    if let Err(err) = write_res {
+                        self.pipe.close_receiver();
+                        return Err(err.into());
+                    }
+                    if write_buf.is_empty() && close_after_write {
+                        info!(stream_id = self.stream_id, "connection rejected");
+                        break;
+                    }
+                },
+                // the next message is only taken once the previous message
+                // has been written so a slow socket applies backpressure to
+                // the senders
+                Some(msg) = self.pipe.recv(), if write_buf.is_empty() && !close_after_write => {
+                    write_buf.extend_from_slice(&msg.to_bytes()?[..]);
+                    close_after_write = Self::is_rejection(&self.msg_reg, &msg);
+                },
+                _ = tokio::time::sleep_until(heartbeat_deadline), if self.heartbeat.is_some() => {
+                    if ping_sent_at.is_some() {
+                        warn!(stream_id = self.stream_id, "closing connection after missed heartbeat");
+                        self.pipe.close_receiver();
+                        return Err(ConnectionError::HeartbeatTimedOut.into());
+                    }
+                    let ping = Message::new(Box::new(messages::common::Ping::Ping))
+                        .set_sent_from_worker_id(self.worker_id);
+                    write_buf.extend_from_slice(&ping.to_bytes()?[..]);
+                    ping_sent_at = Some(Instant::now());
                 },
                 _ = self.connection_ct.cancelled() => {
                     break;
@@ -166,8 +330,9 @@ impl Connection {
 
         info!("closing connection...");
         self.pipe.close_receiver();
+        let mut stream = reader.unsplit(writer);
         tokio::select! {
-            res = self.stream.shutdown() => {
+            res = stream.shutdown() => {
                 if let Err(err) = res {
                     return Err(err.into());
                 }
@@ -180,6 +345,14 @@ impl Connection {
         Ok(())
     }
 
+    fn is_rejection(msg_reg: &MessageRegistry, msg: &Message) -> bool {
+        if msg.msg.msg_name() != MessageName::Identify {
+            return false;
+        }
+        let identify: &messages::common::Identify = msg_reg.cast_msg(msg);
+        matches!(identify, messages::common::Identify::Rejected { .. })
+    }
+
     pub fn cleanup(&self) {
         self.connection_ct.cancel();
     }
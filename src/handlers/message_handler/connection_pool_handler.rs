@@ -1,4 +1,6 @@
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Result;
 use thiserror::Error;
@@ -7,11 +9,14 @@ use tokio::sync::mpsc::Sender;
 use tokio::sync::{mpsc, Mutex};
 use tokio_util::sync::CancellationToken;
 use tokio_util::task::TaskTracker;
-use tracing::info;
+use tracing::{info, warn};
 
-use super::connection::{Connection, ConnectionComm};
+use super::connection::{Connection, ConnectionComm, Heartbeat};
+use super::connection_stats::ConnectionStats;
 use super::message_registry::MessageRegistry;
-use super::messages::message::Message;
+use super::messages;
+use super::messages::message::{Message, MessageName};
+use super::tls::Tls;
 use super::Pipe;
 
 #[derive(Error, Debug)]
@@ -20,6 +25,28 @@ pub enum ConnectionPoolError {
     TimedOutWaitingForConnectionsToClose,
     #[error("timed out waiting for connection to worker {0}")]
     TimedOutWaitingForNewConnectionToWorker(String),
+    #[error("no connection to worker: {0}")]
+    NoConnectionToWorker(u128),
+    #[error("failed to send message to {0} after {1} attempts")]
+    SendFailedAfterRetries(String, u32),
+}
+
+enum OutboundEvent {
+    Connected { address: String, stream: TcpStream },
+    ConnectFailed { address: String },
+    Closed { stream_id: u128 },
+}
+
+struct OutboundConnection {
+    address: String,
+    comm: ConnectionComm,
+}
+
+// A message waiting for a new connection to the address.
+struct PendingMessage {
+    address: String,
+    attempts: u32,
+    msg: Message,
 }
 
 pub struct ConnectionPoolHandler {
@@ -31,7 +58,25 @@ pub struct ConnectionPoolHandler {
     pipe: Pipe,
 
     inbound_connections: Arc<Mutex<Vec<ConnectionComm>>>,
-    outbound_connections: Arc<Mutex<Vec<ConnectionComm>>>,
+    outbound_connections: Arc<Mutex<Vec<OutboundConnection>>>,
+
+    // outbound streams and workers are mapped to their address so messages
+    // can be sent to the new connection after a reconnect
+    stream_addresses: HashMap<u128, String>,
+    worker_addresses: HashMap<u128, String>,
+    pending_msgs: Vec<PendingMessage>,
+
+    max_connect_attempts: u32,
+    connect_backoff: Duration,
+    max_connect_backoff: Duration,
+    max_send_retries: u32,
+
+    // worker connections are plaintext unless tls is set
+    tls: Option<Arc<Tls>>,
+    heartbeat: Option<Heartbeat>,
+    stats: Arc<ConnectionStats>,
+    // token sent to the workers this worker connects to
+    auth_token: Option<String>,
 }
 
 impl ConnectionPoolHandler {
@@ -50,10 +95,62 @@ impl ConnectionPoolHandler {
             pipe: p1,
             inbound_connections: Arc::new(Mutex::new(Vec::new())),
             outbound_connections: Arc::new(Mutex::new(Vec::new())),
+            stream_addresses: HashMap::new(),
+            worker_addresses: HashMap::new(),
+            pending_msgs: Vec::new(),
+            max_connect_attempts: 12 * 5,
+            connect_backoff: Duration::from_secs(1),
+            max_connect_backoff: Duration::from_secs(30),
+            max_send_retries: 3,
+            tls: None,
+            heartbeat: None,
+            stats: Arc::new(ConnectionStats::new()),
+            auth_token: None,
         };
         (hndlr, p2)
     }
 
+    // The backoff doubles after each failed attempt up to max_backoff.
+    pub fn set_connect_backoff(
+        &mut self,
+        max_attempts: u32,
+        backoff: Duration,
+        max_backoff: Duration,
+    ) -> &Self {
+        self.max_connect_attempts = max_attempts;
+        self.connect_backoff = backoff;
+        self.max_connect_backoff = max_backoff;
+        self
+    }
+
+    pub fn set_max_send_retries(&mut self, max_send_retries: u32) -> &Self {
+        self.max_send_retries = max_send_retries;
+        self
+    }
+
+    pub fn set_tls(&mut self, tls: Tls) -> &Self {
+        self.tls = Some(Arc::new(tls));
+        self
+    }
+
+    pub fn set_auth_token(&mut self, token: Option<String>) -> &Self {
+        self.auth_token = token;
+        self
+    }
+
+    // Idle connections are pinged and closed when the peer stops
+    // responding.
+    pub fn set_heartbeat(&mut self, heartbeat: Heartbeat) -> &Self {
+        self.heartbeat = Some(heartbeat);
+        self
+    }
+
+    // The stats are shared with every connection of the pool so they can
+    // be read while the pool is running.
+    pub fn stats(&self) -> Arc<ConnectionStats> {
+        self.stats.clone()
+    }
+
     pub async fn async_main(&mut self, ct: CancellationToken) -> Result<()> {
         info!("Starting Messenger...");
 
@@ -61,24 +158,14 @@ impl ConnectionPoolHandler {
         let listener = TcpListener::bind(&self.address).await?;
 
         let (connection_tx, mut connection_rx) = mpsc::channel::<Message>(1);
-        let (stream_connect_tx, mut stream_connect_rx) = mpsc::channel::<TcpStream>(1);
+        let (outbound_event_tx, mut outbound_event_rx) = mpsc::channel::<OutboundEvent>(1);
         info!("Messenger listening on {}", self.address);
 
         info!("Attempting to connect to addresses");
-        for cta in &self.connect_to_addresses {
-            let ct = ct.clone();
-            let stream_connect_tx = stream_connect_tx.clone();
-            let cta = cta.clone();
-            tt.spawn(async move {
-                if let Err(err) =
-                    Self::connect_to_address(ct, stream_connect_tx, cta, 12 * 5, 1).await
-                {
-                    info!("error: {}", err);
-                }
-            });
+        for cta in self.connect_to_addresses.clone() {
+            self.spawn_connect_to_address(&tt, ct.clone(), outbound_event_tx.clone(), cta, false);
         }
 
-        // TODO: handle connections that close; need reconnect
         loop {
             tokio::select! {
                 // connection handling
@@ -87,14 +174,24 @@ impl ConnectionPoolHandler {
                         Ok((socket, _)) => {
                             let (mut connection, connection_comm) =
                                 Connection::new(self.worker_id.clone(), socket, connection_tx.clone(), Arc::clone(&self.msg_reg), true);
+                            if let Some(tls) = &self.tls {
+                                connection.set_tls_accept(tls.clone());
+                            }
+                            if let Some(heartbeat) = self.heartbeat {
+                                connection.set_heartbeat(heartbeat);
+                            }
+                            connection.set_stats(self.stats.clone());
+                            let stream_id = connection_comm.stream_id;
                             self.inbound_connections.lock().await.push(connection_comm);
 
                             // Spawn a new task to handle the connection
                             let ct2 = ct.clone();
+                            let inbound_connections = self.inbound_connections.clone();
                             tt.spawn(async move {
                                 if let Err(err) = connection.async_main(ct2).await {
                                     info!("error reading from tcp socket: {}", err);
                                 }
+                                inbound_connections.lock().await.retain(|comm| comm.stream_id != stream_id);
                             });
                         },
                         Err(err) => {
@@ -102,22 +199,32 @@ impl ConnectionPoolHandler {
                         }
                     }
                 }
-                Some(new_tcpstream_connection) = stream_connect_rx.recv() => {
-                    let (mut connection, connection_comm) = Connection::new(self.worker_id, new_tcpstream_connection, connection_tx.clone(), Arc::clone(&self.msg_reg), false);
-                    connection.set_send_identification();
-                    self.outbound_connections.lock().await.push(connection_comm);
-
-                    // Spawn a new task to handle the connection
-                    let ct2 = ct.clone();
-                    tt.spawn(async move {
-                        if let Err(err) = connection.async_main(ct2).await {
-                            info!("error reading from tcp socket: {}", err);
+                Some(event) = outbound_event_rx.recv() => {
+                    match event {
+                        OutboundEvent::Connected { address, stream } => {
+                            self.add_outbound_connection(&tt, ct.clone(), outbound_event_tx.clone(), connection_tx.clone(), address, stream).await?;
                         }
-                        connection.cleanup();
-                    });
+                        OutboundEvent::ConnectFailed { address } => {
+                            self.fail_pending_msgs(&address).await?;
+                        }
+                        OutboundEvent::Closed { stream_id } => {
+                            let address = {
+                                let mut outbound_connections = self.outbound_connections.lock().await;
+                                let idx = outbound_connections.iter().position(|conn| conn.comm.stream_id == stream_id);
+                                idx.map(|idx| outbound_connections.remove(idx).address)
+                            };
+                            if let Some(address) = address {
+                                if !ct.is_cancelled() {
+                                    warn!(address, "outbound connection closed; reconnecting");
+                                    self.spawn_connect_to_address(&tt, ct.clone(), outbound_event_tx.clone(), address, true);
+                                }
+                            }
+                        }
+                    }
                 }
                 // message routing
                 Some(msg) = connection_rx.recv() => {
+                    self.learn_worker_address(&msg);
                     if let Err(err) = self.pipe.send(msg).await {
                         info!("error: {}", err);
                         info!("error on receive");
@@ -136,15 +243,12 @@ impl ConnectionPoolHandler {
                             };
                         }
                     } else if let Some(outbound_stream_id) = msg.outbound_stream_id {
-                        for comm in self.outbound_connections.lock().await.iter() {
-                            if comm.stream_id != outbound_stream_id {
-                                continue;
-                            }
-                            if let Err(err) = comm.sender.send(msg.clone()).await {
-                                // if there is an error close the connection
-                                info!("error: {}", err);
-                                comm.connection_ct.cancel();
-                            };
+                        if let Some(address) = self.stream_addresses.get(&outbound_stream_id).cloned() {
+                            self.send_to_address(address, msg, 0).await?;
+                        } else if let Some(worker_id) = msg.route_to_worker_id {
+                            self.send(worker_id, msg).await?;
+                        } else {
+                            info!("unknown outbound stream id: {}", outbound_stream_id);
                         }
                     } else {
                         info!("inbound or outbound stream id was not set");
@@ -172,16 +276,240 @@ impl ConnectionPoolHandler {
         Ok(())
     }
 
+    // Send a message to a worker over its outbound connection. Messages sent
+    // while the worker is reconnecting are held until the new connection is
+    // established.
+    pub async fn send(&mut self, worker_id: u128, msg: Message) -> Result<()> {
+        match self.worker_addresses.get(&worker_id).cloned() {
+            Some(address) => self.send_to_address(address, msg, 0).await,
+            None => Err(ConnectionPoolError::NoConnectionToWorker(worker_id).into()),
+        }
+    }
+
+    async fn send_to_address(
+        &mut self,
+        address: String,
+        msg: Message,
+        attempts: u32,
+    ) -> Result<()> {
+        if attempts > self.max_send_retries {
+            return self.reply_with_send_error(&address, attempts, &msg).await;
+        }
+
+        let comm = self
+            .outbound_connections
+            .lock()
+            .await
+            .iter()
+            .find(|conn| conn.address == address)
+            .map(|conn| {
+                (
+                    conn.comm.stream_id,
+                    conn.comm.sender.clone(),
+                    conn.comm.connection_ct.clone(),
+                )
+            });
+
+        match comm {
+            Some((stream_id, sender, connection_ct)) => {
+                let msg = msg.set_outbound_stream(stream_id);
+                if let Err(err) = sender.send(msg).await {
+                    // the connection closed so the message waits for the
+                    // reconnect
+                    info!("error: {}", err);
+                    connection_ct.cancel();
+                    self.pending_msgs.push(PendingMessage {
+                        address,
+                        attempts: attempts + 1,
+                        msg: err.0,
+                    });
+                }
+            }
+            None => {
+                self.pending_msgs.push(PendingMessage {
+                    address,
+                    attempts,
+                    msg,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    async fn add_outbound_connection(
+        &mut self,
+        tt: &TaskTracker,
+        ct: CancellationToken,
+        outbound_event_tx: Sender<OutboundEvent>,
+        connection_tx: Sender<Message>,
+        address: String,
+        stream: TcpStream,
+    ) -> Result<()> {
+        let (mut connection, connection_comm) = Connection::new(
+            self.worker_id,
+            stream,
+            connection_tx,
+            Arc::clone(&self.msg_reg),
+            false,
+        );
+        connection.set_send_identification();
+        connection.set_auth_token(self.auth_token.clone());
+        if let Some(tls) = &self.tls {
+            connection.set_tls_connect(tls.clone(), address.clone());
+        }
+        if let Some(heartbeat) = self.heartbeat {
+            connection.set_heartbeat(heartbeat);
+        }
+        connection.set_stats(self.stats.clone());
+        let stream_id = connection_comm.stream_id;
+        self.stream_addresses.insert(stream_id, address.clone());
+        self.outbound_connections
+            .lock()
+            .await
+            .push(OutboundConnection {
+                address: address.clone(),
+                comm: connection_comm,
+            });
+
+        // Spawn a new task to handle the connection
+        tt.spawn(async move {
+            if let Err(err) = connection.async_main(ct.clone()).await {
+                info!("error reading from tcp socket: {}", err);
+            }
+            connection.cleanup();
+            tokio::select! {
+                res = outbound_event_tx.send(OutboundEvent::Closed { stream_id }) => {
+                    if let Err(err) = res {
+                        info!("error: {}", err);
+                    }
+                }
+                _ = ct.cancelled() => {}
+            }
+        });
+
+        // retry the messages which were waiting for this address
+        let (pending_msgs, other_msgs): (Vec<PendingMessage>, Vec<PendingMessage>) =
+            std::mem::take(&mut self.pending_msgs)
+                .into_iter()
+                .partition(|pending_msg| pending_msg.address == address);
+        self.pending_msgs = other_msgs;
+        for pending_msg in pending_msgs {
+            self.send_to_address(pending_msg.address, pending_msg.msg, pending_msg.attempts)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn fail_pending_msgs(&mut self, address: &str) -> Result<()> {
+        let (pending_msgs, other_msgs): (Vec<PendingMessage>, Vec<PendingMessage>) =
+            std::mem::take(&mut self.pending_msgs)
+                .into_iter()
+                .partition(|pending_msg| pending_msg.address == *address);
+        self.pending_msgs = other_msgs;
+        for pending_msg in pending_msgs {
+            self.reply_with_send_error(address, pending_msg.attempts, &pending_msg.msg)
+                .await?;
+        }
+        Ok(())
+    }
+
+    // The sender is told the message could not be delivered with a
+    // generic error response to the request.
+    async fn reply_with_send_error(
+        &mut self,
+        address: &str,
+        attempts: u32,
+        msg: &Message,
+    ) -> Result<()> {
+        let err = ConnectionPoolError::SendFailedAfterRetries(address.to_string(), attempts);
+        warn!("{}", err);
+        let mut resp = msg.reply(Box::new(messages::common::GenericResponse::Err {
+            code: messages::common::ErrorCode::Unavailable,
+            message: err.to_string(),
+        }));
+        resp.outbound_stream_id = None;
+        self.pipe.send(resp).await?;
+        Ok(())
+    }
+
+    // Workers identify themselves on new outbound connections.
+    fn learn_worker_address(&mut self, msg: &Message) {
+        let outbound_stream_id = match msg.outbound_stream_id {
+            Some(outbound_stream_id) => outbound_stream_id,
+            None => return,
+        };
+        if msg.msg.msg_name() != MessageName::Identify {
+            return;
+        }
+        let identify: &messages::common::Identify = self.msg_reg.cast_msg(msg);
+        if let messages::common::Identify::Worker { id, .. } = identify {
+            if let Some(address) = self.stream_addresses.get(&outbound_stream_id) {
+                self.worker_addresses.insert(*id, address.clone());
+            }
+        }
+    }
+
+    fn spawn_connect_to_address(
+        &self,
+        tt: &TaskTracker,
+        ct: CancellationToken,
+        sender: Sender<OutboundEvent>,
+        address: String,
+        is_reconnect: bool,
+    ) {
+        let max_attempts = self.max_connect_attempts;
+        let backoff = self.connect_backoff;
+        let max_backoff = self.max_connect_backoff;
+        tt.spawn(async move {
+            let res = Self::connect_to_address(
+                ct.clone(),
+                sender.clone(),
+                address.clone(),
+                max_attempts,
+                backoff,
+                max_backoff,
+                is_reconnect,
+            )
+            .await;
+            if let Err(err) = res {
+                info!("error: {}", err);
+                tokio::select! {
+                    res = sender.send(OutboundEvent::ConnectFailed { address }) => {
+                        if let Err(err) = res {
+                            info!("error: {}", err);
+                        }
+                    }
+                    _ = ct.cancelled() => {}
+                }
+            }
+        });
+    }
+
     async fn connect_to_address(
         ct: CancellationToken,
-        sender: Sender<TcpStream>,
+        sender: Sender<OutboundEvent>,
         address: String,
-        max_retries: u32,
-        sleep_time: u64,
+        max_attempts: u32,
+        backoff: Duration,
+        max_backoff: Duration,
+        is_reconnect: bool,
     ) -> Result<()> {
         let mut try_count = 0u32;
+        let mut backoff = backoff;
+
+        // a reconnect waits before the first attempt so a worker which is
+        // restarting isn't immediately retried
+        if is_reconnect {
+            tokio::select! {
+                _ = tokio::time::sleep(backoff) => {},
+                _ = ct.cancelled() => {
+                    return Ok(());
+                }
+            }
+        }
+
         loop {
-            if try_count > max_retries {
+            if try_count > max_attempts {
                 return Err(
                     ConnectionPoolError::TimedOutWaitingForNewConnectionToWorker(address.clone())
                         .into(),
@@ -193,12 +521,18 @@ impl ConnectionPoolHandler {
                 stream_resp = TcpStream::connect(address.clone()) => {
                     match stream_resp {
                         Ok(stream) => {
-                            sender.send(stream).await?;
+                            sender.send(OutboundEvent::Connected { address, stream }).await?;
                             break;
                         }
                         Err(err) => {
                             info!("error: {}", err);
-                            tokio::time::sleep(std::time::Duration::from_secs(sleep_time)).await;
+                            tokio::select! {
+                                _ = tokio::time::sleep(backoff) => {},
+                                _ = ct.cancelled() => {
+                                    break;
+                                }
+                            }
+                            backoff = std::cmp::min(backoff * 2, max_backoff);
                         }
                     }
                 },
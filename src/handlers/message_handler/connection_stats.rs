@@ -0,0 +1,67 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectionPoolStats {
+    pub active_connections: u64,
+    pub bytes_read: u64,
+    pub messages_parsed: u64,
+    pub parse_errors: u64,
+}
+
+// Counters shared by the pool and each of its connections.
+#[derive(Debug, Default)]
+pub struct ConnectionStats {
+    active_connections: AtomicU64,
+    bytes_read: AtomicU64,
+    messages_parsed: AtomicU64,
+    parse_errors: AtomicU64,
+}
+
+impl ConnectionStats {
+    pub fn new() -> ConnectionStats {
+        ConnectionStats::default()
+    }
+
+    // The connection is counted as active until the returned value is
+    // dropped.
+    pub fn track_connection(self: &Arc<Self>) -> ActiveConnection {
+        self.active_connections.fetch_add(1, Ordering::Relaxed);
+        ActiveConnection {
+            stats: self.clone(),
+        }
+    }
+
+    pub fn record_bytes_read(&self, size: usize) {
+        self.bytes_read.fetch_add(size as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_msg_parsed(&self) {
+        self.messages_parsed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_parse_error(&self) {
+        self.parse_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> ConnectionPoolStats {
+        ConnectionPoolStats {
+            active_connections: self.active_connections.load(Ordering::Relaxed),
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            messages_parsed: self.messages_parsed.load(Ordering::Relaxed),
+            parse_errors: self.parse_errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+pub struct ActiveConnection {
+    stats: Arc<ConnectionStats>,
+}
+
+impl Drop for ActiveConnection {
+    fn drop(&mut self) {
+        self.stats
+            .active_connections
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+}
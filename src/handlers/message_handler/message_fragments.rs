@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use thiserror::Error;
+use tracing::warn;
+
+use super::messages::message::SerializedMessage;
+
+pub const DEFAULT_MAX_REASSEMBLY_BYTES: usize = 256 * 1024 * 1024;
+pub const DEFAULT_REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Error)]
+pub enum MessageFragmentsError {
+    #[error("reassembly buffer reached max size with message: {0}")]
+    ReassemblyBufferReachedMaxSize(u128),
+    #[error("fragment index {1} is invalid for message {0} with {2} fragments")]
+    InvalidFragmentIndex(u128, u32, u32),
+}
+
+#[derive(Debug)]
+struct PartialMessage {
+    fragments: Vec<Option<SerializedMessage>>,
+    num_received: u32,
+    num_bytes: usize,
+    started_at: Instant,
+}
+
+#[derive(Debug, Default)]
+struct MessageFragmentsState {
+    partial_msgs: HashMap<u128, PartialMessage>,
+    num_bytes: usize,
+}
+
+// Buffers the fragments of large messages by msg_id until every fragment
+// has arrived. The buffered data is capped and partial messages which
+// don't complete within the timeout are dropped.
+#[derive(Debug)]
+pub struct MessageFragments {
+    max_bytes: usize,
+    timeout: Duration,
+    state: Mutex<MessageFragmentsState>,
+}
+
+impl MessageFragments {
+    pub fn new(max_bytes: usize, timeout: Duration) -> MessageFragments {
+        MessageFragments {
+            max_bytes,
+            timeout,
+            state: Mutex::new(MessageFragmentsState::default()),
+        }
+    }
+
+    pub fn num_buffered_bytes(&self) -> usize {
+        self.state.lock().unwrap().num_bytes
+    }
+
+    // Returns the reassembled message once the last fragment is added.
+    pub fn add(&self, fragment: SerializedMessage) -> Result<Option<SerializedMessage>> {
+        let mut state = self.state.lock().unwrap();
+        self.drop_timed_out_msgs(&mut state);
+
+        let msg_id = fragment.msg_id();
+        let fragment_index = fragment.fragment_index();
+        let fragment_total = fragment.fragment_total();
+        let fragment_bytes = fragment.msg_data.len();
+
+        if fragment_index >= fragment_total {
+            return Err(MessageFragmentsError::InvalidFragmentIndex(
+                msg_id,
+                fragment_index,
+                fragment_total,
+            )
+            .into());
+        }
+
+        if state.num_bytes + fragment_bytes > self.max_bytes {
+            // the message can't be completed so its fragments are dropped
+            if let Some(partial_msg) = state.partial_msgs.remove(&msg_id) {
+                state.num_bytes -= partial_msg.num_bytes;
+            }
+            return Err(MessageFragmentsError::ReassemblyBufferReachedMaxSize(msg_id).into());
+        }
+
+        let partial_msg = state
+            .partial_msgs
+            .entry(msg_id)
+            .or_insert_with(|| PartialMessage {
+                fragments: (0..fragment_total).map(|_| None).collect(),
+                num_received: 0,
+                num_bytes: 0,
+                started_at: Instant::now(),
+            });
+        match partial_msg.fragments.get_mut(fragment_index as usize) {
+            Some(slot) if slot.is_none() => {
+                *slot = Some(fragment);
+            }
+            _ => {
+                return Err(MessageFragmentsError::InvalidFragmentIndex(
+                    msg_id,
+                    fragment_index,
+                    fragment_total,
+                )
+                .into());
+            }
+        }
+        partial_msg.num_received += 1;
+        partial_msg.num_bytes += fragment_bytes;
+        let is_complete = partial_msg.num_received == fragment_total;
+        state.num_bytes += fragment_bytes;
+
+        if !is_complete {
+            return Ok(None);
+        }
+
+        let partial_msg = state
+            .partial_msgs
+            .remove(&msg_id)
+            .expect("partial message should exist");
+        state.num_bytes -= partial_msg.num_bytes;
+        let fragments = partial_msg.fragments.into_iter().flatten().collect();
+        Ok(Some(SerializedMessage::from_fragments(fragments)?))
+    }
+
+    fn drop_timed_out_msgs(&self, state: &mut MessageFragmentsState) {
+        let timed_out_msg_ids: Vec<u128> = state
+            .partial_msgs
+            .iter()
+            .filter(|(_, partial_msg)| partial_msg.started_at.elapsed() > self.timeout)
+            .map(|(msg_id, _)| *msg_id)
+            .collect();
+        for msg_id in timed_out_msg_ids {
+            if let Some(partial_msg) = state.partial_msgs.remove(&msg_id) {
+                warn!(
+                    msg_id,
+                    num_received = partial_msg.num_received,
+                    num_fragments = partial_msg.fragments.len(),
+                    "dropped partial message after timing out"
+                );
+                state.num_bytes -= partial_msg.num_bytes;
+            }
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// message sizes in bytes
+const MSG_SIZE_BUCKETS: [u64; 8] = [
+    256,
+    1024,
+    4 * 1024,
+    16 * 1024,
+    64 * 1024,
+    256 * 1024,
+    1024 * 1024,
+    4 * 1024 * 1024,
+];
+
+// handler durations in microseconds
+const ROUTE_DURATION_BUCKETS: [u64; 6] = [100, 1_000, 10_000, 100_000, 1_000_000, 10_000_000];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistogramSnapshot {
+    pub bucket_upper_bounds: Vec<u64>,
+    // one more count than bounds; the last count is for
+    // values larger than the last bound
+    pub bucket_counts: Vec<u64>,
+    pub count: u64,
+    pub sum: u64,
+    pub max: u64,
+}
+
+#[derive(Debug)]
+pub struct Histogram {
+    bucket_upper_bounds: Vec<u64>,
+    bucket_counts: Vec<AtomicU64>,
+    count: AtomicU64,
+    sum: AtomicU64,
+    max: AtomicU64,
+}
+
+impl Histogram {
+    pub fn new(bucket_upper_bounds: &[u64]) -> Histogram {
+        Histogram {
+            bucket_upper_bounds: bucket_upper_bounds.to_vec(),
+            bucket_counts: (0..bucket_upper_bounds.len() + 1)
+                .map(|_| AtomicU64::new(0))
+                .collect(),
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+            max: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record(&self, value: u64) {
+        let idx = self
+            .bucket_upper_bounds
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(self.bucket_upper_bounds.len());
+        self.bucket_counts[idx].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(value, Ordering::Relaxed);
+        self.max.fetch_max(value, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        HistogramSnapshot {
+            bucket_upper_bounds: self.bucket_upper_bounds.clone(),
+            bucket_counts: self
+                .bucket_counts
+                .iter()
+                .map(|item| item.load(Ordering::Relaxed))
+                .collect(),
+            count: self.count.load(Ordering::Relaxed),
+            sum: self.sum.load(Ordering::Relaxed),
+            max: self.max.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageMetricsSnapshot {
+    pub msg_size_bytes: HistogramSnapshot,
+    pub route_duration_micros: HistogramSnapshot,
+    pub slow_handler_count: u64,
+}
+
+#[derive(Debug)]
+pub struct MessageMetrics {
+    msg_size_bytes: Histogram,
+    route_duration_micros: Histogram,
+    slow_handler_count: AtomicU64,
+}
+
+impl MessageMetrics {
+    pub fn new() -> MessageMetrics {
+        MessageMetrics {
+            msg_size_bytes: Histogram::new(&MSG_SIZE_BUCKETS),
+            route_duration_micros: Histogram::new(&ROUTE_DURATION_BUCKETS),
+            slow_handler_count: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record_msg_size(&self, size: usize) {
+        self.msg_size_bytes.record(size as u64);
+    }
+
+    pub fn record_route_duration(&self, duration: std::time::Duration) {
+        self.route_duration_micros
+            .record(duration.as_micros().min(u64::MAX as u128) as u64);
+    }
+
+    pub fn record_slow_handler(&self) {
+        self.slow_handler_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn slow_handler_count(&self) -> u64 {
+        self.slow_handler_count.load(Ordering::Relaxed)
+    }
+
+    pub fn snapshot(&self) -> MessageMetricsSnapshot {
+        MessageMetricsSnapshot {
+            msg_size_bytes: self.msg_size_bytes.snapshot(),
+            route_duration_micros: self.route_duration_micros.snapshot(),
+            slow_handler_count: self.slow_handler_count(),
+        }
+    }
+}
+
+impl Default for MessageMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,7 +1,13 @@
+use std::time::Duration;
+
 use anyhow::Result;
 use bytes::BytesMut;
 use thiserror::Error;
 
+use super::message_fragments::{
+    MessageFragments, DEFAULT_MAX_REASSEMBLY_BYTES, DEFAULT_REASSEMBLY_TIMEOUT,
+};
+use super::message_metrics::MessageMetrics;
 use super::messages;
 use super::messages::message::{
     GenericMessageParser, Message, MessageParser, SendableMessage, SerializedMessage,
@@ -18,6 +24,18 @@ pub enum MessageRegistryError {
     UnableToCastMessageToExpectedType(String),
 }
 
+// An incomplete message needs more data from the stream; any other build
+// error means the data can't be decoded.
+pub fn is_incomplete_msg_error(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<SerializedMessageError>(),
+        Some(SerializedMessageError::Incomplete)
+    ) || matches!(
+        err.downcast_ref::<MessageRegistryError>(),
+        Some(MessageRegistryError::IncompleteMessage)
+    )
+}
+
 #[derive(Debug)]
 pub struct RegisteredMessage {
     msg_parser: Box<dyn MessageParser>,
@@ -26,17 +44,31 @@ pub struct RegisteredMessage {
 #[derive(Debug)]
 pub struct MessageRegistry {
     msg_listing: Vec<RegisteredMessage>,
+    metrics: MessageMetrics,
+    fragments: MessageFragments,
 }
 
 impl MessageRegistry {
     pub fn new() -> MessageRegistry {
         let mut reg = MessageRegistry {
             msg_listing: Vec::new(),
+            metrics: MessageMetrics::new(),
+            fragments: MessageFragments::new(
+                DEFAULT_MAX_REASSEMBLY_BYTES,
+                DEFAULT_REASSEMBLY_TIMEOUT,
+            ),
         };
         reg.register_messages();
         reg
     }
 
+    // Limit the bytes buffered while reassembling fragmented messages and
+    // how long a partial message is kept.
+    pub fn set_fragment_reassembly(&mut self, max_bytes: usize, timeout: Duration) -> &Self {
+        self.fragments = MessageFragments::new(max_bytes, timeout);
+        self
+    }
+
     fn register_messages(&mut self) {
         // common
         self.add(Box::new(
@@ -68,6 +100,69 @@ impl MessageRegistry {
         self.add(Box::new(GenericMessageParser::<
             messages::query::OperatorInstanceStatusChange,
         >::new()));
+        self.add(Box::new(GenericMessageParser::<
+            messages::query::GetQueryDiagnostics,
+        >::new()));
+        self.add(Box::new(GenericMessageParser::<
+            messages::query::GetQueryDiagnosticsResp,
+        >::new()));
+        self.add(Box::new(GenericMessageParser::<
+            messages::query::GetQueryPlan,
+        >::new()));
+        self.add(Box::new(GenericMessageParser::<
+            messages::query::GetQueryPlanResp,
+        >::new()));
+        self.add(Box::new(GenericMessageParser::<
+            messages::query::GetQueryData,
+        >::new()));
+        self.add(Box::new(GenericMessageParser::<
+            messages::query::GetQueryDataResp,
+        >::new()));
+        self.add(Box::new(
+            GenericMessageParser::<messages::query::CancelQuery>::new(),
+        ));
+        self.add(Box::new(GenericMessageParser::<
+            messages::query::CancelQueryResp,
+        >::new()));
+        self.add(Box::new(GenericMessageParser::<
+            messages::query::SetQueryPriority,
+        >::new()));
+        self.add(Box::new(GenericMessageParser::<
+            messages::query::SetQueryPriorityResp,
+        >::new()));
+        self.add(Box::new(GenericMessageParser::<
+            messages::query::GetQueryStatus,
+        >::new()));
+        self.add(Box::new(GenericMessageParser::<
+            messages::query::GetQueryStatusResp,
+        >::new()));
+        self.add(Box::new(
+            GenericMessageParser::<messages::query::ListQueries>::new(),
+        ));
+        self.add(Box::new(GenericMessageParser::<
+            messages::query::ListQueriesResp,
+        >::new()));
+        self.add(Box::new(GenericMessageParser::<
+            messages::query::OperatorInstanceMetrics,
+        >::new()));
+        self.add(Box::new(GenericMessageParser::<
+            messages::query::GetQueryMetrics,
+        >::new()));
+        self.add(Box::new(GenericMessageParser::<
+            messages::query::GetQueryMetricsResp,
+        >::new()));
+        self.add(Box::new(GenericMessageParser::<
+            messages::query::SubscribeQueryResults,
+        >::new()));
+        self.add(Box::new(GenericMessageParser::<
+            messages::query::QueryResults,
+        >::new()));
+        self.add(Box::new(GenericMessageParser::<
+            messages::query::StreamQueryResults,
+        >::new()));
+        self.add(Box::new(GenericMessageParser::<
+            messages::query::StreamQueryResultsResp,
+        >::new()));
 
         // operator
         self.add(Box::new(GenericMessageParser::<
@@ -93,8 +188,18 @@ impl MessageRegistry {
             return Err(MessageRegistryError::MessageIdNotInRegistry(reg_msg_id.clone()).into());
         };
 
+        let buf_len = buf.len();
         match SerializedMessage::parse(buf) {
             Ok(ser_msg) => {
+                self.metrics.record_msg_size(buf_len - buf.len());
+                let ser_msg = if ser_msg.is_fragment() {
+                    match self.fragments.add(ser_msg)? {
+                        Some(ser_msg) => ser_msg,
+                        None => return Ok(None),
+                    }
+                } else {
+                    ser_msg
+                };
                 let msg = reg_msg.msg_parser.to_msg(ser_msg)?;
                 Ok(Some(msg))
             }
@@ -105,6 +210,14 @@ impl MessageRegistry {
         }
     }
 
+    pub fn metrics(&self) -> &MessageMetrics {
+        &self.metrics
+    }
+
+    pub fn fragments(&self) -> &MessageFragments {
+        &self.fragments
+    }
+
     pub fn cast_msg<'a, T: SendableMessage>(&'a self, msg: &'a Message) -> &'a T
     where
         T: 'static + SendableMessage,
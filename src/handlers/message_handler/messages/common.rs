@@ -8,8 +8,25 @@ use super::message::{GenericMessage, MessageName, SendableMessage};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Identify {
-    Worker { id: u128 },
-    Connection { id: u128 },
+    // the max header version is exchanged so both sides can agree on the
+    // header version; peers which don't send one are version 0
+    Worker {
+        id: u128,
+        #[serde(default)]
+        token: Option<String>,
+        #[serde(default)]
+        max_header_version: u16,
+    },
+    Connection {
+        id: u128,
+        #[serde(default)]
+        token: Option<String>,
+        #[serde(default)]
+        max_header_version: u16,
+    },
+    Rejected {
+        reason: String,
+    },
 }
 
 impl GenericMessage for Identify {
@@ -44,10 +61,29 @@ impl GenericMessage for Ping {
 /////////////////////////////////////////////////////////////
 //
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ErrorCode {
+    NotFound,
+    Busy,
+    InvalidState,
+    Unavailable,
+}
+
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "not found"),
+            Self::Busy => write!(f, "busy"),
+            Self::InvalidState => write!(f, "invalid state"),
+            Self::Unavailable => write!(f, "unavailable"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum GenericResponse {
     Ok,
-    Error(String),
+    Err { code: ErrorCode, message: String },
 }
 
 impl GenericMessage for GenericResponse {
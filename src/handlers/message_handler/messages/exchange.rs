@@ -56,6 +56,8 @@ pub enum ExchangeRequests {
     GetNextRecordResponseNoneAvailable,
     SendRecordRequest {
         record_id: u64,
+        // increases with each record the producer sends
+        sequence_number: u64,
         #[serde(skip_serializing)]
         record: Arc<arrow::array::RecordBatch>,
         table_aliases: Vec<Vec<String>>, // [["tableName", "tableAlias"], ...]
@@ -68,6 +70,10 @@ pub enum ExchangeRequests {
         record_id: u64,
     },
     OperatorCompletedRecordProcessingResponse,
+    RecordProcessingHeartbeat {
+        operator_id: String,
+        record_id: u64,
+    },
 }
 
 impl ExchangeRequests {
@@ -81,6 +87,7 @@ impl ExchangeRequests {
             Self::SendRecordResponse { .. } => 5,
             Self::OperatorCompletedRecordProcessingRequest { .. } => 6,
             Self::OperatorCompletedRecordProcessingResponse => 7,
+            Self::RecordProcessingHeartbeat { .. } => 8,
         }
     }
 }
@@ -105,6 +112,7 @@ struct ExchangeRequestsGetNextRecordRequest {
 #[derive(Debug, Deserialize)]
 struct ExchangeRequestsSendRecordRequest {
     record_id: u64,
+    sequence_number: u64,
     table_aliases: Vec<Vec<String>>,
 }
 
@@ -122,6 +130,12 @@ struct ExchangeRequestsOperatorCompletedRecordProcessingRequest {
 #[derive(Debug, Deserialize)]
 struct ExchangeRequestsOperatorCompletedRecordProcessingResponse {}
 
+#[derive(Debug, Deserialize)]
+struct ExchangeRequestsRecordProcessingHeartbeat {
+    operator_id: String,
+    record_id: u64,
+}
+
 impl SendableMessage for ExchangeRequests {
     fn to_bytes(&self) -> Result<Vec<u8>> {
         match self {
@@ -211,6 +225,14 @@ impl SendableMessage for ExchangeRequests {
                 buf.put(&meta_data[..]);
                 return Ok(buf.to_vec());
             }
+            Self::RecordProcessingHeartbeat { .. } => {
+                let meta_data = serde_json::to_vec(self)?;
+                let mut buf = BytesMut::with_capacity(1 + 8 + meta_data.len());
+                buf.put_u8(self.msg_id());
+                buf.put_u64(meta_data.len() as u64);
+                buf.put(&meta_data[..]);
+                Ok(buf.to_vec())
+            }
         }
     }
     fn msg_name(&self) -> MessageName {
@@ -358,6 +380,7 @@ impl MessageParser for ExchangeRequestsParser {
             let record = self.parse_record(&mut buf)?;
             let msg = ExchangeRequests::SendRecordRequest {
                 record_id: meta.record_id,
+                sequence_number: meta.sequence_number,
                 record: Arc::new(record),
                 table_aliases: meta.table_aliases,
             };
@@ -414,6 +437,24 @@ impl MessageParser for ExchangeRequestsParser {
                 serde_json::from_slice(&meta_data[..])?;
             let msg = ExchangeRequests::OperatorCompletedRecordProcessingResponse;
 
+            Ok(Message::build_from_serialized_message(
+                ser_msg,
+                Box::new(msg),
+            ))
+        } else if msg_id == 8 {
+            let mut meta_data = BytesMut::with_capacity(meta_data_len as usize);
+            meta_data.resize(meta_data_len as usize, 0);
+            if buf.read_exact(&mut meta_data).is_err() {
+                return Err(ExchangeRequestsError::ReadExactFailed.into());
+            }
+
+            let meta: ExchangeRequestsRecordProcessingHeartbeat =
+                serde_json::from_slice(&meta_data[..])?;
+            let msg = ExchangeRequests::RecordProcessingHeartbeat {
+                operator_id: meta.operator_id,
+                record_id: meta.record_id,
+            };
+
             Ok(Message::build_from_serialized_message(
                 ser_msg,
                 Box::new(msg),
@@ -8,7 +8,19 @@ use std::io::{Cursor, Read};
 use thiserror::Error;
 use uuid::Uuid;
 
-const HEADER_VERSION: u16 = 0;
+// The version of the header written by this worker. Any change to the
+// header layout bumps the version and the oldest version still parsed is
+// kept in MIN_SUPPORTED_HEADER_VERSION so workers can be upgraded
+// incrementally.
+pub const HEADER_VERSION: u16 = 3;
+pub const MIN_SUPPORTED_HEADER_VERSION: u16 = 1;
+
+// payloads larger than this are sent as multiple fragments
+pub const MAX_FRAGMENT_DATA_BYTES: usize = 4 * 1024 * 1024;
+
+// payloads larger than this are zstd compressed
+const COMPRESSION_THRESHOLD_BYTES: usize = 16 * 1024;
+const COMPRESSION_LEVEL: i32 = 1;
 
 #[derive(Debug, Error)]
 pub enum SerializedMessageError {
@@ -18,6 +30,24 @@ pub enum SerializedMessageError {
     BufferReadToEndFailed,
     #[error("unable to cast message type {0} to base type")]
     UnableToCastMessageToType(String),
+    #[error("payload decompression failed")]
+    PayloadDecompressionFailed,
+    #[error("unsupported header version: {0}")]
+    UnsupportedVersion(u16),
+    #[error("invalid fragments for message: {0}")]
+    InvalidFragments(u128),
+}
+
+// Find the header version to use with a peer from the max version it
+// supports. Frames are always written with HEADER_VERSION so peers which
+// can't read it are rejected; older frames are still parsed.
+pub fn negotiate_header_version(peer_max_version: u16) -> Result<u16, SerializedMessageError> {
+    let version = std::cmp::min(HEADER_VERSION, peer_max_version);
+    if version < HEADER_VERSION {
+        Err(SerializedMessageError::UnsupportedVersion(peer_max_version))
+    } else {
+        Ok(version)
+    }
 }
 
 pub trait SendableMessage: fmt::Debug + Send + Sync + Any {
@@ -92,6 +122,36 @@ where
     }
 }
 
+// Layout of a serialized message; all integers are big endian.
+//
+//   header_len: u32 - bytes in the header after this field
+//   -- header version 1 --
+//   data_len: u64
+//   header_version: u16
+//   msg_name_id: u16
+//   msg_id: u128
+//   request_id: u128
+//   sent_from_flags: u8
+//   sent_from_worker_id, sent_from_query_id, sent_from_operation_id,
+//     sent_from_connection_id: u128
+//   routing_flags: u8
+//   route_to_worker_id, route_to_operation_id, route_to_connection_id: u128
+//   flags: u8
+//   -- header version 2 --
+//   fragment_index: u32
+//   fragment_total: u32
+//   -- header version 3 --
+//   in_reply_to: u128
+//   -- end of header --
+//   msg_data: [u8; data_len]
+//
+// The header_len, data_len, header_version and msg_name_id fields stay
+// at the start of the header in every version. New fields are added to
+// the end of the header along with a new HEADER_VERSION.
+//
+// Large payloads are split into fragments which each have a copy of the
+// header and a slice of the (possibly compressed) data. The fragments
+// share the msg_id and are reassembled before the data is decompressed.
 #[derive(Debug, PartialEq)]
 pub struct SerializedMessage {
     // lengths are in bytes
@@ -124,13 +184,24 @@ pub struct SerializedMessage {
     route_to_operation_id: u128,
     route_to_connection_id: u128,
 
-    // the actual user-space message
+    // 1 - msg_data is zstd compressed (bit 0)
+    // 2 - in_reply_to set (bit 1)
+    flags: u8,
+
+    // unfragmented messages have a fragment total of 1
+    fragment_index: u32,
+    fragment_total: u32,
+
+    // msg_id of the message this message replies to
+    in_reply_to: u128,
+
+    // the actual user-space message; always uncompressed once parsed
     pub msg_data: Vec<u8>,
 }
 
 impl SerializedMessage {
     pub fn new(msg: &Message) -> Result<SerializedMessage> {
-        let msg_data = msg.msg.to_bytes()?;
+        let (msg_data, mut flags) = Self::compress(msg.msg.to_bytes()?)?;
 
         let data_len: u64 = msg_data.len() as u64;
         let msg_name_id = msg.msg_name_id;
@@ -181,8 +252,14 @@ impl SerializedMessage {
             sent_from_flags = sent_from_flags | (1 << 3);
         }
 
+        let mut in_reply_to: u128 = 0;
+        if let Some(_id) = msg.in_reply_to {
+            in_reply_to = _id;
+            flags |= 1 << 1;
+        }
+
         let ser_msg = SerializedMessage {
-            header_len: Self::header_len(),
+            header_len: Self::header_len(HEADER_VERSION),
             data_len,
             header_version: HEADER_VERSION,
             msg_name_id,
@@ -197,13 +274,141 @@ impl SerializedMessage {
             route_to_worker_id,
             route_to_operation_id,
             route_to_connection_id,
+            flags,
+            fragment_index: 0,
+            fragment_total: 1,
+            in_reply_to,
             msg_data,
         };
         Ok(ser_msg)
     }
 
-    pub fn header_len() -> u32 {
-        8 + 2 + 2 + 16 + 16 + 1 + 16 + 16 + 16 + 16 + 1 + 16 + 16 + 16
+    // Compressed data is only used when it is smaller than the original.
+    fn compress(msg_data: Vec<u8>) -> Result<(Vec<u8>, u8)> {
+        if msg_data.len() <= COMPRESSION_THRESHOLD_BYTES {
+            return Ok((msg_data, 0));
+        }
+        let compressed_data = zstd::bulk::compress(&msg_data[..], COMPRESSION_LEVEL)?;
+        if compressed_data.len() < msg_data.len() {
+            Ok((compressed_data, 1))
+        } else {
+            Ok((msg_data, 0))
+        }
+    }
+
+    fn decompress(mut self) -> Result<SerializedMessage, SerializedMessageError> {
+        if self.flags & 1 == 1 {
+            self.msg_data = match zstd::stream::decode_all(&self.msg_data[..]) {
+                Ok(msg_data) => msg_data,
+                Err(_) => return Err(SerializedMessageError::PayloadDecompressionFailed),
+            };
+            self.flags &= !1;
+        }
+        self.data_len = self.msg_data.len() as u64;
+        Ok(self)
+    }
+
+    pub fn header_len(header_version: u16) -> u32 {
+        let header_len = 8 + 2 + 2 + 16 + 16 + 1 + 16 + 16 + 16 + 16 + 1 + 16 + 16 + 16 + 1;
+        let header_len = if header_version >= 2 {
+            header_len + 4 + 4
+        } else {
+            header_len
+        };
+        if header_version >= 3 {
+            header_len + 16
+        } else {
+            header_len
+        }
+    }
+
+    // The largest frame written for a single fragment.
+    pub fn max_frame_len() -> usize {
+        4 + Self::header_len(HEADER_VERSION) as usize + MAX_FRAGMENT_DATA_BYTES
+    }
+
+    pub fn msg_id(&self) -> u128 {
+        self.msg_id
+    }
+
+    pub fn fragment_index(&self) -> u32 {
+        self.fragment_index
+    }
+
+    pub fn fragment_total(&self) -> u32 {
+        self.fragment_total
+    }
+
+    pub fn is_fragment(&self) -> bool {
+        self.fragment_total > 1
+    }
+
+    // Split the message into fragments holding at most max_fragment_data_bytes
+    // of the data each.
+    pub fn into_fragments(self, max_fragment_data_bytes: usize) -> Vec<SerializedMessage> {
+        if self.msg_data.len() <= max_fragment_data_bytes {
+            return vec![self];
+        }
+        let fragment_total = self.msg_data.len().div_ceil(max_fragment_data_bytes) as u32;
+        self.msg_data
+            .chunks(max_fragment_data_bytes)
+            .enumerate()
+            .map(|(fragment_index, fragment_data)| SerializedMessage {
+                header_len: self.header_len,
+                header_version: self.header_version,
+                data_len: fragment_data.len() as u64,
+                msg_name_id: self.msg_name_id,
+                msg_id: self.msg_id,
+                request_id: self.request_id,
+                sent_from_flags: self.sent_from_flags,
+                sent_from_worker_id: self.sent_from_worker_id,
+                sent_from_query_id: self.sent_from_query_id,
+                sent_from_operation_id: self.sent_from_operation_id,
+                sent_from_connection_id: self.sent_from_connection_id,
+                routing_flags: self.routing_flags,
+                route_to_worker_id: self.route_to_worker_id,
+                route_to_operation_id: self.route_to_operation_id,
+                route_to_connection_id: self.route_to_connection_id,
+                flags: self.flags,
+                fragment_index: fragment_index as u32,
+                fragment_total,
+                in_reply_to: self.in_reply_to,
+                msg_data: fragment_data.to_vec(),
+            })
+            .collect()
+    }
+
+    // Combine every fragment of a message back into the full message.
+    pub fn from_fragments(
+        mut fragments: Vec<SerializedMessage>,
+    ) -> Result<SerializedMessage, SerializedMessageError> {
+        fragments.sort_by_key(|fragment| fragment.fragment_index);
+        let mut fragments = fragments.into_iter();
+        let mut ser_msg = match fragments.next() {
+            Some(ser_msg) => ser_msg,
+            None => return Err(SerializedMessageError::InvalidFragments(0)),
+        };
+        if ser_msg.fragment_index != 0 {
+            return Err(SerializedMessageError::InvalidFragments(ser_msg.msg_id));
+        }
+
+        let mut num_fragments = 1;
+        for fragment in fragments {
+            if fragment.msg_id != ser_msg.msg_id
+                || fragment.fragment_total != ser_msg.fragment_total
+                || fragment.fragment_index != num_fragments
+            {
+                return Err(SerializedMessageError::InvalidFragments(ser_msg.msg_id));
+            }
+            ser_msg.msg_data.extend(fragment.msg_data);
+            num_fragments += 1;
+        }
+        if num_fragments != ser_msg.fragment_total {
+            return Err(SerializedMessageError::InvalidFragments(ser_msg.msg_id));
+        }
+
+        ser_msg.fragment_total = 1;
+        ser_msg.decompress()
     }
 
     pub fn parse_registered_msg_id(data: &mut BytesMut) -> Result<u16> {
@@ -227,6 +432,11 @@ impl SerializedMessage {
                 let header_len = buf.get_u32();
                 let data_len = buf.get_u64();
                 let header_version = buf.get_u16();
+                if !(MIN_SUPPORTED_HEADER_VERSION..=HEADER_VERSION).contains(&header_version) {
+                    // skip the message so the following messages can be read
+                    data.advance(4 + header_len as usize + data_len as usize);
+                    return Err(SerializedMessageError::UnsupportedVersion(header_version));
+                }
                 let msg_name_id = buf.get_u16();
                 let msg_id = buf.get_u128();
                 let request_id = buf.get_u128();
@@ -241,6 +451,17 @@ impl SerializedMessage {
                 let route_to_worker_id = buf.get_u128();
                 let route_to_operation_id = buf.get_u128();
                 let route_to_connection_id = buf.get_u128();
+                let flags = buf.get_u8();
+                let (fragment_index, fragment_total) = if header_version >= 2 {
+                    (buf.get_u32(), buf.get_u32())
+                } else {
+                    (0, 1)
+                };
+                let in_reply_to = if header_version >= 3 {
+                    buf.get_u128()
+                } else {
+                    0
+                };
 
                 let mut msg_data = BytesMut::with_capacity(data_len as usize);
                 msg_data.resize(data_len as usize, 0);
@@ -265,13 +486,24 @@ impl SerializedMessage {
                     route_to_worker_id,
                     route_to_operation_id,
                     route_to_connection_id,
+                    flags,
+                    fragment_index,
+                    fragment_total,
+                    in_reply_to,
                     msg_data: msg_data.to_vec(),
                 };
 
                 // claim the data from the buffer
                 data.advance(4 + header_len as usize + data_len as usize);
 
-                Ok(ser_msg)
+                // the parsed message holds the uncompressed data so the
+                // lengths describe it if the message is serialized again;
+                // fragments are decompressed once reassembled
+                if ser_msg.is_fragment() {
+                    Ok(ser_msg)
+                } else {
+                    ser_msg.decompress()
+                }
             }
             Err(err) => Err(err),
         }
@@ -288,7 +520,7 @@ impl SerializedMessage {
         let header_len = buf.get_u32();
         let data_len = buf.get_u64();
 
-        if (data.len() as u64) < (header_len as u64 + data_len) {
+        if (data.len() as u64) < (4 + header_len as u64 + data_len) {
             Err(SerializedMessageError::Incomplete.into())
         } else {
             Ok(())
@@ -297,7 +529,7 @@ impl SerializedMessage {
 
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut buf =
-            BytesMut::with_capacity(4 + Self::header_len() as usize + self.data_len as usize);
+            BytesMut::with_capacity(4 + self.header_len as usize + self.data_len as usize);
 
         buf.put_u32(self.header_len);
         buf.put_u64(self.data_len);
@@ -314,6 +546,14 @@ impl SerializedMessage {
         buf.put_u128(self.route_to_worker_id);
         buf.put_u128(self.route_to_operation_id);
         buf.put_u128(self.route_to_connection_id);
+        buf.put_u8(self.flags);
+        if self.header_version >= 2 {
+            buf.put_u32(self.fragment_index);
+            buf.put_u32(self.fragment_total);
+        }
+        if self.header_version >= 3 {
+            buf.put_u128(self.in_reply_to);
+        }
         buf.put(&self.msg_data[..]);
 
         buf.to_vec()
@@ -325,6 +565,8 @@ pub struct Message {
     pub msg_name_id: u16,
     pub msg_id: u128,
     pub request_id: u128,
+    // msg_id of the request this message is a reply to
+    pub in_reply_to: Option<u128>,
     pub msg: Box<dyn SendableMessage>,
 
     // sent from
@@ -350,6 +592,7 @@ impl Clone for Message {
             msg_name_id: self.msg_name_id,
             msg_id: self.msg_id,
             request_id: self.request_id,
+            in_reply_to: self.in_reply_to,
             msg: self.msg.clone_box(),
             sent_from_worker_id: self.sent_from_worker_id,
             sent_from_query_id: self.sent_from_query_id,
@@ -377,6 +620,7 @@ impl Message {
             msg_name_id: msg.msg_name().as_u16(),
             msg_id: Uuid::new_v4().as_u128(),
             request_id: Uuid::new_v4().as_u128(),
+            in_reply_to: None,
             msg,
             sent_from_worker_id: None,
             sent_from_query_id: None,
@@ -391,12 +635,18 @@ impl Message {
         }
     }
 
+    // The reply is routed back to the sender of this message and is sent
+    // from wherever this message was routed to.
     pub fn reply(&self, sendable: Box<dyn SendableMessage>) -> Message {
         let mut msg = Message::new(sendable);
         msg.request_id = self.request_id.clone();
+        msg.in_reply_to = Some(self.msg_id);
         msg.route_to_worker_id = self.sent_from_worker_id.clone();
         msg.route_to_operation_id = self.sent_from_operation_id.clone();
         msg.route_to_connection_id = self.sent_from_connection_id.clone();
+        msg.sent_from_worker_id = self.route_to_worker_id;
+        msg.sent_from_operation_id = self.route_to_operation_id;
+        msg.sent_from_connection_id = self.route_to_connection_id;
         msg.inbound_stream_id = self.inbound_stream_id.clone();
         msg.outbound_stream_id = self.outbound_stream_id.clone();
         msg
@@ -485,6 +735,12 @@ impl Message {
             None
         };
 
+        let in_reply_to = if ser_msg.flags & 2 == 2 {
+            Some(ser_msg.in_reply_to)
+        } else {
+            None
+        };
+
         // route to values
         let route_to_worker_id = if ser_msg.routing_flags & 1 == 1 {
             Some(ser_msg.route_to_worker_id)
@@ -508,6 +764,7 @@ impl Message {
             msg_name_id: ser_msg.msg_name_id,
             msg_id: ser_msg.msg_id,
             request_id: ser_msg.request_id,
+            in_reply_to,
             msg,
             sent_from_worker_id,
             sent_from_query_id,
@@ -528,8 +785,16 @@ impl Message {
         Ok(SerializedMessage::new(&self)?)
     }
 
+    // Large messages are written as consecutive fragments.
     pub fn to_bytes(&self) -> Result<Vec<u8>> {
-        Ok(self.to_serialized_msg()?.to_bytes())
+        let mut msg_bytes = Vec::new();
+        for fragment in self
+            .to_serialized_msg()?
+            .into_fragments(MAX_FRAGMENT_DATA_BYTES)
+        {
+            msg_bytes.extend(fragment.to_bytes());
+        }
+        Ok(msg_bytes)
     }
 }
 
@@ -552,6 +817,27 @@ pub enum MessageName {
     QueryOperatorInstanceStatusChange,
     ExchangeOperatorStatusChange,
     OperatorShutdown,
+    GetQueryDiagnostics,
+    GetQueryDiagnosticsResp,
+    GetQueryPlan,
+    GetQueryPlanResp,
+    GetQueryData,
+    GetQueryDataResp,
+    CancelQuery,
+    CancelQueryResp,
+    SetQueryPriority,
+    SetQueryPriorityResp,
+    GetQueryStatus,
+    GetQueryStatusResp,
+    ListQueries,
+    ListQueriesResp,
+    OperatorInstanceMetrics,
+    GetQueryMetrics,
+    GetQueryMetricsResp,
+    SubscribeQueryResults,
+    QueryResults,
+    StreamQueryResults,
+    StreamQueryResultsResp,
 }
 
 impl MessageName {
@@ -570,6 +856,27 @@ impl MessageName {
             Self::QueryOperatorInstanceStatusChange => "QueryOperatorInstanceStatusChange",
             Self::ExchangeOperatorStatusChange => "ExchangeOperatorStatusChange",
             Self::OperatorShutdown => "OperatorShutdown",
+            Self::GetQueryDiagnostics => "GetQueryDiagnostics",
+            Self::GetQueryDiagnosticsResp => "GetQueryDiagnosticsResp",
+            Self::GetQueryPlan => "GetQueryPlan",
+            Self::GetQueryPlanResp => "GetQueryPlanResp",
+            Self::GetQueryData => "GetQueryData",
+            Self::GetQueryDataResp => "GetQueryDataResp",
+            Self::CancelQuery => "CancelQuery",
+            Self::CancelQueryResp => "CancelQueryResp",
+            Self::SetQueryPriority => "SetQueryPriority",
+            Self::SetQueryPriorityResp => "SetQueryPriorityResp",
+            Self::GetQueryStatus => "GetQueryStatus",
+            Self::GetQueryStatusResp => "GetQueryStatusResp",
+            Self::ListQueries => "ListQueries",
+            Self::ListQueriesResp => "ListQueriesResp",
+            Self::OperatorInstanceMetrics => "OperatorInstanceMetrics",
+            Self::GetQueryMetrics => "GetQueryMetrics",
+            Self::GetQueryMetricsResp => "GetQueryMetricsResp",
+            Self::SubscribeQueryResults => "SubscribeQueryResults",
+            Self::QueryResults => "QueryResults",
+            Self::StreamQueryResults => "StreamQueryResults",
+            Self::StreamQueryResultsResp => "StreamQueryResultsResp",
         }
     }
     pub fn as_u16(&self) -> u16 {
@@ -587,6 +894,27 @@ impl MessageName {
             Self::QueryOperatorInstanceStatusChange => 10,
             Self::ExchangeOperatorStatusChange => 11,
             Self::OperatorShutdown => 12,
+            Self::GetQueryDiagnostics => 13,
+            Self::GetQueryDiagnosticsResp => 14,
+            Self::GetQueryPlan => 15,
+            Self::GetQueryPlanResp => 16,
+            Self::GetQueryData => 17,
+            Self::GetQueryDataResp => 18,
+            Self::CancelQuery => 19,
+            Self::CancelQueryResp => 20,
+            Self::SetQueryPriority => 21,
+            Self::SetQueryPriorityResp => 22,
+            Self::GetQueryStatus => 23,
+            Self::GetQueryStatusResp => 24,
+            Self::ListQueries => 25,
+            Self::ListQueriesResp => 26,
+            Self::OperatorInstanceMetrics => 27,
+            Self::GetQueryMetrics => 28,
+            Self::GetQueryMetricsResp => 29,
+            Self::SubscribeQueryResults => 30,
+            Self::QueryResults => 31,
+            Self::StreamQueryResults => 32,
+            Self::StreamQueryResultsResp => 33,
         }
     }
 }
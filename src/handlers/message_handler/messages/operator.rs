@@ -27,7 +27,7 @@ impl GenericMessage for Shutdown {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum OperatorInstanceStatusChange {
     Complete,
-    Error(String),
+    Error { error: String, retryable: bool },
 }
 
 impl GenericMessage for OperatorInstanceStatusChange {
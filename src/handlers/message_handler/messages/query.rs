@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
@@ -18,6 +20,9 @@ pub enum OperatorInstanceStatusChange {
         query_id: u128,
         operator_instance_id: u128,
         error: String,
+        // the operator instance can be run again
+        #[serde(default)]
+        retryable: bool,
     },
 }
 
@@ -34,14 +39,72 @@ impl GenericMessage for OperatorInstanceStatusChange {
 ////////////////////////////////////////////////////////////
 //
 
+// Counts are in rows; the bytes are the in-memory size of the records
+// written by the operator instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct OperatorMetrics {
+    pub records_in: u64,
+    pub records_out: u64,
+    pub bytes_out: u64,
+    pub wall_time_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperatorInstanceMetrics {
+    pub query_id: u128,
+    pub operator_instance_id: u128,
+    pub metrics: OperatorMetrics,
+}
+
+impl GenericMessage for OperatorInstanceMetrics {
+    fn msg_name() -> MessageName {
+        MessageName::OperatorInstanceMetrics
+    }
+    fn build_msg(data: &Vec<u8>) -> Result<Box<dyn SendableMessage>> {
+        let msg: OperatorInstanceMetrics = serde_json::from_slice(data)?;
+        Ok(Box::new(msg))
+    }
+}
+
+////////////////////////////////////////////////////////////
+//
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum QueryPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RunQuery {
     pub query: String,
+    // the query fails if it hasn't finished within the timeout; the time
+    // spent waiting for a worker doesn't count
+    #[serde(default)]
+    pub timeout: Option<std::time::Duration>,
+    #[serde(default)]
+    pub priority: QueryPriority,
 }
 
 impl RunQuery {
     pub fn new(query: String) -> RunQuery {
-        RunQuery { query }
+        RunQuery {
+            query,
+            timeout: None,
+            priority: QueryPriority::default(),
+        }
+    }
+
+    pub fn set_priority(mut self, priority: QueryPriority) -> RunQuery {
+        self.priority = priority;
+        self
+    }
+
+    pub fn set_timeout(mut self, timeout: std::time::Duration) -> RunQuery {
+        self.timeout = Some(timeout);
+        self
     }
 }
 
@@ -61,6 +124,7 @@ impl GenericMessage for RunQuery {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum RunQueryResp {
     Created { query_id: u128 },
+    Explained { plan: String },
     NotCreated,
 }
 
@@ -111,7 +175,8 @@ pub enum OperatorInstanceAssignment {
         query_id: u128,
         op_instance_id: u128,
         pipeline_id: String,
-        operator: planner::Operator,
+        instance_idx: usize,
+        operator: Box<planner::Operator>,
     },
     AssignAcceptedResponse {
         query_id: u128,
@@ -178,3 +243,506 @@ impl GenericMessage for QueryHandlerRequests {
         Ok(Box::new(msg))
     }
 }
+
+////////////////////////////////////////////////////////////
+//
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetQueryDiagnostics {
+    pub query_id: u128,
+}
+
+impl GetQueryDiagnostics {
+    pub fn new(query_id: u128) -> GetQueryDiagnostics {
+        GetQueryDiagnostics { query_id }
+    }
+}
+
+impl GenericMessage for GetQueryDiagnostics {
+    fn msg_name() -> MessageName {
+        MessageName::GetQueryDiagnostics
+    }
+    fn build_msg(data: &Vec<u8>) -> Result<Box<dyn SendableMessage>> {
+        let msg: GetQueryDiagnostics = serde_json::from_slice(data)?;
+        Ok(Box::new(msg))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OperatorInstanceDiagnostics {
+    pub operator_instance_id: u128,
+    pub operator_id: String,
+    pub pipeline_id: String,
+    pub worker_id: Option<u128>,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryDiagnostics {
+    pub query_id: u128,
+    pub query_error: Option<String>,
+    pub operator_errors: Vec<OperatorInstanceDiagnostics>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum GetQueryDiagnosticsResp {
+    Diagnostics(QueryDiagnostics),
+    QueryNotFound,
+}
+
+impl GenericMessage for GetQueryDiagnosticsResp {
+    fn msg_name() -> MessageName {
+        MessageName::GetQueryDiagnosticsResp
+    }
+    fn build_msg(data: &Vec<u8>) -> Result<Box<dyn SendableMessage>> {
+        let msg: GetQueryDiagnosticsResp = serde_json::from_slice(data)?;
+        Ok(Box::new(msg))
+    }
+}
+
+////////////////////////////////////////////////////////////
+//
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetQueryPlan {
+    pub query_id: u128,
+}
+
+impl GetQueryPlan {
+    pub fn new(query_id: u128) -> GetQueryPlan {
+        GetQueryPlan { query_id }
+    }
+}
+
+impl GenericMessage for GetQueryPlan {
+    fn msg_name() -> MessageName {
+        MessageName::GetQueryPlan
+    }
+    fn build_msg(data: &Vec<u8>) -> Result<Box<dyn SendableMessage>> {
+        let msg: GetQueryPlan = serde_json::from_slice(data)?;
+        Ok(Box::new(msg))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum GetQueryPlanResp {
+    // the physical plan as json
+    Plan(String),
+    QueryNotFound,
+}
+
+impl GenericMessage for GetQueryPlanResp {
+    fn msg_name() -> MessageName {
+        MessageName::GetQueryPlanResp
+    }
+    fn build_msg(data: &Vec<u8>) -> Result<Box<dyn SendableMessage>> {
+        let msg: GetQueryPlanResp = serde_json::from_slice(data)?;
+        Ok(Box::new(msg))
+    }
+}
+
+////////////////////////////////////////////////////////////
+//
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetQueryData {
+    pub query_id: u128,
+    pub file_idx: u64,
+}
+
+impl GetQueryData {
+    pub fn new(query_id: u128, file_idx: u64) -> GetQueryData {
+        GetQueryData { query_id, file_idx }
+    }
+}
+
+impl GenericMessage for GetQueryData {
+    fn msg_name() -> MessageName {
+        MessageName::GetQueryData
+    }
+    fn build_msg(data: &Vec<u8>) -> Result<Box<dyn SendableMessage>> {
+        let msg: GetQueryData = serde_json::from_slice(data)?;
+        Ok(Box::new(msg))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum GetQueryDataResp {
+    Record {
+        file_idx: u64,
+        #[serde(with = "record_ipc")]
+        record: Arc<arrow::array::RecordBatch>,
+    },
+    ReachedEndOfFiles,
+    // the query hasn't completed or some of its manifests aren't
+    // readable yet
+    QueryResultsNotReady,
+    Error(String),
+}
+
+impl GenericMessage for GetQueryDataResp {
+    fn msg_name() -> MessageName {
+        MessageName::GetQueryDataResp
+    }
+    fn build_msg(data: &Vec<u8>) -> Result<Box<dyn SendableMessage>> {
+        let msg: GetQueryDataResp = serde_json::from_slice(data)?;
+        Ok(Box::new(msg))
+    }
+}
+
+// Records are serialized as arrow ipc stream bytes.
+mod record_ipc {
+    use std::sync::Arc;
+
+    use arrow::array::RecordBatch;
+    use serde::{de, ser, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        record: &Arc<RecordBatch>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut data_buf = Vec::new();
+        {
+            let mut record_writer =
+                arrow::ipc::writer::StreamWriter::try_new(&mut data_buf, &record.schema())
+                    .map_err(ser::Error::custom)?;
+            record_writer.write(record).map_err(ser::Error::custom)?;
+            record_writer.finish().map_err(ser::Error::custom)?;
+        }
+        serializer.serialize_bytes(&data_buf)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Arc<RecordBatch>, D::Error> {
+        let data_buf = Vec::<u8>::deserialize(deserializer)?;
+        let mut reader =
+            arrow::ipc::reader::StreamReader::try_new(std::io::Cursor::new(data_buf), None)
+                .map_err(de::Error::custom)?;
+        match reader.next() {
+            Some(record) => Ok(Arc::new(record.map_err(de::Error::custom)?)),
+            None => Err(de::Error::custom("expected a record in the ipc stream")),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////
+//
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CancelQuery {
+    pub query_id: u128,
+}
+
+impl CancelQuery {
+    pub fn new(query_id: u128) -> CancelQuery {
+        CancelQuery { query_id }
+    }
+}
+
+impl GenericMessage for CancelQuery {
+    fn msg_name() -> MessageName {
+        MessageName::CancelQuery
+    }
+    fn build_msg(data: &Vec<u8>) -> Result<Box<dyn SendableMessage>> {
+        let msg: CancelQuery = serde_json::from_slice(data)?;
+        Ok(Box::new(msg))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum CancelQueryResp {
+    Cancelled,
+    // the query completed or failed before it could be cancelled
+    AlreadyFinished,
+    QueryNotFound,
+}
+
+impl GenericMessage for CancelQueryResp {
+    fn msg_name() -> MessageName {
+        MessageName::CancelQueryResp
+    }
+    fn build_msg(data: &Vec<u8>) -> Result<Box<dyn SendableMessage>> {
+        let msg: CancelQueryResp = serde_json::from_slice(data)?;
+        Ok(Box::new(msg))
+    }
+}
+
+////////////////////////////////////////////////////////////
+//
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetQueryPriority {
+    pub query_id: u128,
+    pub priority: QueryPriority,
+}
+
+impl SetQueryPriority {
+    pub fn new(query_id: u128, priority: QueryPriority) -> SetQueryPriority {
+        SetQueryPriority { query_id, priority }
+    }
+}
+
+impl GenericMessage for SetQueryPriority {
+    fn msg_name() -> MessageName {
+        MessageName::SetQueryPriority
+    }
+    fn build_msg(data: &Vec<u8>) -> Result<Box<dyn SendableMessage>> {
+        let msg: SetQueryPriority = serde_json::from_slice(data)?;
+        Ok(Box::new(msg))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SetQueryPriorityResp {
+    Updated,
+    AlreadyFinished,
+    QueryNotFound,
+}
+
+impl GenericMessage for SetQueryPriorityResp {
+    fn msg_name() -> MessageName {
+        MessageName::SetQueryPriorityResp
+    }
+    fn build_msg(data: &Vec<u8>) -> Result<Box<dyn SendableMessage>> {
+        let msg: SetQueryPriorityResp = serde_json::from_slice(data)?;
+        Ok(Box::new(msg))
+    }
+}
+
+////////////////////////////////////////////////////////////
+//
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum QueryStatus {
+    Queued,
+    Running,
+    Complete,
+    Error(String),
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetQueryStatus {
+    pub query_id: u128,
+}
+
+impl GetQueryStatus {
+    pub fn new(query_id: u128) -> GetQueryStatus {
+        GetQueryStatus { query_id }
+    }
+}
+
+impl GenericMessage for GetQueryStatus {
+    fn msg_name() -> MessageName {
+        MessageName::GetQueryStatus
+    }
+    fn build_msg(data: &Vec<u8>) -> Result<Box<dyn SendableMessage>> {
+        let msg: GetQueryStatus = serde_json::from_slice(data)?;
+        Ok(Box::new(msg))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum GetQueryStatusResp {
+    Status(QueryStatus),
+    QueryNotFound,
+}
+
+impl GenericMessage for GetQueryStatusResp {
+    fn msg_name() -> MessageName {
+        MessageName::GetQueryStatusResp
+    }
+    fn build_msg(data: &Vec<u8>) -> Result<Box<dyn SendableMessage>> {
+        let msg: GetQueryStatusResp = serde_json::from_slice(data)?;
+        Ok(Box::new(msg))
+    }
+}
+
+////////////////////////////////////////////////////////////
+//
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListQueries {
+    // only list queries with this status; the error text is ignored
+    pub status: Option<QueryStatus>,
+}
+
+impl ListQueries {
+    pub fn new(status: Option<QueryStatus>) -> ListQueries {
+        ListQueries { status }
+    }
+}
+
+impl GenericMessage for ListQueries {
+    fn msg_name() -> MessageName {
+        MessageName::ListQueries
+    }
+    fn build_msg(data: &Vec<u8>) -> Result<Box<dyn SendableMessage>> {
+        let msg: ListQueries = serde_json::from_slice(data)?;
+        Ok(Box::new(msg))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuerySummary {
+    pub query_id: u128,
+    pub status: QueryStatus,
+    // the sql is truncated for long queries
+    pub query: String,
+    pub submitted_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ListQueriesResp {
+    pub queries: Vec<QuerySummary>,
+}
+
+impl GenericMessage for ListQueriesResp {
+    fn msg_name() -> MessageName {
+        MessageName::ListQueriesResp
+    }
+    fn build_msg(data: &Vec<u8>) -> Result<Box<dyn SendableMessage>> {
+        let msg: ListQueriesResp = serde_json::from_slice(data)?;
+        Ok(Box::new(msg))
+    }
+}
+
+////////////////////////////////////////////////////////////
+//
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetQueryMetrics {
+    pub query_id: u128,
+}
+
+impl GetQueryMetrics {
+    pub fn new(query_id: u128) -> GetQueryMetrics {
+        GetQueryMetrics { query_id }
+    }
+}
+
+impl GenericMessage for GetQueryMetrics {
+    fn msg_name() -> MessageName {
+        MessageName::GetQueryMetrics
+    }
+    fn build_msg(data: &Vec<u8>) -> Result<Box<dyn SendableMessage>> {
+        let msg: GetQueryMetrics = serde_json::from_slice(data)?;
+        Ok(Box::new(msg))
+    }
+}
+
+// Operator instances which haven't completed don't have metrics yet.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OperatorInstanceStats {
+    pub operator_instance_id: u128,
+    pub operator_id: String,
+    pub pipeline_id: String,
+    pub metrics: Option<OperatorMetrics>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum GetQueryMetricsResp {
+    Metrics(Vec<OperatorInstanceStats>),
+    QueryNotFound,
+}
+
+impl GenericMessage for GetQueryMetricsResp {
+    fn msg_name() -> MessageName {
+        MessageName::GetQueryMetricsResp
+    }
+    fn build_msg(data: &Vec<u8>) -> Result<Box<dyn SendableMessage>> {
+        let msg: GetQueryMetricsResp = serde_json::from_slice(data)?;
+        Ok(Box::new(msg))
+    }
+}
+
+////////////////////////////////////////////////////////////
+//
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscribeQueryResults {
+    pub query_id: u128,
+}
+
+impl SubscribeQueryResults {
+    pub fn new(query_id: u128) -> SubscribeQueryResults {
+        SubscribeQueryResults { query_id }
+    }
+}
+
+impl GenericMessage for SubscribeQueryResults {
+    fn msg_name() -> MessageName {
+        MessageName::SubscribeQueryResults
+    }
+    fn build_msg(data: &Vec<u8>) -> Result<Box<dyn SendableMessage>> {
+        let msg: SubscribeQueryResults = serde_json::from_slice(data)?;
+        Ok(Box::new(msg))
+    }
+}
+
+// Sent to the subscribed client as the query produces its results.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum QueryResults {
+    Subscribed,
+    Record {
+        #[serde(with = "record_ipc")]
+        record: Arc<arrow::array::RecordBatch>,
+    },
+    End,
+    Error(String),
+    // the results can only be read from the materialized files
+    AlreadyFinished,
+    QueryNotFound,
+}
+
+impl GenericMessage for QueryResults {
+    fn msg_name() -> MessageName {
+        MessageName::QueryResults
+    }
+    fn build_msg(data: &Vec<u8>) -> Result<Box<dyn SendableMessage>> {
+        let msg: QueryResults = serde_json::from_slice(data)?;
+        Ok(Box::new(msg))
+    }
+}
+
+// Sent by the materialize operator to the query handler which forwards
+// the records to the subscribed clients.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum StreamQueryResults {
+    Record {
+        query_id: u128,
+        #[serde(with = "record_ipc")]
+        record: Arc<arrow::array::RecordBatch>,
+    },
+    End {
+        query_id: u128,
+        operator_instance_id: u128,
+    },
+}
+
+impl GenericMessage for StreamQueryResults {
+    fn msg_name() -> MessageName {
+        MessageName::StreamQueryResults
+    }
+    fn build_msg(data: &Vec<u8>) -> Result<Box<dyn SendableMessage>> {
+        let msg: StreamQueryResults = serde_json::from_slice(data)?;
+        Ok(Box::new(msg))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum StreamQueryResultsResp {
+    Forwarded,
+    NoSubscribers,
+}
+
+impl GenericMessage for StreamQueryResultsResp {
+    fn msg_name() -> MessageName {
+        MessageName::StreamQueryResultsResp
+    }
+    fn build_msg(data: &Vec<u8>) -> Result<Box<dyn SendableMessage>> {
+        let msg: StreamQueryResultsResp = serde_json::from_slice(data)?;
+        Ok(Box::new(msg))
+    }
+}
@@ -1,11 +1,33 @@
 mod comms;
 mod connection;
 mod connection_pool_handler;
+mod connection_stats;
+mod message_fragments;
+mod message_metrics;
 mod message_registry;
 pub mod messages;
 #[cfg(test)]
+pub mod test_comms;
+#[cfg(test)]
+pub mod test_connection;
+#[cfg(test)]
+pub mod test_connection_pool_handler;
+#[cfg(test)]
+pub mod test_message_fragments;
+#[cfg(test)]
 pub mod test_messages;
+#[cfg(test)]
+pub mod test_tls;
+mod tls;
 
-pub use self::comms::{Pipe, Request};
+pub use self::comms::{Pipe, Request, RetryPolicy};
+pub use self::connection::{is_heartbeat, ConnectionStream, Heartbeat};
 pub use self::connection_pool_handler::ConnectionPoolHandler;
-pub use self::message_registry::MessageRegistry;
+pub use self::connection_stats::{ConnectionPoolStats, ConnectionStats};
+pub use self::message_fragments::{
+    MessageFragments, MessageFragmentsError, DEFAULT_MAX_REASSEMBLY_BYTES,
+    DEFAULT_REASSEMBLY_TIMEOUT,
+};
+pub use self::message_metrics::{HistogramSnapshot, MessageMetrics, MessageMetricsSnapshot};
+pub use self::message_registry::{is_incomplete_msg_error, MessageRegistry};
+pub use self::tls::{Tls, TlsConfig, TlsError};
@@ -0,0 +1,158 @@
+use std::time::Duration;
+
+use anyhow::Result;
+
+use super::messages;
+use super::messages::message::{Message, MessageName};
+use super::{Pipe, Request, RetryPolicy};
+
+#[tokio::test]
+async fn test_request_is_retried_until_it_succeeds() -> Result<()> {
+    let (mut pipe, mut stub_pipe) = Pipe::new(10);
+
+    // the stub drops the first two requests and responds to the third
+    let stub = tokio::spawn(async move {
+        let mut attempts = 0;
+        while let Some(msg) = stub_pipe.recv().await {
+            attempts += 1;
+            if attempts == 3 {
+                stub_pipe
+                    .send(msg.reply(Box::new(messages::common::Ping::Pong)))
+                    .await?;
+                break;
+            }
+        }
+        Ok::<u32, anyhow::Error>(attempts)
+    });
+
+    let resp = pipe
+        .send_request(Request {
+            msg: Message::new(Box::new(messages::common::Ping::Ping)),
+            expect_response_msg_name: MessageName::Ping,
+            timeout: chrono::Duration::milliseconds(50),
+            retry_policy: RetryPolicy {
+                max_attempts: 5,
+                base_backoff: Duration::from_millis(10),
+                max_backoff: Duration::from_millis(100),
+                jitter: 0.5,
+            },
+        })
+        .await?;
+    assert_eq!(resp.msg.msg_name(), MessageName::Ping);
+    assert_eq!(stub.await??, 3);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_request_fails_after_the_max_attempts() -> Result<()> {
+    let (mut pipe, mut stub_pipe) = Pipe::new(10);
+
+    let res = pipe
+        .send_request(Request {
+            msg: Message::new(Box::new(messages::common::Ping::Ping)),
+            expect_response_msg_name: MessageName::Ping,
+            timeout: chrono::Duration::milliseconds(20),
+            retry_policy: RetryPolicy {
+                max_attempts: 2,
+                base_backoff: Duration::from_millis(10),
+                max_backoff: Duration::from_millis(100),
+                jitter: 0.0,
+            },
+        })
+        .await;
+    assert!(res.is_err());
+
+    drop(pipe);
+    let mut attempts = 0;
+    while stub_pipe.recv().await.is_some() {
+        attempts += 1;
+    }
+    assert_eq!(attempts, 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_retry_backoff_jitter_stays_within_bounds() {
+    let policy = RetryPolicy {
+        max_attempts: 10,
+        base_backoff: Duration::from_millis(100),
+        max_backoff: Duration::from_secs(1),
+        jitter: 0.25,
+    };
+    for attempt in 0..8 {
+        let expected = (Duration::from_millis(100) * 2u32.pow(attempt)).min(Duration::from_secs(1));
+        for _ in 0..100 {
+            let backoff = policy.backoff(attempt);
+            assert!(
+                backoff >= expected.mul_f64(0.75),
+                "{:?} < {:?}",
+                backoff,
+                expected
+            );
+            assert!(
+                backoff <= expected.mul_f64(1.25),
+                "{:?} > {:?}",
+                backoff,
+                expected
+            );
+        }
+    }
+
+    let policy = RetryPolicy {
+        jitter: 0.0,
+        ..policy
+    };
+    assert_eq!(policy.backoff(1), Duration::from_millis(200));
+}
+
+#[tokio::test]
+async fn test_concurrent_requests_receive_their_own_responses() -> Result<()> {
+    let (mut pipe, mut stub_pipe) = Pipe::new(10);
+
+    // the stub waits for both requests so the response to the first
+    // arrives while the second request is waiting for its response
+    let stub = tokio::spawn(async move {
+        let first_msg = stub_pipe.recv().await.expect("first request");
+        let second_msg = stub_pipe.recv().await.expect("second request");
+        stub_pipe
+            .send(first_msg.reply(Box::new(messages::common::GenericResponse::Ok)))
+            .await?;
+        stub_pipe
+            .send(second_msg.reply(Box::new(messages::common::Ping::Pong)))
+            .await?;
+        Ok::<(), anyhow::Error>(())
+    });
+
+    // both requests share a request id so only the in reply to id
+    // tells the responses apart
+    let first_msg = Message::new(Box::new(messages::common::Ping::Ping));
+    let second_msg =
+        Message::new(Box::new(messages::common::Ping::Ping)).set_request_id(first_msg.request_id);
+    pipe.send(first_msg.clone()).await?;
+
+    let second_resp = pipe
+        .send_request(Request {
+            msg: second_msg.clone(),
+            expect_response_msg_name: MessageName::Ping,
+            timeout: chrono::Duration::seconds(5),
+            retry_policy: RetryPolicy::default(),
+        })
+        .await?;
+    assert_eq!(second_resp.in_reply_to, Some(second_msg.msg_id));
+
+    let first_resp = pipe
+        .recv_request(&first_msg, chrono::Duration::seconds(5))
+        .await?
+        .expect("first response");
+    assert_eq!(first_resp.in_reply_to, Some(first_msg.msg_id));
+    assert_eq!(
+        first_resp.msg.msg_name(),
+        MessageName::CommonGenericResponse
+    );
+
+    stub.await??;
+
+    Ok(())
+}
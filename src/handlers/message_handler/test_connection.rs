@@ -0,0 +1,134 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use super::connection::{Connection, Heartbeat};
+use super::messages;
+use super::messages::message::Message;
+use super::MessageRegistry;
+
+fn large_query(seed: u64) -> String {
+    // pseudo random text so the payload stays large after compression
+    let mut seed = seed;
+    (0..20 * 1024 * 1024)
+        .map(|_| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            (b'a' + ((seed >> 33) % 26) as u8) as char
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_connections_read_while_writing_large_messages() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+    let (outbound_stream, inbound_stream) =
+        tokio::join!(TcpStream::connect(address), listener.accept());
+    let outbound_stream = outbound_stream?;
+    let (inbound_stream, _) = inbound_stream?;
+
+    let msg_reg = Arc::new(MessageRegistry::new());
+    let ct = CancellationToken::new();
+
+    let (inbound_sender, mut inbound_receiver) = mpsc::channel(1);
+    let (mut inbound_conn, inbound_comm) =
+        Connection::new(1, inbound_stream, inbound_sender, msg_reg.clone(), true);
+    let (outbound_sender, mut outbound_receiver) = mpsc::channel(1);
+    let (mut outbound_conn, outbound_comm) =
+        Connection::new(2, outbound_stream, outbound_sender, msg_reg.clone(), false);
+
+    let inbound_ct = ct.clone();
+    let inbound_handle = tokio::spawn(async move { inbound_conn.async_main(inbound_ct).await });
+    let outbound_ct = ct.clone();
+    let outbound_handle = tokio::spawn(async move { outbound_conn.async_main(outbound_ct).await });
+
+    // both sides write a message larger than the socket buffers at the
+    // same time; each side must keep reading while it writes
+    let inbound_query = large_query(1);
+    let outbound_query = large_query(2);
+    inbound_comm
+        .sender
+        .send(Message::new(Box::new(messages::query::RunQuery::new(
+            inbound_query.clone(),
+        ))))
+        .await?;
+    outbound_comm
+        .sender
+        .send(Message::new(Box::new(messages::query::RunQuery::new(
+            outbound_query.clone(),
+        ))))
+        .await?;
+
+    let (inbound_msg, outbound_msg) = tokio::time::timeout(Duration::from_secs(60), async {
+        tokio::join!(inbound_receiver.recv(), outbound_receiver.recv())
+    })
+    .await?;
+
+    let inbound_msg = inbound_msg.expect("expected a message on the inbound connection");
+    assert_eq!(inbound_msg.inbound_stream_id, Some(inbound_comm.stream_id));
+    let run_query: &messages::query::RunQuery = msg_reg.try_cast_msg(&inbound_msg)?;
+    assert_eq!(run_query.query, outbound_query);
+
+    let outbound_msg = outbound_msg.expect("expected a message on the outbound connection");
+    assert_eq!(
+        outbound_msg.outbound_stream_id,
+        Some(outbound_comm.stream_id)
+    );
+    let run_query: &messages::query::RunQuery = msg_reg.try_cast_msg(&outbound_msg)?;
+    assert_eq!(run_query.query, inbound_query);
+
+    ct.cancel();
+    inbound_handle.await??;
+    outbound_handle.await??;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_connections_answer_heartbeats() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+    let (outbound_stream, inbound_stream) =
+        tokio::join!(TcpStream::connect(address), listener.accept());
+    let outbound_stream = outbound_stream?;
+    let (inbound_stream, _) = inbound_stream?;
+
+    let msg_reg = Arc::new(MessageRegistry::new());
+    let ct = CancellationToken::new();
+    let heartbeat = Heartbeat {
+        interval: Duration::from_millis(20),
+        timeout: Duration::from_millis(100),
+    };
+
+    let (inbound_sender, mut inbound_receiver) = mpsc::channel(1);
+    let (mut inbound_conn, _inbound_comm) =
+        Connection::new(1, inbound_stream, inbound_sender, msg_reg.clone(), true);
+    inbound_conn.set_heartbeat(heartbeat);
+    let (outbound_sender, mut outbound_receiver) = mpsc::channel(1);
+    let (mut outbound_conn, _outbound_comm) =
+        Connection::new(2, outbound_stream, outbound_sender, msg_reg.clone(), false);
+    outbound_conn.set_heartbeat(heartbeat);
+
+    let inbound_ct = ct.clone();
+    let inbound_handle = tokio::spawn(async move { inbound_conn.async_main(inbound_ct).await });
+    let outbound_ct = ct.clone();
+    let outbound_handle = tokio::spawn(async move { outbound_conn.async_main(outbound_ct).await });
+
+    // both sides stay connected through many heartbeats and the
+    // heartbeats are not passed on
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(!inbound_handle.is_finished());
+    assert!(!outbound_handle.is_finished());
+    assert!(inbound_receiver.try_recv().is_err());
+    assert!(outbound_receiver.try_recv().is_err());
+
+    ct.cancel();
+    inbound_handle.await??;
+    outbound_handle.await??;
+
+    Ok(())
+}
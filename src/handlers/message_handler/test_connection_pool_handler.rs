@@ -0,0 +1,248 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use bytes::BytesMut;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::sync::CancellationToken;
+
+use super::messages;
+use super::messages::message::{Message, HEADER_VERSION};
+use super::{is_heartbeat, ConnectionPoolHandler, ConnectionStats, Heartbeat, MessageRegistry};
+
+async fn read_msg(
+    msg_reg: &MessageRegistry,
+    stream: &mut TcpStream,
+    buf: &mut BytesMut,
+) -> Result<Message> {
+    loop {
+        if let Ok(Some(msg)) = msg_reg.build_msg(buf) {
+            return Ok(msg);
+        }
+        let size = stream.read_buf(buf).await?;
+        assert!(size > 0, "connection closed before a message was read");
+    }
+}
+
+#[tokio::test]
+async fn test_outbound_connections_reconnect() -> Result<()> {
+    let worker_id = 1;
+    let remote_worker_id = 2;
+    let timeout = Duration::from_secs(10);
+
+    let remote_listener = TcpListener::bind("127.0.0.1:0").await?;
+    let remote_address = remote_listener.local_addr()?.to_string();
+
+    let msg_reg = Arc::new(MessageRegistry::new());
+    let (mut pool, mut router_pipe) = ConnectionPoolHandler::new(
+        worker_id,
+        "127.0.0.1:0".to_string(),
+        vec![remote_address],
+        msg_reg.clone(),
+    );
+    pool.set_connect_backoff(2, Duration::from_millis(200), Duration::from_millis(200));
+
+    let ct = CancellationToken::new();
+    let pool_ct = ct.clone();
+    let pool_handle = tokio::spawn(async move { pool.async_main(pool_ct).await });
+
+    // the pool identifies itself on the new connection
+    let (mut remote_stream, _) = tokio::time::timeout(timeout, remote_listener.accept()).await??;
+    let mut buf = BytesMut::new();
+    let identify = read_msg(&msg_reg, &mut remote_stream, &mut buf).await?;
+    let identify: &messages::common::Identify = msg_reg.try_cast_msg(&identify)?;
+    assert!(matches!(
+        identify,
+        messages::common::Identify::Worker { id, .. } if *id == worker_id
+    ));
+
+    let identify_back = Message::new(Box::new(messages::common::Identify::Worker {
+        id: remote_worker_id,
+        token: None,
+        max_header_version: HEADER_VERSION,
+    }));
+    remote_stream
+        .write_all(&identify_back.to_bytes()?[..])
+        .await?;
+    let identify_back = tokio::time::timeout(timeout, router_pipe.recv())
+        .await?
+        .expect("expected the identify message");
+    let outbound_stream_id = identify_back
+        .outbound_stream_id
+        .expect("expected the outbound stream id");
+
+    // a message sent while the pool is reconnecting is sent on the new
+    // connection
+    drop(remote_stream);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let run_query = Message::new(Box::new(messages::query::RunQuery::new(
+        "select * from read_files('simple/*.parquet')".to_string(),
+    )))
+    .set_route_to_worker_id(remote_worker_id)
+    .set_outbound_stream(outbound_stream_id);
+    router_pipe.send(run_query).await?;
+
+    let (mut remote_stream, _) = tokio::time::timeout(timeout, remote_listener.accept()).await??;
+    let mut buf = BytesMut::new();
+    let identify = read_msg(&msg_reg, &mut remote_stream, &mut buf).await?;
+    assert!(msg_reg
+        .try_cast_msg::<messages::common::Identify>(&identify)
+        .is_ok());
+    let msg =
+        tokio::time::timeout(timeout, read_msg(&msg_reg, &mut remote_stream, &mut buf)).await??;
+    let msg: &messages::query::RunQuery = msg_reg.try_cast_msg(&msg)?;
+    assert_eq!(msg.query, "select * from read_files('simple/*.parquet')");
+
+    // once the worker can't be reached the sender receives an error
+    drop(remote_stream);
+    drop(remote_listener);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let run_query = Message::new(Box::new(messages::query::RunQuery::new(
+        "select * from read_files('simple/*.parquet')".to_string(),
+    )))
+    .set_route_to_worker_id(remote_worker_id)
+    .set_outbound_stream(outbound_stream_id);
+    let request_id = run_query.request_id;
+    router_pipe.send(run_query).await?;
+
+    let resp = tokio::time::timeout(timeout, router_pipe.recv())
+        .await?
+        .expect("expected an error response");
+    assert_eq!(resp.request_id, request_id);
+    let resp: &messages::common::GenericResponse = msg_reg.try_cast_msg(&resp)?;
+    assert!(matches!(
+        resp,
+        messages::common::GenericResponse::Err {
+            code: messages::common::ErrorCode::Unavailable,
+            ..
+        }
+    ));
+
+    ct.cancel();
+    pool_handle.await??;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_silent_peers_are_disconnected() -> Result<()> {
+    let timeout = Duration::from_secs(10);
+
+    let remote_listener = TcpListener::bind("127.0.0.1:0").await?;
+    let remote_address = remote_listener.local_addr()?.to_string();
+
+    let msg_reg = Arc::new(MessageRegistry::new());
+    let (mut pool, _router_pipe) = ConnectionPoolHandler::new(
+        1,
+        "127.0.0.1:0".to_string(),
+        vec![remote_address],
+        msg_reg.clone(),
+    );
+    pool.set_heartbeat(Heartbeat {
+        interval: Duration::from_millis(100),
+        timeout: Duration::from_millis(200),
+    });
+
+    let ct = CancellationToken::new();
+    let pool_ct = ct.clone();
+    let pool_handle = tokio::spawn(async move { pool.async_main(pool_ct).await });
+
+    let (mut remote_stream, _) = tokio::time::timeout(timeout, remote_listener.accept()).await??;
+    let mut buf = BytesMut::new();
+    let identify = read_msg(&msg_reg, &mut remote_stream, &mut buf).await?;
+    assert!(msg_reg
+        .try_cast_msg::<messages::common::Identify>(&identify)
+        .is_ok());
+
+    // the idle connection is pinged
+    let ping =
+        tokio::time::timeout(timeout, read_msg(&msg_reg, &mut remote_stream, &mut buf)).await??;
+    assert!(is_heartbeat(&ping));
+    assert!(matches!(
+        msg_reg.try_cast_msg::<messages::common::Ping>(&ping)?,
+        messages::common::Ping::Ping
+    ));
+
+    // the peer never responds so the connection is closed
+    let size = tokio::time::timeout(timeout, remote_stream.read_buf(&mut buf)).await??;
+    assert_eq!(size, 0);
+
+    ct.cancel();
+    pool_handle.await??;
+
+    Ok(())
+}
+
+async fn wait_for_active_connections(
+    stats: &ConnectionStats,
+    active_connections: u64,
+    timeout: Duration,
+) -> Result<()> {
+    tokio::time::timeout(timeout, async {
+        while stats.snapshot().active_connections != active_connections {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_connection_stats() -> Result<()> {
+    let timeout = Duration::from_secs(10);
+
+    // reserve a port for the pool to listen on
+    let address = TcpListener::bind("127.0.0.1:0")
+        .await?
+        .local_addr()?
+        .to_string();
+
+    let msg_reg = Arc::new(MessageRegistry::new());
+    let (mut pool, mut router_pipe) =
+        ConnectionPoolHandler::new(1, address.clone(), vec![], msg_reg.clone());
+    let stats = pool.stats();
+
+    let ct = CancellationToken::new();
+    let pool_ct = ct.clone();
+    let pool_handle = tokio::spawn(async move { pool.async_main(pool_ct).await });
+
+    let mut streams = Vec::new();
+    for _ in 0..2 {
+        let stream = tokio::time::timeout(timeout, async {
+            loop {
+                if let Ok(stream) = TcpStream::connect(&address).await {
+                    return stream;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await?;
+        streams.push(stream);
+    }
+    wait_for_active_connections(&stats, 2, timeout).await?;
+
+    let msg_bytes = Message::new(Box::new(messages::common::Ping::Ping))
+        .set_route_to_worker_id(1)
+        .to_bytes()?;
+    streams[0].write_all(&msg_bytes[..]).await?;
+    tokio::time::timeout(timeout, router_pipe.recv())
+        .await?
+        .expect("expected the ping message");
+
+    let pool_stats = stats.snapshot();
+    assert_eq!(pool_stats.bytes_read, msg_bytes.len() as u64);
+    assert_eq!(pool_stats.messages_parsed, 1);
+    assert_eq!(pool_stats.parse_errors, 0);
+
+    // closed connections are no longer active
+    drop(streams.remove(0));
+    wait_for_active_connections(&stats, 1, timeout).await?;
+    drop(streams.remove(0));
+    wait_for_active_connections(&stats, 0, timeout).await?;
+
+    ct.cancel();
+    pool_handle.await??;
+
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use anyhow::Result;
+
+use super::messages;
+use super::messages::message::{Message, SerializedMessage};
+use super::{MessageFragments, MessageFragmentsError};
+
+fn query_fragments(query: &str, fragment_bytes: usize) -> Result<Vec<SerializedMessage>> {
+    let msg = Message::new(Box::new(messages::query::RunQuery::new(query.to_string())));
+    Ok(msg.to_serialized_msg()?.into_fragments(fragment_bytes))
+}
+
+#[test]
+fn test_fragments_are_reassembled() -> Result<()> {
+    let query = "select id, size from read_files('simple/*.parquet') where size > 10";
+    let expected_msg = Message::new(Box::new(messages::query::RunQuery::new(query.to_string())));
+    let expected_data = expected_msg.to_serialized_msg()?.msg_data;
+
+    let mut fragments = query_fragments(query, 16)?;
+    assert_eq!(fragments.len(), expected_data.len().div_ceil(16));
+
+    // fragments can arrive in any order
+    fragments.reverse();
+    let fragments_buf = MessageFragments::new(1024, Duration::from_secs(60));
+    let num_fragments = fragments.len();
+    for (idx, fragment) in fragments.into_iter().enumerate() {
+        let ser_msg = fragments_buf.add(fragment)?;
+        if idx + 1 < num_fragments {
+            assert!(ser_msg.is_none());
+            assert!(fragments_buf.num_buffered_bytes() > 0);
+        } else {
+            let ser_msg = ser_msg.expect("expected the reassembled message");
+            assert!(!ser_msg.is_fragment());
+            assert_eq!(ser_msg.msg_data, expected_data);
+        }
+    }
+    assert_eq!(fragments_buf.num_buffered_bytes(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_fragment_reassembly_limits() -> Result<()> {
+    struct TestCase {
+        case_name: String,
+        max_bytes: usize,
+        timeout: Duration,
+        expect_max_size_err: bool,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            case_name: "buffer reached max size".to_string(),
+            max_bytes: 40,
+            timeout: Duration::from_secs(60),
+            expect_max_size_err: true,
+        },
+        TestCase {
+            case_name: "partial message timed out".to_string(),
+            max_bytes: 1024,
+            timeout: Duration::from_millis(10),
+            expect_max_size_err: false,
+        },
+    ];
+
+    let query = "select id, size from read_files('simple/*.parquet') where size > 10";
+    for test_case in test_cases {
+        println!("test case: {}", test_case.case_name);
+
+        let fragments_buf = MessageFragments::new(test_case.max_bytes, test_case.timeout);
+        let mut fragments = query_fragments(query, 16)?.into_iter();
+
+        fragments_buf.add(fragments.next().unwrap())?;
+        fragments_buf.add(fragments.next().unwrap())?;
+        std::thread::sleep(Duration::from_millis(20));
+
+        let mut received_msg = false;
+        let mut received_max_size_err = false;
+        for fragment in fragments {
+            match fragments_buf.add(fragment) {
+                Ok(ser_msg) => received_msg |= ser_msg.is_some(),
+                Err(err) => {
+                    received_max_size_err = true;
+                    assert!(matches!(
+                        err.downcast_ref::<MessageFragmentsError>(),
+                        Some(MessageFragmentsError::ReassemblyBufferReachedMaxSize(_))
+                    ));
+                }
+            }
+        }
+        assert!(!received_msg);
+        assert_eq!(received_max_size_err, test_case.expect_max_size_err);
+
+        // the dropped fragments are no longer buffered
+        if test_case.expect_max_size_err {
+            assert!(fragments_buf.num_buffered_bytes() <= test_case.max_bytes);
+        } else {
+            std::thread::sleep(Duration::from_millis(20));
+            let other_fragment = query_fragments(query, 16)?.remove(0);
+            fragments_buf.add(other_fragment)?;
+            assert_eq!(fragments_buf.num_buffered_bytes(), 16);
+        }
+    }
+
+    Ok(())
+}
@@ -1,8 +1,16 @@
+use std::sync::Arc;
+
 use anyhow::Result;
+use arrow::array::{Int64Array, RecordBatch};
+use arrow::datatypes::{DataType, Field, Schema};
 use bytes::{BufMut, BytesMut};
 
 use super::messages;
-use super::messages::message::{Message, SerializedMessage};
+use super::messages::message::{
+    negotiate_header_version, Message, SerializedMessage, SerializedMessageError, HEADER_VERSION,
+    MAX_FRAGMENT_DATA_BYTES,
+};
+use super::MessageRegistry;
 
 #[test]
 fn test_serialize_and_parse() -> Result<()> {
@@ -28,3 +36,236 @@ fn test_serialize_and_parse() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_large_messages_are_compressed() -> Result<()> {
+    let query = format!(
+        "select {} from read_files('a')",
+        vec!["id"; 20_000].join(", ")
+    );
+    let msg = Message::new(Box::new(messages::query::RunQuery::new(query.clone())));
+    let uncompressed_data = serde_json::to_vec(&messages::query::RunQuery::new(query))?;
+
+    let msg_data = msg.to_bytes()?;
+    assert!(msg_data.len() < uncompressed_data.len());
+
+    // the parsed message holds the uncompressed data
+    let mut buf = BytesMut::new();
+    buf.put(&msg_data[..]);
+    let parsed_ser_msg = SerializedMessage::parse(&mut buf)?;
+    assert!(buf.is_empty());
+    assert_eq!(parsed_ser_msg.msg_data, uncompressed_data);
+
+    // serializing the parsed message again uses the uncompressed lengths
+    let mut buf = BytesMut::new();
+    buf.put(&parsed_ser_msg.to_bytes()[..]);
+    let reparsed_ser_msg = SerializedMessage::parse(&mut buf)?;
+    assert_eq!(reparsed_ser_msg, parsed_ser_msg);
+
+    // a message missing its last byte is incomplete
+    let mut buf = BytesMut::new();
+    buf.put(&msg_data[..msg_data.len() - 1]);
+    assert!(matches!(
+        SerializedMessage::parse(&mut buf),
+        Err(SerializedMessageError::Incomplete)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_unsupported_header_versions_are_rejected() -> Result<()> {
+    let msg = Message::new(Box::new(messages::common::Ping::Ping));
+    let msg_data = msg.to_bytes()?;
+
+    // the version follows the header and data lengths
+    let mut unsupported_msg_data = msg_data.clone();
+    unsupported_msg_data[12..14].copy_from_slice(&(HEADER_VERSION + 1).to_be_bytes());
+
+    let mut buf = BytesMut::new();
+    buf.put(&unsupported_msg_data[..]);
+    buf.put(&msg_data[..]);
+
+    // the unsupported message is skipped and the next message is parsed
+    assert!(matches!(
+        SerializedMessage::parse(&mut buf),
+        Err(SerializedMessageError::UnsupportedVersion(version)) if version == HEADER_VERSION + 1
+    ));
+    assert_eq!(
+        SerializedMessage::parse(&mut buf)?,
+        SerializedMessage::new(&msg)?
+    );
+    assert!(buf.is_empty());
+
+    assert_eq!(
+        negotiate_header_version(HEADER_VERSION + 1)?,
+        HEADER_VERSION
+    );
+    assert_eq!(negotiate_header_version(HEADER_VERSION)?, HEADER_VERSION);
+    assert!(matches!(
+        negotiate_header_version(0),
+        Err(SerializedMessageError::UnsupportedVersion(0))
+    ));
+    // a peer which can't read the written header version is rejected
+    assert!(matches!(
+        negotiate_header_version(1),
+        Err(SerializedMessageError::UnsupportedVersion(1))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_large_payloads_are_sent_in_fragments() -> Result<()> {
+    // pseudo random text so the payload stays large after compression
+    let mut seed: u64 = 42;
+    let query: String = (0..30 * 1024 * 1024)
+        .map(|_| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            (b'a' + ((seed >> 33) % 26) as u8) as char
+        })
+        .collect();
+    let msg = Message::new(Box::new(messages::query::RunQuery::new(query.clone())));
+    let msg_data = msg.to_bytes()?;
+
+    let msg_reg = MessageRegistry::new();
+    let mut buf = BytesMut::new();
+    buf.put(&msg_data[..]);
+
+    let mut num_fragments = 0;
+    let parsed_msg = loop {
+        num_fragments += 1;
+        if let Some(parsed_msg) = msg_reg.build_msg(&mut buf)? {
+            break parsed_msg;
+        }
+        // fragments are never larger than a single frame
+        assert!(msg_data.len() - buf.len() <= num_fragments * SerializedMessage::max_frame_len());
+    };
+    assert!(buf.is_empty());
+    assert!(num_fragments > msg_data.len() / MAX_FRAGMENT_DATA_BYTES);
+    assert_eq!(msg_reg.fragments().num_buffered_bytes(), 0);
+
+    assert_eq!(parsed_msg.msg_id, msg.msg_id);
+    let run_query: &messages::query::RunQuery = msg_reg.try_cast_msg(&parsed_msg)?;
+    assert_eq!(run_query.query, query);
+
+    Ok(())
+}
+
+#[test]
+fn test_streamed_query_results_are_parsed_in_order() -> Result<()> {
+    let schema = Schema::new(vec![Field::new("id", DataType::Int64, false)]);
+    let record = Arc::new(RecordBatch::try_new(
+        Arc::new(schema),
+        vec![Arc::new(Int64Array::from(vec![1, 2, 3]))],
+    )?);
+
+    // the records and the end of the results can arrive in the same read
+    let mut buf = BytesMut::new();
+    let record_msg = Message::new(Box::new(messages::query::QueryResults::Record {
+        record: record.clone(),
+    }));
+    let end_msg = Message::new(Box::new(messages::query::QueryResults::End));
+    buf.put(&record_msg.to_bytes()?[..]);
+    buf.put(&end_msg.to_bytes()?[..]);
+
+    let msg_reg = MessageRegistry::new();
+    let parsed_msg = msg_reg
+        .build_msg(&mut buf)?
+        .expect("expected the record message");
+    let results: messages::query::QueryResults = msg_reg.try_cast_msg_owned(parsed_msg)?;
+    assert_eq!(results, messages::query::QueryResults::Record { record });
+
+    let parsed_msg = msg_reg
+        .build_msg(&mut buf)?
+        .expect("expected the end message");
+    let results: messages::query::QueryResults = msg_reg.try_cast_msg_owned(parsed_msg)?;
+    assert_eq!(results, messages::query::QueryResults::End);
+    assert!(buf.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_generic_error_responses_keep_their_error_code() -> Result<()> {
+    let msg = Message::new(Box::new(messages::common::GenericResponse::Err {
+        code: messages::common::ErrorCode::NotFound,
+        message: "operator instance 10 not found".to_string(),
+    }));
+    let mut buf = BytesMut::new();
+    buf.put(&msg.to_bytes()?[..]);
+
+    let msg_reg = MessageRegistry::new();
+    let parsed_msg = msg_reg
+        .build_msg(&mut buf)?
+        .expect("expected the response message");
+    let resp: &messages::common::GenericResponse = msg_reg.try_cast_msg(&parsed_msg)?;
+    match resp {
+        messages::common::GenericResponse::Err { code, message } => {
+            assert_eq!(*code, messages::common::ErrorCode::NotFound);
+            assert_eq!(message, "operator instance 10 not found");
+        }
+        messages::common::GenericResponse::Ok => panic!("expected an error response"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_replies_keep_the_msg_id_they_reply_to() -> Result<()> {
+    let msg_reg = MessageRegistry::new();
+
+    let req = Message::new(Box::new(messages::common::Ping::Ping));
+    let mut buf = BytesMut::new();
+    buf.put(&req.to_bytes()?[..]);
+    let parsed_req = msg_reg
+        .build_msg(&mut buf)?
+        .expect("expected the request message");
+    assert_eq!(parsed_req.in_reply_to, None);
+
+    let resp = parsed_req.reply(Box::new(messages::common::Ping::Pong));
+    let mut buf = BytesMut::new();
+    buf.put(&resp.to_bytes()?[..]);
+    let parsed_resp = msg_reg
+        .build_msg(&mut buf)?
+        .expect("expected the response message");
+    assert_eq!(parsed_resp.in_reply_to, Some(req.msg_id));
+    assert_eq!(parsed_resp.request_id, req.request_id);
+
+    Ok(())
+}
+
+#[test]
+fn test_replies_route_back_to_the_sender() -> Result<()> {
+    let mut req = Message::new(Box::new(messages::common::Ping::Ping))
+        .set_sent_from_worker_id(1)
+        .set_sent_from_query_id(2)
+        .set_sent_from_operation_id(3)
+        .set_route_to_worker_id(4)
+        .set_route_to_operation_id(5);
+    req.set_sent_from_connection_id(6);
+
+    let resp = req.reply(Box::new(messages::common::Ping::Pong));
+    assert_eq!(resp.in_reply_to, Some(req.msg_id));
+    assert_eq!(resp.request_id, req.request_id);
+    assert_eq!(resp.route_to_worker_id, Some(1));
+    assert_eq!(resp.route_to_operation_id, Some(3));
+    assert_eq!(resp.route_to_connection_id, Some(6));
+    assert_eq!(resp.sent_from_worker_id, Some(4));
+    assert_eq!(resp.sent_from_operation_id, Some(5));
+    assert_eq!(resp.sent_from_connection_id, None);
+    assert_eq!(resp.sent_from_query_id, None);
+
+    // ids missing from the request are missing from the reply
+    let req = Message::new(Box::new(messages::common::Ping::Ping))
+        .set_route_to_operation_id(7)
+        .set_route_to_connection_id(8);
+    let resp = req.reply(Box::new(messages::common::Ping::Pong));
+    assert_eq!(resp.route_to_worker_id, None);
+    assert_eq!(resp.route_to_operation_id, None);
+    assert_eq!(resp.route_to_connection_id, None);
+    assert_eq!(resp.sent_from_operation_id, Some(7));
+    assert_eq!(resp.sent_from_connection_id, Some(8));
+
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use core::fmt;
+use std::sync::Arc;
+
+use anyhow::Result;
+use rustls_pki_types::pem::PemObject;
+use rustls_pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use thiserror::Error;
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::{self, server::WebPkiClientVerifier, RootCertStore};
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+use super::connection::ConnectionStream;
+
+#[derive(Debug, Error)]
+pub enum TlsError {
+    #[error("no certificates found in file: {0}")]
+    NoCertificatesFound(String),
+    #[error("invalid server name in address: {0}")]
+    InvalidServerName(String),
+}
+
+// Paths to the pem encoded certificate chain and private key of this
+// worker and the certificate authority used to verify other workers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TlsConfig {
+    pub cert_path: String,
+    pub key_path: String,
+    pub ca_path: String,
+}
+
+// Both sides of a connection present their certificate and verify the
+// other side's certificate against the certificate authority.
+#[derive(Clone)]
+pub struct Tls {
+    acceptor: TlsAcceptor,
+    connector: TlsConnector,
+}
+
+impl fmt::Debug for Tls {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tls").finish_non_exhaustive()
+    }
+}
+
+impl Tls {
+    pub fn new(config: &TlsConfig) -> Result<Tls> {
+        let certs = Self::read_certs(&config.cert_path)?;
+        let key = PrivateKeyDer::from_pem_file(&config.key_path)?;
+
+        let mut roots = RootCertStore::empty();
+        for ca_cert in Self::read_certs(&config.ca_path)? {
+            roots.add(ca_cert)?;
+        }
+        let roots = Arc::new(roots);
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let client_verifier =
+            WebPkiClientVerifier::builder_with_provider(roots.clone(), provider.clone()).build()?;
+        let server_config = rustls::ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?
+            .with_client_cert_verifier(client_verifier)
+            .with_single_cert(certs.clone(), key.clone_key())?;
+        let client_config = rustls::ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots)
+            .with_client_auth_cert(certs, key)?;
+
+        Ok(Tls {
+            acceptor: TlsAcceptor::from(Arc::new(server_config)),
+            connector: TlsConnector::from(Arc::new(client_config)),
+        })
+    }
+
+    pub async fn accept(&self, stream: TcpStream) -> Result<Box<dyn ConnectionStream>> {
+        Ok(Box::new(self.acceptor.accept(stream).await?))
+    }
+
+    // The server certificate must be valid for the host of the address.
+    pub async fn connect(
+        &self,
+        address: &str,
+        stream: TcpStream,
+    ) -> Result<Box<dyn ConnectionStream>> {
+        let host = match address.rsplit_once(':') {
+            Some((host, _)) => host,
+            None => address,
+        };
+        let server_name = match ServerName::try_from(host.to_string()) {
+            Ok(server_name) => server_name,
+            Err(_) => return Err(TlsError::InvalidServerName(address.to_string()).into()),
+        };
+        Ok(Box::new(self.connector.connect(server_name, stream).await?))
+    }
+
+    fn read_certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
+        let certs = CertificateDer::pem_file_iter(path)?.collect::<Result<Vec<_>, _>>()?;
+        if certs.is_empty() {
+            return Err(TlsError::NoCertificatesFound(path.to_string()).into());
+        }
+        Ok(certs)
+    }
+}
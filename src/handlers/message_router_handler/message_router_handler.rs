@@ -1,4 +1,4 @@
-use std::{sync::Arc, u128};
+use std::{collections::HashSet, sync::Arc, u128};
 
 use anyhow::Result;
 use thiserror::Error;
@@ -8,10 +8,12 @@ use tokio::sync::{
 };
 use tokio_util::sync::CancellationToken;
 use tokio_util::task::TaskTracker;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use crate::handlers::message_handler::messages;
-use crate::handlers::message_handler::messages::message::{Message, MessageName};
+use crate::handlers::message_handler::messages::message::{
+    negotiate_header_version, Message, MessageName, HEADER_VERSION,
+};
 use crate::handlers::message_handler::{MessageRegistry, Pipe};
 
 use super::message_subscriber::{ExternalSubscriber, InternalSubscriber, Subscriber};
@@ -27,6 +29,8 @@ pub struct MessageRouterState {
     external_subscribers: Vec<ExternalSubscriber>,
     internal_subscribers: Vec<InternalSubscriber>,
     internal_sub_sender: Sender<Message>,
+    // inbound streams which identified with the auth token
+    authenticated_inbound_streams: HashSet<u128>,
 }
 
 impl MessageRouterState {
@@ -35,6 +39,7 @@ impl MessageRouterState {
             external_subscribers: Vec::new(),
             internal_subscribers: Vec::new(),
             internal_sub_sender,
+            authenticated_inbound_streams: HashSet::new(),
         }
     }
 
@@ -58,12 +63,39 @@ impl MessageRouterState {
         }
     }
 
+    // A worker which reconnects replaces the subscriber for its old stream.
     pub fn add_external_subscriber(&mut self, sub: ExternalSubscriber) -> Result<()> {
-        self.external_subscribers.retain(|item| *item != sub);
+        self.external_subscribers.retain(|item| match (item, &sub) {
+            (
+                ExternalSubscriber::OutboundWorker { worker_id, .. },
+                ExternalSubscriber::OutboundWorker {
+                    worker_id: new_worker_id,
+                    ..
+                },
+            ) => worker_id != new_worker_id,
+            _ => *item != sub,
+        });
         self.external_subscribers.push(sub);
         Ok(())
     }
 
+    pub fn has_inbound_client_connection(&self, stream_id: &u128) -> bool {
+        self.external_subscribers.iter().any(|item| match item {
+            ExternalSubscriber::InboundClientConnection {
+                inbound_stream_id, ..
+            } => inbound_stream_id == stream_id,
+            _ => false,
+        })
+    }
+
+    pub fn authenticate_inbound_stream(&mut self, stream_id: u128) {
+        self.authenticated_inbound_streams.insert(stream_id);
+    }
+
+    pub fn is_inbound_stream_authenticated(&self, stream_id: &u128) -> bool {
+        self.authenticated_inbound_streams.contains(stream_id)
+    }
+
     pub fn get_all_outbound_streams(&self) -> Vec<u128> {
         let mut outbound_stream_ids = Vec::new();
         for sub in &self.external_subscribers {
@@ -107,6 +139,8 @@ pub struct MessageRouterHandler {
     worker_id: u128,
     msg_reg: Arc<MessageRegistry>,
     task_tracker: TaskTracker,
+    slow_handler_budget: chrono::Duration,
+    auth_token: Option<String>,
 
     connection_pipe: Pipe,
     internal_sub_receiver: Receiver<Message>,
@@ -123,6 +157,8 @@ impl MessageRouterHandler {
         let handler = MessageRouterHandler {
             worker_id,
             task_tracker: TaskTracker::new(),
+            slow_handler_budget: chrono::Duration::milliseconds(250),
+            auth_token: None,
             connection_pipe,
             internal_sub_receiver: receiver,
             state: state.clone(),
@@ -131,6 +167,19 @@ impl MessageRouterHandler {
         (handler, state)
     }
 
+    pub fn set_slow_handler_budget(&mut self, budget: chrono::Duration) -> &Self {
+        self.slow_handler_budget = budget;
+        self
+    }
+
+    // When set, workers and client connections must provide the same
+    // token in their identification message before any other message
+    // from their inbound stream is routed.
+    pub fn set_auth_token(&mut self, token: Option<String>) -> &Self {
+        self.auth_token = token;
+        self
+    }
+
     pub async fn async_main(&mut self, ct: CancellationToken) -> Result<()> {
         loop {
             tokio::select! {
@@ -237,17 +286,68 @@ impl MessageRouterHandler {
     async fn identify_external_subscriber(&mut self, msg: &Message) -> Result<bool> {
         let identify_msg: &messages::common::Identify = self.msg_reg.cast_msg(msg);
         match identify_msg {
-            messages::common::Identify::Worker { id } => {
+            messages::common::Identify::Worker {
+                id,
+                token,
+                max_header_version,
+            } => {
                 if let Some(inbound_stream_id) = msg.inbound_stream_id {
+                    if let Some(auth_token) = &self.auth_token {
+                        if token.as_ref() != Some(auth_token) {
+                            warn!(
+                                worker_id = id,
+                                "rejected worker connection with an invalid auth token"
+                            );
+                            let rejected =
+                                Message::new(Box::new(messages::common::Identify::Rejected {
+                                    reason: "invalid auth token".to_string(),
+                                }))
+                                .set_sent_from_worker_id(self.worker_id)
+                                .set_route_to_worker_id(*id)
+                                .set_inbound_stream_id(inbound_stream_id);
+                            self.connection_pipe.send(rejected).await?;
+                            return Ok(true);
+                        }
+                    }
+                    let header_version = match negotiate_header_version(*max_header_version) {
+                        Ok(version) => version,
+                        Err(err) => {
+                            warn!(worker_id = id, "rejected worker connection: {}", err);
+                            let rejected =
+                                Message::new(Box::new(messages::common::Identify::Rejected {
+                                    reason: err.to_string(),
+                                }))
+                                .set_sent_from_worker_id(self.worker_id)
+                                .set_route_to_worker_id(*id)
+                                .set_inbound_stream_id(inbound_stream_id);
+                            self.connection_pipe.send(rejected).await?;
+                            return Ok(true);
+                        }
+                    };
+                    debug!(
+                        worker_id = id,
+                        header_version, "negotiated header version with worker"
+                    );
+                    self.state
+                        .lock()
+                        .await
+                        .authenticate_inbound_stream(inbound_stream_id);
+
                     let identify_back =
                         Message::new(Box::new(messages::common::Identify::Worker {
                             id: self.worker_id.clone(),
+                            token: None,
+                            max_header_version: HEADER_VERSION,
                         }))
                         .set_sent_from_worker_id(self.worker_id.clone())
                         .set_route_to_worker_id(id.clone())
                         .set_inbound_stream_id(inbound_stream_id);
                     self.connection_pipe.send(identify_back).await?;
                 } else if let Some(outbound_stream_id) = msg.outbound_stream_id {
+                    if let Err(err) = negotiate_header_version(*max_header_version) {
+                        warn!(worker_id = id, "unable to connect to worker: {}", err);
+                        return Ok(true);
+                    }
                     let worker_id = id.clone();
                     let sub = ExternalSubscriber::OutboundWorker {
                         worker_id: worker_id.clone(),
@@ -262,17 +362,56 @@ impl MessageRouterHandler {
                     return Ok(false);
                 }
             }
-            messages::common::Identify::Connection { id } => {
+            messages::common::Identify::Connection {
+                id,
+                token,
+                max_header_version,
+            } => {
                 if let Some(inbound_stream_id) = msg.inbound_stream_id {
+                    if let Err(err) = negotiate_header_version(*max_header_version) {
+                        warn!(connection_id = id, "rejected client connection: {}", err);
+                        let rejected =
+                            Message::new(Box::new(messages::common::Identify::Rejected {
+                                reason: err.to_string(),
+                            }))
+                            .set_sent_from_worker_id(self.worker_id)
+                            .set_route_to_connection_id(*id)
+                            .set_inbound_stream_id(inbound_stream_id);
+                        self.connection_pipe.send(rejected).await?;
+                        return Ok(true);
+                    }
+                    if let Some(auth_token) = &self.auth_token {
+                        if token.as_ref() != Some(auth_token) {
+                            warn!(
+                                connection_id = id,
+                                "rejected client connection with an invalid auth token"
+                            );
+                            let rejected =
+                                Message::new(Box::new(messages::common::Identify::Rejected {
+                                    reason: "invalid auth token".to_string(),
+                                }))
+                                .set_sent_from_worker_id(self.worker_id)
+                                .set_route_to_connection_id(*id)
+                                .set_inbound_stream_id(inbound_stream_id);
+                            self.connection_pipe.send(rejected).await?;
+                            return Ok(true);
+                        }
+                    }
+
                     let sub = ExternalSubscriber::InboundClientConnection {
                         connection_id: id.clone(),
                         inbound_stream_id,
                     };
-                    self.state.lock().await.add_external_subscriber(sub)?;
+                    let mut state = self.state.lock().await;
+                    state.add_external_subscriber(sub)?;
+                    state.authenticate_inbound_stream(inbound_stream_id);
+                    drop(state);
 
                     let identify_back =
                         Message::new(Box::new(messages::common::Identify::Worker {
                             id: self.worker_id.clone(),
+                            token: None,
+                            max_header_version: HEADER_VERSION,
                         }))
                         .set_sent_from_worker_id(self.worker_id.clone())
                         .set_route_to_connection_id(id.clone())
@@ -282,11 +421,32 @@ impl MessageRouterHandler {
                     return Ok(false);
                 }
             }
+            messages::common::Identify::Rejected { .. } => {
+                return Ok(false);
+            }
         }
 
         Ok(true)
     }
 
+    // Messages from inbound streams are only routed once the stream
+    // identified with the auth token. The internal messages and the
+    // messages from streams this worker opened don't have an inbound
+    // stream id.
+    async fn is_authenticated(&self, msg: &Message) -> bool {
+        if self.auth_token.is_none() {
+            return true;
+        }
+        match msg.inbound_stream_id {
+            Some(inbound_stream_id) => self
+                .state
+                .lock()
+                .await
+                .is_inbound_stream_authenticated(&inbound_stream_id),
+            None => true,
+        }
+    }
+
     async fn route_to_internal_subscriber(&mut self, msg: &Message) -> Result<bool> {
         if let Some(route_to_worker_id) = msg.route_to_worker_id {
             if route_to_worker_id != self.worker_id {
@@ -317,9 +477,33 @@ impl MessageRouterHandler {
     }
 
     async fn route_msg(&mut self, msg: &Message) -> Result<bool> {
-        match msg.msg.msg_name() {
+        // tokio's clock so tests can pause time while a route is blocked
+        let start = tokio::time::Instant::now();
+        let res = match msg.msg.msg_name() {
             MessageName::Identify => self.identify_external_subscriber(msg).await,
+            _ if !self.is_authenticated(msg).await => {
+                warn!(
+                    msg_name = msg.msg.msg_name().to_string(),
+                    "dropped message from an unauthenticated connection",
+                );
+                Ok(false)
+            }
             _ => self.route_to_internal_subscriber(msg).await,
+        };
+        let elapsed = start.elapsed();
+
+        let metrics = self.msg_reg.metrics();
+        metrics.record_route_duration(elapsed);
+        if elapsed > self.slow_handler_budget.to_std()? {
+            metrics.record_slow_handler();
+            warn!(
+                msg_name = msg.msg.msg_name().to_string(),
+                elapsed_ms = elapsed.as_millis(),
+                budget_ms = self.slow_handler_budget.num_milliseconds(),
+                "slow message handler",
+            );
         }
+
+        res
     }
 }
@@ -1,5 +1,7 @@
 mod message_router_handler;
 mod message_subscriber;
+#[cfg(test)]
+mod test_message_router_handler;
 
 pub use message_router_handler::{MessageRouterHandler, MessageRouterState};
 pub use message_subscriber::{MessageConsumer, MessageReceiver, Subscriber};
@@ -0,0 +1,311 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use crate::handlers::message_handler::messages;
+use crate::handlers::message_handler::messages::message::{Message, MessageName, HEADER_VERSION};
+use crate::handlers::message_handler::{MessageRegistry, Pipe};
+
+use super::{MessageConsumer, MessageReceiver, MessageRouterHandler, Subscriber};
+
+#[derive(Debug)]
+struct PingSubscriber {
+    sender: mpsc::Sender<Message>,
+}
+
+impl Subscriber for PingSubscriber {}
+
+impl MessageConsumer for PingSubscriber {
+    fn consumes_message(&self, msg: &Message) -> bool {
+        msg.msg.msg_name() == MessageName::Ping
+    }
+}
+
+impl MessageReceiver for PingSubscriber {
+    fn sender(&self) -> mpsc::Sender<Message> {
+        self.sender.clone()
+    }
+}
+
+// Time is paused so the router is only blocked for the time the test
+// advances the clock, not for however long the runtime takes to
+// schedule the tasks.
+#[tokio::test(start_paused = true)]
+async fn test_slow_subscriber_triggers_slow_handler_warning() -> Result<()> {
+    let worker_id = 1;
+    let msg_reg = Arc::new(MessageRegistry::new());
+    let (router_pipe, connection_pipe) = Pipe::new(10);
+
+    let (mut router, router_state) =
+        MessageRouterHandler::new(worker_id, router_pipe, msg_reg.clone());
+    router.set_slow_handler_budget(chrono::Duration::milliseconds(10));
+
+    // the subscriber only buffers a single message and is slow to
+    // drain it so the router blocks while routing the next message
+    let (sender, mut receiver) = mpsc::channel(1);
+    router_state
+        .lock()
+        .await
+        .add_internal_subscriber(Box::new(PingSubscriber { sender }), 2);
+
+    let ct = CancellationToken::new();
+    let router_ct = ct.clone();
+    let router_handle = tokio::spawn(async move { router.async_main(router_ct).await });
+
+    let num_msgs = 3;
+    for _ in 0..num_msgs {
+        let msg =
+            Message::new(Box::new(messages::common::Ping::Ping)).set_route_to_worker_id(worker_id);
+        connection_pipe.send(msg).await?;
+    }
+
+    // the first message fits in the channel and each of the others
+    // blocks the router until the previous one is received
+    for _ in 0..num_msgs {
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let msg = receiver.recv().await;
+        assert!(msg.is_some());
+    }
+
+    // let the router record the last route before it's cancelled
+    tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+    ct.cancel();
+    router_handle.await??;
+
+    let metrics = msg_reg.metrics().snapshot();
+    assert_eq!(metrics.slow_handler_count, num_msgs - 1);
+    assert_eq!(metrics.route_duration_micros.count, num_msgs);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_client_connection_auth_token() -> Result<()> {
+    #[derive(Debug)]
+    struct TestCase {
+        case_name: &'static str,
+        token: Option<String>,
+        max_header_version: u16,
+        expect_accepted: bool,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            case_name: "missing token",
+            token: None,
+            max_header_version: HEADER_VERSION,
+            expect_accepted: false,
+        },
+        TestCase {
+            case_name: "wrong token",
+            token: Some("wrong-secret".to_string()),
+            max_header_version: HEADER_VERSION,
+            expect_accepted: false,
+        },
+        TestCase {
+            case_name: "correct token",
+            token: Some("secret".to_string()),
+            max_header_version: HEADER_VERSION,
+            expect_accepted: true,
+        },
+        TestCase {
+            case_name: "unsupported header version",
+            token: Some("secret".to_string()),
+            max_header_version: 0,
+            expect_accepted: false,
+        },
+        TestCase {
+            case_name: "older header version",
+            token: Some("secret".to_string()),
+            max_header_version: 1,
+            expect_accepted: false,
+        },
+    ];
+
+    for test_case in test_cases {
+        println!("test case: {}", test_case.case_name);
+
+        let worker_id = 1;
+        let connection_id = 2;
+        let inbound_stream_id = 3;
+        let msg_reg = Arc::new(MessageRegistry::new());
+        let (router_pipe, mut connection_pipe) = Pipe::new(10);
+
+        let (mut router, router_state) =
+            MessageRouterHandler::new(worker_id, router_pipe, msg_reg.clone());
+        router.set_auth_token(Some("secret".to_string()));
+
+        let ct = CancellationToken::new();
+        let router_ct = ct.clone();
+        let router_handle = tokio::spawn(async move { router.async_main(router_ct).await });
+
+        let identify = Message::new(Box::new(messages::common::Identify::Connection {
+            id: connection_id,
+            token: test_case.token.clone(),
+            max_header_version: test_case.max_header_version,
+        }))
+        .set_inbound_stream_id(inbound_stream_id);
+        connection_pipe.send(identify).await?;
+
+        let resp = connection_pipe.recv().await.expect("expected a response");
+        assert_eq!(resp.inbound_stream_id, Some(inbound_stream_id));
+        assert_eq!(resp.route_to_connection_id, Some(connection_id));
+        let resp: &messages::common::Identify = msg_reg.try_cast_msg(&resp)?;
+        match resp {
+            messages::common::Identify::Worker {
+                id,
+                max_header_version,
+                ..
+            } => {
+                assert!(test_case.expect_accepted);
+                assert_eq!(*id, worker_id);
+                assert_eq!(*max_header_version, HEADER_VERSION);
+            }
+            messages::common::Identify::Rejected { .. } => {
+                assert!(!test_case.expect_accepted);
+            }
+            _ => panic!("unexpected identify response: {:?}", resp),
+        }
+
+        assert_eq!(
+            router_state
+                .lock()
+                .await
+                .has_inbound_client_connection(&inbound_stream_id),
+            test_case.expect_accepted
+        );
+
+        ct.cancel();
+        router_handle.await??;
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_messages_from_unauthenticated_inbound_streams_are_dropped() -> Result<()> {
+    let worker_id = 1;
+    let inbound_stream_id = 3;
+
+    struct TestCase {
+        case_name: &'static str,
+        identify: Option<messages::common::Identify>,
+        expect_routed: bool,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            case_name: "no identification",
+            identify: None,
+            expect_routed: false,
+        },
+        TestCase {
+            case_name: "worker without a token",
+            identify: Some(messages::common::Identify::Worker {
+                id: 4,
+                token: None,
+                max_header_version: HEADER_VERSION,
+            }),
+            expect_routed: false,
+        },
+        TestCase {
+            case_name: "worker with the wrong token",
+            identify: Some(messages::common::Identify::Worker {
+                id: 4,
+                token: Some("wrong-secret".to_string()),
+                max_header_version: HEADER_VERSION,
+            }),
+            expect_routed: false,
+        },
+        TestCase {
+            case_name: "worker with the token",
+            identify: Some(messages::common::Identify::Worker {
+                id: 4,
+                token: Some("secret".to_string()),
+                max_header_version: HEADER_VERSION,
+            }),
+            expect_routed: true,
+        },
+        TestCase {
+            case_name: "worker with an older header version",
+            identify: Some(messages::common::Identify::Worker {
+                id: 4,
+                token: Some("secret".to_string()),
+                max_header_version: 1,
+            }),
+            expect_routed: false,
+        },
+        TestCase {
+            case_name: "client connection with the token",
+            identify: Some(messages::common::Identify::Connection {
+                id: 5,
+                token: Some("secret".to_string()),
+                max_header_version: HEADER_VERSION,
+            }),
+            expect_routed: true,
+        },
+    ];
+
+    for test_case in test_cases {
+        println!("test case: {}", test_case.case_name);
+
+        let msg_reg = Arc::new(MessageRegistry::new());
+        let (router_pipe, mut connection_pipe) = Pipe::new(10);
+
+        let (mut router, router_state) =
+            MessageRouterHandler::new(worker_id, router_pipe, msg_reg.clone());
+        router.set_auth_token(Some("secret".to_string()));
+
+        let (sender, mut receiver) = mpsc::channel(10);
+        router_state
+            .lock()
+            .await
+            .add_internal_subscriber(Box::new(PingSubscriber { sender }), 2);
+
+        let ct = CancellationToken::new();
+        let router_ct = ct.clone();
+        let router_handle = tokio::spawn(async move { router.async_main(router_ct).await });
+
+        if let Some(identify) = test_case.identify {
+            let identify =
+                Message::new(Box::new(identify)).set_inbound_stream_id(inbound_stream_id);
+            connection_pipe.send(identify).await?;
+            let resp = connection_pipe.recv().await.expect("expected a response");
+            assert_eq!(resp.inbound_stream_id, Some(inbound_stream_id));
+        }
+
+        // the message from the stream doesn't carry a connection id and
+        // is followed by a message from a stream this worker opened
+        let msg = Message::new(Box::new(messages::common::Ping::Ping))
+            .set_route_to_worker_id(worker_id)
+            .set_inbound_stream_id(inbound_stream_id);
+        connection_pipe.send(msg).await?;
+        let msg = Message::new(Box::new(messages::common::Ping::Ping))
+            .set_route_to_worker_id(worker_id)
+            .set_outbound_stream(6);
+        connection_pipe.send(msg).await?;
+
+        let msg = receiver.recv().await.expect("expected a message");
+        if test_case.expect_routed {
+            assert_eq!(msg.inbound_stream_id, Some(inbound_stream_id));
+            let msg = receiver.recv().await.expect("expected a message");
+            assert_eq!(msg.outbound_stream_id, Some(6));
+        } else {
+            assert_eq!(msg.outbound_stream_id, Some(6));
+        }
+        assert_eq!(
+            router_state
+                .lock()
+                .await
+                .is_inbound_stream_authenticated(&inbound_stream_id),
+            test_case.expect_routed
+        );
+
+        ct.cancel();
+        router_handle.await??;
+    }
+
+    Ok(())
+}
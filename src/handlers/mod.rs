@@ -1,4 +1,5 @@
 pub mod message_handler;
 pub mod message_router_handler;
 pub mod operator_handler;
+pub mod query_data_handler;
 pub mod query_handler;
@@ -1,7 +1,11 @@
 use thiserror::Error;
 use tokio_util::sync::CancellationToken;
 
-use super::operator_handler_state::{OperatorInstance, OperatorInstanceConfig, Status};
+use super::operator_handler_state::{
+    OperatorInstance, OperatorInstanceConfig, RecordHeartbeat, Status,
+    DEFAULT_EXCHANGE_MAX_BUFFERED_BYTES, DEFAULT_IDENTIFY_EXCHANGE_MAX_ATTEMPTS,
+};
+use super::operators::NanPolicy;
 use crate::handlers::message_handler::messages;
 
 #[derive(Debug, Error)]
@@ -21,6 +25,7 @@ impl TryFrom<&messages::query::OperatorInstanceAssignment> for OperatorInstance
                 query_id,
                 op_instance_id,
                 pipeline_id,
+                instance_idx,
                 operator,
             } => Ok(OperatorInstance {
                 status: Status::Running,
@@ -29,7 +34,12 @@ impl TryFrom<&messages::query::OperatorInstanceAssignment> for OperatorInstance
                     id: op_instance_id.clone(),
                     query_id: query_id.clone(),
                     pipeline_id: pipeline_id.clone(),
-                    operator: operator.clone(),
+                    instance_idx: *instance_idx,
+                    operator: operator.as_ref().clone(),
+                    record_heartbeat: RecordHeartbeat::default(),
+                    exchange_max_buffered_bytes: DEFAULT_EXCHANGE_MAX_BUFFERED_BYTES,
+                    identify_exchange_max_attempts: DEFAULT_IDENTIFY_EXCHANGE_MAX_ATTEMPTS,
+                    nan_policy: NanPolicy::default(),
                 },
             }),
             _ => Err(TryFromOperatorInstanceError::UnableToConvertMessageToOperatorInstance),
@@ -4,4 +4,7 @@ mod operator_handler_state;
 pub mod operators;
 
 pub use operator_handler::OperatorHandler;
-pub use operator_handler_state::TotalOperatorCompute;
+pub use operator_handler_state::{
+    RecordHeartbeat, TotalOperatorCompute, DEFAULT_EXCHANGE_MAX_BUFFERED_BYTES,
+    DEFAULT_IDENTIFY_EXCHANGE_MAX_ATTEMPTS,
+};
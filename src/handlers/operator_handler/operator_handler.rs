@@ -7,7 +7,10 @@ use tokio_util::sync::CancellationToken;
 use tracing::{error, info};
 use uuid::Uuid;
 
-use super::operator_handler_state::{OperatorHandlerState, OperatorInstance, TotalOperatorCompute};
+use super::operator_handler_state::{
+    OperatorHandlerState, OperatorInstance, RecordHeartbeat, TotalOperatorCompute,
+    DEFAULT_EXCHANGE_MAX_BUFFERED_BYTES, DEFAULT_IDENTIFY_EXCHANGE_MAX_ATTEMPTS,
+};
 use super::operators;
 use crate::handlers::message_handler::messages;
 use crate::handlers::message_handler::messages::message::{Message, MessageName};
@@ -39,6 +42,10 @@ pub struct OperatorHandler {
 
     msg_reg: Arc<MessageRegistry>,
     op_builder: operators::OperatorBuilder,
+    record_heartbeat: RecordHeartbeat,
+    exchange_max_buffered_bytes: usize,
+    identify_exchange_max_attempts: u32,
+    nan_policy: operators::NanPolicy,
 
     tt: tokio_util::task::TaskTracker,
 }
@@ -72,12 +79,36 @@ impl OperatorHandler {
             sender,
             msg_reg,
             op_builder,
+            record_heartbeat: RecordHeartbeat::default(),
+            exchange_max_buffered_bytes: DEFAULT_EXCHANGE_MAX_BUFFERED_BYTES,
+            identify_exchange_max_attempts: DEFAULT_IDENTIFY_EXCHANGE_MAX_ATTEMPTS,
+            nan_policy: operators::NanPolicy::default(),
             tt: tokio_util::task::TaskTracker::new(),
         };
 
         handler
     }
 
+    pub fn set_record_heartbeat(&mut self, record_heartbeat: RecordHeartbeat) -> &Self {
+        self.record_heartbeat = record_heartbeat;
+        self
+    }
+
+    pub fn set_exchange_max_buffered_bytes(&mut self, max_buffered_bytes: usize) -> &Self {
+        self.exchange_max_buffered_bytes = max_buffered_bytes;
+        self
+    }
+
+    pub fn set_identify_exchange_max_attempts(&mut self, max_attempts: u32) -> &Self {
+        self.identify_exchange_max_attempts = max_attempts;
+        self
+    }
+
+    pub fn set_nan_policy(&mut self, nan_policy: operators::NanPolicy) -> &Self {
+        self.nan_policy = nan_policy;
+        self
+    }
+
     pub fn subscriber(&self) -> Box<dyn Subscriber> {
         Box::new(OperatorHandlerSubscriber {
             operator_id: self.operator_id.clone(),
@@ -164,14 +195,21 @@ impl OperatorHandler {
             self.msg_reg.try_cast_msg(&msg)?;
 
         // update the operator state
-        match status_change {
+        let update_res = match status_change {
             messages::operator::OperatorInstanceStatusChange::Complete => {
-                self.state.operator_instance_complete(op_in_id)?;
+                self.state.operator_instance_complete(op_in_id)
             }
-            messages::operator::OperatorInstanceStatusChange::Error(err_msg) => {
-                self.state
-                    .operator_instance_error(op_in_id, err_msg.clone())?;
+            messages::operator::OperatorInstanceStatusChange::Error { error, .. } => {
+                self.state.operator_instance_error(op_in_id, error.clone())
             }
+        };
+        if let Err(err) = update_res {
+            let resp_msg = msg.reply(Box::new(messages::common::GenericResponse::Err {
+                code: messages::common::ErrorCode::NotFound,
+                message: err.to_string(),
+            }));
+            self.router_pipe.send(resp_msg).await?;
+            return Err(err);
         }
 
         // response for the operator instance
@@ -190,11 +228,12 @@ impl OperatorHandler {
                 )
                 .await?;
             }
-            messages::operator::OperatorInstanceStatusChange::Error(err) => {
+            messages::operator::OperatorInstanceStatusChange::Error { error, retryable } => {
                 requests::query::OperatorInstanceStatusChangeRequest::errored_request(
                     op_query_id.clone(),
                     op_in_id.clone(),
-                    err.clone(),
+                    error.clone(),
+                    *retryable,
                     pipe,
                     self.msg_reg.clone(),
                 )
@@ -208,7 +247,11 @@ impl OperatorHandler {
     async fn handle_operator_instance_assignment(&mut self, msg: Message) -> Result<()> {
         let assignment: &messages::query::OperatorInstanceAssignment =
             self.msg_reg.try_cast_msg(&msg)?;
-        let op_in: OperatorInstance = OperatorInstance::try_from(assignment)?;
+        let mut op_in: OperatorInstance = OperatorInstance::try_from(assignment)?;
+        op_in.config.record_heartbeat = self.record_heartbeat;
+        op_in.config.exchange_max_buffered_bytes = self.exchange_max_buffered_bytes;
+        op_in.config.identify_exchange_max_attempts = self.identify_exchange_max_attempts;
+        op_in.config.nan_policy = self.nan_policy;
 
         match self.op_builder.build_operator(&op_in, &self.tt).await {
             Ok(_) => {
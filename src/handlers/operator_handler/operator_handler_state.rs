@@ -1,8 +1,11 @@
+use std::time::Duration;
+
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tokio_util::sync::CancellationToken;
 
+use super::operators::NanPolicy;
 use crate::planner::{self, OperatorCompute};
 
 #[derive(Debug, Error)]
@@ -30,7 +33,39 @@ pub struct OperatorInstanceConfig {
     pub id: u128,
     pub query_id: u128,
     pub pipeline_id: String,
+    // index of this instance among the operator's compute.instances
+    pub instance_idx: usize,
     pub operator: planner::Operator,
+    pub record_heartbeat: RecordHeartbeat,
+    // an exchange spills the records it buffers to the default storage
+    // connection past this many bytes
+    pub exchange_max_buffered_bytes: usize,
+    // times a task asks for the worker and instance of an exchange
+    // before giving up
+    pub identify_exchange_max_attempts: u32,
+    // how NaN float values compare in filters, sorts and min/max
+    pub nan_policy: NanPolicy,
+}
+
+pub const DEFAULT_EXCHANGE_MAX_BUFFERED_BYTES: usize = 256 * 1024 * 1024;
+pub const DEFAULT_IDENTIFY_EXCHANGE_MAX_ATTEMPTS: u32 = 11;
+
+// A task sends a heartbeat to the exchange every interval for each record
+// it's processing. The exchange gives a reserved record to another
+// consumer when it hasn't received a heartbeat within the timeout.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecordHeartbeat {
+    pub interval: Duration,
+    pub timeout: Duration,
+}
+
+impl Default for RecordHeartbeat {
+    fn default() -> Self {
+        RecordHeartbeat {
+            interval: Duration::from_secs(2),
+            timeout: Duration::from_secs(10),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
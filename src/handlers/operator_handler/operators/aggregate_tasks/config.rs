@@ -0,0 +1,7 @@
+use crate::planner::Aggregate;
+
+#[derive(Debug)]
+pub struct AggregateConfig {
+    pub group_by: Vec<sqlparser::ast::Expr>,
+    pub aggregates: Vec<Aggregate>,
+}
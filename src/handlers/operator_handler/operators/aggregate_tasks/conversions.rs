@@ -0,0 +1,35 @@
+use anyhow::Result;
+use thiserror::Error;
+
+use crate::{
+    handlers::operator_handler::operator_handler_state::OperatorInstanceConfig,
+    planner::{OperatorTask, OperatorType},
+};
+
+use super::config::AggregateConfig;
+
+#[derive(Debug, Error)]
+pub enum TryFromAggregateConfigError {
+    #[error("unable to convert")]
+    UnableToConvert,
+}
+
+impl TryFrom<&OperatorInstanceConfig> for AggregateConfig {
+    type Error = TryFromAggregateConfigError;
+
+    fn try_from(op_in_config: &OperatorInstanceConfig) -> Result<AggregateConfig, Self::Error> {
+        match &op_in_config.operator.operator_type {
+            OperatorType::Producer { task, .. } => match task {
+                OperatorTask::Aggregate {
+                    group_by,
+                    aggregates,
+                } => Ok(AggregateConfig {
+                    group_by: group_by.clone(),
+                    aggregates: aggregates.clone(),
+                }),
+                _ => Err(TryFromAggregateConfigError::UnableToConvert),
+            },
+            OperatorType::Exchange { .. } => Err(TryFromAggregateConfigError::UnableToConvert),
+        }
+    }
+}
@@ -0,0 +1,5 @@
+mod aggregate_task;
+mod config;
+mod conversions;
+
+pub use aggregate_task::AggregateTaskBuilder;
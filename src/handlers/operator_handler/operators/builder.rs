@@ -70,11 +70,75 @@ impl OperatorBuilder {
                     )
                     .into())
                 }
+                planner::OperatorTask::HashJoin { .. } => {
+                    match self.build_producer_operator(op_in, tt, task).await {
+                        Ok(_) => {
+                            return Ok(());
+                        }
+                        Err(err) => {
+                            return Err(err.context("failed building hash join producer operator"));
+                        }
+                    }
+                }
                 planner::OperatorTask::Filter { .. } => {
-                    return Err(OperatorBuilderError::NotImplemented(
-                        "filter operator task".to_string(),
-                    )
-                    .into())
+                    match self.build_producer_operator(op_in, tt, task).await {
+                        Ok(_) => {
+                            return Ok(());
+                        }
+                        Err(err) => {
+                            return Err(err.context("failed building filter producer operator"));
+                        }
+                    }
+                }
+                planner::OperatorTask::Aggregate { .. } => {
+                    match self.build_producer_operator(op_in, tt, task).await {
+                        Ok(_) => {
+                            return Ok(());
+                        }
+                        Err(err) => {
+                            return Err(err.context("failed building aggregate producer operator"));
+                        }
+                    }
+                }
+                planner::OperatorTask::Distinct { .. } => {
+                    match self.build_producer_operator(op_in, tt, task).await {
+                        Ok(_) => {
+                            return Ok(());
+                        }
+                        Err(err) => {
+                            return Err(err.context("failed building distinct producer operator"));
+                        }
+                    }
+                }
+                planner::OperatorTask::Sort { .. } => {
+                    match self.build_producer_operator(op_in, tt, task).await {
+                        Ok(_) => {
+                            return Ok(());
+                        }
+                        Err(err) => {
+                            return Err(err.context("failed building sort producer operator"));
+                        }
+                    }
+                }
+                planner::OperatorTask::Limit { .. } => {
+                    match self.build_producer_operator(op_in, tt, task).await {
+                        Ok(_) => {
+                            return Ok(());
+                        }
+                        Err(err) => {
+                            return Err(err.context("failed building limit producer operator"));
+                        }
+                    }
+                }
+                planner::OperatorTask::Union { .. } => {
+                    match self.build_producer_operator(op_in, tt, task).await {
+                        Ok(_) => {
+                            return Ok(());
+                        }
+                        Err(err) => {
+                            return Err(err.context("failed building union producer operator"));
+                        }
+                    }
                 }
                 planner::OperatorTask::MaterializeFiles { data_format, .. } => {
                     match self.build_producer_operator(op_in, tt, task).await {
@@ -95,6 +159,7 @@ impl OperatorBuilder {
                     op_in.config.clone(),
                     self.message_router_state.clone(),
                     self.msg_reg.clone(),
+                    self.conn_reg.get_operator("default")?,
                 )
                 .await?;
                 let ct = op_in.ct.clone();
@@ -1,4 +1,6 @@
+use core::fmt;
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use anyhow::Result;
 use opendal::layers::LoggingLayer;
@@ -9,6 +11,11 @@ use opendal::Operator;
 use opendal::Scheme;
 use thiserror::Error;
 
+use super::read_cache::{ReadCache, ReadCacheLayer, ReadCacheStats};
+
+// Root of the local storage used when no other storage is configured.
+pub const DEFAULT_FS_ROOT: &str = "/tmp/chapterhouse";
+
 #[derive(Debug, Error)]
 pub enum ConnectionRegistryError {
     #[error("connection name not found: {0}")]
@@ -17,42 +24,128 @@ pub enum ConnectionRegistryError {
     NotImplemented(String),
 }
 
-#[derive(Debug)]
+// An S3 compatible bucket. The endpoint can point at another service
+// such as MinIO. Credentials which aren't set are read from the
+// environment, for example AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY.
+#[derive(Clone, PartialEq)]
+pub struct S3Config {
+    pub bucket: String,
+    pub region: Option<String>,
+    pub endpoint: Option<String>,
+    pub access_key: Option<String>,
+    pub secret_key: Option<String>,
+}
+
+impl fmt::Debug for S3Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("S3Config")
+            .field("bucket", &self.bucket)
+            .field("region", &self.region)
+            .field("endpoint", &self.endpoint)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Connection {
     name: String,
     scheme: Scheme,
     config: HashMap<String, String>,
+    // a memory service only holds the files written through its own
+    // operator so every operator of the connection shares this one
+    memory_operator: Option<Operator>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ConnectionRegistry {
     connections: Vec<Connection>,
+    read_cache: Option<Arc<ReadCache>>,
 }
 
 impl ConnectionRegistry {
     pub fn new() -> ConnectionRegistry {
         ConnectionRegistry {
             connections: Vec::new(),
+            read_cache: None,
         }
     }
 
+    // Reads through the operators of every connection are cached. The
+    // cache holds up to the given number of mebibytes.
+    pub fn set_read_cache_mib(&mut self, size_in_mib: usize) -> &Self {
+        self.read_cache = Some(Arc::new(ReadCache::new(size_in_mib * 1024 * 1024)));
+        self
+    }
+
+    pub fn read_cache_stats(&self) -> Option<ReadCacheStats> {
+        self.read_cache
+            .as_ref()
+            .map(|read_cache| read_cache.stats())
+    }
+
+    // Adding a connection with the name of an existing connection
+    // replaces it.
     pub fn add_connection(
         &mut self,
         name: String,
         scheme: Scheme,
         config: HashMap<String, String>,
     ) {
+        self.connections.retain(|item| item.name != name);
+        let memory_operator = match scheme {
+            Scheme::Memory => init_service::<services::Memory>(config.clone()).ok(),
+            _ => None,
+        };
         self.connections.push(Connection {
             name,
             scheme,
             config,
+            memory_operator,
         });
     }
 
+    // Stores the files of the connection in a local directory.
+    pub fn register_fs(&mut self, name: String, root_path: String) {
+        self.add_connection(
+            name,
+            Scheme::Fs,
+            HashMap::from([("root".to_string(), root_path)]),
+        );
+    }
+
+    // Stores the files of the connection in memory. Clones of the registry
+    // share the files so in-process workers can share the storage.
+    pub fn register_memory(&mut self, name: String) {
+        self.add_connection(name, Scheme::Memory, HashMap::new());
+    }
+
+    pub fn register_s3(&mut self, name: String, s3_config: S3Config) {
+        let mut config = HashMap::from([("bucket".to_string(), s3_config.bucket)]);
+        let optional_values = [
+            ("region", s3_config.region),
+            ("endpoint", s3_config.endpoint),
+            ("access_key_id", s3_config.access_key),
+            ("secret_access_key", s3_config.secret_key),
+        ];
+        for (key, value) in optional_values {
+            if let Some(value) = value {
+                config.insert(key.to_string(), value);
+            }
+        }
+        self.add_connection(name, Scheme::S3, config);
+    }
+
     pub fn find_connection(&self, name: &str) -> Option<&Connection> {
         self.connections.iter().find(|item| item.name == name)
     }
 
+    pub fn check_connection(&self, name: &str) -> Result<()> {
+        match self.find_connection(name) {
+            Some(_) => Ok(()),
+            None => Err(ConnectionRegistryError::ConnectionNameNotFound(name.to_string()).into()),
+        }
+    }
+
     pub fn get_operator(&self, name: &str) -> Result<Operator> {
         let conn = if let Some(conn) = self.find_connection(name) {
             conn
@@ -60,14 +153,27 @@ impl ConnectionRegistry {
             return Err(ConnectionRegistryError::ConnectionNameNotFound(name.to_string()).into());
         };
 
-        match conn.scheme {
-            Scheme::S3 => Ok(init_service::<services::S3>(conn.config.clone())?),
-            Scheme::Fs => Ok(init_service::<services::Fs>(conn.config.clone())?),
-            val => Err(ConnectionRegistryError::NotImplemented(format!(
-                "opendal schema type {} ",
-                val.to_string()
-            ))
-            .into()),
+        let op = match conn.scheme {
+            Scheme::S3 => init_service::<services::S3>(conn.config.clone())?,
+            Scheme::Fs => init_service::<services::Fs>(conn.config.clone())?,
+            Scheme::Memory => match &conn.memory_operator {
+                Some(op) => op.clone(),
+                None => init_service::<services::Memory>(conn.config.clone())?,
+            },
+            val => {
+                return Err(ConnectionRegistryError::NotImplemented(format!(
+                    "opendal schema type {} ",
+                    val.to_string()
+                ))
+                .into())
+            }
+        };
+
+        match &self.read_cache {
+            Some(read_cache) => {
+                Ok(op.layer(ReadCacheLayer::new(read_cache.clone(), conn.name.clone())))
+            }
+            None => Ok(op),
         }
     }
 }
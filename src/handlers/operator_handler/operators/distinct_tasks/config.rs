@@ -0,0 +1,4 @@
+#[derive(Debug)]
+pub struct DistinctConfig {
+    pub keys: Vec<sqlparser::ast::Expr>,
+}
@@ -0,0 +1,29 @@
+use anyhow::Result;
+use thiserror::Error;
+
+use crate::{
+    handlers::operator_handler::operator_handler_state::OperatorInstanceConfig,
+    planner::{OperatorTask, OperatorType},
+};
+
+use super::config::DistinctConfig;
+
+#[derive(Debug, Error)]
+pub enum TryFromDistinctConfigError {
+    #[error("unable to convert")]
+    UnableToConvert,
+}
+
+impl TryFrom<&OperatorInstanceConfig> for DistinctConfig {
+    type Error = TryFromDistinctConfigError;
+
+    fn try_from(op_in_config: &OperatorInstanceConfig) -> Result<DistinctConfig, Self::Error> {
+        match &op_in_config.operator.operator_type {
+            OperatorType::Producer { task, .. } => match task {
+                OperatorTask::Distinct { keys } => Ok(DistinctConfig { keys: keys.clone() }),
+                _ => Err(TryFromDistinctConfigError::UnableToConvert),
+            },
+            OperatorType::Exchange { .. } => Err(TryFromDistinctConfigError::UnableToConvert),
+        }
+    }
+}
@@ -0,0 +1,5 @@
+mod config;
+mod conversions;
+mod distinct_task;
+
+pub use distinct_task::DistinctTaskBuilder;
@@ -16,6 +16,10 @@ use crate::handlers::message_router_handler::{
 };
 use crate::handlers::operator_handler::operator_handler_state::OperatorInstanceConfig;
 use crate::handlers::operator_handler::operators::common_message_handlers::handle_ping_message;
+use crate::handlers::operator_handler::operators::record_spiller::{
+    exchange_spill_dir, RecordSpiller,
+};
+use crate::handlers::operator_handler::operators::record_utils;
 use crate::planner;
 
 #[derive(Debug, Error)]
@@ -43,6 +47,7 @@ pub struct ExchangeOperator {
     record_pool: RecordPool,
     inbound_producer_operator_states: Vec<ProducerOperatorStatus>,
     received_all_data_from_producers: bool,
+    spiller: RecordSpiller,
 
     operator_instance_config: OperatorInstanceConfig,
     message_router_state: Arc<Mutex<MessageRouterState>>,
@@ -56,19 +61,27 @@ impl ExchangeOperator {
         op_in_config: OperatorInstanceConfig,
         message_router_state: Arc<Mutex<MessageRouterState>>,
         msg_reg: Arc<MessageRegistry>,
+        storage_conn: opendal::Operator,
     ) -> Result<ExchangeOperator> {
         let router_sender = message_router_state.lock().await.sender();
         let (mut pipe, sender) = Pipe::new_with_existing_sender(router_sender, 10);
         pipe.set_sent_from_query_id(op_in_config.query_id.clone());
         pipe.set_sent_from_operation_id(op_in_config.id.clone());
 
-        let (outbound_producer_ids, inbound_producer_ids) =
+        let (outbound_producer_ids, inbound_producer_ids, ordered, distribution) =
             match &op_in_config.operator.operator_type {
                 planner::OperatorType::Exchange {
                     outbound_producer_ids,
                     inbound_producer_ids,
+                    ordered,
+                    distribution,
                     ..
-                } => (outbound_producer_ids.clone(), inbound_producer_ids.clone()),
+                } => (
+                    outbound_producer_ids.clone(),
+                    inbound_producer_ids.clone(),
+                    *ordered,
+                    distribution.clone(),
+                ),
                 planner::OperatorType::Producer { .. } => {
                     return Err(ExchangeOperatorError::InvalidOperatorType(
                         op_in_config.operator.operator_type.name().to_string(),
@@ -80,7 +93,11 @@ impl ExchangeOperator {
         let record_pool = RecordPool::new(
             outbound_producer_ids,
             RecordPoolConfig {
-                max_heartbeat_interval: chrono::Duration::seconds(10),
+                max_heartbeat_interval: chrono::Duration::from_std(
+                    op_in_config.record_heartbeat.timeout,
+                )?,
+                ordered,
+                distribution,
             },
         );
 
@@ -92,10 +109,16 @@ impl ExchangeOperator {
             })
             .collect();
 
+        let spiller = RecordSpiller::new(
+            storage_conn,
+            exchange_spill_dir(op_in_config.query_id, op_in_config.id),
+        );
+
         Ok(ExchangeOperator {
             record_pool,
             inbound_producer_operator_states: operator_statuses,
             received_all_data_from_producers: false,
+            spiller,
             operator_instance_config: op_in_config,
             message_router_state,
             router_pipe: pipe,
@@ -130,7 +153,11 @@ impl ExchangeOperator {
             "started exchange operator instance",
         );
 
-        self.inner_async_main(ct).await?;
+        let res = self.inner_async_main(ct).await;
+        if let Err(err) = self.spiller.cleanup().await {
+            error!("failed to remove the exchange spill files: {}", err);
+        }
+        res?;
 
         self.message_router_state
             .lock()
@@ -152,6 +179,10 @@ impl ExchangeOperator {
     }
 
     async fn inner_async_main(&mut self, ct: CancellationToken) -> Result<()> {
+        // reserved records are given to another consumer after their
+        // heartbeat goes stale
+        let mut maintain_ticker =
+            tokio::time::interval(self.operator_instance_config.record_heartbeat.interval);
         loop {
             tokio::select! {
                 Some(msg) = self.router_pipe.recv() => {
@@ -174,6 +205,9 @@ impl ExchangeOperator {
                         debug!("message ignored: {}", msg);
                     }
                 }
+                _ = maintain_ticker.tick() => {
+                    self.record_pool.maintain()?;
+                }
                 _ = ct.cancelled() => {
                     break;
                 }
@@ -190,12 +224,14 @@ impl ExchangeOperator {
                 match cast_msg {
                     messages::exchange::ExchangeRequests::SendRecordRequest {
                         record_id,
+                        sequence_number,
                         record,
                         table_aliases,
                     } => {
                         self.handle_send_record_request(
                             msg,
                             record_id,
+                            *sequence_number,
                             record.clone(),
                             table_aliases,
                         )
@@ -219,6 +255,14 @@ impl ExchangeOperator {
                         .await?;
                         Ok(true)
                     }
+                    messages::exchange::ExchangeRequests::RecordProcessingHeartbeat {
+                        operator_id,
+                        record_id,
+                    } => {
+                        self.record_pool
+                            .update_reserved_record_heartbeat(operator_id, *record_id);
+                        Ok(true)
+                    }
                     _ => Ok(false),
                 }
             }
@@ -244,6 +288,22 @@ impl ExchangeOperator {
     async fn handle_operator_status_change(&mut self, msg: &Message) -> Result<()> {
         let cast_msg: &messages::exchange::OperatorStatusChange = self.msg_reg.try_cast_msg(msg)?;
 
+        let operator_id = match cast_msg {
+            messages::exchange::OperatorStatusChange::Complete { operator_id } => operator_id,
+        };
+        if !self
+            .inbound_producer_operator_states
+            .iter()
+            .any(|item| item.operator_id == *operator_id)
+        {
+            let resp_msg = msg.reply(Box::new(messages::common::GenericResponse::Err {
+                code: messages::common::ErrorCode::NotFound,
+                message: format!("operator {} is not an inbound producer", operator_id),
+            }));
+            self.router_pipe.send(resp_msg).await?;
+            return Ok(());
+        }
+
         // reply early
         let resp_msg = msg.reply(Box::new(messages::common::GenericResponse::Ok));
         self.router_pipe.send(resp_msg).await?;
@@ -279,8 +339,19 @@ impl ExchangeOperator {
         operator_id: &String,
         record_id: &u64,
     ) -> Result<()> {
-        self.record_pool
-            .operator_completed_record_processing(operator_id, record_id)?;
+        let op_in_id = if let Some(id) = &msg.sent_from_operation_id {
+            *id
+        } else {
+            return Err(ExchangeOperatorError::OperationInstanceIdNotSetOnMessage.into());
+        };
+
+        if let Some(spill_path) = self.record_pool.operator_completed_record_processing(
+            operator_id,
+            op_in_id,
+            record_id,
+        )? {
+            self.spiller.remove(&spill_path).await?;
+        }
 
         let resp_msg = msg.reply(Box::new(
             ExchangeRequests::OperatorCompletedRecordProcessingResponse,
@@ -294,16 +365,30 @@ impl ExchangeOperator {
         &mut self,
         msg: &Message,
         record_id: &u64,
+        sequence_number: u64,
         record: Arc<arrow::array::RecordBatch>,
         table_aliases: &Vec<Vec<String>>,
     ) -> Result<()> {
+        let producer_instance_id = if let Some(id) = &msg.sent_from_operation_id {
+            *id
+        } else {
+            return Err(ExchangeOperatorError::OperationInstanceIdNotSetOnMessage.into());
+        };
+
         debug!(
             record_id = *record_id,
+            sequence_number = sequence_number,
             num_rows = record.num_rows(),
             "received record",
         );
-        self.record_pool
-            .add_record(record_id.clone(), record.clone(), table_aliases.clone());
+        self.record_pool.add_record(
+            record_id.clone(),
+            record.clone(),
+            table_aliases.clone(),
+            producer_instance_id,
+            sequence_number,
+        )?;
+        self.spill_records().await?;
 
         let resp_msg = msg.reply(Box::new(
             messages::exchange::ExchangeRequests::SendRecordResponse {
@@ -329,6 +414,12 @@ impl ExchangeOperator {
         let rec_res = self.record_pool.get_next_record(operator_id, op_in_id)?;
         match rec_res {
             Some((record_id, record, table_aliases)) => {
+                let record = match record {
+                    RecordData::InMemory(record) => record,
+                    RecordData::Spilled(spill_path) => {
+                        Arc::new(self.spiller.read(&spill_path).await?)
+                    }
+                };
                 let resp_msg = msg.reply(Box::new(
                     messages::exchange::ExchangeRequests::GetNextRecordResponseRecord {
                         record_id,
@@ -339,7 +430,11 @@ impl ExchangeOperator {
                 self.router_pipe.send(resp_msg).await?;
             }
             None => {
-                if self.received_all_data_from_producers {
+                // a reserved record may still be requeued so the consumers
+                // wait for it to be completed
+                if self.received_all_data_from_producers
+                    && !self.record_pool.has_reserved_records(operator_id, op_in_id)
+                {
                     let resp_msg = msg.reply(Box::new(
                         messages::exchange::ExchangeRequests::GetNextRecordResponseNoneLeft,
                     ));
@@ -355,6 +450,24 @@ impl ExchangeOperator {
 
         Ok(())
     }
+
+    async fn spill_records(&mut self) -> Result<()> {
+        let max_buffered_bytes = self.operator_instance_config.exchange_max_buffered_bytes;
+        for (record_id, record) in self.record_pool.records_to_spill(max_buffered_bytes) {
+            let spill_path = self.spiller.spill(&record).await?;
+            debug!(
+                record_id = record_id,
+                spill_path = spill_path,
+                "spilled record",
+            );
+            self.record_pool.mark_record_spilled(record_id, spill_path);
+            debug!(
+                buffered_bytes = self.record_pool.buffered_bytes(),
+                "exchange buffered bytes after spill",
+            );
+        }
+        Ok(())
+    }
 }
 
 //////////////////////////////////////////////////////
@@ -398,6 +511,7 @@ impl MessageConsumer for ExchangeOperatorSubscriber {
                     Ok(messages::exchange::ExchangeRequests::SendRecordRequest { .. }) => true,
                     Ok(messages::exchange::ExchangeRequests::GetNextRecordRequest { .. }) => true,
                     Ok(messages::exchange::ExchangeRequests::OperatorCompletedRecordProcessingRequest { .. }) => true,
+                    Ok(messages::exchange::ExchangeRequests::RecordProcessingHeartbeat { .. }) => true,
                     Err(err) => {
                         error!("{}", err);
                         false
@@ -433,14 +547,26 @@ pub enum RecordPoolError {
     ReservedRecordInstanceMissingForOperator(String),
     #[error("record {0} already processed by operator {1}")]
     RecordAlreadyProcessedByOperator(u64, String),
+    #[error("operator {0} already has {1} consumer instances")]
+    TooManyConsumerInstances(String, usize),
+}
+
+// Records are written to a spill file when the exchange buffers too
+// much of them in memory.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum RecordData {
+    InMemory(Arc<arrow::array::RecordBatch>),
+    Spilled(String),
 }
 
 #[derive(Debug)]
 struct RecordRef {
     id: u64,
-    record: Arc<arrow::array::RecordBatch>,
+    record: RecordData,
     table_aliases: Vec<Vec<String>>,
     processed_by_operators: Vec<String>,
+    // the consumer instances which processed a broadcast record
+    processed_by_instances: Vec<(String, u128)>,
 }
 
 #[derive(Debug)]
@@ -459,78 +585,291 @@ struct RecordProcessingMetrics {
 #[derive(Debug)]
 struct OperatorRecordQueue {
     operator_id: String,
+    // a broadcast pool has a queue for each instance of the operator
+    // while the instances share a single queue otherwise
+    operator_instance_id: Option<u128>,
+    // a hash partitioned pool has a queue for each partition which is
+    // owned by the first instance to request a record from it
+    partition: Option<usize>,
     records_to_process: std::collections::VecDeque<u64>,
     records_reserved_by_operator: std::collections::HashMap<u64, ReservedRecord>,
     record_processing_metrics: std::collections::HashMap<u64, RecordProcessingMetrics>,
 }
 
 #[derive(Debug)]
-struct RecordPoolConfig {
-    max_heartbeat_interval: chrono::Duration,
+pub(crate) struct RecordPoolConfig {
+    pub max_heartbeat_interval: chrono::Duration,
+    pub ordered: bool,
+    pub distribution: planner::ExchangeDistribution,
 }
 
 #[derive(Debug)]
-struct RecordPool {
+pub(crate) struct RecordPool {
     records: std::collections::HashMap<u64, RecordRef>,
     operator_record_queues: Vec<OperatorRecordQueue>,
     operator_ids: Vec<String>,
+    // each producer instance numbers its records from 0 so the pool
+    // gives every (producer instance id, record id) its own pool id;
+    // a record which was already pooled is ignored when resent
+    pool_record_ids: std::collections::HashMap<(u128, u64), u64>,
+    next_pool_record_id: u64,
+    // an ordered pool holds the records which arrived before an earlier
+    // record from the same producer instance
+    next_sequence_numbers: std::collections::HashMap<u128, u64>,
+    out_of_order_records: std::collections::HashMap<u128, std::collections::BTreeMap<u64, u64>>,
+    // memory used by the records which haven't been spilled
+    buffered_bytes: usize,
+    // the records in the order they were queued; the queue of a new
+    // broadcast consumer instance starts with them
+    queued_record_ids: std::collections::VecDeque<u64>,
 
     config: RecordPoolConfig,
 }
 
 impl RecordPool {
-    fn new(mut operator_ids: Vec<String>, config: RecordPoolConfig) -> RecordPool {
+    pub(crate) fn new(mut operator_ids: Vec<String>, config: RecordPoolConfig) -> RecordPool {
         operator_ids.sort();
-        RecordPool {
-            records: std::collections::HashMap::new(),
-            operator_record_queues: operator_ids
-                .iter()
-                .map(|item| OperatorRecordQueue {
+        // the broadcast queues are added as the instances request records
+        let partitions: Vec<Option<usize>> = match config.distribution {
+            planner::ExchangeDistribution::RoundRobin => vec![None],
+            planner::ExchangeDistribution::Broadcast { .. } => Vec::new(),
+            planner::ExchangeDistribution::HashPartition { num_partitions, .. } => {
+                (0..num_partitions).map(Some).collect()
+            }
+        };
+        let operator_record_queues = operator_ids
+            .iter()
+            .flat_map(|item| {
+                partitions.iter().map(|partition| OperatorRecordQueue {
                     operator_id: item.clone(),
+                    operator_instance_id: None,
+                    partition: *partition,
                     records_to_process: std::collections::VecDeque::new(),
                     records_reserved_by_operator: std::collections::HashMap::new(),
                     record_processing_metrics: std::collections::HashMap::new(),
                 })
-                .collect(),
+            })
+            .collect();
+        RecordPool {
+            records: std::collections::HashMap::new(),
+            operator_record_queues,
             operator_ids,
+            pool_record_ids: std::collections::HashMap::new(),
+            next_pool_record_id: 0,
+            next_sequence_numbers: std::collections::HashMap::new(),
+            out_of_order_records: std::collections::HashMap::new(),
+            buffered_bytes: 0,
+            queued_record_ids: std::collections::VecDeque::new(),
             config,
         }
     }
 
-    fn add_record(
+    fn operator_record_queue_idx(
+        &self,
+        operator_id: &String,
+        operator_instance_id: u128,
+    ) -> Option<usize> {
+        self.operator_record_queues.iter().position(|item| {
+            item.operator_id == *operator_id
+                && match item.operator_instance_id {
+                    Some(id) => id == operator_instance_id,
+                    None => item.partition.is_none(),
+                }
+        })
+    }
+
+    // Gives a new instance of the operator its own queue; only the
+    // broadcast and hash partitioned pools have a queue per instance.
+    fn add_instance_queue(
+        &mut self,
+        operator_id: &String,
+        operator_instance_id: u128,
+    ) -> Result<usize> {
+        if !self.operator_ids.contains(operator_id) {
+            return Err(RecordPoolError::OperatorDoesNotExist(operator_id.clone()).into());
+        }
+
+        match self.config.distribution {
+            planner::ExchangeDistribution::Broadcast { consumer_instances } => {
+                let num_instances = self
+                    .operator_record_queues
+                    .iter()
+                    .filter(|item| item.operator_id == *operator_id)
+                    .count();
+                if num_instances >= consumer_instances {
+                    return Err(RecordPoolError::TooManyConsumerInstances(
+                        operator_id.clone(),
+                        consumer_instances,
+                    )
+                    .into());
+                }
+
+                self.operator_record_queues.push(OperatorRecordQueue {
+                    operator_id: operator_id.clone(),
+                    operator_instance_id: Some(operator_instance_id),
+                    partition: None,
+                    records_to_process: self.queued_record_ids.clone(),
+                    records_reserved_by_operator: std::collections::HashMap::new(),
+                    record_processing_metrics: std::collections::HashMap::new(),
+                });
+                Ok(self.operator_record_queues.len() - 1)
+            }
+            planner::ExchangeDistribution::HashPartition { num_partitions, .. } => {
+                let op_queue_idx = self.operator_record_queues.iter().position(|item| {
+                    item.operator_id == *operator_id && item.operator_instance_id.is_none()
+                });
+                match op_queue_idx {
+                    Some(idx) => {
+                        self.operator_record_queues[idx].operator_instance_id =
+                            Some(operator_instance_id);
+                        Ok(idx)
+                    }
+                    None => Err(RecordPoolError::TooManyConsumerInstances(
+                        operator_id.clone(),
+                        num_partitions,
+                    )
+                    .into()),
+                }
+            }
+            planner::ExchangeDistribution::RoundRobin => {
+                Err(RecordPoolError::OperatorDoesNotExist(operator_id.clone()).into())
+            }
+        }
+    }
+
+    // Pools a record sent by a producer instance. The consumers reserve
+    // and complete the record by its pool id rather than the record id
+    // of the producer.
+    pub(crate) fn add_record(
         &mut self,
         record_id: u64,
         record: Arc<arrow::array::RecordBatch>,
         table_aliases: Vec<Vec<String>>,
-    ) {
-        self.records.insert(
-            record_id,
-            RecordRef {
-                id: record_id,
-                record,
-                table_aliases,
-                processed_by_operators: Vec::new(),
-            },
-        );
+        producer_instance_id: u128,
+        sequence_number: u64,
+    ) -> Result<()> {
+        let record_id = match self
+            .pool_record_ids
+            .entry((producer_instance_id, record_id))
+        {
+            std::collections::hash_map::Entry::Occupied(_) => {
+                // the producer resent a record which was already pooled
+                return Ok(());
+            }
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                let pool_record_id = self.next_pool_record_id;
+                self.next_pool_record_id += 1;
+                *entry.insert(pool_record_id)
+            }
+        };
+
+        // the partitions of a record are pooled as separate records
+        let records: Vec<(u64, Arc<arrow::array::RecordBatch>)> = match &self.config.distribution {
+            planner::ExchangeDistribution::HashPartition {
+                keys,
+                num_partitions,
+            } => record_utils::hash_partition_record(&record, keys, *num_partitions)?
+                .into_iter()
+                .enumerate()
+                .filter(|(_, partition_record)| partition_record.num_rows() > 0)
+                .map(|(partition, partition_record)| {
+                    (
+                        partition_record_id(record_id, *num_partitions, partition),
+                        Arc::new(partition_record),
+                    )
+                })
+                .collect(),
+            _ => vec![(record_id, record)],
+        };
+        for (record_id, record) in records {
+            self.buffered_bytes += record.get_array_memory_size();
+            self.records.insert(
+                record_id,
+                RecordRef {
+                    id: record_id,
+                    record: RecordData::InMemory(record),
+                    table_aliases: table_aliases.clone(),
+                    processed_by_operators: Vec::new(),
+                    processed_by_instances: Vec::new(),
+                },
+            );
+        }
+        if !self.config.ordered {
+            self.queue_record(record_id);
+            return Ok(());
+        }
+
+        let next_sequence_number = self
+            .next_sequence_numbers
+            .entry(producer_instance_id)
+            .or_insert(0);
+        if sequence_number < *next_sequence_number {
+            // the producer resent a record which was already queued
+            return Ok(());
+        }
+        let out_of_order_records = self
+            .out_of_order_records
+            .entry(producer_instance_id)
+            .or_default();
+        out_of_order_records.insert(sequence_number, record_id);
+
+        let mut ready_record_ids: Vec<u64> = Vec::new();
+        while let Some(record_id) = out_of_order_records.remove(next_sequence_number) {
+            ready_record_ids.push(record_id);
+            *next_sequence_number += 1;
+        }
+        for record_id in ready_record_ids {
+            self.queue_record(record_id);
+        }
+        Ok(())
+    }
+
+    fn queue_record(&mut self, record_id: u64) {
+        if let planner::ExchangeDistribution::HashPartition { num_partitions, .. } =
+            self.config.distribution
+        {
+            for partition in 0..num_partitions {
+                let partition_record_id = partition_record_id(record_id, num_partitions, partition);
+                if !self.records.contains_key(&partition_record_id) {
+                    continue;
+                }
+                self.queued_record_ids.push_back(partition_record_id);
+                self.operator_record_queues
+                    .iter_mut()
+                    .filter(|item| item.partition == Some(partition))
+                    .for_each(|item| {
+                        item.records_to_process.push_back(partition_record_id);
+                    })
+            }
+            return;
+        }
+
+        self.queued_record_ids.push_back(record_id);
         self.operator_record_queues.iter_mut().for_each(|item| {
-            item.records_to_process.push_back(record_id.clone());
+            item.records_to_process.push_back(record_id);
         })
     }
 
-    fn get_next_record(
+    pub(crate) fn get_next_record(
         &mut self,
         operator_id: &String,
         operator_instance_id: u128,
-    ) -> Result<Option<(u64, Arc<arrow::array::RecordBatch>, Vec<Vec<String>>)>> {
-        let op_queue = if let Some(queue) = self
-            .operator_record_queues
-            .iter_mut()
-            .find(|item| item.operator_id == *operator_id)
-        {
-            queue
-        } else {
-            return Err(RecordPoolError::OperatorDoesNotExist(operator_id.clone()).into());
+    ) -> Result<Option<(u64, RecordData, Vec<Vec<String>>)>> {
+        let op_queue_idx = match self.operator_record_queue_idx(operator_id, operator_instance_id) {
+            Some(idx) => idx,
+            None => self.add_instance_queue(operator_id, operator_instance_id)?,
         };
+        let op_queue = &mut self.operator_record_queues[op_queue_idx];
+
+        // an ordered pool gives the records to one consumer at a time
+        if self.config.ordered
+            && op_queue
+                .records_reserved_by_operator
+                .values()
+                .any(|item| item.operator_instance_id != operator_instance_id)
+        {
+            return Ok(None);
+        }
 
         let record_id = op_queue.records_to_process.pop_front();
         match record_id {
@@ -551,9 +890,11 @@ impl RecordPool {
                         last_heartbeat_time: None,
                     },
                 );
+                // a requeued record keeps its failure count
                 op_queue
                     .record_processing_metrics
-                    .insert(record_id, RecordProcessingMetrics { failure_count: 0 });
+                    .entry(record_id)
+                    .or_insert(RecordProcessingMetrics { failure_count: 0 });
 
                 Ok(Some((
                     record_id,
@@ -565,7 +906,11 @@ impl RecordPool {
         }
     }
 
-    fn update_reserved_record_heartbeat(&mut self, operator_id: &String, record_id: u64) {
+    pub(crate) fn update_reserved_record_heartbeat(
+        &mut self,
+        operator_id: &String,
+        record_id: u64,
+    ) {
         self.operator_record_queues
             .iter_mut()
             .filter(|item| item.operator_id == *operator_id)
@@ -580,33 +925,98 @@ impl RecordPool {
             })
     }
 
-    fn operator_completed_record_processing(
+    // Returns the spill file of the record once every operator has
+    // processed it so the file can be removed.
+    pub(crate) fn operator_completed_record_processing(
         &mut self,
         operator_id: &String,
+        operator_instance_id: u128,
         record_id: &u64,
-    ) -> Result<()> {
-        let op_queue = if let Some(queue) = self
-            .operator_record_queues
-            .iter_mut()
-            .find(|item| item.operator_id == *operator_id)
-        {
-            queue
-        } else {
-            return Err(RecordPoolError::OperatorDoesNotExist(operator_id.clone()).into());
+    ) -> Result<Option<String>> {
+        let op_queue = match self.operator_record_queue_idx(operator_id, operator_instance_id) {
+            Some(idx) => &mut self.operator_record_queues[idx],
+            None => {
+                return Err(RecordPoolError::OperatorDoesNotExist(operator_id.clone()).into());
+            }
         };
 
         let reserved_record = op_queue.records_reserved_by_operator.remove(record_id);
-        if reserved_record.is_none() {
-            return Err(RecordPoolError::ReservedRecordInstanceMissingForOperator(
-                operator_id.clone(),
-            )
-            .into());
+        match reserved_record {
+            Some(res_rec) if res_rec.operator_instance_id == operator_instance_id => (),
+            Some(res_rec) => {
+                // the record was requeued after the consumer's heartbeat
+                // went stale and given to another consumer; the first
+                // consumer to finish the record completes it
+                debug!(
+                    record_id = *record_id,
+                    operator_instance_id = operator_instance_id,
+                    reserved_by_operator_instance_id = res_rec.operator_instance_id,
+                    "record completed by a consumer which no longer reserved it",
+                );
+            }
+            None => {
+                let requeued_idx = op_queue
+                    .records_to_process
+                    .iter()
+                    .position(|item| *item == *record_id);
+                let already_finished = match self.records.get(record_id) {
+                    Some(rec_ref) => {
+                        rec_ref.processed_by_operators.contains(operator_id)
+                            || rec_ref
+                                .processed_by_instances
+                                .contains(&(operator_id.clone(), operator_instance_id))
+                    }
+                    None => true,
+                };
+                if let Some(requeued_idx) = requeued_idx {
+                    // the consumer finished the record after it was
+                    // requeued but before it was given to another consumer
+                    op_queue.records_to_process.remove(requeued_idx);
+                } else if already_finished {
+                    // another consumer finished the record first
+                    debug!(
+                        record_id = *record_id,
+                        operator_instance_id = operator_instance_id,
+                        "ignoring completion of a record which was already completed",
+                    );
+                    return Ok(None);
+                } else {
+                    return Err(RecordPoolError::ReservedRecordInstanceMissingForOperator(
+                        operator_id.clone(),
+                    )
+                    .into());
+                }
+            }
         }
         op_queue.record_processing_metrics.remove(record_id);
 
         let rec_ref = self.records.get_mut(record_id);
         match rec_ref {
             Some(rec_ref) => {
+                // a broadcast record is processed by the operator once
+                // every instance of it processed the record
+                if let planner::ExchangeDistribution::Broadcast { consumer_instances } =
+                    self.config.distribution
+                {
+                    let instance = (operator_id.clone(), operator_instance_id);
+                    if rec_ref.processed_by_instances.contains(&instance) {
+                        return Err(RecordPoolError::RecordAlreadyProcessedByOperator(
+                            *record_id,
+                            operator_id.clone(),
+                        )
+                        .into());
+                    }
+                    rec_ref.processed_by_instances.push(instance);
+                    let num_instances = rec_ref
+                        .processed_by_instances
+                        .iter()
+                        .filter(|item| item.0 == *operator_id)
+                        .count();
+                    if num_instances < consumer_instances {
+                        return Ok(None);
+                    }
+                }
+
                 let already_finished = rec_ref
                     .processed_by_operators
                     .iter()
@@ -627,17 +1037,97 @@ impl RecordPool {
                     let mut pbo = rec_ref.processed_by_operators.clone();
                     pbo.sort();
                     if pbo == self.operator_ids {
-                        self.records.remove(record_id);
+                        if let Some(rec_ref) = self.records.remove(record_id) {
+                            self.queued_record_ids.retain(|item| *item != *record_id);
+                            match rec_ref.record {
+                                RecordData::InMemory(record) => {
+                                    self.buffered_bytes -= record.get_array_memory_size();
+                                }
+                                RecordData::Spilled(spill_path) => {
+                                    return Ok(Some(spill_path));
+                                }
+                            }
+                        }
                     }
                 }
 
-                Ok(())
+                Ok(None)
             }
             None => Err(RecordPoolError::RecordDoesNotExist(record_id.clone()).into()),
         }
     }
 
-    fn maintain(&mut self) -> Result<()> {
+    // Records which were given to a consumer of the operator but haven't
+    // been completed; they're requeued if the consumer stops sending
+    // heartbeats.
+    pub(crate) fn has_reserved_records(
+        &self,
+        operator_id: &String,
+        operator_instance_id: u128,
+    ) -> bool {
+        self.operator_record_queue_idx(operator_id, operator_instance_id)
+            .is_some_and(|idx| {
+                !self.operator_record_queues[idx]
+                    .records_reserved_by_operator
+                    .is_empty()
+            })
+    }
+
+    pub(crate) fn buffered_bytes(&self) -> usize {
+        self.buffered_bytes
+    }
+
+    // The records to spill so the buffered records fit in the max bytes.
+    // The newest records are spilled first since they're read last and
+    // reserved records are never spilled.
+    pub(crate) fn records_to_spill(
+        &self,
+        max_buffered_bytes: usize,
+    ) -> Vec<(u64, Arc<arrow::array::RecordBatch>)> {
+        if self.buffered_bytes <= max_buffered_bytes {
+            return Vec::new();
+        }
+
+        let mut record_ids: Vec<&u64> = self
+            .records
+            .keys()
+            .filter(|record_id| {
+                !self
+                    .operator_record_queues
+                    .iter()
+                    .any(|q| q.records_reserved_by_operator.contains_key(record_id))
+            })
+            .collect();
+        record_ids.sort_by(|a, b| b.cmp(a));
+
+        let mut buffered_bytes = self.buffered_bytes;
+        let mut records: Vec<(u64, Arc<arrow::array::RecordBatch>)> = Vec::new();
+        for record_id in record_ids {
+            if buffered_bytes <= max_buffered_bytes {
+                break;
+            }
+            if let Some(RecordRef {
+                record: RecordData::InMemory(record),
+                ..
+            }) = self.records.get(record_id)
+            {
+                buffered_bytes -= record.get_array_memory_size();
+                records.push((*record_id, record.clone()));
+            }
+        }
+        records
+    }
+
+    pub(crate) fn mark_record_spilled(&mut self, record_id: u64, spill_path: String) {
+        if let Some(rec_ref) = self.records.get_mut(&record_id) {
+            if let RecordData::InMemory(record) = &rec_ref.record {
+                self.buffered_bytes -= record.get_array_memory_size();
+                rec_ref.record = RecordData::Spilled(spill_path);
+            }
+        }
+    }
+
+    pub(crate) fn maintain(&mut self) -> Result<()> {
         self.requeue_reserved_records_with_stale_heartbeat()?;
         Ok(())
     }
@@ -647,17 +1137,24 @@ impl RecordPool {
             q.records_reserved_by_operator
                 .iter()
                 .filter(|res_rec| {
-                    if let Some(last_heartbeat_time) = res_rec.1.last_heartbeat_time {
-                        chrono::Utc::now().signed_duration_since(last_heartbeat_time)
-                            > self.config.max_heartbeat_interval
-                    } else {
-                        false
-                    }
+                    // the timer starts when the record is reserved and is
+                    // reset by each heartbeat
+                    let last_heartbeat_time = res_rec
+                        .1
+                        .last_heartbeat_time
+                        .unwrap_or(res_rec.1.reserved_time);
+                    chrono::Utc::now().signed_duration_since(last_heartbeat_time)
+                        > self.config.max_heartbeat_interval
                 })
                 .map(|res_rec| res_rec.0.clone())
                 .collect::<Vec<u64>>()
                 .iter()
                 .try_for_each(|res_rec_id| {
+                    debug!(
+                        operator_id = q.operator_id,
+                        record_id = *res_rec_id,
+                        "requeuing record with a stale heartbeat",
+                    );
                     q.records_reserved_by_operator.remove(res_rec_id);
                     q.records_to_process.push_front(res_rec_id.clone());
                     if let Some(metrics) = q.record_processing_metrics.get_mut(&res_rec_id) {
@@ -674,3 +1171,7 @@ impl RecordPool {
         })
     }
 }
+
+fn partition_record_id(record_id: u64, num_partitions: usize, partition: usize) -> u64 {
+    record_id * num_partitions as u64 + partition as u64
+}
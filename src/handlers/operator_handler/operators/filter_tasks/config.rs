@@ -0,0 +1,4 @@
+#[derive(Debug)]
+pub struct FilterConfig {
+    pub expr: sqlparser::ast::Expr,
+}
@@ -0,0 +1,29 @@
+use anyhow::Result;
+use thiserror::Error;
+
+use crate::{
+    handlers::operator_handler::operator_handler_state::OperatorInstanceConfig,
+    planner::{OperatorTask, OperatorType},
+};
+
+use super::config::FilterConfig;
+
+#[derive(Debug, Error)]
+pub enum TryFromFilterConfigError {
+    #[error("unable to convert")]
+    UnableToConvert,
+}
+
+impl TryFrom<&OperatorInstanceConfig> for FilterConfig {
+    type Error = TryFromFilterConfigError;
+
+    fn try_from(op_in_config: &OperatorInstanceConfig) -> Result<FilterConfig, Self::Error> {
+        match &op_in_config.operator.operator_type {
+            OperatorType::Producer { task, .. } => match task {
+                OperatorTask::Filter { expr } => Ok(FilterConfig { expr: expr.clone() }),
+                _ => Err(TryFromFilterConfigError::UnableToConvert),
+            },
+            OperatorType::Exchange { .. } => Err(TryFromFilterConfigError::UnableToConvert),
+        }
+    }
+}
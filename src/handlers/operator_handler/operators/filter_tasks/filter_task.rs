@@ -0,0 +1,391 @@
+use anyhow::{Context, Error, Result};
+use arrow::array::RecordBatch;
+use std::sync::Arc;
+use thiserror::Error;
+use tracing::{debug, error};
+
+use crate::handlers::message_handler::messages;
+use crate::handlers::message_handler::messages::message::{Message, MessageName};
+use crate::handlers::message_handler::{MessageRegistry, Pipe};
+use crate::handlers::{
+    message_router_handler::MessageConsumer,
+    operator_handler::{
+        operator_handler_state::OperatorInstanceConfig,
+        operators::{
+            operator_metrics::OperatorMetricsRecorder,
+            operator_task_trackers::RestrictedOperatorTaskTracker,
+            record_utils::{self, RecordComputeQueue},
+            requests,
+            traits::TaskBuilder,
+            ConnectionRegistry,
+        },
+    },
+};
+
+use super::config::FilterConfig;
+
+#[derive(Debug, Error)]
+pub enum FilterTaskError {
+    #[error("more than one exchange is currently not implement")]
+    MoreThanOneExchangeIsCurrentlyNotImplemented,
+}
+
+#[derive(Debug)]
+struct FilterTask {
+    operator_instance_config: OperatorInstanceConfig,
+    filter_config: FilterConfig,
+
+    operator_pipe: Pipe,
+    msg_reg: Arc<MessageRegistry>,
+
+    inbound_exchange_worker_id: Option<u128>,
+    inbound_exchange_operator_instance_id: Option<u128>,
+    outbound_exchange_worker_id: Option<u128>,
+    outbound_exchange_operator_instance_id: Option<u128>,
+    record_id: u64,
+    metrics: OperatorMetricsRecorder,
+}
+
+impl FilterTask {
+    fn new(
+        op_in_config: OperatorInstanceConfig,
+        filter_config: FilterConfig,
+        operator_pipe: Pipe,
+        msg_reg: Arc<MessageRegistry>,
+    ) -> FilterTask {
+        FilterTask {
+            operator_instance_config: op_in_config,
+            filter_config,
+            operator_pipe,
+            msg_reg,
+            inbound_exchange_worker_id: None,
+            inbound_exchange_operator_instance_id: None,
+            outbound_exchange_worker_id: None,
+            outbound_exchange_operator_instance_id: None,
+            record_id: 0,
+            metrics: OperatorMetricsRecorder::new(),
+        }
+    }
+
+    fn consumer(&self) -> Box<dyn MessageConsumer> {
+        Box::new(FilterConsumer {
+            msg_reg: self.msg_reg.clone(),
+        })
+    }
+
+    async fn async_main(&mut self, ct: tokio_util::sync::CancellationToken) -> Result<()> {
+        debug!(
+            operator_task = self
+                .operator_instance_config
+                .operator
+                .operator_type
+                .task_name(),
+            operator_id = self.operator_instance_config.operator.id,
+            operator_instance_id = self.operator_instance_config.id,
+            "started task",
+        );
+
+        // find the inbound exchange
+        let pipe = &mut self.operator_pipe;
+        let req = requests::IdentifyExchangeRequest::request_inbound_exchanges(
+            &self.operator_instance_config,
+            pipe,
+            self.msg_reg.clone(),
+        );
+        tokio::select! {
+            resp = req => {
+                match resp {
+                    Ok(resp) => {
+                        if resp.len() != 1 {
+                            return Err(FilterTaskError::MoreThanOneExchangeIsCurrentlyNotImplemented.into());
+                        }
+                        let resp = resp.first().unwrap();
+                        self.inbound_exchange_operator_instance_id = Some(resp.exchange_operator_instance_id);
+                        self.inbound_exchange_worker_id = Some(resp.exchange_worker_id);
+                    }
+                    Err(err) => {
+                        return Err(err);
+                    }
+                }
+            }
+            _ = ct.cancelled() => {
+                return Ok(());
+            }
+        }
+
+        assert!(self.inbound_exchange_operator_instance_id.is_some());
+        assert!(self.inbound_exchange_worker_id.is_some());
+
+        // loop over all records in the inbound exchange; the records are
+        // filtered on the blocking thread pool while the next records are
+        // requested
+        let mut filtered_records: RecordComputeQueue<(u64, RecordBatch, Vec<Vec<String>>)> =
+            RecordComputeQueue::for_compute(&self.operator_instance_config.operator.compute);
+        loop {
+            if ct.is_cancelled() {
+                break;
+            }
+
+            if filtered_records.is_full() {
+                if let Some(res) = filtered_records.next().await {
+                    let (record_id, filtered_rec, table_aliases) = res?;
+                    self.complete_record(record_id, filtered_rec, table_aliases)
+                        .await?;
+                }
+                continue;
+            }
+
+            let resp = requests::GetNextRecordRequest::get_next_record_request(
+                self.operator_instance_config.operator.id.clone(),
+                self.inbound_exchange_operator_instance_id.unwrap(),
+                self.inbound_exchange_worker_id.unwrap(),
+                &mut self.operator_pipe,
+                self.msg_reg.clone(),
+            )
+            .await?;
+
+            match resp {
+                requests::GetNextRecordResponse::Record {
+                    record_id,
+                    record,
+                    table_aliases,
+                } => {
+                    self.metrics.record_in(&record);
+                    let expr = self.filter_config.expr.clone();
+                    let options = record_utils::ComputeValueOptions {
+                        nan_policy: self.operator_instance_config.nan_policy,
+                        ..record_utils::ComputeValueOptions::default()
+                    };
+                    filtered_records.push(move || {
+                        let filtered_rec = record_utils::filter_record(
+                            record,
+                            &expr,
+                            &table_aliases,
+                            &options,
+                            false,
+                        )?;
+                        Ok((record_id, filtered_rec, table_aliases))
+                    });
+                }
+                requests::GetNextRecordResponse::NoneLeft => {
+                    while let Some(res) = filtered_records.next().await {
+                        let (record_id, filtered_rec, table_aliases) = res?;
+                        self.complete_record(record_id, filtered_rec, table_aliases)
+                            .await?;
+                    }
+                    debug!("complete filter; read all records from the exchange");
+                    break;
+                }
+                requests::GetNextRecordResponse::NoneAvailable => {
+                    // finish a filtered record instead of waiting
+                    if let Some(res) = filtered_records.next().await {
+                        let (record_id, filtered_rec, table_aliases) = res?;
+                        self.complete_record(record_id, filtered_rec, table_aliases)
+                            .await?;
+                        continue;
+                    }
+                    debug!("exchange does not have any record available; waiting 100 milliseconds");
+                    tokio::time::sleep(chrono::Duration::milliseconds(100).to_std()?).await;
+                }
+            }
+        }
+
+        self.metrics
+            .report(
+                &self.operator_instance_config,
+                &mut self.operator_pipe,
+                self.msg_reg.clone(),
+            )
+            .await;
+
+        debug!(
+            operator_task = self
+                .operator_instance_config
+                .operator
+                .operator_type
+                .task_name(),
+            operator_id = self.operator_instance_config.operator.id,
+            operator_instance_id = self.operator_instance_config.id,
+            "closed task",
+        );
+        Ok(())
+    }
+
+    async fn complete_record(
+        &mut self,
+        record_id: u64,
+        filtered_rec: RecordBatch,
+        table_aliases: Vec<Vec<String>>,
+    ) -> Result<()> {
+        // only forward records with rows remaining
+        if filtered_rec.num_rows() > 0 {
+            self.metrics.record_out(&filtered_rec);
+            self.send_record(filtered_rec, table_aliases)
+                .await
+                .context("unable to send record to the exchange")?;
+        }
+
+        // confirm processing of the record
+        requests::OperatorCompletedRecordProcessingRequest::request(
+            self.operator_instance_config.operator.id.clone(),
+            record_id,
+            self.inbound_exchange_operator_instance_id.unwrap(),
+            self.inbound_exchange_worker_id.unwrap(),
+            &mut self.operator_pipe,
+            self.msg_reg.clone(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn send_record(
+        &mut self,
+        record: arrow::array::RecordBatch,
+        table_aliases: Vec<Vec<String>>,
+    ) -> Result<()> {
+        if self.outbound_exchange_worker_id.is_none() {
+            let pipe = &mut self.operator_pipe;
+            let resp = requests::IdentifyExchangeRequest::request_outbound_exchange(
+                &self.operator_instance_config,
+                pipe,
+                self.msg_reg.clone(),
+            )
+            .await?;
+            self.outbound_exchange_operator_instance_id = Some(resp.exchange_operator_instance_id);
+            self.outbound_exchange_worker_id = Some(resp.exchange_worker_id);
+        }
+
+        assert!(self.outbound_exchange_worker_id.is_some());
+
+        let msg_record_id = self.next_record_id();
+        let pipe = &mut self.operator_pipe;
+        requests::SendRecordRequest::send_record_request(
+            msg_record_id,
+            record,
+            table_aliases,
+            self.outbound_exchange_operator_instance_id.unwrap(),
+            self.outbound_exchange_worker_id.unwrap(),
+            pipe,
+            self.msg_reg.clone(),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    fn next_record_id(&mut self) -> u64 {
+        let record_id = self.record_id;
+        self.record_id += 1;
+        record_id
+    }
+}
+
+//////////////////////////////////////////////////////
+// Filter Producer Builder
+
+#[derive(Debug, Clone)]
+pub struct FilterTaskBuilder {}
+
+impl FilterTaskBuilder {
+    pub fn new() -> FilterTaskBuilder {
+        FilterTaskBuilder {}
+    }
+}
+
+impl TaskBuilder for FilterTaskBuilder {
+    fn build(
+        &self,
+        op_in_config: OperatorInstanceConfig,
+        operator_pipe: Pipe,
+        msg_reg: Arc<MessageRegistry>,
+        _: Arc<ConnectionRegistry>,
+        tt: &mut RestrictedOperatorTaskTracker,
+        ct: tokio_util::sync::CancellationToken,
+    ) -> Result<(
+        tokio::sync::oneshot::Receiver<Option<Error>>,
+        Box<dyn MessageConsumer>,
+    )> {
+        let filter_config = FilterConfig::try_from(&op_in_config)?;
+        let mut op = FilterTask::new(op_in_config, filter_config, operator_pipe, msg_reg.clone());
+
+        let consumer = op.consumer();
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        tt.spawn(async move {
+            if let Err(err) = op.async_main(ct).await {
+                error!("{:?}", err);
+                if let Err(err_send) = tx.send(Some(err)) {
+                    error!("{:?}", err_send);
+                }
+            } else {
+                if let Err(err_send) = tx.send(None) {
+                    error!("{:?}", err_send);
+                }
+            }
+        })?;
+
+        Ok((rx, consumer))
+    }
+}
+
+//////////////////////////////////////////////////////
+// Message Consumer
+
+#[derive(Debug, Clone)]
+pub struct FilterConsumer {
+    msg_reg: Arc<MessageRegistry>,
+}
+
+impl MessageConsumer for FilterConsumer {
+    fn consumes_message(&self, msg: &Message) -> bool {
+        match msg.msg.msg_name() {
+            // used to find the exchanges
+            MessageName::Ping => match self.msg_reg.try_cast_msg::<messages::common::Ping>(msg) {
+                Ok(messages::common::Ping::Ping) => false,
+                Ok(messages::common::Ping::Pong) => true,
+                Err(err) => {
+                    error!("{:?}", err);
+                    false
+                }
+            },
+            MessageName::QueryHandlerRequests => {
+                match self
+                    .msg_reg
+                    .try_cast_msg::<messages::query::QueryHandlerRequests>(msg)
+                {
+                    Ok(messages::query::QueryHandlerRequests::ListOperatorInstancesResponse {
+                        ..
+                    }) => true,
+                    Ok(messages::query::QueryHandlerRequests::ListOperatorInstancesRequest {
+                        ..
+                    }) => false,
+                    Err(err) => {
+                        error!("{:?}", err);
+                        false
+                    }
+                }
+            }
+            MessageName::ExchangeRequests => {
+                match self
+                    .msg_reg
+                    .try_cast_msg::<messages::exchange::ExchangeRequests>(msg)
+                {
+                    Ok(messages::exchange::ExchangeRequests::GetNextRecordResponseRecord {
+                        ..
+                    }) => true,
+                    Ok(messages::exchange::ExchangeRequests::GetNextRecordResponseNoneLeft) => true,
+                    Ok(messages::exchange::ExchangeRequests::GetNextRecordResponseNoneAvailable) => true,
+                    Ok(messages::exchange::ExchangeRequests::OperatorCompletedRecordProcessingResponse) => true,
+                    Ok(messages::exchange::ExchangeRequests::SendRecordResponse { .. }) => true,
+                    Err(err) => {
+                        error!("{:?}", err);
+                        false
+                    }
+                    _ => false,
+                }
+            }
+            MessageName::CommonGenericResponse => true,
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,5 @@
+mod config;
+mod conversions;
+mod filter_task;
+
+pub use filter_task::FilterTaskBuilder;
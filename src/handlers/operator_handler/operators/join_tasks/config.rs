@@ -0,0 +1,8 @@
+use crate::planner::JoinType;
+
+#[derive(Debug)]
+pub struct HashJoinConfig {
+    pub join_type: JoinType,
+    pub left_keys: Vec<sqlparser::ast::Expr>,
+    pub right_keys: Vec<sqlparser::ast::Expr>,
+}
@@ -0,0 +1,37 @@
+use anyhow::Result;
+use thiserror::Error;
+
+use crate::{
+    handlers::operator_handler::operator_handler_state::OperatorInstanceConfig,
+    planner::{OperatorTask, OperatorType},
+};
+
+use super::config::HashJoinConfig;
+
+#[derive(Debug, Error)]
+pub enum TryFromHashJoinConfigError {
+    #[error("unable to convert")]
+    UnableToConvert,
+}
+
+impl TryFrom<&OperatorInstanceConfig> for HashJoinConfig {
+    type Error = TryFromHashJoinConfigError;
+
+    fn try_from(op_in_config: &OperatorInstanceConfig) -> Result<HashJoinConfig, Self::Error> {
+        match &op_in_config.operator.operator_type {
+            OperatorType::Producer { task, .. } => match task {
+                OperatorTask::HashJoin {
+                    join_type,
+                    left_keys,
+                    right_keys,
+                } => Ok(HashJoinConfig {
+                    join_type: join_type.clone(),
+                    left_keys: left_keys.clone(),
+                    right_keys: right_keys.clone(),
+                }),
+                _ => Err(TryFromHashJoinConfigError::UnableToConvert),
+            },
+            OperatorType::Exchange { .. } => Err(TryFromHashJoinConfigError::UnableToConvert),
+        }
+    }
+}
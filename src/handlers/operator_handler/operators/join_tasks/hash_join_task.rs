@@ -0,0 +1,392 @@
+use anyhow::{Context, Error, Result};
+use std::sync::Arc;
+use thiserror::Error;
+use tracing::{debug, error};
+
+use crate::handlers::message_handler::messages;
+use crate::handlers::message_handler::messages::message::{Message, MessageName};
+use crate::handlers::message_handler::{MessageRegistry, Pipe};
+use crate::handlers::{
+    message_router_handler::MessageConsumer,
+    operator_handler::{
+        operator_handler_state::OperatorInstanceConfig,
+        operators::{
+            operator_task_trackers::RestrictedOperatorTaskTracker, record_utils, requests,
+            traits::TaskBuilder, ConnectionRegistry,
+        },
+    },
+};
+
+use super::config::HashJoinConfig;
+
+#[derive(Debug, Error)]
+pub enum HashJoinTaskError {
+    #[error("expected 2 inbound exchanges but found {0}")]
+    ExpectedTwoInboundExchanges(usize),
+    #[error("build side of the join exceeds the operator memory limit of {0} MiB")]
+    BuildSideExceedsMemoryLimit(usize),
+}
+
+// (exchange operator instance id, exchange worker id)
+type ExchangeLocation = (u128, u128);
+
+#[derive(Debug)]
+struct HashJoinTask {
+    operator_instance_config: OperatorInstanceConfig,
+    hash_join_config: HashJoinConfig,
+
+    operator_pipe: Pipe,
+    msg_reg: Arc<MessageRegistry>,
+
+    outbound_exchange_worker_id: Option<u128>,
+    outbound_exchange_operator_instance_id: Option<u128>,
+    record_id: u64,
+}
+
+impl HashJoinTask {
+    fn new(
+        op_in_config: OperatorInstanceConfig,
+        hash_join_config: HashJoinConfig,
+        operator_pipe: Pipe,
+        msg_reg: Arc<MessageRegistry>,
+    ) -> HashJoinTask {
+        HashJoinTask {
+            operator_instance_config: op_in_config,
+            hash_join_config,
+            operator_pipe,
+            msg_reg,
+            outbound_exchange_worker_id: None,
+            outbound_exchange_operator_instance_id: None,
+            record_id: 0,
+        }
+    }
+
+    fn consumer(&self) -> Box<dyn MessageConsumer> {
+        Box::new(HashJoinConsumer {
+            msg_reg: self.msg_reg.clone(),
+        })
+    }
+
+    async fn async_main(&mut self, ct: tokio_util::sync::CancellationToken) -> Result<()> {
+        debug!(
+            operator_task = self
+                .operator_instance_config
+                .operator
+                .operator_type
+                .task_name(),
+            operator_id = self.operator_instance_config.operator.id,
+            operator_instance_id = self.operator_instance_config.id,
+            "started task",
+        );
+
+        // find the inbound exchanges; the first is the left (probe)
+        // side and the second is the right (build) side
+        let pipe = &mut self.operator_pipe;
+        let req = requests::IdentifyExchangeRequest::request_inbound_exchanges(
+            &self.operator_instance_config,
+            pipe,
+            self.msg_reg.clone(),
+        );
+        let (probe_exchange, build_exchange) = tokio::select! {
+            resp = req => {
+                let resp = resp?;
+                if resp.len() != 2 {
+                    return Err(HashJoinTaskError::ExpectedTwoInboundExchanges(resp.len()).into());
+                }
+                (
+                    (resp[0].exchange_operator_instance_id, resp[0].exchange_worker_id),
+                    (resp[1].exchange_operator_instance_id, resp[1].exchange_worker_id),
+                )
+            }
+            _ = ct.cancelled() => {
+                return Ok(());
+            }
+        };
+
+        // read the entire build side into memory
+        let memory_limit_in_mib = self.operator_instance_config.operator.compute.memory_in_mib;
+        let mut build_records: Vec<arrow::array::RecordBatch> = Vec::new();
+        let mut build_table_aliases: Vec<Vec<String>> = Vec::new();
+        let mut build_size_in_bytes = 0usize;
+        loop {
+            if ct.is_cancelled() {
+                return Ok(());
+            }
+
+            let (record_id, record, table_aliases) = match self.next_record(build_exchange).await? {
+                Some(resp) => resp,
+                None => {
+                    debug!("read all records from the build side exchange");
+                    break;
+                }
+            };
+
+            build_size_in_bytes += record.get_array_memory_size();
+            if build_size_in_bytes > memory_limit_in_mib * 1024 * 1024 {
+                return Err(
+                    HashJoinTaskError::BuildSideExceedsMemoryLimit(memory_limit_in_mib).into(),
+                );
+            }
+            if build_records.is_empty() {
+                build_table_aliases = table_aliases;
+            }
+            build_records.push((*record).clone());
+
+            self.confirm_record(build_exchange, record_id).await?;
+        }
+
+        let build_side = record_utils::JoinBuildSide::try_new(
+            &build_records,
+            build_table_aliases,
+            &self.hash_join_config.right_keys,
+        )?;
+        drop(build_records);
+        debug!(
+            build_rows = build_side.num_rows(),
+            "built the hash table for the join"
+        );
+
+        // probe the build side with each record from the left side
+        loop {
+            if ct.is_cancelled() {
+                break;
+            }
+
+            let (record_id, record, table_aliases) = match self.next_record(probe_exchange).await? {
+                Some(resp) => resp,
+                None => {
+                    debug!("complete hash join; read all records from the probe side exchange");
+                    break;
+                }
+            };
+
+            let (joined_rec, joined_table_aliases) = build_side.join_record(
+                &record,
+                &table_aliases,
+                &self.hash_join_config.left_keys,
+                &self.hash_join_config.join_type,
+            )?;
+
+            // only forward records with rows remaining
+            if joined_rec.num_rows() > 0 {
+                self.send_record(joined_rec, joined_table_aliases)
+                    .await
+                    .context("unable to send record to the exchange")?;
+            }
+
+            self.confirm_record(probe_exchange, record_id).await?;
+        }
+
+        debug!(
+            operator_task = self
+                .operator_instance_config
+                .operator
+                .operator_type
+                .task_name(),
+            operator_id = self.operator_instance_config.operator.id,
+            operator_instance_id = self.operator_instance_config.id,
+            "closed task",
+        );
+        Ok(())
+    }
+
+    // Returns None once the exchange has no records left.
+    async fn next_record(
+        &mut self,
+        exchange: ExchangeLocation,
+    ) -> Result<Option<(u64, Arc<arrow::array::RecordBatch>, Vec<Vec<String>>)>> {
+        loop {
+            let resp = requests::GetNextRecordRequest::get_next_record_request(
+                self.operator_instance_config.operator.id.clone(),
+                exchange.0,
+                exchange.1,
+                &mut self.operator_pipe,
+                self.msg_reg.clone(),
+            )
+            .await?;
+
+            match resp {
+                requests::GetNextRecordResponse::Record {
+                    record_id,
+                    record,
+                    table_aliases,
+                } => {
+                    return Ok(Some((record_id, record, table_aliases)));
+                }
+                requests::GetNextRecordResponse::NoneLeft => {
+                    return Ok(None);
+                }
+                requests::GetNextRecordResponse::NoneAvailable => {
+                    debug!("exchange does not have any record available; waiting 100 milliseconds");
+                    tokio::time::sleep(chrono::Duration::milliseconds(100).to_std()?).await;
+                }
+            }
+        }
+    }
+
+    async fn confirm_record(&mut self, exchange: ExchangeLocation, record_id: u64) -> Result<()> {
+        requests::OperatorCompletedRecordProcessingRequest::request(
+            self.operator_instance_config.operator.id.clone(),
+            record_id,
+            exchange.0,
+            exchange.1,
+            &mut self.operator_pipe,
+            self.msg_reg.clone(),
+        )
+        .await
+    }
+
+    async fn send_record(
+        &mut self,
+        record: arrow::array::RecordBatch,
+        table_aliases: Vec<Vec<String>>,
+    ) -> Result<()> {
+        if self.outbound_exchange_worker_id.is_none() {
+            let pipe = &mut self.operator_pipe;
+            let resp = requests::IdentifyExchangeRequest::request_outbound_exchange(
+                &self.operator_instance_config,
+                pipe,
+                self.msg_reg.clone(),
+            )
+            .await?;
+            self.outbound_exchange_operator_instance_id = Some(resp.exchange_operator_instance_id);
+            self.outbound_exchange_worker_id = Some(resp.exchange_worker_id);
+        }
+
+        assert!(self.outbound_exchange_worker_id.is_some());
+
+        let msg_record_id = self.next_record_id();
+        let pipe = &mut self.operator_pipe;
+        requests::SendRecordRequest::send_record_request(
+            msg_record_id,
+            record,
+            table_aliases,
+            self.outbound_exchange_operator_instance_id.unwrap(),
+            self.outbound_exchange_worker_id.unwrap(),
+            pipe,
+            self.msg_reg.clone(),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    fn next_record_id(&mut self) -> u64 {
+        let record_id = self.record_id;
+        self.record_id += 1;
+        record_id
+    }
+}
+
+//////////////////////////////////////////////////////
+// Hash Join Producer Builder
+
+#[derive(Debug, Clone)]
+pub struct HashJoinTaskBuilder {}
+
+impl HashJoinTaskBuilder {
+    pub fn new() -> HashJoinTaskBuilder {
+        HashJoinTaskBuilder {}
+    }
+}
+
+impl TaskBuilder for HashJoinTaskBuilder {
+    fn build(
+        &self,
+        op_in_config: OperatorInstanceConfig,
+        operator_pipe: Pipe,
+        msg_reg: Arc<MessageRegistry>,
+        _: Arc<ConnectionRegistry>,
+        tt: &mut RestrictedOperatorTaskTracker,
+        ct: tokio_util::sync::CancellationToken,
+    ) -> Result<(
+        tokio::sync::oneshot::Receiver<Option<Error>>,
+        Box<dyn MessageConsumer>,
+    )> {
+        let hash_join_config = HashJoinConfig::try_from(&op_in_config)?;
+        let mut op = HashJoinTask::new(
+            op_in_config,
+            hash_join_config,
+            operator_pipe,
+            msg_reg.clone(),
+        );
+
+        let consumer = op.consumer();
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        tt.spawn(async move {
+            if let Err(err) = op.async_main(ct).await {
+                error!("{:?}", err);
+                if let Err(err_send) = tx.send(Some(err)) {
+                    error!("{:?}", err_send);
+                }
+            } else if let Err(err_send) = tx.send(None) {
+                error!("{:?}", err_send);
+            }
+        })?;
+
+        Ok((rx, consumer))
+    }
+}
+
+//////////////////////////////////////////////////////
+// Message Consumer
+
+#[derive(Debug, Clone)]
+pub struct HashJoinConsumer {
+    msg_reg: Arc<MessageRegistry>,
+}
+
+impl MessageConsumer for HashJoinConsumer {
+    fn consumes_message(&self, msg: &Message) -> bool {
+        match msg.msg.msg_name() {
+            // used to find the exchanges
+            MessageName::Ping => match self.msg_reg.try_cast_msg::<messages::common::Ping>(msg) {
+                Ok(messages::common::Ping::Ping) => false,
+                Ok(messages::common::Ping::Pong) => true,
+                Err(err) => {
+                    error!("{:?}", err);
+                    false
+                }
+            },
+            MessageName::QueryHandlerRequests => {
+                match self
+                    .msg_reg
+                    .try_cast_msg::<messages::query::QueryHandlerRequests>(msg)
+                {
+                    Ok(messages::query::QueryHandlerRequests::ListOperatorInstancesResponse {
+                        ..
+                    }) => true,
+                    Ok(messages::query::QueryHandlerRequests::ListOperatorInstancesRequest {
+                        ..
+                    }) => false,
+                    Err(err) => {
+                        error!("{:?}", err);
+                        false
+                    }
+                }
+            }
+            MessageName::ExchangeRequests => {
+                match self
+                    .msg_reg
+                    .try_cast_msg::<messages::exchange::ExchangeRequests>(msg)
+                {
+                    Ok(messages::exchange::ExchangeRequests::GetNextRecordResponseRecord {
+                        ..
+                    }) => true,
+                    Ok(messages::exchange::ExchangeRequests::GetNextRecordResponseNoneLeft) => true,
+                    Ok(messages::exchange::ExchangeRequests::GetNextRecordResponseNoneAvailable) => true,
+                    Ok(messages::exchange::ExchangeRequests::OperatorCompletedRecordProcessingResponse) => true,
+                    Ok(messages::exchange::ExchangeRequests::SendRecordResponse { .. }) => true,
+                    Err(err) => {
+                        error!("{:?}", err);
+                        false
+                    }
+                    _ => false,
+                }
+            }
+            MessageName::CommonGenericResponse => true,
+            _ => false,
+        }
+    }
+}
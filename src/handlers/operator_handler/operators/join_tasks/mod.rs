@@ -0,0 +1,5 @@
+mod config;
+mod conversions;
+mod hash_join_task;
+
+pub use hash_join_task::HashJoinTaskBuilder;
@@ -0,0 +1,5 @@
+#[derive(Debug)]
+pub struct LimitConfig {
+    pub limit: Option<u64>,
+    pub offset: Option<u64>,
+}
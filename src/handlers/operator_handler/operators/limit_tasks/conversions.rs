@@ -0,0 +1,32 @@
+use anyhow::Result;
+use thiserror::Error;
+
+use crate::{
+    handlers::operator_handler::operator_handler_state::OperatorInstanceConfig,
+    planner::{OperatorTask, OperatorType},
+};
+
+use super::config::LimitConfig;
+
+#[derive(Debug, Error)]
+pub enum TryFromLimitConfigError {
+    #[error("unable to convert")]
+    UnableToConvert,
+}
+
+impl TryFrom<&OperatorInstanceConfig> for LimitConfig {
+    type Error = TryFromLimitConfigError;
+
+    fn try_from(op_in_config: &OperatorInstanceConfig) -> Result<LimitConfig, Self::Error> {
+        match &op_in_config.operator.operator_type {
+            OperatorType::Producer { task, .. } => match task {
+                OperatorTask::Limit { limit, offset } => Ok(LimitConfig {
+                    limit: *limit,
+                    offset: *offset,
+                }),
+                _ => Err(TryFromLimitConfigError::UnableToConvert),
+            },
+            OperatorType::Exchange { .. } => Err(TryFromLimitConfigError::UnableToConvert),
+        }
+    }
+}
@@ -0,0 +1,353 @@
+use anyhow::{Context, Error, Result};
+use std::sync::Arc;
+use thiserror::Error;
+use tracing::{debug, error};
+
+use crate::handlers::message_handler::messages;
+use crate::handlers::message_handler::messages::message::{Message, MessageName};
+use crate::handlers::message_handler::{MessageRegistry, Pipe};
+use crate::handlers::{
+    message_router_handler::MessageConsumer,
+    operator_handler::{
+        operator_handler_state::OperatorInstanceConfig,
+        operators::{
+            operator_task_trackers::RestrictedOperatorTaskTracker, requests, traits::TaskBuilder,
+            ConnectionRegistry,
+        },
+    },
+};
+
+use super::config::LimitConfig;
+
+#[derive(Debug, Error)]
+pub enum LimitTaskError {
+    #[error("more than one exchange is currently not implement")]
+    MoreThanOneExchangeIsCurrentlyNotImplemented,
+}
+
+// (exchange operator instance id, exchange worker id)
+type ExchangeLocation = (u128, u128);
+
+#[derive(Debug)]
+struct LimitTask {
+    operator_instance_config: OperatorInstanceConfig,
+    limit_config: LimitConfig,
+
+    operator_pipe: Pipe,
+    msg_reg: Arc<MessageRegistry>,
+
+    outbound_exchange_worker_id: Option<u128>,
+    outbound_exchange_operator_instance_id: Option<u128>,
+    record_id: u64,
+}
+
+impl LimitTask {
+    fn new(
+        op_in_config: OperatorInstanceConfig,
+        limit_config: LimitConfig,
+        operator_pipe: Pipe,
+        msg_reg: Arc<MessageRegistry>,
+    ) -> LimitTask {
+        LimitTask {
+            operator_instance_config: op_in_config,
+            limit_config,
+            operator_pipe,
+            msg_reg,
+            outbound_exchange_worker_id: None,
+            outbound_exchange_operator_instance_id: None,
+            record_id: 0,
+        }
+    }
+
+    fn consumer(&self) -> Box<dyn MessageConsumer> {
+        Box::new(LimitConsumer {
+            msg_reg: self.msg_reg.clone(),
+        })
+    }
+
+    async fn async_main(&mut self, ct: tokio_util::sync::CancellationToken) -> Result<()> {
+        debug!(
+            operator_task = self
+                .operator_instance_config
+                .operator
+                .operator_type
+                .task_name(),
+            operator_id = self.operator_instance_config.operator.id,
+            operator_instance_id = self.operator_instance_config.id,
+            "started task",
+        );
+
+        let mut rows_to_skip = self.limit_config.offset.unwrap_or(0) as usize;
+        let mut rows_remaining = self.limit_config.limit.map(|limit| limit as usize);
+
+        if rows_remaining == Some(0) {
+            debug!("limit is zero; no records requested");
+            return Ok(());
+        }
+
+        // find the inbound exchange
+        let pipe = &mut self.operator_pipe;
+        let req = requests::IdentifyExchangeRequest::request_inbound_exchanges(
+            &self.operator_instance_config,
+            pipe,
+            self.msg_reg.clone(),
+        );
+        let inbound_exchange = tokio::select! {
+            resp = req => {
+                let resp = resp?;
+                if resp.len() != 1 {
+                    return Err(LimitTaskError::MoreThanOneExchangeIsCurrentlyNotImplemented.into());
+                }
+                (resp[0].exchange_operator_instance_id, resp[0].exchange_worker_id)
+            }
+            _ = ct.cancelled() => {
+                return Ok(());
+            }
+        };
+
+        loop {
+            if ct.is_cancelled() {
+                break;
+            }
+
+            // stop requesting records once the limit has been reached
+            // so the upstream operators can stop early
+            if rows_remaining == Some(0) {
+                debug!("reached the limit; no more records requested");
+                break;
+            }
+
+            let (record_id, record, table_aliases) =
+                match self.next_record(inbound_exchange).await? {
+                    Some(resp) => resp,
+                    None => {
+                        debug!("read all records from the exchange");
+                        break;
+                    }
+                };
+
+            let skipped = std::cmp::min(rows_to_skip, record.num_rows());
+            rows_to_skip -= skipped;
+            let mut length = record.num_rows() - skipped;
+            if let Some(remaining) = rows_remaining {
+                length = std::cmp::min(length, remaining);
+                rows_remaining = Some(remaining - length);
+            }
+
+            if length > 0 {
+                self.send_record(record.slice(skipped, length), table_aliases)
+                    .await
+                    .context("unable to send record to the exchange")?;
+            }
+
+            self.confirm_record(inbound_exchange, record_id).await?;
+        }
+
+        debug!(
+            operator_task = self
+                .operator_instance_config
+                .operator
+                .operator_type
+                .task_name(),
+            operator_id = self.operator_instance_config.operator.id,
+            operator_instance_id = self.operator_instance_config.id,
+            "closed task",
+        );
+        Ok(())
+    }
+
+    // Returns None once the exchange has no records left.
+    async fn next_record(
+        &mut self,
+        exchange: ExchangeLocation,
+    ) -> Result<Option<(u64, Arc<arrow::array::RecordBatch>, Vec<Vec<String>>)>> {
+        loop {
+            let resp = requests::GetNextRecordRequest::get_next_record_request(
+                self.operator_instance_config.operator.id.clone(),
+                exchange.0,
+                exchange.1,
+                &mut self.operator_pipe,
+                self.msg_reg.clone(),
+            )
+            .await?;
+
+            match resp {
+                requests::GetNextRecordResponse::Record {
+                    record_id,
+                    record,
+                    table_aliases,
+                } => {
+                    return Ok(Some((record_id, record, table_aliases)));
+                }
+                requests::GetNextRecordResponse::NoneLeft => {
+                    return Ok(None);
+                }
+                requests::GetNextRecordResponse::NoneAvailable => {
+                    debug!("exchange does not have any record available; waiting 100 milliseconds");
+                    tokio::time::sleep(chrono::Duration::milliseconds(100).to_std()?).await;
+                }
+            }
+        }
+    }
+
+    async fn confirm_record(&mut self, exchange: ExchangeLocation, record_id: u64) -> Result<()> {
+        requests::OperatorCompletedRecordProcessingRequest::request(
+            self.operator_instance_config.operator.id.clone(),
+            record_id,
+            exchange.0,
+            exchange.1,
+            &mut self.operator_pipe,
+            self.msg_reg.clone(),
+        )
+        .await
+    }
+
+    async fn send_record(
+        &mut self,
+        record: arrow::array::RecordBatch,
+        table_aliases: Vec<Vec<String>>,
+    ) -> Result<()> {
+        if self.outbound_exchange_worker_id.is_none() {
+            let pipe = &mut self.operator_pipe;
+            let resp = requests::IdentifyExchangeRequest::request_outbound_exchange(
+                &self.operator_instance_config,
+                pipe,
+                self.msg_reg.clone(),
+            )
+            .await?;
+            self.outbound_exchange_operator_instance_id = Some(resp.exchange_operator_instance_id);
+            self.outbound_exchange_worker_id = Some(resp.exchange_worker_id);
+        }
+
+        assert!(self.outbound_exchange_worker_id.is_some());
+
+        let msg_record_id = self.next_record_id();
+        let pipe = &mut self.operator_pipe;
+        requests::SendRecordRequest::send_record_request(
+            msg_record_id,
+            record,
+            table_aliases,
+            self.outbound_exchange_operator_instance_id.unwrap(),
+            self.outbound_exchange_worker_id.unwrap(),
+            pipe,
+            self.msg_reg.clone(),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    fn next_record_id(&mut self) -> u64 {
+        let record_id = self.record_id;
+        self.record_id += 1;
+        record_id
+    }
+}
+
+//////////////////////////////////////////////////////
+// Limit Producer Builder
+
+#[derive(Debug, Clone)]
+pub struct LimitTaskBuilder {}
+
+impl LimitTaskBuilder {
+    pub fn new() -> LimitTaskBuilder {
+        LimitTaskBuilder {}
+    }
+}
+
+impl TaskBuilder for LimitTaskBuilder {
+    fn build(
+        &self,
+        op_in_config: OperatorInstanceConfig,
+        operator_pipe: Pipe,
+        msg_reg: Arc<MessageRegistry>,
+        _: Arc<ConnectionRegistry>,
+        tt: &mut RestrictedOperatorTaskTracker,
+        ct: tokio_util::sync::CancellationToken,
+    ) -> Result<(
+        tokio::sync::oneshot::Receiver<Option<Error>>,
+        Box<dyn MessageConsumer>,
+    )> {
+        let limit_config = LimitConfig::try_from(&op_in_config)?;
+        let mut op = LimitTask::new(op_in_config, limit_config, operator_pipe, msg_reg.clone());
+
+        let consumer = op.consumer();
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        tt.spawn(async move {
+            if let Err(err) = op.async_main(ct).await {
+                error!("{:?}", err);
+                if let Err(err_send) = tx.send(Some(err)) {
+                    error!("{:?}", err_send);
+                }
+            } else if let Err(err_send) = tx.send(None) {
+                error!("{:?}", err_send);
+            }
+        })?;
+
+        Ok((rx, consumer))
+    }
+}
+
+//////////////////////////////////////////////////////
+// Message Consumer
+
+#[derive(Debug, Clone)]
+pub struct LimitConsumer {
+    msg_reg: Arc<MessageRegistry>,
+}
+
+impl MessageConsumer for LimitConsumer {
+    fn consumes_message(&self, msg: &Message) -> bool {
+        match msg.msg.msg_name() {
+            // used to find the exchanges
+            MessageName::Ping => match self.msg_reg.try_cast_msg::<messages::common::Ping>(msg) {
+                Ok(messages::common::Ping::Ping) => false,
+                Ok(messages::common::Ping::Pong) => true,
+                Err(err) => {
+                    error!("{:?}", err);
+                    false
+                }
+            },
+            MessageName::QueryHandlerRequests => {
+                match self
+                    .msg_reg
+                    .try_cast_msg::<messages::query::QueryHandlerRequests>(msg)
+                {
+                    Ok(messages::query::QueryHandlerRequests::ListOperatorInstancesResponse {
+                        ..
+                    }) => true,
+                    Ok(messages::query::QueryHandlerRequests::ListOperatorInstancesRequest {
+                        ..
+                    }) => false,
+                    Err(err) => {
+                        error!("{:?}", err);
+                        false
+                    }
+                }
+            }
+            MessageName::ExchangeRequests => {
+                match self
+                    .msg_reg
+                    .try_cast_msg::<messages::exchange::ExchangeRequests>(msg)
+                {
+                    Ok(messages::exchange::ExchangeRequests::GetNextRecordResponseRecord {
+                        ..
+                    }) => true,
+                    Ok(messages::exchange::ExchangeRequests::GetNextRecordResponseNoneLeft) => true,
+                    Ok(messages::exchange::ExchangeRequests::GetNextRecordResponseNoneAvailable) => true,
+                    Ok(messages::exchange::ExchangeRequests::OperatorCompletedRecordProcessingResponse) => true,
+                    Ok(messages::exchange::ExchangeRequests::SendRecordResponse { .. }) => true,
+                    Err(err) => {
+                        error!("{:?}", err);
+                        false
+                    }
+                    _ => false,
+                }
+            }
+            MessageName::CommonGenericResponse => true,
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,8 @@
+mod config;
+mod conversions;
+mod limit_task;
+
+#[cfg(test)]
+mod test_limit_task;
+
+pub use limit_task::LimitTaskBuilder;
@@ -0,0 +1,222 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::{Int64Array, RecordBatch};
+use arrow::datatypes::{DataType, Field, Schema};
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+
+use crate::handlers::message_handler::messages;
+use crate::handlers::message_handler::messages::message::MessageName;
+use crate::handlers::message_handler::{MessageRegistry, Pipe};
+use crate::handlers::operator_handler::operator_handler_state::{
+    OperatorInstanceConfig, RecordHeartbeat, DEFAULT_EXCHANGE_MAX_BUFFERED_BYTES,
+    DEFAULT_IDENTIFY_EXCHANGE_MAX_ATTEMPTS,
+};
+use crate::handlers::operator_handler::operators::{
+    operator_task_trackers::RestrictedOperatorTaskTracker, traits::TaskBuilder, ConnectionRegistry,
+    NanPolicy,
+};
+use crate::planner::{Operator, OperatorCompute, OperatorTask, OperatorType};
+
+use super::limit_task::LimitTaskBuilder;
+
+const ROWS_PER_RECORD: i64 = 10;
+
+fn build_record(record_idx: i64) -> Result<RecordBatch> {
+    let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+    let start = record_idx * ROWS_PER_RECORD;
+    Ok(RecordBatch::try_new(
+        schema,
+        vec![Arc::new(Int64Array::from_iter_values(
+            start..start + ROWS_PER_RECORD,
+        ))],
+    )?)
+}
+
+fn build_op_in_config(limit: Option<u64>, offset: Option<u64>) -> OperatorInstanceConfig {
+    OperatorInstanceConfig {
+        id: 1,
+        query_id: 2,
+        pipeline_id: "pipeline".to_string(),
+        instance_idx: 0,
+        operator: Operator {
+            id: "operator_p1_producer".to_string(),
+            plan_id: 1,
+            operator_type: OperatorType::Producer {
+                task: OperatorTask::Limit { limit, offset },
+                outbound_exchange_id: "operator_p1_exchange".to_string(),
+                inbound_exchange_ids: vec!["operator_p0_exchange".to_string()],
+            },
+            compute: OperatorCompute {
+                instances: 1,
+                cpu_in_thousandths: 1000,
+                memory_in_mib: 512,
+            },
+        },
+        record_heartbeat: RecordHeartbeat::default(),
+        exchange_max_buffered_bytes: DEFAULT_EXCHANGE_MAX_BUFFERED_BYTES,
+        identify_exchange_max_attempts: DEFAULT_IDENTIFY_EXCHANGE_MAX_ATTEMPTS,
+        nan_policy: NanPolicy::default(),
+    }
+}
+
+struct ExchangeStats {
+    get_next_record_requests: usize,
+    sent_ids: Vec<i64>,
+}
+
+// Acts as both the inbound and outbound exchange of the task. The inbound
+// side has num_records records and the ids of the rows sent to the
+// outbound side are collected.
+async fn run_exchange(
+    mut pipe: Pipe,
+    msg_reg: Arc<MessageRegistry>,
+    num_records: i64,
+) -> Result<ExchangeStats> {
+    let exchange_operator_instance_id = 10;
+    let exchange_worker_id = 20;
+    let mut stats = ExchangeStats {
+        get_next_record_requests: 0,
+        sent_ids: Vec::new(),
+    };
+
+    while let Some(msg) = pipe.recv().await {
+        let resp = match msg.msg.msg_name() {
+            MessageName::QueryHandlerRequests => msg.reply(Box::new(
+                messages::query::QueryHandlerRequests::ListOperatorInstancesResponse {
+                    op_instance_ids: vec![exchange_operator_instance_id],
+                },
+            )),
+            MessageName::Ping => msg
+                .reply(Box::new(messages::common::Ping::Pong))
+                .set_sent_from_worker_id(exchange_worker_id),
+            MessageName::ExchangeRequests => {
+                let cast_msg: &messages::exchange::ExchangeRequests = msg_reg.try_cast_msg(&msg)?;
+                match cast_msg {
+                    messages::exchange::ExchangeRequests::GetNextRecordRequest { .. } => {
+                        let record_idx = stats.get_next_record_requests as i64;
+                        stats.get_next_record_requests += 1;
+                        if record_idx < num_records {
+                            msg.reply(Box::new(
+                                messages::exchange::ExchangeRequests::GetNextRecordResponseRecord {
+                                    record_id: record_idx as u64,
+                                    record: Arc::new(build_record(record_idx)?),
+                                    table_aliases: vec![Vec::new()],
+                                },
+                            ))
+                        } else {
+                            msg.reply(Box::new(
+                                messages::exchange::ExchangeRequests::GetNextRecordResponseNoneLeft,
+                            ))
+                        }
+                    }
+                    messages::exchange::ExchangeRequests::SendRecordRequest {
+                        record_id,
+                        record,
+                        ..
+                    } => {
+                        let ids = record
+                            .column(0)
+                            .as_any()
+                            .downcast_ref::<Int64Array>()
+                            .unwrap();
+                        stats.sent_ids.extend(ids.values().iter());
+                        msg.reply(Box::new(
+                            messages::exchange::ExchangeRequests::SendRecordResponse {
+                                record_id: *record_id,
+                            },
+                        ))
+                    }
+                    messages::exchange::ExchangeRequests::OperatorCompletedRecordProcessingRequest {
+                        ..
+                    } => msg.reply(Box::new(
+                        messages::exchange::ExchangeRequests::OperatorCompletedRecordProcessingResponse,
+                    )),
+                    _ => panic!("unexpected exchange request: {}", msg),
+                }
+            }
+            _ => panic!("unexpected message: {}", msg),
+        };
+        pipe.send(resp).await?;
+    }
+
+    Ok(stats)
+}
+
+#[tokio::test]
+async fn test_limit_task_stops_requesting_records() -> Result<()> {
+    struct TestCase {
+        case_name: &'static str,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        expected_get_next_record_requests: usize,
+        expected_ids: Vec<i64>,
+    }
+
+    // the exchange has 5 records of 10 rows each
+    let test_cases = vec![
+        TestCase {
+            case_name: "limit-within-the-first-record",
+            limit: Some(5),
+            offset: None,
+            expected_get_next_record_requests: 1,
+            expected_ids: (0..5).collect(),
+        },
+        TestCase {
+            case_name: "limit-with-offset-spanning-records",
+            limit: Some(12),
+            offset: Some(15),
+            expected_get_next_record_requests: 3,
+            expected_ids: (15..27).collect(),
+        },
+        TestCase {
+            case_name: "limit-of-zero",
+            limit: Some(0),
+            offset: None,
+            expected_get_next_record_requests: 0,
+            expected_ids: Vec::new(),
+        },
+        TestCase {
+            case_name: "offset-without-limit-reads-every-record",
+            limit: None,
+            offset: Some(45),
+            expected_get_next_record_requests: 6,
+            expected_ids: (45..50).collect(),
+        },
+    ];
+
+    for test_case in test_cases {
+        println!("test case: {}", test_case.case_name);
+
+        let msg_reg = Arc::new(MessageRegistry::new());
+        let (task_pipe, exchange_pipe) = Pipe::new(10);
+        let exchange = tokio::spawn(run_exchange(exchange_pipe, msg_reg.clone(), 5));
+
+        let tt = TaskTracker::new();
+        let mut restricted_tt = RestrictedOperatorTaskTracker::new(&tt, 1);
+        let (task_res, _) = LimitTaskBuilder::new().build(
+            build_op_in_config(test_case.limit, test_case.offset),
+            task_pipe,
+            msg_reg.clone(),
+            Arc::new(ConnectionRegistry::new()),
+            &mut restricted_tt,
+            CancellationToken::new(),
+        )?;
+
+        // the task reports completion, which the producer operator sends
+        // on as a completed status change, even though the exchange
+        // still has records left
+        let task_err = tokio::time::timeout(std::time::Duration::from_secs(5), task_res).await??;
+        assert!(task_err.is_none());
+
+        let stats = exchange.await??;
+        assert_eq!(
+            stats.get_next_record_requests,
+            test_case.expected_get_next_record_requests
+        );
+        assert_eq!(stats.sent_ids, test_case.expected_ids);
+    }
+
+    Ok(())
+}
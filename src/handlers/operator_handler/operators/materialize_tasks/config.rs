@@ -1,10 +1,37 @@
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+
 use crate::planner;
 
 #[derive(Debug)]
 pub struct MaterializeFilesConfig {
     pub data_format: planner::DataFormat,
     pub fields: Vec<sqlparser::ast::SelectItem>,
+    // rows buffered before a file is written
+    pub target_file_rows: usize,
+    pub partition_by: Vec<String>,
+    pub max_open_partitions: usize,
+
+    // parquet writer properties
+    pub row_group_size: usize,
+    pub compression: planner::ParquetCompression,
+    pub data_page_size: usize,
+
+    // name of the registered storage connection
+    pub connection: String,
+}
 
-    pub outbound_exchange_id: String,
-    pub inbound_exchange_ids: Vec<String>,
+impl MaterializeFilesConfig {
+    pub fn parquet_writer_properties(&self) -> WriterProperties {
+        let compression = match self.compression {
+            planner::ParquetCompression::Snappy => Compression::SNAPPY,
+            planner::ParquetCompression::Zstd => Compression::ZSTD(ZstdLevel::default()),
+            planner::ParquetCompression::Uncompressed => Compression::UNCOMPRESSED,
+        };
+        WriterProperties::builder()
+            .set_max_row_group_size(self.row_group_size)
+            .set_compression(compression)
+            .set_data_page_size_limit(self.data_page_size)
+            .build()
+    }
 }
@@ -21,19 +21,25 @@ impl TryFrom<&OperatorInstanceConfig> for MaterializeFilesConfig {
         op_in_config: &OperatorInstanceConfig,
     ) -> Result<MaterializeFilesConfig, Self::Error> {
         match &op_in_config.operator.operator_type {
-            OperatorType::Producer {
-                task,
-                outbound_exchange_id,
-                inbound_exchange_ids,
-            } => match task {
+            OperatorType::Producer { task, .. } => match task {
                 OperatorTask::MaterializeFiles {
                     data_format,
+                    parquet_writer,
+                    target_file_rows,
+                    partition_by,
+                    max_open_partitions,
                     fields,
+                    connection,
                 } => Ok(MaterializeFilesConfig {
                     data_format: data_format.clone(),
                     fields: fields.clone(),
-                    outbound_exchange_id: outbound_exchange_id.clone(),
-                    inbound_exchange_ids: inbound_exchange_ids.clone(),
+                    target_file_rows: *target_file_rows,
+                    partition_by: partition_by.clone(),
+                    max_open_partitions: *max_open_partitions,
+                    row_group_size: parquet_writer.row_group_size,
+                    compression: parquet_writer.compression.clone(),
+                    data_page_size: parquet_writer.data_page_size,
+                    connection: connection.clone(),
                 }),
                 _ => Err(TryFromMaterializeFilesConfigError::UnableToConvert.into()),
             },
@@ -0,0 +1,161 @@
+use anyhow::Result;
+use arrow::datatypes::Schema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::planner::DataFormat;
+
+// Each materialize operator instance writes its own manifest since the
+// instances do not know about the files written by the others.
+pub const MANIFEST_FILE_PREFIX: &str = "_manifest_";
+// Written by the query handler once every materialize instance has
+// completed; the results aren't readable until it exists.
+pub const RESULTS_COMPLETION_FILE_NAME: &str = "_complete.json";
+
+pub fn query_results_dir(query_id: u128) -> String {
+    format!("/query_results/{}/", Uuid::from_u128(query_id))
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestField {
+    pub name: String,
+    pub data_type: String,
+    pub nullable: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestFile {
+    // path relative to the query results directory
+    pub path: String,
+    pub num_rows: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MaterializeManifest {
+    pub operator_instance_id: u128,
+    pub data_format: DataFormat,
+    pub fields: Vec<ManifestField>,
+    pub files: Vec<ManifestFile>,
+}
+
+impl MaterializeManifest {
+    pub fn new(operator_instance_id: u128, data_format: DataFormat) -> MaterializeManifest {
+        MaterializeManifest {
+            operator_instance_id,
+            data_format,
+            fields: Vec::new(),
+            files: Vec::new(),
+        }
+    }
+
+    // The schema is taken from the first file added.
+    pub fn add_file(&mut self, path: String, num_rows: usize, schema: &Schema) {
+        if self.files.is_empty() {
+            self.fields = schema
+                .fields()
+                .iter()
+                .map(|field| ManifestField {
+                    name: field.name().clone(),
+                    data_type: field.data_type().to_string(),
+                    nullable: field.is_nullable(),
+                })
+                .collect();
+        }
+        self.files.push(ManifestFile { path, num_rows });
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.files.iter().map(|file| file.num_rows).sum()
+    }
+
+    // The manifest is written to a temporary key and then renamed so a
+    // reader never sees a partially written manifest. Services which
+    // can't rename copy the temporary key instead, and services which
+    // can do neither, like memory, store each write as a whole.
+    pub async fn write(&self, storage_conn: &opendal::Operator, query_id: u128) -> Result<()> {
+        let results_dir = query_results_dir(query_id);
+        let manifest_path = format!(
+            "{}{}{}.json",
+            results_dir, MANIFEST_FILE_PREFIX, self.operator_instance_id
+        );
+        let tmp_path = format!("{}.tmp", manifest_path);
+
+        let capability = storage_conn.info().full_capability();
+        if !capability.rename && !capability.copy {
+            storage_conn
+                .write(&manifest_path, serde_json::to_vec(self)?)
+                .await?;
+            return Ok(());
+        }
+
+        storage_conn
+            .write(&tmp_path, serde_json::to_vec(self)?)
+            .await?;
+
+        if capability.rename {
+            storage_conn.rename(&tmp_path, &manifest_path).await?;
+        } else {
+            storage_conn.copy(&tmp_path, &manifest_path).await?;
+            storage_conn.delete(&tmp_path).await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResultsCompletion {
+    // one manifest is written by each materialize operator instance
+    pub num_manifests: usize,
+}
+
+impl ResultsCompletion {
+    pub fn new(num_manifests: usize) -> ResultsCompletion {
+        ResultsCompletion { num_manifests }
+    }
+
+    pub async fn write(&self, storage_conn: &opendal::Operator, query_id: u128) -> Result<()> {
+        let path = format!(
+            "{}{}",
+            query_results_dir(query_id),
+            RESULTS_COMPLETION_FILE_NAME
+        );
+        storage_conn.write(&path, serde_json::to_vec(self)?).await?;
+        Ok(())
+    }
+}
+
+// Returns None if the query hasn't completed.
+pub async fn read_results_completion(
+    storage_conn: &opendal::Operator,
+    query_id: u128,
+) -> Result<Option<ResultsCompletion>> {
+    let path = format!(
+        "{}{}",
+        query_results_dir(query_id),
+        RESULTS_COMPLETION_FILE_NAME
+    );
+    match storage_conn.read(&path).await {
+        Ok(data) => Ok(Some(serde_json::from_slice(&data.to_vec())?)),
+        Err(err) if err.kind() == opendal::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+// Read the manifests of every materialize operator instance of the query.
+pub async fn read_manifests(
+    storage_conn: &opendal::Operator,
+    query_id: u128,
+) -> Result<Vec<MaterializeManifest>> {
+    let results_dir = query_results_dir(query_id);
+    let mut manifests = Vec::new();
+    for entry in storage_conn.list(&results_dir).await? {
+        let name = entry.name();
+        if !name.starts_with(MANIFEST_FILE_PREFIX) || !name.ends_with(".json") {
+            continue;
+        }
+        let data = storage_conn.read(entry.path()).await?.to_vec();
+        manifests.push(serde_json::from_slice::<MaterializeManifest>(&data)?);
+    }
+    manifests.sort_by_key(|manifest| manifest.operator_instance_id);
+    Ok(manifests)
+}
@@ -1,8 +1,8 @@
 use anyhow::{Error, Result};
+use arrow::array::RecordBatch;
 use std::{path::PathBuf, sync::Arc};
 use thiserror::Error;
 use tracing::{debug, error};
-use uuid::Uuid;
 
 use crate::handlers::message_handler::messages;
 use crate::handlers::message_handler::messages::message::{Message, MessageName};
@@ -12,13 +12,19 @@ use crate::handlers::{
     operator_handler::{
         operator_handler_state::OperatorInstanceConfig,
         operators::{
-            operator_task_trackers::RestrictedOperatorTaskTracker, record_utils, requests,
-            traits::TaskBuilder, ConnectionRegistry,
+            operator_metrics::OperatorMetricsRecorder,
+            operator_task_trackers::RestrictedOperatorTaskTracker,
+            record_utils::{self, RecordComputeQueue},
+            requests,
+            traits::TaskBuilder,
+            ConnectionRegistry,
         },
     },
 };
 
 use super::config::MaterializeFilesConfig;
+use super::manifest::{self, MaterializeManifest};
+use super::record_files;
 
 #[derive(Debug, Error)]
 pub enum MaterializeFilesTaskError {
@@ -39,6 +45,13 @@ struct MaterializeFilesTask {
 
     exchange_worker_id: Option<u128>,
     exchange_operator_instance_id: Option<u128>,
+    // tells the exchange the records which haven't been confirmed are
+    // still being processed
+    heartbeat: Option<requests::RecordProcessingHeartbeat>,
+    metrics: OperatorMetricsRecorder,
+    // records are pushed to the clients subscribed to the query results
+    // until the query handler reports there aren't any
+    stream_results: bool,
 }
 
 impl MaterializeFilesTask {
@@ -57,6 +70,9 @@ impl MaterializeFilesTask {
             conn_reg,
             exchange_worker_id: None,
             exchange_operator_instance_id: None,
+            heartbeat: None,
+            metrics: OperatorMetricsRecorder::new(),
+            stream_results: true,
         }
     }
 
@@ -78,8 +94,9 @@ impl MaterializeFilesTask {
             "started task",
         );
 
-        // get the default connection
-        let storage_conn = self.conn_reg.get_operator("default")?;
+        let storage_conn = self
+            .conn_reg
+            .get_operator(&self.materialize_file_config.connection)?;
 
         // find the exchange
         let ref mut pipe = self.operator_pipe;
@@ -112,85 +129,146 @@ impl MaterializeFilesTask {
         assert!(self.exchange_operator_instance_id.is_some());
         assert!(self.exchange_worker_id.is_some());
 
-        let query_uuid_id = Uuid::from_u128(self.operator_instance_config.query_id.clone());
+        self.heartbeat = Some(requests::RecordProcessingHeartbeat::start(
+            self.operator_instance_config.operator.id.clone(),
+            self.exchange_operator_instance_id.unwrap(),
+            self.exchange_worker_id.unwrap(),
+            self.operator_instance_config.record_heartbeat.interval,
+            self.operator_pipe.sender(),
+        ));
 
         // loop over all records in the exchange
-        let ref mut operator_pipe = self.operator_pipe;
+        let mut manifest = MaterializeManifest::new(
+            self.operator_instance_config.id,
+            self.materialize_file_config.data_format.clone(),
+        );
+        let mut rec_buffers = record_files::PartitionedRecordFileBuffers::new(
+            self.materialize_file_config.partition_by.clone(),
+            self.materialize_file_config.target_file_rows,
+            self.materialize_file_config.max_open_partitions,
+        );
+        // the records are projected on the blocking thread pool while
+        // the next records are requested
+        let mut projections: RecordComputeQueue<(u64, RecordBatch)> =
+            RecordComputeQueue::for_compute(&self.operator_instance_config.operator.compute);
 
         loop {
+            if projections.is_full() {
+                if let Some(res) = projections.next().await {
+                    let (record_id, proj_rec) = res?;
+                    self.materialize_record(
+                        &storage_conn,
+                        &mut manifest,
+                        &mut rec_buffers,
+                        record_id,
+                        proj_rec,
+                    )
+                    .await?;
+                }
+                continue;
+            }
+
             let resp = requests::GetNextRecordRequest::get_next_record_request(
                 self.operator_instance_config.operator.id.clone(),
-                self.exchange_operator_instance_id.unwrap().clone(),
-                self.exchange_worker_id.unwrap().clone(),
-                operator_pipe,
+                self.exchange_operator_instance_id.unwrap(),
+                self.exchange_worker_id.unwrap(),
+                &mut self.operator_pipe,
                 self.msg_reg.clone(),
             )
             .await?;
 
-            match &resp {
+            match resp {
                 requests::GetNextRecordResponse::Record {
                     record_id,
                     record,
                     table_aliases,
                 } => {
-                    // TODO: implement heartbeat for record processing
-                    // TODO: use thread-pool for record operations
-                    // evalute the expressions for each column and materialize the result
-                    // to a parquet file
-                    let proj_rec = record_utils::project_record(
-                        &self.materialize_file_config.fields,
-                        record.clone(),
-                        table_aliases,
-                    )?;
-
-                    // materialize the projected record
-                    let mut rec_path_buf = PathBuf::from("/query_results");
-                    rec_path_buf.push(format!("{}", query_uuid_id));
-                    rec_path_buf.push(format!("rec_{}.parquet", record_id));
-                    let rec_path = if let Some(rec_path) = rec_path_buf.to_str() {
-                        rec_path
-                    } else {
-                        return Err(
-                            MaterializeFilesTaskError::RecordPathFormattingReturnedNoneResult
-                                .into(),
-                        );
-                    };
-                    let writer = storage_conn
-                        .writer_with(rec_path)
-                        .chunk(16 * 1024 * 1024)
-                        .concurrent(4)
-                        .await?;
-
-                    let parquet_writer = parquet_opendal::AsyncWriter::new(writer);
-                    let mut arrow_parquet_writer = parquet::arrow::AsyncArrowWriter::try_new(
-                        parquet_writer,
-                        proj_rec.schema(),
-                        None,
-                    )?;
-                    arrow_parquet_writer.write(&proj_rec).await?;
-                    arrow_parquet_writer.close().await?;
-
-                    // confirm processing of the record
-                    requests::OperatorCompletedRecordProcessingRequest::request(
-                        self.operator_instance_config.operator.id.clone(),
-                        record_id.clone(),
-                        self.exchange_operator_instance_id.unwrap().clone(),
-                        self.exchange_worker_id.unwrap().clone(),
-                        operator_pipe,
-                        self.msg_reg.clone(),
-                    )
-                    .await?;
+                    if let Some(heartbeat) = &self.heartbeat {
+                        heartbeat.add(record_id);
+                    }
+                    // evalute the expressions for each column; the result
+                    // is materialized to a file in the configured data format
+                    self.metrics.record_in(&record);
+                    let fields = self.materialize_file_config.fields.clone();
+                    projections.push(move || {
+                        let proj_rec =
+                            record_utils::project_record(&fields, record, &table_aliases)?;
+                        Ok((record_id, proj_rec))
+                    });
                 }
                 requests::GetNextRecordResponse::NoneLeft => {
-                    debug!("complete materialization; read all records from the exchange");
+                    while let Some(res) = projections.next().await {
+                        let (record_id, proj_rec) = res?;
+                        self.materialize_record(
+                            &storage_conn,
+                            &mut manifest,
+                            &mut rec_buffers,
+                            record_id,
+                            proj_rec,
+                        )
+                        .await?;
+                    }
+
+                    for (partition_dir, first_record_id, rec) in rec_buffers.flush()? {
+                        self.metrics.record_out(&rec);
+                        Self::write_file(
+                            &storage_conn,
+                            &self.materialize_file_config,
+                            &mut manifest,
+                            self.operator_instance_config.query_id,
+                            &partition_dir,
+                            first_record_id,
+                            rec,
+                        )
+                        .await?;
+                    }
+                    manifest
+                        .write(&storage_conn, self.operator_instance_config.query_id)
+                        .await?;
+                    if self.stream_results {
+                        requests::query::StreamQueryResultsRequest::end_request(
+                            self.operator_instance_config.query_id,
+                            self.operator_instance_config.id,
+                            &mut self.operator_pipe,
+                            self.msg_reg.clone(),
+                        )
+                        .await?;
+                    }
+                    debug!(
+                        num_files = manifest.files.len(),
+                        num_rows = manifest.num_rows(),
+                        "complete materialization; read all records from the exchange"
+                    );
                     break;
                 }
                 requests::GetNextRecordResponse::NoneAvailable => {
+                    // finish a projected record instead of waiting
+                    if let Some(res) = projections.next().await {
+                        let (record_id, proj_rec) = res?;
+                        self.materialize_record(
+                            &storage_conn,
+                            &mut manifest,
+                            &mut rec_buffers,
+                            record_id,
+                            proj_rec,
+                        )
+                        .await?;
+                        continue;
+                    }
                     debug!("exchange does not have any record available; waiting 1 second");
                     tokio::time::sleep(chrono::Duration::milliseconds(100).to_std()?).await;
                 }
             }
         }
+        self.heartbeat = None;
+
+        self.metrics
+            .report(
+                &self.operator_instance_config,
+                &mut self.operator_pipe,
+                self.msg_reg.clone(),
+            )
+            .await;
 
         debug!(
             operator_task = self
@@ -204,6 +282,98 @@ impl MaterializeFilesTask {
         );
         Ok(())
     }
+
+    // Streams the projected record to the subscribed clients, buffers it
+    // and writes the files which have reached the target number of rows
+    // before confirming the record was processed.
+    async fn materialize_record(
+        &mut self,
+        storage_conn: &opendal::Operator,
+        manifest: &mut MaterializeManifest,
+        rec_buffers: &mut record_files::PartitionedRecordFileBuffers,
+        record_id: u64,
+        proj_rec: RecordBatch,
+    ) -> Result<()> {
+        if self.stream_results {
+            self.stream_results = requests::query::StreamQueryResultsRequest::record_request(
+                self.operator_instance_config.query_id,
+                Arc::new(proj_rec.clone()),
+                &mut self.operator_pipe,
+                self.msg_reg.clone(),
+            )
+            .await?;
+        }
+
+        let files = rec_buffers.push(record_id, proj_rec)?;
+        for (partition_dir, first_record_id, rec) in files {
+            self.metrics.record_out(&rec);
+            Self::write_file(
+                storage_conn,
+                &self.materialize_file_config,
+                manifest,
+                self.operator_instance_config.query_id,
+                &partition_dir,
+                first_record_id,
+                rec,
+            )
+            .await?;
+        }
+
+        // confirm processing of the record
+        requests::OperatorCompletedRecordProcessingRequest::request(
+            self.operator_instance_config.operator.id.clone(),
+            record_id,
+            self.exchange_operator_instance_id.unwrap(),
+            self.exchange_worker_id.unwrap(),
+            &mut self.operator_pipe,
+            self.msg_reg.clone(),
+        )
+        .await?;
+        if let Some(heartbeat) = &self.heartbeat {
+            heartbeat.remove(record_id);
+        }
+        Ok(())
+    }
+
+    async fn write_file(
+        storage_conn: &opendal::Operator,
+        config: &MaterializeFilesConfig,
+        manifest: &mut MaterializeManifest,
+        query_id: u128,
+        partition_dir: &str,
+        first_record_id: u64,
+        rec: arrow::array::RecordBatch,
+    ) -> Result<()> {
+        // the path relative to the query results directory is kept
+        // in the manifest
+        let mut rec_path_buf = PathBuf::new();
+        if !partition_dir.is_empty() {
+            rec_path_buf.push(partition_dir);
+        }
+        rec_path_buf.push(format!(
+            "rec_{}.{}",
+            first_record_id,
+            config.data_format.file_extension()
+        ));
+        let rel_rec_path = if let Some(rec_path) = rec_path_buf.to_str() {
+            rec_path.to_string()
+        } else {
+            return Err(MaterializeFilesTaskError::RecordPathFormattingReturnedNoneResult.into());
+        };
+        let rec_path = format!("{}{}", manifest::query_results_dir(query_id), rel_rec_path);
+
+        record_files::write_record_file(
+            storage_conn,
+            &rec_path,
+            &config.data_format,
+            config.parquet_writer_properties(),
+            std::slice::from_ref(&rec),
+        )
+        .await?;
+
+        manifest.add_file(rel_rec_path, rec.num_rows(), &rec.schema());
+        Ok(())
+    }
 }
 
 //////////////////////////////////////////////////////
@@ -315,6 +485,10 @@ impl MessageConsumer for MaterializeFilesConsumer {
                     _ => false,
                 }
             }
+            // used to report the metrics
+            MessageName::CommonGenericResponse => true,
+            // used to stream the results
+            MessageName::StreamQueryResultsResp => true,
             // used ...
             _ => false,
         }
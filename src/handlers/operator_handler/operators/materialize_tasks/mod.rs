@@ -1,5 +1,19 @@
 mod config;
 mod conversions;
+mod manifest;
 mod materialize_files_task;
+mod record_files;
 
+#[cfg(test)]
+mod test_manifest;
+#[cfg(test)]
+mod test_record_files;
+#[cfg(all(test, feature = "minio-tests"))]
+mod test_s3_storage;
+
+pub use manifest::{
+    query_results_dir, read_manifests, read_results_completion, MaterializeManifest,
+    ResultsCompletion,
+};
 pub use materialize_files_task::MaterializeFilesTaskBuilder;
+pub use record_files::{read_record_file, record_file_data_format, write_record_file};
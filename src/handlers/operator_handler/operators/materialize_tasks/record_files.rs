@@ -0,0 +1,296 @@
+use std::io::Cursor;
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::RecordBatch;
+use futures::StreamExt;
+use parquet::file::properties::WriterProperties;
+use thiserror::Error;
+
+use crate::handlers::operator_handler::operators::record_utils;
+use crate::planner::DataFormat;
+
+#[derive(Debug, Error)]
+pub enum RecordFilesError {
+    #[error("file path does not have an extension: {0}")]
+    FilePathDoesNotHaveAnExtension(String),
+    #[error("no records to write to file: {0}")]
+    NoRecordsToWrite(String),
+}
+
+// Find the data format of a materialized file from its extension.
+pub fn record_file_data_format(rec_path: &str) -> Result<DataFormat> {
+    match std::path::Path::new(rec_path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+    {
+        Some(ext) => Ok(ext.parse::<DataFormat>()?),
+        None => Err(RecordFilesError::FilePathDoesNotHaveAnExtension(rec_path.to_string()).into()),
+    }
+}
+
+pub async fn write_record_file(
+    storage_conn: &opendal::Operator,
+    rec_path: &str,
+    data_format: &DataFormat,
+    parquet_props: WriterProperties,
+    recs: &[RecordBatch],
+) -> Result<()> {
+    let schema = match recs.first() {
+        Some(rec) => rec.schema(),
+        None => return Err(RecordFilesError::NoRecordsToWrite(rec_path.to_string()).into()),
+    };
+
+    match data_format {
+        DataFormat::Parquet => {
+            let writer = storage_conn
+                .writer_with(rec_path)
+                .chunk(16 * 1024 * 1024)
+                .concurrent(4)
+                .await?;
+
+            let parquet_writer = parquet_opendal::AsyncWriter::new(writer);
+            let mut arrow_parquet_writer = parquet::arrow::AsyncArrowWriter::try_new(
+                parquet_writer,
+                schema,
+                Some(parquet_props),
+            )?;
+            for rec in recs {
+                arrow_parquet_writer.write(rec).await?;
+            }
+            arrow_parquet_writer.close().await?;
+        }
+        DataFormat::Csv => {
+            // the writer emits the header before the first record only
+            let mut buf: Vec<u8> = Vec::new();
+            let mut csv_writer = arrow::csv::WriterBuilder::new()
+                .with_header(true)
+                .build(&mut buf);
+            for rec in recs {
+                csv_writer.write(rec)?;
+            }
+            drop(csv_writer);
+
+            storage_conn.write(rec_path, buf).await?;
+        }
+        DataFormat::JsonLines => {
+            // struct columns are written as nested json objects
+            let mut buf: Vec<u8> = Vec::new();
+            let mut json_writer = arrow::json::LineDelimitedWriter::new(&mut buf);
+            for rec in recs {
+                json_writer.write(rec)?;
+            }
+            json_writer.finish()?;
+
+            storage_conn.write(rec_path, buf).await?;
+        }
+    }
+    Ok(())
+}
+
+// Read all records in a materialized file. The format is chosen from the
+// file extension; csv and json schemas are inferred from the file.
+pub async fn read_record_file(
+    storage_conn: &opendal::Operator,
+    rec_path: &str,
+) -> Result<Vec<RecordBatch>> {
+    let mut records: Vec<RecordBatch> = Vec::new();
+    match record_file_data_format(rec_path)? {
+        DataFormat::Parquet => {
+            let reader = storage_conn.reader_with(rec_path).await?;
+            let content_len = storage_conn.stat(rec_path).await?.content_length();
+            let parquet_reader = parquet_opendal::AsyncReader::new(reader, content_len);
+            let mut rec_stream =
+                parquet::arrow::ParquetRecordBatchStreamBuilder::new(parquet_reader)
+                    .await?
+                    .build()?;
+            while let Some(rec) = rec_stream.next().await {
+                records.push(rec?);
+            }
+        }
+        DataFormat::Csv => {
+            let data = storage_conn.read(rec_path).await?.to_vec();
+            let (schema, _) = arrow::csv::reader::Format::default()
+                .with_header(true)
+                .infer_schema(Cursor::new(&data), None)?;
+            let csv_reader = arrow::csv::ReaderBuilder::new(Arc::new(schema))
+                .with_header(true)
+                .build(Cursor::new(&data))?;
+            for rec in csv_reader {
+                records.push(rec?);
+            }
+        }
+        DataFormat::JsonLines => {
+            let data = storage_conn.read(rec_path).await?.to_vec();
+            let (schema, _) = arrow::json::reader::infer_json_schema(Cursor::new(&data), None)?;
+            let json_reader =
+                arrow::json::ReaderBuilder::new(Arc::new(schema)).build(Cursor::new(&data))?;
+            for rec in json_reader {
+                records.push(rec?);
+            }
+        }
+    }
+    Ok(records)
+}
+
+////////////////////////////////////////////////////////////
+// Record File Buffer
+
+// Accumulates records until there are enough rows to fill a file. Each
+// file is identified by the id of the first record buffered for it.
+#[derive(Debug)]
+pub struct RecordFileBuffer {
+    target_file_rows: usize,
+    records: Vec<RecordBatch>,
+    num_rows: usize,
+    first_record_id: Option<u64>,
+}
+
+impl RecordFileBuffer {
+    pub fn new(target_file_rows: usize) -> RecordFileBuffer {
+        RecordFileBuffer {
+            target_file_rows,
+            records: Vec::new(),
+            num_rows: 0,
+            first_record_id: None,
+        }
+    }
+
+    // Returns the files which are ready to be written. A record with a
+    // different schema than the buffered records starts a new file.
+    pub fn push(&mut self, record_id: u64, rec: RecordBatch) -> Result<Vec<(u64, RecordBatch)>> {
+        let mut files = Vec::new();
+        if let Some(buffered_rec) = self.records.first() {
+            if buffered_rec.schema() != rec.schema() {
+                files.extend(self.flush()?);
+            }
+        }
+
+        if self.first_record_id.is_none() {
+            self.first_record_id = Some(record_id);
+        }
+        self.num_rows += rec.num_rows();
+        self.records.push(rec);
+
+        if self.num_rows >= self.target_file_rows {
+            files.extend(self.flush()?);
+        }
+        Ok(files)
+    }
+
+    pub fn flush(&mut self) -> Result<Option<(u64, RecordBatch)>> {
+        let first_record_id = match self.first_record_id.take() {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+        let records = std::mem::take(&mut self.records);
+        self.num_rows = 0;
+
+        let rec = arrow::compute::concat_batches(&records[0].schema(), &records)?;
+        Ok(Some((first_record_id, rec)))
+    }
+}
+
+////////////////////////////////////////////////////////////
+// Partitioned Record File Buffers
+
+pub const HIVE_DEFAULT_PARTITION: &str = "__HIVE_DEFAULT__";
+
+// Format the hive style directory of a partition; `<col>=<value>` for each
+// partition column. Characters which would change the path are escaped.
+pub fn hive_partition_dir(columns: &[String], values: &[Option<String>]) -> String {
+    let escape = |value: &str| -> String {
+        value
+            .chars()
+            .map(|c| match c {
+                '/' | '\\' | '=' | '%' => format!("%{:02X}", c as u32),
+                _ => c.to_string(),
+            })
+            .collect()
+    };
+    columns
+        .iter()
+        .zip(values.iter())
+        .map(|(column, value)| match value {
+            Some(value) => format!("{}={}", escape(column), escape(value)),
+            None => format!("{}={}", escape(column), HIVE_DEFAULT_PARTITION),
+        })
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+// Splits records by the partition columns and buffers each partition
+// separately. Only max_open_partitions are buffered at once; the oldest
+// partition is flushed to make room for a new one.
+#[derive(Debug)]
+pub struct PartitionedRecordFileBuffers {
+    partition_by: Vec<String>,
+    target_file_rows: usize,
+    max_open_partitions: usize,
+    buffers: Vec<(String, RecordFileBuffer)>,
+}
+
+impl PartitionedRecordFileBuffers {
+    pub fn new(
+        partition_by: Vec<String>,
+        target_file_rows: usize,
+        max_open_partitions: usize,
+    ) -> PartitionedRecordFileBuffers {
+        PartitionedRecordFileBuffers {
+            partition_by,
+            target_file_rows,
+            max_open_partitions: std::cmp::max(1, max_open_partitions),
+            buffers: Vec::new(),
+        }
+    }
+
+    // Returns the partition directory, first record id and record of each
+    // file which is ready to be written.
+    pub fn push(
+        &mut self,
+        record_id: u64,
+        rec: RecordBatch,
+    ) -> Result<Vec<(String, u64, RecordBatch)>> {
+        let mut files = Vec::new();
+        for (values, partition_rec) in record_utils::partition_record(&rec, &self.partition_by)? {
+            let partition_dir = hive_partition_dir(&self.partition_by, &values);
+
+            let buffer_idx = match self
+                .buffers
+                .iter()
+                .position(|(dir, _)| *dir == partition_dir)
+            {
+                Some(idx) => idx,
+                None => {
+                    if self.buffers.len() >= self.max_open_partitions {
+                        let (dir, mut buffer) = self.buffers.remove(0);
+                        if let Some((first_record_id, rec)) = buffer.flush()? {
+                            files.push((dir, first_record_id, rec));
+                        }
+                    }
+                    self.buffers.push((
+                        partition_dir.clone(),
+                        RecordFileBuffer::new(self.target_file_rows),
+                    ));
+                    self.buffers.len() - 1
+                }
+            };
+
+            let (_, buffer) = &mut self.buffers[buffer_idx];
+            for (first_record_id, rec) in buffer.push(record_id, partition_rec)? {
+                files.push((partition_dir.clone(), first_record_id, rec));
+            }
+        }
+        Ok(files)
+    }
+
+    pub fn flush(&mut self) -> Result<Vec<(String, u64, RecordBatch)>> {
+        let mut files = Vec::new();
+        for (dir, mut buffer) in self.buffers.drain(..) {
+            if let Some((first_record_id, rec)) = buffer.flush()? {
+                files.push((dir, first_record_id, rec));
+            }
+        }
+        Ok(files)
+    }
+}
@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use arrow::datatypes::{DataType, Field, Schema};
+
+use crate::handlers::operator_handler::operators::ConnectionRegistry;
+use crate::planner::DataFormat;
+
+use super::manifest::{query_results_dir, read_manifests, ManifestField, MaterializeManifest};
+
+#[tokio::test]
+async fn test_manifests_are_written_and_read() -> Result<()> {
+    let dir = tempdir::TempDir::new("manifest")?;
+    let mut conn_reg = ConnectionRegistry::new();
+    conn_reg.add_connection(
+        "default".to_string(),
+        opendal::Scheme::Fs,
+        HashMap::from([("root".to_string(), dir.path().to_string_lossy().to_string())]),
+    );
+    let conn = conn_reg.get_operator("default")?;
+
+    let query_id = 7u128;
+    let schema = Schema::new(vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("size", DataType::Utf8, true),
+    ]);
+
+    // no manifests are found before any are written
+    conn.create_dir(&query_results_dir(query_id)).await?;
+    assert!(read_manifests(&conn, query_id).await?.is_empty());
+
+    let mut manifest_b = MaterializeManifest::new(2, DataFormat::Parquet);
+    manifest_b.add_file("rec_3.parquet".to_string(), 5, &schema);
+    manifest_b.write(&conn, query_id).await?;
+
+    let mut manifest_a = MaterializeManifest::new(1, DataFormat::Parquet);
+    manifest_a.add_file("size=small/rec_0.parquet".to_string(), 10, &schema);
+    manifest_a.add_file("size=large/rec_1.parquet".to_string(), 2, &schema);
+    manifest_a.write(&conn, query_id).await?;
+
+    assert_eq!(
+        manifest_a.fields,
+        vec![
+            ManifestField {
+                name: "id".to_string(),
+                data_type: "Int64".to_string(),
+                nullable: false,
+            },
+            ManifestField {
+                name: "size".to_string(),
+                data_type: "Utf8".to_string(),
+                nullable: true,
+            },
+        ]
+    );
+    assert_eq!(manifest_a.num_rows(), 12);
+
+    let manifests = read_manifests(&conn, query_id).await?;
+    assert_eq!(manifests, vec![manifest_a, manifest_b]);
+
+    // the temporary manifest keys are renamed
+    let names: Vec<String> = conn
+        .list(&query_results_dir(query_id))
+        .await?
+        .iter()
+        .map(|entry| entry.name().to_string())
+        .filter(|name| name.ends_with(".tmp"))
+        .collect();
+    assert!(names.is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,344 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::{ArrayRef, Int64Array, RecordBatch, StringArray, StructArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+
+use crate::handlers::operator_handler::operators::ConnectionRegistry;
+use crate::planner::{DataFormat, ParquetCompression};
+
+use super::config::MaterializeFilesConfig;
+use super::manifest::{query_results_dir, read_manifests, MaterializeManifest};
+use super::record_files::{
+    hive_partition_dir, read_record_file, record_file_data_format, write_record_file,
+    PartitionedRecordFileBuffers, RecordFileBuffer,
+};
+
+fn storage_conn(dir: &tempdir::TempDir) -> Result<opendal::Operator> {
+    let mut conn_reg = ConnectionRegistry::new();
+    conn_reg.add_connection(
+        "default".to_string(),
+        opendal::Scheme::Fs,
+        HashMap::from([("root".to_string(), dir.path().to_string_lossy().to_string())]),
+    );
+    conn_reg.get_operator("default")
+}
+
+#[tokio::test]
+async fn test_record_files_round_trip() -> Result<()> {
+    struct TestCase {
+        case_name: String,
+        data_format: DataFormat,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            case_name: "parquet".to_string(),
+            data_format: DataFormat::Parquet,
+        },
+        TestCase {
+            case_name: "csv".to_string(),
+            data_format: DataFormat::Csv,
+        },
+        TestCase {
+            case_name: "jsonl".to_string(),
+            data_format: DataFormat::JsonLines,
+        },
+    ];
+
+    let dir = tempdir::TempDir::new("record_files")?;
+    let conn = storage_conn(&dir)?;
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int64, true),
+        Field::new("size", DataType::Utf8, true),
+    ]));
+    let record = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int64Array::from(vec![1, 2, 3])),
+            Arc::new(StringArray::from(vec!["small", "medium", "large"])),
+        ],
+    )?;
+
+    for test_case in test_cases {
+        println!("test case: {}", test_case.case_name);
+
+        let rec_path = format!(
+            "/query_results/q/rec_0.{}",
+            test_case.data_format.file_extension()
+        );
+        assert_eq!(record_file_data_format(&rec_path)?, test_case.data_format);
+
+        write_record_file(
+            &conn,
+            &rec_path,
+            &test_case.data_format,
+            WriterProperties::default(),
+            &[record.clone()],
+        )
+        .await?;
+        let records = read_record_file(&conn, &rec_path).await?;
+
+        assert_eq!(records, vec![record.clone()]);
+    }
+
+    assert!(record_file_data_format("/query_results/q/rec_0").is_err());
+    assert!(record_file_data_format("/query_results/q/rec_0.orc").is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_materialized_results_are_read_through_the_fs_backend() -> Result<()> {
+    let dir = tempdir::TempDir::new("fs_backend")?;
+    let mut conn_reg = ConnectionRegistry::new();
+    conn_reg.register_fs(
+        "default".to_string(),
+        dir.path().to_string_lossy().to_string(),
+    );
+    let conn = conn_reg.get_operator("default")?;
+
+    let query_id = 11u128;
+    let record = RecordBatch::try_from_iter(vec![
+        ("id", Arc::new(Int64Array::from(vec![1, 2])) as ArrayRef),
+        (
+            "size",
+            Arc::new(StringArray::from(vec!["small", "large"])) as ArrayRef,
+        ),
+    ])?;
+
+    let rec_path = format!("{}rec_0.parquet", query_results_dir(query_id));
+    write_record_file(
+        &conn,
+        &rec_path,
+        &DataFormat::Parquet,
+        WriterProperties::default(),
+        &[record.clone()],
+    )
+    .await?;
+    let mut manifest = MaterializeManifest::new(1, DataFormat::Parquet);
+    manifest.add_file(
+        "rec_0.parquet".to_string(),
+        record.num_rows(),
+        &record.schema(),
+    );
+    manifest.write(&conn, query_id).await?;
+
+    // the files are stored in the local directory
+    let local_path = dir
+        .path()
+        .join(rec_path.trim_start_matches('/'))
+        .to_path_buf();
+    assert!(local_path.exists());
+
+    let manifests = read_manifests(&conn, query_id).await?;
+    assert_eq!(manifests, vec![manifest]);
+
+    let file_path = format!(
+        "{}{}",
+        query_results_dir(query_id),
+        manifests[0].files[0].path
+    );
+    let records = read_record_file(&conn, &file_path).await?;
+    assert_eq!(records, vec![record]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_json_lines_write_struct_columns_as_objects() -> Result<()> {
+    let dir = tempdir::TempDir::new("record_files_struct")?;
+    let conn = storage_conn(&dir)?;
+
+    let point = StructArray::from(vec![
+        (
+            Arc::new(Field::new("x", DataType::Int64, true)),
+            Arc::new(Int64Array::from(vec![1, 2])) as ArrayRef,
+        ),
+        (
+            Arc::new(Field::new("label", DataType::Utf8, true)),
+            Arc::new(StringArray::from(vec!["a", "b"])) as ArrayRef,
+        ),
+    ]);
+    let record = RecordBatch::try_from_iter(vec![("point", Arc::new(point) as ArrayRef)])?;
+
+    let rec_path = "/query_results/q/rec_0.jsonl";
+    write_record_file(
+        &conn,
+        rec_path,
+        &DataFormat::JsonLines,
+        WriterProperties::default(),
+        &[record],
+    )
+    .await?;
+
+    let data = String::from_utf8(conn.read(rec_path).await?.to_vec())?;
+    assert_eq!(
+        data,
+        "{\"point\":{\"x\":1,\"label\":\"a\"}}\n{\"point\":{\"x\":2,\"label\":\"b\"}}\n"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_parquet_writer_properties_set_the_row_groups() -> Result<()> {
+    let dir = tempdir::TempDir::new("record_files_row_groups")?;
+    let conn = storage_conn(&dir)?;
+
+    let config = MaterializeFilesConfig {
+        data_format: DataFormat::Parquet,
+        fields: vec![],
+        target_file_rows: 15,
+        partition_by: vec![],
+        max_open_partitions: 1,
+        row_group_size: 4,
+        compression: ParquetCompression::Zstd,
+        data_page_size: 1024,
+        connection: "default".to_string(),
+    };
+
+    // three batches of five rows
+    let records = (0..3)
+        .map(|batch_idx| {
+            RecordBatch::try_from_iter(vec![(
+                "id",
+                Arc::new(Int64Array::from_iter_values(
+                    (0..5).map(|idx| batch_idx * 5 + idx),
+                )) as ArrayRef,
+            )])
+        })
+        .collect::<Result<Vec<RecordBatch>, _>>()?;
+
+    let rec_path = "/query_results/q/rec_0.parquet";
+    write_record_file(
+        &conn,
+        rec_path,
+        &config.data_format,
+        config.parquet_writer_properties(),
+        &records,
+    )
+    .await?;
+
+    let file = std::fs::File::open(dir.path().join("query_results/q/rec_0.parquet"))?;
+    let reader = SerializedFileReader::new(file)?;
+    let metadata = reader.metadata();
+    let row_group_rows: Vec<i64> = metadata
+        .row_groups()
+        .iter()
+        .map(|row_group| row_group.num_rows())
+        .collect();
+    assert_eq!(row_group_rows, vec![4, 4, 4, 3]);
+    for row_group in metadata.row_groups() {
+        assert!(matches!(
+            row_group.column(0).compression(),
+            Compression::ZSTD(_)
+        ));
+    }
+
+    let read_records = read_record_file(&conn, rec_path).await?;
+    assert_eq!(
+        read_records.iter().map(|rec| rec.num_rows()).sum::<usize>(),
+        15
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_record_file_buffer_combines_records() -> Result<()> {
+    let id_record = |ids: Vec<i64>| -> Result<RecordBatch> {
+        Ok(RecordBatch::try_from_iter(vec![(
+            "id",
+            Arc::new(Int64Array::from(ids)) as ArrayRef,
+        )])?)
+    };
+
+    let mut buffer = RecordFileBuffer::new(4);
+    assert!(buffer.flush()?.is_none());
+
+    // records are buffered until the target number of rows is reached
+    assert!(buffer.push(10, id_record(vec![1, 2])?)?.is_empty());
+    assert!(buffer.push(11, id_record(vec![3])?)?.is_empty());
+    let files = buffer.push(12, id_record(vec![4, 5])?)?;
+    assert_eq!(files, vec![(10, id_record(vec![1, 2, 3, 4, 5])?)]);
+    assert!(buffer.flush()?.is_none());
+
+    // a record with a different schema starts a new file
+    assert!(buffer.push(13, id_record(vec![6])?)?.is_empty());
+    let size_record = RecordBatch::try_from_iter(vec![(
+        "size",
+        Arc::new(StringArray::from(vec!["small"])) as ArrayRef,
+    )])?;
+    let files = buffer.push(14, size_record.clone())?;
+    assert_eq!(files, vec![(13, id_record(vec![6])?)]);
+
+    // the remaining rows are flushed as a partial file
+    assert_eq!(buffer.flush()?, Some((14, size_record)));
+    assert!(buffer.flush()?.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_partitioned_record_file_buffers() -> Result<()> {
+    let size_record = |ids: Vec<i64>, sizes: Vec<Option<&str>>| -> Result<RecordBatch> {
+        Ok(RecordBatch::try_from_iter_with_nullable(vec![
+            ("id", Arc::new(Int64Array::from(ids)) as ArrayRef, false),
+            ("size", Arc::new(StringArray::from(sizes)) as ArrayRef, true),
+        ])?)
+    };
+
+    assert_eq!(
+        hive_partition_dir(
+            &vec!["size".to_string(), "path".to_string()],
+            &vec![None, Some("a/b=c".to_string())]
+        ),
+        "size=__HIVE_DEFAULT__/path=a%2Fb%3Dc".to_string()
+    );
+
+    // only two partitions are buffered at once
+    let mut buffers = PartitionedRecordFileBuffers::new(vec!["size".to_string()], 100, 2);
+
+    let files = buffers.push(
+        0,
+        size_record(vec![1, 2, 3], vec![Some("small"), None, Some("small")])?,
+    )?;
+    assert!(files.is_empty());
+
+    // a third partition flushes the oldest partition
+    let files = buffers.push(1, size_record(vec![4, 5], vec![Some("large"), None])?)?;
+    assert_eq!(
+        files,
+        vec![(
+            "size=small".to_string(),
+            0,
+            size_record(vec![1, 3], vec![Some("small"), Some("small")])?
+        )]
+    );
+
+    let files = buffers.flush()?;
+    assert_eq!(
+        files,
+        vec![
+            (
+                "size=__HIVE_DEFAULT__".to_string(),
+                0,
+                size_record(vec![2, 5], vec![None, None])?
+            ),
+            (
+                "size=large".to_string(),
+                1,
+                size_record(vec![4], vec![Some("large")])?
+            ),
+        ]
+    );
+    assert!(buffers.flush()?.is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::{ArrayRef, Int64Array, RecordBatch, StringArray};
+use parquet::file::properties::WriterProperties;
+use uuid::Uuid;
+
+use crate::handlers::operator_handler::operators::{ConnectionRegistry, S3Config};
+use crate::planner::DataFormat;
+
+use super::manifest::{query_results_dir, read_manifests, MaterializeManifest};
+use super::record_files::{read_record_file, write_record_file};
+
+// Run a MinIO container and create the bucket before running the test:
+//
+//   docker run -d -p 9000:9000 minio/minio server /data
+//   cargo test --features minio-tests
+//
+// The endpoint, bucket and credentials can be changed with the
+// MINIO_ENDPOINT, MINIO_BUCKET, MINIO_ACCESS_KEY and MINIO_SECRET_KEY
+// environment variables.
+fn minio_conn() -> Result<opendal::Operator> {
+    let env_or = |key: &str, default: &str| std::env::var(key).unwrap_or(default.to_string());
+
+    let mut conn_reg = ConnectionRegistry::new();
+    conn_reg.register_s3(
+        "default".to_string(),
+        S3Config {
+            bucket: env_or("MINIO_BUCKET", "chapterhouse"),
+            region: Some("us-east-1".to_string()),
+            endpoint: Some(env_or("MINIO_ENDPOINT", "http://127.0.0.1:9000")),
+            access_key: Some(env_or("MINIO_ACCESS_KEY", "minioadmin")),
+            secret_key: Some(env_or("MINIO_SECRET_KEY", "minioadmin")),
+        },
+    );
+    conn_reg.get_operator("default")
+}
+
+#[tokio::test]
+async fn test_materialized_results_are_read_through_the_s3_backend() -> Result<()> {
+    let conn = minio_conn()?;
+
+    let query_id = Uuid::new_v4().as_u128();
+    let record = RecordBatch::try_from_iter(vec![
+        ("id", Arc::new(Int64Array::from(vec![1, 2])) as ArrayRef),
+        (
+            "size",
+            Arc::new(StringArray::from(vec!["small", "large"])) as ArrayRef,
+        ),
+    ])?;
+
+    let rec_path = format!("{}rec_0.parquet", query_results_dir(query_id));
+    write_record_file(
+        &conn,
+        &rec_path,
+        &DataFormat::Parquet,
+        WriterProperties::default(),
+        &[record.clone()],
+    )
+    .await?;
+    let mut manifest = MaterializeManifest::new(1, DataFormat::Parquet);
+    manifest.add_file(
+        "rec_0.parquet".to_string(),
+        record.num_rows(),
+        &record.schema(),
+    );
+    manifest.write(&conn, query_id).await?;
+
+    let manifests = read_manifests(&conn, query_id).await?;
+    assert_eq!(manifests, vec![manifest]);
+
+    let records = read_record_file(&conn, &rec_path).await?;
+    assert_eq!(records, vec![record]);
+
+    conn.remove_all(&query_results_dir(query_id)).await?;
+
+    Ok(())
+}
@@ -1,16 +1,45 @@
+mod aggregate_tasks;
 mod builder;
 mod common_message_handlers;
 mod connection_registry;
+mod distinct_tasks;
 mod exchange_operator;
+mod filter_tasks;
+mod join_tasks;
+mod limit_tasks;
 mod materialize_tasks;
+mod operator_metrics;
 mod operator_task_registry;
 mod operator_task_trackers;
 mod producer_operator;
+mod read_cache;
+mod record_spiller;
 mod record_utils;
 pub mod requests;
+mod retryable_errors;
+mod sort_tasks;
 mod table_func_tasks;
+#[cfg(test)]
+mod test_connection_registry;
+#[cfg(test)]
+mod test_exchange_operator;
+#[cfg(test)]
+mod test_operator_metrics;
+#[cfg(test)]
+mod test_read_cache;
+#[cfg(test)]
+mod test_retryable_errors;
 mod traits;
+mod union_tasks;
 
 pub use builder::OperatorBuilder;
-pub use connection_registry::ConnectionRegistry;
+pub use connection_registry::{ConnectionRegistry, S3Config, DEFAULT_FS_ROOT};
+pub use materialize_tasks::{
+    query_results_dir, read_manifests, read_record_file, read_results_completion,
+    record_file_data_format, MaterializeManifest, ResultsCompletion,
+};
 pub use operator_task_registry::{build_default_operator_task_registry, OperatorTaskRegistry};
+pub use read_cache::ReadCacheStats;
+pub use record_utils::{project_record, NanPolicy, RecordComputeQueue};
+pub use retryable_errors::is_retryable;
+pub use table_func_tasks::find_table_func_schema;
@@ -0,0 +1,62 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use tracing::error;
+
+use crate::handlers::message_handler::messages;
+use crate::handlers::message_handler::{MessageRegistry, Pipe};
+use crate::handlers::operator_handler::operator_handler_state::OperatorInstanceConfig;
+
+use super::requests;
+
+// Accumulates the metrics of an operator instance while its task runs.
+#[derive(Debug)]
+pub struct OperatorMetricsRecorder {
+    started_at: Instant,
+    metrics: messages::query::OperatorMetrics,
+}
+
+impl OperatorMetricsRecorder {
+    pub fn new() -> OperatorMetricsRecorder {
+        OperatorMetricsRecorder {
+            started_at: Instant::now(),
+            metrics: messages::query::OperatorMetrics::default(),
+        }
+    }
+
+    pub fn record_in(&mut self, record: &arrow::array::RecordBatch) {
+        self.metrics.records_in += record.num_rows() as u64;
+    }
+
+    pub fn record_out(&mut self, record: &arrow::array::RecordBatch) {
+        self.metrics.records_out += record.num_rows() as u64;
+        self.metrics.bytes_out += record.get_array_memory_size() as u64;
+    }
+
+    pub fn metrics(&self) -> messages::query::OperatorMetrics {
+        let mut metrics = self.metrics;
+        metrics.wall_time_ms = self.started_at.elapsed().as_millis() as u64;
+        metrics
+    }
+
+    // The metrics are only informational so a failed report doesn't
+    // fail the operator instance.
+    pub async fn report(
+        &self,
+        op_in_config: &OperatorInstanceConfig,
+        pipe: &mut Pipe,
+        msg_reg: Arc<MessageRegistry>,
+    ) {
+        let res = requests::query::OperatorInstanceMetricsRequest::request(
+            op_in_config.query_id,
+            op_in_config.id,
+            self.metrics(),
+            pipe,
+            msg_reg,
+        )
+        .await;
+        if let Err(err) = res {
+            error!("failed to report operator instance metrics: {:?}", err);
+        }
+    }
+}
@@ -1,8 +1,10 @@
 use crate::planner::{self, DataFormat};
 
 use super::{
-    materialize_tasks, table_func_tasks,
+    aggregate_tasks, distinct_tasks, filter_tasks, join_tasks, limit_tasks, materialize_tasks,
+    sort_tasks, table_func_tasks,
     traits::{TableFuncSyntaxValidator, TaskBuilder},
+    union_tasks,
 };
 use anyhow::Result;
 use thiserror::Error;
@@ -13,6 +15,20 @@ pub enum OperatorTaskRegistryError {
     NotImplemented(String),
     #[error("materialize file task builder already set")]
     MaterializeFileTaskBuilderAlreadySet,
+    #[error("filter task builder already set")]
+    FilterTaskBuilderAlreadySet,
+    #[error("hash join task builder already set")]
+    HashJoinTaskBuilderAlreadySet,
+    #[error("aggregate task builder already set")]
+    AggregateTaskBuilderAlreadySet,
+    #[error("distinct task builder already set")]
+    DistinctTaskBuilderAlreadySet,
+    #[error("sort task builder already set")]
+    SortTaskBuilderAlreadySet,
+    #[error("limit task builder already set")]
+    LimitTaskBuilderAlreadySet,
+    #[error("union task builder already set")]
+    UnionTaskBuilderAlreadySet,
     #[error("task func task builder already added for function: {0}")]
     TaskFuncTaskBuilderAlreadyAddedForFunction(String),
 }
@@ -29,6 +45,13 @@ struct MaterializeFileTaskDef {
 
 pub struct OperatorTaskRegistry {
     table_func_tasks: Vec<TableFuncTaskDef>,
+    filter_task: Option<Box<dyn TaskBuilder>>,
+    hash_join_task: Option<Box<dyn TaskBuilder>>,
+    aggregate_task: Option<Box<dyn TaskBuilder>>,
+    distinct_task: Option<Box<dyn TaskBuilder>>,
+    sort_task: Option<Box<dyn TaskBuilder>>,
+    limit_task: Option<Box<dyn TaskBuilder>>,
+    union_task: Option<Box<dyn TaskBuilder>>,
     materialize_files_task: Option<MaterializeFileTaskDef>,
 }
 
@@ -36,10 +59,73 @@ impl OperatorTaskRegistry {
     pub fn new() -> OperatorTaskRegistry {
         OperatorTaskRegistry {
             table_func_tasks: Vec::new(),
+            filter_task: None,
+            hash_join_task: None,
+            aggregate_task: None,
+            distinct_task: None,
+            sort_task: None,
+            limit_task: None,
+            union_task: None,
             materialize_files_task: None,
         }
     }
 
+    pub fn add_filter_task_builder(mut self, builder: Box<dyn TaskBuilder>) -> Result<Self> {
+        if self.filter_task.is_some() {
+            return Err(OperatorTaskRegistryError::FilterTaskBuilderAlreadySet.into());
+        }
+        self.filter_task = Some(builder);
+        Ok(self)
+    }
+
+    pub fn add_hash_join_task_builder(mut self, builder: Box<dyn TaskBuilder>) -> Result<Self> {
+        if self.hash_join_task.is_some() {
+            return Err(OperatorTaskRegistryError::HashJoinTaskBuilderAlreadySet.into());
+        }
+        self.hash_join_task = Some(builder);
+        Ok(self)
+    }
+
+    pub fn add_aggregate_task_builder(mut self, builder: Box<dyn TaskBuilder>) -> Result<Self> {
+        if self.aggregate_task.is_some() {
+            return Err(OperatorTaskRegistryError::AggregateTaskBuilderAlreadySet.into());
+        }
+        self.aggregate_task = Some(builder);
+        Ok(self)
+    }
+
+    pub fn add_distinct_task_builder(mut self, builder: Box<dyn TaskBuilder>) -> Result<Self> {
+        if self.distinct_task.is_some() {
+            return Err(OperatorTaskRegistryError::DistinctTaskBuilderAlreadySet.into());
+        }
+        self.distinct_task = Some(builder);
+        Ok(self)
+    }
+
+    pub fn add_sort_task_builder(mut self, builder: Box<dyn TaskBuilder>) -> Result<Self> {
+        if self.sort_task.is_some() {
+            return Err(OperatorTaskRegistryError::SortTaskBuilderAlreadySet.into());
+        }
+        self.sort_task = Some(builder);
+        Ok(self)
+    }
+
+    pub fn add_limit_task_builder(mut self, builder: Box<dyn TaskBuilder>) -> Result<Self> {
+        if self.limit_task.is_some() {
+            return Err(OperatorTaskRegistryError::LimitTaskBuilderAlreadySet.into());
+        }
+        self.limit_task = Some(builder);
+        Ok(self)
+    }
+
+    pub fn add_union_task_builder(mut self, builder: Box<dyn TaskBuilder>) -> Result<Self> {
+        if self.union_task.is_some() {
+            return Err(OperatorTaskRegistryError::UnionTaskBuilderAlreadySet.into());
+        }
+        self.union_task = Some(builder);
+        Ok(self)
+    }
+
     pub fn add_materialize_files_builder(
         mut self,
         builder: Box<dyn TaskBuilder>,
@@ -89,10 +175,13 @@ impl OperatorTaskRegistry {
                 format!("find task builder for OperatorTask type {}", task.name()),
             )
             .into()),
-            planner::OperatorTask::Filter { .. } => Err(OperatorTaskRegistryError::NotImplemented(
-                format!("find task builder for OperatorTask type {}", task.name()),
-            )
-            .into()),
+            planner::OperatorTask::HashJoin { .. } => Ok(self.hash_join_task.as_ref()),
+            planner::OperatorTask::Filter { .. } => Ok(self.filter_task.as_ref()),
+            planner::OperatorTask::Aggregate { .. } => Ok(self.aggregate_task.as_ref()),
+            planner::OperatorTask::Distinct { .. } => Ok(self.distinct_task.as_ref()),
+            planner::OperatorTask::Sort { .. } => Ok(self.sort_task.as_ref()),
+            planner::OperatorTask::Limit { .. } => Ok(self.limit_task.as_ref()),
+            planner::OperatorTask::Union { .. } => Ok(self.union_task.as_ref()),
             planner::OperatorTask::MaterializeFiles { data_format, .. } => {
                 if let Some(materialize_files_task) = &self.materialize_files_task {
                     if materialize_files_task
@@ -134,9 +223,24 @@ pub fn build_default_operator_task_registry() -> Result<OperatorTaskRegistry> {
             Box::new(table_func_tasks::ReadFilesTaskBuilder::new()),
             Box::new(table_func_tasks::ReadFilesSyntaxValidator::new()),
         )?
+        .add_table_func_task_builder(
+            Box::new(table_func_tasks::ValuesTaskBuilder::new()),
+            Box::new(table_func_tasks::ValuesSyntaxValidator::new()),
+        )?
+        .add_table_func_task_builder(
+            Box::new(table_func_tasks::GenerateSeriesTaskBuilder::new()),
+            Box::new(table_func_tasks::GenerateSeriesSyntaxValidator::new()),
+        )?
+        .add_hash_join_task_builder(Box::new(join_tasks::HashJoinTaskBuilder::new()))?
+        .add_filter_task_builder(Box::new(filter_tasks::FilterTaskBuilder::new()))?
+        .add_aggregate_task_builder(Box::new(aggregate_tasks::AggregateTaskBuilder::new()))?
+        .add_distinct_task_builder(Box::new(distinct_tasks::DistinctTaskBuilder::new()))?
+        .add_sort_task_builder(Box::new(sort_tasks::SortTaskBuilder::new()))?
+        .add_limit_task_builder(Box::new(limit_tasks::LimitTaskBuilder::new()))?
+        .add_union_task_builder(Box::new(union_tasks::UnionTaskBuilder::new()))?
         .add_materialize_files_builder(
             Box::new(materialize_tasks::MaterializeFilesTaskBuilder::new()),
-            vec![DataFormat::Parquet],
+            vec![DataFormat::Parquet, DataFormat::Csv, DataFormat::JsonLines],
         )?;
     Ok(reg)
 }
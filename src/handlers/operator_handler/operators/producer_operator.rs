@@ -18,6 +18,7 @@ use crate::handlers::operator_handler::operators::common_message_handlers::handl
 use crate::handlers::operator_handler::operators::requests;
 
 use super::operator_task_trackers::RestrictedOperatorTaskTracker;
+use super::retryable_errors::is_retryable;
 
 #[derive(Debug, Error)]
 pub enum ProducerOperatorError {
@@ -164,6 +165,9 @@ impl ProducerOperator {
                             let pong_msg = handle_ping_message(&msg, ping_msg)?;
                             self.router_pipe.send(pong_msg).await?;
                         }
+                    } else if msg.msg.msg_name() == MessageName::OperatorShutdown {
+                        self.handle_operator_shutdown(&msg).await?;
+                        continue;
                     }
                     if let Some(task_msg_consumer) = &self.task_msg_consumer {
                         if task_msg_consumer.consumes_message(&msg) {
@@ -189,7 +193,7 @@ impl ProducerOperator {
                         }
                         Ok(Some(res_err)) => {
                             requests::operator::OperatorInstanceStatusChangeRequest::errored_request(
-                                res_err.to_string(), pipe, self.msg_reg.clone()
+                                format!("{:#}", res_err), is_retryable(&res_err), pipe, self.msg_reg.clone()
                             ).await?;
                         }
                         Err(err) => {
@@ -205,6 +209,22 @@ impl ProducerOperator {
         }
         Ok(())
     }
+
+    // The task is cancelled and the operator keeps running until the task
+    // exits so its status is still reported and its compute released.
+    async fn handle_operator_shutdown(&mut self, msg: &Message) -> Result<()> {
+        let _: &messages::operator::Shutdown = self.msg_reg.try_cast_msg(msg)?;
+
+        debug!(
+            operator_instance_id = self.operator_instance_config.id,
+            "producer operator shutting down"
+        );
+        self.task_ct.cancel();
+
+        let resp_msg = msg.reply(Box::new(messages::common::GenericResponse::Ok));
+        self.router_pipe.send(resp_msg).await?;
+        Ok(())
+    }
 }
 
 //////////////////////////////////////////////////////
@@ -0,0 +1,333 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use opendal::raw::{
+    oio, Access, Layer, LayeredAccess, OpCopy, OpDelete, OpList, OpRead, OpRename, OpWrite, RpCopy,
+    RpDelete, RpList, RpRead, RpRename, RpWrite,
+};
+use opendal::{Buffer, Result};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReadCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ReadCacheKey {
+    conn_name: String,
+    path: String,
+    offset: u64,
+    size: Option<u64>,
+}
+
+#[derive(Debug)]
+struct ReadCacheEntry {
+    data: Buffer,
+    used_at: u64,
+}
+
+#[derive(Debug, Default)]
+struct ReadCacheState {
+    entries: HashMap<ReadCacheKey, ReadCacheEntry>,
+    // the keys ordered from least to most recently used
+    lru: BTreeMap<u64, ReadCacheKey>,
+    bytes: usize,
+    clock: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl ReadCacheState {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    fn remove(&mut self, key: &ReadCacheKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.lru.remove(&entry.used_at);
+            self.bytes -= entry.data.len();
+        }
+    }
+}
+
+// A bounded cache of the byte ranges most recently read from storage.
+// The cache is shared by every operator built by the connection
+// registry, so the keys include the connection name.
+#[derive(Debug)]
+pub struct ReadCache {
+    capacity_bytes: usize,
+    state: Mutex<ReadCacheState>,
+}
+
+impl ReadCache {
+    pub fn new(capacity_bytes: usize) -> ReadCache {
+        ReadCache {
+            capacity_bytes,
+            state: Mutex::new(ReadCacheState::default()),
+        }
+    }
+
+    pub fn stats(&self) -> ReadCacheStats {
+        let state = self.state.lock().unwrap();
+        ReadCacheStats {
+            hits: state.hits,
+            misses: state.misses,
+            entries: state.entries.len() as u64,
+            bytes: state.bytes as u64,
+        }
+    }
+
+    fn get(&self, key: &ReadCacheKey) -> Option<Buffer> {
+        let mut state = self.state.lock().unwrap();
+        let used_at = state.tick();
+        let (data, last_used_at) = match state.entries.get_mut(key) {
+            Some(entry) => {
+                let last_used_at = entry.used_at;
+                entry.used_at = used_at;
+                (entry.data.clone(), last_used_at)
+            }
+            None => {
+                state.misses += 1;
+                return None;
+            }
+        };
+        state.lru.remove(&last_used_at);
+        state.lru.insert(used_at, key.clone());
+        state.hits += 1;
+        Some(data)
+    }
+
+    // Ranges larger than the cache are not stored.
+    fn insert(&self, key: ReadCacheKey, data: Buffer) {
+        if data.len() > self.capacity_bytes {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        state.remove(&key);
+        while state.bytes + data.len() > self.capacity_bytes {
+            let oldest_key = match state.lru.first_key_value() {
+                Some((_, oldest_key)) => oldest_key.clone(),
+                None => break,
+            };
+            state.remove(&oldest_key);
+        }
+
+        let used_at = state.tick();
+        state.bytes += data.len();
+        state.lru.insert(used_at, key.clone());
+        state.entries.insert(key, ReadCacheEntry { data, used_at });
+    }
+
+    // Drop every cached range of the path since its content changed.
+    fn invalidate(&self, conn_name: &str, path: &str) {
+        let mut state = self.state.lock().unwrap();
+        let keys: Vec<ReadCacheKey> = state
+            .entries
+            .keys()
+            .filter(|key| key.conn_name == conn_name && key.path == path)
+            .cloned()
+            .collect();
+        for key in keys {
+            state.remove(&key);
+        }
+    }
+}
+
+//////////////////////////////////////////////////////
+// Read Cache Layer
+
+#[derive(Debug, Clone)]
+pub struct ReadCacheLayer {
+    cache: Arc<ReadCache>,
+    conn_name: String,
+}
+
+impl ReadCacheLayer {
+    pub fn new(cache: Arc<ReadCache>, conn_name: String) -> ReadCacheLayer {
+        ReadCacheLayer { cache, conn_name }
+    }
+}
+
+impl<A: Access> Layer<A> for ReadCacheLayer {
+    type LayeredAccess = ReadCacheAccessor<A>;
+
+    fn layer(&self, inner: A) -> Self::LayeredAccess {
+        ReadCacheAccessor {
+            inner,
+            cache: self.cache.clone(),
+            conn_name: self.conn_name.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ReadCacheAccessor<A: Access> {
+    inner: A,
+    cache: Arc<ReadCache>,
+    conn_name: String,
+}
+
+impl<A: Access> ReadCacheAccessor<A> {
+    fn key(&self, path: &str, args: &OpRead) -> ReadCacheKey {
+        let range = args.range();
+        ReadCacheKey {
+            conn_name: self.conn_name.clone(),
+            path: path.to_string(),
+            offset: range.offset(),
+            size: range.size(),
+        }
+    }
+}
+
+impl<A: Access> LayeredAccess for ReadCacheAccessor<A> {
+    type Inner = A;
+    type Reader = CachedReader;
+    type BlockingReader = A::BlockingReader;
+    type Writer = InvalidatingWriter<A::Writer>;
+    type BlockingWriter = A::BlockingWriter;
+    type Lister = A::Lister;
+    type BlockingLister = A::BlockingLister;
+    type Deleter = InvalidatingDeleter<A::Deleter>;
+    type BlockingDeleter = A::BlockingDeleter;
+
+    fn inner(&self) -> &Self::Inner {
+        &self.inner
+    }
+
+    // The whole range is read from storage on a miss so it can be stored.
+    async fn read(&self, path: &str, args: OpRead) -> Result<(RpRead, Self::Reader)> {
+        let key = self.key(path, &args);
+        if let Some(data) = self.cache.get(&key) {
+            let rp = RpRead::new().with_size(Some(data.len() as u64));
+            return Ok((rp, CachedReader::new(data)));
+        }
+
+        let (rp, mut reader) = self.inner.read(path, args).await?;
+        let data = oio::Read::read_all(&mut reader).await?;
+        self.cache.insert(key, data.clone());
+        Ok((rp, CachedReader::new(data)))
+    }
+
+    // The ranges are dropped again once the write is done since they may
+    // have been read while writing.
+    async fn write(&self, path: &str, args: OpWrite) -> Result<(RpWrite, Self::Writer)> {
+        self.cache.invalidate(&self.conn_name, path);
+        let (rp, writer) = self.inner.write(path, args).await?;
+        Ok((
+            rp,
+            InvalidatingWriter {
+                inner: writer,
+                cache: self.cache.clone(),
+                conn_name: self.conn_name.clone(),
+                path: path.to_string(),
+            },
+        ))
+    }
+
+    async fn rename(&self, from: &str, to: &str, args: OpRename) -> Result<RpRename> {
+        let rp = self.inner.rename(from, to, args).await;
+        self.cache.invalidate(&self.conn_name, from);
+        self.cache.invalidate(&self.conn_name, to);
+        rp
+    }
+
+    async fn copy(&self, from: &str, to: &str, args: OpCopy) -> Result<RpCopy> {
+        let rp = self.inner.copy(from, to, args).await;
+        self.cache.invalidate(&self.conn_name, to);
+        rp
+    }
+
+    async fn delete(&self) -> Result<(RpDelete, Self::Deleter)> {
+        let (rp, deleter) = self.inner.delete().await?;
+        Ok((
+            rp,
+            InvalidatingDeleter {
+                inner: deleter,
+                cache: self.cache.clone(),
+                conn_name: self.conn_name.clone(),
+            },
+        ))
+    }
+
+    async fn list(&self, path: &str, args: OpList) -> Result<(RpList, Self::Lister)> {
+        self.inner.list(path, args).await
+    }
+
+    fn blocking_read(&self, path: &str, args: OpRead) -> Result<(RpRead, Self::BlockingReader)> {
+        self.inner.blocking_read(path, args)
+    }
+
+    fn blocking_write(&self, path: &str, args: OpWrite) -> Result<(RpWrite, Self::BlockingWriter)> {
+        self.cache.invalidate(&self.conn_name, path);
+        self.inner.blocking_write(path, args)
+    }
+
+    fn blocking_list(&self, path: &str, args: OpList) -> Result<(RpList, Self::BlockingLister)> {
+        self.inner.blocking_list(path, args)
+    }
+
+    fn blocking_delete(&self) -> Result<(RpDelete, Self::BlockingDeleter)> {
+        self.inner.blocking_delete()
+    }
+}
+
+pub struct CachedReader {
+    data: Option<Buffer>,
+}
+
+impl CachedReader {
+    fn new(data: Buffer) -> CachedReader {
+        CachedReader { data: Some(data) }
+    }
+}
+
+impl oio::Read for CachedReader {
+    async fn read(&mut self) -> Result<Buffer> {
+        Ok(self.data.take().unwrap_or_default())
+    }
+}
+
+pub struct InvalidatingWriter<W> {
+    inner: W,
+    cache: Arc<ReadCache>,
+    conn_name: String,
+    path: String,
+}
+
+impl<W: oio::Write> oio::Write for InvalidatingWriter<W> {
+    async fn write(&mut self, bs: Buffer) -> Result<()> {
+        self.inner.write(bs).await
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        let res = self.inner.close().await;
+        self.cache.invalidate(&self.conn_name, &self.path);
+        res
+    }
+
+    async fn abort(&mut self) -> Result<()> {
+        self.inner.abort().await
+    }
+}
+
+pub struct InvalidatingDeleter<D> {
+    inner: D,
+    cache: Arc<ReadCache>,
+    conn_name: String,
+}
+
+impl<D: oio::Delete> oio::Delete for InvalidatingDeleter<D> {
+    fn delete(&mut self, path: &str, args: OpDelete) -> Result<()> {
+        self.cache.invalidate(&self.conn_name, path);
+        self.inner.delete(path, args)
+    }
+
+    async fn flush(&mut self) -> Result<usize> {
+        self.inner.flush().await
+    }
+}
@@ -0,0 +1,80 @@
+use anyhow::Result;
+use arrow::array::RecordBatch;
+use parquet::file::properties::WriterProperties;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::planner::DataFormat;
+
+use super::materialize_tasks::{read_record_file, write_record_file};
+
+#[derive(Debug, Error)]
+pub enum RecordSpillerError {
+    #[error("spill file {0} does not contain any records")]
+    SpillFileDoesNotContainAnyRecords(String),
+}
+
+pub fn exchange_spill_dir(query_id: u128, operator_instance_id: u128) -> String {
+    format!(
+        "/exchange_spill/{}/{}/",
+        Uuid::from_u128(query_id),
+        Uuid::from_u128(operator_instance_id)
+    )
+}
+
+// Writes the records an exchange can't keep in memory to parquet files in
+// the storage connection and reads them back when they're requested.
+#[derive(Debug)]
+pub struct RecordSpiller {
+    storage_conn: opendal::Operator,
+    spill_dir: String,
+    num_spilled: u64,
+}
+
+impl RecordSpiller {
+    pub fn new(storage_conn: opendal::Operator, spill_dir: String) -> RecordSpiller {
+        RecordSpiller {
+            storage_conn,
+            spill_dir,
+            num_spilled: 0,
+        }
+    }
+
+    pub async fn spill(&mut self, record: &RecordBatch) -> Result<String> {
+        let spill_path = format!("{}spill_{}.parquet", self.spill_dir, self.num_spilled);
+        self.num_spilled += 1;
+        write_record_file(
+            &self.storage_conn,
+            &spill_path,
+            &DataFormat::Parquet,
+            WriterProperties::builder().build(),
+            std::slice::from_ref(record),
+        )
+        .await?;
+        Ok(spill_path)
+    }
+
+    pub async fn read(&self, spill_path: &str) -> Result<RecordBatch> {
+        let records = read_record_file(&self.storage_conn, spill_path).await?;
+        let schema = match records.first() {
+            Some(rec) => rec.schema(),
+            None => {
+                return Err(RecordSpillerError::SpillFileDoesNotContainAnyRecords(
+                    spill_path.to_string(),
+                )
+                .into())
+            }
+        };
+        Ok(arrow::compute::concat_batches(&schema, &records)?)
+    }
+
+    pub async fn remove(&self, spill_path: &str) -> Result<()> {
+        self.storage_conn.delete(spill_path).await?;
+        Ok(())
+    }
+
+    pub async fn cleanup(&self) -> Result<()> {
+        self.storage_conn.remove_all(&self.spill_dir).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,924 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::{
+    Array, ArrayRef, AsArray, BooleanArray, Float64Array, Int64Array, RecordBatch, Scalar,
+    StringArray,
+};
+use arrow::compute::kernels::{cmp, comparison, concat_elements, numeric};
+use arrow::datatypes::{DataType, Float32Type, Float64Type};
+use arrow::error::ArrowError;
+use sqlparser::ast::{
+    BinaryOperator, Expr, Function, FunctionArg, FunctionArgExpr, FunctionArguments, Ident,
+    TrimWhereField, UnaryOperator, Value,
+};
+use thiserror::Error;
+
+use super::scalar_functions::{find_builtin_function, substring, trim, ScalarFunctionError};
+
+#[derive(Debug, Error)]
+pub enum ComputeValueError {
+    #[error("not implemented: {0}")]
+    NotImplemented(String),
+    #[error("column not found: {0}")]
+    ColumnNotFound(String),
+    #[error("column reference is ambiguous: {0}")]
+    AmbiguousColumnReference(String),
+    #[error("unable to parse number: {0}")]
+    UnableToParseNumber(String),
+    #[error("unable to compare data type {0} with {1}")]
+    UnableToCompareDataTypes(DataType, DataType),
+    #[error("unable to apply operator {0} to data types {1} and {2}")]
+    UnableToApplyOperator(String, DataType, DataType),
+    #[error("unable to apply operator {0} to data type {1}")]
+    UnableToApplyUnaryOperator(String, DataType),
+    #[error("expected a {expected} value but received data type {found}")]
+    TypeMismatch { expected: String, found: DataType },
+    #[error("function not found: {0}")]
+    FunctionNotFound(String),
+    #[error("in list must contain at least one value")]
+    EmptyInList,
+    #[error("unable to find a common data type for {0} and {1}")]
+    UnableToFindCommonDataType(DataType, DataType),
+    #[error("unable to parse string as {0}")]
+    UnableToParseTemporalValue(DataType),
+    #[error("unable to mix timezone aware and timezone naive timestamps: {0} and {1}")]
+    UnableToMixTimezoneAwareAndNaive(DataType, DataType),
+    #[error("divide by zero")]
+    DivideByZero,
+    #[error("arithmetic overflow: {0}")]
+    ArithmeticOverflow(String),
+    #[error(transparent)]
+    ScalarFunction(#[from] ScalarFunctionError),
+    #[error(transparent)]
+    Arrow(#[from] ArrowError),
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl ComputeValueError {
+    fn from_error(err: anyhow::Error) -> ComputeValueError {
+        let err = match err.downcast::<ComputeValueError>() {
+            Ok(err) => return err,
+            Err(err) => err,
+        };
+        let err = match err.downcast::<ScalarFunctionError>() {
+            Ok(err) => return ComputeValueError::ScalarFunction(err),
+            Err(err) => err,
+        };
+        match err.downcast::<ArrowError>() {
+            Ok(ArrowError::DivideByZero) => ComputeValueError::DivideByZero,
+            Ok(ArrowError::ArithmeticOverflow(msg)) => ComputeValueError::ArithmeticOverflow(msg),
+            Ok(err) => ComputeValueError::Arrow(err),
+            Err(err) => ComputeValueError::Other(err),
+        }
+    }
+}
+
+// How NaN float values behave in comparisons.
+//
+// TotalOrder follows Postgres: NaN equals NaN and is greater than every
+// other value, including infinity. This is the same ordering the arrow
+// sort kernels use so sorts and comparisons agree with each other.
+//
+// Ieee follows IEEE 754: any comparison with a NaN is false except for
+// `<>` which is true. Sorting still places NaN after every other value
+// since a sort needs a total order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NanPolicy {
+    #[default]
+    TotalOrder,
+    Ieee,
+}
+
+pub type ScalarFunction = Arc<dyn Fn(&[ArrayRef]) -> Result<ArrayRef> + Send + Sync>;
+
+#[derive(Clone, Default)]
+pub struct ComputeValueOptions {
+    pub nan_policy: NanPolicy,
+    // functions looked up before the builtin functions; the names are
+    // lowercase
+    pub functions: HashMap<String, ScalarFunction>,
+}
+
+impl fmt::Debug for ComputeValueOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ComputeValueOptions")
+            .field("nan_policy", &self.nan_policy)
+            .field(
+                "functions",
+                &self.functions.keys().collect::<Vec<&String>>(),
+            )
+            .finish()
+    }
+}
+
+// Errors are always returned as a ComputeValueError so callers can tell
+// a bad expression apart from a failure in an arrow kernel.
+pub fn compute_value(
+    rec: &RecordBatch,
+    table_aliases: &[Vec<String>],
+    expr: &Expr,
+    options: &ComputeValueOptions,
+) -> Result<ArrayRef, ComputeValueError> {
+    compute_expr(rec, table_aliases, expr, options).map_err(ComputeValueError::from_error)
+}
+
+fn compute_expr(
+    rec: &RecordBatch,
+    table_aliases: &[Vec<String>],
+    expr: &Expr,
+    options: &ComputeValueOptions,
+) -> Result<ArrayRef> {
+    // an aggregate names its output columns after the expressions
+    // that computed them, such as "sum(b)" or "a + 1"
+    if !matches!(
+        expr,
+        Expr::Identifier(_) | Expr::CompoundIdentifier(_) | Expr::Value(_) | Expr::Nested(_)
+    ) {
+        if let Ok(idx) = rec.schema().index_of(&expr.to_string()) {
+            return Ok(rec.column(idx).clone());
+        }
+    }
+
+    match expr {
+        Expr::Identifier(ident) => find_column(rec, table_aliases, None, ident),
+        Expr::CompoundIdentifier(idents) => match &idents[..] {
+            [alias, ident] => find_column(rec, table_aliases, Some(alias), ident),
+            _ => Err(
+                ComputeValueError::NotImplemented(format!("compound identifier: {}", expr)).into(),
+            ),
+        },
+        Expr::Nested(expr) => compute_expr(rec, table_aliases, expr, options),
+        Expr::Value(val) => compute_literal(rec, val),
+        Expr::BinaryOp {
+            left,
+            op: op @ (BinaryOperator::And | BinaryOperator::Or),
+            right,
+        } => compute_logical_op(rec, table_aliases, left, op, right, options),
+        Expr::BinaryOp { left, op, right } => {
+            let left = compute_expr(rec, table_aliases, left, options)?;
+            let right = compute_expr(rec, table_aliases, right, options)?;
+            compute_binary_op(left, op, right, options)
+        }
+        Expr::UnaryOp { op, expr } => {
+            let value = compute_expr(rec, table_aliases, expr, options)?;
+            compute_unary_op(op, value)
+        }
+        Expr::Function(func) => compute_function(rec, table_aliases, func, options),
+        Expr::InList {
+            expr,
+            list,
+            negated,
+        } => compute_in_list(rec, table_aliases, expr, list, *negated, options),
+        Expr::Like { any: false, .. } | Expr::ILike { any: false, .. } => {
+            compute_like(rec, table_aliases, expr, options)
+        }
+        Expr::Case {
+            operand,
+            conditions,
+            results,
+            else_result,
+        } => compute_case(
+            rec,
+            table_aliases,
+            operand.as_deref(),
+            conditions,
+            results,
+            else_result.as_deref(),
+            options,
+        ),
+        Expr::Trim {
+            expr,
+            trim_where,
+            trim_what,
+            trim_characters: None,
+        } => {
+            let value = compute_expr(rec, table_aliases, expr, options)?;
+            let characters = match trim_what {
+                Some(trim_what) => Some(compute_expr(rec, table_aliases, trim_what, options)?),
+                None => None,
+            };
+            let (leading, trailing) = match trim_where {
+                Some(TrimWhereField::Leading) => (true, false),
+                Some(TrimWhereField::Trailing) => (false, true),
+                Some(TrimWhereField::Both) | None => (true, true),
+            };
+            trim(&value, characters.as_ref(), leading, trailing)
+        }
+        Expr::Substring {
+            expr,
+            substring_from,
+            substring_for,
+            ..
+        } => {
+            let value = compute_expr(rec, table_aliases, expr, options)?;
+            let from = match substring_from {
+                Some(from) => Some(compute_expr(rec, table_aliases, from, options)?),
+                None => None,
+            };
+            let length = match substring_for {
+                Some(length) => Some(compute_expr(rec, table_aliases, length, options)?),
+                None => None,
+            };
+            substring(&value, from.as_ref(), length.as_ref())
+        }
+        Expr::IsNull(expr) => {
+            let value = compute_expr(rec, table_aliases, expr, options)?;
+            Ok(Arc::new(arrow::compute::is_null(&value)?))
+        }
+        Expr::IsNotNull(expr) => {
+            let value = compute_expr(rec, table_aliases, expr, options)?;
+            Ok(Arc::new(arrow::compute::is_not_null(&value)?))
+        }
+        Expr::Between {
+            expr,
+            negated,
+            low,
+            high,
+        } => compute_between(rec, table_aliases, expr, low, high, *negated, options),
+        _ => Err(ComputeValueError::NotImplemented(format!("expression: {}", expr)).into()),
+    }
+}
+
+fn find_column(
+    rec: &RecordBatch,
+    table_aliases: &[Vec<String>],
+    alias: Option<&Ident>,
+    ident: &Ident,
+) -> Result<ArrayRef> {
+    let mut matches = rec
+        .schema()
+        .fields()
+        .iter()
+        .enumerate()
+        .filter(|(idx, field)| {
+            field.name() == &ident.value
+                && match alias {
+                    Some(alias) => table_aliases
+                        .get(*idx)
+                        .is_some_and(|aliases| aliases.contains(&alias.value)),
+                    None => true,
+                }
+        })
+        .map(|(idx, _)| idx)
+        .collect::<Vec<usize>>();
+
+    let name = match alias {
+        Some(alias) => format!("{}.{}", alias, ident),
+        None => format!("{}", ident),
+    };
+    match matches.len() {
+        0 => Err(ComputeValueError::ColumnNotFound(name).into()),
+        1 => Ok(rec.column(matches.remove(0)).clone()),
+        _ => Err(ComputeValueError::AmbiguousColumnReference(name).into()),
+    }
+}
+
+fn compute_literal(rec: &RecordBatch, val: &Value) -> Result<ArrayRef> {
+    match val {
+        Value::Number(num, _) => {
+            if let Ok(num) = num.parse::<i64>() {
+                Ok(Arc::new(Int64Array::from_value(num, rec.num_rows())))
+            } else if let Ok(num) = num.parse::<f64>() {
+                Ok(Arc::new(Float64Array::from_value(num, rec.num_rows())))
+            } else {
+                Err(ComputeValueError::UnableToParseNumber(num.clone()).into())
+            }
+        }
+        Value::SingleQuotedString(val) | Value::DoubleQuotedString(val) => Ok(Arc::new(
+            StringArray::from_iter_values(std::iter::repeat_n(val, rec.num_rows())),
+        )),
+        Value::Null => Ok(arrow::array::new_null_array(
+            &DataType::Null,
+            rec.num_rows(),
+        )),
+        _ => Err(ComputeValueError::NotImplemented(format!("value: {}", val)).into()),
+    }
+}
+
+// Evaluates AND/OR with SQL three-valued logic. The right operand is only
+// evaluated for the rows the left operand did not already decide; false
+// for AND and true for OR. Decided rows are filled with null before
+// combining since the kleene kernels ignore the right value for them.
+fn compute_logical_op(
+    rec: &RecordBatch,
+    table_aliases: &[Vec<String>],
+    left: &Expr,
+    op: &BinaryOperator,
+    right: &Expr,
+    options: &ComputeValueOptions,
+) -> Result<ArrayRef> {
+    let left = compute_expr(rec, table_aliases, left, options)?;
+    let left = as_boolean_array(&left)?;
+
+    let decided_value = *op == BinaryOperator::Or;
+    let undecided = left
+        .iter()
+        .map(|val| Some(val != Some(decided_value)))
+        .collect::<BooleanArray>();
+
+    let right = match undecided.true_count() {
+        0 => BooleanArray::new_null(rec.num_rows()),
+        num_undecided if num_undecided == rec.num_rows() => {
+            let right = compute_expr(rec, table_aliases, right, options)?;
+            as_boolean_array(&right)?.clone()
+        }
+        _ => {
+            let undecided_rec = arrow::compute::filter_record_batch(rec, &undecided)?;
+            let right = compute_expr(&undecided_rec, table_aliases, right, options)?;
+            let mut right_vals = as_boolean_array(&right)?.iter();
+            undecided
+                .values()
+                .iter()
+                .map(|is_undecided| {
+                    if is_undecided {
+                        right_vals.next().flatten()
+                    } else {
+                        None
+                    }
+                })
+                .collect::<BooleanArray>()
+        }
+    };
+
+    let res = match op {
+        BinaryOperator::And => arrow::compute::and_kleene(left, &right)?,
+        _ => arrow::compute::or_kleene(left, &right)?,
+    };
+    Ok(Arc::new(res))
+}
+
+// Compares the value against each item in the list and ors the results
+// together, so a list containing null produces null instead of false
+// when no other item matches. Items that are strings when the value is
+// numeric (or the reverse) are cast to the value's type first.
+fn compute_in_list(
+    rec: &RecordBatch,
+    table_aliases: &[Vec<String>],
+    expr: &Expr,
+    list: &[Expr],
+    negated: bool,
+    options: &ComputeValueOptions,
+) -> Result<ArrayRef> {
+    if list.is_empty() {
+        return Err(ComputeValueError::EmptyInList.into());
+    }
+
+    let value = compute_expr(rec, table_aliases, expr, options)?;
+
+    let mut res: Option<BooleanArray> = None;
+    for item in list {
+        let item = compute_expr(rec, table_aliases, item, options)?;
+        let item = if (is_string_type(value.data_type()) && item.data_type().is_numeric())
+            || (value.data_type().is_numeric() && is_string_type(item.data_type()))
+        {
+            arrow::compute::cast(&item, value.data_type())?
+        } else {
+            item
+        };
+
+        let item_res = compute_comparison(value.clone(), &BinaryOperator::Eq, item, options)?;
+        let item_res = as_boolean_array(&item_res)?;
+        res = match res {
+            Some(res) => Some(arrow::compute::or_kleene(&res, item_res)?),
+            None => Some(item_res.clone()),
+        };
+    }
+
+    // the list is not empty so res is always set
+    let res = res.unwrap();
+    if negated {
+        Ok(Arc::new(arrow::compute::not(&res)?))
+    } else {
+        Ok(Arc::new(res))
+    }
+}
+
+// Same as value >= low and value <= high, bounds are inclusive.
+fn compute_between(
+    rec: &RecordBatch,
+    table_aliases: &[Vec<String>],
+    expr: &Expr,
+    low: &Expr,
+    high: &Expr,
+    negated: bool,
+    options: &ComputeValueOptions,
+) -> Result<ArrayRef> {
+    let value = compute_expr(rec, table_aliases, expr, options)?;
+    let low = compute_expr(rec, table_aliases, low, options)?;
+    let high = compute_expr(rec, table_aliases, high, options)?;
+
+    let above_low = compute_comparison(value.clone(), &BinaryOperator::GtEq, low, options)?;
+    let below_high = compute_comparison(value, &BinaryOperator::LtEq, high, options)?;
+    let res = arrow::compute::and_kleene(
+        as_boolean_array(&above_low)?,
+        as_boolean_array(&below_high)?,
+    )?;
+
+    if negated {
+        Ok(Arc::new(arrow::compute::not(&res)?))
+    } else {
+        Ok(Arc::new(res))
+    }
+}
+
+// % matches any number of characters and _ a single character. A
+// backslash escapes the next character so \% matches a literal %.
+// Other escape characters are not supported.
+fn compute_like(
+    rec: &RecordBatch,
+    table_aliases: &[Vec<String>],
+    like_expr: &Expr,
+    options: &ComputeValueOptions,
+) -> Result<ArrayRef> {
+    let (expr, pattern, escape_char, negated, case_insensitive) = match like_expr {
+        Expr::Like {
+            negated,
+            expr,
+            pattern,
+            escape_char,
+            ..
+        } => (expr, pattern, escape_char, *negated, false),
+        Expr::ILike {
+            negated,
+            expr,
+            pattern,
+            escape_char,
+            ..
+        } => (expr, pattern, escape_char, *negated, true),
+        _ => {
+            return Err(ComputeValueError::NotImplemented(format!(
+                "like expression: {}",
+                like_expr
+            ))
+            .into())
+        }
+    };
+
+    if let Some(escape_char) = escape_char {
+        if escape_char != "\\" {
+            return Err(ComputeValueError::NotImplemented(format!(
+                "like escape character: {}",
+                escape_char
+            ))
+            .into());
+        }
+    }
+
+    let value = compute_expr(rec, table_aliases, expr, options)?;
+    let pattern = compute_expr(rec, table_aliases, pattern, options)?;
+    if !is_string_type(value.data_type()) || !is_string_type(pattern.data_type()) {
+        let op = if case_insensitive { "ILIKE" } else { "LIKE" };
+        return Err(ComputeValueError::UnableToApplyOperator(
+            op.to_string(),
+            value.data_type().clone(),
+            pattern.data_type().clone(),
+        )
+        .into());
+    }
+    let value = arrow::compute::cast(&value, &DataType::Utf8)?;
+    let pattern = arrow::compute::cast(&pattern, &DataType::Utf8)?;
+
+    let res = match (case_insensitive, negated) {
+        (false, false) => comparison::like(&value, &pattern)?,
+        (false, true) => comparison::nlike(&value, &pattern)?,
+        (true, false) => comparison::ilike(&value, &pattern)?,
+        (true, true) => comparison::nilike(&value, &pattern)?,
+    };
+    Ok(Arc::new(res))
+}
+
+// The result of the first condition that is true is used for each row.
+// A null condition counts as false and rows without a true condition use
+// the else result, or null when there is no else. Results are promoted
+// to a common type, e.g. an Int64 and a Float64 result produce Float64.
+fn compute_case(
+    rec: &RecordBatch,
+    table_aliases: &[Vec<String>],
+    operand: Option<&Expr>,
+    conditions: &[Expr],
+    results: &[Expr],
+    else_result: Option<&Expr>,
+    options: &ComputeValueOptions,
+) -> Result<ArrayRef> {
+    let operand = match operand {
+        Some(operand) => Some(compute_expr(rec, table_aliases, operand, options)?),
+        None => None,
+    };
+
+    let mut masks = Vec::new();
+    for condition in conditions {
+        let condition = compute_expr(rec, table_aliases, condition, options)?;
+        let mask = match &operand {
+            Some(operand) => {
+                compute_comparison(operand.clone(), &BinaryOperator::Eq, condition, options)?
+            }
+            None => condition,
+        };
+        masks.push(arrow::compute::prep_null_mask_filter(as_boolean_array(
+            &mask,
+        )?));
+    }
+
+    let mut values = Vec::new();
+    for result in results {
+        values.push(compute_expr(rec, table_aliases, result, options)?);
+    }
+    let else_value = match else_result {
+        Some(else_result) => compute_expr(rec, table_aliases, else_result, options)?,
+        None => arrow::array::new_null_array(&DataType::Null, rec.num_rows()),
+    };
+
+    let mut result_type = else_value.data_type().clone();
+    for value in &values {
+        result_type = common_data_type(&result_type, value.data_type())?;
+    }
+
+    // apply the conditions in reverse so the first true condition wins
+    let mut res = arrow::compute::cast(&else_value, &result_type)?;
+    for (mask, value) in masks.iter().zip(values.iter()).rev() {
+        let value = arrow::compute::cast(value, &result_type)?;
+        res = arrow::compute::kernels::zip::zip(mask, &value, &res)?;
+    }
+    Ok(res)
+}
+
+fn common_data_type(left: &DataType, right: &DataType) -> Result<DataType> {
+    if left == right || *right == DataType::Null {
+        Ok(left.clone())
+    } else if *left == DataType::Null {
+        Ok(right.clone())
+    } else if left.is_numeric() && right.is_numeric() {
+        if left.is_floating() || right.is_floating() {
+            Ok(DataType::Float64)
+        } else {
+            Ok(DataType::Int64)
+        }
+    } else if is_string_type(left) && is_string_type(right) {
+        Ok(DataType::Utf8)
+    } else {
+        Err(ComputeValueError::UnableToFindCommonDataType(left.clone(), right.clone()).into())
+    }
+}
+
+fn as_boolean_array(arr: &ArrayRef) -> Result<&BooleanArray> {
+    match arr.as_boolean_opt() {
+        Some(arr) => Ok(arr),
+        None => Err(ComputeValueError::TypeMismatch {
+            expected: "boolean".to_string(),
+            found: arr.data_type().clone(),
+        }
+        .into()),
+    }
+}
+
+fn compute_function(
+    rec: &RecordBatch,
+    table_aliases: &[Vec<String>],
+    func: &Function,
+    options: &ComputeValueOptions,
+) -> Result<ArrayRef> {
+    let name = func.name.to_string().to_lowercase();
+    let scalar_func = options.functions.get(&name);
+    let builtin_func = find_builtin_function(&name);
+    if scalar_func.is_none() && builtin_func.is_none() {
+        return Err(ComputeValueError::FunctionNotFound(name).into());
+    }
+
+    let args = match &func.args {
+        FunctionArguments::None => Vec::new(),
+        FunctionArguments::List(arg_list) => arg_list
+            .args
+            .iter()
+            .map(|arg| match arg {
+                FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) => {
+                    compute_expr(rec, table_aliases, expr, options)
+                }
+                _ => Err(
+                    ComputeValueError::NotImplemented(format!("function argument: {}", arg)).into(),
+                ),
+            })
+            .collect::<Result<Vec<ArrayRef>>>()?,
+        FunctionArguments::Subquery(_) => {
+            return Err(ComputeValueError::NotImplemented(format!("function: {}", func)).into())
+        }
+    };
+
+    // functions added to the options take precedence over the built in ones
+    match (scalar_func, builtin_func) {
+        (Some(scalar_func), _) => scalar_func(&args),
+        (None, Some(builtin_func)) => builtin_func(&args),
+        (None, None) => Err(ComputeValueError::FunctionNotFound(name).into()),
+    }
+}
+
+fn compute_unary_op(op: &UnaryOperator, value: ArrayRef) -> Result<ArrayRef> {
+    // a null literal stays null
+    if *value.data_type() == DataType::Null {
+        return match op {
+            UnaryOperator::Not => Ok(arrow::array::new_null_array(
+                &DataType::Boolean,
+                value.len(),
+            )),
+            _ => Ok(value),
+        };
+    }
+
+    match op {
+        UnaryOperator::Not => {
+            let value = as_boolean_array(&value)?;
+            Ok(Arc::new(arrow::compute::not(value)?))
+        }
+        UnaryOperator::Minus | UnaryOperator::Plus => {
+            if !value.data_type().is_numeric() {
+                return Err(ComputeValueError::UnableToApplyUnaryOperator(
+                    op.to_string(),
+                    value.data_type().clone(),
+                )
+                .into());
+            }
+            if *op == UnaryOperator::Minus {
+                Ok(numeric::neg(&value)?)
+            } else {
+                Ok(value)
+            }
+        }
+        _ => Err(ComputeValueError::NotImplemented(format!("unary operator: {}", op)).into()),
+    }
+}
+
+fn compute_binary_op(
+    left: ArrayRef,
+    op: &BinaryOperator,
+    right: ArrayRef,
+    options: &ComputeValueOptions,
+) -> Result<ArrayRef> {
+    match op {
+        BinaryOperator::Eq
+        | BinaryOperator::NotEq
+        | BinaryOperator::Lt
+        | BinaryOperator::LtEq
+        | BinaryOperator::Gt
+        | BinaryOperator::GtEq => compute_comparison(left, op, right, options),
+        BinaryOperator::Plus
+        | BinaryOperator::Minus
+        | BinaryOperator::Multiply
+        | BinaryOperator::Divide
+        | BinaryOperator::Modulo => compute_arithmetic(left, op, right),
+        BinaryOperator::StringConcat => compute_string_concat(left, right),
+        _ => Err(ComputeValueError::NotImplemented(format!("binary operator: {}", op)).into()),
+    }
+}
+
+fn compute_comparison(
+    left: ArrayRef,
+    op: &BinaryOperator,
+    right: ArrayRef,
+    options: &ComputeValueOptions,
+) -> Result<ArrayRef> {
+    let (left, right) = coerce_comparison_types(left, right)?;
+
+    let res = match op {
+        BinaryOperator::Eq => cmp::eq(&left, &right)?,
+        BinaryOperator::NotEq => cmp::neq(&left, &right)?,
+        BinaryOperator::Lt => cmp::lt(&left, &right)?,
+        BinaryOperator::LtEq => cmp::lt_eq(&left, &right)?,
+        BinaryOperator::Gt => cmp::gt(&left, &right)?,
+        BinaryOperator::GtEq => cmp::gt_eq(&left, &right)?,
+        _ => {
+            return Err(
+                ComputeValueError::NotImplemented(format!("comparison operator: {}", op)).into(),
+            )
+        }
+    };
+
+    // the arrow kernels compare floats with a total order
+    // which already matches NanPolicy::TotalOrder
+    match options.nan_policy {
+        NanPolicy::TotalOrder => Ok(Arc::new(res)),
+        NanPolicy::Ieee => {
+            let (left_nans, right_nans) = match (nan_mask(&left), nan_mask(&right)) {
+                (Some(left_nans), Some(right_nans)) => (left_nans, right_nans),
+                _ => return Ok(Arc::new(res)),
+            };
+            let nan_res = *op == BinaryOperator::NotEq;
+            let res = res
+                .iter()
+                .zip(left_nans.into_iter().zip(right_nans))
+                .map(|(val, (left_nan, right_nan))| {
+                    val.map(|val| if left_nan || right_nan { nan_res } else { val })
+                })
+                .collect::<BooleanArray>();
+            Ok(Arc::new(res))
+        }
+    }
+}
+
+// Integer operands are computed as Int64 and anything involving a float
+// as Float64. Integer overflow is an error rather than wrapping around and
+// integer division truncates towards zero, e.g. 7 / 2 is 3.
+fn compute_arithmetic(left: ArrayRef, op: &BinaryOperator, right: ArrayRef) -> Result<ArrayRef> {
+    // a null literal takes the type of the other operand
+    let (left, right) = match (left.data_type(), right.data_type()) {
+        (DataType::Null, right_type) => (arrow::compute::cast(&left, right_type)?, right),
+        (left_type, DataType::Null) => {
+            let right = arrow::compute::cast(&right, left_type)?;
+            (left, right)
+        }
+        _ => (left, right),
+    };
+    let (left_type, right_type) = (left.data_type(), right.data_type());
+
+    // subtracting two dates or timestamps gives a duration
+    if *op == BinaryOperator::Minus && (is_temporal_type(left_type) || is_temporal_type(right_type))
+    {
+        let (left, right) = coerce_temporal_types(left, right)?;
+        return Ok(numeric::sub(&left, &right)?);
+    }
+
+    if !left_type.is_numeric() || !right_type.is_numeric() {
+        return Err(ComputeValueError::UnableToApplyOperator(
+            op.to_string(),
+            left_type.clone(),
+            right_type.clone(),
+        )
+        .into());
+    }
+
+    let target_type = if left_type.is_floating() || right_type.is_floating() {
+        DataType::Float64
+    } else {
+        DataType::Int64
+    };
+    let left = arrow::compute::cast(&left, &target_type)?;
+    let right = arrow::compute::cast(&right, &target_type)?;
+
+    // dividing by zero gives null instead of an error, or inf and NaN
+    // for floats, so replace zero divisors with null first
+    let right = match op {
+        BinaryOperator::Divide | BinaryOperator::Modulo => {
+            let zero = arrow::compute::cast(&Int64Array::from(vec![0]), &target_type)?;
+            let is_zero = cmp::eq(&right, &Scalar::new(zero))?;
+            arrow::compute::nullif(&right, &is_zero)?
+        }
+        _ => right,
+    };
+
+    let res = match op {
+        BinaryOperator::Plus => numeric::add(&left, &right)?,
+        BinaryOperator::Minus => numeric::sub(&left, &right)?,
+        BinaryOperator::Multiply => numeric::mul(&left, &right)?,
+        BinaryOperator::Divide => numeric::div(&left, &right)?,
+        BinaryOperator::Modulo => numeric::rem(&left, &right)?,
+        _ => {
+            return Err(
+                ComputeValueError::NotImplemented(format!("arithmetic operator: {}", op)).into(),
+            )
+        }
+    };
+    Ok(res)
+}
+
+// Non-string operands are cast to strings, e.g. 'id-' || 1 is 'id-1'.
+// A null on either side produces null.
+fn compute_string_concat(left: ArrayRef, right: ArrayRef) -> Result<ArrayRef> {
+    let left = arrow::compute::cast(&left, &DataType::Utf8)?;
+    let right = arrow::compute::cast(&right, &DataType::Utf8)?;
+    let res =
+        concat_elements::concat_elements_utf8(left.as_string::<i32>(), right.as_string::<i32>())?;
+    Ok(Arc::new(res))
+}
+
+fn coerce_comparison_types(left: ArrayRef, right: ArrayRef) -> Result<(ArrayRef, ArrayRef)> {
+    if left.data_type() == right.data_type() {
+        return Ok((left, right));
+    }
+
+    let (left_type, right_type) = (left.data_type(), right.data_type());
+
+    // a null literal compares as null against any type
+    if *left_type == DataType::Null {
+        return Ok((arrow::compute::cast(&left, right_type)?, right));
+    } else if *right_type == DataType::Null {
+        return Ok((left.clone(), arrow::compute::cast(&right, left_type)?));
+    }
+
+    if is_temporal_type(left_type) || is_temporal_type(right_type) {
+        return coerce_temporal_types(left, right);
+    }
+
+    // string literals are Utf8 so compare them against large
+    // and view string columns as Utf8
+    if is_string_type(left_type) && is_string_type(right_type) {
+        return Ok((
+            arrow::compute::cast(&left, &DataType::Utf8)?,
+            arrow::compute::cast(&right, &DataType::Utf8)?,
+        ));
+    }
+
+    if !left_type.is_numeric() || !right_type.is_numeric() {
+        return Err(ComputeValueError::UnableToCompareDataTypes(
+            left_type.clone(),
+            right_type.clone(),
+        )
+        .into());
+    }
+
+    let target_type = if left_type.is_floating() || right_type.is_floating() {
+        DataType::Float64
+    } else {
+        DataType::Int64
+    };
+    Ok((
+        arrow::compute::cast(&left, &target_type)?,
+        arrow::compute::cast(&right, &target_type)?,
+    ))
+}
+
+// Strings are parsed as the type of the other side, so
+// ts > '2024-01-01' compares against midnight of that day. For timezone
+// aware timestamps a string without an offset is read in the column's
+// timezone. Dates are compared with timestamps as midnight of that day.
+// Timezone aware and naive timestamps can't be mixed since there is no
+// way to tell which instant a naive timestamp refers to.
+fn coerce_temporal_types(left: ArrayRef, right: ArrayRef) -> Result<(ArrayRef, ArrayRef)> {
+    let (left_type, right_type) = (left.data_type().clone(), right.data_type().clone());
+    match (&left_type, &right_type) {
+        (left_type, right_type) if left_type == right_type => Ok((left, right)),
+        (_, right_type) if is_string_type(right_type) && is_temporal_type(&left_type) => {
+            Ok((left, parse_temporal(&right, &left_type)?))
+        }
+        (left_type, _) if is_string_type(left_type) && is_temporal_type(&right_type) => {
+            Ok((parse_temporal(&left, &right_type)?, right))
+        }
+        (DataType::Timestamp(_, left_tz), DataType::Timestamp(_, right_tz)) => {
+            if left_tz.is_some() != right_tz.is_some() {
+                return Err(ComputeValueError::UnableToMixTimezoneAwareAndNaive(
+                    left_type.clone(),
+                    right_type.clone(),
+                )
+                .into());
+            }
+            Ok((left, arrow::compute::cast(&right, &left_type)?))
+        }
+        (DataType::Timestamp(..), DataType::Date32 | DataType::Date64) => {
+            Ok((left, arrow::compute::cast(&right, &left_type)?))
+        }
+        (DataType::Date32 | DataType::Date64, DataType::Timestamp(..)) => {
+            Ok((arrow::compute::cast(&left, &right_type)?, right))
+        }
+        (DataType::Date32, DataType::Date64) => {
+            Ok((arrow::compute::cast(&left, &right_type)?, right))
+        }
+        (DataType::Date64, DataType::Date32) => {
+            Ok((left, arrow::compute::cast(&right, &left_type)?))
+        }
+        _ => Err(ComputeValueError::UnableToCompareDataTypes(left_type, right_type).into()),
+    }
+}
+
+fn parse_temporal(arr: &ArrayRef, data_type: &DataType) -> Result<ArrayRef> {
+    let options = arrow::compute::CastOptions {
+        safe: false,
+        ..Default::default()
+    };
+    match arrow::compute::cast_with_options(arr, data_type, &options) {
+        Ok(arr) => Ok(arr),
+        Err(_) => Err(ComputeValueError::UnableToParseTemporalValue(data_type.clone()).into()),
+    }
+}
+
+fn is_temporal_type(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Date32 | DataType::Date64 | DataType::Timestamp(..)
+    )
+}
+
+fn is_string_type(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View
+    )
+}
+
+pub fn nan_mask(arr: &ArrayRef) -> Option<Vec<bool>> {
+    match arr.data_type() {
+        DataType::Float32 => Some(
+            arr.as_primitive::<Float32Type>()
+                .iter()
+                .map(|val| val.is_some_and(|val| val.is_nan()))
+                .collect(),
+        ),
+        DataType::Float64 => Some(
+            arr.as_primitive::<Float64Type>()
+                .iter()
+                .map(|val| val.is_some_and(|val| val.is_nan()))
+                .collect(),
+        ),
+        _ => None,
+    }
+}
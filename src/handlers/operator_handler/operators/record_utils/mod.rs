@@ -1,6 +1,50 @@
+mod compute_value;
+mod record_accumulators;
+mod record_aggregate;
 mod record_aliases;
+mod record_compute_queue;
+mod record_distinct;
 mod record_filter;
+mod record_group_keys;
+mod record_join;
+mod record_partition;
 mod record_projection;
+mod record_sort;
+mod record_union;
+mod scalar_functions;
+#[cfg(test)]
+mod test_compute_value;
+#[cfg(test)]
+mod test_record_accumulators;
+#[cfg(test)]
+mod test_record_aggregate;
+#[cfg(test)]
+mod test_record_compute_queue;
+#[cfg(test)]
+mod test_record_distinct;
+#[cfg(test)]
+mod test_record_filter;
+#[cfg(test)]
+mod test_record_group_keys;
+#[cfg(test)]
+mod test_record_join;
+#[cfg(test)]
+mod test_record_partition;
+#[cfg(test)]
+mod test_record_projection;
+#[cfg(test)]
+mod test_record_sort;
+#[cfg(test)]
+mod test_record_union;
 
+pub use compute_value::{ComputeValueOptions, NanPolicy};
+pub use record_aggregate::RecordAggregator;
 pub use record_aliases::get_record_table_aliases;
+pub use record_compute_queue::RecordComputeQueue;
+pub use record_distinct::RecordDistinct;
+pub use record_filter::filter_record;
+pub use record_join::JoinBuildSide;
+pub use record_partition::{hash_partition_record, partition_record};
 pub use record_projection::project_record;
+pub use record_sort::{RecordSorter, SortedRunMerger};
+pub use record_union::RecordUnion;
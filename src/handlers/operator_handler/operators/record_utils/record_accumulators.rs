@@ -0,0 +1,323 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::{Array, ArrayRef, AsArray, Float64Array, Int64Array, UInt32Array};
+use arrow::datatypes::{DataType, Float64Type, Int64Type};
+use arrow::row::{RowConverter, SortField};
+use thiserror::Error;
+
+use super::compute_value::{nan_mask, NanPolicy};
+
+#[derive(Debug, Error)]
+pub enum AccumulatorError {
+    #[error("{0} does not support data type {1}")]
+    UnsupportedDataType(String, DataType),
+    #[error("unable to merge accumulator {0} into {1}")]
+    UnableToMergeAccumulators(String, String),
+    #[error("sum overflowed")]
+    SumOverflowed,
+}
+
+// Aggregate accumulators follow SQL null semantics: null inputs are
+// skipped by every function except count(*), so an all-null group
+// produces null for sum/avg/min/max and 0 for count(col).
+#[derive(Debug, Clone)]
+pub enum Accumulator {
+    Count {
+        count: i64,
+    },
+    CountStar {
+        count: i64,
+    },
+    SumInt {
+        sum: Option<i64>,
+    },
+    SumFloat {
+        sum: Option<f64>,
+    },
+    Avg {
+        sum: f64,
+        count: i64,
+    },
+    Min {
+        value: Option<ArrayRef>,
+        data_type: DataType,
+        nan_policy: NanPolicy,
+    },
+    Max {
+        value: Option<ArrayRef>,
+        data_type: DataType,
+        nan_policy: NanPolicy,
+    },
+}
+
+impl Accumulator {
+    pub fn new_count() -> Accumulator {
+        Accumulator::Count { count: 0 }
+    }
+
+    pub fn new_count_star() -> Accumulator {
+        Accumulator::CountStar { count: 0 }
+    }
+
+    pub fn new_sum(input_type: &DataType) -> Result<Accumulator> {
+        if input_type.is_integer() {
+            Ok(Accumulator::SumInt { sum: None })
+        } else if input_type.is_floating() {
+            Ok(Accumulator::SumFloat { sum: None })
+        } else {
+            Err(AccumulatorError::UnsupportedDataType("sum".to_string(), input_type.clone()).into())
+        }
+    }
+
+    pub fn new_avg(input_type: &DataType) -> Result<Accumulator> {
+        if input_type.is_integer() || input_type.is_floating() {
+            Ok(Accumulator::Avg { sum: 0.0, count: 0 })
+        } else {
+            Err(AccumulatorError::UnsupportedDataType("avg".to_string(), input_type.clone()).into())
+        }
+    }
+
+    // NanPolicy::TotalOrder treats NaN as larger than every other value so
+    // max returns NaN when the group contains one. NanPolicy::Ieee skips
+    // NaN values the same way nulls are skipped.
+    pub fn new_min(input_type: &DataType, nan_policy: NanPolicy) -> Accumulator {
+        Accumulator::Min {
+            value: None,
+            data_type: input_type.clone(),
+            nan_policy,
+        }
+    }
+
+    pub fn new_max(input_type: &DataType, nan_policy: NanPolicy) -> Accumulator {
+        Accumulator::Max {
+            value: None,
+            data_type: input_type.clone(),
+            nan_policy,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Accumulator::Count { .. } => "count",
+            Accumulator::CountStar { .. } => "count(*)",
+            Accumulator::SumInt { .. } | Accumulator::SumFloat { .. } => "sum",
+            Accumulator::Avg { .. } => "avg",
+            Accumulator::Min { .. } => "min",
+            Accumulator::Max { .. } => "max",
+        }
+    }
+
+    pub fn output_type(&self) -> DataType {
+        match self {
+            Accumulator::Count { .. } | Accumulator::CountStar { .. } => DataType::Int64,
+            Accumulator::SumInt { .. } => DataType::Int64,
+            Accumulator::SumFloat { .. } | Accumulator::Avg { .. } => DataType::Float64,
+            Accumulator::Min { data_type, .. } | Accumulator::Max { data_type, .. } => {
+                data_type.clone()
+            }
+        }
+    }
+
+    pub fn update(&mut self, values: &ArrayRef) -> Result<()> {
+        match self {
+            Accumulator::Count { count } => {
+                *count += non_null_count(values) as i64;
+            }
+            Accumulator::CountStar { count } => {
+                *count += values.len() as i64;
+            }
+            Accumulator::SumInt { sum } => {
+                let values = arrow::compute::cast(values, &DataType::Int64)?;
+                let values_sum = arrow::compute::sum_checked(values.as_primitive::<Int64Type>())?;
+                *sum = add_int_sums(*sum, values_sum)?;
+            }
+            Accumulator::SumFloat { sum } => {
+                let values = arrow::compute::cast(values, &DataType::Float64)?;
+                let values_sum = arrow::compute::sum(values.as_primitive::<Float64Type>());
+                *sum = add_float_sums(*sum, values_sum);
+            }
+            Accumulator::Avg { sum, count } => {
+                let values = arrow::compute::cast(values, &DataType::Float64)?;
+                if let Some(values_sum) = arrow::compute::sum(values.as_primitive::<Float64Type>())
+                {
+                    *sum += values_sum;
+                }
+                *count += non_null_count(&values) as i64;
+            }
+            Accumulator::Min {
+                value, nan_policy, ..
+            } => {
+                *value = min_max_value(value.as_ref(), values, true, *nan_policy)?;
+            }
+            Accumulator::Max {
+                value, nan_policy, ..
+            } => {
+                *value = min_max_value(value.as_ref(), values, false, *nan_policy)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Merge the state of a partial accumulator for the same
+    // function into this accumulator.
+    pub fn merge(&mut self, other: &Accumulator) -> Result<()> {
+        match (&mut *self, other) {
+            (Accumulator::Count { count }, Accumulator::Count { count: other_count })
+            | (Accumulator::CountStar { count }, Accumulator::CountStar { count: other_count }) => {
+                *count += other_count;
+            }
+            (Accumulator::SumInt { sum }, Accumulator::SumInt { sum: other_sum }) => {
+                *sum = add_int_sums(*sum, *other_sum)?;
+            }
+            (Accumulator::SumFloat { sum }, Accumulator::SumFloat { sum: other_sum }) => {
+                *sum = add_float_sums(*sum, *other_sum);
+            }
+            (
+                Accumulator::Avg { sum, count },
+                Accumulator::Avg {
+                    sum: other_sum,
+                    count: other_count,
+                },
+            ) => {
+                *sum += other_sum;
+                *count += other_count;
+            }
+            (
+                Accumulator::Min {
+                    value, nan_policy, ..
+                },
+                Accumulator::Min {
+                    value: Some(other_value),
+                    ..
+                },
+            ) => {
+                *value = min_max_value(value.as_ref(), other_value, true, *nan_policy)?;
+            }
+            (
+                Accumulator::Max {
+                    value, nan_policy, ..
+                },
+                Accumulator::Max {
+                    value: Some(other_value),
+                    ..
+                },
+            ) => {
+                *value = min_max_value(value.as_ref(), other_value, false, *nan_policy)?;
+            }
+            (Accumulator::Min { .. }, Accumulator::Min { value: None, .. })
+            | (Accumulator::Max { .. }, Accumulator::Max { value: None, .. }) => {}
+            _ => {
+                return Err(AccumulatorError::UnableToMergeAccumulators(
+                    other.name().to_string(),
+                    self.name().to_string(),
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    // Returns a single element array with the aggregated value.
+    pub fn evaluate(&self) -> Result<ArrayRef> {
+        match self {
+            Accumulator::Count { count } | Accumulator::CountStar { count } => {
+                Ok(Arc::new(Int64Array::from(vec![*count])))
+            }
+            Accumulator::SumInt { sum } => Ok(Arc::new(Int64Array::from(vec![*sum]))),
+            Accumulator::SumFloat { sum } => Ok(Arc::new(Float64Array::from(vec![*sum]))),
+            Accumulator::Avg { sum, count } => {
+                let avg = if *count == 0 {
+                    None
+                } else {
+                    Some(*sum / *count as f64)
+                };
+                Ok(Arc::new(Float64Array::from(vec![avg])))
+            }
+            Accumulator::Min {
+                value, data_type, ..
+            }
+            | Accumulator::Max {
+                value, data_type, ..
+            } => match value {
+                Some(value) => Ok(value.clone()),
+                None => Ok(arrow::array::new_null_array(data_type, 1)),
+            },
+        }
+    }
+}
+
+fn non_null_count(values: &ArrayRef) -> usize {
+    values.len() - values.logical_nulls().map_or(0, |nulls| nulls.null_count())
+}
+
+fn add_int_sums(left: Option<i64>, right: Option<i64>) -> Result<Option<i64>> {
+    match (left, right) {
+        (Some(left), Some(right)) => match left.checked_add(right) {
+            Some(sum) => Ok(Some(sum)),
+            None => Err(AccumulatorError::SumOverflowed.into()),
+        },
+        (left, None) => Ok(left),
+        (None, right) => Ok(right),
+    }
+}
+
+fn add_float_sums(left: Option<f64>, right: Option<f64>) -> Option<f64> {
+    match (left, right) {
+        (Some(left), Some(right)) => Some(left + right),
+        (left, None) => left,
+        (None, right) => right,
+    }
+}
+
+// Find the smallest (or largest) non-null value of the current value and
+// the new values. Values are compared using the arrow row format so any
+// orderable data type is supported.
+fn min_max_value(
+    current: Option<&ArrayRef>,
+    values: &ArrayRef,
+    is_min: bool,
+    nan_policy: NanPolicy,
+) -> Result<Option<ArrayRef>> {
+    let values = match current {
+        Some(current) => arrow::compute::concat(&[current.as_ref(), values.as_ref()])?,
+        None => values.clone(),
+    };
+
+    let nans = match nan_policy {
+        NanPolicy::Ieee => nan_mask(&values),
+        NanPolicy::TotalOrder => None,
+    };
+
+    let converter = RowConverter::new(vec![SortField::new(values.data_type().clone())])?;
+    let rows = converter.convert_columns(std::slice::from_ref(&values))?;
+
+    let mut best_idx: Option<usize> = None;
+    for idx in 0..values.len() {
+        if values.is_null(idx) || nans.as_ref().is_some_and(|nans| nans[idx]) {
+            continue;
+        }
+        best_idx = match best_idx {
+            Some(best_idx) => {
+                let ord = rows.row(idx).cmp(&rows.row(best_idx));
+                if (is_min && ord.is_lt()) || (!is_min && ord.is_gt()) {
+                    Some(idx)
+                } else {
+                    Some(best_idx)
+                }
+            }
+            None => Some(idx),
+        };
+    }
+
+    // take the value instead of slicing so the accumulator does
+    // not hold onto the buffers of the whole record
+    match best_idx {
+        Some(idx) => Ok(Some(arrow::compute::take(
+            &values,
+            &UInt32Array::from(vec![idx as u32]),
+            None,
+        )?)),
+        None => Ok(None),
+    }
+}
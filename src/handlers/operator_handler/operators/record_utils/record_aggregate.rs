@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::{ArrayRef, BooleanArray, RecordBatch, UInt32Array};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::row::OwnedRow;
+use sqlparser::ast::Expr;
+use thiserror::Error;
+
+use crate::planner::{Aggregate, AggregateFunction};
+
+use super::compute_value::{compute_value, ComputeValueOptions, NanPolicy};
+use super::record_accumulators::Accumulator;
+use super::record_group_keys::RecordGroupKeys;
+
+#[derive(Debug, Error)]
+pub enum RecordAggregatorError {
+    #[error("aggregate requires an argument: {0}")]
+    AggregateRequiresAnArgument(String),
+}
+
+// Hash aggregation over a stream of records. Each record is aggregated
+// on its own into partial accumulators which are then merged into the
+// accumulators of the groups seen so far. Groups are output in order of
+// first appearance.
+//
+// The output record has one column per group by expression followed by
+// one column per aggregate. Each column is named after the expression
+// that produced it so later stages can reference the aggregate results.
+#[derive(Debug)]
+pub struct RecordAggregator {
+    group_by: Vec<Expr>,
+    aggregates: Vec<Aggregate>,
+    options: ComputeValueOptions,
+
+    group_keys: Option<RecordGroupKeys>,
+    group_idxs: HashMap<OwnedRow, usize>,
+    groups: Vec<(OwnedRow, Vec<Accumulator>)>,
+    memory_size_in_bytes: usize,
+}
+
+impl RecordAggregator {
+    pub fn new(group_by: Vec<Expr>, aggregates: Vec<Aggregate>) -> RecordAggregator {
+        RecordAggregator {
+            group_by,
+            aggregates,
+            options: ComputeValueOptions::default(),
+            group_keys: None,
+            group_idxs: HashMap::new(),
+            groups: Vec::new(),
+            memory_size_in_bytes: 0,
+        }
+    }
+
+    // Decides whether min and max skip NaN values or return NaN.
+    pub fn set_nan_policy(&mut self, nan_policy: NanPolicy) -> &Self {
+        self.options.nan_policy = nan_policy;
+        self
+    }
+
+    pub fn num_groups(&self) -> usize {
+        self.groups.len()
+    }
+
+    // An estimate of the memory held by the groups; the encoded
+    // key of each group plus its accumulators.
+    pub fn memory_size_in_bytes(&self) -> usize {
+        self.memory_size_in_bytes
+    }
+
+    pub fn update(&mut self, rec: &RecordBatch, table_aliases: &[Vec<String>]) -> Result<()> {
+        // without any group by expressions every row belongs to the same group
+        let mut key_columns: Vec<ArrayRef> = Vec::new();
+        if self.group_by.is_empty() {
+            key_columns.push(Arc::new(BooleanArray::from(vec![true; rec.num_rows()])));
+        }
+        for expr in &self.group_by {
+            key_columns.push(compute_value(rec, table_aliases, expr, &self.options)?);
+        }
+        let mut arg_columns: Vec<Option<ArrayRef>> = Vec::new();
+        for aggregate in &self.aggregates {
+            match &aggregate.arg {
+                Some(arg) => {
+                    arg_columns.push(Some(compute_value(rec, table_aliases, arg, &self.options)?))
+                }
+                None => arg_columns.push(None),
+            }
+        }
+
+        if self.group_keys.is_none() {
+            self.group_keys = Some(RecordGroupKeys::new(
+                key_columns
+                    .iter()
+                    .map(|col| col.data_type().clone())
+                    .collect(),
+            )?);
+        }
+        let record_groups = self.group_keys.as_ref().unwrap().group_rows(&key_columns)?;
+
+        for (key, row_idxs) in record_groups {
+            let row_idxs = UInt32Array::from(row_idxs);
+
+            // partial aggregation of the rows for this record
+            let mut partials: Vec<Accumulator> = Vec::new();
+            for (aggregate, arg_column) in self.aggregates.iter().zip(arg_columns.iter()) {
+                let mut acc =
+                    new_accumulator(aggregate, arg_column.as_ref(), self.options.nan_policy)?;
+                match arg_column {
+                    Some(arg_column) => {
+                        acc.update(&arrow::compute::take(arg_column, &row_idxs, None)?)?;
+                    }
+                    None => {
+                        acc.update(&(Arc::new(row_idxs.clone()) as ArrayRef))?;
+                    }
+                }
+                partials.push(acc);
+            }
+
+            // merge the partial state into the group's accumulators
+            match self.group_idxs.get(&key) {
+                Some(group_idx) => {
+                    let accs = &mut self.groups[*group_idx].1;
+                    for (acc, partial) in accs.iter_mut().zip(partials.iter()) {
+                        acc.merge(partial)?;
+                    }
+                }
+                None => {
+                    self.memory_size_in_bytes += 2 * key.row().as_ref().len()
+                        + partials.len() * std::mem::size_of::<Accumulator>();
+                    self.group_idxs.insert(key.clone(), self.groups.len());
+                    self.groups.push((key, partials));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Returns the aggregated record along with its table aliases. An
+    // aggregate without any group by expressions always returns a single
+    // row, even when no records were aggregated.
+    pub fn finish(&self) -> Result<(RecordBatch, Vec<Vec<String>>)> {
+        let mut fields: Vec<Field> = Vec::new();
+        let mut columns: Vec<ArrayRef> = Vec::new();
+        let mut table_aliases: Vec<Vec<String>> = Vec::new();
+
+        if let Some(group_keys) = &self.group_keys {
+            if !self.group_by.is_empty() {
+                let key_columns =
+                    group_keys.keys_to_columns(self.groups.iter().map(|(key, _)| key.row()))?;
+                for (expr, col) in self.group_by.iter().zip(key_columns) {
+                    let (name, aliases) = group_key_name(expr);
+                    fields.push(Field::new(name, col.data_type().clone(), true));
+                    columns.push(col);
+                    table_aliases.push(aliases);
+                }
+            }
+        } else if !self.group_by.is_empty() {
+            // no records were aggregated so there are no groups
+            for expr in &self.group_by {
+                let (name, aliases) = group_key_name(expr);
+                fields.push(Field::new(name, DataType::Null, true));
+                columns.push(arrow::array::new_null_array(&DataType::Null, 0));
+                table_aliases.push(aliases);
+            }
+        }
+
+        let empty_group: Vec<Accumulator>;
+        let groups: Vec<&Vec<Accumulator>> = if self.groups.is_empty() && self.group_by.is_empty() {
+            empty_group = self
+                .aggregates
+                .iter()
+                .map(|aggregate| new_accumulator(aggregate, None, self.options.nan_policy))
+                .collect::<Result<Vec<Accumulator>>>()?;
+            vec![&empty_group]
+        } else {
+            self.groups.iter().map(|(_, accs)| accs).collect()
+        };
+
+        for (agg_idx, aggregate) in self.aggregates.iter().enumerate() {
+            let values = groups
+                .iter()
+                .map(|accs| accs[agg_idx].evaluate())
+                .collect::<Result<Vec<ArrayRef>>>()?;
+            let col = if values.is_empty() {
+                // there are no groups to take the type from
+                let output_type =
+                    new_accumulator(aggregate, None, self.options.nan_policy)?.output_type();
+                arrow::array::new_empty_array(&output_type)
+            } else {
+                arrow::compute::concat(
+                    &values
+                        .iter()
+                        .map(|value| value.as_ref())
+                        .collect::<Vec<&dyn arrow::array::Array>>(),
+                )?
+            };
+            fields.push(Field::new(
+                aggregate.expr.to_string(),
+                col.data_type().clone(),
+                true,
+            ));
+            columns.push(col);
+            table_aliases.push(Vec::new());
+        }
+
+        let record = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)?;
+        Ok((record, table_aliases))
+    }
+}
+
+// The accumulator input type is only known once the argument has been
+// computed. When no records were aggregated the argument type is unknown
+// and a null typed accumulator is used instead.
+fn new_accumulator(
+    aggregate: &Aggregate,
+    arg_column: Option<&ArrayRef>,
+    nan_policy: NanPolicy,
+) -> Result<Accumulator> {
+    let arg_type = arg_column.map_or(DataType::Null, |col| col.data_type().clone());
+    match (&aggregate.func, &aggregate.arg) {
+        (AggregateFunction::Count, None) => Ok(Accumulator::new_count_star()),
+        (AggregateFunction::Count, Some(_)) => Ok(Accumulator::new_count()),
+        (_, None) => Err(RecordAggregatorError::AggregateRequiresAnArgument(
+            aggregate.expr.to_string(),
+        )
+        .into()),
+        (AggregateFunction::Sum, Some(_)) if arg_type == DataType::Null => {
+            Accumulator::new_sum(&DataType::Int64)
+        }
+        (AggregateFunction::Sum, Some(_)) => Accumulator::new_sum(&arg_type),
+        (AggregateFunction::Avg, Some(_)) if arg_type == DataType::Null => {
+            Accumulator::new_avg(&DataType::Float64)
+        }
+        (AggregateFunction::Avg, Some(_)) => Accumulator::new_avg(&arg_type),
+        (AggregateFunction::Min, Some(_)) => Ok(Accumulator::new_min(&arg_type, nan_policy)),
+        (AggregateFunction::Max, Some(_)) => Ok(Accumulator::new_max(&arg_type, nan_policy)),
+    }
+}
+
+// Column references keep their name, and table alias when qualified,
+// so they can still be referenced the same way after the aggregate.
+fn group_key_name(expr: &Expr) -> (String, Vec<String>) {
+    match expr {
+        Expr::Identifier(ident) => (ident.value.clone(), Vec::new()),
+        Expr::CompoundIdentifier(idents) if idents.len() == 2 => {
+            (idents[1].value.clone(), vec![idents[0].value.clone()])
+        }
+        _ => (expr.to_string(), Vec::new()),
+    }
+}
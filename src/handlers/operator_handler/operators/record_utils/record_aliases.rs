@@ -20,6 +20,60 @@ pub fn get_record_table_aliases(
     let alias = match task {
         planner::OperatorTask::TableFunc { alias, .. } => alias,
         planner::OperatorTask::Table { alias, .. } => alias,
+        planner::OperatorTask::Distinct { .. } => {
+            return Err(
+                GetRecordTableAliasesError::OperatorTaskTypeDoesNotHaveAnAliasField(format!(
+                    "{}",
+                    task
+                ))
+                .into(),
+            );
+        }
+        planner::OperatorTask::Sort { .. } => {
+            return Err(
+                GetRecordTableAliasesError::OperatorTaskTypeDoesNotHaveAnAliasField(format!(
+                    "{}",
+                    task
+                ))
+                .into(),
+            );
+        }
+        planner::OperatorTask::Limit { .. } => {
+            return Err(
+                GetRecordTableAliasesError::OperatorTaskTypeDoesNotHaveAnAliasField(format!(
+                    "{}",
+                    task
+                ))
+                .into(),
+            );
+        }
+        planner::OperatorTask::Union { .. } => {
+            return Err(
+                GetRecordTableAliasesError::OperatorTaskTypeDoesNotHaveAnAliasField(format!(
+                    "{}",
+                    task
+                ))
+                .into(),
+            );
+        }
+        planner::OperatorTask::Aggregate { .. } => {
+            return Err(
+                GetRecordTableAliasesError::OperatorTaskTypeDoesNotHaveAnAliasField(format!(
+                    "{}",
+                    task
+                ))
+                .into(),
+            );
+        }
+        planner::OperatorTask::HashJoin { .. } => {
+            return Err(
+                GetRecordTableAliasesError::OperatorTaskTypeDoesNotHaveAnAliasField(format!(
+                    "{}",
+                    task
+                ))
+                .into(),
+            );
+        }
         planner::OperatorTask::Filter { .. } => {
             return Err(
                 GetRecordTableAliasesError::OperatorTaskTypeDoesNotHaveAnAliasField(format!(
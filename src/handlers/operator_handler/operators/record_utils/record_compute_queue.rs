@@ -0,0 +1,59 @@
+use std::collections::VecDeque;
+
+use anyhow::Result;
+use tokio::task::JoinHandle;
+
+use crate::planner::OperatorCompute;
+
+// Runs cpu heavy record operations, like projections and filters, on
+// tokio's blocking thread pool so the task can keep requesting records
+// while they're computed. The results are returned in the order the
+// operations were pushed so the records are acknowledged in order.
+#[derive(Debug)]
+pub struct RecordComputeQueue<T> {
+    max_in_flight: usize,
+    in_flight: VecDeque<JoinHandle<Result<T>>>,
+}
+
+impl<T: Send + 'static> RecordComputeQueue<T> {
+    pub fn new(max_in_flight: usize) -> RecordComputeQueue<T> {
+        RecordComputeQueue {
+            max_in_flight: std::cmp::max(1, max_in_flight),
+            in_flight: VecDeque::new(),
+        }
+    }
+
+    // Two operations for each cpu given to the operator so one can be
+    // computed while the result of another is sent.
+    pub fn for_compute(compute: &OperatorCompute) -> RecordComputeQueue<T> {
+        Self::new(2 * compute.cpu_in_thousandths.div_ceil(1000))
+    }
+
+    pub fn len(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.in_flight.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.in_flight.len() >= self.max_in_flight
+    }
+
+    pub fn push<F>(&mut self, op: F)
+    where
+        F: FnOnce() -> Result<T> + Send + 'static,
+    {
+        self.in_flight.push_back(tokio::task::spawn_blocking(op));
+    }
+
+    // Waits for the oldest operation; None when there aren't any.
+    pub async fn next(&mut self) -> Option<Result<T>> {
+        let handle = self.in_flight.pop_front()?;
+        match handle.await {
+            Ok(res) => Some(res),
+            Err(err) => Some(Err(err.into())),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use arrow::array::{ArrayRef, BooleanArray, RecordBatch};
+use arrow::row::{RowConverter, SortField};
+use sqlparser::ast::Expr;
+
+use super::compute_value::{compute_value, ComputeValueOptions};
+
+// Removes rows whose keys have already been seen, across all of the
+// records passed to it. The keys of each row are encoded in the arrow row
+// format and kept in a hash set so nulls compare equal to one another.
+// Without any keys every column of the record is used.
+#[derive(Debug)]
+pub struct RecordDistinct {
+    keys: Vec<Expr>,
+    options: ComputeValueOptions,
+
+    converter: Option<RowConverter>,
+    seen_keys: HashSet<Box<[u8]>>,
+    memory_size_in_bytes: usize,
+}
+
+impl RecordDistinct {
+    pub fn new(keys: Vec<Expr>) -> RecordDistinct {
+        RecordDistinct {
+            keys,
+            options: ComputeValueOptions::default(),
+            converter: None,
+            seen_keys: HashSet::new(),
+            memory_size_in_bytes: 0,
+        }
+    }
+
+    pub fn num_keys(&self) -> usize {
+        self.seen_keys.len()
+    }
+
+    // An estimate of the memory held by the seen keys.
+    pub fn memory_size_in_bytes(&self) -> usize {
+        self.memory_size_in_bytes
+    }
+
+    // Returns the rows of the record with keys not seen before, in the
+    // order they appear in the record.
+    pub fn distinct_record(
+        &mut self,
+        rec: &RecordBatch,
+        table_aliases: &[Vec<String>],
+    ) -> Result<RecordBatch> {
+        let key_columns: Vec<ArrayRef> = if self.keys.is_empty() {
+            rec.columns().to_vec()
+        } else {
+            self.keys
+                .iter()
+                .map(|expr| compute_value(rec, table_aliases, expr, &self.options))
+                .collect::<Result<Vec<ArrayRef>, _>>()?
+        };
+
+        if self.converter.is_none() {
+            self.converter = Some(RowConverter::new(
+                key_columns
+                    .iter()
+                    .map(|col| SortField::new(col.data_type().clone()))
+                    .collect(),
+            )?);
+        }
+        let rows = self
+            .converter
+            .as_ref()
+            .unwrap()
+            .convert_columns(&key_columns)?;
+
+        let mut keep: Vec<bool> = Vec::with_capacity(rec.num_rows());
+        for row in rows.iter() {
+            let key: Box<[u8]> = row.as_ref().into();
+            if self.seen_keys.contains(&key) {
+                keep.push(false);
+            } else {
+                self.memory_size_in_bytes += 2 * key.len();
+                self.seen_keys.insert(key);
+                keep.push(true);
+            }
+        }
+
+        Ok(arrow::compute::filter_record_batch(
+            rec,
+            &BooleanArray::from(keep),
+        )?)
+    }
+}
@@ -1,27 +1,43 @@
 use std::sync::Arc;
 
 use anyhow::Result;
-use arrow::array::{ArrayRef, RecordBatch};
+use arrow::array::{Array, AsArray, BooleanArray, RecordBatch};
+use arrow::datatypes::DataType;
 use thiserror::Error;
 
+use super::compute_value::{compute_value, ComputeValueOptions};
+
 #[derive(Debug, Error)]
 pub enum FilterRecordError {
-    #[error("not implemented")]
-    NotImplemented,
+    #[error("filter expression must be a boolean but received data type {0}")]
+    FilterExpressionMustBeABoolean(DataType),
 }
 
+// Keeps the rows where the expression is true. Following SQL's three
+// valued logic, rows where the expression is false are removed and rows
+// where the expression is null are only kept when keep_null is set.
 pub fn filter_record(
     rec: Arc<RecordBatch>,
     expr: &sqlparser::ast::Expr,
     table_aliases: &Vec<Vec<String>>,
+    options: &ComputeValueOptions,
+    keep_null: bool,
 ) -> Result<RecordBatch> {
-    Err(FilterRecordError::NotImplemented.into())
-}
+    let mask = compute_value(&rec, table_aliases, expr, options)?;
+    let mask = match mask.as_boolean_opt() {
+        Some(mask) => mask,
+        None => {
+            return Err(
+                FilterRecordError::FilterExpressionMustBeABoolean(mask.data_type().clone()).into(),
+            )
+        }
+    };
 
-fn compute_value(
-    rec: Arc<RecordBatch>,
-    temp_rec: ArrayRef,
-    expr: &sqlparser::ast::Expr,
-) -> Result<ArrayRef> {
-    Err(FilterRecordError::NotImplemented.into())
+    // resolve the null entries so the filter kernel only sees true or false
+    let mask: BooleanArray = mask
+        .iter()
+        .map(|val| Some(val.unwrap_or(keep_null)))
+        .collect();
+
+    Ok(arrow::compute::filter_record_batch(&rec, &mask)?)
 }
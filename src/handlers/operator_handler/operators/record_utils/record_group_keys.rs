@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::ops::Range;
+
+use anyhow::Result;
+use arrow::array::ArrayRef;
+use arrow::datatypes::DataType;
+use arrow::row::{OwnedRow, Row, RowConverter, SortField};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum RecordGroupKeysError {
+    #[error("expected {0} key columns but received {1}")]
+    UnexpectedNumberOfKeyColumns(usize, usize),
+    #[error("key column data type {0} does not match expected data type {1}")]
+    KeyColumnDataTypeMismatch(DataType, DataType),
+    #[error("number of partitions must be greater than zero")]
+    NumberOfPartitionsMustBeGreaterThanZero,
+}
+
+// Builds composite group keys out of one or more key columns. Each
+// row of the key columns is encoded into a single comparable byte
+// row so grouping by `a, b` uses the values of both columns. Null
+// key values encode identically so all nulls land in the same group.
+#[derive(Debug)]
+pub struct RecordGroupKeys {
+    key_types: Vec<DataType>,
+    converter: RowConverter,
+}
+
+impl RecordGroupKeys {
+    pub fn new(key_types: Vec<DataType>) -> Result<RecordGroupKeys> {
+        let converter = RowConverter::new(
+            key_types
+                .iter()
+                .map(|item| SortField::new(item.clone()))
+                .collect(),
+        )?;
+        Ok(RecordGroupKeys {
+            key_types,
+            converter,
+        })
+    }
+
+    // Returns each distinct composite key along with the indices of the
+    // rows belonging to it. Groups are returned in order of first appearance.
+    pub fn group_rows(&self, key_columns: &[ArrayRef]) -> Result<Vec<(OwnedRow, Vec<u32>)>> {
+        self.check_key_columns(key_columns)?;
+
+        let rows = self.converter.convert_columns(key_columns)?;
+
+        let mut group_idxs: HashMap<Row<'_>, usize> = HashMap::new();
+        let mut groups: Vec<(Row<'_>, Vec<u32>)> = Vec::new();
+        for (row_idx, row) in rows.iter().enumerate() {
+            match group_idxs.get(&row) {
+                Some(group_idx) => {
+                    groups[*group_idx].1.push(row_idx as u32);
+                }
+                None => {
+                    group_idxs.insert(row, groups.len());
+                    groups.push((row, vec![row_idx as u32]));
+                }
+            }
+        }
+
+        Ok(groups
+            .into_iter()
+            .map(|(row, idxs)| (row.owned(), idxs))
+            .collect())
+    }
+
+    // Returns the contiguous runs of rows sharing a composite key. When the
+    // key columns are sorted each run is a complete group within the
+    // record, so an aggregate can finish a group as soon as the key changes
+    // instead of holding every group in a hash map. The last run may continue
+    // into the next record which the caller detects by comparing keys.
+    pub fn group_sorted_rows(
+        &self,
+        key_columns: &[ArrayRef],
+    ) -> Result<Vec<(OwnedRow, Range<usize>)>> {
+        self.check_key_columns(key_columns)?;
+
+        let rows = self.converter.convert_columns(key_columns)?;
+
+        let mut runs: Vec<(OwnedRow, Range<usize>)> = Vec::new();
+        let mut run_start = 0;
+        for row_idx in 1..=rows.num_rows() {
+            if row_idx == rows.num_rows() || rows.row(row_idx) != rows.row(run_start) {
+                runs.push((rows.row(run_start).owned(), run_start..row_idx));
+                run_start = row_idx;
+            }
+        }
+
+        Ok(runs)
+    }
+
+    // Converts the composite keys back into one array per key column.
+    pub fn keys_to_columns<'a>(
+        &self,
+        keys: impl IntoIterator<Item = Row<'a>>,
+    ) -> Result<Vec<ArrayRef>> {
+        Ok(self.converter.convert_rows(keys)?)
+    }
+
+    fn check_key_columns(&self, key_columns: &[ArrayRef]) -> Result<()> {
+        if key_columns.len() != self.key_types.len() {
+            return Err(RecordGroupKeysError::UnexpectedNumberOfKeyColumns(
+                self.key_types.len(),
+                key_columns.len(),
+            )
+            .into());
+        }
+        for (col, key_type) in key_columns.iter().zip(self.key_types.iter()) {
+            if col.data_type() != key_type {
+                return Err(RecordGroupKeysError::KeyColumnDataTypeMismatch(
+                    col.data_type().clone(),
+                    key_type.clone(),
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+}
+
+// Maps a composite key onto a partition. The hash only depends on the
+// encoded key bytes so the same key always converges on the same
+// partition regardless of which record or worker produced it.
+pub fn group_key_partition(key: Row<'_>, num_partitions: usize) -> Result<usize> {
+    if num_partitions == 0 {
+        return Err(RecordGroupKeysError::NumberOfPartitionsMustBeGreaterThanZero.into());
+    }
+
+    let mut hasher = DefaultHasher::new();
+    key.as_ref().hash(&mut hasher);
+    Ok((hasher.finish() % num_partitions as u64) as usize)
+}
@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::{Array, ArrayRef, RecordBatch, UInt32Array};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::row::{RowConverter, SortField};
+use sqlparser::ast::Expr;
+use thiserror::Error;
+
+use crate::planner::JoinType;
+
+use super::compute_value::{compute_value, ComputeValueOptions};
+
+#[derive(Debug, Error)]
+pub enum RecordJoinError {
+    #[error("expected {0} join keys but received {1}")]
+    UnexpectedNumberOfJoinKeys(usize, usize),
+    #[error("left join key data type {0} does not match right join key data type {1}")]
+    JoinKeyDataTypeMismatch(DataType, DataType),
+}
+
+// The build (right) side of a hash join. Every build record is
+// concatenated into a single record and the rows are indexed by their
+// encoded join key. Rows with a null in any key column are never indexed
+// since null never equals another value in a join condition.
+#[derive(Debug)]
+pub struct JoinBuildSide {
+    record: Option<RecordBatch>,
+    table_aliases: Vec<Vec<String>>,
+    key_types: Vec<DataType>,
+    converter: Option<RowConverter>,
+    index: HashMap<Box<[u8]>, Vec<u32>>,
+}
+
+impl JoinBuildSide {
+    pub fn try_new(
+        records: &[RecordBatch],
+        table_aliases: Vec<Vec<String>>,
+        right_keys: &[Expr],
+    ) -> Result<JoinBuildSide> {
+        let mut build_side = JoinBuildSide {
+            record: None,
+            table_aliases,
+            key_types: Vec::new(),
+            converter: None,
+            index: HashMap::new(),
+        };
+
+        let record = match records.first() {
+            Some(first) => arrow::compute::concat_batches(&first.schema(), records)?,
+            None => return Ok(build_side),
+        };
+
+        let key_columns = compute_join_keys(&record, &build_side.table_aliases, right_keys)?;
+        build_side.key_types = key_columns
+            .iter()
+            .map(|col| col.data_type().clone())
+            .collect();
+
+        let converter = RowConverter::new(
+            build_side
+                .key_types
+                .iter()
+                .map(|item| SortField::new(item.clone()))
+                .collect(),
+        )?;
+        let rows = converter.convert_columns(&key_columns)?;
+        let null_rows = null_key_rows(&key_columns, record.num_rows());
+        for (row_idx, row) in rows.iter().enumerate() {
+            if null_rows[row_idx] {
+                continue;
+            }
+            build_side
+                .index
+                .entry(row.as_ref().into())
+                .or_default()
+                .push(row_idx as u32);
+        }
+
+        build_side.record = Some(record);
+        build_side.converter = Some(converter);
+        Ok(build_side)
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.record.as_ref().map_or(0, |rec| rec.num_rows())
+    }
+
+    // Joins a probe (left) record against the build side. The output
+    // contains the left columns followed by the right columns along with
+    // the table aliases for each column. For a left join, probe rows
+    // without a match are kept with nulls in the right columns.
+    //
+    // When the build side received no records at all its schema is unknown,
+    // so a left join returns the probe columns by themselves.
+    pub fn join_record(
+        &self,
+        probe: &RecordBatch,
+        probe_aliases: &[Vec<String>],
+        left_keys: &[Expr],
+        join_type: &JoinType,
+    ) -> Result<(RecordBatch, Vec<Vec<String>>)> {
+        let (build_record, converter) = match (&self.record, &self.converter) {
+            (Some(build_record), Some(converter)) => (build_record, converter),
+            _ => {
+                return match join_type {
+                    JoinType::Inner => Ok((
+                        RecordBatch::new_empty(probe.schema()),
+                        probe_aliases.to_vec(),
+                    )),
+                    JoinType::Left => Ok((probe.clone(), probe_aliases.to_vec())),
+                };
+            }
+        };
+
+        if left_keys.len() != self.key_types.len() {
+            return Err(RecordJoinError::UnexpectedNumberOfJoinKeys(
+                self.key_types.len(),
+                left_keys.len(),
+            )
+            .into());
+        }
+        let key_columns = compute_join_keys(probe, probe_aliases, left_keys)?;
+        for (col, key_type) in key_columns.iter().zip(self.key_types.iter()) {
+            if col.data_type() != key_type {
+                return Err(RecordJoinError::JoinKeyDataTypeMismatch(
+                    col.data_type().clone(),
+                    key_type.clone(),
+                )
+                .into());
+            }
+        }
+
+        let rows = converter.convert_columns(&key_columns)?;
+        let null_rows = null_key_rows(&key_columns, probe.num_rows());
+        let mut left_idxs: Vec<u32> = Vec::new();
+        let mut right_idxs: Vec<Option<u32>> = Vec::new();
+        for (row_idx, row) in rows.iter().enumerate() {
+            let matches = if null_rows[row_idx] {
+                None
+            } else {
+                self.index.get(row.as_ref())
+            };
+            match (matches, join_type) {
+                (Some(matches), _) => {
+                    for build_idx in matches {
+                        left_idxs.push(row_idx as u32);
+                        right_idxs.push(Some(*build_idx));
+                    }
+                }
+                (None, JoinType::Left) => {
+                    left_idxs.push(row_idx as u32);
+                    right_idxs.push(None);
+                }
+                (None, JoinType::Inner) => {}
+            }
+        }
+
+        let left_idxs = UInt32Array::from(left_idxs);
+        let right_idxs = UInt32Array::from(right_idxs);
+
+        let mut fields: Vec<Field> = Vec::new();
+        let mut columns: Vec<ArrayRef> = Vec::new();
+        for (field, col) in probe.schema().fields().iter().zip(probe.columns()) {
+            fields.push((**field).clone());
+            columns.push(arrow::compute::take(col, &left_idxs, None)?);
+        }
+        for (field, col) in build_record
+            .schema()
+            .fields()
+            .iter()
+            .zip(build_record.columns())
+        {
+            let nullable = field.is_nullable() || *join_type == JoinType::Left;
+            fields.push((**field).clone().with_nullable(nullable));
+            columns.push(arrow::compute::take(col, &right_idxs, None)?);
+        }
+
+        let record = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)?;
+        let mut table_aliases = probe_aliases.to_vec();
+        table_aliases.extend(self.table_aliases.iter().cloned());
+
+        Ok((record, table_aliases))
+    }
+}
+
+// Keys are normalized so an int32 column can be joined to an int64
+// column and a utf8 column to a large utf8 column.
+fn compute_join_keys(
+    rec: &RecordBatch,
+    table_aliases: &[Vec<String>],
+    keys: &[Expr],
+) -> Result<Vec<ArrayRef>> {
+    let options = ComputeValueOptions::default();
+    let mut key_columns: Vec<ArrayRef> = Vec::new();
+    for key in keys {
+        let col = compute_value(rec, table_aliases, key, &options)?;
+        let normalized_type = match col.data_type() {
+            data_type if data_type.is_integer() => DataType::Int64,
+            data_type if data_type.is_floating() => DataType::Float64,
+            DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View => DataType::Utf8,
+            data_type => data_type.clone(),
+        };
+        key_columns.push(arrow::compute::cast(&col, &normalized_type)?);
+    }
+    Ok(key_columns)
+}
+
+fn null_key_rows(key_columns: &[ArrayRef], num_rows: usize) -> Vec<bool> {
+    let mut null_rows = vec![false; num_rows];
+    for col in key_columns {
+        if let Some(nulls) = col.logical_nulls() {
+            for (row_idx, is_null) in null_rows.iter_mut().enumerate() {
+                *is_null |= nulls.is_null(row_idx);
+            }
+        }
+    }
+    null_rows
+}
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use arrow::array::{RecordBatch, UInt32Array};
+use arrow::row::{RowConverter, SortField};
+use arrow::util::display::{ArrayFormatter, FormatOptions};
+use thiserror::Error;
+
+use super::record_group_keys::group_key_partition;
+
+#[derive(Debug, Error)]
+pub enum RecordPartitionError {
+    #[error("partition column not found: {0}")]
+    PartitionColumnNotFound(String),
+}
+
+// Splits the record into one record for each distinct combination of the
+// partition column values. The values are formatted as strings and null
+// values are None. Partitions are returned in the order their first row
+// appears in the record.
+pub fn partition_record(
+    rec: &RecordBatch,
+    columns: &[String],
+) -> Result<Vec<(Vec<Option<String>>, RecordBatch)>> {
+    let arrays = columns
+        .iter()
+        .map(|column| match rec.column_by_name(column) {
+            Some(array) => Ok(array.clone()),
+            None => Err(RecordPartitionError::PartitionColumnNotFound(
+                column.clone(),
+            )),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let options = FormatOptions::default();
+    let formatters = arrays
+        .iter()
+        .map(|array| ArrayFormatter::try_new(array.as_ref(), &options))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut partitions: Vec<(Vec<Option<String>>, Vec<u32>)> = Vec::new();
+    let mut partition_idxs: HashMap<Vec<Option<String>>, usize> = HashMap::new();
+    for row_idx in 0..rec.num_rows() {
+        let values: Vec<Option<String>> = arrays
+            .iter()
+            .zip(formatters.iter())
+            .map(|(array, formatter)| {
+                if array.is_null(row_idx) {
+                    None
+                } else {
+                    Some(formatter.value(row_idx).to_string())
+                }
+            })
+            .collect();
+
+        match partition_idxs.get(&values) {
+            Some(partition_idx) => partitions[*partition_idx].1.push(row_idx as u32),
+            None => {
+                partition_idxs.insert(values.clone(), partitions.len());
+                partitions.push((values, vec![row_idx as u32]));
+            }
+        }
+    }
+
+    let mut partitioned_recs = Vec::new();
+    for (values, row_idxs) in partitions {
+        let indices = UInt32Array::from(row_idxs);
+        let partition_columns = rec
+            .columns()
+            .iter()
+            .map(|array| arrow::compute::take(array.as_ref(), &indices, None))
+            .collect::<Result<Vec<_>, _>>()?;
+        partitioned_recs.push((
+            values,
+            RecordBatch::try_new(rec.schema(), partition_columns)?,
+        ));
+    }
+    Ok(partitioned_recs)
+}
+
+// Splits the record into num_partitions records by hashing the key
+// columns of each row. The record at index i holds the rows of partition
+// i and is empty when none of the rows hash to it, so rows with the same
+// key always land in the same partition.
+pub fn hash_partition_record(
+    rec: &RecordBatch,
+    keys: &[String],
+    num_partitions: usize,
+) -> Result<Vec<RecordBatch>> {
+    let key_columns = keys
+        .iter()
+        .map(|key| match rec.column_by_name(key) {
+            Some(array) => Ok(array.clone()),
+            None => Err(RecordPartitionError::PartitionColumnNotFound(key.clone())),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let converter = RowConverter::new(
+        key_columns
+            .iter()
+            .map(|array| SortField::new(array.data_type().clone()))
+            .collect(),
+    )?;
+    let rows = converter.convert_columns(&key_columns)?;
+
+    let mut partition_row_idxs: Vec<Vec<u32>> = vec![Vec::new(); num_partitions];
+    for (row_idx, row) in rows.iter().enumerate() {
+        partition_row_idxs[group_key_partition(row, num_partitions)?].push(row_idx as u32);
+    }
+
+    partition_row_idxs
+        .into_iter()
+        .map(|row_idxs| {
+            Ok(arrow::compute::take_record_batch(
+                rec,
+                &UInt32Array::from(row_idxs),
+            )?)
+        })
+        .collect()
+}
@@ -1,10 +1,12 @@
 use anyhow::Result;
 use arrow::array::{Array, RecordBatch};
 use arrow::datatypes::{Field, Schema};
-use sqlparser::ast::SelectItem;
+use sqlparser::ast::{Expr, SelectItem};
 use std::sync::Arc;
 use thiserror::Error;
 
+use super::compute_value::{compute_value, ComputeValueOptions};
+
 #[derive(Debug, Error)]
 pub enum ProjectRecordError {
     #[error("not implemented: {0}")]
@@ -18,6 +20,7 @@ pub fn project_record(
 ) -> Result<RecordBatch> {
     let mut proj_fields: Vec<Field> = Vec::new();
     let mut proj_arrays: Vec<Arc<dyn Array>> = Vec::new();
+    let options = ComputeValueOptions::default();
 
     for field in fields {
         match field {
@@ -33,17 +36,27 @@ pub fn project_record(
                 )
                 .into());
             }
-            SelectItem::UnnamedExpr(_) => {
-                return Err(ProjectRecordError::NotImplemented(
-                    "SelectItem::UnnamedExpr".to_string(),
-                )
-                .into());
+            SelectItem::UnnamedExpr(expr) => {
+                let arr = compute_value(&record, table_aliases, expr, &options)?;
+                // a column keeps its name whether or not it's qualified
+                let name = match expr {
+                    Expr::Identifier(ident) => ident.value.clone(),
+                    Expr::CompoundIdentifier(idents) if !idents.is_empty() => {
+                        idents[idents.len() - 1].value.clone()
+                    }
+                    _ => expr.to_string(),
+                };
+                proj_fields.push(Field::new(name, arr.data_type().clone(), true));
+                proj_arrays.push(arr);
             }
-            SelectItem::ExprWithAlias { .. } => {
-                return Err(ProjectRecordError::NotImplemented(
-                    "SelectItem::ExprWithAlias".to_string(),
-                )
-                .into());
+            SelectItem::ExprWithAlias { expr, alias } => {
+                let arr = compute_value(&record, table_aliases, expr, &options)?;
+                proj_fields.push(Field::new(
+                    alias.value.clone(),
+                    arr.data_type().clone(),
+                    true,
+                ));
+                proj_arrays.push(arr);
             }
         }
     }
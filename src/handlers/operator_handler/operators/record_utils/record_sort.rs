@@ -0,0 +1,242 @@
+use anyhow::Result;
+use arrow::array::{Array, ArrayRef, RecordBatch, UInt32Array};
+use arrow::compute::SortOptions;
+use arrow::row::{RowConverter, Rows, SortField};
+use thiserror::Error;
+
+use crate::planner::SortExpr;
+
+use super::compute_value::{compute_value, ComputeValueOptions, NanPolicy};
+
+#[derive(Debug, Error)]
+pub enum RecordSortError {
+    #[error("run {0} does not exist")]
+    RunDoesNotExist(usize),
+    #[error("run {0} needs another record before merging can continue")]
+    RunNeedsAnotherRecord(usize),
+}
+
+// Sorts records by the sort expressions. Rows are compared using the
+// arrow row format with the direction and nulls ordering of each sort
+// expression, so a record sorted here and the runs merged by a
+// SortedRunMerger order rows the same way. The sort is stable.
+#[derive(Debug)]
+pub struct RecordSorter {
+    exprs: Vec<SortExpr>,
+    options: ComputeValueOptions,
+    converter: Option<RowConverter>,
+}
+
+impl RecordSorter {
+    pub fn new(exprs: Vec<SortExpr>) -> RecordSorter {
+        RecordSorter {
+            exprs,
+            options: ComputeValueOptions::default(),
+            converter: None,
+        }
+    }
+
+    pub fn set_nan_policy(&mut self, nan_policy: NanPolicy) -> &Self {
+        self.options.nan_policy = nan_policy;
+        self
+    }
+
+    pub fn sort_record(
+        &mut self,
+        rec: &RecordBatch,
+        table_aliases: &[Vec<String>],
+    ) -> Result<RecordBatch> {
+        let rows = self.sort_rows(rec, table_aliases)?;
+
+        let mut idxs: Vec<u32> = (0..rec.num_rows() as u32).collect();
+        idxs.sort_by(|a, b| rows.row(*a as usize).cmp(&rows.row(*b as usize)));
+        let idxs = UInt32Array::from(idxs);
+
+        let columns = rec
+            .columns()
+            .iter()
+            .map(|col| arrow::compute::take(col, &idxs, None))
+            .collect::<Result<Vec<ArrayRef>, _>>()?;
+        Ok(RecordBatch::try_new(rec.schema(), columns)?)
+    }
+
+    fn sort_rows(&mut self, rec: &RecordBatch, table_aliases: &[Vec<String>]) -> Result<Rows> {
+        let mut key_columns: Vec<ArrayRef> = Vec::new();
+        for sort_expr in &self.exprs {
+            key_columns.push(compute_value(
+                rec,
+                table_aliases,
+                &sort_expr.expr,
+                &self.options,
+            )?);
+        }
+
+        if self.converter.is_none() {
+            let fields = self
+                .exprs
+                .iter()
+                .zip(key_columns.iter())
+                .map(|(sort_expr, col)| {
+                    SortField::new_with_options(
+                        col.data_type().clone(),
+                        SortOptions {
+                            descending: !sort_expr.asc,
+                            nulls_first: sort_expr.nulls_first,
+                        },
+                    )
+                })
+                .collect();
+            self.converter = Some(RowConverter::new(fields)?);
+        }
+
+        Ok(self
+            .converter
+            .as_ref()
+            .unwrap()
+            .convert_columns(&key_columns)?)
+    }
+}
+
+#[derive(Debug)]
+struct RunCursor {
+    record: RecordBatch,
+    rows: Rows,
+    pos: usize,
+}
+
+impl RunCursor {
+    fn is_exhausted(&self) -> bool {
+        self.pos >= self.record.num_rows()
+    }
+}
+
+// A k-way merge of sorted runs. Each run is read one record at a time;
+// the caller pushes the next record of a run whenever next_run_to_fill
+// returns it and marks the run finished once it has no records left.
+// When rows compare equal the row from the lower run comes first so the
+// merge is stable as long as the runs are numbered in input order.
+#[derive(Debug)]
+pub struct SortedRunMerger {
+    sorter: RecordSorter,
+    table_aliases: Vec<Vec<String>>,
+    cursors: Vec<Option<RunCursor>>,
+    finished: Vec<bool>,
+}
+
+impl SortedRunMerger {
+    pub fn new(
+        exprs: Vec<SortExpr>,
+        table_aliases: Vec<Vec<String>>,
+        num_runs: usize,
+    ) -> SortedRunMerger {
+        SortedRunMerger {
+            sorter: RecordSorter::new(exprs),
+            table_aliases,
+            cursors: (0..num_runs).map(|_| None).collect(),
+            finished: vec![false; num_runs],
+        }
+    }
+
+    pub fn push_record(&mut self, run_idx: usize, record: RecordBatch) -> Result<()> {
+        if run_idx >= self.cursors.len() {
+            return Err(RecordSortError::RunDoesNotExist(run_idx).into());
+        }
+        let rows = self.sorter.sort_rows(&record, &self.table_aliases)?;
+        self.cursors[run_idx] = Some(RunCursor {
+            record,
+            rows,
+            pos: 0,
+        });
+        Ok(())
+    }
+
+    pub fn finish_run(&mut self, run_idx: usize) -> Result<()> {
+        if run_idx >= self.finished.len() {
+            return Err(RecordSortError::RunDoesNotExist(run_idx).into());
+        }
+        self.finished[run_idx] = true;
+        Ok(())
+    }
+
+    // Returns a run which has no rows left to merge but may still
+    // have more records.
+    pub fn next_run_to_fill(&self) -> Option<usize> {
+        (0..self.cursors.len()).find(|run_idx| {
+            !self.finished[*run_idx]
+                && self.cursors[*run_idx]
+                    .as_ref()
+                    .is_none_or(|cursor| cursor.is_exhausted())
+        })
+    }
+
+    // Merges up to max_rows rows. The merge stops early when a run runs
+    // out of rows since its next record could contain smaller rows.
+    // Returns None once every run is finished and fully merged.
+    pub fn next_record(&mut self, max_rows: usize) -> Result<Option<RecordBatch>> {
+        if let Some(run_idx) = self.next_run_to_fill() {
+            return Err(RecordSortError::RunNeedsAnotherRecord(run_idx).into());
+        }
+
+        let mut idxs: Vec<(usize, usize)> = Vec::new();
+        while idxs.len() < max_rows {
+            let mut min_run: Option<usize> = None;
+            for (run_idx, cursor) in self.cursors.iter().enumerate() {
+                let cursor = match cursor {
+                    Some(cursor) if !cursor.is_exhausted() => cursor,
+                    _ => continue,
+                };
+                min_run = match min_run {
+                    Some(min_idx) => {
+                        let min_cursor = self.cursors[min_idx].as_ref().unwrap();
+                        if cursor.rows.row(cursor.pos) < min_cursor.rows.row(min_cursor.pos) {
+                            Some(run_idx)
+                        } else {
+                            Some(min_idx)
+                        }
+                    }
+                    None => Some(run_idx),
+                };
+            }
+
+            let run_idx = match min_run {
+                Some(run_idx) => run_idx,
+                None => break,
+            };
+            let cursor = self.cursors[run_idx].as_mut().unwrap();
+            idxs.push((run_idx, cursor.pos));
+            cursor.pos += 1;
+            if cursor.is_exhausted() && !self.finished[run_idx] {
+                break;
+            }
+        }
+
+        if idxs.is_empty() {
+            return Ok(None);
+        }
+
+        // runs without a record are never referenced by the indices
+        // so any record can stand in for them
+        let stand_in = self
+            .cursors
+            .iter()
+            .flatten()
+            .next()
+            .map(|cursor| cursor.record.clone())
+            .unwrap();
+        let records: Vec<&RecordBatch> = self
+            .cursors
+            .iter()
+            .map(|cursor| cursor.as_ref().map_or(&stand_in, |cursor| &cursor.record))
+            .collect();
+
+        let mut columns: Vec<ArrayRef> = Vec::new();
+        for col_idx in 0..stand_in.num_columns() {
+            let arrays: Vec<&dyn Array> = records
+                .iter()
+                .map(|record| record.column(col_idx).as_ref())
+                .collect();
+            columns.push(arrow::compute::interleave(&arrays, &idxs)?);
+        }
+        Ok(Some(RecordBatch::try_new(stand_in.schema(), columns)?))
+    }
+}
@@ -0,0 +1,71 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::{ArrayRef, RecordBatch};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum RecordUnionError {
+    #[error("expected {0} columns but the record has {1}")]
+    ColumnCountMismatch(usize, usize),
+    #[error("union column {0} of type {1} can't be cast to {2}")]
+    ColumnTypeMismatch(usize, DataType, DataType),
+}
+
+// Aligns the records of both sides of a union to a single schema. The
+// first record sets the column names and types and every later record is
+// cast to them, so the left side's records need to be aligned first.
+// Columns are always nullable since either side may contain nulls.
+#[derive(Debug)]
+pub struct RecordUnion {
+    schema: Option<SchemaRef>,
+}
+
+impl RecordUnion {
+    pub fn new() -> RecordUnion {
+        RecordUnion { schema: None }
+    }
+
+    pub fn align_record(&mut self, rec: &RecordBatch) -> Result<RecordBatch> {
+        let schema = match &self.schema {
+            Some(schema) => schema.clone(),
+            None => {
+                let fields: Vec<Field> = rec
+                    .schema()
+                    .fields()
+                    .iter()
+                    .map(|field| Field::new(field.name(), field.data_type().clone(), true))
+                    .collect();
+                let schema = Arc::new(Schema::new(fields));
+                self.schema = Some(schema.clone());
+                schema
+            }
+        };
+
+        if rec.num_columns() != schema.fields().len() {
+            return Err(RecordUnionError::ColumnCountMismatch(
+                schema.fields().len(),
+                rec.num_columns(),
+            )
+            .into());
+        }
+
+        let mut columns: Vec<ArrayRef> = Vec::new();
+        for (idx, (col, field)) in rec.columns().iter().zip(schema.fields()).enumerate() {
+            if col.data_type() == field.data_type() {
+                columns.push(col.clone());
+            } else if arrow::compute::can_cast_types(col.data_type(), field.data_type()) {
+                columns.push(arrow::compute::cast(col, field.data_type())?);
+            } else {
+                return Err(RecordUnionError::ColumnTypeMismatch(
+                    idx,
+                    col.data_type().clone(),
+                    field.data_type().clone(),
+                )
+                .into());
+            }
+        }
+        Ok(RecordBatch::try_new(schema, columns)?)
+    }
+}
@@ -0,0 +1,263 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::{Array, ArrayRef, AsArray, Int32Array, StringArray, StringBuilder};
+use arrow::datatypes::{DataType, Int64Type};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ScalarFunctionError {
+    #[error("function {0} expects {1} argument(s) but received {2}")]
+    WrongNumberOfArguments(String, usize, usize),
+    #[error("function {0} expects a string argument but received data type {1}")]
+    ExpectedStringArgument(String, DataType),
+    #[error("function {0} expects an integer argument but received data type {1}")]
+    ExpectedIntegerArgument(String, DataType),
+    #[error("function {0} expects at least {1} argument(s) but received {2}")]
+    NotEnoughArguments(String, usize, usize),
+    #[error("negative substring length not allowed: {0}")]
+    NegativeSubstringLength(i64),
+}
+
+pub type BuiltinFunction = fn(&[ArrayRef]) -> Result<ArrayRef>;
+
+// Built in scalar functions. Every function returns null for a null
+// input value.
+pub fn find_builtin_function(name: &str) -> Option<BuiltinFunction> {
+    match name {
+        "upper" => Some(upper),
+        "lower" => Some(lower),
+        "length" | "char_length" | "character_length" => Some(length),
+        "ltrim" => Some(ltrim),
+        "rtrim" => Some(rtrim),
+        "btrim" => Some(btrim),
+        "concat" => Some(concat),
+        _ => None,
+    }
+}
+
+fn upper(args: &[ArrayRef]) -> Result<ArrayRef> {
+    let value = string_arg("upper", args, 0, 1)?;
+    let res: StringArray = value
+        .as_string::<i32>()
+        .iter()
+        .map(|val| val.map(|val| val.to_uppercase()))
+        .collect();
+    Ok(Arc::new(res))
+}
+
+fn lower(args: &[ArrayRef]) -> Result<ArrayRef> {
+    let value = string_arg("lower", args, 0, 1)?;
+    let res: StringArray = value
+        .as_string::<i32>()
+        .iter()
+        .map(|val| val.map(|val| val.to_lowercase()))
+        .collect();
+    Ok(Arc::new(res))
+}
+
+// The number of characters, not bytes.
+fn length(args: &[ArrayRef]) -> Result<ArrayRef> {
+    let value = string_arg("length", args, 0, 1)?;
+    let res: Int32Array = value
+        .as_string::<i32>()
+        .iter()
+        .map(|val| val.map(|val| val.chars().count() as i32))
+        .collect();
+    Ok(Arc::new(res))
+}
+
+fn ltrim(args: &[ArrayRef]) -> Result<ArrayRef> {
+    trim_args("ltrim", args, true, false)
+}
+
+fn rtrim(args: &[ArrayRef]) -> Result<ArrayRef> {
+    trim_args("rtrim", args, false, true)
+}
+
+fn btrim(args: &[ArrayRef]) -> Result<ArrayRef> {
+    trim_args("btrim", args, true, true)
+}
+
+fn trim_args(name: &str, args: &[ArrayRef], leading: bool, trailing: bool) -> Result<ArrayRef> {
+    if args.len() == 2 {
+        let value = string_arg(name, args, 0, 2)?;
+        let characters = string_arg(name, args, 1, 2)?;
+        trim(&value, Some(&characters), leading, trailing)
+    } else {
+        let value = string_arg(name, args, 0, 1)?;
+        trim(&value, None, leading, trailing)
+    }
+}
+
+// Removes any of the characters from the start and/or end of each value.
+// Spaces are removed when no characters are given.
+pub fn trim(
+    value: &ArrayRef,
+    characters: Option<&ArrayRef>,
+    leading: bool,
+    trailing: bool,
+) -> Result<ArrayRef> {
+    let value = cast_string("trim", value)?;
+    let characters = match characters {
+        Some(characters) => cast_string("trim", characters)?,
+        None => Arc::new(StringArray::from_iter_values(std::iter::repeat_n(
+            " ",
+            value.len(),
+        ))),
+    };
+
+    let res: StringArray = value
+        .as_string::<i32>()
+        .iter()
+        .zip(characters.as_string::<i32>().iter())
+        .map(|(val, chars)| match (val, chars) {
+            (Some(val), Some(chars)) => {
+                let is_trimmed = |c: char| chars.contains(c);
+                let val = if leading {
+                    val.trim_start_matches(is_trimmed)
+                } else {
+                    val
+                };
+                let val = if trailing {
+                    val.trim_end_matches(is_trimmed)
+                } else {
+                    val
+                };
+                Some(val.to_string())
+            }
+            _ => None,
+        })
+        .collect();
+    Ok(Arc::new(res))
+}
+
+// Null arguments are treated as empty strings, the same as Postgres, so
+// concat('a', null, 'b') is 'ab'. Use || to get null instead.
+fn concat(args: &[ArrayRef]) -> Result<ArrayRef> {
+    if args.is_empty() {
+        return Err(ScalarFunctionError::NotEnoughArguments("concat".to_string(), 1, 0).into());
+    }
+
+    let args = args
+        .iter()
+        .map(|arg| arrow::compute::cast(arg, &DataType::Utf8))
+        .collect::<Result<Vec<ArrayRef>, _>>()?;
+
+    let num_rows = args[0].len();
+    let mut res = StringBuilder::new();
+    for row_idx in 0..num_rows {
+        let mut val = String::new();
+        for arg in &args {
+            let arg = arg.as_string::<i32>();
+            if arg.is_valid(row_idx) {
+                val.push_str(arg.value(row_idx));
+            }
+        }
+        res.append_value(val);
+    }
+    Ok(Arc::new(res.finish()))
+}
+
+// SQL substring with 1-based character positions. The start may be
+// before the first character or past the last one; only the characters
+// inside the requested range are returned, which can be an empty string.
+// For example substring('hello' from 0 for 3) is 'he'. A negative length
+// is an error and a null argument produces null.
+pub fn substring(
+    value: &ArrayRef,
+    from: Option<&ArrayRef>,
+    length: Option<&ArrayRef>,
+) -> Result<ArrayRef> {
+    let value = cast_string("substring", value)?;
+    let from = match from {
+        Some(from) => Some(cast_integer("substring", from)?),
+        None => None,
+    };
+    let length = match length {
+        Some(length) => Some(cast_integer("substring", length)?),
+        None => None,
+    };
+    let from = from.as_ref().map(|from| from.as_primitive::<Int64Type>());
+    let length = length
+        .as_ref()
+        .map(|length| length.as_primitive::<Int64Type>());
+
+    let value = value.as_string::<i32>();
+    let mut res = StringBuilder::new();
+    for row_idx in 0..value.len() {
+        let start = match from {
+            Some(from) if from.is_null(row_idx) => None,
+            Some(from) => Some(from.value(row_idx)),
+            None => Some(1),
+        };
+        let len = match length {
+            Some(length) if length.is_null(row_idx) => None,
+            Some(length) => Some(Some(length.value(row_idx))),
+            None => Some(None),
+        };
+
+        match (value.is_valid(row_idx), start, len) {
+            (true, Some(start), Some(len)) => {
+                if let Some(len) = len {
+                    if len < 0 {
+                        return Err(ScalarFunctionError::NegativeSubstringLength(len).into());
+                    }
+                }
+                // positions are 1-based and the end is exclusive
+                let end = len.map(|len| start.saturating_add(len));
+                let skip = start.max(1) - 1;
+                let take = match end {
+                    Some(end) => end.saturating_sub(1).saturating_sub(skip).max(0),
+                    None => i64::MAX,
+                };
+                let val: String = value
+                    .value(row_idx)
+                    .chars()
+                    .skip(skip as usize)
+                    .take(take as usize)
+                    .collect();
+                res.append_value(val);
+            }
+            _ => res.append_null(),
+        }
+    }
+    Ok(Arc::new(res.finish()))
+}
+
+fn string_arg(name: &str, args: &[ArrayRef], idx: usize, num_args: usize) -> Result<ArrayRef> {
+    if args.len() != num_args {
+        return Err(ScalarFunctionError::WrongNumberOfArguments(
+            name.to_string(),
+            num_args,
+            args.len(),
+        )
+        .into());
+    }
+    cast_string(name, &args[idx])
+}
+
+fn cast_string(name: &str, value: &ArrayRef) -> Result<ArrayRef> {
+    match value.data_type() {
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View | DataType::Null => {
+            Ok(arrow::compute::cast(value, &DataType::Utf8)?)
+        }
+        data_type => Err(ScalarFunctionError::ExpectedStringArgument(
+            name.to_string(),
+            data_type.clone(),
+        )
+        .into()),
+    }
+}
+
+fn cast_integer(name: &str, value: &ArrayRef) -> Result<ArrayRef> {
+    if value.data_type().is_integer() || *value.data_type() == DataType::Null {
+        Ok(arrow::compute::cast(value, &DataType::Int64)?)
+    } else {
+        Err(ScalarFunctionError::ExpectedIntegerArgument(
+            name.to_string(),
+            value.data_type().clone(),
+        )
+        .into())
+    }
+}
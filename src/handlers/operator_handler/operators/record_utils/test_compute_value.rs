@@ -0,0 +1,1255 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::{
+    Array, ArrayRef, AsArray, BooleanArray, Date32Array, DurationMicrosecondArray, Float64Array,
+    Int32Array, Int64Array, LargeStringArray, RecordBatch, StringArray, TimestampMicrosecondArray,
+};
+use arrow::datatypes::{DataType, Field, Schema};
+use sqlparser::ast::Expr;
+use sqlparser::dialect::GenericDialect;
+use sqlparser::parser::Parser;
+
+use super::compute_value::{
+    compute_value, ComputeValueError, ComputeValueOptions, NanPolicy, ScalarFunction,
+};
+
+fn parse_expr(sql: &str) -> Result<Expr> {
+    Ok(Parser::new(&GenericDialect {})
+        .try_with_sql(sql)?
+        .parse_expr()?)
+}
+
+fn float_record() -> Result<RecordBatch> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Float64, true),
+        Field::new("b", DataType::Float64, true),
+        Field::new("c", DataType::Int32, true),
+    ]));
+    let a: ArrayRef = Arc::new(Float64Array::from(vec![
+        Some(1.0),
+        Some(f64::NAN),
+        Some(f64::INFINITY),
+        Some(f64::NEG_INFINITY),
+        Some(f64::NAN),
+        None,
+    ]));
+    let b: ArrayRef = Arc::new(Float64Array::from(vec![
+        Some(f64::NAN),
+        Some(f64::NAN),
+        Some(f64::INFINITY),
+        Some(1.0),
+        Some(2.0),
+        Some(f64::NAN),
+    ]));
+    let c: ArrayRef = Arc::new(Int32Array::from(vec![
+        Some(1),
+        Some(2),
+        Some(3),
+        Some(4),
+        Some(5),
+        Some(6),
+    ]));
+    Ok(RecordBatch::try_new(schema, vec![a, b, c])?)
+}
+
+fn compute_bools(rec: &RecordBatch, sql: &str, nan_policy: NanPolicy) -> Result<Vec<Option<bool>>> {
+    let table_aliases = vec![vec!["t".to_string()]; rec.num_columns()];
+    let res = compute_value(
+        rec,
+        &table_aliases,
+        &parse_expr(sql)?,
+        &ComputeValueOptions {
+            nan_policy,
+            ..Default::default()
+        },
+    )?;
+    Ok(res.as_boolean().iter().collect())
+}
+
+#[test]
+fn test_float_comparisons_with_nan_and_inf() -> Result<()> {
+    struct TestCase {
+        sql: &'static str,
+        nan_policy: NanPolicy,
+        expected: Vec<Option<bool>>,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            sql: "a = b",
+            nan_policy: NanPolicy::TotalOrder,
+            expected: vec![
+                Some(false),
+                Some(true),
+                Some(true),
+                Some(false),
+                Some(false),
+                None,
+            ],
+        },
+        TestCase {
+            sql: "a > b",
+            nan_policy: NanPolicy::TotalOrder,
+            expected: vec![
+                Some(false),
+                Some(false),
+                Some(false),
+                Some(false),
+                Some(true),
+                None,
+            ],
+        },
+        TestCase {
+            sql: "a = b",
+            nan_policy: NanPolicy::Ieee,
+            expected: vec![
+                Some(false),
+                Some(false),
+                Some(true),
+                Some(false),
+                Some(false),
+                None,
+            ],
+        },
+        TestCase {
+            sql: "a > b",
+            nan_policy: NanPolicy::Ieee,
+            expected: vec![
+                Some(false),
+                Some(false),
+                Some(false),
+                Some(false),
+                Some(false),
+                None,
+            ],
+        },
+        TestCase {
+            sql: "a <> b",
+            nan_policy: NanPolicy::Ieee,
+            expected: vec![
+                Some(true),
+                Some(true),
+                Some(false),
+                Some(true),
+                Some(true),
+                None,
+            ],
+        },
+        TestCase {
+            sql: "t.a >= 1.5",
+            nan_policy: NanPolicy::TotalOrder,
+            expected: vec![
+                Some(false),
+                Some(true),
+                Some(true),
+                Some(false),
+                Some(true),
+                None,
+            ],
+        },
+        TestCase {
+            sql: "t.a >= 1.5",
+            nan_policy: NanPolicy::Ieee,
+            expected: vec![
+                Some(false),
+                Some(false),
+                Some(true),
+                Some(false),
+                Some(false),
+                None,
+            ],
+        },
+        TestCase {
+            sql: "(c < a)",
+            nan_policy: NanPolicy::Ieee,
+            expected: vec![
+                Some(false),
+                Some(false),
+                Some(true),
+                Some(false),
+                Some(false),
+                None,
+            ],
+        },
+    ];
+
+    let rec = float_record()?;
+    for test_case in test_cases {
+        let res = compute_bools(&rec, test_case.sql, test_case.nan_policy)?;
+        assert_eq!(
+            res, test_case.expected,
+            "sql: {}, nan_policy: {:?}",
+            test_case.sql, test_case.nan_policy
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_unknown_column_is_an_error() -> Result<()> {
+    let rec = float_record()?;
+    assert!(compute_bools(&rec, "z > 1", NanPolicy::TotalOrder).is_err());
+    assert!(compute_bools(&rec, "other.a > 1", NanPolicy::TotalOrder).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_logical_operators_only_evaluate_undecided_rows() -> Result<()> {
+    struct TestCase {
+        sql: &'static str,
+        expected: Vec<Option<bool>>,
+        expected_evaluated_rows: usize,
+    }
+
+    // c: 1, 2, 3, 4, 5, 6
+    // a: 1.0, NaN, Inf, -Inf, NaN, null
+    let test_cases = vec![
+        TestCase {
+            sql: "c > 4 and is_even(c)",
+            expected: vec![
+                Some(false),
+                Some(false),
+                Some(false),
+                Some(false),
+                Some(false),
+                Some(true),
+            ],
+            expected_evaluated_rows: 2,
+        },
+        TestCase {
+            sql: "c > 4 or is_even(c)",
+            expected: vec![
+                Some(false),
+                Some(true),
+                Some(false),
+                Some(true),
+                Some(true),
+                Some(true),
+            ],
+            expected_evaluated_rows: 4,
+        },
+        TestCase {
+            sql: "a < 2 and is_even(c)",
+            expected: vec![
+                Some(false),
+                Some(false),
+                Some(false),
+                Some(true),
+                Some(false),
+                None,
+            ],
+            expected_evaluated_rows: 3,
+        },
+        TestCase {
+            sql: "a < 2 or is_even(c)",
+            expected: vec![
+                Some(true),
+                Some(true),
+                Some(false),
+                Some(true),
+                Some(false),
+                Some(true),
+            ],
+            expected_evaluated_rows: 4,
+        },
+        TestCase {
+            sql: "c > 10 and is_even(c)",
+            expected: vec![Some(false); 6],
+            expected_evaluated_rows: 0,
+        },
+        TestCase {
+            sql: "c > 3 and a > 0",
+            expected: vec![
+                Some(false),
+                Some(false),
+                Some(false),
+                Some(false),
+                Some(true),
+                None,
+            ],
+            expected_evaluated_rows: 0,
+        },
+    ];
+
+    let rec = float_record()?;
+    let table_aliases = vec![vec!["t".to_string()]; rec.num_columns()];
+    for test_case in test_cases {
+        let evaluated_rows = Arc::new(AtomicUsize::new(0));
+        let func_evaluated_rows = evaluated_rows.clone();
+        let is_even: ScalarFunction = Arc::new(move |args: &[ArrayRef]| {
+            func_evaluated_rows.fetch_add(args[0].len(), Ordering::SeqCst);
+            let res = arrow::array::BooleanArray::from(
+                args[0]
+                    .as_primitive::<arrow::datatypes::Int32Type>()
+                    .iter()
+                    .map(|val| val.map(|val| val % 2 == 0))
+                    .collect::<Vec<Option<bool>>>(),
+            );
+            Ok(Arc::new(res) as ArrayRef)
+        });
+
+        let mut options = ComputeValueOptions::default();
+        options.functions.insert("is_even".to_string(), is_even);
+
+        let res = compute_value(&rec, &table_aliases, &parse_expr(test_case.sql)?, &options)?;
+        let res: Vec<Option<bool>> = res.as_boolean().iter().collect();
+        assert_eq!(res, test_case.expected, "sql: {}", test_case.sql);
+        assert_eq!(
+            evaluated_rows.load(Ordering::SeqCst),
+            test_case.expected_evaluated_rows,
+            "sql: {}",
+            test_case.sql
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_string_concat() -> Result<()> {
+    struct TestCase {
+        sql: &'static str,
+        expected: Vec<Option<&'static str>>,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            sql: "first || ' ' || last",
+            expected: vec![Some("Ada Lovelace"), None, None, Some(" Hopper")],
+        },
+        TestCase {
+            sql: "t.first || '-' || id",
+            expected: vec![Some("Ada-1"), Some("Alan-2"), None, Some("-4")],
+        },
+        TestCase {
+            sql: "'id:' || id || (last || '!')",
+            expected: vec![
+                Some("id:1Lovelace!"),
+                None,
+                Some("id:3Curie!"),
+                Some("id:4Hopper!"),
+            ],
+        },
+    ];
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, true),
+        Field::new("first", DataType::Utf8, true),
+        Field::new("last", DataType::Utf8, true),
+    ]));
+    let id: ArrayRef = Arc::new(Int32Array::from(vec![Some(1), Some(2), Some(3), Some(4)]));
+    let first: ArrayRef = Arc::new(StringArray::from(vec![
+        Some("Ada"),
+        Some("Alan"),
+        None,
+        Some(""),
+    ]));
+    let last: ArrayRef = Arc::new(StringArray::from(vec![
+        Some("Lovelace"),
+        None,
+        Some("Curie"),
+        Some("Hopper"),
+    ]));
+    let rec = RecordBatch::try_new(schema, vec![id, first, last])?;
+    let table_aliases = vec![vec!["t".to_string()]; rec.num_columns()];
+
+    for test_case in test_cases {
+        let res = compute_value(
+            &rec,
+            &table_aliases,
+            &parse_expr(test_case.sql)?,
+            &ComputeValueOptions::default(),
+        )?;
+        let res: Vec<Option<&str>> = res.as_string::<i32>().iter().collect();
+        assert_eq!(res, test_case.expected, "sql: {}", test_case.sql);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_arithmetic_precedence() -> Result<()> {
+    struct TestCase {
+        sql: &'static str,
+        expected: Vec<Option<i64>>,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            sql: "a + 1 * b",
+            expected: vec![Some(3), Some(7), None],
+        },
+        TestCase {
+            sql: "(a + 1) * b",
+            expected: vec![Some(4), Some(15), None],
+        },
+        TestCase {
+            sql: "a - b - 1",
+            expected: vec![Some(-2), Some(-4), None],
+        },
+    ];
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Int32, true),
+    ]));
+    let a: ArrayRef = Arc::new(Int32Array::from(vec![Some(1), Some(2), None]));
+    let b: ArrayRef = Arc::new(Int32Array::from(vec![Some(2), Some(5), Some(3)]));
+    let rec = RecordBatch::try_new(schema, vec![a, b])?;
+    let table_aliases = vec![vec!["t".to_string()]; rec.num_columns()];
+
+    for test_case in test_cases {
+        let res = compute_value(
+            &rec,
+            &table_aliases,
+            &parse_expr(test_case.sql)?,
+            &ComputeValueOptions::default(),
+        )?;
+        let res: Vec<Option<i64>> = res
+            .as_primitive::<arrow::datatypes::Int64Type>()
+            .iter()
+            .collect();
+        assert_eq!(res, test_case.expected, "sql: {}", test_case.sql);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_string_literal_comparisons() -> Result<()> {
+    struct TestCase {
+        sql: &'static str,
+        expected: Vec<Option<bool>>,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            sql: "size = 'medium'",
+            expected: vec![Some(true), Some(false), Some(false), None],
+        },
+        TestCase {
+            sql: "'medium' <> size",
+            expected: vec![Some(false), Some(true), Some(true), None],
+        },
+        TestCase {
+            sql: "note = 'it''s'",
+            expected: vec![Some(true), Some(false), Some(false), Some(false)],
+        },
+        TestCase {
+            sql: "note = 'a;b'",
+            expected: vec![Some(false), Some(true), Some(false), Some(false)],
+        },
+        TestCase {
+            sql: "size < 'small'",
+            expected: vec![Some(true), Some(true), Some(false), None],
+        },
+    ];
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("size", DataType::Utf8, true),
+        Field::new("note", DataType::LargeUtf8, true),
+    ]));
+    let size: ArrayRef = Arc::new(StringArray::from(vec![
+        Some("medium"),
+        Some("large"),
+        Some("small"),
+        None,
+    ]));
+    let note: ArrayRef = Arc::new(LargeStringArray::from(vec![
+        Some("it's"),
+        Some("a;b"),
+        Some(""),
+        Some("its"),
+    ]));
+    let rec = RecordBatch::try_new(schema, vec![size, note])?;
+
+    for test_case in test_cases {
+        let res = compute_bools(&rec, test_case.sql, NanPolicy::TotalOrder)?;
+        assert_eq!(res, test_case.expected, "sql: {}", test_case.sql);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_in_list() -> Result<()> {
+    struct TestCase {
+        sql: &'static str,
+        expected: Vec<Option<bool>>,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            sql: "status in ('active', 'pending', 'closed')",
+            expected: vec![Some(true), Some(true), Some(false), None],
+        },
+        TestCase {
+            sql: "status not in ('active', 'pending', 'closed')",
+            expected: vec![Some(false), Some(false), Some(true), None],
+        },
+        TestCase {
+            sql: "id in (1, 3.0, '4')",
+            expected: vec![Some(true), Some(false), Some(true), Some(true)],
+        },
+        TestCase {
+            sql: "status in ('active', 2)",
+            expected: vec![Some(true), Some(false), Some(false), None],
+        },
+        TestCase {
+            sql: "id in (1, null)",
+            expected: vec![Some(true), None, None, None],
+        },
+        TestCase {
+            sql: "id not in (1, null)",
+            expected: vec![Some(false), None, None, None],
+        },
+        TestCase {
+            sql: "id in (id, 100)",
+            expected: vec![Some(true), Some(true), Some(true), Some(true)],
+        },
+    ];
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, true),
+        Field::new("status", DataType::Utf8, true),
+    ]));
+    let id: ArrayRef = Arc::new(Int32Array::from(vec![Some(1), Some(2), Some(3), Some(4)]));
+    let status: ArrayRef = Arc::new(StringArray::from(vec![
+        Some("active"),
+        Some("closed"),
+        Some("deleted"),
+        None,
+    ]));
+    let rec = RecordBatch::try_new(schema, vec![id, status])?;
+
+    for test_case in test_cases {
+        let res = compute_bools(&rec, test_case.sql, NanPolicy::TotalOrder)?;
+        assert_eq!(res, test_case.expected, "sql: {}", test_case.sql);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_between() -> Result<()> {
+    struct TestCase {
+        sql: &'static str,
+        expected: Vec<Option<bool>>,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            sql: "a between 2 and 4",
+            expected: vec![Some(false), Some(true), Some(true), Some(true), None],
+        },
+        TestCase {
+            sql: "a not between 2 and 4",
+            expected: vec![Some(true), Some(false), Some(false), Some(false), None],
+        },
+        TestCase {
+            sql: "a between 2 and 4 and b = 1",
+            expected: vec![Some(false), Some(true), Some(false), Some(true), None],
+        },
+        TestCase {
+            sql: "a between b and b + 2",
+            expected: vec![Some(true), Some(true), Some(true), Some(false), None],
+        },
+        TestCase {
+            sql: "a between 1.5 and 3.5",
+            expected: vec![Some(false), Some(true), Some(true), Some(false), None],
+        },
+    ];
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Int32, true),
+    ]));
+    let a: ArrayRef = Arc::new(Int32Array::from(vec![
+        Some(1),
+        Some(2),
+        Some(3),
+        Some(4),
+        None,
+    ]));
+    let b: ArrayRef = Arc::new(Int32Array::from(vec![
+        Some(1),
+        Some(1),
+        Some(2),
+        Some(1),
+        Some(1),
+    ]));
+    let rec = RecordBatch::try_new(schema, vec![a, b])?;
+
+    for test_case in test_cases {
+        let res = compute_bools(&rec, test_case.sql, NanPolicy::TotalOrder)?;
+        assert_eq!(res, test_case.expected, "sql: {}", test_case.sql);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_like_and_ilike() -> Result<()> {
+    struct TestCase {
+        sql: &'static str,
+        expected: Vec<Option<bool>>,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            sql: "name like 'A%'",
+            expected: vec![Some(true), Some(false), Some(true), Some(false), None],
+        },
+        TestCase {
+            sql: "name not like 'A%'",
+            expected: vec![Some(false), Some(true), Some(false), Some(true), None],
+        },
+        TestCase {
+            sql: "name like 'A_a'",
+            expected: vec![Some(true), Some(false), Some(false), Some(false), None],
+        },
+        TestCase {
+            sql: "name ilike 'a%'",
+            expected: vec![Some(true), Some(true), Some(true), Some(false), None],
+        },
+        TestCase {
+            sql: "name not ilike 'a%'",
+            expected: vec![Some(false), Some(false), Some(false), Some(true), None],
+        },
+        TestCase {
+            sql: "name like '%\\%'",
+            expected: vec![Some(false), Some(false), Some(true), Some(true), None],
+        },
+        TestCase {
+            sql: "name like '%\\%' escape '\\'",
+            expected: vec![Some(false), Some(false), Some(true), Some(true), None],
+        },
+    ];
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, true),
+        Field::new("name", DataType::Utf8, true),
+    ]));
+    let id: ArrayRef = Arc::new(Int32Array::from(vec![
+        Some(1),
+        Some(2),
+        Some(3),
+        Some(4),
+        Some(5),
+    ]));
+    let name: ArrayRef = Arc::new(StringArray::from(vec![
+        Some("Ada"),
+        Some("alan"),
+        Some("Al%"),
+        Some("50%"),
+        None,
+    ]));
+    let rec = RecordBatch::try_new(schema, vec![id, name])?;
+
+    for test_case in test_cases {
+        let res = compute_bools(&rec, test_case.sql, NanPolicy::TotalOrder)?;
+        assert_eq!(res, test_case.expected, "sql: {}", test_case.sql);
+    }
+
+    // the left side must be a string
+    let table_aliases = vec![vec!["t".to_string()]; rec.num_columns()];
+    let res = compute_value(
+        &rec,
+        &table_aliases,
+        &parse_expr("id like '1%'")?,
+        &ComputeValueOptions::default(),
+    );
+    assert!(res.is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_case_expressions() -> Result<()> {
+    struct TestCase {
+        sql: &'static str,
+        expected: ArrayRef,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            sql: "case when amount > 100 then 'big' else 'small' end",
+            expected: Arc::new(StringArray::from(vec![
+                Some("small"),
+                Some("big"),
+                Some("small"),
+                Some("small"),
+            ])),
+        },
+        TestCase {
+            sql: "case when amount > 100 then 'big' when amount > 10 then 'medium' end",
+            expected: Arc::new(StringArray::from(vec![
+                None,
+                Some("big"),
+                Some("medium"),
+                None,
+            ])),
+        },
+        TestCase {
+            sql: "case code when 1 then 'one' when 2 then 'two' else 'other' end",
+            expected: Arc::new(StringArray::from(vec![
+                Some("one"),
+                Some("two"),
+                Some("other"),
+                Some("other"),
+            ])),
+        },
+        TestCase {
+            sql: "case when amount > 100 then code else amount / 2.0 end",
+            expected: Arc::new(Float64Array::from(vec![
+                Some(2.5),
+                Some(2.0),
+                Some(25.0),
+                None,
+            ])),
+        },
+        TestCase {
+            sql: "case when amount > 10 then 1 when amount > 1 then 2 else 3 end",
+            expected: Arc::new(Int64Array::from(vec![Some(2), Some(1), Some(1), Some(3)])),
+        },
+    ];
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("amount", DataType::Int32, true),
+        Field::new("code", DataType::Int32, true),
+    ]));
+    let amount: ArrayRef = Arc::new(Int32Array::from(vec![Some(5), Some(500), Some(50), None]));
+    let code: ArrayRef = Arc::new(Int32Array::from(vec![Some(1), Some(2), Some(3), None]));
+    let rec = RecordBatch::try_new(schema, vec![amount, code])?;
+    let table_aliases = vec![vec!["t".to_string()]; rec.num_columns()];
+
+    for test_case in test_cases {
+        let res = compute_value(
+            &rec,
+            &table_aliases,
+            &parse_expr(test_case.sql)?,
+            &ComputeValueOptions::default(),
+        )?;
+        assert_eq!(&res, &test_case.expected, "sql: {}", test_case.sql);
+    }
+
+    // results without a common type are an error
+    let res = compute_value(
+        &rec,
+        &table_aliases,
+        &parse_expr("case when amount > 100 then 'big' else 1 end")?,
+        &ComputeValueOptions::default(),
+    );
+    assert!(res.is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_string_functions() -> Result<()> {
+    struct TestCase {
+        sql: &'static str,
+        expected: ArrayRef,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            sql: "upper(name)",
+            expected: Arc::new(StringArray::from(vec![
+                Some("  ADA "),
+                Some(""),
+                None,
+                Some("ÉCOLE"),
+            ])),
+        },
+        TestCase {
+            sql: "lower(name)",
+            expected: Arc::new(StringArray::from(vec![
+                Some("  ada "),
+                Some(""),
+                None,
+                Some("école"),
+            ])),
+        },
+        TestCase {
+            sql: "length(name)",
+            expected: Arc::new(Int32Array::from(vec![Some(6), Some(0), None, Some(5)])),
+        },
+        TestCase {
+            sql: "trim(name)",
+            expected: Arc::new(StringArray::from(vec![
+                Some("Ada"),
+                Some(""),
+                None,
+                Some("École"),
+            ])),
+        },
+        TestCase {
+            sql: "ltrim(name)",
+            expected: Arc::new(StringArray::from(vec![
+                Some("Ada "),
+                Some(""),
+                None,
+                Some("École"),
+            ])),
+        },
+        TestCase {
+            sql: "rtrim(name)",
+            expected: Arc::new(StringArray::from(vec![
+                Some("  Ada"),
+                Some(""),
+                None,
+                Some("École"),
+            ])),
+        },
+        TestCase {
+            sql: "trim(leading 'É ' from name)",
+            expected: Arc::new(StringArray::from(vec![
+                Some("Ada "),
+                Some(""),
+                None,
+                Some("cole"),
+            ])),
+        },
+        TestCase {
+            sql: "length(trim(name))",
+            expected: Arc::new(Int32Array::from(vec![Some(3), Some(0), None, Some(5)])),
+        },
+    ];
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, true),
+        Field::new("name", DataType::Utf8, true),
+    ]));
+    let id: ArrayRef = Arc::new(Int32Array::from(vec![Some(1), Some(2), Some(3), Some(4)]));
+    let name: ArrayRef = Arc::new(StringArray::from(vec![
+        Some("  Ada "),
+        Some(""),
+        None,
+        Some("École"),
+    ]));
+    let rec = RecordBatch::try_new(schema, vec![id, name])?;
+    let table_aliases = vec![vec!["t".to_string()]; rec.num_columns()];
+
+    for test_case in test_cases {
+        let res = compute_value(
+            &rec,
+            &table_aliases,
+            &parse_expr(test_case.sql)?,
+            &ComputeValueOptions::default(),
+        )?;
+        assert_eq!(&res, &test_case.expected, "sql: {}", test_case.sql);
+    }
+
+    // non-string arguments and the wrong number of arguments are errors
+    for sql in ["upper(id)", "length(name, name)", "lower()"] {
+        let res = compute_value(
+            &rec,
+            &table_aliases,
+            &parse_expr(sql)?,
+            &ComputeValueOptions::default(),
+        );
+        assert!(res.is_err(), "sql: {}", sql);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_substring_and_concat() -> Result<()> {
+    struct TestCase {
+        sql: &'static str,
+        expected: Vec<Option<&'static str>>,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            sql: "substring(name from 2 for 3)",
+            expected: vec![Some("ell"), Some("é"), Some(""), None],
+        },
+        TestCase {
+            sql: "substring(name from 2)",
+            expected: vec![Some("ello"), Some("é"), Some(""), None],
+        },
+        TestCase {
+            sql: "substring(name for 2)",
+            expected: vec![Some("he"), Some("ré"), Some(""), None],
+        },
+        TestCase {
+            sql: "substring(name from 0 for 3)",
+            expected: vec![Some("he"), Some("ré"), Some(""), None],
+        },
+        TestCase {
+            sql: "substring(name from 0 - 5 for 3)",
+            expected: vec![Some(""), Some(""), Some(""), None],
+        },
+        TestCase {
+            sql: "substring(name from 100 for 3)",
+            expected: vec![Some(""), Some(""), Some(""), None],
+        },
+        TestCase {
+            sql: "substring(name, id, 1)",
+            expected: vec![Some("h"), Some("é"), Some(""), None],
+        },
+        TestCase {
+            sql: "concat(name, '-', id)",
+            expected: vec![Some("hello-1"), Some("ré-2"), Some("-3"), Some("-4")],
+        },
+        TestCase {
+            sql: "concat(name)",
+            expected: vec![Some("hello"), Some("ré"), Some(""), Some("")],
+        },
+        TestCase {
+            sql: "concat(substring(name for 1), 2.5, null)",
+            expected: vec![Some("h2.5"), Some("r2.5"), Some("2.5"), Some("2.5")],
+        },
+    ];
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, true),
+        Field::new("name", DataType::Utf8, true),
+    ]));
+    let id: ArrayRef = Arc::new(Int32Array::from(vec![Some(1), Some(2), Some(3), Some(4)]));
+    let name: ArrayRef = Arc::new(StringArray::from(vec![
+        Some("hello"),
+        Some("ré"),
+        Some(""),
+        None,
+    ]));
+    let rec = RecordBatch::try_new(schema, vec![id, name])?;
+    let table_aliases = vec![vec!["t".to_string()]; rec.num_columns()];
+
+    for test_case in test_cases {
+        let res = compute_value(
+            &rec,
+            &table_aliases,
+            &parse_expr(test_case.sql)?,
+            &ComputeValueOptions::default(),
+        )?;
+        let res: Vec<Option<&str>> = res.as_string::<i32>().iter().collect();
+        assert_eq!(res, test_case.expected, "sql: {}", test_case.sql);
+    }
+
+    for sql in [
+        "substring(name from 1 for 0 - 1)",
+        "substring(name from 'a')",
+        "concat()",
+    ] {
+        let res = compute_value(
+            &rec,
+            &table_aliases,
+            &parse_expr(sql)?,
+            &ComputeValueOptions::default(),
+        );
+        assert!(res.is_err(), "sql: {}", sql);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_date_and_timestamp_values() -> Result<()> {
+    struct TestCase {
+        sql: &'static str,
+        expected: Vec<Option<bool>>,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            sql: "ts > '2024-01-01'",
+            expected: vec![Some(false), Some(true), None],
+        },
+        TestCase {
+            sql: "ts <= '2024-01-01T12:00:00'",
+            expected: vec![Some(true), Some(false), None],
+        },
+        TestCase {
+            sql: "d = '2024-01-01'",
+            expected: vec![Some(false), Some(true), Some(false)],
+        },
+        TestCase {
+            sql: "ts >= d",
+            expected: vec![Some(true), Some(true), None],
+        },
+        // the literal is read in the column's timezone
+        TestCase {
+            sql: "ts_tz = '2024-01-01T02:00:00'",
+            expected: vec![Some(true), Some(false), Some(false)],
+        },
+        TestCase {
+            sql: "ts_tz = '2024-01-01T00:00:00Z'",
+            expected: vec![Some(true), Some(false), Some(false)],
+        },
+    ];
+
+    // 2023-12-31T23:00:00, 2024-01-02T00:00:00, null
+    let ts = TimestampMicrosecondArray::from(vec![
+        Some(1_704_063_600_000_000),
+        Some(1_704_153_600_000_000),
+        None,
+    ]);
+    // 2024-01-01T00:00:00Z, 2024-01-01T00:00:01Z, 2024-01-02T00:00:00Z
+    let ts_tz = TimestampMicrosecondArray::from(vec![
+        1_704_067_200_000_000,
+        1_704_067_201_000_000,
+        1_704_153_600_000_000,
+    ])
+    .with_timezone("+02:00");
+    // 2023-12-31, 2024-01-01, 2024-01-02
+    let d = Date32Array::from(vec![19_722, 19_723, 19_724]);
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("ts", ts.data_type().clone(), true),
+        Field::new("ts_tz", ts_tz.data_type().clone(), true),
+        Field::new("d", DataType::Date32, true),
+    ]));
+    let rec = RecordBatch::try_new(schema, vec![Arc::new(ts), Arc::new(ts_tz), Arc::new(d)])?;
+
+    for test_case in test_cases {
+        let res = compute_bools(&rec, test_case.sql, NanPolicy::TotalOrder)?;
+        assert_eq!(res, test_case.expected, "sql: {}", test_case.sql);
+    }
+
+    // subtracting timestamps gives a duration
+    let table_aliases = vec![vec!["t".to_string()]; rec.num_columns()];
+    let res = compute_value(
+        &rec,
+        &table_aliases,
+        &parse_expr("ts - '2023-12-31'")?,
+        &ComputeValueOptions::default(),
+    )?;
+    assert_eq!(
+        res.as_ref(),
+        &DurationMicrosecondArray::from(vec![Some(82_800_000_000), Some(172_800_000_000), None])
+    );
+
+    for sql in ["ts > 'not a date'", "ts = ts_tz", "ts - ts_tz"] {
+        let res = compute_value(
+            &rec,
+            &table_aliases,
+            &parse_expr(sql)?,
+            &ComputeValueOptions::default(),
+        );
+        assert!(res.is_err(), "sql: {}", sql);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_division_and_modulo() -> Result<()> {
+    struct TestCase {
+        sql: &'static str,
+        expected: ArrayRef,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            sql: "a % 3",
+            expected: Arc::new(Int64Array::from(vec![Some(1), Some(-2), Some(0), None])),
+        },
+        TestCase {
+            sql: "a % b",
+            expected: Arc::new(Int64Array::from(vec![Some(1), None, Some(0), None])),
+        },
+        TestCase {
+            sql: "17 % b",
+            expected: Arc::new(Int64Array::from(vec![Some(2), None, Some(2), Some(1)])),
+        },
+        TestCase {
+            sql: "a / 2",
+            expected: Arc::new(Int64Array::from(vec![Some(3), Some(-2), Some(0), None])),
+        },
+        TestCase {
+            sql: "a / b",
+            expected: Arc::new(Int64Array::from(vec![Some(2), None, Some(0), None])),
+        },
+        TestCase {
+            sql: "a / 0",
+            expected: Arc::new(Int64Array::from(vec![None, None, None, None])),
+        },
+        TestCase {
+            sql: "c % 2",
+            expected: Arc::new(Float64Array::from(vec![
+                Some(1.5),
+                Some(-0.5),
+                Some(0.0),
+                None,
+            ])),
+        },
+        TestCase {
+            sql: "c / b",
+            expected: Arc::new(Float64Array::from(vec![Some(2.5), None, Some(0.0), None])),
+        },
+        TestCase {
+            sql: "a + 1 % 2 * 3",
+            expected: Arc::new(Int64Array::from(vec![Some(10), Some(-2), Some(3), None])),
+        },
+    ];
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Int32, true),
+        Field::new("c", DataType::Float64, true),
+    ]));
+    let a: ArrayRef = Arc::new(Int32Array::from(vec![Some(7), Some(-5), Some(0), None]));
+    let b: ArrayRef = Arc::new(Int32Array::from(vec![Some(3), Some(0), Some(5), Some(4)]));
+    let c: ArrayRef = Arc::new(Float64Array::from(vec![
+        Some(7.5),
+        Some(-0.5),
+        Some(0.0),
+        None,
+    ]));
+    let rec = RecordBatch::try_new(schema, vec![a, b, c])?;
+    let table_aliases = vec![vec!["t".to_string()]; rec.num_columns()];
+
+    for test_case in test_cases {
+        let res = compute_value(
+            &rec,
+            &table_aliases,
+            &parse_expr(test_case.sql)?,
+            &ComputeValueOptions::default(),
+        )?;
+        assert_eq!(&res, &test_case.expected, "sql: {}", test_case.sql);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_unary_operators() -> Result<()> {
+    struct TestCase {
+        sql: &'static str,
+        expected: ArrayRef,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            sql: "-balance",
+            expected: Arc::new(Int32Array::from(vec![Some(-10), Some(5), None])),
+        },
+        TestCase {
+            sql: "+balance",
+            expected: Arc::new(Int32Array::from(vec![Some(10), Some(-5), None])),
+        },
+        TestCase {
+            sql: "-(balance + 1)",
+            expected: Arc::new(Int64Array::from(vec![Some(-11), Some(4), None])),
+        },
+        TestCase {
+            sql: "-2.5",
+            expected: Arc::new(Float64Array::from(vec![-2.5, -2.5, -2.5])),
+        },
+        TestCase {
+            sql: "not is_active",
+            expected: Arc::new(BooleanArray::from(vec![Some(false), Some(true), None])),
+        },
+        TestCase {
+            sql: "not balance > 0",
+            expected: Arc::new(BooleanArray::from(vec![Some(false), Some(true), None])),
+        },
+        TestCase {
+            sql: "not null",
+            expected: Arc::new(BooleanArray::from(vec![None, None, None])),
+        },
+        TestCase {
+            sql: "balance + -null",
+            expected: Arc::new(Int64Array::from(vec![None, None, None])),
+        },
+        TestCase {
+            sql: "not not is_active",
+            expected: Arc::new(BooleanArray::from(vec![Some(true), Some(false), None])),
+        },
+    ];
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("balance", DataType::Int32, true),
+        Field::new("is_active", DataType::Boolean, true),
+    ]));
+    let balance: ArrayRef = Arc::new(Int32Array::from(vec![Some(10), Some(-5), None]));
+    let is_active: ArrayRef = Arc::new(BooleanArray::from(vec![Some(true), Some(false), None]));
+    let rec = RecordBatch::try_new(schema, vec![balance, is_active])?;
+    let table_aliases = vec![vec!["t".to_string()]; rec.num_columns()];
+
+    for test_case in test_cases {
+        let res = compute_value(
+            &rec,
+            &table_aliases,
+            &parse_expr(test_case.sql)?,
+            &ComputeValueOptions::default(),
+        )?;
+        assert_eq!(&res, &test_case.expected, "sql: {}", test_case.sql);
+    }
+
+    for sql in ["not balance", "-is_active", "-'a'"] {
+        let res = compute_value(
+            &rec,
+            &table_aliases,
+            &parse_expr(sql)?,
+            &ComputeValueOptions::default(),
+        );
+        assert!(res.is_err(), "sql: {}", sql);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_errors_are_typed() -> Result<()> {
+    struct TestCase {
+        sql: &'static str,
+        is_expected_error: fn(&ComputeValueError) -> bool,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            sql: "z > 1",
+            is_expected_error: |err| matches!(err, ComputeValueError::ColumnNotFound(_)),
+        },
+        TestCase {
+            sql: "not a",
+            is_expected_error: |err| matches!(err, ComputeValueError::TypeMismatch { expected, found: DataType::Float64 } if expected == "boolean"),
+        },
+        TestCase {
+            sql: "a > 'x'",
+            is_expected_error: |err| {
+                matches!(err, ComputeValueError::UnableToCompareDataTypes(_, _))
+            },
+        },
+        TestCase {
+            sql: "upper(a)",
+            is_expected_error: |err| matches!(err, ComputeValueError::ScalarFunction(_)),
+        },
+        TestCase {
+            sql: "9223372036854775807 + c",
+            is_expected_error: |err| matches!(err, ComputeValueError::ArithmeticOverflow(_)),
+        },
+        TestCase {
+            sql: "a is distinct from b",
+            is_expected_error: |err| matches!(err, ComputeValueError::NotImplemented(_)),
+        },
+    ];
+
+    let rec = float_record()?;
+    let table_aliases = vec![vec!["t".to_string()]; rec.num_columns()];
+    for test_case in test_cases {
+        let err = compute_value(
+            &rec,
+            &table_aliases,
+            &parse_expr(test_case.sql)?,
+            &ComputeValueOptions::default(),
+        )
+        .unwrap_err();
+        assert!(
+            (test_case.is_expected_error)(&err),
+            "sql: {}, err: {:?}",
+            test_case.sql,
+            err
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::{Array, ArrayRef, AsArray, Float64Array, Int32Array, StringArray};
+use arrow::datatypes::{DataType, Float64Type, Int64Type};
+
+use super::compute_value::NanPolicy;
+use super::record_accumulators::Accumulator;
+
+fn evaluate_i64(acc: &Accumulator) -> Result<Option<i64>> {
+    let res = acc.evaluate()?;
+    Ok(res.as_primitive::<Int64Type>().iter().next().unwrap())
+}
+
+fn evaluate_f64(acc: &Accumulator) -> Result<Option<f64>> {
+    let res = acc.evaluate()?;
+    Ok(res.as_primitive::<Float64Type>().iter().next().unwrap())
+}
+
+fn evaluate_i32(acc: &Accumulator) -> Result<Option<i32>> {
+    let res = acc.evaluate()?;
+    Ok(res
+        .as_primitive::<arrow::datatypes::Int32Type>()
+        .iter()
+        .next()
+        .unwrap())
+}
+
+#[test]
+fn test_aggregates_skip_null_values() -> Result<()> {
+    let values: ArrayRef = Arc::new(Int32Array::from(vec![Some(1), None, Some(4), None]));
+
+    let mut count = Accumulator::new_count();
+    let mut count_star = Accumulator::new_count_star();
+    let mut sum = Accumulator::new_sum(&DataType::Int32)?;
+    let mut avg = Accumulator::new_avg(&DataType::Int32)?;
+    let mut min = Accumulator::new_min(&DataType::Int32, NanPolicy::TotalOrder);
+    let mut max = Accumulator::new_max(&DataType::Int32, NanPolicy::TotalOrder);
+    for acc in [
+        &mut count,
+        &mut count_star,
+        &mut sum,
+        &mut avg,
+        &mut min,
+        &mut max,
+    ] {
+        acc.update(&values)?;
+    }
+
+    assert_eq!(evaluate_i64(&count)?, Some(2));
+    assert_eq!(evaluate_i64(&count_star)?, Some(4));
+    assert_eq!(evaluate_i64(&sum)?, Some(5));
+    assert_eq!(evaluate_f64(&avg)?, Some(2.5));
+    assert_eq!(evaluate_i32(&min)?, Some(1));
+    assert_eq!(evaluate_i32(&max)?, Some(4));
+
+    Ok(())
+}
+
+#[test]
+fn test_aggregates_of_an_all_null_group() -> Result<()> {
+    let values: ArrayRef = Arc::new(Int32Array::from(vec![None, None, None]));
+
+    let mut count = Accumulator::new_count();
+    let mut count_star = Accumulator::new_count_star();
+    let mut sum = Accumulator::new_sum(&DataType::Int32)?;
+    let mut avg = Accumulator::new_avg(&DataType::Int32)?;
+    let mut min = Accumulator::new_min(&DataType::Int32, NanPolicy::TotalOrder);
+    let mut max = Accumulator::new_max(&DataType::Int32, NanPolicy::TotalOrder);
+    for acc in [
+        &mut count,
+        &mut count_star,
+        &mut sum,
+        &mut avg,
+        &mut min,
+        &mut max,
+    ] {
+        acc.update(&values)?;
+    }
+
+    assert_eq!(evaluate_i64(&count)?, Some(0));
+    assert_eq!(evaluate_i64(&count_star)?, Some(3));
+    assert_eq!(evaluate_i64(&sum)?, None);
+    assert_eq!(evaluate_f64(&avg)?, None);
+    assert_eq!(evaluate_i32(&min)?, None);
+    assert_eq!(evaluate_i32(&max)?, None);
+    assert_eq!(min.evaluate()?.data_type(), &DataType::Int32);
+
+    Ok(())
+}
+
+#[test]
+fn test_merge_partial_aggregates() -> Result<()> {
+    let values_1: ArrayRef = Arc::new(Float64Array::from(vec![Some(1.0), None]));
+    let values_2: ArrayRef = Arc::new(Float64Array::from(vec![Some(2.0), Some(6.0)]));
+
+    let mut avg = Accumulator::new_avg(&DataType::Float64)?;
+    avg.update(&values_1)?;
+    let mut partial_avg = Accumulator::new_avg(&DataType::Float64)?;
+    partial_avg.update(&values_2)?;
+    avg.merge(&partial_avg)?;
+    assert_eq!(evaluate_f64(&avg)?, Some(3.0));
+
+    let mut max = Accumulator::new_max(&DataType::Float64, NanPolicy::TotalOrder);
+    max.update(&values_1)?;
+    let mut partial_max = Accumulator::new_max(&DataType::Float64, NanPolicy::TotalOrder);
+    partial_max.update(&values_2)?;
+    max.merge(&partial_max)?;
+    assert_eq!(evaluate_f64(&max)?, Some(6.0));
+
+    assert!(max.merge(&avg).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_min_max_with_nan() -> Result<()> {
+    let values: ArrayRef = Arc::new(Float64Array::from(vec![
+        Some(f64::NAN),
+        Some(f64::INFINITY),
+        None,
+        Some(-2.0),
+    ]));
+
+    let mut max = Accumulator::new_max(&DataType::Float64, NanPolicy::TotalOrder);
+    max.update(&values)?;
+    assert!(evaluate_f64(&max)?.unwrap().is_nan());
+
+    let mut max = Accumulator::new_max(&DataType::Float64, NanPolicy::Ieee);
+    max.update(&values)?;
+    assert_eq!(evaluate_f64(&max)?, Some(f64::INFINITY));
+
+    let mut min = Accumulator::new_min(&DataType::Float64, NanPolicy::TotalOrder);
+    min.update(&values)?;
+    assert_eq!(evaluate_f64(&min)?, Some(-2.0));
+
+    Ok(())
+}
+
+#[test]
+fn test_min_max_of_strings() -> Result<()> {
+    let values: ArrayRef = Arc::new(StringArray::from(vec![Some("b"), None, Some("a")]));
+
+    let mut min = Accumulator::new_min(&DataType::Utf8, NanPolicy::TotalOrder);
+    min.update(&values)?;
+    assert_eq!(min.evaluate()?.as_string::<i32>().value(0), "a");
+
+    assert!(Accumulator::new_sum(&DataType::Utf8).is_err());
+
+    Ok(())
+}
@@ -0,0 +1,208 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::{
+    Array, ArrayRef, AsArray, Float64Array, Int32Array, Int64Array, RecordBatch, StringArray,
+};
+use arrow::datatypes::{DataType, Field, Float64Type, Int64Type, Schema};
+use sqlparser::ast::SelectItem;
+use sqlparser::dialect::GenericDialect;
+use sqlparser::parser::Parser;
+
+use crate::planner::{LogicalPlan, LogicalPlanNodeType, LogicalPlanner};
+
+use super::compute_value::NanPolicy;
+use super::record_aggregate::RecordAggregator;
+use super::record_projection::project_record;
+
+fn build_record(ids: Vec<Option<i32>>, sizes: Vec<Option<&str>>) -> Result<RecordBatch> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, true),
+        Field::new("size", DataType::Utf8, true),
+    ]));
+    Ok(RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from(ids)),
+            Arc::new(StringArray::from(sizes)),
+        ],
+    )?)
+}
+
+// plans the query and returns the aggregator and the materialized fields
+fn build_aggregator(query: &str) -> Result<(RecordAggregator, Vec<SelectItem>)> {
+    let plan: LogicalPlan = LogicalPlanner::new(query.to_string()).build()?;
+    let mut aggregator: Option<RecordAggregator> = None;
+    let mut fields: Vec<SelectItem> = Vec::new();
+    for node in plan.get_all_nodes() {
+        match node.node {
+            LogicalPlanNodeType::Aggregate {
+                group_by,
+                aggregates,
+            } => {
+                aggregator = Some(RecordAggregator::new(group_by, aggregates));
+            }
+            LogicalPlanNodeType::Materialize { fields: items } => {
+                fields = items;
+            }
+            _ => (),
+        }
+    }
+    Ok((aggregator.expect("expected an aggregate node"), fields))
+}
+
+#[test]
+fn test_aggregate_records_by_group() -> Result<()> {
+    let (mut aggregator, fields) = build_aggregator(
+        "select size, count(*), count(id), sum(id), avg(id), min(id), max(id) \
+            from t group by size",
+    )?;
+
+    let aliases = vec![vec!["t".to_string()], vec!["t".to_string()]];
+    aggregator.update(
+        &build_record(
+            vec![Some(1), Some(2), None],
+            vec![Some("small"), Some("large"), Some("small")],
+        )?,
+        &aliases,
+    )?;
+    aggregator.update(
+        &build_record(
+            vec![Some(3), Some(4), Some(5)],
+            vec![Some("large"), None, Some("small")],
+        )?,
+        &aliases,
+    )?;
+    assert_eq!(aggregator.num_groups(), 3);
+    assert!(aggregator.memory_size_in_bytes() > 0);
+
+    let (rec, table_aliases) = aggregator.finish()?;
+    let rec = project_record(&fields, Arc::new(rec), &table_aliases)?;
+    assert_eq!(rec.num_rows(), 3);
+
+    let sizes: ArrayRef = Arc::new(StringArray::from(vec![Some("small"), Some("large"), None]));
+    let count_star: ArrayRef = Arc::new(Int64Array::from(vec![3, 2, 1]));
+    let count: ArrayRef = Arc::new(Int64Array::from(vec![2, 2, 1]));
+    let sum: ArrayRef = Arc::new(Int64Array::from(vec![6, 5, 4]));
+    let avg: ArrayRef = Arc::new(Float64Array::from(vec![3.0, 2.5, 4.0]));
+    let min: ArrayRef = Arc::new(Int32Array::from(vec![1, 2, 4]));
+    let max: ArrayRef = Arc::new(Int32Array::from(vec![5, 3, 4]));
+    let expected_columns = vec![sizes, count_star, count, sum, avg, min, max];
+    for (idx, expected) in expected_columns.iter().enumerate() {
+        assert_eq!(rec.column(idx), expected, "column {}", idx);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_aggregate_expressions_reference_aggregate_results() -> Result<()> {
+    let (mut aggregator, fields) =
+        build_aggregator("select id % 2 as parity, sum(id) / count(id) from t group by parity")?;
+
+    let aliases = vec![Vec::new(), Vec::new()];
+    aggregator.update(
+        &build_record(
+            vec![Some(1), Some(2), Some(3), Some(4)],
+            vec![None, None, None, None],
+        )?,
+        &aliases,
+    )?;
+
+    let (rec, table_aliases) = aggregator.finish()?;
+    let rec = project_record(&fields, Arc::new(rec), &table_aliases)?;
+
+    assert_eq!(rec.schema().field(0).name(), "parity");
+    assert_eq!(
+        rec.column(0).as_primitive::<Int64Type>().values().to_vec(),
+        vec![1, 0]
+    );
+    assert_eq!(
+        rec.column(1).as_primitive::<Int64Type>().values().to_vec(),
+        vec![2, 3]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_aggregate_without_group_by_on_no_records() -> Result<()> {
+    let (aggregator, _) = build_aggregator("select count(*), sum(id), avg(id) from t")?;
+
+    let (rec, _) = aggregator.finish()?;
+    assert_eq!(rec.num_rows(), 1);
+    assert_eq!(rec.column(0).as_primitive::<Int64Type>().value(0), 0);
+    assert!(rec.column(1).is_null(0));
+    assert!(rec.column(2).as_primitive::<Float64Type>().is_null(0));
+
+    let (aggregator, _) =
+        build_aggregator("select size, count(*), sum(id), avg(id) from t group by size")?;
+    let (rec, _) = aggregator.finish()?;
+    assert_eq!(rec.num_rows(), 0);
+    let data_types: Vec<DataType> = rec
+        .schema()
+        .fields()
+        .iter()
+        .map(|field| field.data_type().clone())
+        .collect();
+    assert_eq!(
+        data_types,
+        vec![
+            DataType::Null,
+            DataType::Int64,
+            DataType::Int64,
+            DataType::Float64
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_aggregate_columns_are_named_after_expressions() -> Result<()> {
+    let items = Parser::new(&GenericDialect {})
+        .try_with_sql("count(*), sum(id)")?
+        .parse_projection()?;
+    let (mut aggregator, _) = build_aggregator("select count(*), sum(id) from t")?;
+    aggregator.update(
+        &build_record(vec![Some(1), Some(2)], vec![None, None])?,
+        &[Vec::new(), Vec::new()],
+    )?;
+    let (rec, _) = aggregator.finish()?;
+    for (field, item) in rec.schema().fields().iter().zip(items) {
+        assert_eq!(field.name(), &item.to_string());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_max_follows_the_nan_policy() -> Result<()> {
+    let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Float64, true)]));
+    let rec = RecordBatch::try_new(
+        schema,
+        vec![Arc::new(Float64Array::from(vec![
+            Some(1.0),
+            Some(f64::NAN),
+            Some(f64::INFINITY),
+        ]))],
+    )?;
+    let aliases = vec![vec!["t".to_string()]];
+
+    for (nan_policy, expect_nan) in [(NanPolicy::TotalOrder, true), (NanPolicy::Ieee, false)] {
+        let (mut aggregator, fields) = build_aggregator("select max(a) from t")?;
+        aggregator.set_nan_policy(nan_policy);
+        aggregator.update(&rec, &aliases)?;
+
+        let (res, table_aliases) = aggregator.finish()?;
+        let res = project_record(&fields, Arc::new(res), &table_aliases)?;
+        let max = res.column(0).as_primitive::<Float64Type>().value(0);
+        if expect_nan {
+            assert!(max.is_nan(), "nan_policy: {:?}", nan_policy);
+        } else {
+            assert_eq!(max, f64::INFINITY, "nan_policy: {:?}", nan_policy);
+        }
+    }
+
+    Ok(())
+}
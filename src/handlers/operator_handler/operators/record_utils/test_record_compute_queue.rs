@@ -0,0 +1,70 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+
+use super::record_compute_queue::RecordComputeQueue;
+
+#[tokio::test]
+async fn test_results_are_returned_in_push_order() -> Result<()> {
+    let mut queue: RecordComputeQueue<usize> = RecordComputeQueue::new(3);
+    assert!(queue.is_empty());
+
+    // the first operations take the longest
+    for idx in 0..3 {
+        queue.push(move || {
+            std::thread::sleep(Duration::from_millis(30 - 10 * idx as u64));
+            Ok(idx)
+        });
+    }
+    assert!(queue.is_full());
+    assert_eq!(queue.len(), 3);
+
+    let mut results = Vec::new();
+    while let Some(res) = queue.next().await {
+        results.push(res?);
+    }
+    assert_eq!(results, vec![0, 1, 2]);
+    assert!(queue.next().await.is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_operations_run_concurrently() -> Result<()> {
+    let running = Arc::new(AtomicUsize::new(0));
+    let max_running = Arc::new(AtomicUsize::new(0));
+
+    let mut queue: RecordComputeQueue<()> = RecordComputeQueue::new(4);
+    for _ in 0..4 {
+        let running = running.clone();
+        let max_running = max_running.clone();
+        queue.push(move || {
+            let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+            max_running.fetch_max(now_running, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(50));
+            running.fetch_sub(1, Ordering::SeqCst);
+            Ok(())
+        });
+    }
+    while let Some(res) = queue.next().await {
+        res?;
+    }
+    assert!(max_running.load(Ordering::SeqCst) > 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_errors_are_returned() -> Result<()> {
+    let mut queue: RecordComputeQueue<usize> = RecordComputeQueue::new(0);
+    queue.push(|| Err(anyhow::Error::msg("projection failed")));
+    // at least one operation is always allowed
+    assert!(queue.is_full());
+
+    let err = queue.next().await.expect("expected a result").unwrap_err();
+    assert_eq!(err.to_string(), "projection failed");
+
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::{Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use sqlparser::ast::Expr;
+use sqlparser::dialect::GenericDialect;
+use sqlparser::parser::Parser;
+
+use super::record_distinct::RecordDistinct;
+
+fn parse_expr(sql: &str) -> Result<Expr> {
+    Ok(Parser::new(&GenericDialect {})
+        .try_with_sql(sql)?
+        .parse_expr()?)
+}
+
+fn build_record(ids: Vec<Option<i32>>, regions: Vec<Option<&str>>) -> Result<RecordBatch> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, true),
+        Field::new("region", DataType::Utf8, true),
+    ]));
+    Ok(RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from(ids)),
+            Arc::new(StringArray::from(regions)),
+        ],
+    )?)
+}
+
+fn records() -> Result<Vec<RecordBatch>> {
+    Ok(vec![
+        build_record(
+            vec![Some(1), Some(2), Some(1), None],
+            vec![Some("east"), Some("west"), Some("east"), Some("west")],
+        )?,
+        build_record(
+            vec![Some(2), Some(3), None, Some(1)],
+            vec![Some("west"), Some("east"), Some("west"), None],
+        )?,
+    ])
+}
+
+#[test]
+fn test_distinct_record() -> Result<()> {
+    struct TestCase {
+        case_name: &'static str,
+        keys: Vec<&'static str>,
+        expected_ids: Vec<Option<i32>>,
+        expected_regions: Vec<Option<&'static str>>,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            case_name: "single-key",
+            keys: vec!["region"],
+            expected_ids: vec![Some(1), Some(2), Some(1)],
+            expected_regions: vec![Some("east"), Some("west"), None],
+        },
+        TestCase {
+            case_name: "multiple-keys",
+            keys: vec!["id", "t.region"],
+            expected_ids: vec![Some(1), Some(2), None, Some(3), Some(1)],
+            expected_regions: vec![Some("east"), Some("west"), Some("west"), Some("east"), None],
+        },
+        TestCase {
+            case_name: "all-columns",
+            keys: vec![],
+            expected_ids: vec![Some(1), Some(2), None, Some(3), Some(1)],
+            expected_regions: vec![Some("east"), Some("west"), Some("west"), Some("east"), None],
+        },
+        TestCase {
+            case_name: "expression-key",
+            keys: vec!["id % 2"],
+            expected_ids: vec![Some(1), Some(2), None],
+            expected_regions: vec![Some("east"), Some("west"), Some("west")],
+        },
+    ];
+
+    let aliases = vec![vec!["t".to_string()], vec!["t".to_string()]];
+    for test_case in test_cases {
+        println!("test case: {}", test_case.case_name);
+
+        let keys = test_case
+            .keys
+            .iter()
+            .map(|key| parse_expr(key))
+            .collect::<Result<Vec<Expr>>>()?;
+        let mut distinct = RecordDistinct::new(keys);
+
+        let mut ids: Vec<Option<i32>> = Vec::new();
+        let mut regions: Vec<Option<String>> = Vec::new();
+        for record in records()? {
+            let rec = distinct.distinct_record(&record, &aliases)?;
+            let id_col = rec.column(0).as_any().downcast_ref::<Int32Array>().unwrap();
+            let region_col = rec
+                .column(1)
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap();
+            ids.extend(id_col.iter());
+            regions.extend(
+                region_col
+                    .iter()
+                    .map(|item| item.map(|item| item.to_string())),
+            );
+        }
+
+        assert_eq!(ids, test_case.expected_ids);
+        assert_eq!(
+            regions,
+            test_case
+                .expected_regions
+                .iter()
+                .map(|item| item.map(|item| item.to_string()))
+                .collect::<Vec<Option<String>>>()
+        );
+        assert_eq!(distinct.num_keys(), ids.len());
+        assert!(distinct.memory_size_in_bytes() > 0);
+
+        // the output only has unique key combinations
+        let unique: HashSet<(Option<i32>, Option<String>)> =
+            ids.iter().cloned().zip(regions.iter().cloned()).collect();
+        assert_eq!(unique.len(), ids.len());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,314 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::{ArrayRef, AsArray, Float64Array, Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Int32Type, Schema};
+use sqlparser::ast::Expr;
+use sqlparser::dialect::GenericDialect;
+use sqlparser::parser::Parser;
+
+use super::compute_value::{ComputeValueError, ComputeValueOptions, NanPolicy};
+use super::record_filter::filter_record;
+
+fn parse_expr(sql: &str) -> Result<Expr> {
+    Ok(Parser::new(&GenericDialect {})
+        .try_with_sql(sql)?
+        .parse_expr()?)
+}
+
+#[test]
+fn test_filter_record() -> Result<()> {
+    struct TestCase {
+        sql: &'static str,
+        expected_ids: Vec<Option<i32>>,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            sql: "a > 3 and b = 'x'",
+            expected_ids: vec![Some(4)],
+        },
+        TestCase {
+            sql: "a > 3 or b = 'x'",
+            expected_ids: vec![Some(1), Some(4), Some(5)],
+        },
+        TestCase {
+            sql: "(a + 1) * 2 >= 10 and t.a < 5",
+            expected_ids: vec![Some(4)],
+        },
+        TestCase {
+            sql: "not a > 3",
+            expected_ids: vec![Some(1), Some(2)],
+        },
+        TestCase {
+            sql: "-a < -3 and not b = 'y'",
+            expected_ids: vec![Some(4)],
+        },
+        TestCase {
+            sql: "a = 100",
+            expected_ids: vec![],
+        },
+    ];
+
+    // the null values in row 3 make every expression null
+    // so the row must always be removed
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, true),
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Utf8, true),
+    ]));
+    let id: ArrayRef = Arc::new(Int32Array::from(vec![
+        Some(1),
+        Some(2),
+        Some(3),
+        Some(4),
+        Some(5),
+    ]));
+    let a: ArrayRef = Arc::new(Int32Array::from(vec![
+        Some(1),
+        Some(2),
+        None,
+        Some(4),
+        Some(5),
+    ]));
+    let b: ArrayRef = Arc::new(StringArray::from(vec![
+        Some("x"),
+        Some("y"),
+        None,
+        Some("x"),
+        Some("y"),
+    ]));
+    let rec = Arc::new(RecordBatch::try_new(schema, vec![id, a, b])?);
+    let table_aliases = vec![vec!["t".to_string()]; rec.num_columns()];
+
+    for test_case in test_cases {
+        let res = filter_record(
+            rec.clone(),
+            &parse_expr(test_case.sql)?,
+            &table_aliases,
+            &ComputeValueOptions::default(),
+            false,
+        )?;
+        let ids: Vec<Option<i32>> = res.column(0).as_primitive::<Int32Type>().iter().collect();
+        assert_eq!(ids, test_case.expected_ids, "sql: {}", test_case.sql);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_non_boolean_filter_is_an_error() -> Result<()> {
+    let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+    let a: ArrayRef = Arc::new(Int32Array::from(vec![Some(1), Some(2)]));
+    let rec = Arc::new(RecordBatch::try_new(schema, vec![a])?);
+    let table_aliases = vec![vec!["t".to_string()]];
+
+    let res = filter_record(
+        rec,
+        &parse_expr("a + 1")?,
+        &table_aliases,
+        &ComputeValueOptions::default(),
+        false,
+    );
+    assert!(res.is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_filter_record_on_nullability() -> Result<()> {
+    struct TestCase {
+        sql: &'static str,
+        expected_ids: Vec<Option<i32>>,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            sql: "email is null",
+            expected_ids: vec![Some(2), Some(4)],
+        },
+        TestCase {
+            sql: "email is not null",
+            expected_ids: vec![Some(1), Some(3)],
+        },
+        TestCase {
+            sql: "all_null is null",
+            expected_ids: vec![Some(1), Some(2), Some(3), Some(4)],
+        },
+        TestCase {
+            sql: "all_null is not null",
+            expected_ids: vec![],
+        },
+        TestCase {
+            sql: "id is null",
+            expected_ids: vec![],
+        },
+        TestCase {
+            sql: "id is not null",
+            expected_ids: vec![Some(1), Some(2), Some(3), Some(4)],
+        },
+        TestCase {
+            sql: "email is null and id > 2",
+            expected_ids: vec![Some(4)],
+        },
+    ];
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("email", DataType::Utf8, true),
+        Field::new("all_null", DataType::Utf8, true),
+    ]));
+    let id: ArrayRef = Arc::new(Int32Array::from(vec![1, 2, 3, 4]));
+    let email: ArrayRef = Arc::new(StringArray::from(vec![
+        Some("a@example.com"),
+        None,
+        Some(""),
+        None,
+    ]));
+    let all_null: ArrayRef = Arc::new(StringArray::from(vec![None::<&str>; 4]));
+    let rec = Arc::new(RecordBatch::try_new(schema, vec![id, email, all_null])?);
+    let table_aliases = vec![vec!["t".to_string()]; rec.num_columns()];
+
+    for test_case in test_cases {
+        let res = filter_record(
+            rec.clone(),
+            &parse_expr(test_case.sql)?,
+            &table_aliases,
+            &ComputeValueOptions::default(),
+            false,
+        )?;
+        let ids: Vec<Option<i32>> = res.column(0).as_primitive::<Int32Type>().iter().collect();
+        assert_eq!(ids, test_case.expected_ids, "sql: {}", test_case.sql);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_compute_value_errors_can_be_downcast() -> Result<()> {
+    let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+    let a: ArrayRef = Arc::new(Int32Array::from(vec![Some(1), Some(2)]));
+    let rec = Arc::new(RecordBatch::try_new(schema, vec![a])?);
+    let table_aliases = vec![vec!["t".to_string()]];
+
+    let err = filter_record(
+        rec,
+        &parse_expr("b > 1")?,
+        &table_aliases,
+        &ComputeValueOptions::default(),
+        false,
+    )
+    .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<ComputeValueError>(),
+        Some(ComputeValueError::ColumnNotFound(name)) if name == "b"
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_null_predicates_are_not_kept_by_default() -> Result<()> {
+    struct TestCase {
+        sql: &'static str,
+        keep_null: bool,
+        expected_ids: Vec<Option<i32>>,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            sql: "a > 1",
+            keep_null: false,
+            expected_ids: vec![Some(2)],
+        },
+        TestCase {
+            sql: "a > 1",
+            keep_null: true,
+            expected_ids: vec![Some(2), Some(3), Some(4)],
+        },
+        TestCase {
+            sql: "a = b",
+            keep_null: false,
+            expected_ids: vec![Some(1)],
+        },
+        TestCase {
+            sql: "a = b",
+            keep_null: true,
+            expected_ids: vec![Some(1), Some(3), Some(4)],
+        },
+        // null or true is true and null and false is false
+        TestCase {
+            sql: "a > 1 or b = 5",
+            keep_null: false,
+            expected_ids: vec![Some(2), Some(3)],
+        },
+        TestCase {
+            sql: "a > 1 and b = 5",
+            keep_null: true,
+            expected_ids: vec![Some(3), Some(4)],
+        },
+    ];
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Int32, true),
+    ]));
+    let id: ArrayRef = Arc::new(Int32Array::from(vec![1, 2, 3, 4]));
+    let a: ArrayRef = Arc::new(Int32Array::from(vec![Some(1), Some(2), None, None]));
+    let b: ArrayRef = Arc::new(Int32Array::from(vec![Some(1), Some(3), Some(5), None]));
+    let rec = Arc::new(RecordBatch::try_new(schema, vec![id, a, b])?);
+    let table_aliases = vec![vec!["t".to_string()]; rec.num_columns()];
+
+    for test_case in test_cases {
+        let expr = parse_expr(test_case.sql)?;
+        let res = filter_record(
+            rec.clone(),
+            &expr,
+            &table_aliases,
+            &ComputeValueOptions::default(),
+            test_case.keep_null,
+        )?;
+        let ids: Vec<Option<i32>> = res.column(0).as_primitive::<Int32Type>().iter().collect();
+        assert_eq!(
+            ids, test_case.expected_ids,
+            "sql: {}, keep_null: {}",
+            test_case.sql, test_case.keep_null
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_filter_record_follows_the_nan_policy() -> Result<()> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("a", DataType::Float64, false),
+    ]));
+    let id: ArrayRef = Arc::new(Int32Array::from(vec![1, 2]));
+    let a: ArrayRef = Arc::new(Float64Array::from(vec![1.0, f64::NAN]));
+    let rec = Arc::new(RecordBatch::try_new(schema, vec![id, a])?);
+    let table_aliases = vec![vec!["t".to_string()]; rec.num_columns()];
+
+    for (nan_policy, expected_ids) in [
+        (NanPolicy::TotalOrder, vec![Some(1), Some(2)]),
+        (NanPolicy::Ieee, vec![Some(1)]),
+    ] {
+        let options = ComputeValueOptions {
+            nan_policy,
+            ..ComputeValueOptions::default()
+        };
+        let res = filter_record(
+            rec.clone(),
+            &parse_expr("a = a")?,
+            &table_aliases,
+            &options,
+            false,
+        )?;
+        let ids: Vec<Option<i32>> = res.column(0).as_primitive::<Int32Type>().iter().collect();
+        assert_eq!(ids, expected_ids, "nan_policy: {:?}", nan_policy);
+    }
+
+    Ok(())
+}
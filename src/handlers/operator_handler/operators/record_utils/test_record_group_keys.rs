@@ -0,0 +1,176 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::{Array, ArrayRef, AsArray, Int32Array, StringArray, UInt32Array};
+use arrow::datatypes::{DataType, Int64Type};
+use arrow::row::OwnedRow;
+
+use super::record_accumulators::Accumulator;
+use super::record_group_keys::{group_key_partition, RecordGroupKeys};
+
+#[test]
+fn test_group_rows_by_two_columns_with_null_key() -> Result<()> {
+    let a: ArrayRef = Arc::new(Int32Array::from(vec![
+        Some(1),
+        Some(1),
+        None,
+        None,
+        Some(1),
+        Some(2),
+    ]));
+    let b: ArrayRef = Arc::new(StringArray::from(vec![
+        Some("x"),
+        Some("x"),
+        Some("y"),
+        Some("y"),
+        Some("z"),
+        Some("x"),
+    ]));
+
+    let group_keys = RecordGroupKeys::new(vec![DataType::Int32, DataType::Utf8])?;
+    let groups = group_keys.group_rows(&[a, b])?;
+
+    let group_idxs: Vec<Vec<u32>> = groups.iter().map(|(_, idxs)| idxs.clone()).collect();
+    assert_eq!(group_idxs, vec![vec![0, 1], vec![2, 3], vec![4], vec![5]]);
+
+    let key_cols = group_keys.keys_to_columns(groups.iter().map(|(key, _)| key.row()))?;
+    let key_a = key_cols[0].as_any().downcast_ref::<Int32Array>().unwrap();
+    let key_b = key_cols[1].as_any().downcast_ref::<StringArray>().unwrap();
+    assert_eq!(
+        key_a.iter().collect::<Vec<_>>(),
+        vec![Some(1), None, Some(1), Some(2)]
+    );
+    assert_eq!(
+        key_b.iter().collect::<Vec<_>>(),
+        vec![Some("x"), Some("y"), Some("z"), Some("x")]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_group_key_partition_is_stable_across_records() -> Result<()> {
+    let group_keys = RecordGroupKeys::new(vec![DataType::Int32, DataType::Utf8])?;
+
+    let rec_1_groups = group_keys.group_rows(&[
+        Arc::new(Int32Array::from(vec![Some(1), None])) as ArrayRef,
+        Arc::new(StringArray::from(vec![Some("x"), Some("y")])) as ArrayRef,
+    ])?;
+    let rec_2_groups = group_keys.group_rows(&[
+        Arc::new(Int32Array::from(vec![Some(7), None, Some(1)])) as ArrayRef,
+        Arc::new(StringArray::from(vec![Some("q"), Some("y"), Some("x")])) as ArrayRef,
+    ])?;
+
+    let num_partitions = 8;
+    assert_eq!(
+        group_key_partition(rec_1_groups[0].0.row(), num_partitions)?,
+        group_key_partition(rec_2_groups[2].0.row(), num_partitions)?,
+    );
+    assert_eq!(
+        group_key_partition(rec_1_groups[1].0.row(), num_partitions)?,
+        group_key_partition(rec_2_groups[1].0.row(), num_partitions)?,
+    );
+    assert!(group_key_partition(rec_1_groups[0].0.row(), 0).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_sorted_group_runs_match_hash_grouping() -> Result<()> {
+    // sorted on (a, b) and split into two records with the
+    // (2, "y") group spanning the record boundary
+    let records: Vec<(Vec<ArrayRef>, ArrayRef)> = vec![
+        (
+            vec![
+                Arc::new(Int32Array::from(vec![None, Some(1), Some(1), Some(2)])),
+                Arc::new(StringArray::from(vec![
+                    Some("x"),
+                    Some("x"),
+                    Some("z"),
+                    Some("y"),
+                ])),
+            ],
+            Arc::new(Int32Array::from(vec![Some(1), Some(2), None, Some(4)])),
+        ),
+        (
+            vec![
+                Arc::new(Int32Array::from(vec![Some(2), Some(2), Some(3)])),
+                Arc::new(StringArray::from(vec![Some("y"), Some("y"), None])),
+            ],
+            Arc::new(Int32Array::from(vec![Some(5), Some(6), Some(7)])),
+        ),
+    ];
+
+    let group_keys = RecordGroupKeys::new(vec![DataType::Int32, DataType::Utf8])?;
+
+    // streaming; only the current group is held between records
+    let mut streaming_results: Vec<(OwnedRow, Option<i64>)> = Vec::new();
+    let mut current: Option<(OwnedRow, Accumulator)> = None;
+    for (key_columns, values) in &records {
+        for (key, run) in group_keys.group_sorted_rows(key_columns)? {
+            let run_values = values.slice(run.start, run.len());
+            match &mut current {
+                Some((current_key, acc)) if *current_key == key => {
+                    acc.update(&run_values)?;
+                }
+                _ => {
+                    if let Some((done_key, acc)) = current.take() {
+                        streaming_results.push((done_key, sum_value(&acc)?));
+                    }
+                    let mut acc = Accumulator::new_sum(&DataType::Int32)?;
+                    acc.update(&run_values)?;
+                    current = Some((key, acc));
+                }
+            }
+        }
+    }
+    if let Some((done_key, acc)) = current.take() {
+        streaming_results.push((done_key, sum_value(&acc)?));
+    }
+
+    // hash aggregation over all of the input at once
+    let key_columns = (0..2)
+        .map(|idx| {
+            let cols: Vec<&dyn Array> = records
+                .iter()
+                .map(|(key_columns, _)| key_columns[idx].as_ref())
+                .collect();
+            arrow::compute::concat(&cols)
+        })
+        .collect::<Result<Vec<ArrayRef>, _>>()?;
+    let values = arrow::compute::concat(
+        &records
+            .iter()
+            .map(|(_, values)| values.as_ref())
+            .collect::<Vec<&dyn Array>>(),
+    )?;
+    let mut hash_results: Vec<(OwnedRow, Option<i64>)> = Vec::new();
+    for (key, idxs) in group_keys.group_rows(&key_columns)? {
+        let group_values = arrow::compute::take(&values, &UInt32Array::from(idxs), None)?;
+        let mut acc = Accumulator::new_sum(&DataType::Int32)?;
+        acc.update(&group_values)?;
+        hash_results.push((key, sum_value(&acc)?));
+    }
+
+    assert_eq!(streaming_results.len(), 5);
+    assert_eq!(streaming_results, hash_results);
+    assert_eq!(
+        streaming_results
+            .iter()
+            .map(|(_, sum)| *sum)
+            .collect::<Vec<_>>(),
+        vec![Some(1), Some(2), None, Some(15), Some(7)]
+    );
+
+    Ok(())
+}
+
+fn sum_value(acc: &Accumulator) -> Result<Option<i64>> {
+    let res = acc.evaluate()?;
+    let res = res.as_primitive::<Int64Type>();
+    Ok(if res.is_null(0) {
+        None
+    } else {
+        Some(res.value(0))
+    })
+}
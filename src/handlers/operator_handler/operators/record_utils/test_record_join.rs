@@ -0,0 +1,154 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::{Array, Int32Array, Int64Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use sqlparser::ast::Expr;
+use sqlparser::dialect::GenericDialect;
+use sqlparser::parser::Parser;
+
+use crate::planner::JoinType;
+
+use super::record_join::{JoinBuildSide, RecordJoinError};
+
+fn parse_expr(sql: &str) -> Result<Expr> {
+    Ok(Parser::new(&GenericDialect {})
+        .try_with_sql(sql)?
+        .parse_expr()?)
+}
+
+fn left_record() -> Result<RecordBatch> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, true),
+        Field::new("name", DataType::Utf8, true),
+    ]));
+    Ok(RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from(vec![Some(1), Some(2), Some(3), None])),
+            Arc::new(StringArray::from(vec!["a", "b", "c", "d"])),
+        ],
+    )?)
+}
+
+fn right_records() -> Result<Vec<RecordBatch>> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("size", DataType::Utf8, false),
+    ]));
+    Ok(vec![
+        RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 3])),
+                Arc::new(StringArray::from(vec!["small", "large"])),
+            ],
+        )?,
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![1, 4])),
+                Arc::new(StringArray::from(vec!["medium", "huge"])),
+            ],
+        )?,
+    ])
+}
+
+#[test]
+fn test_join_record() -> Result<()> {
+    struct TestCase {
+        case_name: &'static str,
+        join_type: JoinType,
+        expected_names: Vec<&'static str>,
+        expected_sizes: Vec<Option<&'static str>>,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            case_name: "inner-join",
+            join_type: JoinType::Inner,
+            expected_names: vec!["a", "a", "c"],
+            expected_sizes: vec![Some("small"), Some("medium"), Some("large")],
+        },
+        TestCase {
+            case_name: "left-join",
+            join_type: JoinType::Left,
+            expected_names: vec!["a", "a", "b", "c", "d"],
+            expected_sizes: vec![Some("small"), Some("medium"), None, Some("large"), None],
+        },
+    ];
+
+    let left_aliases = vec![vec!["l".to_string()], vec!["l".to_string()]];
+    let right_aliases = vec![vec!["r".to_string()], vec!["r".to_string()]];
+    let build_side =
+        JoinBuildSide::try_new(&right_records()?, right_aliases, &[parse_expr("r.id")?])?;
+    assert_eq!(build_side.num_rows(), 4);
+
+    for test_case in test_cases {
+        println!("test case: {}", test_case.case_name);
+
+        let (rec, aliases) = build_side.join_record(
+            &left_record()?,
+            &left_aliases,
+            &[parse_expr("l.id")?],
+            &test_case.join_type,
+        )?;
+
+        assert_eq!(rec.num_columns(), 4);
+        assert_eq!(
+            aliases,
+            vec![
+                vec!["l".to_string()],
+                vec!["l".to_string()],
+                vec!["r".to_string()],
+                vec!["r".to_string()]
+            ]
+        );
+        assert_eq!(
+            rec.schema().field(3).is_nullable(),
+            test_case.join_type == JoinType::Left
+        );
+
+        let names = rec
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        let sizes = rec
+            .column(3)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(
+            names.iter().map(|item| item.unwrap()).collect::<Vec<_>>(),
+            test_case.expected_names
+        );
+        assert_eq!(sizes.iter().collect::<Vec<_>>(), test_case.expected_sizes);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_join_on_mismatched_key_types_is_an_error() -> Result<()> {
+    let build_side = JoinBuildSide::try_new(
+        &right_records()?,
+        vec![vec!["r".to_string()], vec!["r".to_string()]],
+        &[parse_expr("r.id")?],
+    )?;
+
+    let err = build_side
+        .join_record(
+            &left_record()?,
+            &[vec!["l".to_string()], vec!["l".to_string()]],
+            &[parse_expr("l.name")?],
+            &JoinType::Inner,
+        )
+        .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<RecordJoinError>(),
+        Some(RecordJoinError::JoinKeyDataTypeMismatch(..))
+    ));
+
+    Ok(())
+}
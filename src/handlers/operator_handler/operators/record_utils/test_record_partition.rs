@@ -0,0 +1,136 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::{ArrayRef, Int64Array, RecordBatch, StringArray};
+
+use super::record_partition::{hash_partition_record, partition_record};
+
+#[test]
+fn test_partition_record() -> Result<()> {
+    let rec = RecordBatch::try_from_iter(vec![
+        (
+            "id",
+            Arc::new(Int64Array::from(vec![1, 2, 3, 4, 5])) as ArrayRef,
+        ),
+        (
+            "size",
+            Arc::new(StringArray::from(vec![
+                Some("small"),
+                None,
+                Some("large"),
+                Some("small"),
+                None,
+            ])) as ArrayRef,
+        ),
+        (
+            "year",
+            Arc::new(Int64Array::from(vec![2020, 2020, 2021, 2021, 2020])) as ArrayRef,
+        ),
+    ])?;
+    let filter_ids = |ids: Vec<i64>| -> Result<RecordBatch> {
+        let mask = arrow::array::BooleanArray::from(
+            (1..=5).map(|id| ids.contains(&id)).collect::<Vec<bool>>(),
+        );
+        Ok(arrow::compute::filter_record_batch(&rec, &mask)?)
+    };
+
+    struct TestCase {
+        case_name: String,
+        columns: Vec<String>,
+        expected_partitions: Vec<(Vec<Option<String>>, Vec<i64>)>,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            case_name: "no-columns".to_string(),
+            columns: vec![],
+            expected_partitions: vec![(vec![], vec![1, 2, 3, 4, 5])],
+        },
+        TestCase {
+            case_name: "nullable-column".to_string(),
+            columns: vec!["size".to_string()],
+            expected_partitions: vec![
+                (vec![Some("small".to_string())], vec![1, 4]),
+                (vec![None], vec![2, 5]),
+                (vec![Some("large".to_string())], vec![3]),
+            ],
+        },
+        TestCase {
+            case_name: "two-columns".to_string(),
+            columns: vec!["year".to_string(), "size".to_string()],
+            expected_partitions: vec![
+                (
+                    vec![Some("2020".to_string()), Some("small".to_string())],
+                    vec![1],
+                ),
+                (vec![Some("2020".to_string()), None], vec![2, 5]),
+                (
+                    vec![Some("2021".to_string()), Some("large".to_string())],
+                    vec![3],
+                ),
+                (
+                    vec![Some("2021".to_string()), Some("small".to_string())],
+                    vec![4],
+                ),
+            ],
+        },
+    ];
+
+    for test_case in test_cases {
+        println!("test case: {}", test_case.case_name);
+
+        let partitions = partition_record(&rec, &test_case.columns)?;
+        let expected_partitions = test_case
+            .expected_partitions
+            .into_iter()
+            .map(|(values, ids)| Ok((values, filter_ids(ids)?)))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(partitions, expected_partitions);
+    }
+
+    assert!(partition_record(&rec, &vec!["missing".to_string()]).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_hash_partition_record() -> Result<()> {
+    let ids: Vec<i64> = (0..50).map(|id| id % 10).collect();
+    let rec = RecordBatch::try_from_iter(vec![
+        ("id", Arc::new(Int64Array::from(ids.clone())) as ArrayRef),
+        (
+            "row",
+            Arc::new(Int64Array::from((0..50).collect::<Vec<i64>>())) as ArrayRef,
+        ),
+    ])?;
+
+    let partitions = hash_partition_record(&rec, &["id".to_string()], 4)?;
+    assert_eq!(partitions.len(), 4);
+    assert_eq!(
+        partitions.iter().map(|item| item.num_rows()).sum::<usize>(),
+        50
+    );
+
+    // the same key is in a single partition and partitioning again
+    // puts it in the same one
+    let again = hash_partition_record(&rec, &["id".to_string()], 4)?;
+    let mut key_partitions: Vec<Option<usize>> = vec![None; 10];
+    for (partition_idx, (partition, partition_again)) in
+        partitions.iter().zip(again.iter()).enumerate()
+    {
+        assert_eq!(partition, partition_again);
+        let keys = partition
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .expect("id column should be an Int64Array");
+        for key in keys.values().iter() {
+            let key_partition = key_partitions[*key as usize].get_or_insert(partition_idx);
+            assert_eq!(*key_partition, partition_idx);
+        }
+    }
+
+    assert!(hash_partition_record(&rec, &["missing".to_string()], 4).is_err());
+
+    Ok(())
+}
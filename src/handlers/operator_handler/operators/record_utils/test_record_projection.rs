@@ -0,0 +1,142 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::{ArrayRef, BooleanArray, Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use sqlparser::ast::SelectItem;
+use sqlparser::dialect::GenericDialect;
+use sqlparser::parser::Parser;
+
+use super::record_projection::project_record;
+
+fn parse_select_items(sql: &str) -> Result<Vec<SelectItem>> {
+    Ok(Parser::new(&GenericDialect {})
+        .try_with_sql(sql)?
+        .parse_projection()?)
+}
+
+#[test]
+fn test_project_record() -> Result<()> {
+    struct TestCase {
+        sql: &'static str,
+        expected_fields: Vec<Field>,
+        expected_columns: Vec<ArrayRef>,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            sql: "*",
+            expected_fields: vec![
+                Field::new("id", DataType::Int32, false),
+                Field::new("amount", DataType::Int32, true),
+                Field::new("name", DataType::Utf8, true),
+            ],
+            expected_columns: vec![
+                Arc::new(Int32Array::from(vec![1, 2, 3])),
+                Arc::new(Int32Array::from(vec![Some(5), Some(500), None])),
+                Arc::new(StringArray::from(vec![Some("Ada"), Some(""), None])),
+            ],
+        },
+        TestCase {
+            sql: "id, case when amount > 100 then 'big' else 'small' end as size",
+            expected_fields: vec![
+                Field::new("id", DataType::Int32, true),
+                Field::new("size", DataType::Utf8, true),
+            ],
+            expected_columns: vec![
+                Arc::new(Int32Array::from(vec![1, 2, 3])),
+                Arc::new(StringArray::from(vec!["small", "big", "small"])),
+            ],
+        },
+        TestCase {
+            sql: "upper(name) as name, length(name)",
+            expected_fields: vec![
+                Field::new("name", DataType::Utf8, true),
+                Field::new("length(name)", DataType::Int32, true),
+            ],
+            expected_columns: vec![
+                Arc::new(StringArray::from(vec![Some("ADA"), Some(""), None])),
+                Arc::new(Int32Array::from(vec![Some(3), Some(0), None])),
+            ],
+        },
+        TestCase {
+            sql: "-amount as neg_amount, not amount > 100",
+            expected_fields: vec![
+                Field::new("neg_amount", DataType::Int32, true),
+                Field::new("NOT amount > 100", DataType::Boolean, true),
+            ],
+            expected_columns: vec![
+                Arc::new(Int32Array::from(vec![Some(-5), Some(-500), None])),
+                Arc::new(BooleanArray::from(vec![Some(true), Some(false), None])),
+            ],
+        },
+        TestCase {
+            sql: "substring(name from 1 for 2) as short, concat(name, '!') as loud",
+            expected_fields: vec![
+                Field::new("short", DataType::Utf8, true),
+                Field::new("loud", DataType::Utf8, true),
+            ],
+            expected_columns: vec![
+                Arc::new(StringArray::from(vec![Some("Ad"), Some(""), None])),
+                Arc::new(StringArray::from(vec![Some("Ada!"), Some("!"), Some("!")])),
+            ],
+        },
+    ];
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("amount", DataType::Int32, true),
+        Field::new("name", DataType::Utf8, true),
+    ]));
+    let id: ArrayRef = Arc::new(Int32Array::from(vec![1, 2, 3]));
+    let amount: ArrayRef = Arc::new(Int32Array::from(vec![Some(5), Some(500), None]));
+    let name: ArrayRef = Arc::new(StringArray::from(vec![Some("Ada"), Some(""), None]));
+    let rec = Arc::new(RecordBatch::try_new(schema, vec![id, amount, name])?);
+    let table_aliases = vec![vec!["t".to_string()]; rec.num_columns()];
+
+    for test_case in test_cases {
+        let res = project_record(
+            &parse_select_items(test_case.sql)?,
+            rec.clone(),
+            &table_aliases,
+        )?;
+        let fields: Vec<Field> = res
+            .schema()
+            .fields()
+            .iter()
+            .map(|field| (**field).clone())
+            .collect();
+        assert_eq!(fields, test_case.expected_fields, "sql: {}", test_case.sql);
+        assert_eq!(
+            res.columns(),
+            &test_case.expected_columns[..],
+            "sql: {}",
+            test_case.sql
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_qualified_and_unqualified_columns_bind_to_the_same_column() -> Result<()> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, true),
+    ]));
+    let record = Arc::new(RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2])),
+            Arc::new(StringArray::from(vec![Some("Ada"), None])),
+        ],
+    )?);
+    let table_aliases = vec![vec!["t".to_string()], vec!["t".to_string()]];
+
+    let rec = project_record(&parse_select_items("t.id, id")?, record, &table_aliases)?;
+    assert_eq!(rec.schema().field(0).name(), "id");
+    assert_eq!(rec.schema().field(1).name(), "id");
+    assert_eq!(rec.column(0), rec.column(1));
+
+    Ok(())
+}
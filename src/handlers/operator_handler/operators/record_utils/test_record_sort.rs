@@ -0,0 +1,146 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::{AsArray, Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Int32Type, Schema};
+use sqlparser::dialect::GenericDialect;
+use sqlparser::parser::Parser;
+
+use crate::planner::SortExpr;
+
+use super::record_sort::{RecordSorter, SortedRunMerger};
+
+fn parse_sort_exprs(sql: &str) -> Result<Vec<SortExpr>> {
+    let mut parser = Parser::new(&GenericDialect {}).try_with_sql(sql)?;
+    let exprs = parser.parse_comma_separated(|parser| parser.parse_order_by_expr())?;
+    Ok(exprs
+        .into_iter()
+        .map(|order_by_expr| {
+            let asc = order_by_expr.asc.unwrap_or(true);
+            SortExpr {
+                expr: order_by_expr.expr,
+                asc,
+                nulls_first: order_by_expr.nulls_first.unwrap_or(!asc),
+            }
+        })
+        .collect())
+}
+
+fn build_record(keys: Vec<Option<i32>>, ids: Vec<&str>) -> Result<RecordBatch> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("key", DataType::Int32, true),
+        Field::new("id", DataType::Utf8, false),
+    ]));
+    Ok(RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from(keys)),
+            Arc::new(StringArray::from(ids)),
+        ],
+    )?)
+}
+
+fn ids(rec: &RecordBatch) -> Vec<String> {
+    rec.column(1)
+        .as_string::<i32>()
+        .iter()
+        .map(|item| item.unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn test_sort_record() -> Result<()> {
+    struct TestCase {
+        order_by: &'static str,
+        expected_ids: Vec<&'static str>,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            order_by: "key",
+            expected_ids: vec!["b", "d", "a", "e", "c"],
+        },
+        TestCase {
+            order_by: "key desc",
+            expected_ids: vec!["c", "a", "e", "b", "d"],
+        },
+        TestCase {
+            order_by: "key nulls first",
+            expected_ids: vec!["c", "b", "d", "a", "e"],
+        },
+        TestCase {
+            order_by: "key desc nulls last, id desc",
+            expected_ids: vec!["e", "a", "d", "b", "c"],
+        },
+    ];
+
+    let rec = build_record(
+        vec![Some(2), Some(1), None, Some(1), Some(2)],
+        vec!["a", "b", "c", "d", "e"],
+    )?;
+    for test_case in test_cases {
+        println!("order by: {}", test_case.order_by);
+        let mut sorter = RecordSorter::new(parse_sort_exprs(test_case.order_by)?);
+        let sorted = sorter.sort_record(&rec, &[Vec::new(), Vec::new()])?;
+        assert_eq!(ids(&sorted), test_case.expected_ids);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_merge_sorted_runs_matches_a_single_sort() -> Result<()> {
+    let exprs = parse_sort_exprs("key desc")?;
+    let table_aliases = vec![Vec::new(), Vec::new()];
+
+    // each run is sorted and split across records; nulls
+    // come first since the key is descending
+    let runs = vec![
+        vec![
+            build_record(vec![Some(9), Some(5)], vec!["r0-a", "r0-b"])?,
+            build_record(vec![Some(5), Some(1)], vec!["r0-c", "r0-d"])?,
+        ],
+        vec![
+            build_record(vec![None, Some(7)], vec!["r1-a", "r1-b"])?,
+            build_record(vec![Some(5)], vec!["r1-c"])?,
+        ],
+        vec![],
+    ];
+
+    let mut merger = SortedRunMerger::new(exprs.clone(), table_aliases.clone(), runs.len());
+    let mut run_positions = vec![0usize; runs.len()];
+    let mut merged: Vec<RecordBatch> = Vec::new();
+    loop {
+        while let Some(run_idx) = merger.next_run_to_fill() {
+            match runs[run_idx].get(run_positions[run_idx]) {
+                Some(rec) => merger.push_record(run_idx, rec.clone())?,
+                None => merger.finish_run(run_idx)?,
+            }
+            run_positions[run_idx] += 1;
+        }
+        match merger.next_record(3)? {
+            Some(rec) => {
+                assert!(rec.num_rows() <= 3);
+                merged.push(rec);
+            }
+            None => break,
+        }
+    }
+
+    let all_records: Vec<RecordBatch> = runs.into_iter().flatten().collect();
+    let all = arrow::compute::concat_batches(&all_records[0].schema(), &all_records)?;
+    let sorted = RecordSorter::new(exprs).sort_record(&all, &table_aliases)?;
+
+    let merged = arrow::compute::concat_batches(&merged[0].schema(), &merged)?;
+    assert_eq!(
+        ids(&merged),
+        vec!["r1-a", "r0-a", "r1-b", "r0-b", "r0-c", "r1-c", "r0-d"]
+    );
+    assert_eq!(ids(&merged), ids(&sorted));
+    assert_eq!(
+        merged.column(0).as_primitive::<Int32Type>(),
+        sorted.column(0).as_primitive::<Int32Type>()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::{Array, Int32Array, Int64Array, ListArray, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Int32Type, Schema};
+
+use super::record_union::{RecordUnion, RecordUnionError};
+
+fn build_record(id: Arc<dyn Array>, id_name: &str, sizes: Vec<&str>) -> Result<RecordBatch> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new(id_name, id.data_type().clone(), false),
+        Field::new("size", DataType::Utf8, false),
+    ]));
+    Ok(RecordBatch::try_new(
+        schema,
+        vec![id, Arc::new(StringArray::from(sizes))],
+    )?)
+}
+
+#[test]
+fn test_align_records_to_the_first_record() -> Result<()> {
+    let mut union = RecordUnion::new();
+
+    let left = build_record(Arc::new(Int64Array::from(vec![1, 2])), "id", vec!["a", "b"])?;
+    let rec = union.align_record(&left)?;
+    assert_eq!(rec.schema().field(0).name(), "id");
+    assert!(rec.schema().field(0).is_nullable());
+    assert_eq!(rec.column(0), left.column(0));
+
+    // the right side is renamed and cast to the left side's schema
+    let right = build_record(Arc::new(Int32Array::from(vec![3])), "other_id", vec!["c"])?;
+    let rec = union.align_record(&right)?;
+    assert_eq!(rec.schema(), union.align_record(&left)?.schema());
+    assert_eq!(
+        rec.column(0).as_any().downcast_ref::<Int64Array>().unwrap(),
+        &Int64Array::from(vec![3])
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_align_mismatched_records_is_an_error() -> Result<()> {
+    let mut union = RecordUnion::new();
+    union.align_record(&build_record(
+        Arc::new(Int64Array::from(vec![1])),
+        "id",
+        vec!["a"],
+    )?)?;
+
+    let single_column = RecordBatch::try_new(
+        Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)])),
+        vec![Arc::new(Int64Array::from(vec![1]))],
+    )?;
+    let err = union.align_record(&single_column).unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<RecordUnionError>(),
+        Some(RecordUnionError::ColumnCountMismatch(2, 1))
+    ));
+
+    let list_column =
+        ListArray::from_iter_primitive::<Int32Type, _, _>(vec![Some(vec![Some(1), Some(2)])]);
+    let err = union
+        .align_record(&build_record(Arc::new(list_column), "id", vec!["a"])?)
+        .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<RecordUnionError>(),
+        Some(RecordUnionError::ColumnTypeMismatch(0, ..))
+    ));
+
+    Ok(())
+}
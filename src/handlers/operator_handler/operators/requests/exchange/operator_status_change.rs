@@ -2,12 +2,13 @@ use std::sync::Arc;
 
 use anyhow::Result;
 use thiserror::Error;
-use tracing::{debug, error};
+use tracing::debug;
 
 use crate::handlers::{
     message_handler::{
         messages::{
             self,
+            common::ErrorCode,
             message::{Message, MessageName},
         },
         MessageRegistry, Pipe, Request,
@@ -17,8 +18,8 @@ use crate::handlers::{
 
 #[derive(Debug, Error)]
 pub enum OperatorStatusChangeRequestError {
-    #[error("received error response: {0}")]
-    ReceivedErrorResponse(String),
+    #[error("received error response: {0}: {1}")]
+    ReceivedErrorResponse(ErrorCode, String),
 }
 
 pub struct OperatorStatusChangeRequest<'a> {
@@ -55,7 +56,7 @@ impl<'a> OperatorStatusChangeRequest<'a> {
         let msg = messages::exchange::OperatorStatusChange::Complete {
             operator_id: self.operator_id.clone(),
         };
-        retry::retry_request!(self.operator_status_change(&msg), 3, 10)
+        self.operator_status_change(&msg).await
     }
 
     async fn operator_status_change(
@@ -71,6 +72,7 @@ impl<'a> OperatorStatusChangeRequest<'a> {
                 msg,
                 expect_response_msg_name: MessageName::CommonGenericResponse,
                 timeout: chrono::Duration::seconds(3),
+                retry_policy: retry::REQUEST_RETRY_POLICY,
             })
             .await?;
 
@@ -78,9 +80,10 @@ impl<'a> OperatorStatusChangeRequest<'a> {
             self.msg_reg.try_cast_msg(&resp_msg)?;
         match resp_cast_msg {
             messages::common::GenericResponse::Ok => Ok(()),
-            messages::common::GenericResponse::Error(err) => {
-                Err(OperatorStatusChangeRequestError::ReceivedErrorResponse(err.clone()).into())
-            }
+            messages::common::GenericResponse::Err { code, message } => Err(
+                OperatorStatusChangeRequestError::ReceivedErrorResponse(*code, message.clone())
+                    .into(),
+            ),
         }
     }
 }
@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use anyhow::Result;
 use thiserror::Error;
-use tracing::{debug, error};
+use tracing::debug;
 
 use crate::handlers::message_handler::messages;
 use crate::handlers::message_handler::messages::message::{Message, MessageName};
@@ -61,7 +61,7 @@ impl<'a> GetNextRecordRequest<'a> {
     }
 
     async fn process_request(&mut self) -> Result<GetNextRecordResponse> {
-        retry::retry_request!(self.get_next_record(), 3, 10)
+        self.get_next_record().await
     }
 
     async fn get_next_record(&mut self) -> Result<GetNextRecordResponse> {
@@ -80,6 +80,7 @@ impl<'a> GetNextRecordRequest<'a> {
                 msg: get_next_msg,
                 expect_response_msg_name: MessageName::ExchangeRequests,
                 timeout: chrono::Duration::seconds(10),
+                retry_policy: retry::REQUEST_RETRY_POLICY,
             })
             .await?;
 
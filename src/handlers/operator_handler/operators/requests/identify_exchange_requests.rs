@@ -1,4 +1,5 @@
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Result;
 use thiserror::Error;
@@ -6,8 +7,10 @@ use tracing::debug;
 
 use crate::handlers::message_handler::messages;
 use crate::handlers::message_handler::messages::message::{Message, MessageName};
-use crate::handlers::message_handler::{MessageRegistry, Pipe, Request};
-use crate::handlers::operator_handler::operator_handler_state::OperatorInstanceConfig;
+use crate::handlers::message_handler::{MessageRegistry, Pipe, Request, RetryPolicy};
+use crate::handlers::operator_handler::operator_handler_state::{
+    OperatorInstanceConfig, DEFAULT_IDENTIFY_EXCHANGE_MAX_ATTEMPTS,
+};
 
 #[derive(Debug, Error)]
 pub enum IdentifyExchangeRequestError {
@@ -29,6 +32,16 @@ pub enum IdentifyExchangeRequestError {
     OperatorInstanceIdCouldNotBeDetermined,
 }
 
+// the exchange may not have been assigned to a worker yet when the
+// producer starts so the query handler is asked again; the max attempts
+// come from the operator instance config
+const IDENTIFY_EXCHANGE_RETRY_POLICY: RetryPolicy = RetryPolicy {
+    max_attempts: DEFAULT_IDENTIFY_EXCHANGE_MAX_ATTEMPTS,
+    base_backoff: Duration::from_secs(1),
+    max_backoff: Duration::from_secs(5),
+    jitter: 0.2,
+};
+
 pub struct IdentifyExchangeResponse {
     pub exchange_operator_instance_id: u128,
     pub exchange_worker_id: u128,
@@ -39,11 +52,14 @@ pub struct IdentifyExchangeRequest<'a> {
     exchange_worker_id: Option<u128>,
     exchange_id: String,
     query_id: u128,
+    retry_policy: RetryPolicy,
     pipe: &'a mut Pipe,
     msg_reg: Arc<MessageRegistry>,
 }
 
 impl<'a> IdentifyExchangeRequest<'a> {
+    // Finds the instance and worker of the exchange the operator sends
+    // its records to.
     pub async fn request_outbound_exchange(
         op_in_config: &OperatorInstanceConfig,
         pipe: &'a mut Pipe,
@@ -68,12 +84,15 @@ impl<'a> IdentifyExchangeRequest<'a> {
         Self::request(
             op_in_config.query_id.clone(),
             exchange_id,
+            op_in_config.identify_exchange_max_attempts,
             pipe,
             msg_reg.clone(),
         )
         .await
     }
 
+    // Finds the instance and worker of each exchange the operator reads
+    // its records from, in the order of the inbound exchange ids.
     pub async fn request_inbound_exchanges(
         op_in_config: &OperatorInstanceConfig,
         pipe: &'a mut Pipe,
@@ -102,6 +121,7 @@ impl<'a> IdentifyExchangeRequest<'a> {
                 Self::request(
                     op_in_config.query_id.clone(),
                     exchange_id,
+                    op_in_config.identify_exchange_max_attempts,
                     pipe,
                     msg_reg.clone(),
                 )
@@ -115,6 +135,7 @@ impl<'a> IdentifyExchangeRequest<'a> {
     async fn request(
         query_id: u128,
         exchange_id: String,
+        max_attempts: u32,
         pipe: &mut Pipe,
         msg_reg: Arc<MessageRegistry>,
     ) -> Result<IdentifyExchangeResponse> {
@@ -125,6 +146,10 @@ impl<'a> IdentifyExchangeRequest<'a> {
             exchange_worker_id: None,
             exchange_id,
             query_id,
+            retry_policy: RetryPolicy {
+                max_attempts,
+                ..IDENTIFY_EXCHANGE_RETRY_POLICY
+            },
             pipe,
             msg_reg,
         };
@@ -144,38 +169,16 @@ impl<'a> IdentifyExchangeRequest<'a> {
     }
 
     async fn identify_exchange(&mut self) -> Result<()> {
-        self.exchange_operator_instance_id = Some(
-            self.get_exchange_operator_instance_id_with_retry(10)
-                .await?,
+        self.exchange_operator_instance_id =
+            Some(self.get_exchange_operator_instance_id_with_retry().await?);
+        self.exchange_worker_id = Some(
+            self.get_exchange_worker_id()
+                .await
+                .map_err(|err| err.context("failed to get the exchange operator worker id"))?,
         );
-        self.exchange_worker_id = Some(self.get_exchange_worker_id_with_retry(10).await?);
         Ok(())
     }
 
-    async fn get_exchange_worker_id_with_retry(&mut self, num_retries: u8) -> Result<u128> {
-        let mut last_err: Option<anyhow::Error> = None;
-        for retry_idx in 0..(num_retries + 1) {
-            match self.get_exchange_worker_id().await {
-                Ok(val) => {
-                    return Ok(val);
-                }
-                Err(err) => {
-                    last_err = Some(err);
-
-                    tokio::time::sleep(std::time::Duration::from_secs(std::cmp::min(
-                        retry_idx as u64 + 1,
-                        5,
-                    )))
-                    .await;
-                    continue;
-                }
-            }
-        }
-        return Err(last_err
-            .unwrap()
-            .context("failed to get the exchange operator worker id"));
-    }
-
     async fn get_exchange_worker_id(&mut self) -> Result<u128> {
         let mut msg = Message::new(Box::new(messages::common::Ping::Ping));
         if let Some(exchange_operator_instance_id) = self.exchange_operator_instance_id {
@@ -190,6 +193,7 @@ impl<'a> IdentifyExchangeRequest<'a> {
                 msg,
                 expect_response_msg_name: MessageName::Ping,
                 timeout: chrono::Duration::seconds(10),
+                retry_policy: RetryPolicy::default(),
             })
             .await?;
 
@@ -208,12 +212,13 @@ impl<'a> IdentifyExchangeRequest<'a> {
         }
     }
 
-    async fn get_exchange_operator_instance_id_with_retry(
-        &mut self,
-        num_retries: u8,
-    ) -> Result<u128> {
+    // The query handler's response is retried when it doesn't have an
+    // instance of the exchange yet so the request isn't retried by the pipe.
+    // Only this loop uses the configured max attempts.
+    async fn get_exchange_operator_instance_id_with_retry(&mut self) -> Result<u128> {
+        let max_attempts = self.retry_policy.max_attempts;
         let mut last_err: Option<anyhow::Error> = None;
-        for retry_idx in 0..(num_retries + 1) {
+        for attempt in 0..max_attempts {
             let res = self.get_exchange_operator_instance_id().await;
             match res {
                 Ok(Some(val)) => {
@@ -225,11 +230,9 @@ impl<'a> IdentifyExchangeRequest<'a> {
                         query_id = self.query_id.clone(),
                         "query handler didn't have an operator instance id for exchange operator; retrying after delay",
                     );
-                    tokio::time::sleep(std::time::Duration::from_secs(std::cmp::min(
-                        retry_idx as u64 + 1,
-                        5,
-                    )))
-                    .await;
+                    if attempt + 1 < max_attempts {
+                        tokio::time::sleep(self.retry_policy.backoff(attempt)).await;
+                    }
                     continue;
                 }
                 Err(err) => {
@@ -238,14 +241,10 @@ impl<'a> IdentifyExchangeRequest<'a> {
                         query_id = self.query_id.clone(),
                         "query handler request returned an error; retrying after delay"
                     );
-                    if retry_idx == num_retries {
+                    if attempt + 1 == max_attempts {
                         last_err = Some(err);
                     } else {
-                        tokio::time::sleep(std::time::Duration::from_secs(std::cmp::min(
-                            retry_idx as u64 + 1,
-                            5,
-                        )))
-                        .await;
+                        tokio::time::sleep(self.retry_policy.backoff(attempt)).await;
                         continue;
                     }
                 }
@@ -273,6 +272,7 @@ impl<'a> IdentifyExchangeRequest<'a> {
                 msg: list_msg,
                 expect_response_msg_name: MessageName::QueryHandlerRequests,
                 timeout: chrono::Duration::seconds(10),
+                retry_policy: RetryPolicy::default(),
             })
             .await?;
 
@@ -4,10 +4,15 @@ mod identify_exchange_requests;
 pub mod operator;
 mod operator_completed_record_processing_request;
 pub mod query;
+mod record_processing_heartbeat;
 pub mod retry;
 mod send_record_request;
 
+#[cfg(test)]
+mod test_identify_exchange_requests;
+
 pub use get_next_record_request::{GetNextRecordRequest, GetNextRecordResponse};
 pub use identify_exchange_requests::IdentifyExchangeRequest;
 pub use operator_completed_record_processing_request::OperatorCompletedRecordProcessingRequest;
+pub use record_processing_heartbeat::RecordProcessingHeartbeat;
 pub use send_record_request::SendRecordRequest;
@@ -2,12 +2,13 @@ use std::sync::Arc;
 
 use anyhow::Result;
 use thiserror::Error;
-use tracing::{debug, error};
+use tracing::debug;
 
 use crate::handlers::{
     message_handler::{
         messages::{
             self,
+            common::ErrorCode,
             message::{Message, MessageName},
         },
         MessageRegistry, Pipe, Request,
@@ -17,8 +18,8 @@ use crate::handlers::{
 
 #[derive(Debug, Error)]
 pub enum OperatorInstanceStatusChangeRequestError {
-    #[error("received error response: {0}")]
-    ReceivedErrorResponse(String),
+    #[error("received error response: {0}: {1}")]
+    ReceivedErrorResponse(ErrorCode, String),
 }
 
 pub struct OperatorInstanceStatusChangeRequest<'a> {
@@ -39,22 +40,26 @@ impl<'a> OperatorInstanceStatusChangeRequest<'a> {
 
     pub async fn errored_request(
         err: String,
+        retryable: bool,
         pipe: &'a mut Pipe,
         msg_reg: Arc<MessageRegistry>,
     ) -> Result<()> {
         let mut req = OperatorInstanceStatusChangeRequest { pipe, msg_reg };
-        req.inner_errored_request(err).await?;
+        req.inner_errored_request(err, retryable).await?;
         Ok(())
     }
 
     async fn inner_completed_request(&mut self) -> Result<()> {
         let msg = messages::operator::OperatorInstanceStatusChange::Complete;
-        retry::retry_request!(self.operator_instance_status_change(&msg), 3, 10)
+        self.operator_instance_status_change(&msg).await
     }
 
-    async fn inner_errored_request(&mut self, err: String) -> Result<()> {
-        let msg = messages::operator::OperatorInstanceStatusChange::Error(err);
-        retry::retry_request!(self.operator_instance_status_change(&msg), 3, 10)
+    async fn inner_errored_request(&mut self, err: String, retryable: bool) -> Result<()> {
+        let msg = messages::operator::OperatorInstanceStatusChange::Error {
+            error: err,
+            retryable,
+        };
+        self.operator_instance_status_change(&msg).await
     }
 
     async fn operator_instance_status_change(
@@ -69,6 +74,7 @@ impl<'a> OperatorInstanceStatusChangeRequest<'a> {
                 msg,
                 expect_response_msg_name: MessageName::CommonGenericResponse,
                 timeout: chrono::Duration::seconds(3),
+                retry_policy: retry::REQUEST_RETRY_POLICY,
             })
             .await?;
 
@@ -76,8 +82,12 @@ impl<'a> OperatorInstanceStatusChangeRequest<'a> {
             self.msg_reg.try_cast_msg(&resp_msg)?;
         match resp_cast_msg {
             messages::common::GenericResponse::Ok => Ok(()),
-            messages::common::GenericResponse::Error(err) => Err(
-                OperatorInstanceStatusChangeRequestError::ReceivedErrorResponse(err.clone()).into(),
+            messages::common::GenericResponse::Err { code, message } => Err(
+                OperatorInstanceStatusChangeRequestError::ReceivedErrorResponse(
+                    *code,
+                    message.clone(),
+                )
+                .into(),
             ),
         }
     }
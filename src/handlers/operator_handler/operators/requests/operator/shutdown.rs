@@ -2,16 +2,16 @@ use std::sync::Arc;
 
 use anyhow::Result;
 use thiserror::Error;
-use tracing::error;
 
+use crate::handlers::message_handler::messages::common::ErrorCode;
 use crate::handlers::message_handler::messages::message::{Message, MessageName};
 use crate::handlers::message_handler::{messages, MessageRegistry, Pipe, Request};
 use crate::handlers::operator_handler::operators::requests::retry;
 
 #[derive(Debug, Error)]
 pub enum ShutdownRequestError {
-    #[error("received error response: {0}")]
-    ReceivedErrorResponse(String),
+    #[error("received error response: {0}: {1}")]
+    ReceivedErrorResponse(ErrorCode, String),
 }
 
 pub struct ShutdownRequest<'a> {
@@ -38,8 +38,7 @@ impl<'a> ShutdownRequest<'a> {
     }
 
     async fn inner_shutdown_request(&mut self, msg: messages::operator::Shutdown) -> Result<()> {
-        let msg = &msg;
-        retry::retry_request!(self.shutdown_request(msg), 3, 10)?
+        self.shutdown_request(&msg).await
     }
 
     async fn shutdown_request(&mut self, msg: &messages::operator::Shutdown) -> Result<()> {
@@ -51,6 +50,7 @@ impl<'a> ShutdownRequest<'a> {
                 msg,
                 expect_response_msg_name: MessageName::CommonGenericResponse,
                 timeout: chrono::Duration::seconds(3),
+                retry_policy: retry::REQUEST_RETRY_POLICY,
             })
             .await?;
 
@@ -58,8 +58,8 @@ impl<'a> ShutdownRequest<'a> {
             self.msg_reg.try_cast_msg(&resp_msg)?;
         match resp_msg_cast {
             messages::common::GenericResponse::Ok => Ok(()),
-            messages::common::GenericResponse::Error(err) => {
-                Err(ShutdownRequestError::ReceivedErrorResponse(err.clone()).into())
+            messages::common::GenericResponse::Err { code, message } => {
+                Err(ShutdownRequestError::ReceivedErrorResponse(*code, message.clone()).into())
             }
         }
     }
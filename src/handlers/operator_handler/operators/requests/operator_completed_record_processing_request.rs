@@ -1,7 +1,7 @@
 use anyhow::Result;
 use std::sync::Arc;
 use thiserror::Error;
-use tracing::{debug, error};
+use tracing::debug;
 
 use crate::handlers::message_handler::messages;
 use crate::handlers::message_handler::messages::message::{Message, MessageName};
@@ -54,7 +54,7 @@ impl<'a> OperatorCompletedRecordProcessingRequest<'a> {
     }
 
     async fn inner_request(&mut self) -> Result<()> {
-        retry::retry_request!(self.operator_completed_record_processing(), 3, 10)
+        self.operator_completed_record_processing().await
     }
 
     async fn operator_completed_record_processing(&mut self) -> Result<()> {
@@ -73,6 +73,7 @@ impl<'a> OperatorCompletedRecordProcessingRequest<'a> {
                 msg,
                 expect_response_msg_name: MessageName::ExchangeRequests,
                 timeout: chrono::Duration::seconds(10),
+                retry_policy: retry::REQUEST_RETRY_POLICY,
             })
             .await?;
 
@@ -1,3 +1,7 @@
+mod operator_instance_metrics;
 mod operator_instance_status_change;
+mod stream_query_results;
 
+pub use operator_instance_metrics::OperatorInstanceMetricsRequest;
 pub use operator_instance_status_change::OperatorInstanceStatusChangeRequest;
+pub use stream_query_results::StreamQueryResultsRequest;
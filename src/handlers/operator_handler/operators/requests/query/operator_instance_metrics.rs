@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use thiserror::Error;
+use tracing::debug;
+
+use crate::handlers::{
+    message_handler::{
+        messages::{
+            self,
+            common::ErrorCode,
+            message::{Message, MessageName},
+        },
+        MessageRegistry, Pipe, Request,
+    },
+    operator_handler::operators::requests::retry,
+};
+
+#[derive(Debug, Error)]
+pub enum OperatorInstanceMetricsRequestError {
+    #[error("received error response: {0}: {1}")]
+    ReceivedErrorResponse(ErrorCode, String),
+}
+
+pub struct OperatorInstanceMetricsRequest<'a> {
+    pipe: &'a mut Pipe,
+    msg_reg: Arc<MessageRegistry>,
+}
+
+impl<'a> OperatorInstanceMetricsRequest<'a> {
+    pub async fn request(
+        query_id: u128,
+        operator_instance_id: u128,
+        metrics: messages::query::OperatorMetrics,
+        pipe: &'a mut Pipe,
+        msg_reg: Arc<MessageRegistry>,
+    ) -> Result<()> {
+        debug!(
+            query_id = query_id,
+            operator_instance_id = operator_instance_id,
+            "request",
+        );
+        let mut req = OperatorInstanceMetricsRequest { pipe, msg_reg };
+        let msg = messages::query::OperatorInstanceMetrics {
+            query_id,
+            operator_instance_id,
+            metrics,
+        };
+        req.operator_instance_metrics(&msg).await
+    }
+
+    async fn operator_instance_metrics(
+        &mut self,
+        msg: &messages::query::OperatorInstanceMetrics,
+    ) -> Result<()> {
+        let msg = Message::new(Box::new(msg.clone()));
+
+        let resp_msg = self
+            .pipe
+            .send_request(Request {
+                msg,
+                expect_response_msg_name: MessageName::CommonGenericResponse,
+                timeout: chrono::Duration::seconds(3),
+                retry_policy: retry::REQUEST_RETRY_POLICY,
+            })
+            .await?;
+
+        let resp_cast_msg: &messages::common::GenericResponse =
+            self.msg_reg.try_cast_msg(&resp_msg)?;
+        match resp_cast_msg {
+            messages::common::GenericResponse::Ok => Ok(()),
+            messages::common::GenericResponse::Err { code, message } => Err(
+                OperatorInstanceMetricsRequestError::ReceivedErrorResponse(*code, message.clone())
+                    .into(),
+            ),
+        }
+    }
+}
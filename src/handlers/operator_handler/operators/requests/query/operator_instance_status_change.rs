@@ -2,12 +2,13 @@ use std::sync::Arc;
 
 use anyhow::Result;
 use thiserror::Error;
-use tracing::{debug, error};
+use tracing::debug;
 
 use crate::handlers::{
     message_handler::{
         messages::{
             self,
+            common::ErrorCode,
             message::{Message, MessageName},
         },
         MessageRegistry, Pipe, Request,
@@ -17,8 +18,8 @@ use crate::handlers::{
 
 #[derive(Debug, Error)]
 pub enum OperatorInstanceStatusChangeRequestError {
-    #[error("received error response: {0}")]
-    ReceivedErrorResponse(String),
+    #[error("received error response: {0}: {1}")]
+    ReceivedErrorResponse(ErrorCode, String),
 }
 
 pub struct OperatorInstanceStatusChangeRequest<'a> {
@@ -54,6 +55,7 @@ impl<'a> OperatorInstanceStatusChangeRequest<'a> {
         query_id: u128,
         operator_instance_id: u128,
         err: String,
+        retryable: bool,
         pipe: &'a mut Pipe,
         msg_reg: Arc<MessageRegistry>,
     ) -> Result<()> {
@@ -63,7 +65,7 @@ impl<'a> OperatorInstanceStatusChangeRequest<'a> {
             pipe,
             msg_reg,
         };
-        req.inner_errored_request(err).await?;
+        req.inner_errored_request(err, retryable).await?;
         Ok(())
     }
 
@@ -72,16 +74,17 @@ impl<'a> OperatorInstanceStatusChangeRequest<'a> {
             query_id: self.query_id,
             operator_instance_id: self.operator_instance_id,
         };
-        retry::retry_request!(self.operator_instance_status_change(&msg), 3, 10)
+        self.operator_instance_status_change(&msg).await
     }
 
-    async fn inner_errored_request(&mut self, err: String) -> Result<()> {
+    async fn inner_errored_request(&mut self, err: String, retryable: bool) -> Result<()> {
         let msg = messages::query::OperatorInstanceStatusChange::Error {
             query_id: self.query_id,
             operator_instance_id: self.operator_instance_id,
             error: err,
+            retryable,
         };
-        retry::retry_request!(self.operator_instance_status_change(&msg), 3, 10)
+        self.operator_instance_status_change(&msg).await
     }
 
     async fn operator_instance_status_change(
@@ -96,6 +99,7 @@ impl<'a> OperatorInstanceStatusChangeRequest<'a> {
                 msg,
                 expect_response_msg_name: MessageName::CommonGenericResponse,
                 timeout: chrono::Duration::seconds(3),
+                retry_policy: retry::REQUEST_RETRY_POLICY,
             })
             .await?;
 
@@ -103,8 +107,12 @@ impl<'a> OperatorInstanceStatusChangeRequest<'a> {
             self.msg_reg.try_cast_msg(&resp_msg)?;
         match resp_cast_msg {
             messages::common::GenericResponse::Ok => Ok(()),
-            messages::common::GenericResponse::Error(err) => Err(
-                OperatorInstanceStatusChangeRequestError::ReceivedErrorResponse(err.clone()).into(),
+            messages::common::GenericResponse::Err { code, message } => Err(
+                OperatorInstanceStatusChangeRequestError::ReceivedErrorResponse(
+                    *code,
+                    message.clone(),
+                )
+                .into(),
             ),
         }
     }
@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use tracing::debug;
+
+use crate::handlers::{
+    message_handler::{
+        messages::{
+            self,
+            message::{Message, MessageName},
+        },
+        MessageRegistry, Pipe, Request,
+    },
+    operator_handler::operators::requests::retry,
+};
+
+pub struct StreamQueryResultsRequest<'a> {
+    pipe: &'a mut Pipe,
+    msg_reg: Arc<MessageRegistry>,
+}
+
+impl<'a> StreamQueryResultsRequest<'a> {
+    // Returns false when no client is subscribed to the results.
+    pub async fn record_request(
+        query_id: u128,
+        record: Arc<arrow::array::RecordBatch>,
+        pipe: &'a mut Pipe,
+        msg_reg: Arc<MessageRegistry>,
+    ) -> Result<bool> {
+        debug!(query_id = query_id, "request");
+        let mut req = StreamQueryResultsRequest { pipe, msg_reg };
+        let msg = messages::query::StreamQueryResults::Record { query_id, record };
+        req.stream_query_results(&msg).await
+    }
+
+    pub async fn end_request(
+        query_id: u128,
+        operator_instance_id: u128,
+        pipe: &'a mut Pipe,
+        msg_reg: Arc<MessageRegistry>,
+    ) -> Result<bool> {
+        debug!(
+            query_id = query_id,
+            operator_instance_id = operator_instance_id,
+            "request",
+        );
+        let mut req = StreamQueryResultsRequest { pipe, msg_reg };
+        let msg = messages::query::StreamQueryResults::End {
+            query_id,
+            operator_instance_id,
+        };
+        req.stream_query_results(&msg).await
+    }
+
+    async fn stream_query_results(
+        &mut self,
+        msg: &messages::query::StreamQueryResults,
+    ) -> Result<bool> {
+        let msg = Message::new(Box::new(msg.clone()));
+
+        let resp_msg = self
+            .pipe
+            .send_request(Request {
+                msg,
+                expect_response_msg_name: MessageName::StreamQueryResultsResp,
+                timeout: chrono::Duration::seconds(3),
+                retry_policy: retry::REQUEST_RETRY_POLICY,
+            })
+            .await?;
+
+        let resp_cast_msg: &messages::query::StreamQueryResultsResp =
+            self.msg_reg.try_cast_msg(&resp_msg)?;
+        match resp_cast_msg {
+            messages::query::StreamQueryResultsResp::Forwarded => Ok(true),
+            messages::query::StreamQueryResultsResp::NoSubscribers => Ok(false),
+        }
+    }
+}
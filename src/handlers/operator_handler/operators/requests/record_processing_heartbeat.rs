@@ -0,0 +1,94 @@
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error};
+
+use crate::handlers::message_handler::messages;
+use crate::handlers::message_handler::messages::message::Message;
+
+// Sends a heartbeat to the exchange every interval for each record the
+// task is processing so the exchange doesn't give the records to another
+// consumer. The heartbeats stop when this is dropped.
+#[derive(Debug)]
+pub struct RecordProcessingHeartbeat {
+    record_ids: Arc<Mutex<BTreeSet<u64>>>,
+    ct: CancellationToken,
+}
+
+impl RecordProcessingHeartbeat {
+    pub fn start(
+        operator_id: String,
+        exchange_operator_instance_id: u128,
+        exchange_worker_id: u128,
+        interval: Duration,
+        sender: mpsc::Sender<Message>,
+    ) -> RecordProcessingHeartbeat {
+        let record_ids: Arc<Mutex<BTreeSet<u64>>> = Arc::new(Mutex::new(BTreeSet::new()));
+        let ct = CancellationToken::new();
+
+        let heartbeat_record_ids = record_ids.clone();
+        let heartbeat_ct = ct.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // the first tick completes immediately
+            ticker.tick().await;
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = heartbeat_ct.cancelled() => {
+                        break;
+                    }
+                }
+
+                let record_ids: Vec<u64> = match heartbeat_record_ids.lock() {
+                    Ok(record_ids) => record_ids.iter().cloned().collect(),
+                    Err(err) => {
+                        error!("{}", err);
+                        break;
+                    }
+                };
+                for record_id in record_ids {
+                    debug!(
+                        operator_id = operator_id,
+                        record_id = record_id,
+                        "sending record processing heartbeat"
+                    );
+                    let msg = Message::new(Box::new(
+                        messages::exchange::ExchangeRequests::RecordProcessingHeartbeat {
+                            operator_id: operator_id.clone(),
+                            record_id,
+                        },
+                    ))
+                    .set_route_to_worker_id(exchange_worker_id)
+                    .set_route_to_operation_id(exchange_operator_instance_id);
+                    if sender.send(msg).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        RecordProcessingHeartbeat { record_ids, ct }
+    }
+
+    pub fn add(&self, record_id: u64) {
+        if let Ok(mut record_ids) = self.record_ids.lock() {
+            record_ids.insert(record_id);
+        }
+    }
+
+    pub fn remove(&self, record_id: u64) {
+        if let Ok(mut record_ids) = self.record_ids.lock() {
+            record_ids.remove(&record_id);
+        }
+    }
+}
+
+impl Drop for RecordProcessingHeartbeat {
+    fn drop(&mut self) {
+        self.ct.cancel();
+    }
+}
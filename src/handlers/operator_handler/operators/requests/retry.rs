@@ -1,25 +1,12 @@
-#[macro_export]
-macro_rules! retry_request {
-    ($f:expr, $retries:expr, $interval_in_millis:expr) => {{
-        let mut last_err: Option<anyhow::Error> = None;
-        for retry_idx in 0..($retries + 1) {
-            match $f.await {
-                Ok(val) => {
-                    return Ok(val);
-                }
-                Err(err) => {
-                    error!("{:?}", err);
-                    last_err = Some(err);
+use std::time::Duration;
 
-                    tokio::time::sleep(std::time::Duration::from_secs(std::cmp::min(
-                        (retry_idx + 1) * $interval_in_millis as u64,
-                        5,
-                    )))
-                    .await;
-                }
-            }
-        }
-        Err(last_err.unwrap().context("failed after retries"))
-    }};
-}
-pub use retry_request;
+use crate::handlers::message_handler::RetryPolicy;
+
+// Requests between the tasks, the exchanges and the query handler are
+// sent again when an attempt fails or times out.
+pub const REQUEST_RETRY_POLICY: RetryPolicy = RetryPolicy {
+    max_attempts: 4,
+    base_backoff: Duration::from_millis(500),
+    max_backoff: Duration::from_secs(5),
+    jitter: 0.2,
+};
@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use anyhow::Result;
 use thiserror::Error;
-use tracing::{debug, error};
+use tracing::debug;
 
 use crate::handlers::message_handler::messages;
 use crate::handlers::message_handler::messages::message::{Message, MessageName};
@@ -60,13 +60,16 @@ impl<'a> SendRecordRequest<'a> {
     }
 
     async fn process_request(&mut self) -> Result<()> {
-        retry::retry_request!(self.send_record(), 3, 10)
+        self.send_record().await
     }
 
     async fn send_record(&mut self) -> Result<()> {
         let msg = Message::new(Box::new(
             messages::exchange::ExchangeRequests::SendRecordRequest {
                 record_id: self.record_id.clone(),
+                // the producers number their records in the order they're
+                // sent
+                sequence_number: self.record_id,
                 record: self.record.clone(),
                 table_aliases: self.table_aliases.clone(),
             },
@@ -80,6 +83,7 @@ impl<'a> SendRecordRequest<'a> {
                 msg,
                 expect_response_msg_name: MessageName::ExchangeRequests,
                 timeout: chrono::Duration::seconds(10),
+                retry_policy: retry::REQUEST_RETRY_POLICY,
             })
             .await?;
 
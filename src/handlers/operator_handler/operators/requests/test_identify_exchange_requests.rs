@@ -0,0 +1,122 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use crate::handlers::message_handler::messages;
+use crate::handlers::message_handler::messages::message::MessageName;
+use crate::handlers::message_handler::{MessageRegistry, Pipe};
+use crate::handlers::operator_handler::operator_handler_state::{
+    OperatorInstanceConfig, RecordHeartbeat, DEFAULT_EXCHANGE_MAX_BUFFERED_BYTES,
+};
+use crate::handlers::operator_handler::operators::NanPolicy;
+use crate::planner::{Operator, OperatorCompute, OperatorTask, OperatorType};
+
+use super::IdentifyExchangeRequest;
+
+fn build_op_in_config(identify_exchange_max_attempts: u32) -> OperatorInstanceConfig {
+    OperatorInstanceConfig {
+        id: 1,
+        query_id: 2,
+        pipeline_id: "pipeline".to_string(),
+        instance_idx: 0,
+        operator: Operator {
+            id: "operator_p1_producer".to_string(),
+            plan_id: 1,
+            operator_type: OperatorType::Producer {
+                task: OperatorTask::Limit {
+                    limit: Some(1),
+                    offset: None,
+                },
+                outbound_exchange_id: "operator_p1_exchange".to_string(),
+                inbound_exchange_ids: vec!["operator_p0_exchange".to_string()],
+            },
+            compute: OperatorCompute {
+                instances: 1,
+                cpu_in_thousandths: 1000,
+                memory_in_mib: 512,
+            },
+        },
+        record_heartbeat: RecordHeartbeat::default(),
+        exchange_max_buffered_bytes: DEFAULT_EXCHANGE_MAX_BUFFERED_BYTES,
+        identify_exchange_max_attempts,
+        nan_policy: NanPolicy::default(),
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_identify_exchange_gives_up_after_the_max_attempts() -> Result<()> {
+    let msg_reg = Arc::new(MessageRegistry::new());
+    let (mut pipe, mut stub_pipe) = Pipe::new(10);
+
+    // the query handler never has an instance of the exchange
+    let stub = tokio::spawn(async move {
+        let mut list_requests = 0;
+        while let Some(msg) = stub_pipe.recv().await {
+            assert_eq!(msg.msg.msg_name(), MessageName::QueryHandlerRequests);
+            list_requests += 1;
+            stub_pipe
+                .send(msg.reply(Box::new(
+                    messages::query::QueryHandlerRequests::ListOperatorInstancesResponse {
+                        op_instance_ids: Vec::new(),
+                    },
+                )))
+                .await?;
+        }
+        Ok::<u32, anyhow::Error>(list_requests)
+    });
+
+    let op_in_config = build_op_in_config(2);
+    let start = tokio::time::Instant::now();
+    let res =
+        IdentifyExchangeRequest::request_outbound_exchange(&op_in_config, &mut pipe, msg_reg).await;
+    assert!(res.is_err());
+
+    // only a single backoff of about a second between the two attempts;
+    // no sleep after the last attempt
+    assert!(start.elapsed() < std::time::Duration::from_millis(1500));
+
+    drop(pipe);
+    assert_eq!(stub.await??, 2);
+
+    Ok(())
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_identify_exchange_worker_ping_is_not_retried_per_attempt() -> Result<()> {
+    let msg_reg = Arc::new(MessageRegistry::new());
+    let (mut pipe, mut stub_pipe) = Pipe::new(10);
+
+    // the query handler knows the exchange instance but the exchange
+    // never answers the ping
+    let stub = tokio::spawn(async move {
+        let mut pings = 0;
+        while let Some(msg) = stub_pipe.recv().await {
+            match msg.msg.msg_name() {
+                MessageName::QueryHandlerRequests => {
+                    stub_pipe
+                        .send(msg.reply(Box::new(
+                            messages::query::QueryHandlerRequests::ListOperatorInstancesResponse {
+                                op_instance_ids: vec![3],
+                            },
+                        )))
+                        .await?;
+                }
+                MessageName::Ping => {
+                    pings += 1;
+                }
+                name => panic!("unexpected message {:?}", name),
+            }
+        }
+        Ok::<u32, anyhow::Error>(pings)
+    });
+
+    let op_in_config = build_op_in_config(5);
+    let res =
+        IdentifyExchangeRequest::request_outbound_exchange(&op_in_config, &mut pipe, msg_reg).await;
+    assert!(res.is_err());
+
+    drop(pipe);
+    assert_eq!(stub.await??, 1);
+
+    Ok(())
+}
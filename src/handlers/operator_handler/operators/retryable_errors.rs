@@ -0,0 +1,22 @@
+use std::io;
+
+// Errors which are likely to succeed when the operator instance is run
+// again, like a storage service which is temporarily unavailable.
+pub fn is_retryable(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        if let Some(err) = cause.downcast_ref::<opendal::Error>() {
+            return err.is_temporary();
+        }
+        if let Some(err) = cause.downcast_ref::<io::Error>() {
+            return matches!(
+                err.kind(),
+                io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::ConnectionRefused
+                    | io::ErrorKind::TimedOut
+                    | io::ErrorKind::Interrupted
+            );
+        }
+        false
+    })
+}
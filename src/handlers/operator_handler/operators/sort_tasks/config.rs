@@ -0,0 +1,6 @@
+use crate::planner::SortExpr;
+
+#[derive(Debug)]
+pub struct SortConfig {
+    pub exprs: Vec<SortExpr>,
+}
@@ -0,0 +1,31 @@
+use anyhow::Result;
+use thiserror::Error;
+
+use crate::{
+    handlers::operator_handler::operator_handler_state::OperatorInstanceConfig,
+    planner::{OperatorTask, OperatorType},
+};
+
+use super::config::SortConfig;
+
+#[derive(Debug, Error)]
+pub enum TryFromSortConfigError {
+    #[error("unable to convert")]
+    UnableToConvert,
+}
+
+impl TryFrom<&OperatorInstanceConfig> for SortConfig {
+    type Error = TryFromSortConfigError;
+
+    fn try_from(op_in_config: &OperatorInstanceConfig) -> Result<SortConfig, Self::Error> {
+        match &op_in_config.operator.operator_type {
+            OperatorType::Producer { task, .. } => match task {
+                OperatorTask::Sort { exprs } => Ok(SortConfig {
+                    exprs: exprs.clone(),
+                }),
+                _ => Err(TryFromSortConfigError::UnableToConvert),
+            },
+            OperatorType::Exchange { .. } => Err(TryFromSortConfigError::UnableToConvert),
+        }
+    }
+}
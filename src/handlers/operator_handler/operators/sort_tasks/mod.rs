@@ -0,0 +1,5 @@
+mod config;
+mod conversions;
+mod sort_task;
+
+pub use sort_task::SortTaskBuilder;
@@ -0,0 +1,510 @@
+use anyhow::{Context, Error, Result};
+use futures::StreamExt;
+use std::{path::PathBuf, sync::Arc};
+use thiserror::Error;
+use tracing::{debug, error};
+use uuid::Uuid;
+
+use crate::handlers::message_handler::messages;
+use crate::handlers::message_handler::messages::message::{Message, MessageName};
+use crate::handlers::message_handler::{MessageRegistry, Pipe};
+use crate::handlers::{
+    message_router_handler::MessageConsumer,
+    operator_handler::{
+        operator_handler_state::OperatorInstanceConfig,
+        operators::{
+            operator_task_trackers::RestrictedOperatorTaskTracker, record_utils, requests,
+            traits::TaskBuilder, ConnectionRegistry,
+        },
+    },
+};
+
+use super::config::SortConfig;
+
+#[derive(Debug, Error)]
+pub enum SortTaskError {
+    #[error("more than one exchange is currently not implement")]
+    MoreThanOneExchangeIsCurrentlyNotImplemented,
+    #[error("run path formatting returned None result")]
+    RunPathFormattingReturnedNoneResult,
+}
+
+const MAX_ROWS_PER_RECORD: usize = 10_000;
+
+// (exchange operator instance id, exchange worker id)
+type ExchangeLocation = (u128, u128);
+
+#[derive(Debug)]
+struct SortTask {
+    operator_instance_config: OperatorInstanceConfig,
+    sort_config: SortConfig,
+
+    operator_pipe: Pipe,
+    msg_reg: Arc<MessageRegistry>,
+    conn_reg: Arc<ConnectionRegistry>,
+
+    outbound_exchange_worker_id: Option<u128>,
+    outbound_exchange_operator_instance_id: Option<u128>,
+    record_id: u64,
+}
+
+impl SortTask {
+    fn new(
+        op_in_config: OperatorInstanceConfig,
+        sort_config: SortConfig,
+        operator_pipe: Pipe,
+        msg_reg: Arc<MessageRegistry>,
+        conn_reg: Arc<ConnectionRegistry>,
+    ) -> SortTask {
+        SortTask {
+            operator_instance_config: op_in_config,
+            sort_config,
+            operator_pipe,
+            msg_reg,
+            conn_reg,
+            outbound_exchange_worker_id: None,
+            outbound_exchange_operator_instance_id: None,
+            record_id: 0,
+        }
+    }
+
+    fn consumer(&self) -> Box<dyn MessageConsumer> {
+        Box::new(SortConsumer {
+            msg_reg: self.msg_reg.clone(),
+        })
+    }
+
+    async fn async_main(&mut self, ct: tokio_util::sync::CancellationToken) -> Result<()> {
+        debug!(
+            operator_task = self
+                .operator_instance_config
+                .operator
+                .operator_type
+                .task_name(),
+            operator_id = self.operator_instance_config.operator.id,
+            operator_instance_id = self.operator_instance_config.id,
+            "started task",
+        );
+
+        // get the default connection; used to spill sorted runs
+        let storage_conn = self.conn_reg.get_operator("default")?;
+
+        // find the inbound exchange
+        let pipe = &mut self.operator_pipe;
+        let req = requests::IdentifyExchangeRequest::request_inbound_exchanges(
+            &self.operator_instance_config,
+            pipe,
+            self.msg_reg.clone(),
+        );
+        let inbound_exchange = tokio::select! {
+            resp = req => {
+                let resp = resp?;
+                if resp.len() != 1 {
+                    return Err(SortTaskError::MoreThanOneExchangeIsCurrentlyNotImplemented.into());
+                }
+                (resp[0].exchange_operator_instance_id, resp[0].exchange_worker_id)
+            }
+            _ = ct.cancelled() => {
+                return Ok(());
+            }
+        };
+
+        // sorting a run copies the buffered records so only half
+        // of the memory is used for buffering
+        let buffer_limit_in_bytes =
+            self.operator_instance_config.operator.compute.memory_in_mib * 1024 * 1024 / 2;
+        let mut sorter = record_utils::RecordSorter::new(self.sort_config.exprs.clone());
+        sorter.set_nan_policy(self.operator_instance_config.nan_policy);
+        let mut buffered_records: Vec<arrow::array::RecordBatch> = Vec::new();
+        let mut buffered_bytes = 0usize;
+        let mut table_aliases: Option<Vec<Vec<String>>> = None;
+        let mut run_paths: Vec<String> = Vec::new();
+
+        loop {
+            if ct.is_cancelled() {
+                return Ok(());
+            }
+
+            let (record_id, record, record_table_aliases) =
+                match self.next_record(inbound_exchange).await? {
+                    Some(resp) => resp,
+                    None => {
+                        debug!("read all records from the exchange");
+                        break;
+                    }
+                };
+            let table_aliases = table_aliases.get_or_insert(record_table_aliases);
+
+            buffered_bytes += record.get_array_memory_size();
+            buffered_records.push((*record).clone());
+            if buffered_bytes > buffer_limit_in_bytes {
+                let run =
+                    self.sort_buffered_records(&mut sorter, &buffered_records, table_aliases)?;
+                let run_path = self.run_path(run_paths.len())?;
+                debug!(run_path = run_path, "spilling sorted run");
+                self.write_run(&storage_conn, &run_path, &run).await?;
+                run_paths.push(run_path);
+                buffered_records.clear();
+                buffered_bytes = 0;
+            }
+
+            self.confirm_record(inbound_exchange, record_id).await?;
+        }
+
+        let table_aliases = match table_aliases {
+            Some(table_aliases) => table_aliases,
+            None => {
+                debug!("no records to sort");
+                return Ok(());
+            }
+        };
+
+        if run_paths.is_empty() {
+            // everything fit in memory
+            let sorted =
+                self.sort_buffered_records(&mut sorter, &buffered_records, &table_aliases)?;
+            drop(buffered_records);
+            let mut offset = 0;
+            while offset < sorted.num_rows() {
+                let length = std::cmp::min(MAX_ROWS_PER_RECORD, sorted.num_rows() - offset);
+                self.send_record(sorted.slice(offset, length), table_aliases.clone())
+                    .await
+                    .context("unable to send record to the exchange")?;
+                offset += length;
+            }
+        } else {
+            if !buffered_records.is_empty() {
+                let run =
+                    self.sort_buffered_records(&mut sorter, &buffered_records, &table_aliases)?;
+                let run_path = self.run_path(run_paths.len())?;
+                self.write_run(&storage_conn, &run_path, &run).await?;
+                run_paths.push(run_path);
+            }
+            drop(buffered_records);
+
+            self.merge_runs(&storage_conn, &run_paths, table_aliases, &ct)
+                .await?;
+            storage_conn.remove_all(&self.runs_dir()?).await?;
+        }
+
+        debug!(
+            operator_task = self
+                .operator_instance_config
+                .operator
+                .operator_type
+                .task_name(),
+            operator_id = self.operator_instance_config.operator.id,
+            operator_instance_id = self.operator_instance_config.id,
+            "closed task",
+        );
+        Ok(())
+    }
+
+    fn sort_buffered_records(
+        &self,
+        sorter: &mut record_utils::RecordSorter,
+        records: &[arrow::array::RecordBatch],
+        table_aliases: &[Vec<String>],
+    ) -> Result<arrow::array::RecordBatch> {
+        let record = arrow::compute::concat_batches(&records[0].schema(), records)?;
+        sorter.sort_record(&record, table_aliases)
+    }
+
+    // k-way merge of the spilled runs. Each run is read back one record
+    // at a time so only a single record per run is held in memory.
+    async fn merge_runs(
+        &mut self,
+        storage_conn: &opendal::Operator,
+        run_paths: &[String],
+        table_aliases: Vec<Vec<String>>,
+        ct: &tokio_util::sync::CancellationToken,
+    ) -> Result<()> {
+        debug!(runs = run_paths.len(), "merging sorted runs");
+
+        let mut run_streams = Vec::new();
+        for run_path in run_paths {
+            let reader = storage_conn
+                .reader_with(run_path)
+                .chunk(16 * 1024 * 1024)
+                .await?;
+            let content_len = storage_conn.stat(run_path).await?.content_length();
+            let parquet_reader = parquet_opendal::AsyncReader::new(reader, content_len);
+            run_streams.push(
+                parquet::arrow::ParquetRecordBatchStreamBuilder::new(parquet_reader)
+                    .await?
+                    .with_batch_size(MAX_ROWS_PER_RECORD)
+                    .build()?,
+            );
+        }
+
+        let mut merger = record_utils::SortedRunMerger::new(
+            self.sort_config.exprs.clone(),
+            table_aliases.clone(),
+            run_paths.len(),
+        );
+        loop {
+            if ct.is_cancelled() {
+                return Ok(());
+            }
+
+            while let Some(run_idx) = merger.next_run_to_fill() {
+                match run_streams[run_idx].next().await {
+                    Some(record) => merger.push_record(run_idx, record?)?,
+                    None => merger.finish_run(run_idx)?,
+                }
+            }
+
+            match merger.next_record(MAX_ROWS_PER_RECORD)? {
+                Some(record) => {
+                    self.send_record(record, table_aliases.clone())
+                        .await
+                        .context("unable to send record to the exchange")?;
+                }
+                None => {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    async fn write_run(
+        &self,
+        storage_conn: &opendal::Operator,
+        run_path: &str,
+        run: &arrow::array::RecordBatch,
+    ) -> Result<()> {
+        let writer = storage_conn
+            .writer_with(run_path)
+            .chunk(16 * 1024 * 1024)
+            .concurrent(4)
+            .await?;
+        let parquet_writer = parquet_opendal::AsyncWriter::new(writer);
+        let mut arrow_parquet_writer =
+            parquet::arrow::AsyncArrowWriter::try_new(parquet_writer, run.schema(), None)?;
+        arrow_parquet_writer.write(run).await?;
+        arrow_parquet_writer.close().await?;
+        Ok(())
+    }
+
+    fn runs_dir(&self) -> Result<String> {
+        let mut runs_path_buf = PathBuf::from("/query_sort_runs");
+        runs_path_buf.push(format!(
+            "{}",
+            Uuid::from_u128(self.operator_instance_config.query_id)
+        ));
+        runs_path_buf.push(format!(
+            "{}",
+            Uuid::from_u128(self.operator_instance_config.id)
+        ));
+        match runs_path_buf.to_str() {
+            // opendal treats paths ending with a slash as directories
+            Some(runs_path) => Ok(format!("{}/", runs_path)),
+            None => Err(SortTaskError::RunPathFormattingReturnedNoneResult.into()),
+        }
+    }
+
+    fn run_path(&self, run_idx: usize) -> Result<String> {
+        Ok(format!("{}run_{}.parquet", self.runs_dir()?, run_idx))
+    }
+
+    // Returns None once the exchange has no records left.
+    async fn next_record(
+        &mut self,
+        exchange: ExchangeLocation,
+    ) -> Result<Option<(u64, Arc<arrow::array::RecordBatch>, Vec<Vec<String>>)>> {
+        loop {
+            let resp = requests::GetNextRecordRequest::get_next_record_request(
+                self.operator_instance_config.operator.id.clone(),
+                exchange.0,
+                exchange.1,
+                &mut self.operator_pipe,
+                self.msg_reg.clone(),
+            )
+            .await?;
+
+            match resp {
+                requests::GetNextRecordResponse::Record {
+                    record_id,
+                    record,
+                    table_aliases,
+                } => {
+                    return Ok(Some((record_id, record, table_aliases)));
+                }
+                requests::GetNextRecordResponse::NoneLeft => {
+                    return Ok(None);
+                }
+                requests::GetNextRecordResponse::NoneAvailable => {
+                    debug!("exchange does not have any record available; waiting 100 milliseconds");
+                    tokio::time::sleep(chrono::Duration::milliseconds(100).to_std()?).await;
+                }
+            }
+        }
+    }
+
+    async fn confirm_record(&mut self, exchange: ExchangeLocation, record_id: u64) -> Result<()> {
+        requests::OperatorCompletedRecordProcessingRequest::request(
+            self.operator_instance_config.operator.id.clone(),
+            record_id,
+            exchange.0,
+            exchange.1,
+            &mut self.operator_pipe,
+            self.msg_reg.clone(),
+        )
+        .await
+    }
+
+    async fn send_record(
+        &mut self,
+        record: arrow::array::RecordBatch,
+        table_aliases: Vec<Vec<String>>,
+    ) -> Result<()> {
+        if self.outbound_exchange_worker_id.is_none() {
+            let pipe = &mut self.operator_pipe;
+            let resp = requests::IdentifyExchangeRequest::request_outbound_exchange(
+                &self.operator_instance_config,
+                pipe,
+                self.msg_reg.clone(),
+            )
+            .await?;
+            self.outbound_exchange_operator_instance_id = Some(resp.exchange_operator_instance_id);
+            self.outbound_exchange_worker_id = Some(resp.exchange_worker_id);
+        }
+
+        assert!(self.outbound_exchange_worker_id.is_some());
+
+        let msg_record_id = self.next_record_id();
+        let pipe = &mut self.operator_pipe;
+        requests::SendRecordRequest::send_record_request(
+            msg_record_id,
+            record,
+            table_aliases,
+            self.outbound_exchange_operator_instance_id.unwrap(),
+            self.outbound_exchange_worker_id.unwrap(),
+            pipe,
+            self.msg_reg.clone(),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    fn next_record_id(&mut self) -> u64 {
+        let record_id = self.record_id;
+        self.record_id += 1;
+        record_id
+    }
+}
+
+//////////////////////////////////////////////////////
+// Sort Producer Builder
+
+#[derive(Debug, Clone)]
+pub struct SortTaskBuilder {}
+
+impl SortTaskBuilder {
+    pub fn new() -> SortTaskBuilder {
+        SortTaskBuilder {}
+    }
+}
+
+impl TaskBuilder for SortTaskBuilder {
+    fn build(
+        &self,
+        op_in_config: OperatorInstanceConfig,
+        operator_pipe: Pipe,
+        msg_reg: Arc<MessageRegistry>,
+        conn_reg: Arc<ConnectionRegistry>,
+        tt: &mut RestrictedOperatorTaskTracker,
+        ct: tokio_util::sync::CancellationToken,
+    ) -> Result<(
+        tokio::sync::oneshot::Receiver<Option<Error>>,
+        Box<dyn MessageConsumer>,
+    )> {
+        let sort_config = SortConfig::try_from(&op_in_config)?;
+        let mut op = SortTask::new(
+            op_in_config,
+            sort_config,
+            operator_pipe,
+            msg_reg.clone(),
+            conn_reg.clone(),
+        );
+
+        let consumer = op.consumer();
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        tt.spawn(async move {
+            if let Err(err) = op.async_main(ct).await {
+                error!("{:?}", err);
+                if let Err(err_send) = tx.send(Some(err)) {
+                    error!("{:?}", err_send);
+                }
+            } else if let Err(err_send) = tx.send(None) {
+                error!("{:?}", err_send);
+            }
+        })?;
+
+        Ok((rx, consumer))
+    }
+}
+
+//////////////////////////////////////////////////////
+// Message Consumer
+
+#[derive(Debug, Clone)]
+pub struct SortConsumer {
+    msg_reg: Arc<MessageRegistry>,
+}
+
+impl MessageConsumer for SortConsumer {
+    fn consumes_message(&self, msg: &Message) -> bool {
+        match msg.msg.msg_name() {
+            // used to find the exchanges
+            MessageName::Ping => match self.msg_reg.try_cast_msg::<messages::common::Ping>(msg) {
+                Ok(messages::common::Ping::Ping) => false,
+                Ok(messages::common::Ping::Pong) => true,
+                Err(err) => {
+                    error!("{:?}", err);
+                    false
+                }
+            },
+            MessageName::QueryHandlerRequests => {
+                match self
+                    .msg_reg
+                    .try_cast_msg::<messages::query::QueryHandlerRequests>(msg)
+                {
+                    Ok(messages::query::QueryHandlerRequests::ListOperatorInstancesResponse {
+                        ..
+                    }) => true,
+                    Ok(messages::query::QueryHandlerRequests::ListOperatorInstancesRequest {
+                        ..
+                    }) => false,
+                    Err(err) => {
+                        error!("{:?}", err);
+                        false
+                    }
+                }
+            }
+            MessageName::ExchangeRequests => {
+                match self
+                    .msg_reg
+                    .try_cast_msg::<messages::exchange::ExchangeRequests>(msg)
+                {
+                    Ok(messages::exchange::ExchangeRequests::GetNextRecordResponseRecord {
+                        ..
+                    }) => true,
+                    Ok(messages::exchange::ExchangeRequests::GetNextRecordResponseNoneLeft) => true,
+                    Ok(messages::exchange::ExchangeRequests::GetNextRecordResponseNoneAvailable) => true,
+                    Ok(messages::exchange::ExchangeRequests::OperatorCompletedRecordProcessingResponse) => true,
+                    Ok(messages::exchange::ExchangeRequests::SendRecordResponse { .. }) => true,
+                    Err(err) => {
+                        error!("{:?}", err);
+                        false
+                    }
+                    _ => false,
+                }
+            }
+            MessageName::CommonGenericResponse => true,
+            _ => false,
+        }
+    }
+}
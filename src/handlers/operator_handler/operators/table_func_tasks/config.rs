@@ -1,10 +1,29 @@
-use sqlparser::ast::FunctionArg;
+use sqlparser::ast::{Expr, FunctionArg};
+
+// Paths passed to table funcs can start with the name of a registered
+// connection, for example 'archive://logs/*.parquet'. The rest of the
+// path is relative to the root of the connection.
+pub fn split_connection_prefix(path: &str) -> (Option<String>, String) {
+    match path.split_once("://") {
+        Some((conn_name, rest))
+            if !conn_name.is_empty()
+                && conn_name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') =>
+        {
+            (Some(conn_name.to_string()), rest.to_string())
+        }
+        _ => (None, path.to_string()),
+    }
+}
 
 #[derive(Debug)]
 pub struct TableFuncConfig {
     pub alias: Option<String>,
     pub func_name: String,
     pub args: Vec<FunctionArg>,
+    pub projection: Option<Vec<String>>,
+    pub predicate: Option<Expr>,
     pub max_rows_per_batch: usize,
 
     pub outbound_exchange_id: String,
@@ -28,11 +28,15 @@ impl TryFrom<&OperatorInstanceConfig> for TableFuncConfig {
                     alias,
                     func_name,
                     args,
+                    projection,
+                    predicate,
                     max_rows_per_batch,
                 } => Ok(TableFuncConfig {
                     alias: alias.clone(),
                     func_name: func_name.clone(),
                     args: args.clone(),
+                    projection: projection.clone(),
+                    predicate: predicate.clone(),
                     max_rows_per_batch: max_rows_per_batch.clone(),
                     outbound_exchange_id: outbound_exchange_id.clone(),
                     inbound_exchange_ids: inbound_exchange_ids.clone(),
@@ -0,0 +1,97 @@
+use std::io::Cursor;
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::RecordBatch;
+use arrow::datatypes::SchemaRef;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum CsvFilesError {
+    #[error("schema of csv file {0} does not match the schema of {1}; {0}: {2}, {1}: {3}")]
+    SchemaMismatch(String, String, String, String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvOptions {
+    pub header: bool,
+    pub delimiter: u8,
+}
+
+impl Default for CsvOptions {
+    fn default() -> CsvOptions {
+        CsvOptions {
+            header: true,
+            delimiter: b',',
+        }
+    }
+}
+
+impl CsvOptions {
+    fn format(&self) -> arrow::csv::reader::Format {
+        arrow::csv::reader::Format::default()
+            .with_header(self.header)
+            .with_delimiter(self.delimiter)
+    }
+}
+
+// The column types are inferred from every row of each file. A column
+// with both integers and floats is a float column. Every file must
+// have the same schema so the records can be combined.
+pub async fn infer_csv_schema(
+    conn: &opendal::Operator,
+    paths: &[String],
+    options: &CsvOptions,
+) -> Result<Option<SchemaRef>> {
+    let mut first: Option<(&String, SchemaRef)> = None;
+    for path in paths {
+        let data = conn.read(path).await?.to_vec();
+        let (schema, _) = options.format().infer_schema(Cursor::new(&data), None)?;
+        let schema = Arc::new(schema);
+
+        match &first {
+            Some((first_path, first_schema)) => {
+                if first_schema.fields() != schema.fields() {
+                    return Err(CsvFilesError::SchemaMismatch(
+                        path.clone(),
+                        first_path.to_string(),
+                        schema_description(&schema),
+                        schema_description(first_schema),
+                    )
+                    .into());
+                }
+            }
+            None => first = Some((path, schema)),
+        }
+    }
+    Ok(first.map(|(_, schema)| schema))
+}
+
+pub async fn read_csv_file(
+    conn: &opendal::Operator,
+    path: &str,
+    schema: SchemaRef,
+    options: &CsvOptions,
+    max_rows_per_batch: usize,
+) -> Result<Vec<RecordBatch>> {
+    let data = conn.read(path).await?.to_vec();
+    let csv_reader = arrow::csv::ReaderBuilder::new(schema)
+        .with_format(options.format())
+        .with_batch_size(max_rows_per_batch)
+        .build(Cursor::new(&data))?;
+
+    let mut records = Vec::new();
+    for rec in csv_reader {
+        records.push(rec?);
+    }
+    Ok(records)
+}
+
+fn schema_description(schema: &SchemaRef) -> String {
+    schema
+        .fields()
+        .iter()
+        .map(|field| format!("{} {}", field.name(), field.data_type()))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
@@ -0,0 +1,396 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Error, Result};
+use arrow::array::{Int64Array, RecordBatch};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use sqlparser::ast::{Expr, FunctionArg, FunctionArgExpr, UnaryOperator, Value};
+use thiserror::Error;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error};
+
+use crate::handlers::message_handler::messages;
+use crate::handlers::message_handler::messages::message::{Message, MessageName};
+use crate::handlers::message_handler::{MessageRegistry, Pipe};
+use crate::handlers::message_router_handler::MessageConsumer;
+use crate::handlers::operator_handler::operator_handler_state::OperatorInstanceConfig;
+use crate::handlers::operator_handler::operators::operator_metrics::OperatorMetricsRecorder;
+use crate::handlers::operator_handler::operators::operator_task_trackers::RestrictedOperatorTaskTracker;
+use crate::handlers::operator_handler::operators::requests::{
+    IdentifyExchangeRequest, SendRecordRequest,
+};
+use crate::handlers::operator_handler::operators::traits::{TableFuncSyntaxValidator, TaskBuilder};
+use crate::handlers::operator_handler::operators::{record_utils, ConnectionRegistry};
+
+use super::config::TableFuncConfig;
+
+const GENERATE_SERIES_COLUMN: &str = "generate_series";
+
+#[derive(Debug, Error)]
+pub enum GenerateSeriesConfigError {
+    #[error("generate_series takes a start, a stop and an optional step but got {0} args")]
+    InvalidNumberOfArgs(usize),
+    #[error("invalid argument {0}: expected an integer")]
+    InvalidArgument(usize),
+    #[error("the step can't be zero")]
+    ZeroStep,
+}
+
+#[derive(Debug, Clone)]
+pub struct GenerateSeriesSyntaxValidator {}
+
+impl GenerateSeriesSyntaxValidator {
+    pub fn new() -> GenerateSeriesSyntaxValidator {
+        GenerateSeriesSyntaxValidator {}
+    }
+}
+
+impl TableFuncSyntaxValidator for GenerateSeriesSyntaxValidator {
+    fn valid(&self, config: &TableFuncConfig) -> bool {
+        Series::try_from_args(&config.args).is_ok()
+    }
+    fn implements_func_name(&self) -> String {
+        "generate_series".to_string()
+    }
+}
+
+// The integers from start to stop, including stop, counting by step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Series {
+    pub start: i64,
+    pub stop: i64,
+    pub step: i64,
+}
+
+impl Series {
+    pub fn try_from_args(args: &[FunctionArg]) -> Result<Series> {
+        if args.len() != 2 && args.len() != 3 {
+            return Err(GenerateSeriesConfigError::InvalidNumberOfArgs(args.len()).into());
+        }
+        let values = args
+            .iter()
+            .enumerate()
+            .map(|(idx, arg)| match arg {
+                FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) => parse_int(idx, expr),
+                _ => Err(GenerateSeriesConfigError::InvalidArgument(idx).into()),
+            })
+            .collect::<Result<Vec<i64>>>()?;
+
+        let step = values.get(2).cloned().unwrap_or(1);
+        if step == 0 {
+            return Err(GenerateSeriesConfigError::ZeroStep.into());
+        }
+        Ok(Series {
+            start: values[0],
+            stop: values[1],
+            step,
+        })
+    }
+
+    // The next batch of at most max_rows values starting at next, or
+    // None once the series is exhausted.
+    pub fn batch(&self, next: i64, max_rows: usize) -> Option<Vec<i64>> {
+        let mut values = Vec::new();
+        let mut value = next;
+        while values.len() < max_rows && self.contains(value) {
+            values.push(value);
+            value = match value.checked_add(self.step) {
+                Some(value) => value,
+                None => break,
+            };
+        }
+        if values.is_empty() {
+            None
+        } else {
+            Some(values)
+        }
+    }
+
+    fn contains(&self, value: i64) -> bool {
+        if self.step > 0 {
+            value >= self.start && value <= self.stop
+        } else {
+            value <= self.start && value >= self.stop
+        }
+    }
+}
+
+fn parse_int(idx: usize, expr: &Expr) -> Result<i64> {
+    match expr {
+        Expr::Value(Value::Number(val, _)) => val
+            .parse::<i64>()
+            .map_err(|_| GenerateSeriesConfigError::InvalidArgument(idx).into()),
+        Expr::UnaryOp {
+            op: UnaryOperator::Minus,
+            expr,
+        } => parse_int(idx, expr).map(|val| -val),
+        Expr::Nested(expr) => parse_int(idx, expr),
+        _ => Err(GenerateSeriesConfigError::InvalidArgument(idx).into()),
+    }
+}
+
+fn generate_series_schema_ref() -> SchemaRef {
+    Arc::new(Schema::new(vec![Field::new(
+        GENERATE_SERIES_COLUMN,
+        DataType::Int64,
+        false,
+    )]))
+}
+
+pub fn generate_series_schema(args: &[FunctionArg]) -> Result<Option<SchemaRef>> {
+    Series::try_from_args(args)?;
+    Ok(Some(generate_series_schema_ref()))
+}
+
+#[derive(Debug)]
+pub struct GenerateSeriesTask {
+    operator_instance_config: OperatorInstanceConfig,
+    series: Series,
+    max_rows_per_batch: usize,
+
+    operator_pipe: Pipe,
+    msg_reg: Arc<MessageRegistry>,
+
+    exchange_worker_id: Option<u128>,
+    exchange_operator_instance_id: Option<u128>,
+    record_id: u64,
+    metrics: OperatorMetricsRecorder,
+}
+
+impl GenerateSeriesTask {
+    pub fn new(
+        op_in_config: OperatorInstanceConfig,
+        series: Series,
+        max_rows_per_batch: usize,
+        operator_pipe: Pipe,
+        msg_reg: Arc<MessageRegistry>,
+    ) -> GenerateSeriesTask {
+        GenerateSeriesTask {
+            operator_instance_config: op_in_config,
+            series,
+            max_rows_per_batch: std::cmp::max(1, max_rows_per_batch),
+            operator_pipe,
+            msg_reg,
+            exchange_worker_id: None,
+            exchange_operator_instance_id: None,
+            record_id: 0,
+            metrics: OperatorMetricsRecorder::new(),
+        }
+    }
+
+    pub fn consumer(&self) -> Box<dyn MessageConsumer> {
+        Box::new(GenerateSeriesConsumer {
+            msg_reg: self.msg_reg.clone(),
+        })
+    }
+
+    // The series is sent in batches so the task stops part way through
+    // when it's cancelled, for example below a satisfied limit.
+    pub async fn async_main(&mut self, ct: CancellationToken) -> Result<()> {
+        debug!(
+            operator_task = self
+                .operator_instance_config
+                .operator
+                .operator_type
+                .task_name(),
+            operator_id = self.operator_instance_config.operator.id,
+            operator_instance_id = self.operator_instance_config.id,
+            "started task",
+        );
+
+        let mut next = self.series.start;
+        while !ct.is_cancelled() {
+            let values = match self.series.batch(next, self.max_rows_per_batch) {
+                Some(values) => values,
+                None => break,
+            };
+            let last = values[values.len() - 1];
+            let record = RecordBatch::try_new(
+                generate_series_schema_ref(),
+                vec![Arc::new(Int64Array::from(values))],
+            )?;
+
+            tokio::select! {
+                res = self.send_record(record) => {
+                    res.context("unable to send record to the exchange")?;
+                }
+                _ = ct.cancelled() => break,
+            }
+
+            next = match last.checked_add(self.series.step) {
+                Some(next) => next,
+                None => break,
+            };
+        }
+
+        self.metrics
+            .report(
+                &self.operator_instance_config,
+                &mut self.operator_pipe,
+                self.msg_reg.clone(),
+            )
+            .await;
+
+        debug!(
+            operator_task = self
+                .operator_instance_config
+                .operator
+                .operator_type
+                .task_name(),
+            operator_id = self.operator_instance_config.operator.id,
+            operator_instance_id = self.operator_instance_config.id,
+            "closed task",
+        );
+
+        Ok(())
+    }
+
+    async fn send_record(&mut self, record: RecordBatch) -> Result<()> {
+        if self.exchange_worker_id.is_none() {
+            let resp = IdentifyExchangeRequest::request_outbound_exchange(
+                &self.operator_instance_config,
+                &mut self.operator_pipe,
+                self.msg_reg.clone(),
+            )
+            .await?;
+            self.exchange_operator_instance_id = Some(resp.exchange_operator_instance_id);
+            self.exchange_worker_id = Some(resp.exchange_worker_id);
+        }
+
+        assert!(self.exchange_worker_id.is_some());
+
+        let msg_record_id = self.next_record_id();
+        let table_aliases = record_utils::get_record_table_aliases(
+            &self.operator_instance_config.operator.operator_type,
+            &record,
+        )?;
+
+        self.metrics.record_out(&record);
+        SendRecordRequest::send_record_request(
+            msg_record_id,
+            record,
+            table_aliases,
+            self.exchange_operator_instance_id.unwrap(),
+            self.exchange_worker_id.unwrap(),
+            &mut self.operator_pipe,
+            self.msg_reg.clone(),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    fn next_record_id(&mut self) -> u64 {
+        let record_id = self.record_id;
+        self.record_id += 1;
+        record_id
+    }
+}
+
+//////////////////////////////////////////////////////
+// Table Func Producer Builder
+
+#[derive(Debug, Clone)]
+pub struct GenerateSeriesTaskBuilder {}
+
+impl GenerateSeriesTaskBuilder {
+    pub fn new() -> GenerateSeriesTaskBuilder {
+        GenerateSeriesTaskBuilder {}
+    }
+}
+
+impl TaskBuilder for GenerateSeriesTaskBuilder {
+    fn build(
+        &self,
+        op_in_config: OperatorInstanceConfig,
+        operator_pipe: Pipe,
+        msg_reg: Arc<MessageRegistry>,
+        _conn_reg: Arc<ConnectionRegistry>,
+        tt: &mut RestrictedOperatorTaskTracker,
+        ct: CancellationToken,
+    ) -> Result<(
+        tokio::sync::oneshot::Receiver<Option<Error>>,
+        Box<dyn MessageConsumer>,
+    )> {
+        let table_func_config = TableFuncConfig::try_from(&op_in_config)?;
+        let series = Series::try_from_args(&table_func_config.args)?;
+        let mut op = GenerateSeriesTask::new(
+            op_in_config,
+            series,
+            table_func_config.max_rows_per_batch,
+            operator_pipe,
+            msg_reg.clone(),
+        );
+
+        let consumer = op.consumer();
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        tt.spawn(async move {
+            if let Err(err) = op.async_main(ct).await {
+                error!("{:?}", err);
+                if let Err(err_send) = tx.send(Some(err)) {
+                    error!("{:?}", err_send);
+                }
+            } else if let Err(err_send) = tx.send(None) {
+                error!("{:?}", err_send);
+            }
+        })?;
+
+        Ok((rx, consumer))
+    }
+}
+
+//////////////////////////////////////////////////////
+// Message Consumer
+
+#[derive(Debug, Clone)]
+pub struct GenerateSeriesConsumer {
+    msg_reg: Arc<MessageRegistry>,
+}
+
+impl MessageConsumer for GenerateSeriesConsumer {
+    fn consumes_message(&self, msg: &Message) -> bool {
+        match msg.msg.msg_name() {
+            MessageName::Ping => match self.msg_reg.try_cast_msg::<messages::common::Ping>(msg) {
+                Ok(messages::common::Ping::Ping) => false,
+                Ok(messages::common::Ping::Pong) => true,
+                Err(err) => {
+                    error!("{:?}", err);
+                    false
+                }
+            },
+            MessageName::ExchangeRequests => {
+                match self
+                    .msg_reg
+                    .try_cast_msg::<messages::exchange::ExchangeRequests>(msg)
+                {
+                    Ok(messages::exchange::ExchangeRequests::SendRecordResponse { .. }) => true,
+                    Ok(messages::exchange::ExchangeRequests::SendRecordRequest { .. }) => false,
+                    Err(err) => {
+                        error!("{:?}", err);
+                        false
+                    }
+                    _ => false,
+                }
+            }
+            MessageName::QueryHandlerRequests => {
+                match self
+                    .msg_reg
+                    .try_cast_msg::<messages::query::QueryHandlerRequests>(msg)
+                {
+                    Ok(messages::query::QueryHandlerRequests::ListOperatorInstancesResponse {
+                        ..
+                    }) => true,
+                    Ok(messages::query::QueryHandlerRequests::ListOperatorInstancesRequest {
+                        ..
+                    }) => false,
+                    Err(err) => {
+                        error!("{:?}", err);
+                        false
+                    }
+                }
+            }
+            MessageName::CommonGenericResponse => true,
+            _ => false,
+        }
+    }
+}
@@ -1,6 +1,24 @@
 mod config;
 mod conversions;
+mod csv_files;
+mod generate_series_task;
+mod parquet_pruning;
 mod read_files_task;
+mod schemas;
+#[cfg(test)]
+mod test_config;
+#[cfg(test)]
+mod test_generate_series_task;
+#[cfg(test)]
+mod test_read_files_task;
+#[cfg(test)]
+mod test_schemas;
+#[cfg(test)]
+mod test_values_task;
+mod values_task;
 
 pub use config::TableFuncConfig;
+pub use generate_series_task::{GenerateSeriesSyntaxValidator, GenerateSeriesTaskBuilder};
 pub use read_files_task::{ReadFilesSyntaxValidator, ReadFilesTaskBuilder};
+pub use schemas::find_table_func_schema;
+pub use values_task::{ValuesSyntaxValidator, ValuesTaskBuilder};
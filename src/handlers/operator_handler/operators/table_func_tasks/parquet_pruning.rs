@@ -0,0 +1,213 @@
+use std::cmp::Ordering;
+
+use arrow::datatypes::{DataType, Schema};
+use parquet::file::metadata::{ParquetMetaData, RowGroupMetaData};
+use parquet::file::statistics::Statistics;
+use sqlparser::ast::{BinaryOperator, Expr, UnaryOperator, Value};
+
+#[derive(Debug, Clone, PartialEq)]
+enum StatsValue {
+    Int(i64),
+    Float(f64),
+    Str(Vec<u8>),
+}
+
+impl StatsValue {
+    fn compare(&self, other: &StatsValue) -> Option<Ordering> {
+        match (self, other) {
+            (StatsValue::Int(left), StatsValue::Int(right)) => Some(left.cmp(right)),
+            (StatsValue::Int(left), StatsValue::Float(right)) => (*left as f64).partial_cmp(right),
+            (StatsValue::Float(left), StatsValue::Int(right)) => left.partial_cmp(&(*right as f64)),
+            (StatsValue::Float(left), StatsValue::Float(right)) => left.partial_cmp(right),
+            (StatsValue::Str(left), StatsValue::Str(right)) => Some(left.cmp(right)),
+            _ => None,
+        }
+    }
+
+    fn from_literal(expr: &Expr) -> Option<StatsValue> {
+        match expr {
+            Expr::Value(Value::Number(val, _)) => Self::from_number(val.clone()),
+            Expr::UnaryOp {
+                op: UnaryOperator::Minus,
+                expr,
+            } => match expr.as_ref() {
+                Expr::Value(Value::Number(val, _)) => Self::from_number(format!("-{}", val)),
+                _ => None,
+            },
+            Expr::Value(Value::SingleQuotedString(val)) => {
+                Some(StatsValue::Str(val.as_bytes().to_vec()))
+            }
+            _ => None,
+        }
+    }
+
+    fn from_number(val: String) -> Option<StatsValue> {
+        match val.parse::<i64>() {
+            Ok(val) => Some(StatsValue::Int(val)),
+            Err(_) => val.parse::<f64>().ok().map(StatsValue::Float),
+        }
+    }
+}
+
+// Returns the indices of the row groups which may have rows matching
+// the predicate. A row group is skipped when the min and max statistics
+// of a column show none of its rows can match one of the and-ed
+// comparisons.
+pub fn prune_row_groups(
+    predicate: &Expr,
+    metadata: &ParquetMetaData,
+    schema: &Schema,
+) -> Vec<usize> {
+    let mut conjuncts: Vec<&Expr> = Vec::new();
+    split_conjuncts(predicate, &mut conjuncts);
+
+    metadata
+        .row_groups()
+        .iter()
+        .enumerate()
+        .filter(|(_, row_group)| {
+            conjuncts
+                .iter()
+                .all(|expr| row_group_may_match(expr, row_group, schema))
+        })
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+fn split_conjuncts<'a>(expr: &'a Expr, conjuncts: &mut Vec<&'a Expr>) {
+    match expr {
+        Expr::BinaryOp {
+            left,
+            op: BinaryOperator::And,
+            right,
+        } => {
+            split_conjuncts(left, conjuncts);
+            split_conjuncts(right, conjuncts);
+        }
+        Expr::Nested(expr) => split_conjuncts(expr, conjuncts),
+        _ => conjuncts.push(expr),
+    }
+}
+
+fn column_name(expr: &Expr) -> Option<&str> {
+    match expr {
+        Expr::Identifier(ident) => Some(ident.value.as_str()),
+        Expr::CompoundIdentifier(idents) => idents.last().map(|ident| ident.value.as_str()),
+        _ => None,
+    }
+}
+
+// The min and max of the column in the row group. Only columns whose
+// statistics are ordered the same way as the values are used.
+fn column_bounds(
+    name: &str,
+    row_group: &RowGroupMetaData,
+    schema: &Schema,
+) -> Option<(StatsValue, StatsValue)> {
+    let (_, field) = schema.column_with_name(name)?;
+    if !matches!(
+        field.data_type(),
+        DataType::Int8
+            | DataType::Int16
+            | DataType::Int32
+            | DataType::Int64
+            | DataType::Float32
+            | DataType::Float64
+            | DataType::Utf8
+            | DataType::LargeUtf8
+    ) {
+        return None;
+    }
+
+    let column = row_group
+        .columns()
+        .iter()
+        .find(|column| column.column_path().parts() == [name.to_string()])?;
+    match column.statistics()? {
+        Statistics::Int32(stats) => Some((
+            StatsValue::Int(*stats.min_opt()? as i64),
+            StatsValue::Int(*stats.max_opt()? as i64),
+        )),
+        Statistics::Int64(stats) => Some((
+            StatsValue::Int(*stats.min_opt()?),
+            StatsValue::Int(*stats.max_opt()?),
+        )),
+        Statistics::Float(stats) => Some((
+            StatsValue::Float(*stats.min_opt()? as f64),
+            StatsValue::Float(*stats.max_opt()? as f64),
+        )),
+        Statistics::Double(stats) => Some((
+            StatsValue::Float(*stats.min_opt()?),
+            StatsValue::Float(*stats.max_opt()?),
+        )),
+        Statistics::ByteArray(stats) => Some((
+            StatsValue::Str(stats.min_opt()?.data().to_vec()),
+            StatsValue::Str(stats.max_opt()?.data().to_vec()),
+        )),
+        _ => None,
+    }
+}
+
+// Comparisons which can't be checked against the statistics may match.
+fn row_group_may_match(expr: &Expr, row_group: &RowGroupMetaData, schema: &Schema) -> bool {
+    match expr {
+        Expr::BinaryOp { left, op, right } => {
+            let (column, op, literal) = match (column_name(left), column_name(right)) {
+                (Some(column), None) => (column, op.clone(), right.as_ref()),
+                (None, Some(column)) => {
+                    let op = match op {
+                        BinaryOperator::Lt => BinaryOperator::Gt,
+                        BinaryOperator::LtEq => BinaryOperator::GtEq,
+                        BinaryOperator::Gt => BinaryOperator::Lt,
+                        BinaryOperator::GtEq => BinaryOperator::LtEq,
+                        op => op.clone(),
+                    };
+                    (column, op, left.as_ref())
+                }
+                _ => return true,
+            };
+            let (value, (min, max)) = match (
+                StatsValue::from_literal(literal),
+                column_bounds(column, row_group, schema),
+            ) {
+                (Some(value), Some(bounds)) => (value, bounds),
+                _ => return true,
+            };
+            let (min_cmp, max_cmp) = match (min.compare(&value), max.compare(&value)) {
+                (Some(min_cmp), Some(max_cmp)) => (min_cmp, max_cmp),
+                _ => return true,
+            };
+            match op {
+                BinaryOperator::Eq => min_cmp != Ordering::Greater && max_cmp != Ordering::Less,
+                BinaryOperator::NotEq => {
+                    !(min_cmp == Ordering::Equal && max_cmp == Ordering::Equal)
+                }
+                BinaryOperator::Lt => min_cmp == Ordering::Less,
+                BinaryOperator::LtEq => min_cmp != Ordering::Greater,
+                BinaryOperator::Gt => max_cmp == Ordering::Greater,
+                BinaryOperator::GtEq => max_cmp != Ordering::Less,
+                _ => true,
+            }
+        }
+        Expr::Between {
+            expr,
+            negated: false,
+            low,
+            high,
+        } => {
+            let bounds = column_name(expr).and_then(|name| column_bounds(name, row_group, schema));
+            match (
+                bounds,
+                StatsValue::from_literal(low),
+                StatsValue::from_literal(high),
+            ) {
+                (Some((min, max)), Some(low), Some(high)) => {
+                    max.compare(&low) != Some(Ordering::Less)
+                        && min.compare(&high) != Some(Ordering::Greater)
+                }
+                _ => true,
+            }
+        }
+        _ => true,
+    }
+}
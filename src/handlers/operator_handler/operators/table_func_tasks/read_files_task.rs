@@ -1,7 +1,11 @@
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 
 use anyhow::{Context, Error, Result};
 use futures::StreamExt;
+use parquet::arrow::async_reader::ParquetRecordBatchStream;
+use parquet::arrow::{ParquetRecordBatchStreamBuilder, ProjectionMask};
+use parquet::schema::types::SchemaDescriptor;
 use thiserror::Error;
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info};
@@ -18,7 +22,11 @@ use crate::handlers::operator_handler::operators::requests::{
 use crate::handlers::operator_handler::operators::traits::{TableFuncSyntaxValidator, TaskBuilder};
 use crate::handlers::operator_handler::operators::{record_utils, ConnectionRegistry};
 
-use super::config::TableFuncConfig;
+use crate::planner::DataFormat;
+
+use super::config::{split_connection_prefix, TableFuncConfig};
+use super::csv_files::{infer_csv_schema, read_csv_file, CsvOptions};
+use super::parquet_pruning::prune_row_groups;
 
 #[derive(Debug, Error)]
 pub enum ReadFilesError {
@@ -28,10 +36,14 @@ pub enum ReadFilesError {
 
 #[derive(Debug, Error)]
 pub enum ReadFilesConfigError {
-    #[error("invalid argument")]
+    #[error("invalid argument {0}: {1}")]
     InvalidArgument(usize, &'static str),
     #[error("number of arguments greater than expected: {0}")]
     NumberOfArgumentsGreaterThanExpected(usize),
+    #[error("read_files does not support the data format: {0}")]
+    UnsupportedDataFormat(DataFormat),
+    #[error("path connection {0} conflicts with the connection argument {1}")]
+    ConflictingConnections(String, String),
 }
 
 #[derive(Debug, Clone)]
@@ -55,22 +67,43 @@ impl TableFuncSyntaxValidator for ReadFilesSyntaxValidator {
     }
 }
 
+// the path and the connection, format, delimiter and header args
+const MAX_READ_FILES_ARGS: usize = 5;
+
 #[derive(Debug, Clone)]
 pub struct ReadFilesConfig {
     path: String,
+    // `*` matches within a directory and `**` matches across directories
+    path_matcher: globset::GlobMatcher,
     connection: Option<String>,
+    // files matching the path with another extension are skipped
+    data_format: DataFormat,
+    csv_options: CsvOptions,
+    // the parquet columns read; None reads every column
+    projection: Option<Vec<String>>,
+    // used to skip the parquet row groups which can't match the filter
+    predicate: Option<sqlparser::ast::Expr>,
     max_rows_per_batch: usize,
 }
 
 impl ReadFilesConfig {
     fn parse_config(config: &TableFuncConfig) -> Result<ReadFilesConfig> {
-        if config.args.len() > 2 {
-            return Err(ReadFilesConfigError::NumberOfArgumentsGreaterThanExpected(
-                config.args.len(),
-            )
-            .into());
+        let mut read_files_config = Self::parse_args(&config.args, config.max_rows_per_batch)?;
+        read_files_config.set_projection(config.projection.clone());
+        read_files_config.set_predicate(config.predicate.clone());
+        Ok(read_files_config)
+    }
+
+    pub fn parse_args(
+        args: &[sqlparser::ast::FunctionArg],
+        max_rows_per_batch: usize,
+    ) -> Result<ReadFilesConfig> {
+        if args.len() > MAX_READ_FILES_ARGS {
+            return Err(
+                ReadFilesConfigError::NumberOfArgumentsGreaterThanExpected(args.len()).into(),
+            );
         }
-        let path = match config.args.get(0) {
+        let prefixed_path = match args.first() {
             Some(sqlparser::ast::FunctionArg::Unnamed(sqlparser::ast::FunctionArgExpr::Expr(
                 sqlparser::ast::Expr::Value(sqlparser::ast::Value::SingleQuotedString(val)),
             ))) => val,
@@ -79,32 +112,110 @@ impl ReadFilesConfig {
             }
         }
         .clone();
-        let connection = match config.args.get(1) {
-            Some(sqlparser::ast::FunctionArg::Named {
-                name:
-                    sqlparser::ast::Ident {
-                        value,
-                        quote_style: None,
-                    },
-                arg:
-                    sqlparser::ast::FunctionArgExpr::Expr(sqlparser::ast::Expr::Value(
-                        sqlparser::ast::Value::SingleQuotedString(connection_name),
-                    )),
-                ..
-            }) if *value == "connection".to_string() => Some(connection_name.clone()),
-            None => None,
-            _ => {
-                return Err(ReadFilesConfigError::InvalidArgument(1, "connection").into());
+
+        let mut arg_connection: Option<String> = None;
+        let mut arg_data_format: Option<DataFormat> = None;
+        let mut csv_options = CsvOptions::default();
+        for (idx, arg) in args.iter().enumerate().skip(1) {
+            let (name, value) = match arg {
+                sqlparser::ast::FunctionArg::Named {
+                    name:
+                        sqlparser::ast::Ident {
+                            value: name,
+                            quote_style: None,
+                        },
+                    arg: sqlparser::ast::FunctionArgExpr::Expr(sqlparser::ast::Expr::Value(value)),
+                    ..
+                } => (name.as_str(), value),
+                _ => {
+                    return Err(ReadFilesConfigError::InvalidArgument(idx, "namedArgument").into());
+                }
+            };
+            match (name, value) {
+                ("connection", sqlparser::ast::Value::SingleQuotedString(val)) => {
+                    arg_connection = Some(val.clone());
+                }
+                ("format", sqlparser::ast::Value::SingleQuotedString(val)) => {
+                    arg_data_format = Some(val.parse::<DataFormat>()?);
+                }
+                ("delimiter", sqlparser::ast::Value::SingleQuotedString(val)) => {
+                    csv_options.delimiter = match val.as_bytes() {
+                        [delimiter] => *delimiter,
+                        _ => {
+                            return Err(
+                                ReadFilesConfigError::InvalidArgument(idx, "delimiter").into()
+                            );
+                        }
+                    };
+                }
+                ("header", sqlparser::ast::Value::Boolean(val)) => {
+                    csv_options.header = *val;
+                }
+                _ => {
+                    return Err(ReadFilesConfigError::InvalidArgument(idx, "namedArgument").into());
+                }
             }
+        }
+
+        let (path_connection, path) = split_connection_prefix(&prefixed_path);
+        let connection = match (path_connection, arg_connection) {
+            (Some(path_conn), Some(arg_conn)) if path_conn != arg_conn => {
+                return Err(
+                    ReadFilesConfigError::ConflictingConnections(path_conn, arg_conn).into(),
+                );
+            }
+            (Some(conn), _) | (None, Some(conn)) => Some(conn),
+            (None, None) => None,
         };
 
+        // the format is taken from the extension of the path when it
+        // isn't given
+        let data_format = match arg_data_format {
+            Some(data_format) => data_format,
+            None => std::path::Path::new(&path)
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .and_then(|ext| ext.parse::<DataFormat>().ok())
+                .unwrap_or(DataFormat::Parquet),
+        };
+        if data_format == DataFormat::JsonLines {
+            return Err(ReadFilesConfigError::UnsupportedDataFormat(data_format).into());
+        }
+
+        let path_matcher = globset::GlobBuilder::new(path.as_str())
+            .literal_separator(true)
+            .build()?
+            .compile_matcher();
+
         Ok(ReadFilesConfig {
             path,
+            path_matcher,
             connection,
-            max_rows_per_batch: config.max_rows_per_batch,
+            data_format,
+            csv_options,
+            projection: None,
+            predicate: None,
+            max_rows_per_batch,
         })
     }
 
+    pub fn set_projection(&mut self, projection: Option<Vec<String>>) -> &Self {
+        self.projection = projection;
+        self
+    }
+
+    pub fn set_predicate(&mut self, predicate: Option<sqlparser::ast::Expr>) -> &Self {
+        self.predicate = predicate;
+        self
+    }
+
+    pub fn get_connection(&self, conn_reg: &ConnectionRegistry) -> Result<opendal::Operator> {
+        match &self.connection {
+            Some(conn_name) => conn_reg.get_operator(conn_name.as_str()),
+            None => conn_reg.get_operator("default"),
+        }
+    }
+
     fn parse_path_prefix(&self) -> &str {
         let special_chars = ['*', '?', '[', ']', '{', '}'];
         let prefix_end = self
@@ -113,6 +224,121 @@ impl ReadFilesConfig {
             .unwrap_or_else(|| self.path.len());
         &self.path[..prefix_end].trim_end_matches('/')
     }
+
+    fn reads_path(&self, path: &str) -> bool {
+        let extension = std::path::Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str());
+        match extension {
+            Some(ext) => {
+                ext == self.data_format.file_extension() && self.path_matcher.is_match(path)
+            }
+            None => false,
+        }
+    }
+
+    // Lists the files matching the path in the order they're listed by
+    // the connection.
+    pub async fn list_files(&self, conn: &opendal::Operator) -> Result<Vec<String>> {
+        let mut lister = conn
+            .lister_with(self.parse_path_prefix())
+            .recursive(true)
+            .await?;
+
+        let mut paths = Vec::new();
+        while let Some(entry) = lister.next().await {
+            let entry = entry?;
+            if entry.metadata().is_file() && self.reads_path(entry.path()) {
+                paths.push(entry.path().to_string());
+            }
+        }
+        Ok(paths)
+    }
+}
+
+// Selects the root columns of the parquet file with the projected names.
+// The first column is read when none of the names match so the records
+// still have the row counts of the file.
+pub fn projection_mask(
+    schema_descr: &SchemaDescriptor,
+    schema: &arrow::datatypes::Schema,
+    projection: &[String],
+) -> ProjectionMask {
+    let mut indices: Vec<usize> = schema
+        .fields()
+        .iter()
+        .enumerate()
+        .filter(|(_, field)| projection.contains(field.name()))
+        .map(|(idx, _)| idx)
+        .collect();
+    if indices.is_empty() && !schema.fields().is_empty() {
+        indices.push(0);
+    }
+    ProjectionMask::roots(schema_descr, indices)
+}
+
+pub async fn parquet_record_stream(
+    conn: &opendal::Operator,
+    path: &str,
+    projection: Option<&[String]>,
+    predicate: Option<&sqlparser::ast::Expr>,
+    max_rows_per_batch: usize,
+) -> Result<ParquetRecordBatchStream<parquet_opendal::AsyncReader>> {
+    let reader = conn
+        .reader_with(path)
+        .gap(512 * 1024)
+        .chunk(16 * 1024 * 1024)
+        .concurrent(4)
+        .await?;
+    let content_len = conn.stat(path).await?.content_length();
+    let parquet_reader = parquet_opendal::AsyncReader::new(reader, content_len)
+        .with_prefetch_footer_size(512 * 1024);
+    let builder = ParquetRecordBatchStreamBuilder::new(parquet_reader)
+        .await?
+        .with_batch_size(max_rows_per_batch);
+    let builder = match projection {
+        Some(projection) => {
+            let mask = projection_mask(builder.parquet_schema(), builder.schema(), projection);
+            builder.with_projection(mask)
+        }
+        None => builder,
+    };
+    let builder = match predicate {
+        Some(predicate) => {
+            let row_groups = prune_row_groups(predicate, builder.metadata(), builder.schema());
+            builder.with_row_groups(row_groups)
+        }
+        None => builder,
+    };
+    Ok(builder.build()?)
+}
+
+// Reads the schema from the footer of the first parquet file matching
+// the path; csv schemas are inferred from every file. Returns None when
+// no files match.
+pub async fn read_files_schema(
+    args: &[sqlparser::ast::FunctionArg],
+    conn_reg: &ConnectionRegistry,
+) -> Result<Option<arrow::datatypes::SchemaRef>> {
+    let read_files_config = ReadFilesConfig::parse_args(args, 1)?;
+    let conn = read_files_config.get_connection(conn_reg)?;
+
+    let paths = read_files_config.list_files(&conn).await?;
+    if read_files_config.data_format == DataFormat::Csv {
+        return infer_csv_schema(&conn, &paths, &read_files_config.csv_options).await;
+    }
+
+    if let Some(path) = paths.first() {
+        let path = path.as_str();
+        let reader = conn.reader_with(path).await?;
+        let content_len = conn.stat(path).await?.content_length();
+        let parquet_reader = parquet_opendal::AsyncReader::new(reader, content_len)
+            .with_prefetch_footer_size(512 * 1024);
+        let builder = parquet::arrow::ParquetRecordBatchStreamBuilder::new(parquet_reader).await?;
+        return Ok(Some(builder.schema().clone()));
+    }
+
+    Ok(None)
 }
 
 #[derive(Debug)]
@@ -167,40 +393,34 @@ impl ReadFilesTask {
             "started task",
         );
 
-        let conn = match &self.read_files_config.connection {
-            Some(conn_name) => self.conn_reg.get_operator(conn_name.as_str())?,
-            None => self.conn_reg.get_operator("default")?,
-        };
-
-        let mut lister = conn
-            .lister_with(self.read_files_config.parse_path_prefix())
-            .recursive(true)
-            .await?;
+        let conn = self.read_files_config.get_connection(&self.conn_reg)?;
 
-        let path_matcher =
-            globset::Glob::new(self.read_files_config.path.as_str())?.compile_matcher();
-
-        loop {
-            tokio::select! {
-                entry = lister.next() => {
-                    match entry {
-                        Some(Ok(val)) => {
-                            let path = val.path();
-                            if !path_matcher.is_match(path) {
-                                continue;
-                            }
+        let paths = tokio::select! {
+            paths = self.read_files_config.list_files(&conn) => paths?,
+            _ = ct.cancelled() => Vec::new(),
+        };
+        // every instance infers the schema from all of the files so they
+        // produce records with the same schema
+        let csv_schema = match self.read_files_config.data_format {
+            DataFormat::Csv => {
+                infer_csv_schema(&conn, &paths, &self.read_files_config.csv_options).await?
+            }
+            _ => None,
+        };
 
-                            self.read_records(ct.clone(), path, &conn).await?;
-                        },
-                        Some(Err(err)) => return Err(err.into()),
-                        None => {
-                            break;
-                        },
-                    };
-                },
-                _ = ct.cancelled() => {
-                    break;
+        for path in paths {
+            if ct.is_cancelled() {
+                break;
+            }
+            if !self.instance_reads_path(&path) {
+                continue;
+            }
+            match &csv_schema {
+                Some(schema) => {
+                    self.read_csv_records(ct.clone(), &path, &conn, schema.clone())
+                        .await?
                 }
+                None => self.read_records(ct.clone(), &path, &conn).await?,
             }
         }
 
@@ -218,6 +438,19 @@ impl ReadFilesTask {
         Ok(())
     }
 
+    // The files are split between the instances of the operator by a
+    // hash of their path; every instance lists the same files but only
+    // reads its share of them.
+    fn instance_reads_path(&self, path: &str) -> bool {
+        let instances = self.operator_instance_config.operator.compute.instances;
+        if instances <= 1 {
+            return true;
+        }
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        path.hash(&mut hasher);
+        (hasher.finish() % instances as u64) as usize == self.operator_instance_config.instance_idx
+    }
+
     async fn read_records(
         &mut self,
         ct: CancellationToken,
@@ -226,19 +459,14 @@ impl ReadFilesTask {
     ) -> Result<()> {
         debug!("read_records(path={})", path);
 
-        let reader = conn
-            .reader_with(path)
-            .gap(512 * 1024)
-            .chunk(16 * 1024 * 1024)
-            .concurrent(4)
-            .await?;
-        let content_len = conn.stat(path).await?.content_length();
-        let parquet_reader = parquet_opendal::AsyncReader::new(reader, content_len)
-            .with_prefetch_footer_size(512 * 1024);
-        let mut rec_stream = parquet::arrow::ParquetRecordBatchStreamBuilder::new(parquet_reader)
-            .await?
-            .with_batch_size(self.read_files_config.max_rows_per_batch)
-            .build()?;
+        let mut rec_stream = parquet_record_stream(
+            conn,
+            path,
+            self.read_files_config.projection.as_deref(),
+            self.read_files_config.predicate.as_ref(),
+            self.read_files_config.max_rows_per_batch,
+        )
+        .await?;
 
         debug!("reading records from file");
         while let Some(record_res) = rec_stream.next().await {
@@ -261,6 +489,35 @@ impl ReadFilesTask {
         Ok(())
     }
 
+    async fn read_csv_records(
+        &mut self,
+        ct: CancellationToken,
+        path: &str,
+        conn: &opendal::Operator,
+        schema: arrow::datatypes::SchemaRef,
+    ) -> Result<()> {
+        debug!("read_csv_records(path={})", path);
+
+        let records = read_csv_file(
+            conn,
+            path,
+            schema,
+            &self.read_files_config.csv_options,
+            self.read_files_config.max_rows_per_batch,
+        )
+        .await?;
+        for record in records {
+            if ct.is_cancelled() {
+                return Err(ReadFilesError::Cancelled.into());
+            }
+            self.send_record(record)
+                .await
+                .context("unable to send record to the exchange")?;
+        }
+
+        Ok(())
+    }
+
     async fn send_record(&mut self, record: arrow::array::RecordBatch) -> Result<()> {
         if self.exchange_worker_id == None {
             let ref mut pipe = self.operator_pipe;
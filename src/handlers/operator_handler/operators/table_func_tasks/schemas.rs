@@ -0,0 +1,23 @@
+use anyhow::Result;
+use arrow::datatypes::SchemaRef;
+use sqlparser::ast::FunctionArg;
+
+use super::generate_series_task::generate_series_schema;
+use super::read_files_task::read_files_schema;
+use super::values_task::values_schema;
+use crate::handlers::operator_handler::operators::ConnectionRegistry;
+
+// Returns the schema of the records a table func produces, or None
+// when it can't be determined before the table func runs.
+pub async fn find_table_func_schema(
+    func_name: &str,
+    args: &[FunctionArg],
+    conn_reg: &ConnectionRegistry,
+) -> Result<Option<SchemaRef>> {
+    match func_name {
+        "read_files" => read_files_schema(args, conn_reg).await,
+        "values" => values_schema(args),
+        "generate_series" => generate_series_schema(args),
+        _ => Ok(None),
+    }
+}
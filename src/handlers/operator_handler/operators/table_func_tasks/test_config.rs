@@ -0,0 +1,46 @@
+use super::config::split_connection_prefix;
+
+#[test]
+fn test_split_connection_prefix() {
+    struct TestCase {
+        path: String,
+        expected_connection: Option<String>,
+        expected_path: String,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            path: "data/*.parquet".to_string(),
+            expected_connection: None,
+            expected_path: "data/*.parquet".to_string(),
+        },
+        TestCase {
+            path: "archive://data/**/*.parquet".to_string(),
+            expected_connection: Some("archive".to_string()),
+            expected_path: "data/**/*.parquet".to_string(),
+        },
+        TestCase {
+            path: "s3_conn-2://*.parquet".to_string(),
+            expected_connection: Some("s3_conn-2".to_string()),
+            expected_path: "*.parquet".to_string(),
+        },
+        TestCase {
+            path: "://data/*.parquet".to_string(),
+            expected_connection: None,
+            expected_path: "://data/*.parquet".to_string(),
+        },
+        TestCase {
+            path: "data/{a,b}://*.parquet".to_string(),
+            expected_connection: None,
+            expected_path: "data/{a,b}://*.parquet".to_string(),
+        },
+    ];
+
+    for test_case in test_cases {
+        println!("test case: {}", test_case.path);
+
+        let (connection, path) = split_connection_prefix(&test_case.path);
+        assert_eq!(connection, test_case.expected_connection);
+        assert_eq!(path, test_case.expected_path);
+    }
+}
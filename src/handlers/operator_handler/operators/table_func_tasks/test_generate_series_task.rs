@@ -0,0 +1,107 @@
+use anyhow::Result;
+use sqlparser::ast::FunctionArg;
+
+use crate::planner::{LogicalPlanNodeType, LogicalPlanner};
+
+use super::generate_series_task::Series;
+
+// the args the logical planner passes to the generate_series table func
+fn series_args(query: &str) -> Result<Vec<FunctionArg>> {
+    let plan = LogicalPlanner::new(query.to_string()).build()?;
+    for node in plan.get_all_nodes() {
+        if let LogicalPlanNodeType::TableFunc { name, args, .. } = node.node {
+            assert_eq!(name, "generate_series");
+            return Ok(args);
+        }
+    }
+    Err(anyhow::Error::msg("expected a generate_series table func"))
+}
+
+#[test]
+fn test_generate_series_args() -> Result<()> {
+    struct TestCase {
+        case_name: String,
+        query: String,
+        expected_series: Option<Series>,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            case_name: "start, stop and step".to_string(),
+            query: "select * from generate_series(1, 5, 1)".to_string(),
+            expected_series: Some(Series {
+                start: 1,
+                stop: 5,
+                step: 1,
+            }),
+        },
+        TestCase {
+            case_name: "the step defaults to one".to_string(),
+            query: "select * from generate_series(-2, 3)".to_string(),
+            expected_series: Some(Series {
+                start: -2,
+                stop: 3,
+                step: 1,
+            }),
+        },
+        TestCase {
+            case_name: "a zero step is invalid".to_string(),
+            query: "select * from generate_series(1, 5, 0)".to_string(),
+            expected_series: None,
+        },
+        TestCase {
+            case_name: "floats are invalid".to_string(),
+            query: "select * from generate_series(1, 5.5)".to_string(),
+            expected_series: None,
+        },
+        TestCase {
+            case_name: "a stop is required".to_string(),
+            query: "select * from generate_series(1)".to_string(),
+            expected_series: None,
+        },
+    ];
+
+    for test_case in test_cases {
+        println!("test case: {}", test_case.case_name);
+
+        let series = Series::try_from_args(&series_args(&test_case.query)?);
+        match test_case.expected_series {
+            Some(expected_series) => assert_eq!(series?, expected_series),
+            None => assert!(series.is_err()),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_generate_series_batches() -> Result<()> {
+    let series = Series {
+        start: 1,
+        stop: 7,
+        step: 2,
+    };
+    assert_eq!(series.batch(1, 3), Some(vec![1, 3, 5]));
+    assert_eq!(series.batch(7, 3), Some(vec![7]));
+    assert_eq!(series.batch(9, 3), None);
+
+    let series = Series {
+        start: 3,
+        stop: 1,
+        step: -1,
+    };
+    assert_eq!(series.batch(3, 10), Some(vec![3, 2, 1]));
+
+    // the series ends instead of overflowing
+    let series = Series {
+        start: i64::MAX - 1,
+        stop: i64::MAX,
+        step: 1,
+    };
+    assert_eq!(
+        series.batch(i64::MAX - 1, 10),
+        Some(vec![i64::MAX - 1, i64::MAX])
+    );
+
+    Ok(())
+}
@@ -0,0 +1,450 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::{Float64Array, Int64Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use futures::StreamExt;
+use parquet::arrow::ArrowWriter;
+use parquet::file::properties::WriterProperties;
+use sqlparser::ast::{Expr, FunctionArg, FunctionArgExpr, FunctionArgOperator, Ident, Value};
+use sqlparser::dialect::GenericDialect;
+use sqlparser::parser::Parser;
+
+use crate::handlers::operator_handler::operators::ConnectionRegistry;
+use crate::planner::{LogicalPlanner, OperatorTask, OperatorType, PhysicalPlanner, PlannerConfig};
+
+use super::csv_files::{read_csv_file, CsvOptions};
+use super::read_files_task::{parquet_record_stream, projection_mask, ReadFilesConfig};
+use super::schemas::find_table_func_schema;
+
+fn path_args(path: &str) -> Vec<FunctionArg> {
+    vec![FunctionArg::Unnamed(FunctionArgExpr::Expr(Expr::Value(
+        Value::SingleQuotedString(path.to_string()),
+    )))]
+}
+
+fn write_parquet_file(path: std::path::PathBuf, record: &RecordBatch) -> Result<()> {
+    let file = std::fs::File::create(path)?;
+    let mut writer = ArrowWriter::try_new(file, record.schema(), None)?;
+    writer.write(record)?;
+    writer.close()?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_read_files_scans_the_matching_parquet_files() -> Result<()> {
+    struct TestCase {
+        path: String,
+        expected_paths: Vec<String>,
+    }
+
+    let dir = tempdir::TempDir::new("read_files")?;
+    std::fs::create_dir_all(dir.path().join("data/nested"))?;
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("size", DataType::Utf8, false),
+    ]));
+    let record_a = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int64Array::from(vec![1, 2])),
+            Arc::new(StringArray::from(vec!["small", "medium"])),
+        ],
+    )?;
+    let record_b = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int64Array::from(vec![3])),
+            Arc::new(StringArray::from(vec!["large"])),
+        ],
+    )?;
+    write_parquet_file(dir.path().join("data/a.parquet"), &record_a)?;
+    write_parquet_file(dir.path().join("data/nested/b.parquet"), &record_b)?;
+    // files which aren't parquet are skipped
+    std::fs::write(dir.path().join("data/notes.txt"), "not parquet")?;
+    std::fs::write(dir.path().join("data/nested/c.txt"), "not parquet")?;
+
+    let mut conn_reg = ConnectionRegistry::new();
+    conn_reg.register_fs(
+        "default".to_string(),
+        dir.path().to_string_lossy().to_string(),
+    );
+    let conn = conn_reg.get_operator("default")?;
+
+    let test_cases = vec![
+        TestCase {
+            path: "data/*.parquet".to_string(),
+            expected_paths: vec!["data/a.parquet".to_string()],
+        },
+        TestCase {
+            path: "data/**/*.parquet".to_string(),
+            expected_paths: vec![
+                "data/a.parquet".to_string(),
+                "data/nested/b.parquet".to_string(),
+            ],
+        },
+        TestCase {
+            path: "data/**".to_string(),
+            expected_paths: vec![
+                "data/a.parquet".to_string(),
+                "data/nested/b.parquet".to_string(),
+            ],
+        },
+        TestCase {
+            path: "data/nested/*".to_string(),
+            expected_paths: vec!["data/nested/b.parquet".to_string()],
+        },
+        TestCase {
+            path: "other/*.parquet".to_string(),
+            expected_paths: vec![],
+        },
+    ];
+
+    for test_case in test_cases {
+        println!("test case: {}", test_case.path);
+
+        let config = ReadFilesConfig::parse_args(&path_args(&test_case.path), 10)?;
+        let mut paths = config.list_files(&conn).await?;
+        paths.sort();
+        assert_eq!(paths, test_case.expected_paths);
+    }
+
+    // both files are read as records
+    let config = ReadFilesConfig::parse_args(&path_args("data/**/*.parquet"), 10)?;
+    let mut paths = config.list_files(&conn).await?;
+    paths.sort();
+    let mut records = Vec::new();
+    for path in paths {
+        let mut rec_stream = parquet_record_stream(&conn, &path, None, None, 10).await?;
+        while let Some(record) = rec_stream.next().await {
+            records.push(record?);
+        }
+    }
+    assert_eq!(records, vec![record_a, record_b]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_read_files_only_reads_the_projected_columns() -> Result<()> {
+    let dir = tempdir::TempDir::new("read_files_projection")?;
+    std::fs::create_dir_all(dir.path().join("data"))?;
+
+    let record = RecordBatch::try_new(
+        Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("size", DataType::Utf8, false),
+            Field::new("price", DataType::Float64, false),
+        ])),
+        vec![
+            Arc::new(Int64Array::from(vec![1, 2])),
+            Arc::new(StringArray::from(vec!["small", "medium"])),
+            Arc::new(Float64Array::from(vec![1.5, 2.5])),
+        ],
+    )?;
+    write_parquet_file(dir.path().join("data/a.parquet"), &record)?;
+
+    let mut conn_reg = ConnectionRegistry::new();
+    conn_reg.register_fs(
+        "default".to_string(),
+        dir.path().to_string_lossy().to_string(),
+    );
+    let conn = conn_reg.get_operator("default")?;
+
+    // the scan reads the selected and filtered columns
+    let query = "select id from read_files('data/*.parquet') where size != 'small'";
+    let logical_plan = LogicalPlanner::new(query.to_string()).build()?;
+    let physical_plan = PhysicalPlanner::new(logical_plan, PlannerConfig::default()).build()?;
+    let projection = physical_plan.get_pipelines()[0]
+        .get_operators()
+        .into_iter()
+        .find_map(|op| match op.operator_type {
+            OperatorType::Producer {
+                task: OperatorTask::TableFunc { projection, .. },
+                ..
+            } => projection,
+            _ => None,
+        })
+        .expect("expected a projection");
+
+    let reader = conn.reader_with("data/a.parquet").await?;
+    let content_len = conn.stat("data/a.parquet").await?.content_length();
+    let builder = parquet::arrow::ParquetRecordBatchStreamBuilder::new(
+        parquet_opendal::AsyncReader::new(reader, content_len),
+    )
+    .await?;
+    let mask = projection_mask(builder.parquet_schema(), builder.schema(), &projection);
+    let included: Vec<bool> = (0..3).map(|idx| mask.leaf_included(idx)).collect();
+    assert_eq!(included, vec![true, true, false]);
+
+    let mut rec_stream =
+        parquet_record_stream(&conn, "data/a.parquet", Some(&projection), None, 10).await?;
+    let mut records = Vec::new();
+    while let Some(rec) = rec_stream.next().await {
+        records.push(rec?);
+    }
+    assert_eq!(records, vec![record.project(&[0, 1])?]);
+
+    // the first column is read when none of the columns are needed so
+    // the row counts are kept
+    let mut rec_stream =
+        parquet_record_stream(&conn, "data/a.parquet", Some(&[]), None, 10).await?;
+    let mut records = Vec::new();
+    while let Some(rec) = rec_stream.next().await {
+        records.push(rec?);
+    }
+    assert_eq!(records, vec![record.project(&[0])?]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_read_files_skips_row_groups_using_the_statistics() -> Result<()> {
+    struct TestCase {
+        case_name: String,
+        predicate: String,
+        expected_ids: Vec<i64>,
+    }
+
+    let dir = tempdir::TempDir::new("read_files_predicate")?;
+    std::fs::create_dir_all(dir.path().join("data"))?;
+
+    // each row group has two rows
+    let record = RecordBatch::try_new(
+        Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("size", DataType::Utf8, false),
+        ])),
+        vec![
+            Arc::new(Int64Array::from(vec![1, 2, 3, 4])),
+            Arc::new(StringArray::from(vec!["large", "medium", "small", "small"])),
+        ],
+    )?;
+    let file = std::fs::File::create(dir.path().join("data/a.parquet"))?;
+    let props = WriterProperties::builder()
+        .set_max_row_group_size(2)
+        .build();
+    let mut writer = ArrowWriter::try_new(file, record.schema(), Some(props))?;
+    writer.write(&record)?;
+    writer.close()?;
+
+    let mut conn_reg = ConnectionRegistry::new();
+    conn_reg.register_fs(
+        "default".to_string(),
+        dir.path().to_string_lossy().to_string(),
+    );
+    let conn = conn_reg.get_operator("default")?;
+
+    let test_cases = vec![
+        TestCase {
+            case_name: "first row group skipped".to_string(),
+            predicate: "id > 2".to_string(),
+            expected_ids: vec![3, 4],
+        },
+        TestCase {
+            case_name: "literal on the left".to_string(),
+            predicate: "2 >= id".to_string(),
+            expected_ids: vec![1, 2],
+        },
+        TestCase {
+            case_name: "string statistics".to_string(),
+            predicate: "size = 'small' and id >= 0".to_string(),
+            expected_ids: vec![3, 4],
+        },
+        TestCase {
+            case_name: "between".to_string(),
+            predicate: "id between 5 and 10".to_string(),
+            expected_ids: vec![],
+        },
+        TestCase {
+            case_name: "types which can't be compared".to_string(),
+            predicate: "id = 'small'".to_string(),
+            expected_ids: vec![1, 2, 3, 4],
+        },
+    ];
+
+    for test_case in test_cases {
+        println!("test case: {}", test_case.case_name);
+
+        let predicate = Parser::new(&GenericDialect {})
+            .try_with_sql(&test_case.predicate)?
+            .parse_expr()?;
+        let mut rec_stream =
+            parquet_record_stream(&conn, "data/a.parquet", None, Some(&predicate), 10).await?;
+        let mut ids: Vec<i64> = Vec::new();
+        while let Some(rec) = rec_stream.next().await {
+            let rec = rec?;
+            let id_array = rec
+                .column(0)
+                .as_any()
+                .downcast_ref::<Int64Array>()
+                .expect("expected an int64 array");
+            ids.extend(id_array.values().iter());
+        }
+        assert_eq!(ids, test_case.expected_ids);
+    }
+
+    Ok(())
+}
+
+fn named_arg(name: &str, value: Value) -> FunctionArg {
+    FunctionArg::Named {
+        name: Ident::new(name),
+        arg: FunctionArgExpr::Expr(Expr::Value(value)),
+        operator: FunctionArgOperator::RightArrow,
+    }
+}
+
+async fn read_csv_records(
+    args: &[FunctionArg],
+    conn_reg: &ConnectionRegistry,
+) -> Result<Vec<RecordBatch>> {
+    let schema = find_table_func_schema("read_files", args, conn_reg)
+        .await?
+        .expect("expected a schema");
+
+    let config = ReadFilesConfig::parse_args(args, 10)?;
+    let conn = config.get_connection(conn_reg)?;
+    let mut paths = config.list_files(&conn).await?;
+    paths.sort();
+
+    let mut records = Vec::new();
+    for path in paths {
+        records
+            .extend(read_csv_file(&conn, &path, schema.clone(), &CsvOptions::default(), 10).await?);
+    }
+    Ok(records)
+}
+
+#[tokio::test]
+async fn test_read_files_scans_csv_files() -> Result<()> {
+    struct TestCase {
+        case_name: String,
+        args: Vec<FunctionArg>,
+        expected_schema: Schema,
+    }
+
+    let dir = tempdir::TempDir::new("read_files_csv")?;
+    std::fs::create_dir_all(dir.path().join("data"))?;
+    std::fs::create_dir_all(dir.path().join("semicolon"))?;
+    std::fs::create_dir_all(dir.path().join("no_header"))?;
+    std::fs::write(
+        dir.path().join("data/a.csv"),
+        "id,price,name\n1,2,small\n2,2.5,medium\n",
+    )?;
+    std::fs::write(
+        dir.path().join("data/b.csv"),
+        "id,price,name\n3,4.0,large\n",
+    )?;
+    std::fs::write(dir.path().join("data/c.txt"), "not,a,csv\n")?;
+    std::fs::write(dir.path().join("semicolon/a.csv"), "id;name\n1;small\n")?;
+    std::fs::write(dir.path().join("no_header/a.csv"), "1,small\n")?;
+
+    let mut conn_reg = ConnectionRegistry::new();
+    conn_reg.register_fs(
+        "default".to_string(),
+        dir.path().to_string_lossy().to_string(),
+    );
+
+    let test_cases = vec![
+        TestCase {
+            case_name: "format from the extension".to_string(),
+            args: path_args("data/*.csv"),
+            expected_schema: Schema::new(vec![
+                Field::new("id", DataType::Int64, true),
+                Field::new("price", DataType::Float64, true),
+                Field::new("name", DataType::Utf8, true),
+            ]),
+        },
+        TestCase {
+            case_name: "format argument".to_string(),
+            args: vec![
+                path_args("data/*").remove(0),
+                named_arg("format", Value::SingleQuotedString("csv".to_string())),
+            ],
+            expected_schema: Schema::new(vec![
+                Field::new("id", DataType::Int64, true),
+                Field::new("price", DataType::Float64, true),
+                Field::new("name", DataType::Utf8, true),
+            ]),
+        },
+        TestCase {
+            case_name: "delimiter argument".to_string(),
+            args: vec![
+                path_args("semicolon/*.csv").remove(0),
+                named_arg("delimiter", Value::SingleQuotedString(";".to_string())),
+            ],
+            expected_schema: Schema::new(vec![
+                Field::new("id", DataType::Int64, true),
+                Field::new("name", DataType::Utf8, true),
+            ]),
+        },
+        TestCase {
+            case_name: "header argument".to_string(),
+            args: vec![
+                path_args("no_header/*.csv").remove(0),
+                named_arg("header", Value::Boolean(false)),
+            ],
+            expected_schema: Schema::new(vec![
+                Field::new("column_1", DataType::Int64, true),
+                Field::new("column_2", DataType::Utf8, true),
+            ]),
+        },
+    ];
+
+    for test_case in test_cases {
+        println!("test case: {}", test_case.case_name);
+
+        let schema = find_table_func_schema("read_files", &test_case.args, &conn_reg)
+            .await?
+            .expect("expected a schema");
+        assert_eq!(schema.fields(), test_case.expected_schema.fields());
+    }
+
+    // the integer prices of the first file are read as floats
+    let records = read_csv_records(&path_args("data/*.csv"), &conn_reg).await?;
+    let rows: usize = records.iter().map(|record| record.num_rows()).sum();
+    assert_eq!(rows, 3);
+    let prices = records[0]
+        .column(1)
+        .as_any()
+        .downcast_ref::<Float64Array>()
+        .expect("expected a float column");
+    assert_eq!(prices.values().to_vec(), vec![2.0, 2.5]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_read_files_csv_schemas_must_match() -> Result<()> {
+    let dir = tempdir::TempDir::new("read_files_csv_mismatch")?;
+    std::fs::create_dir_all(dir.path().join("data"))?;
+    std::fs::write(dir.path().join("data/a.csv"), "id,name\n1,small\n")?;
+    std::fs::write(dir.path().join("data/b.csv"), "id,name\nx,large\n")?;
+
+    let mut conn_reg = ConnectionRegistry::new();
+    conn_reg.register_fs(
+        "default".to_string(),
+        dir.path().to_string_lossy().to_string(),
+    );
+
+    let err = find_table_func_schema("read_files", &path_args("data/*.csv"), &conn_reg)
+        .await
+        .expect_err("expected the schemas to differ");
+    let err = err.to_string();
+    assert!(err.starts_with("schema of csv file data/"), "{}", err);
+    assert!(err.contains("id Int64, name Utf8"), "{}", err);
+    assert!(err.contains("id Utf8, name Utf8"), "{}", err);
+
+    // json lines files aren't supported
+    let err = find_table_func_schema("read_files", &path_args("data/*.jsonl"), &conn_reg)
+        .await
+        .expect_err("expected an unsupported format");
+    assert_eq!(
+        err.to_string(),
+        "read_files does not support the data format: DataFormat::JsonLines"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::{Int64Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use parquet::arrow::ArrowWriter;
+use sqlparser::ast::{Expr, FunctionArg, FunctionArgExpr, FunctionArgOperator, Ident, Value};
+
+use crate::handlers::operator_handler::operators::ConnectionRegistry;
+
+use super::schemas::find_table_func_schema;
+
+fn path_args(path: &str) -> Vec<FunctionArg> {
+    vec![FunctionArg::Unnamed(FunctionArgExpr::Expr(Expr::Value(
+        Value::SingleQuotedString(path.to_string()),
+    )))]
+}
+
+#[tokio::test]
+async fn test_read_files_schema_is_read_from_the_parquet_footer() -> Result<()> {
+    let dir = tempdir::TempDir::new("read_files_schema")?;
+    std::fs::create_dir(dir.path().join("data"))?;
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("size", DataType::Utf8, true),
+    ]));
+    let record = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int64Array::from(vec![1, 2])),
+            Arc::new(StringArray::from(vec![Some("small"), None])),
+        ],
+    )?;
+    let file = std::fs::File::create(dir.path().join("data/a.parquet"))?;
+    let mut writer = ArrowWriter::try_new(file, schema.clone(), None)?;
+    writer.write(&record)?;
+    writer.close()?;
+
+    let mut conn_reg = ConnectionRegistry::new();
+    conn_reg.add_connection(
+        "default".to_string(),
+        opendal::Scheme::Fs,
+        HashMap::from([("root".to_string(), dir.path().to_string_lossy().to_string())]),
+    );
+
+    let found = find_table_func_schema("read_files", &path_args("data/*.parquet"), &conn_reg)
+        .await?
+        .expect("expected a schema");
+    assert_eq!(found.fields(), schema.fields());
+
+    // no files match the path
+    let found =
+        find_table_func_schema("read_files", &path_args("other/*.parquet"), &conn_reg).await?;
+    assert!(found.is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_read_files_schema_uses_the_named_connection() -> Result<()> {
+    let default_dir = tempdir::TempDir::new("read_files_default")?;
+    let archive_dir = tempdir::TempDir::new("read_files_archive")?;
+    std::fs::create_dir(archive_dir.path().join("data"))?;
+
+    let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+    let record = RecordBatch::try_new(schema.clone(), vec![Arc::new(Int64Array::from(vec![1]))])?;
+    let file = std::fs::File::create(archive_dir.path().join("data/a.parquet"))?;
+    let mut writer = ArrowWriter::try_new(file, schema.clone(), None)?;
+    writer.write(&record)?;
+    writer.close()?;
+
+    let mut conn_reg = ConnectionRegistry::new();
+    conn_reg.register_fs(
+        "default".to_string(),
+        default_dir.path().to_string_lossy().to_string(),
+    );
+    conn_reg.register_fs(
+        "archive".to_string(),
+        archive_dir.path().to_string_lossy().to_string(),
+    );
+
+    // the files are only in the archive connection
+    let found =
+        find_table_func_schema("read_files", &path_args("data/*.parquet"), &conn_reg).await?;
+    assert!(found.is_none());
+
+    let found = find_table_func_schema(
+        "read_files",
+        &path_args("archive://data/*.parquet"),
+        &conn_reg,
+    )
+    .await?
+    .expect("expected a schema");
+    assert_eq!(found.fields(), schema.fields());
+
+    let mut args = path_args("archive://data/*.parquet");
+    args.push(FunctionArg::Named {
+        name: Ident::new("connection"),
+        arg: FunctionArgExpr::Expr(Expr::Value(Value::SingleQuotedString(
+            "default".to_string(),
+        ))),
+        operator: FunctionArgOperator::RightArrow,
+    });
+    let err = find_table_func_schema("read_files", &args, &conn_reg)
+        .await
+        .expect_err("expected conflicting connections");
+    assert_eq!(
+        err.to_string(),
+        "path connection archive conflicts with the connection argument default"
+    );
+
+    let err = find_table_func_schema(
+        "read_files",
+        &path_args("missing://data/*.parquet"),
+        &conn_reg,
+    )
+    .await
+    .expect_err("expected an unregistered connection");
+    assert_eq!(err.to_string(), "connection name not found: missing");
+
+    Ok(())
+}
@@ -0,0 +1,110 @@
+use anyhow::Result;
+use arrow::array::{ArrayRef, BooleanArray, Float64Array, Int64Array, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use sqlparser::ast::FunctionArg;
+use std::sync::Arc;
+
+use crate::planner::{LogicalPlanNodeType, LogicalPlanner};
+
+use super::values_task::values_record;
+
+// the args the logical planner passes to the values table func
+fn values_args(query: &str) -> Result<Vec<FunctionArg>> {
+    let plan = LogicalPlanner::new(query.to_string()).build()?;
+    for node in plan.get_all_nodes() {
+        if let LogicalPlanNodeType::TableFunc { name, args, .. } = node.node {
+            assert_eq!(name, "values");
+            return Ok(args);
+        }
+    }
+    Err(anyhow::Error::msg("expected a values table func"))
+}
+
+#[test]
+fn test_values_record() -> Result<()> {
+    struct TestCase {
+        case_name: String,
+        query: String,
+        expected_schema: Schema,
+        expected_columns: Vec<ArrayRef>,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            case_name: "column names from the alias".to_string(),
+            query: "select * from values((1, 'a'), (2, 'b')) as t(id, name)".to_string(),
+            expected_schema: Schema::new(vec![
+                Field::new("id", DataType::Int64, false),
+                Field::new("name", DataType::Utf8, false),
+            ]),
+            expected_columns: vec![
+                Arc::new(Int64Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec!["a", "b"])),
+            ],
+        },
+        TestCase {
+            case_name: "mixed ints and floats are floats".to_string(),
+            query: "select * from values((1, true), (-2.5, null))".to_string(),
+            expected_schema: Schema::new(vec![
+                Field::new("column1", DataType::Float64, false),
+                Field::new("column2", DataType::Boolean, true),
+            ]),
+            expected_columns: vec![
+                Arc::new(Float64Array::from(vec![1.0, -2.5])),
+                Arc::new(BooleanArray::from(vec![Some(true), None])),
+            ],
+        },
+        TestCase {
+            case_name: "standard values syntax".to_string(),
+            query: "select * from (values (1), (-3)) as t(id)".to_string(),
+            expected_schema: Schema::new(vec![Field::new("id", DataType::Int64, false)]),
+            expected_columns: vec![Arc::new(Int64Array::from(vec![1, -3]))],
+        },
+    ];
+
+    for test_case in test_cases {
+        println!("test case: {}", test_case.case_name);
+
+        let record = values_record(&values_args(&test_case.query)?)?;
+        assert_eq!(record.schema().as_ref(), &test_case.expected_schema);
+        assert_eq!(record.columns(), test_case.expected_columns.as_slice());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_values_record_errors() -> Result<()> {
+    struct TestCase {
+        case_name: String,
+        query: String,
+        expected_error: String,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            case_name: "rows of different lengths".to_string(),
+            query: "select * from values((1, 'a'), (2))".to_string(),
+            expected_error: "row 1 has 1 values but the first row has 2".to_string(),
+        },
+        TestCase {
+            case_name: "strings and numbers".to_string(),
+            query: "select * from values((1), ('a')) as t(id)".to_string(),
+            expected_error: "column id has values of more than one type".to_string(),
+        },
+        TestCase {
+            case_name: "too many column names".to_string(),
+            query: "select * from values((1), (2)) as t(id, name)".to_string(),
+            expected_error: "2 column names were given for 1 columns".to_string(),
+        },
+    ];
+
+    for test_case in test_cases {
+        println!("test case: {}", test_case.case_name);
+
+        let err = values_record(&values_args(&test_case.query)?).unwrap_err();
+        assert_eq!(err.to_string(), test_case.expected_error);
+    }
+
+    Ok(())
+}
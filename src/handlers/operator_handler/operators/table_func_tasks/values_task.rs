@@ -0,0 +1,471 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Error, Result};
+use arrow::array::{ArrayRef, BooleanArray, Float64Array, Int64Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use sqlparser::ast::{Expr, FunctionArg, FunctionArgExpr, Ident, UnaryOperator, Value};
+use thiserror::Error;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error};
+
+use crate::handlers::message_handler::messages;
+use crate::handlers::message_handler::messages::message::{Message, MessageName};
+use crate::handlers::message_handler::{MessageRegistry, Pipe};
+use crate::handlers::message_router_handler::MessageConsumer;
+use crate::handlers::operator_handler::operator_handler_state::OperatorInstanceConfig;
+use crate::handlers::operator_handler::operators::operator_task_trackers::RestrictedOperatorTaskTracker;
+use crate::handlers::operator_handler::operators::requests::{
+    IdentifyExchangeRequest, SendRecordRequest,
+};
+use crate::handlers::operator_handler::operators::traits::{TableFuncSyntaxValidator, TaskBuilder};
+use crate::handlers::operator_handler::operators::{record_utils, ConnectionRegistry};
+
+use super::config::TableFuncConfig;
+
+#[derive(Debug, Error)]
+pub enum ValuesConfigError {
+    #[error("invalid argument {0}: {1}")]
+    InvalidArgument(usize, &'static str),
+    #[error("values requires at least one row")]
+    NoRows,
+    #[error("row {0} has {1} values but the first row has {2}")]
+    RowLengthMismatch(usize, usize, usize),
+    #[error("{0} column names were given for {1} columns")]
+    ColumnNamesLengthMismatch(usize, usize),
+    #[error("column {0} has values of more than one type")]
+    MixedColumnTypes(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct ValuesSyntaxValidator {}
+
+impl ValuesSyntaxValidator {
+    pub fn new() -> ValuesSyntaxValidator {
+        ValuesSyntaxValidator {}
+    }
+}
+
+impl TableFuncSyntaxValidator for ValuesSyntaxValidator {
+    fn valid(&self, config: &TableFuncConfig) -> bool {
+        values_record(&config.args).is_ok()
+    }
+    fn implements_func_name(&self) -> String {
+        "values".to_string()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ValuesLiteral {
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Bool(bool),
+    Null,
+}
+
+impl ValuesLiteral {
+    fn parse(idx: usize, expr: &Expr) -> Result<ValuesLiteral> {
+        match expr {
+            Expr::Value(Value::Number(val, _)) => Self::parse_number(idx, val, false),
+            Expr::UnaryOp {
+                op: UnaryOperator::Minus,
+                expr,
+            } => match expr.as_ref() {
+                Expr::Value(Value::Number(val, _)) => Self::parse_number(idx, val, true),
+                _ => Err(ValuesConfigError::InvalidArgument(idx, "literal").into()),
+            },
+            Expr::Value(Value::SingleQuotedString(val))
+            | Expr::Value(Value::DoubleQuotedString(val)) => Ok(ValuesLiteral::Str(val.clone())),
+            Expr::Value(Value::Boolean(val)) => Ok(ValuesLiteral::Bool(*val)),
+            Expr::Value(Value::Null) => Ok(ValuesLiteral::Null),
+            Expr::Nested(expr) => Self::parse(idx, expr),
+            _ => Err(ValuesConfigError::InvalidArgument(idx, "literal").into()),
+        }
+    }
+
+    fn parse_number(idx: usize, val: &str, negative: bool) -> Result<ValuesLiteral> {
+        let val = if negative {
+            format!("-{}", val)
+        } else {
+            val.to_string()
+        };
+        if let Ok(val) = val.parse::<i64>() {
+            return Ok(ValuesLiteral::Int(val));
+        }
+        match val.parse::<f64>() {
+            Ok(val) => Ok(ValuesLiteral::Float(val)),
+            Err(_) => Err(ValuesConfigError::InvalidArgument(idx, "number").into()),
+        }
+    }
+
+    fn data_type(&self) -> Option<DataType> {
+        match self {
+            ValuesLiteral::Int(_) => Some(DataType::Int64),
+            ValuesLiteral::Float(_) => Some(DataType::Float64),
+            ValuesLiteral::Str(_) => Some(DataType::Utf8),
+            ValuesLiteral::Bool(_) => Some(DataType::Boolean),
+            ValuesLiteral::Null => None,
+        }
+    }
+}
+
+// the literal rows and the optional column names
+type ValuesArgs = (Vec<Vec<ValuesLiteral>>, Option<Vec<String>>);
+
+// Each unnamed arg is a tuple holding one row and the optional columns
+// arg holds the names of the columns.
+fn parse_args(args: &[FunctionArg]) -> Result<ValuesArgs> {
+    let mut rows: Vec<Vec<ValuesLiteral>> = Vec::new();
+    let mut column_names: Option<Vec<String>> = None;
+    for (idx, arg) in args.iter().enumerate() {
+        match arg {
+            FunctionArg::Unnamed(FunctionArgExpr::Expr(Expr::Tuple(exprs))) => {
+                if column_names.is_some() {
+                    return Err(ValuesConfigError::InvalidArgument(idx, "row").into());
+                }
+                let row = exprs
+                    .iter()
+                    .map(|expr| ValuesLiteral::parse(idx, expr))
+                    .collect::<Result<Vec<ValuesLiteral>>>()?;
+                if let Some(first_row) = rows.first() {
+                    if first_row.len() != row.len() {
+                        return Err(ValuesConfigError::RowLengthMismatch(
+                            idx,
+                            row.len(),
+                            first_row.len(),
+                        )
+                        .into());
+                    }
+                }
+                rows.push(row);
+            }
+            FunctionArg::Named {
+                name: Ident { value: name, .. },
+                arg: FunctionArgExpr::Expr(Expr::Tuple(exprs)),
+                ..
+            } if name == "columns" && column_names.is_none() => {
+                let names = exprs
+                    .iter()
+                    .map(|expr| match expr {
+                        Expr::Identifier(ident) => Ok(ident.value.clone()),
+                        _ => Err(ValuesConfigError::InvalidArgument(idx, "columns").into()),
+                    })
+                    .collect::<Result<Vec<String>>>()?;
+                column_names = Some(names);
+            }
+            _ => {
+                return Err(ValuesConfigError::InvalidArgument(idx, "row").into());
+            }
+        }
+    }
+    Ok((rows, column_names))
+}
+
+// A column with both integers and floats is a float column; a column
+// holding only nulls is a string column.
+fn column_data_type(name: &str, values: &[&ValuesLiteral]) -> Result<DataType> {
+    let mut data_type: Option<DataType> = None;
+    for value in values {
+        data_type = match (data_type, value.data_type()) {
+            (None, value_type) => value_type,
+            (Some(col_type), None) => Some(col_type),
+            (Some(col_type), Some(value_type)) if col_type == value_type => Some(col_type),
+            (Some(DataType::Int64), Some(DataType::Float64))
+            | (Some(DataType::Float64), Some(DataType::Int64)) => Some(DataType::Float64),
+            _ => {
+                return Err(ValuesConfigError::MixedColumnTypes(name.to_string()).into());
+            }
+        };
+    }
+    Ok(data_type.unwrap_or(DataType::Utf8))
+}
+
+fn column_array(data_type: &DataType, values: &[&ValuesLiteral]) -> ArrayRef {
+    match data_type {
+        DataType::Int64 => Arc::new(Int64Array::from_iter(values.iter().map(
+            |value| match value {
+                ValuesLiteral::Int(val) => Some(*val),
+                _ => None,
+            },
+        ))),
+        DataType::Float64 => Arc::new(Float64Array::from_iter(values.iter().map(
+            |value| match value {
+                ValuesLiteral::Int(val) => Some(*val as f64),
+                ValuesLiteral::Float(val) => Some(*val),
+                _ => None,
+            },
+        ))),
+        DataType::Boolean => Arc::new(BooleanArray::from_iter(values.iter().map(
+            |value| match value {
+                ValuesLiteral::Bool(val) => Some(*val),
+                _ => None,
+            },
+        ))),
+        _ => Arc::new(StringArray::from_iter(values.iter().map(
+            |value| match value {
+                ValuesLiteral::Str(val) => Some(val.clone()),
+                _ => None,
+            },
+        ))),
+    }
+}
+
+// Builds the single record holding all of the rows. Columns are named
+// column1, column2, ... unless the names are given.
+pub fn values_record(args: &[FunctionArg]) -> Result<RecordBatch> {
+    let (rows, column_names) = parse_args(args)?;
+    let num_columns = match rows.first() {
+        Some(row) => row.len(),
+        None => {
+            return Err(ValuesConfigError::NoRows.into());
+        }
+    };
+    let column_names = match column_names {
+        Some(names) if names.len() != num_columns => {
+            return Err(
+                ValuesConfigError::ColumnNamesLengthMismatch(names.len(), num_columns).into(),
+            );
+        }
+        Some(names) => names,
+        None => (1..=num_columns)
+            .map(|idx| format!("column{}", idx))
+            .collect(),
+    };
+
+    let mut fields = Vec::new();
+    let mut columns = Vec::new();
+    for (col_idx, name) in column_names.iter().enumerate() {
+        let values: Vec<&ValuesLiteral> = rows.iter().map(|row| &row[col_idx]).collect();
+        let data_type = column_data_type(name, &values)?;
+        let nullable = values.iter().any(|value| **value == ValuesLiteral::Null);
+        columns.push(column_array(&data_type, &values));
+        fields.push(Field::new(name, data_type, nullable));
+    }
+
+    Ok(RecordBatch::try_new(
+        Arc::new(Schema::new(fields)),
+        columns,
+    )?)
+}
+
+pub fn values_schema(args: &[FunctionArg]) -> Result<Option<SchemaRef>> {
+    Ok(Some(values_record(args)?.schema()))
+}
+
+#[derive(Debug)]
+pub struct ValuesTask {
+    operator_instance_config: OperatorInstanceConfig,
+    record: RecordBatch,
+
+    operator_pipe: Pipe,
+    msg_reg: Arc<MessageRegistry>,
+
+    exchange_worker_id: Option<u128>,
+    exchange_operator_instance_id: Option<u128>,
+    record_id: u64,
+}
+
+impl ValuesTask {
+    pub fn new(
+        op_in_config: OperatorInstanceConfig,
+        record: RecordBatch,
+        operator_pipe: Pipe,
+        msg_reg: Arc<MessageRegistry>,
+    ) -> ValuesTask {
+        ValuesTask {
+            operator_instance_config: op_in_config,
+            record,
+            operator_pipe,
+            msg_reg,
+            exchange_worker_id: None,
+            exchange_operator_instance_id: None,
+            record_id: 0,
+        }
+    }
+
+    pub fn consumer(&self) -> Box<dyn MessageConsumer> {
+        Box::new(ValuesConsumer {
+            msg_reg: self.msg_reg.clone(),
+        })
+    }
+
+    pub async fn async_main(&mut self, ct: CancellationToken) -> Result<()> {
+        debug!(
+            operator_task = self
+                .operator_instance_config
+                .operator
+                .operator_type
+                .task_name(),
+            operator_id = self.operator_instance_config.operator.id,
+            operator_instance_id = self.operator_instance_config.id,
+            "started task",
+        );
+
+        if !ct.is_cancelled() {
+            tokio::select! {
+                res = self.send_record(self.record.clone()) => {
+                    res.context("unable to send record to the exchange")?;
+                }
+                _ = ct.cancelled() => (),
+            }
+        }
+
+        debug!(
+            operator_task = self
+                .operator_instance_config
+                .operator
+                .operator_type
+                .task_name(),
+            operator_id = self.operator_instance_config.operator.id,
+            operator_instance_id = self.operator_instance_config.id,
+            "closed task",
+        );
+
+        Ok(())
+    }
+
+    async fn send_record(&mut self, record: RecordBatch) -> Result<()> {
+        if self.exchange_worker_id.is_none() {
+            let resp = IdentifyExchangeRequest::request_outbound_exchange(
+                &self.operator_instance_config,
+                &mut self.operator_pipe,
+                self.msg_reg.clone(),
+            )
+            .await?;
+            self.exchange_operator_instance_id = Some(resp.exchange_operator_instance_id);
+            self.exchange_worker_id = Some(resp.exchange_worker_id);
+        }
+
+        assert!(self.exchange_worker_id.is_some());
+
+        let msg_record_id = self.next_record_id();
+        let table_aliases = record_utils::get_record_table_aliases(
+            &self.operator_instance_config.operator.operator_type,
+            &record,
+        )?;
+
+        SendRecordRequest::send_record_request(
+            msg_record_id,
+            record,
+            table_aliases,
+            self.exchange_operator_instance_id.unwrap(),
+            self.exchange_worker_id.unwrap(),
+            &mut self.operator_pipe,
+            self.msg_reg.clone(),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    fn next_record_id(&mut self) -> u64 {
+        let record_id = self.record_id;
+        self.record_id += 1;
+        record_id
+    }
+}
+
+//////////////////////////////////////////////////////
+// Table Func Producer Builder
+
+#[derive(Debug, Clone)]
+pub struct ValuesTaskBuilder {}
+
+impl ValuesTaskBuilder {
+    pub fn new() -> ValuesTaskBuilder {
+        ValuesTaskBuilder {}
+    }
+}
+
+impl TaskBuilder for ValuesTaskBuilder {
+    fn build(
+        &self,
+        op_in_config: OperatorInstanceConfig,
+        operator_pipe: Pipe,
+        msg_reg: Arc<MessageRegistry>,
+        _conn_reg: Arc<ConnectionRegistry>,
+        tt: &mut RestrictedOperatorTaskTracker,
+        ct: CancellationToken,
+    ) -> Result<(
+        tokio::sync::oneshot::Receiver<Option<Error>>,
+        Box<dyn MessageConsumer>,
+    )> {
+        let table_func_config = TableFuncConfig::try_from(&op_in_config)?;
+        let record = values_record(&table_func_config.args)?;
+        let mut op = ValuesTask::new(op_in_config, record, operator_pipe, msg_reg.clone());
+
+        let consumer = op.consumer();
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        tt.spawn(async move {
+            if let Err(err) = op.async_main(ct).await {
+                error!("{:?}", err);
+                if let Err(err_send) = tx.send(Some(err)) {
+                    error!("{:?}", err_send);
+                }
+            } else {
+                if let Err(err_send) = tx.send(None) {
+                    error!("{:?}", err_send);
+                }
+            }
+        })?;
+
+        Ok((rx, consumer))
+    }
+}
+
+//////////////////////////////////////////////////////
+// Message Consumer
+
+#[derive(Debug, Clone)]
+pub struct ValuesConsumer {
+    msg_reg: Arc<MessageRegistry>,
+}
+
+impl MessageConsumer for ValuesConsumer {
+    fn consumes_message(&self, msg: &Message) -> bool {
+        match msg.msg.msg_name() {
+            MessageName::Ping => match self.msg_reg.try_cast_msg::<messages::common::Ping>(msg) {
+                Ok(messages::common::Ping::Ping) => false,
+                Ok(messages::common::Ping::Pong) => true,
+                Err(err) => {
+                    error!("{:?}", err);
+                    false
+                }
+            },
+            MessageName::ExchangeRequests => {
+                match self
+                    .msg_reg
+                    .try_cast_msg::<messages::exchange::ExchangeRequests>(msg)
+                {
+                    Ok(messages::exchange::ExchangeRequests::SendRecordResponse { .. }) => true,
+                    Ok(messages::exchange::ExchangeRequests::SendRecordRequest { .. }) => false,
+                    Err(err) => {
+                        error!("{:?}", err);
+                        false
+                    }
+                    _ => false,
+                }
+            }
+            MessageName::QueryHandlerRequests => {
+                match self
+                    .msg_reg
+                    .try_cast_msg::<messages::query::QueryHandlerRequests>(msg)
+                {
+                    Ok(messages::query::QueryHandlerRequests::ListOperatorInstancesResponse {
+                        ..
+                    }) => true,
+                    Ok(messages::query::QueryHandlerRequests::ListOperatorInstancesRequest {
+                        ..
+                    }) => false,
+                    Err(err) => {
+                        error!("{:?}", err);
+                        false
+                    }
+                }
+            }
+            MessageName::CommonGenericResponse => true,
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use anyhow::Result;
+
+use super::connection_registry::{ConnectionRegistry, S3Config};
+
+#[test]
+fn test_register_s3() -> Result<()> {
+    struct TestCase {
+        case_name: String,
+        s3_config: S3Config,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            case_name: "credentials and endpoint".to_string(),
+            s3_config: S3Config {
+                bucket: "results".to_string(),
+                region: Some("us-east-1".to_string()),
+                endpoint: Some("http://127.0.0.1:9000".to_string()),
+                access_key: Some("access".to_string()),
+                secret_key: Some("secret".to_string()),
+            },
+        },
+        TestCase {
+            case_name: "credentials from the environment".to_string(),
+            s3_config: S3Config {
+                bucket: "results".to_string(),
+                region: Some("us-east-1".to_string()),
+                endpoint: None,
+                access_key: None,
+                secret_key: None,
+            },
+        },
+    ];
+
+    for test_case in test_cases {
+        println!("test case: {}", test_case.case_name);
+
+        let mut conn_reg = ConnectionRegistry::new();
+        conn_reg.register_s3("default".to_string(), test_case.s3_config);
+
+        let conn = conn_reg.get_operator("default")?;
+        assert_eq!(conn.info().scheme(), opendal::Scheme::S3);
+        assert_eq!(conn.info().name(), "results");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_registering_a_name_again_replaces_the_connection() -> Result<()> {
+    let mut conn_reg = ConnectionRegistry::new();
+    conn_reg.register_fs("default".to_string(), "/tmp/chapterhouse".to_string());
+    conn_reg.register_s3(
+        "default".to_string(),
+        S3Config {
+            bucket: "results".to_string(),
+            region: Some("us-east-1".to_string()),
+            endpoint: None,
+            access_key: None,
+            secret_key: None,
+        },
+    );
+
+    let conn = conn_reg.get_operator("default")?;
+    assert_eq!(conn.info().scheme(), opendal::Scheme::S3);
+
+    Ok(())
+}
+
+#[test]
+fn test_s3_config_debug_hides_the_credentials() {
+    let s3_config = S3Config {
+        bucket: "results".to_string(),
+        region: None,
+        endpoint: None,
+        access_key: Some("access".to_string()),
+        secret_key: Some("secret".to_string()),
+    };
+
+    let debug = format!("{:?}", s3_config);
+    assert!(debug.contains("results"));
+    assert!(!debug.contains("access"));
+    assert!(!debug.contains("secret"));
+}
+
+#[tokio::test]
+async fn test_memory_connections_share_their_files() -> Result<()> {
+    let mut conn_reg = ConnectionRegistry::new();
+    conn_reg.register_memory("default".to_string());
+    let worker_conn_reg = conn_reg.clone();
+
+    conn_reg
+        .get_operator("default")?
+        .write("results/a.txt", "a")
+        .await?;
+    let data = worker_conn_reg
+        .get_operator("default")?
+        .read("results/a.txt")
+        .await?;
+    assert_eq!(data.to_vec(), b"a".to_vec());
+
+    // registering the connection again starts with no files
+    conn_reg.register_memory("default".to_string());
+    assert!(
+        !conn_reg
+            .get_operator("default")?
+            .exists("results/a.txt")
+            .await?
+    );
+
+    Ok(())
+}
@@ -0,0 +1,497 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use arrow::array::{Int64Array, RecordBatch};
+use arrow::datatypes::{DataType, Field, Schema};
+
+use super::exchange_operator::{RecordData, RecordPool, RecordPoolConfig};
+use super::record_spiller::{exchange_spill_dir, RecordSpiller};
+use super::ConnectionRegistry;
+use crate::planner::ExchangeDistribution;
+
+const OPERATOR_ID: &str = "operator_p1_producer";
+const PRODUCER_INSTANCE_ID: u128 = 10;
+
+fn build_record(id: i64) -> Result<Arc<RecordBatch>> {
+    let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+    let record = RecordBatch::try_new(schema, vec![Arc::new(Int64Array::from(vec![id]))])?;
+    Ok(Arc::new(record))
+}
+
+fn build_record_pool(max_heartbeat_interval: Duration) -> Result<RecordPool> {
+    let mut pool = RecordPool::new(
+        vec![OPERATOR_ID.to_string()],
+        RecordPoolConfig {
+            max_heartbeat_interval: chrono::Duration::from_std(max_heartbeat_interval)?,
+            ordered: false,
+            distribution: ExchangeDistribution::RoundRobin,
+        },
+    );
+    pool.add_record(0, build_record(0)?, vec![], PRODUCER_INSTANCE_ID, 0)?;
+    Ok(pool)
+}
+
+fn next_record_id(pool: &mut RecordPool, operator_instance_id: u128) -> Result<Option<u64>> {
+    let rec = pool.get_next_record(&OPERATOR_ID.to_string(), operator_instance_id)?;
+    Ok(rec.map(|(record_id, _, _)| record_id))
+}
+
+// completes the next record and returns the ids it holds
+fn complete_next_record(
+    pool: &mut RecordPool,
+    operator_instance_id: u128,
+) -> Result<Option<Vec<i64>>> {
+    let operator_id = OPERATOR_ID.to_string();
+    let (record_id, record) = match pool.get_next_record(&operator_id, operator_instance_id)? {
+        Some((record_id, RecordData::InMemory(record), _)) => (record_id, record),
+        Some((_, RecordData::Spilled(_), _)) => panic!("the record should not be spilled"),
+        None => return Ok(None),
+    };
+    pool.operator_completed_record_processing(&operator_id, operator_instance_id, &record_id)?;
+    let ids = record
+        .column(0)
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .expect("id column should be an Int64Array");
+    Ok(Some(ids.values().to_vec()))
+}
+
+#[tokio::test]
+async fn test_slow_consumer_sending_heartbeats_keeps_the_record() -> Result<()> {
+    let mut pool = build_record_pool(Duration::from_millis(200))?;
+    assert_eq!(next_record_id(&mut pool, 1)?, Some(0));
+
+    // the consumer takes longer than the timeout to process the record
+    // but sends a heartbeat before each timeout passes
+    for _ in 0..3 {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        pool.update_reserved_record_heartbeat(&OPERATOR_ID.to_string(), 0);
+        pool.maintain()?;
+        assert_eq!(next_record_id(&mut pool, 1)?, None);
+    }
+
+    pool.operator_completed_record_processing(&OPERATOR_ID.to_string(), 1, &0)?;
+    pool.maintain()?;
+    assert_eq!(next_record_id(&mut pool, 1)?, None);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_record_is_requeued_after_the_heartbeat_goes_stale() -> Result<()> {
+    let mut pool = build_record_pool(Duration::from_millis(100))?;
+    assert_eq!(next_record_id(&mut pool, 1)?, Some(0));
+    pool.update_reserved_record_heartbeat(&OPERATOR_ID.to_string(), 0);
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    pool.maintain()?;
+    assert_eq!(next_record_id(&mut pool, 1)?, Some(0));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_consumer_finishing_a_requeued_record() -> Result<()> {
+    let mut pool = build_record_pool(Duration::from_millis(100))?;
+    assert_eq!(next_record_id(&mut pool, 1)?, Some(0));
+
+    // the consumer never sent a heartbeat
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    pool.maintain()?;
+
+    // the record isn't given to another consumer once the first one
+    // finishes it
+    pool.operator_completed_record_processing(&OPERATOR_ID.to_string(), 1, &0)?;
+    assert_eq!(next_record_id(&mut pool, 1)?, None);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_record_is_given_to_another_consumer_when_never_completed() -> Result<()> {
+    let operator_id = OPERATOR_ID.to_string();
+    let mut pool = build_record_pool(Duration::from_millis(100))?;
+
+    // the first consumer takes the record and dies without completing it
+    assert_eq!(next_record_id(&mut pool, 1)?, Some(0));
+    assert_eq!(next_record_id(&mut pool, 2)?, None);
+    assert!(pool.has_reserved_records(&operator_id, 2));
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    pool.maintain()?;
+    assert_eq!(next_record_id(&mut pool, 2)?, Some(0));
+
+    pool.operator_completed_record_processing(&operator_id, 2, &0)?;
+    assert!(!pool.has_reserved_records(&operator_id, 2));
+    assert_eq!(next_record_id(&mut pool, 2)?, None);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_completion_from_the_first_consumer_after_the_record_was_reassigned() -> Result<()> {
+    let operator_id = OPERATOR_ID.to_string();
+    let mut pool = build_record_pool(Duration::from_millis(100))?;
+
+    assert_eq!(next_record_id(&mut pool, 1)?, Some(0));
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    pool.maintain()?;
+    assert_eq!(next_record_id(&mut pool, 2)?, Some(0));
+
+    // the slow first consumer completes the record before the second one
+    pool.operator_completed_record_processing(&operator_id, 1, &0)?;
+    assert!(!pool.has_reserved_records(&operator_id, 2));
+    pool.operator_completed_record_processing(&operator_id, 2, &0)?;
+    assert_eq!(next_record_id(&mut pool, 2)?, None);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ordered_pool_delivers_records_in_sequence_order() -> Result<()> {
+    let operator_id = OPERATOR_ID.to_string();
+    let num_records: u64 = 8;
+
+    // the records are sent concurrently so they arrive out of order
+    let (sender, mut receiver) = tokio::sync::mpsc::channel::<(u64, Arc<RecordBatch>)>(16);
+    for sequence_number in 0..num_records {
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            let delay = (num_records - sequence_number) * 10 + (sequence_number % 3) * 15;
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+            let record = build_record(sequence_number as i64).unwrap();
+            sender.send((sequence_number, record)).await.unwrap();
+        });
+    }
+    drop(sender);
+
+    let mut ordered_pool = RecordPool::new(
+        vec![operator_id.clone()],
+        RecordPoolConfig {
+            max_heartbeat_interval: chrono::Duration::seconds(10),
+            ordered: true,
+            distribution: ExchangeDistribution::RoundRobin,
+        },
+    );
+    let mut unordered_pool = RecordPool::new(
+        vec![operator_id.clone()],
+        RecordPoolConfig {
+            max_heartbeat_interval: chrono::Duration::seconds(10),
+            ordered: false,
+            distribution: ExchangeDistribution::RoundRobin,
+        },
+    );
+    let mut arrival_order: Vec<u64> = Vec::new();
+    while let Some((sequence_number, record)) = receiver.recv().await {
+        arrival_order.push(sequence_number);
+        for pool in [&mut ordered_pool, &mut unordered_pool] {
+            pool.add_record(
+                sequence_number,
+                record.clone(),
+                vec![],
+                PRODUCER_INSTANCE_ID,
+                sequence_number,
+            )?;
+        }
+    }
+    let expected_order: Vec<u64> = (0..num_records).collect();
+    assert_ne!(arrival_order, expected_order);
+
+    // each record holds its sequence number
+    let mut ordered_delivery: Vec<u64> = Vec::new();
+    let mut unordered_delivery: Vec<u64> = Vec::new();
+    for _ in 0..num_records {
+        if let Some(ids) = complete_next_record(&mut ordered_pool, 1)? {
+            ordered_delivery.push(ids[0] as u64);
+        }
+        if let Some(ids) = complete_next_record(&mut unordered_pool, 1)? {
+            unordered_delivery.push(ids[0] as u64);
+        }
+    }
+    assert_eq!(ordered_delivery, expected_order);
+    assert_eq!(unordered_delivery, arrival_order);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ordered_pool_gives_records_to_one_consumer_at_a_time() -> Result<()> {
+    let operator_id = OPERATOR_ID.to_string();
+    let mut pool = RecordPool::new(
+        vec![operator_id.clone()],
+        RecordPoolConfig {
+            max_heartbeat_interval: chrono::Duration::seconds(10),
+            ordered: true,
+            distribution: ExchangeDistribution::RoundRobin,
+        },
+    );
+    for sequence_number in 0..3 {
+        pool.add_record(
+            sequence_number,
+            build_record(sequence_number as i64)?,
+            vec![],
+            PRODUCER_INSTANCE_ID,
+            sequence_number,
+        )?;
+    }
+
+    assert_eq!(next_record_id(&mut pool, 1)?, Some(0));
+    assert_eq!(next_record_id(&mut pool, 1)?, Some(1));
+    assert_eq!(next_record_id(&mut pool, 2)?, None);
+
+    pool.operator_completed_record_processing(&operator_id, 1, &0)?;
+    pool.operator_completed_record_processing(&operator_id, 1, &1)?;
+    assert_eq!(next_record_id(&mut pool, 2)?, Some(2));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_records_past_the_max_buffered_bytes_are_spilled() -> Result<()> {
+    let dir = tempdir::TempDir::new("exchange_spill")?;
+    let mut conn_reg = ConnectionRegistry::new();
+    conn_reg.add_connection(
+        "default".to_string(),
+        opendal::Scheme::Fs,
+        HashMap::from([("root".to_string(), dir.path().to_string_lossy().to_string())]),
+    );
+    let conn = conn_reg.get_operator("default")?;
+    let spill_dir = exchange_spill_dir(1, 2);
+    let mut spiller = RecordSpiller::new(conn.clone(), spill_dir.clone());
+
+    let operator_id = OPERATOR_ID.to_string();
+    let mut pool = RecordPool::new(
+        vec![operator_id.clone()],
+        RecordPoolConfig {
+            max_heartbeat_interval: chrono::Duration::seconds(10),
+            ordered: false,
+            distribution: ExchangeDistribution::RoundRobin,
+        },
+    );
+
+    // the consumer hasn't requested any records so only two of them
+    // stay in memory
+    let max_buffered_bytes = build_record(0)?.get_array_memory_size() * 2;
+    for record_id in 0..5 {
+        pool.add_record(
+            record_id,
+            build_record(record_id as i64)?,
+            vec![],
+            PRODUCER_INSTANCE_ID,
+            record_id,
+        )?;
+        for (record_id, record) in pool.records_to_spill(max_buffered_bytes) {
+            let spill_path = spiller.spill(&record).await?;
+            pool.mark_record_spilled(record_id, spill_path);
+        }
+        assert!(pool.buffered_bytes() <= max_buffered_bytes);
+    }
+    let spill_files = conn
+        .list(&spill_dir)
+        .await?
+        .into_iter()
+        .filter(|entry| entry.path().ends_with(".parquet"))
+        .count();
+    assert_eq!(spill_files, 3);
+
+    let mut values: Vec<i64> = Vec::new();
+    while let Some((record_id, record, _)) = pool.get_next_record(&operator_id, 1)? {
+        let record = match record {
+            RecordData::InMemory(record) => record,
+            RecordData::Spilled(spill_path) => Arc::new(spiller.read(&spill_path).await?),
+        };
+        let ids = record
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .expect("id column should be an Int64Array");
+        values.extend(ids.values().iter());
+
+        if let Some(spill_path) =
+            pool.operator_completed_record_processing(&operator_id, 1, &record_id)?
+        {
+            spiller.remove(&spill_path).await?;
+        }
+    }
+    assert_eq!(values, vec![0, 1, 2, 3, 4]);
+    assert_eq!(pool.buffered_bytes(), 0);
+
+    spiller.cleanup().await?;
+    assert!(!dir.path().join("exchange_spill/00000000-0000-0000-0000-000000000001/00000000-0000-0000-0000-000000000002").exists());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_broadcast_pool_gives_every_record_to_each_consumer() -> Result<()> {
+    let operator_id = OPERATOR_ID.to_string();
+    let mut pool = RecordPool::new(
+        vec![operator_id.clone()],
+        RecordPoolConfig {
+            max_heartbeat_interval: chrono::Duration::seconds(10),
+            ordered: false,
+            distribution: ExchangeDistribution::Broadcast {
+                consumer_instances: 2,
+            },
+        },
+    );
+    for record_id in 0..2 {
+        pool.add_record(
+            record_id,
+            build_record(record_id as i64)?,
+            vec![],
+            PRODUCER_INSTANCE_ID,
+            record_id,
+        )?;
+    }
+
+    // the first consumer reads every record before the second one
+    // requests any of them
+    for record_id in 0..2 {
+        assert_eq!(next_record_id(&mut pool, 1)?, Some(record_id));
+        pool.operator_completed_record_processing(&operator_id, 1, &record_id)?;
+    }
+    assert_eq!(next_record_id(&mut pool, 1)?, None);
+
+    pool.add_record(2, build_record(2)?, vec![], PRODUCER_INSTANCE_ID, 2)?;
+    assert_eq!(next_record_id(&mut pool, 1)?, Some(2));
+    pool.operator_completed_record_processing(&operator_id, 1, &2)?;
+
+    let mut record_ids: Vec<u64> = Vec::new();
+    while let Some(record_id) = next_record_id(&mut pool, 2)? {
+        assert!(pool.has_reserved_records(&operator_id, 2));
+        pool.operator_completed_record_processing(&operator_id, 2, &record_id)?;
+        record_ids.push(record_id);
+    }
+    assert_eq!(record_ids, vec![0, 1, 2]);
+
+    // the records are removed once both consumers processed them
+    assert_eq!(pool.buffered_bytes(), 0);
+    assert!(next_record_id(&mut pool, 3).is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_hash_partitioned_pool_gives_each_key_to_one_consumer() -> Result<()> {
+    let operator_id = OPERATOR_ID.to_string();
+    let num_partitions = 3;
+    let mut pool = RecordPool::new(
+        vec![operator_id.clone()],
+        RecordPoolConfig {
+            max_heartbeat_interval: chrono::Duration::seconds(10),
+            ordered: false,
+            distribution: ExchangeDistribution::HashPartition {
+                keys: vec!["id".to_string()],
+                num_partitions,
+            },
+        },
+    );
+    let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+    for record_id in 0..4 {
+        let ids: Vec<i64> = (0..20).map(|id| id % 7).collect();
+        let record = RecordBatch::try_new(schema.clone(), vec![Arc::new(Int64Array::from(ids))])?;
+        pool.add_record(
+            record_id,
+            Arc::new(record),
+            vec![],
+            PRODUCER_INSTANCE_ID,
+            record_id,
+        )?;
+    }
+
+    let mut consumer_keys: Vec<Vec<i64>> = Vec::new();
+    for operator_instance_id in 0..num_partitions as u128 {
+        let mut keys: Vec<i64> = Vec::new();
+        while let Some((record_id, record, _)) =
+            pool.get_next_record(&operator_id, operator_instance_id)?
+        {
+            let record = match record {
+                RecordData::InMemory(record) => record,
+                RecordData::Spilled(_) => panic!("the record should not be spilled"),
+            };
+            let ids = record
+                .column(0)
+                .as_any()
+                .downcast_ref::<Int64Array>()
+                .expect("id column should be an Int64Array");
+            keys.extend(ids.values().iter());
+            pool.operator_completed_record_processing(
+                &operator_id,
+                operator_instance_id,
+                &record_id,
+            )?;
+        }
+        keys.sort();
+        keys.dedup();
+        consumer_keys.push(keys);
+    }
+
+    // every row is delivered and each key lands on a single consumer
+    let mut all_keys: Vec<i64> = consumer_keys.concat();
+    all_keys.sort();
+    assert_eq!(all_keys, (0..7).collect::<Vec<i64>>());
+    assert_eq!(pool.buffered_bytes(), 0);
+    assert!(next_record_id(&mut pool, num_partitions as u128).is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_records_from_producers_with_overlapping_ids_are_each_delivered() -> Result<()> {
+    let distributions = vec![
+        (false, ExchangeDistribution::RoundRobin),
+        (true, ExchangeDistribution::RoundRobin),
+        (
+            false,
+            ExchangeDistribution::HashPartition {
+                keys: vec!["id".to_string()],
+                num_partitions: 2,
+            },
+        ),
+    ];
+    for (ordered, distribution) in distributions {
+        let num_partitions = match &distribution {
+            ExchangeDistribution::HashPartition { num_partitions, .. } => *num_partitions,
+            _ => 1,
+        };
+        let mut pool = RecordPool::new(
+            vec![OPERATOR_ID.to_string()],
+            RecordPoolConfig {
+                max_heartbeat_interval: chrono::Duration::seconds(10),
+                ordered,
+                distribution,
+            },
+        );
+
+        // both producers number their records from 0 and the first
+        // record of each producer is resent
+        for (producer_instance_id, first_id) in
+            [(PRODUCER_INSTANCE_ID, 0), (PRODUCER_INSTANCE_ID + 1, 100)]
+        {
+            for record_id in [0, 1, 2, 0] {
+                pool.add_record(
+                    record_id,
+                    build_record(first_id + record_id as i64)?,
+                    vec![],
+                    producer_instance_id,
+                    record_id,
+                )?;
+            }
+        }
+
+        let mut ids: Vec<i64> = Vec::new();
+        for operator_instance_id in 0..num_partitions as u128 {
+            while let Some(record_ids) = complete_next_record(&mut pool, operator_instance_id)? {
+                ids.extend(record_ids);
+            }
+        }
+        ids.sort();
+        assert_eq!(ids, vec![0, 1, 2, 100, 101, 102], "ordered: {}", ordered);
+        assert_eq!(pool.buffered_bytes(), 0);
+    }
+
+    Ok(())
+}
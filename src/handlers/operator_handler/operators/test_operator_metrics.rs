@@ -0,0 +1,35 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::{Int32Array, RecordBatch};
+use arrow::datatypes::{DataType, Field, Schema};
+
+use super::operator_metrics::OperatorMetricsRecorder;
+
+fn build_record(num_rows: i32) -> Result<RecordBatch> {
+    let schema = Schema::new(vec![Field::new("id", DataType::Int32, false)]);
+    Ok(RecordBatch::try_new(
+        Arc::new(schema),
+        vec![Arc::new(Int32Array::from_iter_values(0..num_rows))],
+    )?)
+}
+
+#[test]
+fn test_operator_metrics_recorder() -> Result<()> {
+    let mut recorder = OperatorMetricsRecorder::new();
+
+    let rec_in = build_record(10)?;
+    let rec_out = build_record(4)?;
+    recorder.record_in(&rec_in);
+    recorder.record_in(&rec_in);
+    recorder.record_out(&rec_out);
+    std::thread::sleep(std::time::Duration::from_millis(5));
+
+    let metrics = recorder.metrics();
+    assert_eq!(metrics.records_in, 20);
+    assert_eq!(metrics.records_out, 4);
+    assert_eq!(metrics.bytes_out, rec_out.get_array_memory_size() as u64);
+    assert!(metrics.wall_time_ms >= 5);
+
+    Ok(())
+}
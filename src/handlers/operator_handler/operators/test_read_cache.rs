@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use opendal::services;
+
+use super::connection_registry::ConnectionRegistry;
+use super::read_cache::{ReadCache, ReadCacheLayer, ReadCacheStats};
+
+fn cached_conn(dir: &tempdir::TempDir, cache: Arc<ReadCache>) -> Result<opendal::Operator> {
+    let conn = opendal::Operator::new(
+        services::Fs::default().root(dir.path().to_string_lossy().as_ref()),
+    )?
+    .layer(ReadCacheLayer::new(cache, "default".to_string()))
+    .finish();
+    Ok(conn)
+}
+
+#[tokio::test]
+async fn test_read_cache_hits_and_misses() -> Result<()> {
+    let dir = tempdir::TempDir::new("read_cache")?;
+    let cache = Arc::new(ReadCache::new(1024));
+    let conn = cached_conn(&dir, cache.clone())?;
+
+    conn.write("data.bin", b"0123456789".to_vec()).await?;
+
+    let data = conn.read_with("data.bin").range(2..6).await?.to_vec();
+    assert_eq!(data, b"2345".to_vec());
+    let data = conn.read_with("data.bin").range(2..6).await?.to_vec();
+    assert_eq!(data, b"2345".to_vec());
+    let data = conn.read("data.bin").await?.to_vec();
+    assert_eq!(data, b"0123456789".to_vec());
+
+    assert_eq!(
+        cache.stats(),
+        ReadCacheStats {
+            hits: 1,
+            misses: 2,
+            entries: 2,
+            bytes: 14,
+        }
+    );
+
+    // writing the path drops its cached ranges
+    conn.write("data.bin", b"abcdefghij".to_vec()).await?;
+    let data = conn.read_with("data.bin").range(2..6).await?.to_vec();
+    assert_eq!(data, b"cdef".to_vec());
+    assert_eq!(cache.stats().misses, 3);
+
+    // deleting the path drops its cached ranges
+    conn.delete("data.bin").await?;
+    assert!(conn.read_with("data.bin").range(2..6).await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_read_cache_evicts_the_least_recently_used_ranges() -> Result<()> {
+    let dir = tempdir::TempDir::new("read_cache_evict")?;
+    let cache = Arc::new(ReadCache::new(8));
+    let conn = cached_conn(&dir, cache.clone())?;
+
+    conn.write("data.bin", b"0123456789".to_vec()).await?;
+
+    conn.read_with("data.bin").range(0..4).await?;
+    conn.read_with("data.bin").range(4..8).await?;
+    // the first range is used again so the second range is evicted
+    conn.read_with("data.bin").range(0..4).await?;
+    conn.read_with("data.bin").range(6..10).await?;
+    assert_eq!(cache.stats().bytes, 8);
+
+    conn.read_with("data.bin").range(0..4).await?;
+    conn.read_with("data.bin").range(4..8).await?;
+    assert_eq!(
+        cache.stats(),
+        ReadCacheStats {
+            hits: 2,
+            misses: 4,
+            entries: 2,
+            bytes: 8,
+        }
+    );
+
+    // ranges larger than the cache are not stored
+    conn.read("data.bin").await?;
+    assert_eq!(cache.stats().entries, 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_connection_registry_read_cache() -> Result<()> {
+    let dir = tempdir::TempDir::new("read_cache_registry")?;
+    let mut conn_reg = ConnectionRegistry::new();
+    assert_eq!(conn_reg.read_cache_stats(), None);
+
+    conn_reg.register_fs(
+        "default".to_string(),
+        dir.path().to_string_lossy().to_string(),
+    );
+    conn_reg.set_read_cache_mib(1);
+
+    conn_reg
+        .get_operator("default")?
+        .write("data.bin", b"0123456789".to_vec())
+        .await?;
+
+    // the cache is shared by the operators of the registry
+    conn_reg.get_operator("default")?.read("data.bin").await?;
+    conn_reg.get_operator("default")?.read("data.bin").await?;
+    assert_eq!(
+        conn_reg.read_cache_stats(),
+        Some(ReadCacheStats {
+            hits: 1,
+            misses: 1,
+            entries: 1,
+            bytes: 10,
+        })
+    );
+
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use std::io;
+
+use anyhow::anyhow;
+
+use super::is_retryable;
+
+#[test]
+fn test_is_retryable() {
+    struct TestCase {
+        case_name: String,
+        err: anyhow::Error,
+        expected: bool,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            case_name: "temporary storage error".to_string(),
+            err: opendal::Error::new(opendal::ErrorKind::Unexpected, "service unavailable")
+                .set_temporary()
+                .into(),
+            expected: true,
+        },
+        TestCase {
+            case_name: "persistent storage error".to_string(),
+            err: opendal::Error::new(opendal::ErrorKind::NotFound, "file not found").into(),
+            expected: false,
+        },
+        TestCase {
+            case_name: "connection reset with context".to_string(),
+            err: anyhow::Error::from(io::Error::from(io::ErrorKind::ConnectionReset))
+                .context("failed reading the record file"),
+            expected: true,
+        },
+        TestCase {
+            case_name: "invalid data".to_string(),
+            err: io::Error::from(io::ErrorKind::InvalidData).into(),
+            expected: false,
+        },
+        TestCase {
+            case_name: "other error".to_string(),
+            err: anyhow!("column not found"),
+            expected: false,
+        },
+    ];
+
+    for test_case in test_cases {
+        println!("test case: {}", test_case.case_name);
+        assert_eq!(is_retryable(&test_case.err), test_case.expected);
+    }
+}
@@ -0,0 +1,6 @@
+#[derive(Debug)]
+pub struct UnionConfig {
+    pub distinct: bool,
+    pub left_fields: Vec<sqlparser::ast::SelectItem>,
+    pub right_fields: Vec<sqlparser::ast::SelectItem>,
+}
@@ -0,0 +1,37 @@
+use anyhow::Result;
+use thiserror::Error;
+
+use crate::{
+    handlers::operator_handler::operator_handler_state::OperatorInstanceConfig,
+    planner::{OperatorTask, OperatorType},
+};
+
+use super::config::UnionConfig;
+
+#[derive(Debug, Error)]
+pub enum TryFromUnionConfigError {
+    #[error("unable to convert")]
+    UnableToConvert,
+}
+
+impl TryFrom<&OperatorInstanceConfig> for UnionConfig {
+    type Error = TryFromUnionConfigError;
+
+    fn try_from(op_in_config: &OperatorInstanceConfig) -> Result<UnionConfig, Self::Error> {
+        match &op_in_config.operator.operator_type {
+            OperatorType::Producer { task, .. } => match task {
+                OperatorTask::Union {
+                    distinct,
+                    left_fields,
+                    right_fields,
+                } => Ok(UnionConfig {
+                    distinct: *distinct,
+                    left_fields: left_fields.clone(),
+                    right_fields: right_fields.clone(),
+                }),
+                _ => Err(TryFromUnionConfigError::UnableToConvert),
+            },
+            OperatorType::Exchange { .. } => Err(TryFromUnionConfigError::UnableToConvert),
+        }
+    }
+}
@@ -0,0 +1,5 @@
+mod config;
+mod conversions;
+mod union_task;
+
+pub use union_task::UnionTaskBuilder;
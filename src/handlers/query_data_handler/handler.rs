@@ -0,0 +1,205 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use thiserror::Error;
+use tokio::sync::{mpsc, Mutex};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use crate::handlers::message_handler::messages;
+use crate::handlers::message_handler::messages::message::{Message, MessageName};
+use crate::handlers::message_handler::{MessageRegistry, Pipe};
+use crate::handlers::message_router_handler::{
+    MessageConsumer, MessageReceiver, MessageRouterState, Subscriber,
+};
+use crate::handlers::operator_handler::operators::{self, ConnectionRegistry};
+
+#[derive(Debug, Error)]
+pub enum QueryDataHandlerError {
+    #[error("file does not contain a record: {0}")]
+    FileDoesNotContainARecord(String),
+}
+
+// Serves the materialized results of queries to clients.
+#[derive(Debug)]
+pub struct QueryDataHandler {
+    operator_id: u128,
+    message_router_state: Arc<Mutex<MessageRouterState>>,
+    router_pipe: Pipe,
+    sender: mpsc::Sender<Message>,
+    msg_reg: Arc<MessageRegistry>,
+    conn_reg: Arc<ConnectionRegistry>,
+    results_connection: String,
+}
+
+impl QueryDataHandler {
+    pub async fn new(
+        message_router_state: Arc<Mutex<MessageRouterState>>,
+        msg_reg: Arc<MessageRegistry>,
+        conn_reg: Arc<ConnectionRegistry>,
+    ) -> QueryDataHandler {
+        let operator_id = Uuid::new_v4().as_u128();
+
+        let router_sender = message_router_state.lock().await.sender();
+        let (mut pipe, sender) = Pipe::new_with_existing_sender(router_sender, 10);
+        pipe.set_sent_from_operation_id(operator_id);
+
+        QueryDataHandler {
+            operator_id,
+            message_router_state,
+            router_pipe: pipe,
+            sender,
+            msg_reg,
+            conn_reg,
+            results_connection: "default".to_string(),
+        }
+    }
+
+    // The query results are read from the named storage connection; it
+    // must be the connection the results are materialized to.
+    pub fn set_results_connection(&mut self, results_connection: String) -> &Self {
+        self.results_connection = results_connection;
+        self
+    }
+
+    pub fn subscriber(&self) -> Box<dyn Subscriber> {
+        Box::new(QueryDataHandlerSubscriber {
+            sender: self.sender.clone(),
+        })
+    }
+
+    pub async fn async_main(&mut self, ct: CancellationToken) -> Result<()> {
+        self.message_router_state
+            .lock()
+            .await
+            .add_internal_subscriber(self.subscriber(), self.operator_id);
+
+        loop {
+            tokio::select! {
+                Some(msg) = self.router_pipe.recv() => {
+                    debug!("recieved message: {}", msg);
+                    self.handle_message(msg).await?;
+                }
+                _ = ct.cancelled() => {
+                    break;
+                }
+            }
+        }
+
+        self.message_router_state
+            .lock()
+            .await
+            .remove_internal_subscriber(&self.operator_id);
+
+        info!("closing the query data handler...");
+        self.router_pipe.close_receiver();
+
+        Ok(())
+    }
+
+    async fn handle_message(&mut self, msg: Message) -> Result<()> {
+        match msg.msg.msg_name() {
+            MessageName::GetQueryData => self
+                .handle_get_query_data(&msg)
+                .await
+                .context("failed handling the get query data request")?,
+            _ => {
+                info!("unknown message received: {:?}", msg);
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_get_query_data(&mut self, msg: &Message) -> Result<()> {
+        let get_data: &messages::query::GetQueryData = self.msg_reg.try_cast_msg(msg)?;
+
+        let resp = match self.conn_reg.get_operator(&self.results_connection) {
+            Ok(storage_conn) => {
+                get_query_data(&storage_conn, get_data.query_id, get_data.file_idx).await
+            }
+            Err(err) => messages::query::GetQueryDataResp::Error(err.to_string()),
+        };
+
+        let resp_msg = msg.reply(Box::new(resp));
+        self.router_pipe.send(resp_msg).await?;
+
+        Ok(())
+    }
+}
+
+// Find the record in the file at file_idx. The files are listed in the
+// materialization manifests so a missing or unreadable file is an error
+// instead of the end of the results. Nothing is served until the query has
+// completed and every materialize instance's manifest can be read, otherwise
+// the files of a missing manifest would be skipped.
+pub async fn get_query_data(
+    storage_conn: &opendal::Operator,
+    query_id: u128,
+    file_idx: u64,
+) -> messages::query::GetQueryDataResp {
+    let completion = match operators::read_results_completion(storage_conn, query_id).await {
+        Ok(Some(completion)) => completion,
+        Ok(None) => return messages::query::GetQueryDataResp::QueryResultsNotReady,
+        Err(err) => return messages::query::GetQueryDataResp::Error(err.to_string()),
+    };
+    let manifests = match operators::read_manifests(storage_conn, query_id).await {
+        Ok(manifests) => manifests,
+        Err(err) => return messages::query::GetQueryDataResp::Error(err.to_string()),
+    };
+    if manifests.len() < completion.num_manifests {
+        return messages::query::GetQueryDataResp::QueryResultsNotReady;
+    }
+
+    let file = manifests
+        .iter()
+        .flat_map(|manifest| manifest.files.iter())
+        .nth(file_idx as usize);
+    let file = match file {
+        Some(file) => file,
+        None => return messages::query::GetQueryDataResp::ReachedEndOfFiles,
+    };
+
+    let rec_path = format!("{}{}", operators::query_results_dir(query_id), file.path);
+    match read_file_record(storage_conn, &rec_path).await {
+        Ok(record) => messages::query::GetQueryDataResp::Record {
+            file_idx,
+            record: Arc::new(record),
+        },
+        Err(err) => messages::query::GetQueryDataResp::Error(format!("{:#}", err)),
+    }
+}
+
+async fn read_file_record(
+    storage_conn: &opendal::Operator,
+    rec_path: &str,
+) -> Result<arrow::array::RecordBatch> {
+    let records = operators::read_record_file(storage_conn, rec_path)
+        .await
+        .with_context(|| format!("failed reading file {}", rec_path))?;
+    match records.first() {
+        Some(record) => Ok(arrow::compute::concat_batches(&record.schema(), &records)?),
+        None => Err(QueryDataHandlerError::FileDoesNotContainARecord(rec_path.to_string()).into()),
+    }
+}
+
+/////////////////////////////////////////////////
+// Message subscriber for the query data handler
+#[derive(Debug)]
+pub struct QueryDataHandlerSubscriber {
+    sender: mpsc::Sender<Message>,
+}
+
+impl Subscriber for QueryDataHandlerSubscriber {}
+
+impl MessageConsumer for QueryDataHandlerSubscriber {
+    fn consumes_message(&self, msg: &Message) -> bool {
+        matches!(msg.msg.msg_name(), MessageName::GetQueryData)
+    }
+}
+
+impl MessageReceiver for QueryDataHandlerSubscriber {
+    fn sender(&self) -> mpsc::Sender<Message> {
+        self.sender.clone()
+    }
+}
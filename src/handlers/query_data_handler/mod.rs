@@ -0,0 +1,5 @@
+mod handler;
+#[cfg(test)]
+mod test_query_data_handler;
+
+pub use handler::QueryDataHandler;
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::{ArrayRef, Int64Array, RecordBatch};
+use parquet::arrow::ArrowWriter;
+
+use crate::handlers::message_handler::messages::query::GetQueryDataResp;
+use crate::handlers::operator_handler::operators::{
+    query_results_dir, ConnectionRegistry, MaterializeManifest, ResultsCompletion,
+};
+use crate::planner::DataFormat;
+
+use super::handler::get_query_data;
+
+fn id_record(ids: Vec<i64>) -> Result<RecordBatch> {
+    Ok(RecordBatch::try_from_iter(vec![(
+        "id",
+        Arc::new(Int64Array::from(ids)) as ArrayRef,
+    )])?)
+}
+
+#[tokio::test]
+async fn test_get_query_data_follows_the_manifests() -> Result<()> {
+    let dir = tempdir::TempDir::new("query_data")?;
+    let mut conn_reg = ConnectionRegistry::new();
+    conn_reg.add_connection(
+        "default".to_string(),
+        opendal::Scheme::Fs,
+        HashMap::from([("root".to_string(), dir.path().to_string_lossy().to_string())]),
+    );
+    let conn = conn_reg.get_operator("default")?;
+
+    let query_id = 3u128;
+    let results_path = dir
+        .path()
+        .join(query_results_dir(query_id).trim_start_matches('/'));
+    std::fs::create_dir_all(&results_path)?;
+
+    // no manifests have been written yet
+    assert_eq!(
+        get_query_data(&conn, query_id, 0).await,
+        GetQueryDataResp::QueryResultsNotReady
+    );
+
+    // the record ids in the file names have gaps
+    let files = vec![
+        ("rec_0.parquet", vec![1, 2]),
+        ("rec_5.parquet", vec![3]),
+        ("rec_9.parquet", vec![4, 5, 6]),
+    ];
+    for (name, ids) in &files {
+        let record = id_record(ids.clone())?;
+        let file = std::fs::File::create(results_path.join(name))?;
+        let mut writer = ArrowWriter::try_new(file, record.schema(), None)?;
+        writer.write(&record)?;
+        writer.close()?;
+    }
+
+    let schema = id_record(vec![])?.schema();
+    let mut manifest_a = MaterializeManifest::new(1, DataFormat::Parquet);
+    manifest_a.add_file("rec_0.parquet".to_string(), 2, &schema);
+    manifest_a.add_file("rec_5.parquet".to_string(), 1, &schema);
+    manifest_a.write(&conn, query_id).await?;
+
+    // the query hasn't completed
+    assert_eq!(
+        get_query_data(&conn, query_id, 0).await,
+        GetQueryDataResp::QueryResultsNotReady
+    );
+
+    // the query completed but the second manifest isn't readable yet
+    ResultsCompletion::new(2).write(&conn, query_id).await?;
+    assert_eq!(
+        get_query_data(&conn, query_id, 0).await,
+        GetQueryDataResp::QueryResultsNotReady
+    );
+
+    let mut manifest_b = MaterializeManifest::new(2, DataFormat::Parquet);
+    manifest_b.add_file("rec_9.parquet".to_string(), 3, &schema);
+    manifest_b.write(&conn, query_id).await?;
+
+    for (file_idx, (_, ids)) in files.iter().enumerate() {
+        assert_eq!(
+            get_query_data(&conn, query_id, file_idx as u64).await,
+            GetQueryDataResp::Record {
+                file_idx: file_idx as u64,
+                record: Arc::new(id_record(ids.clone())?),
+            }
+        );
+    }
+    assert_eq!(
+        get_query_data(&conn, query_id, 3).await,
+        GetQueryDataResp::ReachedEndOfFiles
+    );
+
+    // a file listed in the manifest which can't be read is an error
+    // instead of the end of the files
+    std::fs::remove_file(results_path.join("rec_5.parquet"))?;
+    assert!(matches!(
+        get_query_data(&conn, query_id, 1).await,
+        GetQueryDataResp::Error(_)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_get_query_data_resp_record_serialization() -> Result<()> {
+    let resp = GetQueryDataResp::Record {
+        file_idx: 2,
+        record: Arc::new(id_record(vec![1, 2, 3])?),
+    };
+    let data = serde_json::to_vec(&resp)?;
+    let parsed: GetQueryDataResp = serde_json::from_slice(&data)?;
+    assert_eq!(parsed, resp);
+
+    Ok(())
+}
@@ -1,4 +1,11 @@
 mod query_handler;
 mod query_handler_state;
+mod query_store;
+#[cfg(test)]
+mod test_query_handler_state;
+#[cfg(test)]
+mod test_query_store;
 
 pub use query_handler::QueryHandler;
+pub use query_handler_state::SchedulingPolicy;
+pub use query_store::{QueryRecord, QueryStore, QUERY_STATE_DIR};
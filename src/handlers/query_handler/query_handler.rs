@@ -1,28 +1,44 @@
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use anyhow::{Context, Result};
 use thiserror::Error;
 use tokio::sync::{mpsc, Mutex};
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 use uuid::Uuid;
 
-use super::query_handler_state::{self, QueryHandlerState, QueryHandlerStateError, Status};
+use super::query_handler_state::{
+    self, QueryHandlerState, QueryHandlerStateError, RestartOutcome, SchedulingPolicy, Status,
+};
+use super::query_store::QueryStore;
 use crate::handlers::message_handler::messages;
 use crate::handlers::message_handler::messages::message::{Message, MessageName};
 use crate::handlers::message_handler::{MessageRegistry, Pipe};
 use crate::handlers::message_router_handler::{
     MessageConsumer, MessageReceiver, MessageRouterState, Subscriber,
 };
-use crate::handlers::operator_handler::operators::requests;
+use crate::handlers::operator_handler::operators::{self, requests, ConnectionRegistry};
 use crate::planner;
 
+const QUERY_TIMEOUT_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
 #[derive(Debug, Error)]
 pub enum QueryHandlerError {
     #[error("incorrect message: {0}")]
     IncorrectMessage(String),
 }
 
+// Clients subscribed to the results of a query. The records are sent as
+// replies to the subscribe message so they're routed back to the
+// client's connection.
+#[derive(Debug, Default)]
+struct ResultSubscription {
+    subscribe_msgs: Vec<Message>,
+    // materialize instances which have sent all of their records
+    ended_op_instances: HashSet<u128>,
+}
+
 #[derive(Debug)]
 pub struct QueryHandler {
     operator_id: u128,
@@ -31,12 +47,20 @@ pub struct QueryHandler {
     router_pipe: Pipe,
     sender: mpsc::Sender<Message>,
     msg_reg: Arc<MessageRegistry>,
+    conn_reg: Arc<ConnectionRegistry>,
+    planner_config: planner::PlannerConfig,
+    // queries are only persisted when a store is given
+    query_store: Option<QueryStore>,
+    result_subscriptions: HashMap<u128, ResultSubscription>,
 }
 
 impl QueryHandler {
     pub async fn new(
         message_router_state: Arc<Mutex<MessageRouterState>>,
         msg_reg: Arc<MessageRegistry>,
+        conn_reg: Arc<ConnectionRegistry>,
+        planner_config: planner::PlannerConfig,
+        query_store: Option<QueryStore>,
     ) -> QueryHandler {
         let operator_id = Uuid::new_v4().as_u128();
 
@@ -44,18 +68,66 @@ impl QueryHandler {
         let (mut pipe, sender) = Pipe::new_with_existing_sender(router_sender, 10);
         pipe.set_sent_from_operation_id(operator_id);
 
-        let handler = QueryHandler {
+        let mut handler = QueryHandler {
             operator_id,
             state: QueryHandlerState::new(),
             message_router_state,
             router_pipe: pipe,
             sender,
             msg_reg,
+            conn_reg,
+            planner_config,
+            query_store,
+            result_subscriptions: HashMap::new(),
         };
+        if let Err(err) = handler.restore_queries().await {
+            warn!("unable to restore the persisted queries: {}", err);
+        }
 
         handler
     }
 
+    async fn restore_queries(&mut self) -> Result<()> {
+        let records = match &mut self.query_store {
+            Some(query_store) => query_store.read_all().await?,
+            None => return Ok(()),
+        };
+        for record in records {
+            let physical_plan = match self.build_physical_plan(record.query.clone()).await {
+                Ok((physical_plan, _)) => physical_plan,
+                Err(err) => {
+                    warn!(
+                        query_id = record.id,
+                        "unable to plan the persisted query: {}", err
+                    );
+                    continue;
+                }
+            };
+            info!(query_id = record.id, "restored query");
+            self.state
+                .add_query(query_handler_state::Query::from_record(
+                    record,
+                    physical_plan,
+                ));
+        }
+        Ok(())
+    }
+
+    // Failing to write the queries shouldn't stop the queries from
+    // running.
+    async fn checkpoint_queries(&mut self) {
+        if let Some(query_store) = &mut self.query_store {
+            if let Err(err) = query_store.checkpoint(self.state.queries()).await {
+                warn!("unable to persist the queries: {}", err);
+            }
+        }
+    }
+
+    pub fn set_scheduling_policy(&mut self, scheduling_policy: SchedulingPolicy) -> &Self {
+        self.state.set_scheduling_policy(scheduling_policy);
+        self
+    }
+
     pub fn subscriber(&self) -> Box<dyn Subscriber> {
         Box::new(QueryHandlerSubscriber {
             operator_id: self.operator_id.clone(),
@@ -70,8 +142,27 @@ impl QueryHandler {
             .await
             .add_internal_subscriber(self.subscriber(), self.operator_id);
 
+        // restored queries may have instances waiting for a worker
+        if self
+            .state
+            .queries()
+            .iter()
+            .any(|query| !query.status.terminal())
+        {
+            let in_avail_msg = Message::new(Box::new(
+                messages::query::OperatorInstanceAvailable::Notification,
+            ));
+            self.router_pipe.send(in_avail_msg).await?;
+        }
+
+        let mut timeout_interval = tokio::time::interval(QUERY_TIMEOUT_CHECK_INTERVAL);
         loop {
             tokio::select! {
+                _ = timeout_interval.tick() => {
+                    self.fail_timed_out_queries().await?;
+                    self.close_failed_result_subscriptions().await?;
+                    self.checkpoint_queries().await;
+                }
                 Some(msg) = self.router_pipe.recv() => {
                     debug!("recieved message: {}", msg);
                     let res = self.handle_message(msg).await;
@@ -82,6 +173,8 @@ impl QueryHandler {
                             return Err(err);
                         }
                     }
+                    self.close_failed_result_subscriptions().await?;
+                    self.checkpoint_queries().await;
                 }
                 _ = ct.cancelled() => {
                     break;
@@ -122,6 +215,47 @@ impl QueryHandler {
                 .handle_operator_instance_status_change(&msg)
                 .await
                 .context("failed handling the operator instance status change")?,
+            MessageName::GetQueryDiagnostics => self
+                .handle_get_query_diagnostics(&msg)
+                .await
+                .context("failed handling the get query diagnostics request")?,
+            MessageName::GetQueryPlan => self
+                .handle_get_query_plan(&msg)
+                .await
+                .context("failed handling the get query plan request")?,
+            MessageName::CancelQuery => self
+                .handle_cancel_query(&msg)
+                .await
+                .context("failed handling the cancel query request")?,
+            MessageName::OperatorInstanceMetrics => self
+                .handle_operator_instance_metrics(&msg)
+                .await
+                .context("failed handling the operator instance metrics")?,
+            MessageName::GetQueryMetrics => self
+                .handle_get_query_metrics(&msg)
+                .await
+                .context("failed handling the get query metrics request")?,
+            MessageName::SubscribeQueryResults => {
+                self.handle_subscribe_query_results(&msg)
+                    .await
+                    .context("failed handling the subscribe query results request")?
+            }
+            MessageName::StreamQueryResults => self
+                .handle_stream_query_results(&msg)
+                .await
+                .context("failed handling the streamed query results")?,
+            MessageName::ListQueries => self
+                .handle_list_queries(&msg)
+                .await
+                .context("failed handling the list queries request")?,
+            MessageName::GetQueryStatus => self
+                .handle_get_query_status(&msg)
+                .await
+                .context("failed handling the get query status request")?,
+            MessageName::SetQueryPriority => self
+                .handle_set_query_priority(&msg)
+                .await
+                .context("failed handling the set query priority request")?,
             _ => {
                 info!("unknown message received: {:?}", msg);
             }
@@ -138,32 +272,98 @@ impl QueryHandler {
         let resp_msg = msg.reply(Box::new(messages::common::GenericResponse::Ok));
         self.router_pipe.send(resp_msg).await?;
 
-        // update the operator instance status
+        // the operators of a stopped query are already shutting down
         let (query_id, op_in_id) = match cast_msg {
             messages::query::OperatorInstanceStatusChange::Complete {
                 query_id,
                 operator_instance_id,
-            } => {
-                self.state.update_operator_instance_status(
-                    query_id,
-                    operator_instance_id,
-                    Status::Complete,
-                )?;
-                (query_id, operator_instance_id)
-            }
+            } => (*query_id, *operator_instance_id),
             messages::query::OperatorInstanceStatusChange::Error {
                 query_id,
                 operator_instance_id,
-                error,
+                ..
+            } => (*query_id, *operator_instance_id),
+        };
+        let (query_id, op_in_id) = (&query_id, &op_in_id);
+        // instances stopped early are already complete
+        if self
+            .state
+            .get_operator_instance(query_id, op_in_id)?
+            .status
+            .terminal()
+        {
+            return Ok(());
+        }
+
+        // update the operator instance status
+        match cast_msg {
+            messages::query::OperatorInstanceStatusChange::Complete { .. } => {
+                self.state
+                    .update_operator_instance_status(query_id, op_in_id, Status::Complete)?;
+            }
+            messages::query::OperatorInstanceStatusChange::Error {
+                error, retryable, ..
             } => {
                 self.state.update_operator_instance_status(
                     query_id,
-                    operator_instance_id,
+                    op_in_id,
                     Status::Error(error.clone()),
                 )?;
-                (query_id, operator_instance_id)
+                if let Some(worker_id) = msg.sent_from_worker_id {
+                    self.state
+                        .update_operator_instance_worker(query_id, op_in_id, worker_id)?;
+                }
+
+                // requeue the instance and let the workers know it can be
+                // assigned again
+                if *retryable {
+                    match self
+                        .state
+                        .restart_operator_instance(query_id, op_in_id, error.clone())?
+                    {
+                        RestartOutcome::Restarted => {
+                            info!(
+                                query_id,
+                                operator_instance_id = op_in_id,
+                                "retrying operator instance after error: {}",
+                                error
+                            );
+                            let in_avail_msg = Message::new(Box::new(
+                                messages::query::OperatorInstanceAvailable::Notification,
+                            ));
+                            self.router_pipe.send(in_avail_msg).await?;
+                        }
+                        RestartOutcome::QueryFailed(op_instances) => {
+                            info!(
+                                query_id,
+                                operator_instance_id = op_in_id,
+                                "query failed after retrying: {}",
+                                error
+                            );
+                            self.shutdown_operator_instances(*query_id, op_instances)
+                                .await;
+                        }
+                        RestartOutcome::AlreadyFinished => {}
+                    }
+                    return Ok(());
+                }
+
+                // the query can't finish without the failed instance
+                if let Some(op_instances) = self.state.fail_query(query_id, error.clone())? {
+                    info!(
+                        query_id,
+                        operator_instance_id = op_in_id,
+                        "query failed: {}",
+                        error
+                    );
+                    self.shutdown_operator_instances(*query_id, op_instances)
+                        .await;
+                }
+                return Ok(());
             }
-        };
+        }
+
+        self.stop_producers_without_consumers(query_id).await;
 
         // notify the exchanges of the producer status change
         if self
@@ -236,6 +436,378 @@ impl QueryHandler {
             }
         }
 
+        self.complete_query_if_finished(query_id).await?;
+
+        Ok(())
+    }
+
+    // Stop the producers whose records won't be read so a query with a
+    // satisfied limit doesn't wait for its scans to finish.
+    async fn stop_producers_without_consumers(&mut self, query_id: &u128) {
+        let op_instances = match self.state.stop_producers_without_consumers(query_id) {
+            Ok(op_instances) => op_instances,
+            Err(err) => {
+                info!(query_id, "unable to stop unneeded producers: {}", err);
+                return;
+            }
+        };
+        if !op_instances.is_empty() {
+            debug!(
+                query_id,
+                num_operator_instances = op_instances.len(),
+                "stopping producers without any running consumers"
+            );
+        }
+        self.shutdown_operator_instances(*query_id, op_instances)
+            .await;
+    }
+
+    // The completion is written before the query is marked complete so
+    // the data handler can serve the results as soon as a client sees the
+    // complete status.
+    async fn complete_query_if_finished(&mut self, query_id: &u128) -> Result<()> {
+        if self.state.find_query(query_id)?.status.terminal()
+            || !self.state.all_producer_instances_complete(query_id)?
+        {
+            return Ok(());
+        }
+
+        let completion =
+            operators::ResultsCompletion::new(self.state.num_materialize_instances(query_id)?);
+        let write_res = match self
+            .conn_reg
+            .get_operator(&self.planner_config.results_connection)
+        {
+            Ok(storage_conn) => completion.write(&storage_conn, *query_id).await,
+            Err(err) => Err(err),
+        };
+        match write_res {
+            Ok(()) => self.state.update_query_status(query_id, Status::Complete)?,
+            Err(err) => self.state.update_query_status(
+                query_id,
+                Status::Error(format!("failed writing the results completion: {}", err)),
+            )?,
+        }
+        Ok(())
+    }
+
+    async fn handle_operator_instance_metrics(&mut self, msg: &Message) -> Result<()> {
+        let op_in_metrics: &messages::query::OperatorInstanceMetrics =
+            self.msg_reg.try_cast_msg(msg)?;
+
+        let resp_msg = msg.reply(Box::new(messages::common::GenericResponse::Ok));
+        self.router_pipe.send(resp_msg).await?;
+
+        self.state.update_operator_instance_metrics(
+            &op_in_metrics.query_id,
+            &op_in_metrics.operator_instance_id,
+            op_in_metrics.metrics,
+        )?;
+
+        Ok(())
+    }
+
+    async fn handle_get_query_metrics(&mut self, msg: &Message) -> Result<()> {
+        let get_metrics: &messages::query::GetQueryMetrics = self.msg_reg.try_cast_msg(msg)?;
+
+        let resp = match self.state.get_query_metrics(&get_metrics.query_id) {
+            Ok(stats) => messages::query::GetQueryMetricsResp::Metrics(stats),
+            Err(err) => match err.downcast_ref::<QueryHandlerStateError>() {
+                Some(QueryHandlerStateError::QueryNotFound(_)) => {
+                    messages::query::GetQueryMetricsResp::QueryNotFound
+                }
+                _ => return Err(err),
+            },
+        };
+
+        let resp_msg = msg.reply(Box::new(resp));
+        self.router_pipe.send(resp_msg).await?;
+
+        Ok(())
+    }
+
+    async fn handle_get_query_diagnostics(&mut self, msg: &Message) -> Result<()> {
+        let get_diagnostics: &messages::query::GetQueryDiagnostics =
+            self.msg_reg.try_cast_msg(msg)?;
+
+        let resp = match self.state.get_query_diagnostics(&get_diagnostics.query_id) {
+            Ok(diagnostics) => messages::query::GetQueryDiagnosticsResp::Diagnostics(diagnostics),
+            Err(err) => match err.downcast_ref::<QueryHandlerStateError>() {
+                Some(QueryHandlerStateError::QueryNotFound(_)) => {
+                    messages::query::GetQueryDiagnosticsResp::QueryNotFound
+                }
+                _ => return Err(err),
+            },
+        };
+
+        let resp_msg = msg.reply(Box::new(resp));
+        self.router_pipe.send(resp_msg).await?;
+
+        Ok(())
+    }
+
+    async fn handle_cancel_query(&mut self, msg: &Message) -> Result<()> {
+        let cancel_query: &messages::query::CancelQuery = self.msg_reg.try_cast_msg(msg)?;
+        let query_id = cancel_query.query_id;
+
+        let (resp, op_instances) = match self.state.cancel_query(&query_id) {
+            Ok(Some(op_instances)) => (messages::query::CancelQueryResp::Cancelled, op_instances),
+            Ok(None) => (
+                messages::query::CancelQueryResp::AlreadyFinished,
+                Vec::new(),
+            ),
+            Err(err) => match err.downcast_ref::<QueryHandlerStateError>() {
+                Some(QueryHandlerStateError::QueryNotFound(_)) => {
+                    (messages::query::CancelQueryResp::QueryNotFound, Vec::new())
+                }
+                _ => return Err(err),
+            },
+        };
+
+        let resp_msg = msg.reply(Box::new(resp));
+        self.router_pipe.send(resp_msg).await?;
+
+        self.shutdown_operator_instances(query_id, op_instances)
+            .await;
+
+        Ok(())
+    }
+
+    async fn handle_subscribe_query_results(&mut self, msg: &Message) -> Result<()> {
+        let subscribe: &messages::query::SubscribeQueryResults = self.msg_reg.try_cast_msg(msg)?;
+
+        let resp = match self.state.find_query(&subscribe.query_id) {
+            Ok(query) => match &query.status {
+                Status::Complete => messages::query::QueryResults::AlreadyFinished,
+                Status::Error(err) => messages::query::QueryResults::Error(err.clone()),
+                Status::Cancelled => {
+                    messages::query::QueryResults::Error("query cancelled".to_string())
+                }
+                _ => {
+                    self.result_subscriptions
+                        .entry(subscribe.query_id)
+                        .or_default()
+                        .subscribe_msgs
+                        .push(msg.clone());
+                    messages::query::QueryResults::Subscribed
+                }
+            },
+            Err(err) => match err.downcast_ref::<QueryHandlerStateError>() {
+                Some(QueryHandlerStateError::QueryNotFound(_)) => {
+                    messages::query::QueryResults::QueryNotFound
+                }
+                _ => return Err(err),
+            },
+        };
+
+        let resp_msg = msg.reply(Box::new(resp));
+        self.router_pipe.send(resp_msg).await?;
+
+        Ok(())
+    }
+
+    async fn handle_stream_query_results(&mut self, msg: &Message) -> Result<()> {
+        let stream_results: &messages::query::StreamQueryResults =
+            self.msg_reg.try_cast_msg(msg)?;
+
+        let resp = match stream_results {
+            messages::query::StreamQueryResults::Record { query_id, record } => {
+                match self.result_subscriptions.get(query_id) {
+                    Some(subscription) => {
+                        for subscribe_msg in &subscription.subscribe_msgs {
+                            let record_msg = subscribe_msg.reply(Box::new(
+                                messages::query::QueryResults::Record {
+                                    record: record.clone(),
+                                },
+                            ));
+                            self.router_pipe.send(record_msg).await?;
+                        }
+                        messages::query::StreamQueryResultsResp::Forwarded
+                    }
+                    None => messages::query::StreamQueryResultsResp::NoSubscribers,
+                }
+            }
+            messages::query::StreamQueryResults::End {
+                query_id,
+                operator_instance_id,
+            } => {
+                if self.result_subscriptions.contains_key(query_id) {
+                    let (query_id, op_in_id) = (*query_id, *operator_instance_id);
+                    self.end_result_subscription(&query_id, &op_in_id).await?;
+                    messages::query::StreamQueryResultsResp::Forwarded
+                } else {
+                    messages::query::StreamQueryResultsResp::NoSubscribers
+                }
+            }
+        };
+
+        let resp_msg = msg.reply(Box::new(resp));
+        self.router_pipe.send(resp_msg).await?;
+
+        Ok(())
+    }
+
+    // the subscription ends once every materialize instance has sent
+    // all of its records
+    async fn end_result_subscription(&mut self, query_id: &u128, op_in_id: &u128) -> Result<()> {
+        let op_id = self
+            .state
+            .get_operator_instance(query_id, op_in_id)?
+            .operator_id;
+        let num_op_instances = self.state.get_operator_instances(query_id, &op_id)?.len();
+
+        let subscription = match self.result_subscriptions.get_mut(query_id) {
+            Some(subscription) => subscription,
+            None => return Ok(()),
+        };
+        subscription.ended_op_instances.insert(*op_in_id);
+        if subscription.ended_op_instances.len() < num_op_instances {
+            return Ok(());
+        }
+
+        if let Some(subscription) = self.result_subscriptions.remove(query_id) {
+            for subscribe_msg in subscription.subscribe_msgs {
+                let end_msg = subscribe_msg.reply(Box::new(messages::query::QueryResults::End));
+                self.router_pipe.send(end_msg).await?;
+            }
+        }
+        Ok(())
+    }
+
+    // subscribers of a query which failed or was cancelled won't receive
+    // any more records
+    async fn close_failed_result_subscriptions(&mut self) -> Result<()> {
+        let mut failed_queries = Vec::new();
+        for query_id in self.result_subscriptions.keys() {
+            let error = match self.state.find_query(query_id) {
+                Ok(query) => match &query.status {
+                    Status::Error(err) => err.clone(),
+                    Status::Cancelled => "query cancelled".to_string(),
+                    _ => continue,
+                },
+                Err(_) => "query not found".to_string(),
+            };
+            failed_queries.push((*query_id, error));
+        }
+
+        for (query_id, error) in failed_queries {
+            if let Some(subscription) = self.result_subscriptions.remove(&query_id) {
+                for subscribe_msg in subscription.subscribe_msgs {
+                    let error_msg = subscribe_msg.reply(Box::new(
+                        messages::query::QueryResults::Error(error.clone()),
+                    ));
+                    self.router_pipe.send(error_msg).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_list_queries(&mut self, msg: &Message) -> Result<()> {
+        let list_queries: &messages::query::ListQueries = self.msg_reg.try_cast_msg(msg)?;
+
+        let resp = messages::query::ListQueriesResp {
+            queries: self.state.list_queries(list_queries.status.as_ref()),
+        };
+        let resp_msg = msg.reply(Box::new(resp));
+        self.router_pipe.send(resp_msg).await?;
+
+        Ok(())
+    }
+
+    async fn handle_get_query_status(&mut self, msg: &Message) -> Result<()> {
+        let get_status: &messages::query::GetQueryStatus = self.msg_reg.try_cast_msg(msg)?;
+
+        let resp = match self.state.find_query(&get_status.query_id) {
+            Ok(query) => messages::query::GetQueryStatusResp::Status(
+                messages::query::QueryStatus::from(&query.status),
+            ),
+            Err(err) => match err.downcast_ref::<QueryHandlerStateError>() {
+                Some(QueryHandlerStateError::QueryNotFound(_)) => {
+                    messages::query::GetQueryStatusResp::QueryNotFound
+                }
+                _ => return Err(err),
+            },
+        };
+
+        let resp_msg = msg.reply(Box::new(resp));
+        self.router_pipe.send(resp_msg).await?;
+
+        Ok(())
+    }
+
+    async fn handle_set_query_priority(&mut self, msg: &Message) -> Result<()> {
+        let set_priority: &messages::query::SetQueryPriority = self.msg_reg.try_cast_msg(msg)?;
+
+        let resp = match self
+            .state
+            .update_query_priority(&set_priority.query_id, set_priority.priority)
+        {
+            Ok(true) => messages::query::SetQueryPriorityResp::Updated,
+            Ok(false) => messages::query::SetQueryPriorityResp::AlreadyFinished,
+            Err(err) => match err.downcast_ref::<QueryHandlerStateError>() {
+                Some(QueryHandlerStateError::QueryNotFound(_)) => {
+                    messages::query::SetQueryPriorityResp::QueryNotFound
+                }
+                _ => return Err(err),
+            },
+        };
+
+        let resp_msg = msg.reply(Box::new(resp));
+        self.router_pipe.send(resp_msg).await?;
+
+        Ok(())
+    }
+
+    async fn fail_timed_out_queries(&mut self) -> Result<()> {
+        for (query_id, op_instances) in self.state.fail_timed_out_queries(chrono::Utc::now())? {
+            info!(query_id, "query timed out");
+            self.shutdown_operator_instances(query_id, op_instances)
+                .await;
+        }
+        Ok(())
+    }
+
+    // an instance which can't be reached has already stopped
+    async fn shutdown_operator_instances(
+        &mut self,
+        query_id: u128,
+        op_instances: Vec<query_handler_state::OperatorInstance>,
+    ) {
+        for op_in in op_instances {
+            if let Err(err) = requests::operator::ShutdownRequest::shutdown_immediate_request(
+                op_in.id,
+                &mut self.router_pipe,
+                self.msg_reg.clone(),
+            )
+            .await
+            {
+                info!(
+                    query_id,
+                    operator_instance_id = op_in.id,
+                    "unable to shutdown stopped operator instance: {}",
+                    err
+                );
+            }
+        }
+    }
+
+    async fn handle_get_query_plan(&mut self, msg: &Message) -> Result<()> {
+        let get_plan: &messages::query::GetQueryPlan = self.msg_reg.try_cast_msg(msg)?;
+
+        let resp = match self.state.find_query(&get_plan.query_id) {
+            Ok(query) => messages::query::GetQueryPlanResp::Plan(query.physical_plan.to_json()?),
+            Err(err) => match err.downcast_ref::<QueryHandlerStateError>() {
+                Some(QueryHandlerStateError::QueryNotFound(_)) => {
+                    messages::query::GetQueryPlanResp::QueryNotFound
+                }
+                _ => return Err(err),
+            },
+        };
+
+        let resp_msg = msg.reply(Box::new(resp));
+        self.router_pipe.send(resp_msg).await?;
+
         Ok(())
     }
 
@@ -289,6 +861,16 @@ impl QueryHandler {
                     op_instance_id,
                     Status::Running,
                 )?;
+                if let Some(worker_id) = msg.sent_from_worker_id {
+                    self.state.update_operator_instance_worker(
+                        query_id,
+                        op_instance_id,
+                        worker_id,
+                    )?;
+                }
+                // the consumers may have completed while it was being sent
+                let query_id = *query_id;
+                self.stop_producers_without_consumers(&query_id).await;
             }
             messages::query::OperatorInstanceAssignment::AssignRejectedResponse {
                 query_id,
@@ -307,6 +889,13 @@ impl QueryHandler {
                     op_instance_id,
                     Status::Error(error.clone()),
                 )?;
+                if let Some(worker_id) = msg.sent_from_worker_id {
+                    self.state.update_operator_instance_worker(
+                        query_id,
+                        op_instance_id,
+                        worker_id,
+                    )?;
+                }
             }
             messages::query::OperatorInstanceAssignment::Assign { .. } => {
                 return Err(
@@ -344,7 +933,8 @@ impl QueryHandler {
                         op_instance_id: item.1.id,
                         query_id: item.0,
                         pipeline_id: item.1.pipeline_id.clone(),
-                        operator: item.2.clone(),
+                        instance_idx: item.1.instance_idx,
+                        operator: Box::new(item.2.clone()),
                     },
                 ))
             })
@@ -354,21 +944,48 @@ impl QueryHandler {
         Ok(())
     }
 
+    // The plan is built once to find the table sources and then again
+    // with their schemas so unknown columns are found before the query
+    // is created.
+    // Returns the plan and whether the query was an EXPLAIN.
+    async fn build_logical_plan(&self, query: String) -> Result<(planner::LogicalPlan, bool)> {
+        let mut logical_planner = planner::LogicalPlanner::new(query);
+        let logical_plan = logical_planner.build()?;
+        let explain = logical_planner.is_explain();
+
+        let mut table_schemas = planner::TableSchemas::new();
+        for table_source in logical_plan.get_table_sources() {
+            if let planner::LogicalPlanNodeType::TableFunc { name, args, .. } = &table_source {
+                let schema = operators::find_table_func_schema(name, args, &self.conn_reg).await?;
+                if let Some(schema) = schema {
+                    table_schemas.add_schema(table_source, schema);
+                }
+            }
+        }
+        if table_schemas.is_empty() {
+            return Ok((logical_plan, explain));
+        }
+
+        let logical_plan = logical_planner.set_table_schemas(table_schemas).build()?;
+        Ok((logical_plan, explain))
+    }
+
+    // Returns the plan and whether the query was an EXPLAIN.
+    async fn build_physical_plan(&self, query: String) -> Result<(planner::PhysicalPlan, bool)> {
+        let (logical_plan, explain) = self.build_logical_plan(query).await?;
+        self.conn_reg
+            .check_connection(&self.planner_config.results_connection)?;
+        let physical_plan =
+            planner::PhysicalPlanner::new(logical_plan, self.planner_config.clone()).build()?;
+        Ok((physical_plan, explain))
+    }
+
     async fn handle_run_query(&mut self, msg: &Message) -> Result<()> {
         let run_query: &messages::query::RunQuery = self.msg_reg.try_cast_msg(&msg)?;
 
-        let logical_plan = match planner::LogicalPlanner::new(run_query.query.clone()).build() {
-            Ok(plan) => plan,
-            Err(err) => {
-                info!("error: {}", err);
-                let not_created_resp =
-                    msg.reply(Box::new(messages::query::RunQueryResp::NotCreated));
-                self.router_pipe.send(not_created_resp).await?;
-                return Ok(());
-            }
-        };
-        let physical_plan = match planner::PhysicalPlanner::new(logical_plan).build() {
-            Ok(plan) => plan,
+        let (physical_plan, explain) = match self.build_physical_plan(run_query.query.clone()).await
+        {
+            Ok(res) => res,
             Err(err) => {
                 info!("error: {}", err);
                 let not_created_resp =
@@ -378,7 +995,18 @@ impl QueryHandler {
             }
         };
 
+        // an explained query is never scheduled, only its plan is returned
+        if explain {
+            let explained_resp = msg.reply(Box::new(messages::query::RunQueryResp::Explained {
+                plan: physical_plan.to_json()?,
+            }));
+            self.router_pipe.send(explained_resp).await?;
+            return Ok(());
+        }
+
         let mut query = query_handler_state::Query::new(run_query.query.clone(), physical_plan);
+        query.set_timeout(run_query.timeout);
+        query.set_priority(run_query.priority);
         query.init();
 
         let run_query_resp = msg.reply(Box::new(messages::query::RunQueryResp::Created {
@@ -453,6 +1081,16 @@ impl MessageConsumer for QueryHandlerSubscriber {
                 }
             }
             MessageName::QueryOperatorInstanceStatusChange => return true,
+            MessageName::GetQueryDiagnostics => return true,
+            MessageName::GetQueryPlan => return true,
+            MessageName::CancelQuery => return true,
+            MessageName::SetQueryPriority => return true,
+            MessageName::GetQueryStatus => return true,
+            MessageName::ListQueries => return true,
+            MessageName::SubscribeQueryResults => return true,
+            MessageName::StreamQueryResults => return true,
+            MessageName::OperatorInstanceMetrics => return true,
+            MessageName::GetQueryMetrics => return true,
             _ => (),
         }
 
@@ -1,12 +1,19 @@
+use std::collections::VecDeque;
+use std::time::Duration;
 use std::u128;
 
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use uuid::Uuid;
 
+use super::query_store::QueryRecord;
 use crate::{
-    handlers::operator_handler::TotalOperatorCompute,
-    planner::{self, Operator},
+    handlers::{
+        message_handler::messages::{self, query::QueryPriority},
+        operator_handler::TotalOperatorCompute,
+    },
+    planner::{self, Operator, OperatorCompute},
 };
 
 #[derive(Debug, Clone, Error)]
@@ -23,7 +30,31 @@ pub enum QueryHandlerStateError {
     ExpectedProducerOperatorType,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+// total number of operator instance restarts allowed across
+// all instances of a query before the query fails
+pub const DEFAULT_QUERY_RESTART_BUDGET: usize = 10;
+// number of times a single operator instance is restarted before
+// the query fails
+pub const DEFAULT_OPERATOR_INSTANCE_MAX_RETRIES: usize = 3;
+// share of the compute given to a query by the weighted fair policy
+pub const DEFAULT_QUERY_WEIGHT: usize = 1;
+// longer queries are truncated when listed
+const MAX_LISTED_QUERY_CHARS: usize = 100;
+
+// How the available compute is divided between the runnable queries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SchedulingPolicy {
+    // drain the queries in the order they were added
+    Fifo,
+    // claim one operator instance from each query in turn
+    #[default]
+    RoundRobin,
+    // claim from the query with the fewest scheduled operator instances
+    // relative to its weight
+    WeightedFair,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Status {
     Queued,
     SendingToWorker,
@@ -31,6 +62,7 @@ pub enum Status {
     SentShutdown(chrono::DateTime<chrono::Utc>),
     Complete,
     Error(String),
+    Cancelled,
 }
 
 impl Status {
@@ -38,36 +70,82 @@ impl Status {
         match self {
             Status::Complete => true,
             Status::Error(_) => true,
+            Status::Cancelled => true,
             _ => false,
         }
     }
 }
 
-#[derive(Debug, Clone)]
+impl From<&Status> for messages::query::QueryStatus {
+    fn from(status: &Status) -> Self {
+        match status {
+            Status::Queued => messages::query::QueryStatus::Queued,
+            Status::SendingToWorker | Status::Running | Status::SentShutdown(_) => {
+                messages::query::QueryStatus::Running
+            }
+            Status::Complete => messages::query::QueryStatus::Complete,
+            Status::Error(err) => messages::query::QueryStatus::Error(err.clone()),
+            Status::Cancelled => messages::query::QueryStatus::Cancelled,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct OperatorInstance {
     pub id: u128,
     pub status: Status,
     pub pipeline_id: String,
     pub operator_id: String,
+    pub instance_idx: usize,
+    pub worker_id: Option<u128>,
+    pub retries: usize,
+    #[serde(default)]
+    pub metrics: Option<messages::query::OperatorMetrics>,
 }
 
 impl OperatorInstance {
-    pub fn new(pipeline_id: String, operator_id: String) -> OperatorInstance {
+    pub fn new(pipeline_id: String, operator_id: String, instance_idx: usize) -> OperatorInstance {
         OperatorInstance {
             id: Uuid::new_v4().as_u128(),
             status: Status::Queued,
             pipeline_id,
             operator_id,
+            instance_idx,
+            worker_id: None,
+            retries: 0,
+            metrics: None,
         }
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum RestartOutcome {
+    // the instance was queued to be assigned again
+    Restarted,
+    // the query ran out of retries and failed; holds the instances which
+    // were sent to a worker and need to be shut down
+    QueryFailed(Vec<OperatorInstance>),
+    // the query had already finished
+    AlreadyFinished,
+}
+
 #[derive(Debug, Clone)]
 pub struct Query {
     pub id: u128,
     pub query: String,
     pub physical_plan: planner::PhysicalPlan,
     pub status: Status,
+    pub restart_budget: usize,
+    pub restarts: usize,
+    pub max_retries: usize,
+    // share of the compute given to the query by the weighted fair policy
+    pub weight: usize,
+    pub priority: QueryPriority,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    // when the first operator instance was assigned to a worker; the
+    // timeout doesn't include the time spent queued
+    pub assigned_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub timeout: Option<Duration>,
 
     pub operator_instances: Vec<OperatorInstance>,
 }
@@ -79,11 +157,79 @@ impl Query {
             query,
             physical_plan,
             status: Status::Queued,
+            restart_budget: DEFAULT_QUERY_RESTART_BUDGET,
+            restarts: 0,
+            max_retries: DEFAULT_OPERATOR_INSTANCE_MAX_RETRIES,
+            weight: DEFAULT_QUERY_WEIGHT,
+            priority: QueryPriority::default(),
+            started_at: chrono::Utc::now(),
+            assigned_at: None,
+            timeout: None,
             operator_instances: Vec::new(),
         };
         query
     }
 
+    pub fn set_restart_budget(&mut self, restart_budget: usize) -> &Self {
+        self.restart_budget = restart_budget;
+        self
+    }
+
+    // Rebuild a persisted query. An instance which was being sent to a
+    // worker may never have been assigned so it's queued again.
+    pub fn from_record(record: QueryRecord, physical_plan: planner::PhysicalPlan) -> Query {
+        let mut query = Query::new(record.query, physical_plan);
+        query.id = record.id;
+        query.status = record.status;
+        query.restart_budget = record.restart_budget;
+        query.restarts = record.restarts;
+        query.max_retries = record.max_retries;
+        query.weight = record.weight;
+        query.started_at = record.started_at;
+        query.assigned_at = record.assigned_at;
+        query.timeout = record.timeout;
+        query.priority = record.priority;
+        query.operator_instances = record.operator_instances;
+        for op_in in &mut query.operator_instances {
+            if op_in.status == Status::SendingToWorker {
+                op_in.status = Status::Queued;
+            }
+        }
+        query
+    }
+
+    pub fn set_max_retries(&mut self, max_retries: usize) -> &Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn set_priority(&mut self, priority: QueryPriority) -> &Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn set_weight(&mut self, weight: usize) -> &Self {
+        self.weight = weight;
+        self
+    }
+
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) -> &Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // The timeout is measured from when the query was first assigned to a
+    // worker so queries waiting for compute don't time out.
+    pub fn timed_out(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        match (self.timeout, self.assigned_at) {
+            (Some(timeout), Some(assigned_at)) => match chrono::Duration::from_std(timeout) {
+                Ok(timeout) => now - assigned_at > timeout,
+                Err(_) => false,
+            },
+            _ => false,
+        }
+    }
+
     pub fn init(&mut self) -> &Self {
         self.add_operator_instances_from_physical_plan()
     }
@@ -91,8 +237,13 @@ impl Query {
     fn add_operator_instances_from_physical_plan(&mut self) -> &Self {
         for pipeline in self.physical_plan.get_pipelines_ref() {
             for op in pipeline.get_operators_ref() {
-                self.operator_instances
-                    .push(OperatorInstance::new(pipeline.id.clone(), op.id.clone()));
+                for instance_idx in 0..op.compute.instances {
+                    self.operator_instances.push(OperatorInstance::new(
+                        pipeline.id.clone(),
+                        op.id.clone(),
+                        instance_idx,
+                    ));
+                }
             }
         }
         self
@@ -102,17 +253,57 @@ impl Query {
 #[derive(Debug)]
 pub struct QueryHandlerState {
     queries: Vec<Query>,
+    scheduling_policy: SchedulingPolicy,
 }
 
 impl QueryHandlerState {
     pub fn new() -> QueryHandlerState {
         QueryHandlerState {
             queries: Vec::new(),
+            scheduling_policy: SchedulingPolicy::default(),
         }
     }
 
+    pub fn set_scheduling_policy(&mut self, scheduling_policy: SchedulingPolicy) -> &Self {
+        self.scheduling_policy = scheduling_policy;
+        self
+    }
+
+    pub fn queries(&self) -> &[Query] {
+        &self.queries
+    }
+
+    pub fn list_queries(
+        &self,
+        status: Option<&messages::query::QueryStatus>,
+    ) -> Vec<messages::query::QuerySummary> {
+        self.queries
+            .iter()
+            .map(|query| messages::query::QuerySummary {
+                query_id: query.id,
+                status: messages::query::QueryStatus::from(&query.status),
+                query: Self::truncate_query(&query.query),
+                submitted_at: query.started_at,
+            })
+            .filter(|summary| match status {
+                Some(status) => {
+                    std::mem::discriminant(&summary.status) == std::mem::discriminant(status)
+                }
+                None => true,
+            })
+            .collect()
+    }
+
+    fn truncate_query(query: &str) -> String {
+        if query.chars().count() <= MAX_LISTED_QUERY_CHARS {
+            return query.to_string();
+        }
+        let truncated: String = query.chars().take(MAX_LISTED_QUERY_CHARS).collect();
+        format!("{}...", truncated)
+    }
+
     pub fn add_query(&mut self, query: Query) {
-        self.queries.push(query.clone());
+        self.queries.push(query);
     }
 
     pub fn find_query(&self, query_id: &u128) -> Result<&Query> {
@@ -141,18 +332,6 @@ impl QueryHandlerState {
             .ok_or(QueryHandlerStateError::OperatorInstanceNotFound(op_instance_id.clone()).into())
     }
 
-    pub fn find_operator_instance_mut<'a>(
-        &'a mut self,
-        query: &'a mut Query,
-        op_instance_id: u128,
-    ) -> Result<&'a mut OperatorInstance> {
-        query
-            .operator_instances
-            .iter_mut()
-            .find(|item| item.id == op_instance_id.clone())
-            .ok_or(QueryHandlerStateError::OperatorInstanceNotFound(op_instance_id).into())
-    }
-
     pub fn get_operator_instance(
         &self,
         query_id: &u128,
@@ -199,6 +378,91 @@ impl QueryHandlerState {
         Err(QueryHandlerStateError::OperatorInstanceNotFound(op_instance_id.clone()).into())
     }
 
+    pub fn update_operator_instance_worker(
+        &mut self,
+        query_id: &u128,
+        op_instance_id: &u128,
+        worker_id: u128,
+    ) -> Result<()> {
+        let query = self.find_query_mut(query_id)?;
+        let op_in = query
+            .operator_instances
+            .iter_mut()
+            .find(|item| item.id == *op_instance_id)
+            .ok_or(QueryHandlerStateError::OperatorInstanceNotFound(
+                *op_instance_id,
+            ))?;
+        op_in.worker_id = Some(worker_id);
+        Ok(())
+    }
+
+    pub fn update_operator_instance_metrics(
+        &mut self,
+        query_id: &u128,
+        op_instance_id: &u128,
+        metrics: messages::query::OperatorMetrics,
+    ) -> Result<()> {
+        let query = self.find_query_mut(query_id)?;
+        let op_in = query
+            .operator_instances
+            .iter_mut()
+            .find(|item| item.id == *op_instance_id)
+            .ok_or(QueryHandlerStateError::OperatorInstanceNotFound(
+                *op_instance_id,
+            ))?;
+        op_in.metrics = Some(metrics);
+        Ok(())
+    }
+
+    pub fn get_query_metrics(
+        &self,
+        query_id: &u128,
+    ) -> Result<Vec<messages::query::OperatorInstanceStats>> {
+        let query = self.find_query(query_id)?;
+        Ok(query
+            .operator_instances
+            .iter()
+            .map(|op_in| messages::query::OperatorInstanceStats {
+                operator_instance_id: op_in.id,
+                operator_id: op_in.operator_id.clone(),
+                pipeline_id: op_in.pipeline_id.clone(),
+                metrics: op_in.metrics,
+            })
+            .collect())
+    }
+
+    pub fn get_query_diagnostics(
+        &self,
+        query_id: &u128,
+    ) -> Result<messages::query::QueryDiagnostics> {
+        let query = self.find_query(query_id)?;
+
+        let query_error = match &query.status {
+            Status::Error(err) => Some(err.clone()),
+            _ => None,
+        };
+        let operator_errors = query
+            .operator_instances
+            .iter()
+            .filter_map(|op_in| match &op_in.status {
+                Status::Error(err) => Some(messages::query::OperatorInstanceDiagnostics {
+                    operator_instance_id: op_in.id,
+                    operator_id: op_in.operator_id.clone(),
+                    pipeline_id: op_in.pipeline_id.clone(),
+                    worker_id: op_in.worker_id,
+                    error: err.clone(),
+                }),
+                _ => None,
+            })
+            .collect();
+
+        Ok(messages::query::QueryDiagnostics {
+            query_id: query.id,
+            query_error,
+            operator_errors,
+        })
+    }
+
     pub fn update_query_status(&mut self, query_id: &u128, status: Status) -> Result<()> {
         for query in &mut self.queries {
             if query.id != *query_id {
@@ -210,32 +474,139 @@ impl QueryHandlerState {
         Err(QueryHandlerStateError::QueryNotFound(query_id.clone()).into())
     }
 
-    pub fn get_outbound_exchange_id(&self, query_id: &u128, op_in_id: &u128) -> Result<String> {
-        let query = self.find_query(query_id)?;
-        let op_in = self.find_operator_instance(query, op_in_id)?;
-        if let Some(op) = query
-            .physical_plan
-            .get_operator(op_in.pipeline_id.clone(), op_in.operator_id.clone())
-        {
-            match &op.operator_type {
-                planner::OperatorType::Producer {
-                    outbound_exchange_id,
-                    ..
-                } => Ok(outbound_exchange_id.clone()),
-                planner::OperatorType::Exchange { .. } => {
-                    Err(QueryHandlerStateError::ExpectedProducerOperatorType.into())
-                }
+    // Returns false if the query has already finished.
+    pub fn update_query_priority(
+        &mut self,
+        query_id: &u128,
+        priority: QueryPriority,
+    ) -> Result<bool> {
+        let query = self.find_query_mut(query_id)?;
+        if query.status.terminal() {
+            return Ok(false);
+        }
+        query.priority = priority;
+        Ok(true)
+    }
+
+    // Cancel the query and each of its operator instances which hasn't
+    // already finished. Returns the instances which were sent to a worker
+    // and need to be shut down, or None if the query had already finished.
+    pub fn cancel_query(&mut self, query_id: &u128) -> Result<Option<Vec<OperatorInstance>>> {
+        self.stop_query(query_id, Status::Cancelled)
+    }
+
+    // Fail the query with the error and cancel each of its operator
+    // instances which hasn't already finished. Returns the instances which
+    // need to be shut down, or None if the query had already finished.
+    pub fn fail_query(
+        &mut self,
+        query_id: &u128,
+        error: String,
+    ) -> Result<Option<Vec<OperatorInstance>>> {
+        self.stop_query(query_id, Status::Error(error))
+    }
+
+    // Fail each running query which has passed its timeout. Returns the
+    // operator instances of those queries which need to be shut down.
+    pub fn fail_timed_out_queries(
+        &mut self,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<(u128, Vec<OperatorInstance>)>> {
+        let timed_out_query_ids: Vec<u128> = self
+            .queries
+            .iter()
+            .filter(|query| !query.status.terminal() && query.timed_out(now))
+            .map(|query| query.id)
+            .collect();
+
+        let mut result = Vec::new();
+        for query_id in timed_out_query_ids {
+            if let Some(op_instances) =
+                self.stop_query(&query_id, Status::Error("timeout".to_string()))?
+            {
+                result.push((query_id, op_instances));
             }
-        } else {
-            Err(QueryHandlerStateError::OperatorNotInPhysicalPlan(op_in.operator_id.clone()).into())
         }
+        Ok(result)
     }
 
-    pub fn get_inbound_exchange_ids(
-        &self,
+    fn stop_query(
+        &mut self,
         query_id: &u128,
-        op_in_id: &u128,
-    ) -> Result<Vec<String>> {
+        status: Status,
+    ) -> Result<Option<Vec<OperatorInstance>>> {
+        let query = self.find_query_mut(query_id)?;
+        if query.status.terminal() {
+            return Ok(None);
+        }
+        query.status = status;
+
+        let mut sent_to_worker = Vec::new();
+        for op_in in &mut query.operator_instances {
+            if op_in.status.terminal() {
+                continue;
+            }
+            if op_in.status != Status::Queued {
+                sent_to_worker.push(op_in.clone());
+            }
+            op_in.status = Status::Cancelled;
+        }
+        Ok(Some(sent_to_worker))
+    }
+
+    // Requeue a failed operator instance so it can be assigned to a worker
+    // again. Each restart counts against the query's restart budget and the
+    // instance's max retries. Once either is used up the query is stopped
+    // with the last error.
+    pub fn restart_operator_instance(
+        &mut self,
+        query_id: &u128,
+        op_instance_id: &u128,
+        last_error: String,
+    ) -> Result<RestartOutcome> {
+        let query = self.find_query_mut(query_id)?;
+        if query.status.terminal() {
+            return Ok(RestartOutcome::AlreadyFinished);
+        }
+
+        if query.restarts >= query.restart_budget {
+            let error = format!(
+                "retry budget exhausted after {} restarts; last error: {}",
+                query.restarts, last_error
+            );
+            return self.fail_restarted_query(query_id, error);
+        }
+
+        let max_retries = query.max_retries;
+        let op_in = query
+            .operator_instances
+            .iter_mut()
+            .find(|item| item.id == *op_instance_id)
+            .ok_or(QueryHandlerStateError::OperatorInstanceNotFound(
+                *op_instance_id,
+            ))?;
+        if op_in.retries >= max_retries {
+            let error = format!(
+                "operator instance failed after {} retries; last error: {}",
+                op_in.retries, last_error
+            );
+            return self.fail_restarted_query(query_id, error);
+        }
+        op_in.status = Status::Queued;
+        op_in.retries += 1;
+        query.restarts += 1;
+
+        Ok(RestartOutcome::Restarted)
+    }
+
+    fn fail_restarted_query(&mut self, query_id: &u128, error: String) -> Result<RestartOutcome> {
+        match self.fail_query(query_id, error)? {
+            Some(op_instances) => Ok(RestartOutcome::QueryFailed(op_instances)),
+            None => Ok(RestartOutcome::AlreadyFinished),
+        }
+    }
+
+    pub fn get_outbound_exchange_id(&self, query_id: &u128, op_in_id: &u128) -> Result<String> {
         let query = self.find_query(query_id)?;
         let op_in = self.find_operator_instance(query, op_in_id)?;
         if let Some(op) = query
@@ -244,9 +615,9 @@ impl QueryHandlerState {
         {
             match &op.operator_type {
                 planner::OperatorType::Producer {
-                    inbound_exchange_ids,
+                    outbound_exchange_id,
                     ..
-                } => Ok(inbound_exchange_ids.clone()),
+                } => Ok(outbound_exchange_id.clone()),
                 planner::OperatorType::Exchange { .. } => {
                     Err(QueryHandlerStateError::ExpectedProducerOperatorType.into())
                 }
@@ -292,6 +663,97 @@ impl QueryHandlerState {
         Ok(!any_not_complete)
     }
 
+    // The producers feeding an exchange are no longer needed once every
+    // consumer of the exchange has completed, e.g. below a satisfied limit.
+    // The unneeded instances are marked complete so the producers feeding
+    // them are stopped as well. Instances still being sent to a worker are
+    // left alone until the worker accepts them. Returns the instances which
+    // are running on a worker and need to be shut down.
+    pub fn stop_producers_without_consumers(
+        &mut self,
+        query_id: &u128,
+    ) -> Result<Vec<OperatorInstance>> {
+        let mut sent_to_worker = Vec::new();
+        loop {
+            let query = self.find_query(query_id)?;
+            if query.status.terminal() {
+                return Ok(sent_to_worker);
+            }
+
+            let mut unneeded_producer_ids: Vec<String> = Vec::new();
+            for pipeline in query.physical_plan.get_pipelines_ref() {
+                for op in pipeline.get_operators_ref() {
+                    if let planner::OperatorType::Exchange {
+                        outbound_producer_ids,
+                        inbound_producer_ids,
+                        ..
+                    } = &op.operator_type
+                    {
+                        let consumers_complete = !outbound_producer_ids.is_empty()
+                            && query.operator_instances.iter().all(|op_in| {
+                                !outbound_producer_ids.contains(&op_in.operator_id)
+                                    || op_in.status == Status::Complete
+                            });
+                        if consumers_complete {
+                            unneeded_producer_ids.extend(inbound_producer_ids.iter().cloned());
+                        }
+                    }
+                }
+            }
+
+            let query = self.find_query_mut(query_id)?;
+            let mut stopped_any = false;
+            for op_in in &mut query.operator_instances {
+                if !unneeded_producer_ids.contains(&op_in.operator_id)
+                    || op_in.status.terminal()
+                    || op_in.status == Status::SendingToWorker
+                {
+                    continue;
+                }
+                if op_in.status != Status::Queued {
+                    sent_to_worker.push(op_in.clone());
+                }
+                op_in.status = Status::Complete;
+                stopped_any = true;
+            }
+            if !stopped_any {
+                return Ok(sent_to_worker);
+            }
+        }
+    }
+
+    // Exchanges are shut down instead of completing so only the producer
+    // instances are checked.
+    pub fn all_producer_instances_complete(&self, query_id: &u128) -> Result<bool> {
+        let query = self.find_query(query_id)?;
+        for op_in in &query.operator_instances {
+            if op_in.status == Status::Complete {
+                continue;
+            }
+            if self.operator_instance_is_producer(query_id, &op_in.id)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    pub fn num_materialize_instances(&self, query_id: &u128) -> Result<usize> {
+        let query = self.find_query(query_id)?;
+        let mut num_instances = 0;
+        for pipeline in query.physical_plan.get_pipelines_ref() {
+            for op in pipeline.get_operators_ref() {
+                if let planner::OperatorType::Producer {
+                    task: planner::OperatorTask::MaterializeFiles { .. },
+                    ..
+                } = &op.operator_type
+                {
+                    num_instances += op.compute.instances;
+                }
+            }
+        }
+        Ok(num_instances)
+    }
+
     pub fn get_exchange_ids_without_any_consumers(&self, query_id: &u128) -> Result<Vec<String>> {
         let query = self.find_query(query_id)?;
 
@@ -326,6 +788,18 @@ impl QueryHandlerState {
                             }
                         }
 
+                        // the exchange was already told to shutdown
+                        if self
+                            .get_operator_instances(query_id, &op.id)?
+                            .iter()
+                            .all(|op_in| {
+                                matches!(op_in.status, Status::SentShutdown(_))
+                                    || op_in.status.terminal()
+                            })
+                        {
+                            continue 'op_loop;
+                        }
+
                         exchange_ids.push(op.id.clone());
                     }
                     planner::OperatorType::Producer { .. } => {
@@ -344,44 +818,154 @@ impl QueryHandlerState {
     ) -> Vec<(u128, &OperatorInstance, &Operator)> {
         let mut compute = available_compute.clone();
 
-        let mut result: Vec<(u128, &OperatorInstance, &Operator)> = Vec::new();
-        for query in &mut self.queries {
-            if compute.any_depleated() {
-                break;
-            }
+        // the instances of each runnable query which can be claimed
+        let mut candidates: Vec<(usize, VecDeque<(usize, OperatorCompute)>)> = Vec::new();
+        for (query_idx, query) in self.queries.iter().enumerate() {
             if query.status.terminal() {
                 continue;
             }
+            let query_candidates: VecDeque<(usize, OperatorCompute)> = query
+                .operator_instances
+                .iter()
+                .enumerate()
+                .filter(|(_, op_in)| op_in.status != Status::Running && !op_in.status.terminal())
+                .filter_map(|(op_in_idx, op_in)| {
+                    query
+                        .physical_plan
+                        .get_operator(op_in.pipeline_id.clone(), op_in.operator_id.clone())
+                        .map(|operator| (op_in_idx, operator.compute.clone()))
+                })
+                .collect();
+            if !query_candidates.is_empty() {
+                candidates.push((query_idx, query_candidates));
+            }
+        }
 
-            for op_in in &mut query.operator_instances {
-                let operator = if let Some(operator) = query
+        // higher priority queries are given the compute first and the
+        // policy divides the compute between queries of the same priority
+        let mut claims: Vec<(usize, usize)> = Vec::new();
+        for priority in [
+            QueryPriority::High,
+            QueryPriority::Normal,
+            QueryPriority::Low,
+        ] {
+            let (priority_candidates, other_candidates) = candidates
+                .into_iter()
+                .partition(|(query_idx, _)| self.queries[*query_idx].priority == priority);
+            candidates = other_candidates;
+            claims.extend(self.claim_with_policy(&mut compute, priority_candidates));
+        }
+
+        let now = chrono::Utc::now();
+        for (query_idx, op_in_idx) in &claims {
+            let query = &mut self.queries[*query_idx];
+            if query.status == Status::Queued {
+                query.status = Status::Running;
+            }
+            if query.assigned_at.is_none() {
+                query.assigned_at = Some(now);
+            }
+            query.operator_instances[*op_in_idx].status = Status::SendingToWorker;
+        }
+
+        claims
+            .into_iter()
+            .filter_map(|(query_idx, op_in_idx)| {
+                let query = &self.queries[query_idx];
+                let op_in = &query.operator_instances[op_in_idx];
+                query
                     .physical_plan
                     .get_operator(op_in.pipeline_id.clone(), op_in.operator_id.clone())
-                {
-                    operator
-                } else {
-                    continue;
-                };
-                let op_in_compute = operator.compute.clone();
-                if op_in.status == Status::Running || op_in.status.terminal() {
-                    continue;
-                } else if compute
-                    .clone()
-                    .subtract_single_operator_compute(&op_in_compute)
-                    .any_depleated()
-                {
-                    continue;
-                }
+                    .map(|operator| (query.id, op_in, operator))
+            })
+            .collect()
+    }
 
-                if query.status == Status::Queued {
-                    query.status = Status::Running;
+    // Returns the (query index, operator instance index) of each claimed
+    // instance.
+    fn claim_with_policy(
+        &self,
+        compute: &mut TotalOperatorCompute,
+        mut candidates: Vec<(usize, VecDeque<(usize, OperatorCompute)>)>,
+    ) -> Vec<(usize, usize)> {
+        let mut claims: Vec<(usize, usize)> = Vec::new();
+        match self.scheduling_policy {
+            SchedulingPolicy::Fifo => {
+                for (query_idx, query_candidates) in &mut candidates {
+                    while let Some(op_in_idx) = Self::claim_next(compute, query_candidates) {
+                        claims.push((*query_idx, op_in_idx));
+                    }
+                }
+            }
+            SchedulingPolicy::RoundRobin => {
+                while !candidates.is_empty() {
+                    candidates.retain_mut(|(query_idx, query_candidates)| {
+                        match Self::claim_next(compute, query_candidates) {
+                            Some(op_in_idx) => {
+                                claims.push((*query_idx, op_in_idx));
+                                true
+                            }
+                            None => false,
+                        }
+                    });
+                }
+            }
+            SchedulingPolicy::WeightedFair => {
+                let mut scheduled: Vec<usize> = candidates
+                    .iter()
+                    .map(|(query_idx, _)| {
+                        self.queries[*query_idx]
+                            .operator_instances
+                            .iter()
+                            .filter(|op_in| {
+                                op_in.status == Status::Running
+                                    || op_in.status == Status::SendingToWorker
+                            })
+                            .count()
+                    })
+                    .collect();
+                while !candidates.is_empty() {
+                    // the query with the fewest scheduled instances for its weight
+                    let idx = (0..candidates.len())
+                        .min_by(|a, b| {
+                            let weight_a = self.queries[candidates[*a].0].weight.max(1);
+                            let weight_b = self.queries[candidates[*b].0].weight.max(1);
+                            (scheduled[*a] * weight_b).cmp(&(scheduled[*b] * weight_a))
+                        })
+                        .expect("candidates should not be empty");
+                    let (query_idx, query_candidates) = &mut candidates[idx];
+                    match Self::claim_next(compute, query_candidates) {
+                        Some(op_in_idx) => {
+                            claims.push((*query_idx, op_in_idx));
+                            scheduled[idx] += 1;
+                        }
+                        None => {
+                            candidates.remove(idx);
+                            scheduled.remove(idx);
+                        }
+                    }
                 }
-                op_in.status = Status::SendingToWorker;
-                compute.subtract_single_operator_compute(&op_in_compute);
-                result.push((query.id.clone(), op_in, operator));
             }
         }
+        claims
+    }
 
-        result
+    // Claim the first candidate which fits in the remaining compute.
+    fn claim_next(
+        compute: &mut TotalOperatorCompute,
+        candidates: &mut VecDeque<(usize, OperatorCompute)>,
+    ) -> Option<usize> {
+        if compute.any_depleated() {
+            return None;
+        }
+        let idx = candidates.iter().position(|(_, op_in_compute)| {
+            !compute
+                .clone()
+                .subtract_single_operator_compute(op_in_compute)
+                .any_depleated()
+        })?;
+        let (op_in_idx, op_in_compute) = candidates.remove(idx)?;
+        compute.subtract_single_operator_compute(&op_in_compute);
+        Some(op_in_idx)
     }
 }
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::handlers::message_handler::messages::query::QueryPriority;
+
+use super::query_handler_state::{
+    OperatorInstance, Query, Status, DEFAULT_OPERATOR_INSTANCE_MAX_RETRIES,
+    DEFAULT_QUERY_RESTART_BUDGET, DEFAULT_QUERY_WEIGHT,
+};
+
+pub const QUERY_STATE_DIR: &str = "/query_state/";
+
+// The query metadata needed to rebuild a query after a restart. The
+// physical plan isn't stored since planning the sql again produces the
+// same operator and pipeline ids.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryRecord {
+    pub id: u128,
+    pub query: String,
+    pub status: Status,
+    // records written before these were stored use the defaults
+    #[serde(default = "default_restart_budget")]
+    pub restart_budget: usize,
+    pub restarts: usize,
+    #[serde(default = "default_max_retries")]
+    pub max_retries: usize,
+    #[serde(default = "default_weight")]
+    pub weight: usize,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    #[serde(default)]
+    pub assigned_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub timeout: Option<Duration>,
+    pub priority: QueryPriority,
+    pub operator_instances: Vec<OperatorInstance>,
+}
+
+impl QueryRecord {
+    pub fn new(query: &Query) -> QueryRecord {
+        QueryRecord {
+            id: query.id,
+            query: query.query.clone(),
+            status: query.status.clone(),
+            restart_budget: query.restart_budget,
+            restarts: query.restarts,
+            max_retries: query.max_retries,
+            weight: query.weight,
+            started_at: query.started_at,
+            assigned_at: query.assigned_at,
+            timeout: query.timeout,
+            priority: query.priority,
+            operator_instances: query.operator_instances.clone(),
+        }
+    }
+}
+
+fn default_restart_budget() -> usize {
+    DEFAULT_QUERY_RESTART_BUDGET
+}
+
+fn default_max_retries() -> usize {
+    DEFAULT_OPERATOR_INSTANCE_MAX_RETRIES
+}
+
+fn default_weight() -> usize {
+    DEFAULT_QUERY_WEIGHT
+}
+
+// Writes each query to the storage connection as json whenever it
+// changes.
+#[derive(Debug)]
+pub struct QueryStore {
+    storage_conn: opendal::Operator,
+    // the last data written for each query
+    written: HashMap<u128, Vec<u8>>,
+}
+
+impl QueryStore {
+    pub fn new(storage_conn: opendal::Operator) -> QueryStore {
+        QueryStore {
+            storage_conn,
+            written: HashMap::new(),
+        }
+    }
+
+    pub async fn checkpoint(&mut self, queries: &[Query]) -> Result<()> {
+        for query in queries {
+            let data = serde_json::to_vec(&QueryRecord::new(query))?;
+            if self.written.get(&query.id) == Some(&data) {
+                continue;
+            }
+            self.write(query.id, data.clone()).await?;
+            self.written.insert(query.id, data);
+        }
+        Ok(())
+    }
+
+    pub async fn read_all(&mut self) -> Result<Vec<QueryRecord>> {
+        let mut records = Vec::new();
+        let entries = match self.storage_conn.list(QUERY_STATE_DIR).await {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == opendal::ErrorKind::NotFound => return Ok(records),
+            Err(err) => return Err(err.into()),
+        };
+        for entry in entries {
+            if !entry.name().ends_with(".json") {
+                continue;
+            }
+            let data = self.storage_conn.read(entry.path()).await?.to_vec();
+            let record: QueryRecord = serde_json::from_slice(&data)?;
+            self.written.insert(record.id, data);
+            records.push(record);
+        }
+        records.sort_by_key(|record| record.started_at);
+        Ok(records)
+    }
+
+    // The record is written to a temporary key and then renamed so a
+    // reader never sees a partially written record.
+    async fn write(&self, query_id: u128, data: Vec<u8>) -> Result<()> {
+        let path = format!("{}{}.json", QUERY_STATE_DIR, Uuid::from_u128(query_id));
+        let tmp_path = format!("{}.tmp", path);
+
+        self.storage_conn.write(&tmp_path, data).await?;
+
+        let capability = self.storage_conn.info().full_capability();
+        if capability.rename {
+            self.storage_conn.rename(&tmp_path, &path).await?;
+        } else {
+            self.storage_conn.copy(&tmp_path, &path).await?;
+            self.storage_conn.delete(&tmp_path).await?;
+        }
+        Ok(())
+    }
+}
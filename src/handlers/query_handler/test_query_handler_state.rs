@@ -0,0 +1,847 @@
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::planner::{LogicalPlanner, PhysicalPlanner, PlannerConfig};
+
+use crate::handlers::message_handler::messages::query::{
+    OperatorMetrics, QueryPriority, QueryStatus,
+};
+use crate::handlers::operator_handler::TotalOperatorCompute;
+
+use super::query_handler_state::{
+    Query, QueryHandlerState, RestartOutcome, SchedulingPolicy, Status,
+};
+
+fn build_query(sql: &str) -> Result<Query> {
+    let logical_plan = LogicalPlanner::new(sql.to_string()).build()?;
+    let physical_plan = PhysicalPlanner::new(logical_plan, PlannerConfig::default()).build()?;
+    let mut query = Query::new(sql.to_string(), physical_plan);
+    query.init();
+    Ok(query)
+}
+
+#[test]
+fn test_repeated_restarts_exhaust_the_restart_budget() -> Result<()> {
+    let mut query = build_query("select * from read_files('data/path/*.parquet')")?;
+    query.set_restart_budget(3);
+    let query_id = query.id;
+    let op_in_id = query.operator_instances[0].id;
+    let running_op_in_id = query.operator_instances[1].id;
+    let queued_op_in_id = query.operator_instances[2].id;
+
+    let mut state = QueryHandlerState::new();
+    state.add_query(query);
+    state.update_operator_instance_status(&query_id, &running_op_in_id, Status::Running)?;
+
+    for idx in 0..3 {
+        state.update_operator_instance_status(
+            &query_id,
+            &op_in_id,
+            Status::Error(format!("connection reset {}", idx)),
+        )?;
+        let outcome = state.restart_operator_instance(
+            &query_id,
+            &op_in_id,
+            format!("connection reset {}", idx),
+        )?;
+        assert_eq!(outcome, RestartOutcome::Restarted);
+        assert_eq!(
+            state.get_operator_instance(&query_id, &op_in_id)?.status,
+            Status::Queued
+        );
+        assert_eq!(state.find_query(&query_id)?.status, Status::Queued);
+    }
+
+    state.update_operator_instance_status(
+        &query_id,
+        &op_in_id,
+        Status::Error("connection reset 3".to_string()),
+    )?;
+    let outcome =
+        state.restart_operator_instance(&query_id, &op_in_id, "connection reset 3".to_string())?;
+    let RestartOutcome::QueryFailed(op_instances) = outcome else {
+        panic!("expected the query to fail, got {:?}", outcome);
+    };
+    // only the instance sent to a worker needs to be shut down
+    assert_eq!(
+        op_instances
+            .iter()
+            .map(|op_in| op_in.id)
+            .collect::<Vec<u128>>(),
+        vec![running_op_in_id]
+    );
+
+    let query = state.find_query(&query_id)?;
+    assert!(query.status.terminal());
+    assert_eq!(
+        query.status,
+        Status::Error(
+            "retry budget exhausted after 3 restarts; last error: connection reset 3".to_string()
+        )
+    );
+    for op_in_id in [running_op_in_id, queued_op_in_id] {
+        assert_eq!(
+            state.get_operator_instance(&query_id, &op_in_id)?.status,
+            Status::Cancelled
+        );
+    }
+
+    // a failed query isn't restarted
+    assert_eq!(
+        state.restart_operator_instance(&query_id, &op_in_id, "connection reset 4".to_string())?,
+        RestartOutcome::AlreadyFinished
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_a_failed_query_stops_its_operator_instances() -> Result<()> {
+    let query = build_query("select * from read_files('data/path/*.parquet')")?;
+    let query_id = query.id;
+    let failed_op_in = query.operator_instances[0].clone();
+    let running_op_in = query.operator_instances[1].clone();
+    let queued_op_in = query.operator_instances[2].clone();
+
+    let mut state = QueryHandlerState::new();
+    state.add_query(query);
+
+    state.update_operator_instance_status(&query_id, &running_op_in.id, Status::Running)?;
+    state.update_operator_instance_status(
+        &query_id,
+        &failed_op_in.id,
+        Status::Error("invalid parquet file".to_string()),
+    )?;
+
+    let op_instances = state
+        .fail_query(&query_id, "invalid parquet file".to_string())?
+        .expect("query should not have finished");
+    assert_eq!(
+        op_instances
+            .iter()
+            .map(|op_in| op_in.id)
+            .collect::<Vec<u128>>(),
+        vec![running_op_in.id]
+    );
+
+    let query = state.find_query(&query_id)?;
+    assert!(query.status.terminal());
+    assert_eq!(
+        query.status,
+        Status::Error("invalid parquet file".to_string())
+    );
+    assert_eq!(
+        state
+            .get_operator_instance(&query_id, &failed_op_in.id)?
+            .status,
+        Status::Error("invalid parquet file".to_string())
+    );
+    assert_eq!(
+        state
+            .get_operator_instance(&query_id, &queued_op_in.id)?
+            .status,
+        Status::Cancelled
+    );
+
+    // a failed query is only stopped once
+    assert_eq!(
+        state.fail_query(&query_id, "invalid parquet file".to_string())?,
+        None
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_query_diagnostics_report_the_failing_operator() -> Result<()> {
+    let query = build_query("select * from read_files('data/path/*.parquet')")?;
+    let query_id = query.id;
+    let failed_op_in = query.operator_instances[0].clone();
+    let running_op_in = query.operator_instances[1].clone();
+
+    let mut state = QueryHandlerState::new();
+    state.add_query(query);
+
+    let worker_id = 42;
+    state.update_operator_instance_worker(&query_id, &failed_op_in.id, worker_id)?;
+    state.update_operator_instance_status(&query_id, &running_op_in.id, Status::Running)?;
+    state.update_operator_instance_status(
+        &query_id,
+        &failed_op_in.id,
+        Status::Error("unable to read file".to_string()),
+    )?;
+
+    let diagnostics = state.get_query_diagnostics(&query_id)?;
+    assert_eq!(diagnostics.query_id, query_id);
+    assert_eq!(diagnostics.query_error, None);
+    assert_eq!(diagnostics.operator_errors.len(), 1);
+
+    let op_err = &diagnostics.operator_errors[0];
+    assert_eq!(op_err.operator_instance_id, failed_op_in.id);
+    assert_eq!(op_err.operator_id, failed_op_in.operator_id);
+    assert_eq!(op_err.worker_id, Some(worker_id));
+    assert_eq!(op_err.error, "unable to read file".to_string());
+
+    state.update_query_status(&query_id, Status::Error("operator failed".to_string()))?;
+    let diagnostics = state.get_query_diagnostics(&query_id)?;
+    assert_eq!(diagnostics.query_error, Some("operator failed".to_string()));
+
+    assert!(state.get_query_diagnostics(&0).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_query_metrics_are_reported_per_operator_instance() -> Result<()> {
+    let query = build_query("select * from read_files('data/path/*.parquet')")?;
+    let query_id = query.id;
+    let num_op_instances = query.operator_instances.len();
+    let complete_op_in = query.operator_instances[0].clone();
+
+    let mut state = QueryHandlerState::new();
+    state.add_query(query);
+
+    let metrics = OperatorMetrics {
+        records_in: 100,
+        records_out: 40,
+        bytes_out: 2048,
+        wall_time_ms: 15,
+    };
+    state.update_operator_instance_metrics(&query_id, &complete_op_in.id, metrics)?;
+
+    let stats = state.get_query_metrics(&query_id)?;
+    assert_eq!(stats.len(), num_op_instances);
+    for op_in_stats in stats {
+        if op_in_stats.operator_instance_id == complete_op_in.id {
+            assert_eq!(op_in_stats.operator_id, complete_op_in.operator_id);
+            assert_eq!(op_in_stats.metrics, Some(metrics));
+        } else {
+            assert_eq!(op_in_stats.metrics, None);
+        }
+    }
+
+    assert!(state.get_query_metrics(&0).is_err());
+    assert!(state
+        .update_operator_instance_metrics(&query_id, &0, metrics)
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_an_instance_is_queued_for_each_operator_instance() -> Result<()> {
+    let sql = "select * from read_files('data/path/*.parquet')";
+    let logical_plan = LogicalPlanner::new(sql.to_string()).build()?;
+    let config = PlannerConfig {
+        default_producer_instances: 4,
+        ..PlannerConfig::default()
+    };
+    let physical_plan = PhysicalPlanner::new(logical_plan, config).build()?;
+    let mut query = Query::new(sql.to_string(), physical_plan.clone());
+    query.init();
+
+    for op in physical_plan.get_pipelines()[0].get_operators() {
+        let instance_idxs: Vec<usize> = query
+            .operator_instances
+            .iter()
+            .filter(|op_in| op_in.operator_id == op.id)
+            .map(|op_in| op_in.instance_idx)
+            .collect();
+        assert_eq!(
+            instance_idxs,
+            (0..op.compute.instances).collect::<Vec<usize>>()
+        );
+    }
+    assert_eq!(query.operator_instances.len(), 4 + 1 + 4 + 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_added_queries_own_their_operator_instances() -> Result<()> {
+    let query = build_query("select * from read_files('data/path/*.parquet')")?;
+    let query_id = query.id;
+    let expected_instances = query.operator_instances.clone();
+    assert!(!expected_instances.is_empty());
+
+    let mut state = QueryHandlerState::new();
+    state.add_query(query);
+
+    let query = state.find_query(&query_id)?;
+    assert_eq!(query.operator_instances, expected_instances);
+    for op_in in &expected_instances {
+        assert_eq!(state.find_operator_instance(query, &op_in.id)?, op_in);
+        assert!(state
+            .get_operator_instances(&query_id, &op_in.operator_id)?
+            .contains(op_in));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_cancelling_a_query_leaves_finished_operators_alone() -> Result<()> {
+    let query = build_query("select * from read_files('data/path/*.parquet')")?;
+    let query_id = query.id;
+    let complete_op_in = query.operator_instances[0].clone();
+    let running_op_in = query.operator_instances[1].clone();
+    let queued_op_in = query.operator_instances[2].clone();
+
+    let mut state = QueryHandlerState::new();
+    state.add_query(query);
+    state.update_query_status(&query_id, Status::Running)?;
+    state.update_operator_instance_status(&query_id, &complete_op_in.id, Status::Complete)?;
+    state.update_operator_instance_status(&query_id, &running_op_in.id, Status::Running)?;
+
+    let sent_to_worker = state
+        .cancel_query(&query_id)?
+        .expect("expected the query to be cancelled");
+    assert_eq!(
+        sent_to_worker
+            .iter()
+            .map(|op_in| op_in.id)
+            .collect::<Vec<u128>>(),
+        vec![running_op_in.id]
+    );
+
+    let query = state.find_query(&query_id)?;
+    assert_eq!(query.status, Status::Cancelled);
+    assert!(query.status.terminal());
+    assert_eq!(
+        state
+            .get_operator_instance(&query_id, &complete_op_in.id)?
+            .status,
+        Status::Complete
+    );
+    assert_eq!(
+        state
+            .get_operator_instance(&query_id, &running_op_in.id)?
+            .status,
+        Status::Cancelled
+    );
+    assert_eq!(
+        state
+            .get_operator_instance(&query_id, &queued_op_in.id)?
+            .status,
+        Status::Cancelled
+    );
+
+    // a finished query can't be cancelled again
+    assert!(state.cancel_query(&query_id)?.is_none());
+    assert!(state.cancel_query(&0).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_producer_instances_complete_without_the_exchanges() -> Result<()> {
+    let query = build_query("select * from read_files('data/path/*.parquet')")?;
+    let query_id = query.id;
+    let op_in_ids: Vec<u128> = query
+        .operator_instances
+        .iter()
+        .map(|op_in| op_in.id)
+        .collect();
+    let materialize_op_ids: Vec<String> = query
+        .physical_plan
+        .get_pipelines_ref()
+        .iter()
+        .flat_map(|pipeline| pipeline.get_operators_ref())
+        .filter(|op| {
+            op.operator_type.name() == "Producer"
+                && op.operator_type.task_name() == "MaterializeFiles"
+        })
+        .map(|op| op.id.clone())
+        .collect();
+
+    let mut state = QueryHandlerState::new();
+    state.add_query(query);
+    assert!(!state.all_producer_instances_complete(&query_id)?);
+
+    let mut num_materialize_instances = 0;
+    for op_in_id in &op_in_ids {
+        if !state.operator_instance_is_producer(&query_id, op_in_id)? {
+            continue;
+        }
+        state.update_operator_instance_status(&query_id, op_in_id, Status::Complete)?;
+        let op_in = state.get_operator_instance(&query_id, op_in_id)?;
+        if materialize_op_ids.contains(&op_in.operator_id) {
+            num_materialize_instances += 1;
+        }
+    }
+    assert!(state.all_producer_instances_complete(&query_id)?);
+    assert!(num_materialize_instances > 0);
+    assert_eq!(
+        state.num_materialize_instances(&query_id)?,
+        num_materialize_instances
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_producers_below_a_satisfied_limit_are_stopped() -> Result<()> {
+    let query =
+        build_query("select * from read_files('data/path/*.parquet') where id > 3 limit 10")?;
+    let query_id = query.id;
+    let instances_of = |task_name: &str| -> Vec<u128> {
+        let op_ids: Vec<String> = query
+            .physical_plan
+            .get_pipelines_ref()
+            .iter()
+            .flat_map(|pipeline| pipeline.get_operators_ref())
+            .filter(|op| {
+                op.operator_type.name() == "Producer" && op.operator_type.task_name() == task_name
+            })
+            .map(|op| op.id.clone())
+            .collect();
+        query
+            .operator_instances
+            .iter()
+            .filter(|op_in| op_ids.contains(&op_in.operator_id))
+            .map(|op_in| op_in.id)
+            .collect()
+    };
+    let scan_op_in_ids = instances_of("TableFunc");
+    let filter_op_in_ids = instances_of("Filter");
+    let limit_op_in_ids = instances_of("Limit");
+    let materialize_op_in_ids = instances_of("MaterializeFiles");
+    assert!(!scan_op_in_ids.is_empty() && !filter_op_in_ids.is_empty());
+    assert_eq!(limit_op_in_ids.len(), 1);
+
+    let mut state = QueryHandlerState::new();
+    state.add_query(query);
+    state.update_query_status(&query_id, Status::Running)?;
+    for op_in_id in scan_op_in_ids.iter().chain(filter_op_in_ids.iter()) {
+        state.update_operator_instance_status(&query_id, op_in_id, Status::Running)?;
+    }
+    state.update_operator_instance_status(&query_id, &limit_op_in_ids[0], Status::Running)?;
+
+    // nothing is stopped while the limit is still reading
+    assert!(state
+        .stop_producers_without_consumers(&query_id)?
+        .is_empty());
+
+    // the limit completing stops the filters and, through them, the scans
+    state.update_operator_instance_status(&query_id, &limit_op_in_ids[0], Status::Complete)?;
+    let mut stopped: Vec<u128> = state
+        .stop_producers_without_consumers(&query_id)?
+        .iter()
+        .map(|op_in| op_in.id)
+        .collect();
+    stopped.sort();
+    let mut expected: Vec<u128> = scan_op_in_ids
+        .iter()
+        .chain(filter_op_in_ids.iter())
+        .cloned()
+        .collect();
+    expected.sort();
+    assert_eq!(stopped, expected);
+
+    for op_in_id in &expected {
+        assert_eq!(
+            state.get_operator_instance(&query_id, op_in_id)?.status,
+            Status::Complete
+        );
+    }
+    // the materialization still reads the limited records
+    for op_in_id in &materialize_op_in_ids {
+        assert_eq!(
+            state.get_operator_instance(&query_id, op_in_id)?.status,
+            Status::Queued
+        );
+    }
+    assert!(state
+        .stop_producers_without_consumers(&query_id)?
+        .is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_exchanges_are_only_shutdown_once() -> Result<()> {
+    let query = build_query("select * from read_files('data/path/*.parquet') limit 10")?;
+    let query_id = query.id;
+    let producer_op_in_ids: Vec<u128> = query
+        .operator_instances
+        .iter()
+        .filter(|op_in| op_in.operator_id.ends_with("_producer"))
+        .map(|op_in| op_in.id)
+        .collect();
+    assert!(!producer_op_in_ids.is_empty());
+
+    let mut state = QueryHandlerState::new();
+    state.add_query(query);
+    state.update_query_status(&query_id, Status::Running)?;
+    for op_in_id in &producer_op_in_ids {
+        state.update_operator_instance_status(&query_id, op_in_id, Status::Complete)?;
+    }
+
+    let exchange_ids = state.get_exchange_ids_without_any_consumers(&query_id)?;
+    assert_eq!(exchange_ids.len(), 3);
+
+    for exchange_id in &exchange_ids {
+        for exchange_instance in state.get_operator_instances(&query_id, exchange_id)? {
+            state.update_operator_instance_status(
+                &query_id,
+                &exchange_instance.id,
+                Status::SentShutdown(chrono::Utc::now()),
+            )?;
+        }
+    }
+    assert!(state
+        .get_exchange_ids_without_any_consumers(&query_id)?
+        .is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_queries_fail_after_their_timeout() -> Result<()> {
+    let mut slow_query = build_query("select * from read_files('data/path/*.parquet')")?;
+    slow_query.set_timeout(Some(Duration::from_secs(10)));
+    let slow_query_id = slow_query.id;
+
+    let mut state = QueryHandlerState::new();
+    state.add_query(slow_query);
+
+    // the timeout starts once the query is assigned to a worker
+    assert!(!state
+        .find_query(&slow_query_id)?
+        .timed_out(chrono::Utc::now() + chrono::Duration::seconds(11)));
+    let available_compute = TotalOperatorCompute {
+        instances: 2,
+        memory_in_mib: 1_000_000,
+        cpu_in_thousandths: 1_000_000,
+    };
+    let claimed_op_in_ids: Vec<u128> = state
+        .claim_operator_instances_up_to_compute_available(&available_compute)
+        .iter()
+        .map(|(_, op_in, _)| op_in.id)
+        .collect();
+    assert_eq!(claimed_op_in_ids.len(), 1);
+    let slow_op_in = state.get_operator_instance(&slow_query_id, &claimed_op_in_ids[0])?;
+    state.update_operator_instance_status(&slow_query_id, &slow_op_in.id, Status::Running)?;
+
+    // queries without a timeout run until they finish
+    let query = build_query("select * from read_files('data/path/*.parquet')")?;
+    let query_id = query.id;
+    state.add_query(query);
+
+    // queries waiting for compute don't time out
+    let mut queued_query = build_query("select * from read_files('data/path/*.parquet')")?;
+    queued_query.set_timeout(Some(Duration::from_secs(10)));
+    let queued_query_id = queued_query.id;
+    state.add_query(queued_query);
+
+    let now = chrono::Utc::now();
+    assert!(state.fail_timed_out_queries(now)?.is_empty());
+    assert_eq!(state.find_query(&slow_query_id)?.status, Status::Running);
+
+    let timed_out = state.fail_timed_out_queries(now + chrono::Duration::seconds(11))?;
+    assert_eq!(timed_out.len(), 1);
+    assert_eq!(timed_out[0].0, slow_query_id);
+    assert_eq!(
+        timed_out[0]
+            .1
+            .iter()
+            .map(|op_in| op_in.id)
+            .collect::<Vec<u128>>(),
+        vec![slow_op_in.id]
+    );
+    assert_eq!(
+        state.find_query(&slow_query_id)?.status,
+        Status::Error("timeout".to_string())
+    );
+    assert_eq!(
+        state
+            .get_operator_instance(&slow_query_id, &slow_op_in.id)?
+            .status,
+        Status::Cancelled
+    );
+    assert_eq!(state.find_query(&query_id)?.status, Status::Queued);
+    assert_eq!(state.find_query(&queued_query_id)?.status, Status::Queued);
+
+    // a failed query only times out once
+    assert!(state
+        .fail_timed_out_queries(now + chrono::Duration::seconds(20))?
+        .is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_an_instance_is_only_retried_up_to_max_retries() -> Result<()> {
+    let mut query = build_query("select * from read_files('data/path/*.parquet')")?;
+    query.set_max_retries(2);
+    let query_id = query.id;
+    let op_in_id = query.operator_instances[0].id;
+    let other_op_in_id = query.operator_instances[1].id;
+
+    let mut state = QueryHandlerState::new();
+    state.add_query(query);
+
+    for idx in 0..2 {
+        let outcome = state.restart_operator_instance(
+            &query_id,
+            &op_in_id,
+            format!("storage unavailable {}", idx),
+        )?;
+        assert_eq!(outcome, RestartOutcome::Restarted);
+        assert_eq!(
+            state.get_operator_instance(&query_id, &op_in_id)?.retries,
+            idx + 1
+        );
+    }
+
+    // retries are counted per instance
+    assert_eq!(
+        state.restart_operator_instance(
+            &query_id,
+            &other_op_in_id,
+            "storage unavailable".to_string()
+        )?,
+        RestartOutcome::Restarted
+    );
+
+    let outcome = state.restart_operator_instance(
+        &query_id,
+        &op_in_id,
+        "storage unavailable 2".to_string(),
+    )?;
+    assert!(matches!(outcome, RestartOutcome::QueryFailed(_)));
+    assert_eq!(
+        state.find_query(&query_id)?.status,
+        Status::Error(
+            "operator instance failed after 2 retries; last error: storage unavailable 2"
+                .to_string()
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_scheduling_policies_share_compute_between_queries() -> Result<()> {
+    struct TestCase {
+        case_name: String,
+        scheduling_policy: SchedulingPolicy,
+        weights: Vec<usize>,
+        available_instances: usize,
+        expected_claims: Vec<usize>,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            case_name: "fifo drains the first query".to_string(),
+            scheduling_policy: SchedulingPolicy::Fifo,
+            weights: vec![1, 1, 1],
+            available_instances: 7,
+            expected_claims: vec![4, 2, 0],
+        },
+        TestCase {
+            case_name: "round robin".to_string(),
+            scheduling_policy: SchedulingPolicy::RoundRobin,
+            weights: vec![1, 1, 1],
+            available_instances: 7,
+            expected_claims: vec![2, 2, 2],
+        },
+        TestCase {
+            case_name: "weighted fair".to_string(),
+            scheduling_policy: SchedulingPolicy::WeightedFair,
+            weights: vec![1, 1, 2],
+            available_instances: 9,
+            expected_claims: vec![2, 2, 4],
+        },
+    ];
+
+    for test_case in test_cases {
+        println!("test case: {}", test_case.case_name);
+
+        let mut state = QueryHandlerState::new();
+        state.set_scheduling_policy(test_case.scheduling_policy);
+        let mut query_ids = Vec::new();
+        for weight in &test_case.weights {
+            let mut query = build_query("select * from read_files('data/path/*.parquet')")?;
+            query.set_weight(*weight);
+            query_ids.push(query.id);
+            state.add_query(query);
+        }
+
+        let available_compute = TotalOperatorCompute {
+            instances: test_case.available_instances,
+            memory_in_mib: 1_000_000,
+            cpu_in_thousandths: 1_000_000,
+        };
+        let claimed_query_ids: Vec<u128> = state
+            .claim_operator_instances_up_to_compute_available(&available_compute)
+            .iter()
+            .map(|(query_id, _, _)| *query_id)
+            .collect();
+
+        let claims: Vec<usize> = query_ids
+            .iter()
+            .map(|query_id| {
+                claimed_query_ids
+                    .iter()
+                    .filter(|claimed_query_id| *claimed_query_id == query_id)
+                    .count()
+            })
+            .collect();
+        assert_eq!(claims, test_case.expected_claims);
+
+        for (query_id, num_claims) in query_ids.iter().zip(claims) {
+            let num_sending = state
+                .find_query(query_id)?
+                .operator_instances
+                .iter()
+                .filter(|op_in| op_in.status == Status::SendingToWorker)
+                .count();
+            assert_eq!(num_sending, num_claims);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_higher_priority_queries_are_scheduled_first() -> Result<()> {
+    let mut low_query = build_query("select * from read_files('data/path/*.parquet')")?;
+    low_query.set_priority(QueryPriority::Low);
+    let low_query_id = low_query.id;
+    let high_query = build_query("select * from read_files('data/path/*.parquet')")?;
+    let high_query_id = high_query.id;
+
+    let mut state = QueryHandlerState::new();
+    state.add_query(low_query);
+    state.add_query(high_query);
+
+    // the query submitted second is raised above the first
+    assert!(state.update_query_priority(&high_query_id, QueryPriority::High)?);
+
+    let available_compute = TotalOperatorCompute {
+        instances: 3,
+        memory_in_mib: 1_000_000,
+        cpu_in_thousandths: 1_000_000,
+    };
+    let claimed_query_ids: Vec<u128> = state
+        .claim_operator_instances_up_to_compute_available(&available_compute)
+        .iter()
+        .map(|(query_id, _, _)| *query_id)
+        .collect();
+    assert_eq!(claimed_query_ids, vec![high_query_id, high_query_id]);
+    assert_eq!(state.find_query(&low_query_id)?.status, Status::Queued);
+
+    // the workers accept the assigned instances
+    let assigned_op_in_ids: Vec<u128> = state
+        .find_query(&high_query_id)?
+        .operator_instances
+        .iter()
+        .filter(|op_in| op_in.status == Status::SendingToWorker)
+        .map(|op_in| op_in.id)
+        .collect();
+    for op_in_id in assigned_op_in_ids {
+        state.update_operator_instance_status(&high_query_id, &op_in_id, Status::Running)?;
+    }
+
+    // the low priority query is given the compute left over
+    let available_compute = TotalOperatorCompute {
+        instances: 5,
+        memory_in_mib: 1_000_000,
+        cpu_in_thousandths: 1_000_000,
+    };
+    let claimed_query_ids: Vec<u128> = state
+        .claim_operator_instances_up_to_compute_available(&available_compute)
+        .iter()
+        .map(|(query_id, _, _)| *query_id)
+        .collect();
+    assert_eq!(
+        claimed_query_ids,
+        vec![high_query_id, high_query_id, low_query_id, low_query_id]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_list_queries() -> Result<()> {
+    struct TestCase {
+        case_name: String,
+        status: Option<QueryStatus>,
+        expected_query_idxs: Vec<usize>,
+    }
+
+    let long_sql = format!(
+        "select id, {} from read_files('data/path/*.parquet')",
+        (0..40)
+            .map(|idx| format!("id + {} as c{}", idx, idx))
+            .collect::<Vec<String>>()
+            .join(", ")
+    );
+    let sqls = vec![
+        "select * from read_files('data/path/*.parquet')".to_string(),
+        long_sql.clone(),
+        "select id from read_files('data/path/*.parquet')".to_string(),
+    ];
+
+    let mut state = QueryHandlerState::new();
+    let mut query_ids = Vec::new();
+    for sql in &sqls {
+        let query = build_query(sql)?;
+        query_ids.push(query.id);
+        state.add_query(query);
+    }
+    state.update_query_status(&query_ids[1], Status::SendingToWorker)?;
+    state.update_query_status(&query_ids[2], Status::Error("file not found".to_string()))?;
+
+    let test_cases = vec![
+        TestCase {
+            case_name: "all queries".to_string(),
+            status: None,
+            expected_query_idxs: vec![0, 1, 2],
+        },
+        TestCase {
+            case_name: "running queries".to_string(),
+            status: Some(QueryStatus::Running),
+            expected_query_idxs: vec![1],
+        },
+        TestCase {
+            case_name: "errored queries ignore the error text".to_string(),
+            status: Some(QueryStatus::Error(String::new())),
+            expected_query_idxs: vec![2],
+        },
+        TestCase {
+            case_name: "no complete queries".to_string(),
+            status: Some(QueryStatus::Complete),
+            expected_query_idxs: vec![],
+        },
+    ];
+
+    for test_case in test_cases {
+        println!("test case: {}", test_case.case_name);
+        let listed_query_ids: Vec<u128> = state
+            .list_queries(test_case.status.as_ref())
+            .iter()
+            .map(|summary| summary.query_id)
+            .collect();
+        let expected_query_ids: Vec<u128> = test_case
+            .expected_query_idxs
+            .iter()
+            .map(|idx| query_ids[*idx])
+            .collect();
+        assert_eq!(listed_query_ids, expected_query_ids);
+    }
+
+    let summaries = state.list_queries(None);
+    assert_eq!(summaries[0].query, sqls[0]);
+    assert_eq!(summaries[0].status, QueryStatus::Queued);
+    assert_eq!(summaries[1].query.chars().count(), 103);
+    assert!(long_sql.starts_with(summaries[1].query.trim_end_matches("...")));
+    assert_eq!(
+        summaries[2].status,
+        QueryStatus::Error("file not found".to_string())
+    );
+
+    Ok(())
+}
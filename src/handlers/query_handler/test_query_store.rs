@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::handlers::message_handler::messages::query::QueryPriority;
+use crate::handlers::operator_handler::operators::ConnectionRegistry;
+use crate::planner::{LogicalPlanner, PhysicalPlan, PhysicalPlanner, PlannerConfig};
+
+use super::query_handler_state::{Query, QueryHandlerState, Status};
+use super::query_store::{QueryStore, QUERY_STATE_DIR};
+
+fn build_physical_plan(sql: &str) -> Result<PhysicalPlan> {
+    let logical_plan = LogicalPlanner::new(sql.to_string()).build()?;
+    PhysicalPlanner::new(logical_plan, PlannerConfig::default()).build()
+}
+
+#[tokio::test]
+async fn test_queries_are_restored_from_the_store() -> Result<()> {
+    let dir = tempdir::TempDir::new("query_store")?;
+    let mut conn_reg = ConnectionRegistry::new();
+    conn_reg.add_connection(
+        "default".to_string(),
+        opendal::Scheme::Fs,
+        HashMap::from([("root".to_string(), dir.path().to_string_lossy().to_string())]),
+    );
+
+    // nothing has been persisted yet
+    let mut query_store = QueryStore::new(conn_reg.get_operator("default")?);
+    assert!(query_store.read_all().await?.is_empty());
+
+    let sql = "select * from read_files('data/path/*.parquet')";
+    let mut running_query = Query::new(sql.to_string(), build_physical_plan(sql)?);
+    running_query.init();
+    running_query.set_timeout(Some(Duration::from_secs(30)));
+    running_query.set_priority(QueryPriority::High);
+    running_query.set_restart_budget(4);
+    running_query.set_max_retries(2);
+    running_query.set_weight(5);
+    let running_query_id = running_query.id;
+    let running_op_in_id = running_query.operator_instances[0].id;
+    let sending_op_in_id = running_query.operator_instances[1].id;
+
+    let mut complete_query = Query::new(sql.to_string(), build_physical_plan(sql)?);
+    complete_query.init();
+    let complete_query_id = complete_query.id;
+
+    let mut state = QueryHandlerState::new();
+    state.add_query(running_query);
+    state.add_query(complete_query);
+    state.update_query_status(&running_query_id, Status::Running)?;
+    state.update_operator_instance_status(&running_query_id, &running_op_in_id, Status::Running)?;
+    state.update_operator_instance_worker(&running_query_id, &running_op_in_id, 7)?;
+    state.update_operator_instance_status(
+        &running_query_id,
+        &sending_op_in_id,
+        Status::SendingToWorker,
+    )?;
+    state.update_query_status(&complete_query_id, Status::Complete)?;
+    query_store.checkpoint(state.queries()).await?;
+
+    // a new store reads the queries written by the old one
+    let mut query_store = QueryStore::new(conn_reg.get_operator("default")?);
+    let records = query_store.read_all().await?;
+    assert_eq!(
+        records
+            .iter()
+            .map(|record| record.id)
+            .collect::<Vec<u128>>(),
+        vec![running_query_id, complete_query_id]
+    );
+
+    let mut restored_state = QueryHandlerState::new();
+    for record in records {
+        let physical_plan = build_physical_plan(&record.query)?;
+        restored_state.add_query(Query::from_record(record, physical_plan));
+    }
+
+    let running_query = restored_state.find_query(&running_query_id)?;
+    assert_eq!(running_query.status, Status::Running);
+    assert_eq!(running_query.timeout, Some(Duration::from_secs(30)));
+    assert_eq!(running_query.priority, QueryPriority::High);
+    assert_eq!(running_query.restart_budget, 4);
+    assert_eq!(running_query.max_retries, 2);
+    assert_eq!(running_query.weight, 5);
+    assert_eq!(running_query.assigned_at, None);
+    let running_op_in =
+        restored_state.get_operator_instance(&running_query_id, &running_op_in_id)?;
+    assert_eq!(running_op_in.status, Status::Running);
+    assert_eq!(running_op_in.worker_id, Some(7));
+    // the instance may never have reached the worker
+    assert_eq!(
+        restored_state
+            .get_operator_instance(&running_query_id, &sending_op_in_id)?
+            .status,
+        Status::Queued
+    );
+    assert_eq!(
+        restored_state.find_query(&complete_query_id)?.status,
+        Status::Complete
+    );
+
+    // only changed queries are written again
+    let complete_query_path = dir
+        .path()
+        .join(QUERY_STATE_DIR.trim_start_matches('/'))
+        .join(format!("{}.json", Uuid::from_u128(complete_query_id)));
+    std::fs::remove_file(&complete_query_path)?;
+    restored_state.update_query_status(&running_query_id, Status::Complete)?;
+    query_store.checkpoint(restored_state.queries()).await?;
+    assert!(!complete_query_path.exists());
+    assert_eq!(query_store.read_all().await?.len(), 1);
+
+    Ok(())
+}
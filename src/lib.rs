@@ -2,3 +2,6 @@ pub mod client;
 pub mod handlers;
 pub mod planner;
 pub mod worker;
+
+#[cfg(test)]
+pub mod test_support;
@@ -2,12 +2,18 @@ use std::collections::HashMap;
 use std::usize;
 
 use anyhow::Result;
+use arrow::datatypes::SchemaRef;
+use serde::{Deserialize, Serialize};
 use sqlparser::ast::{
-    Expr, FunctionArg, ObjectName, Query, Select, SelectItem, SetExpr, Statement, TableAlias,
-    TableFactor, TableFunctionArgs, TableWithJoins,
+    BinaryOperator, Distinct, Expr, Function, FunctionArg, FunctionArgExpr, FunctionArgOperator,
+    FunctionArguments, GroupByExpr, Ident, JoinConstraint, JoinOperator, ObjectName, Offset,
+    OrderBy, Query, Select, SelectItem, SetExpr, SetOperator, SetQuantifier, Statement, TableAlias,
+    TableFactor, TableFunctionArgs, TableWithJoins, Value, Values, WildcardAdditionalOptions,
 };
 use sqlparser::dialect::GenericDialect;
+use sqlparser::keywords::Keyword;
 use sqlparser::parser::Parser;
+use sqlparser::tokenizer::{Token, Tokenizer};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -18,6 +24,90 @@ pub enum PlanError {
     NodeDoesNotExist(usize),
     #[error("not implemented: {0}")]
     NotImplemented(String),
+    #[error("invalid aggregate: {0}")]
+    InvalidAggregate(String),
+    #[error("expected BY after {0}")]
+    ExpectedByAfterKeyword(String),
+    #[error("invalid number: {0}")]
+    InvalidNumber(String),
+    #[error("invalid join condition: {0}")]
+    InvalidJoinCondition(String),
+    #[error("union column {0} does not match between the left and right queries")]
+    UnionColumnMismatch(usize),
+    #[error("ambiguous column {name}; found in tables: {}", tables.join(", "))]
+    AmbiguousColumn { name: String, tables: Vec<String> },
+    #[error("unknown column {name}; available columns: {}", available.join(", "))]
+    UnknownColumn {
+        name: String,
+        available: Vec<String>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, PartialOrd, Ord, Eq, Serialize, Deserialize)]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl AggregateFunction {
+    pub fn from_name(name: &str) -> Option<AggregateFunction> {
+        match name.to_lowercase().as_str() {
+            "count" => Some(Self::Count),
+            "sum" => Some(Self::Sum),
+            "avg" => Some(Self::Avg),
+            "min" => Some(Self::Min),
+            "max" => Some(Self::Max),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Count => "count",
+            Self::Sum => "sum",
+            Self::Avg => "avg",
+            Self::Min => "min",
+            Self::Max => "max",
+        }
+    }
+}
+
+// An aggregate function call found in the select list. The arg is
+// None for count(*) since it counts rows rather than values. The expr
+// is the original function call so later stages can reference the
+// aggregate's result by the expression that produced it.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Ord, Eq, Serialize, Deserialize)]
+pub struct Aggregate {
+    pub func: AggregateFunction,
+    pub arg: Option<Expr>,
+    pub expr: Expr,
+}
+
+#[derive(Clone, Debug, PartialEq, PartialOrd, Ord, Eq, Serialize, Deserialize)]
+pub enum JoinType {
+    Inner,
+    Left,
+}
+
+impl JoinType {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Inner => "inner",
+            Self::Left => "left",
+        }
+    }
+}
+
+// A single sort key. Nulls sort last for ascending keys and first for
+// descending keys unless NULLS FIRST/LAST is given, the same as Postgres.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Ord, Eq, Serialize, Deserialize)]
+pub struct SortExpr {
+    pub expr: Expr,
+    pub asc: bool,
+    pub nulls_first: bool,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -34,6 +124,36 @@ pub enum LogicalPlanNodeType {
     Filter {
         expr: Expr,
     },
+    // an equi-join; the left_keys are evaluated against the records
+    // from the first inbound node and right_keys against the second
+    Join {
+        join_type: JoinType,
+        left_keys: Vec<Expr>,
+        right_keys: Vec<Expr>,
+    },
+    Aggregate {
+        group_by: Vec<Expr>,
+        aggregates: Vec<Aggregate>,
+    },
+    // removes rows with the same keys; no keys means all columns
+    Distinct {
+        keys: Vec<Expr>,
+    },
+    Sort {
+        exprs: Vec<SortExpr>,
+    },
+    Limit {
+        limit: Option<u64>,
+        offset: Option<u64>,
+    },
+    // combines the records from the first inbound node, projected with
+    // the left_fields, and the second, projected with the right_fields;
+    // distinct removes duplicate rows from the result
+    Union {
+        distinct: bool,
+        left_fields: Vec<SelectItem>,
+        right_fields: Vec<SelectItem>,
+    },
     Materialize {
         fields: Vec<SelectItem>,
     },
@@ -42,7 +162,13 @@ pub enum LogicalPlanNodeType {
 #[derive(Clone, Debug, PartialEq)]
 pub enum StageType {
     TableSource,
+    Join,
     Filter,
+    Aggregate,
+    Distinct,
+    Sort,
+    Limit,
+    Union,
     Materialize,
 }
 
@@ -59,6 +185,29 @@ impl Stage {
     }
 }
 
+// The estimated output of a plan node. There aren't any table statistics
+// yet so table sources use default estimates which the downstream nodes
+// then scale.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EstimatedStats {
+    pub rows: usize,
+    pub row_bytes: usize,
+}
+
+impl EstimatedStats {
+    pub fn total_bytes(&self) -> usize {
+        self.rows.saturating_mul(self.row_bytes)
+    }
+}
+
+const DEFAULT_TABLE_ROWS: usize = 1_000_000;
+const DEFAULT_TABLE_ROW_BYTES: usize = 128;
+const DEFAULT_COLUMN_BYTES: usize = 16;
+// fraction of rows expected to pass a filter
+const FILTER_SELECTIVITY_DIVISOR: usize = 2;
+// expected number of rows per group
+const ROWS_PER_GROUP: usize = 10;
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct LogicalPlanNode {
     pub id: usize,
@@ -103,6 +252,19 @@ impl LogicalPlan {
         self.nodes.clone()
     }
 
+    pub fn get_table_sources(&self) -> Vec<LogicalPlanNodeType> {
+        self.nodes
+            .iter()
+            .filter(|node| {
+                matches!(
+                    node.node,
+                    LogicalPlanNodeType::TableFunc { .. } | LogicalPlanNodeType::Table { .. }
+                )
+            })
+            .map(|node| node.node.clone())
+            .collect()
+    }
+
     pub fn get_all_node_ids(&self) -> Vec<usize> {
         let mut ids: Vec<usize> = Vec::new();
         for node in &self.nodes {
@@ -132,13 +294,89 @@ impl LogicalPlan {
         }
     }
 
-    pub fn add_node(&mut self, node: LogicalPlanNodeType, stage: Stage) -> usize {
-        self.nodes.push(LogicalPlanNode {
-            node,
-            stage,
-            id: self.nodes.len(),
+    // Estimates the output of the node from the estimates of its
+    // inbound nodes.
+    pub fn get_estimated_stats(&self, node_idx: usize) -> Result<EstimatedStats> {
+        let node = match self.get_node(node_idx) {
+            Some(node) => node,
+            None => return Err(PlanError::NodeDoesNotExist(node_idx).into()),
+        };
+        let inbound_stats = self
+            .get_inbound_nodes(node_idx)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|inbound_idx| self.get_estimated_stats(inbound_idx))
+            .collect::<Result<Vec<EstimatedStats>>>()?;
+        let input = inbound_stats.first().cloned().unwrap_or(EstimatedStats {
+            rows: DEFAULT_TABLE_ROWS,
+            row_bytes: DEFAULT_TABLE_ROW_BYTES,
         });
-        return self.nodes.len();
+
+        let stats = match &node.node {
+            LogicalPlanNodeType::TableFunc { .. } | LogicalPlanNodeType::Table { .. } => {
+                EstimatedStats {
+                    rows: DEFAULT_TABLE_ROWS,
+                    row_bytes: DEFAULT_TABLE_ROW_BYTES,
+                }
+            }
+            LogicalPlanNodeType::Filter { .. } => EstimatedStats {
+                rows: input.rows / FILTER_SELECTIVITY_DIVISOR,
+                row_bytes: input.row_bytes,
+            },
+            LogicalPlanNodeType::Join { .. } => EstimatedStats {
+                rows: inbound_stats
+                    .iter()
+                    .map(|stats| stats.rows)
+                    .max()
+                    .unwrap_or(0),
+                row_bytes: inbound_stats.iter().map(|stats| stats.row_bytes).sum(),
+            },
+            LogicalPlanNodeType::Aggregate {
+                group_by,
+                aggregates,
+            } => EstimatedStats {
+                rows: if group_by.is_empty() {
+                    1
+                } else {
+                    std::cmp::max(1, input.rows / ROWS_PER_GROUP)
+                },
+                row_bytes: (group_by.len() + aggregates.len()) * DEFAULT_COLUMN_BYTES,
+            },
+            LogicalPlanNodeType::Distinct { .. } => EstimatedStats {
+                rows: std::cmp::max(1, input.rows / ROWS_PER_GROUP),
+                row_bytes: input.row_bytes,
+            },
+            LogicalPlanNodeType::Union { distinct, .. } => {
+                let rows: usize = inbound_stats.iter().map(|stats| stats.rows).sum();
+                EstimatedStats {
+                    rows: if *distinct {
+                        std::cmp::max(1, rows / ROWS_PER_GROUP)
+                    } else {
+                        rows
+                    },
+                    row_bytes: inbound_stats
+                        .iter()
+                        .map(|stats| stats.row_bytes)
+                        .max()
+                        .unwrap_or(0),
+                }
+            }
+            LogicalPlanNodeType::Sort { .. } | LogicalPlanNodeType::Materialize { .. } => input,
+            LogicalPlanNodeType::Limit { limit, offset } => {
+                let rows = input.rows.saturating_sub(offset.unwrap_or(0) as usize);
+                EstimatedStats {
+                    rows: limit.map_or(rows, |limit| std::cmp::min(rows, limit as usize)),
+                    row_bytes: input.row_bytes,
+                }
+            }
+        };
+        Ok(stats)
+    }
+
+    pub fn add_node(&mut self, node: LogicalPlanNodeType, stage: Stage) -> usize {
+        let id = self.nodes.len();
+        self.nodes.push(LogicalPlanNode { node, stage, id });
+        id
     }
 
     pub fn connect(&mut self, from_node_idx: usize, to_node_idx: usize) {
@@ -202,11 +440,42 @@ impl LogicalPlan {
     }
 }
 
+// The arrow schemas of the table sources of a query. When the planner
+// has them the column references of each select are checked against the
+// schemas of the select's table sources.
+#[derive(Clone, Debug, Default)]
+pub struct TableSchemas {
+    schemas: Vec<(LogicalPlanNodeType, SchemaRef)>,
+}
+
+impl TableSchemas {
+    pub fn new() -> TableSchemas {
+        TableSchemas::default()
+    }
+
+    pub fn add_schema(&mut self, table_source: LogicalPlanNodeType, schema: SchemaRef) {
+        self.schemas.push((table_source, schema));
+    }
+
+    pub fn get_schema(&self, table_source: &LogicalPlanNodeType) -> Option<SchemaRef> {
+        self.schemas
+            .iter()
+            .find(|(node, _)| node == table_source)
+            .map(|(_, schema)| schema.clone())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.schemas.is_empty()
+    }
+}
+
 pub struct LogicalPlanner {
     query: String,
     ast: Option<Statement>,
     plan: Option<LogicalPlan>,
     stage_idx: usize,
+    table_schemas: Option<TableSchemas>,
+    explain: bool,
 }
 
 impl LogicalPlanner {
@@ -216,9 +485,26 @@ impl LogicalPlanner {
             ast: None,
             plan: None,
             stage_idx: 0,
+            table_schemas: None,
+            explain: false,
         }
     }
 
+    // Any plan already built is discarded so the next build checks the
+    // column references against the schemas.
+    pub fn set_table_schemas(&mut self, table_schemas: TableSchemas) -> &mut Self {
+        self.table_schemas = Some(table_schemas);
+        self.plan = None;
+        self.stage_idx = 0;
+        self
+    }
+
+    // True when the query was prefixed with EXPLAIN and the plan should
+    // be returned instead of executed.
+    pub fn is_explain(&self) -> bool {
+        self.explain
+    }
+
     fn create_stage_id(&mut self) -> usize {
         let id = self.stage_idx;
         self.stage_idx += 1;
@@ -239,7 +525,13 @@ impl LogicalPlanner {
     }
 
     fn build_ast(&self) -> Result<Statement> {
-        let mut ast = Parser::parse_sql(&GenericDialect {}, self.query.as_str())?;
+        let mut ast = match Parser::parse_sql(&GenericDialect {}, self.query.as_str()) {
+            Ok(ast) => ast,
+            Err(err) => {
+                self.check_clause_keywords()?;
+                return Err(err.into());
+            }
+        };
         if ast.len() != 1 {
             Err(PlanError::NumberOfStatementsNotEqualToOne(ast.len()).into())
         } else {
@@ -247,10 +539,43 @@ impl LogicalPlanner {
         }
     }
 
+    // The sql parser reports a missing BY as an unexpected token after the
+    // previous clause so find it here to give a clearer error.
+    fn check_clause_keywords(&self) -> Result<()> {
+        let tokens = Tokenizer::new(&GenericDialect {}, self.query.as_str()).tokenize()?;
+        let mut words = tokens.iter().filter_map(|token| match token {
+            Token::Whitespace(_) => None,
+            Token::Word(word) => Some(word.keyword),
+            _ => Some(Keyword::NoKeyword),
+        });
+        while let Some(keyword) = words.next() {
+            if (keyword == Keyword::ORDER || keyword == Keyword::GROUP)
+                && words.next() != Some(Keyword::BY)
+            {
+                return Err(PlanError::ExpectedByAfterKeyword(format!("{:?}", keyword)).into());
+            }
+        }
+        Ok(())
+    }
+
     fn build_plan(&mut self) -> Result<LogicalPlan> {
         let ast = self.ast.clone();
         match ast {
             Some(Statement::Query(ref query)) => Ok(self.build_select_query_plan(query)?),
+            Some(Statement::Explain {
+                analyze: false,
+                ref statement,
+                ..
+            }) => match **statement {
+                Statement::Query(ref query) => {
+                    self.explain = true;
+                    Ok(self.build_select_query_plan(query)?)
+                }
+                _ => Err(PlanError::NotImplemented(
+                    "explain is only implemented for queries".to_string(),
+                )
+                .into()),
+            },
             _ => {
                 return Err(PlanError::NotImplemented(
                     "sql statement type not implemented".to_string(),
@@ -261,52 +586,239 @@ impl LogicalPlanner {
     }
 
     fn build_select_query_plan(&mut self, query: &Box<Query>) -> Result<LogicalPlan> {
-        // determine the source of data being queried
-        let select: &Box<Select> = match *query.body {
-            SetExpr::Select(ref select) => select,
+        let ref mut logical_plan = LogicalPlan::new();
+
+        // build the select, or the union of selects, and determine the
+        // fields available to the query level clauses
+        let (mut prev_stage, materialize_stage, select_items) = match *query.body {
+            SetExpr::Select(ref select) => {
+                // define static stages used in a select query plan
+                let table_sources_stage =
+                    Stage::new(StageType::TableSource, self.create_stage_id(), false);
+                let filter_stage = Stage::new(StageType::Filter, self.create_stage_id(), false);
+                let materialize_stage =
+                    Stage::new(StageType::Materialize, self.create_stage_id(), true);
+
+                let prev_stage = self.build_select_body(
+                    logical_plan,
+                    select,
+                    &table_sources_stage,
+                    &filter_stage,
+                )?;
+                (prev_stage, materialize_stage, select.projection.clone())
+            }
+            SetExpr::SetOperation {
+                op: SetOperator::Union,
+                ref set_quantifier,
+                ref left,
+                ref right,
+            } => {
+                let union_stage = self.build_union(logical_plan, set_quantifier, left, right)?;
+                let materialize_stage =
+                    Stage::new(StageType::Materialize, self.create_stage_id(), true);
+                // the union has already projected the fields of each select
+                let select_items = vec![SelectItem::Wildcard(WildcardAdditionalOptions::default())];
+                (union_stage, materialize_stage, select_items)
+            }
             _ => return Err(PlanError::NotImplemented("non-select query".to_string()).into()),
         };
 
-        let ref mut logical_plan = LogicalPlan::new();
+        // sort, limit and materialize
+        let sort = self.build_select_sort(&select_items, &query.order_by)?;
+        let limit = self.build_select_limit(query)?;
+        let materialize = self.build_materialization(&select_items)?;
 
-        // define static stages used in a select query plan
-        let table_sources_stage = Stage::new(StageType::TableSource, self.create_stage_id(), false);
-        let filter_stage = Stage::new(StageType::Filter, self.create_stage_id(), false);
-        let materialize_stage = Stage::new(StageType::Materialize, self.create_stage_id(), true);
+        if let Some(sort_node) = sort {
+            let sort_stage = Stage::new(StageType::Sort, self.create_stage_id(), false);
+            logical_plan.add_node(sort_node, sort_stage.clone());
+            logical_plan.connect_stages(prev_stage, sort_stage.clone());
+            prev_stage = sort_stage;
+        }
+        if let Some(limit_node) = limit {
+            let limit_stage = Stage::new(StageType::Limit, self.create_stage_id(), false);
+            logical_plan.add_node(limit_node, limit_stage.clone());
+            logical_plan.connect_stages(prev_stage, limit_stage.clone());
+            prev_stage = limit_stage;
+        }
+        logical_plan.add_node(materialize, materialize_stage.clone());
+        logical_plan.connect_stages(prev_stage, materialize_stage.clone());
 
-        // get table source(s)
-        let table_sources = self.build_select_from(&select.from)?;
-        let mut table_source_nodes: Vec<usize> = Vec::new();
-        for table_source in table_sources {
-            table_source_nodes
-                .push(logical_plan.add_node(table_source, table_sources_stage.clone()));
+        Ok(logical_plan.clone())
+    }
+
+    // Adds the table sources, joins, filter, aggregate and distinct nodes
+    // of a select and returns the stage of the last node added.
+    fn build_select_body(
+        &mut self,
+        logical_plan: &mut LogicalPlan,
+        select: &Select,
+        table_sources_stage: &Stage,
+        filter_stage: &Stage,
+    ) -> Result<Stage> {
+        self.check_column_references(select)?;
+
+        // get table source(s) and join them together
+        let mut prev_stage = table_sources_stage.clone();
+        if select.from.iter().any(|table| !table.joins.is_empty()) {
+            prev_stage =
+                self.build_select_joins(logical_plan, &select.from, table_sources_stage)?;
+        } else {
+            let table_sources = self.build_select_from(&select.from)?;
+            for table_source in table_sources {
+                logical_plan.add_node(table_source, table_sources_stage.clone());
+            }
         }
 
-        // filter and materialize
         let filter = self.build_select_filter(&select.selection)?;
-        let materialize = self.build_materialization(&select.projection)?;
+        let aggregate = self.build_select_aggregate(&select.projection, &select.group_by)?;
+        let distinct = self.build_select_distinct(&select.distinct, &select.projection)?;
 
         if let Some(filter_node) = filter {
             logical_plan.add_node(filter_node, filter_stage.clone());
-            logical_plan.add_node(materialize, materialize_stage.clone());
-            logical_plan.connect_stages(table_sources_stage.clone(), filter_stage.clone());
-            logical_plan.connect_stages(filter_stage.clone(), materialize_stage.clone());
-        } else {
-            logical_plan.add_node(materialize, materialize_stage.clone());
-            logical_plan.connect_stages(table_sources_stage.clone(), materialize_stage.clone());
+            logical_plan.connect_stages(prev_stage, filter_stage.clone());
+            prev_stage = filter_stage.clone();
+        }
+        if let Some(aggregate_node) = aggregate {
+            let aggregate_stage = Stage::new(StageType::Aggregate, self.create_stage_id(), false);
+            logical_plan.add_node(aggregate_node, aggregate_stage.clone());
+            logical_plan.connect_stages(prev_stage, aggregate_stage.clone());
+            prev_stage = aggregate_stage;
+        }
+        if let Some(distinct_node) = distinct {
+            let distinct_stage = Stage::new(StageType::Distinct, self.create_stage_id(), false);
+            logical_plan.add_node(distinct_node, distinct_stage.clone());
+            logical_plan.connect_stages(prev_stage, distinct_stage.clone());
+            prev_stage = distinct_stage;
         }
 
-        Ok(logical_plan.clone())
+        Ok(prev_stage)
+    }
+
+    // Each side of the union is built as its own select with its own
+    // stages. The left side is the first inbound node of the union and
+    // the right side the second. UNION without ALL removes duplicates.
+    fn build_union(
+        &mut self,
+        logical_plan: &mut LogicalPlan,
+        set_quantifier: &SetQuantifier,
+        left: &SetExpr,
+        right: &SetExpr,
+    ) -> Result<Stage> {
+        let distinct = match set_quantifier {
+            SetQuantifier::All => false,
+            SetQuantifier::Distinct | SetQuantifier::None => true,
+            _ => return Err(PlanError::NotImplemented(format!("union {}", set_quantifier)).into()),
+        };
+        let (left, right) = match (left, right) {
+            (SetExpr::Select(left), SetExpr::Select(right)) => (left, right),
+            _ => {
+                return Err(
+                    PlanError::NotImplemented("union of non-select queries".to_string()).into(),
+                )
+            }
+        };
+
+        let left_fields = self.build_select_fields(&left.projection);
+        let right_fields = self.build_select_fields(&right.projection);
+        self.check_union_fields(&left_fields, &right_fields)?;
+
+        let mut side_stages: Vec<Stage> = Vec::new();
+        for select in [left, right] {
+            let table_sources_stage =
+                Stage::new(StageType::TableSource, self.create_stage_id(), false);
+            let filter_stage = Stage::new(StageType::Filter, self.create_stage_id(), false);
+            side_stages.push(self.build_select_body(
+                logical_plan,
+                select,
+                &table_sources_stage,
+                &filter_stage,
+            )?);
+        }
+
+        let union_stage = Stage::new(StageType::Union, self.create_stage_id(), false);
+        logical_plan.add_node(
+            LogicalPlanNodeType::Union {
+                distinct,
+                left_fields,
+                right_fields,
+            },
+            union_stage.clone(),
+        );
+        for side_stage in side_stages {
+            logical_plan.connect_stages(side_stage, union_stage.clone());
+        }
+
+        Ok(union_stage)
+    }
+
+    // The column types are only known once the records are read so only
+    // the number of columns and the types of literal values are checked
+    // here. Wildcards can't be expanded so they skip the check.
+    fn check_union_fields(
+        &self,
+        left_fields: &[SelectItem],
+        right_fields: &[SelectItem],
+    ) -> Result<()> {
+        let has_wildcard = |fields: &[SelectItem]| {
+            fields.iter().any(|field| {
+                matches!(
+                    field,
+                    SelectItem::Wildcard(_) | SelectItem::QualifiedWildcard(_, _)
+                )
+            })
+        };
+        if has_wildcard(left_fields) || has_wildcard(right_fields) {
+            return Ok(());
+        }
+
+        for idx in 0..std::cmp::max(left_fields.len(), right_fields.len()) {
+            let (left_field, right_field) = match (left_fields.get(idx), right_fields.get(idx)) {
+                (Some(left_field), Some(right_field)) => (left_field, right_field),
+                _ => return Err(PlanError::UnionColumnMismatch(idx).into()),
+            };
+            match (
+                self.literal_type_name(left_field),
+                self.literal_type_name(right_field),
+            ) {
+                (Some(left_type), Some(right_type)) if left_type != right_type => {
+                    return Err(PlanError::UnionColumnMismatch(idx).into());
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    // Null is compatible with every type so it doesn't have a name.
+    fn literal_type_name(&self, select_item: &SelectItem) -> Option<&'static str> {
+        let expr = match select_item {
+            SelectItem::UnnamedExpr(expr) => expr,
+            SelectItem::ExprWithAlias { expr, .. } => expr,
+            _ => return None,
+        };
+        match expr {
+            Expr::Value(Value::Number(_, _)) => Some("number"),
+            Expr::Value(Value::SingleQuotedString(_))
+            | Expr::Value(Value::DoubleQuotedString(_)) => Some("string"),
+            Expr::Value(Value::Boolean(_)) => Some("boolean"),
+            _ => None,
+        }
     }
 
-    fn build_materialization(&self, select_items: &Vec<SelectItem>) -> Result<LogicalPlanNodeType> {
+    fn build_materialization(&self, select_items: &[SelectItem]) -> Result<LogicalPlanNodeType> {
+        Ok(LogicalPlanNodeType::Materialize {
+            fields: self.build_select_fields(select_items),
+        })
+    }
+
+    fn build_select_fields(&self, select_items: &[SelectItem]) -> Vec<SelectItem> {
         let mut fields: Vec<SelectItem> = Vec::new();
         for select_item in select_items {
             if self.is_valid_select_item(select_item) {
                 fields.push(select_item.clone())
             }
         }
-        Ok(LogicalPlanNodeType::Materialize { fields })
+        fields
     }
 
     fn is_valid_select_item(&self, select_item: &SelectItem) -> bool {
@@ -337,6 +849,574 @@ impl LogicalPlanner {
         }
     }
 
+    fn build_select_aggregate(
+        &self,
+        select_items: &[SelectItem],
+        group_by: &GroupByExpr,
+    ) -> Result<Option<LogicalPlanNodeType>> {
+        let group_by_exprs = match group_by {
+            GroupByExpr::Expressions(exprs, modifiers) if modifiers.is_empty() => exprs,
+            _ => return Err(PlanError::NotImplemented(format!("group by: {}", group_by)).into()),
+        };
+
+        let mut aggregates: Vec<Aggregate> = Vec::new();
+        for select_item in select_items {
+            match select_item {
+                SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => {
+                    self.find_aggregates(expr, &mut aggregates)?;
+                }
+                _ => (),
+            }
+        }
+
+        if group_by_exprs.is_empty() && aggregates.is_empty() {
+            return Ok(None);
+        }
+
+        // group by items can reference a select item alias
+        let group_by = group_by_exprs
+            .iter()
+            .map(|expr| self.resolve_select_item_alias(expr, select_items))
+            .collect::<Vec<Expr>>();
+
+        for expr in &group_by {
+            let mut group_by_aggregates: Vec<Aggregate> = Vec::new();
+            self.find_aggregates(expr, &mut group_by_aggregates)?;
+            if !group_by_aggregates.is_empty() {
+                return Err(PlanError::InvalidAggregate(format!(
+                    "aggregate functions are not allowed in group by: {}",
+                    expr
+                ))
+                .into());
+            }
+        }
+
+        Ok(Some(LogicalPlanNodeType::Aggregate {
+            group_by,
+            aggregates,
+        }))
+    }
+
+    // The distinct keys are the select items so rows which materialize
+    // to the same values are removed. A wildcard uses all columns.
+    fn build_select_distinct(
+        &self,
+        distinct: &Option<Distinct>,
+        select_items: &[SelectItem],
+    ) -> Result<Option<LogicalPlanNodeType>> {
+        match distinct {
+            Some(Distinct::Distinct) => (),
+            Some(Distinct::On(_)) => {
+                return Err(PlanError::NotImplemented("distinct on".to_string()).into());
+            }
+            None => return Ok(None),
+        }
+
+        let mut keys: Vec<Expr> = Vec::new();
+        for select_item in select_items {
+            match select_item {
+                SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => {
+                    keys.push(expr.clone());
+                }
+                SelectItem::Wildcard(_) => {
+                    return Ok(Some(LogicalPlanNodeType::Distinct { keys: Vec::new() }));
+                }
+                SelectItem::QualifiedWildcard(..) => {
+                    return Err(PlanError::NotImplemented(format!(
+                        "distinct with a qualified wildcard: {}",
+                        select_item
+                    ))
+                    .into());
+                }
+            }
+        }
+
+        Ok(Some(LogicalPlanNodeType::Distinct { keys }))
+    }
+
+    fn build_select_sort(
+        &self,
+        select_items: &[SelectItem],
+        order_by: &Option<OrderBy>,
+    ) -> Result<Option<LogicalPlanNodeType>> {
+        let order_by = match order_by {
+            Some(order_by) if !order_by.exprs.is_empty() => order_by,
+            _ => return Ok(None),
+        };
+        if order_by.interpolate.is_some() {
+            return Err(PlanError::NotImplemented(format!("order by: {}", order_by)).into());
+        }
+
+        let mut exprs: Vec<SortExpr> = Vec::new();
+        for order_by_expr in &order_by.exprs {
+            if order_by_expr.with_fill.is_some() {
+                return Err(
+                    PlanError::NotImplemented(format!("order by: {}", order_by_expr)).into(),
+                );
+            }
+            let asc = order_by_expr.asc.unwrap_or(true);
+            exprs.push(SortExpr {
+                expr: self.resolve_select_item_alias(&order_by_expr.expr, select_items),
+                asc,
+                nulls_first: order_by_expr.nulls_first.unwrap_or(!asc),
+            });
+        }
+
+        Ok(Some(LogicalPlanNodeType::Sort { exprs }))
+    }
+
+    fn build_select_limit(&self, query: &Query) -> Result<Option<LogicalPlanNodeType>> {
+        if !query.limit_by.is_empty() || query.fetch.is_some() {
+            return Err(PlanError::NotImplemented("limit by and fetch".to_string()).into());
+        }
+
+        let limit = match &query.limit {
+            Some(expr) => Some(self.parse_row_count(expr)?),
+            None => None,
+        };
+        let offset = match &query.offset {
+            Some(Offset { value, .. }) => Some(self.parse_row_count(value)?),
+            None => None,
+        };
+
+        if limit.is_none() && offset.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(LogicalPlanNodeType::Limit { limit, offset }))
+    }
+
+    fn parse_row_count(&self, expr: &Expr) -> Result<u64> {
+        match expr {
+            Expr::Value(Value::Number(num, _)) => match num.parse::<u64>() {
+                Ok(num) => Ok(num),
+                Err(_) => Err(PlanError::InvalidNumber(num.clone()).into()),
+            },
+            _ => Err(PlanError::InvalidNumber(expr.to_string()).into()),
+        }
+    }
+
+    // Replace a reference to a select item alias with the aliased expression.
+    fn resolve_select_item_alias(&self, expr: &Expr, select_items: &[SelectItem]) -> Expr {
+        match expr {
+            Expr::Identifier(ident) => select_items
+                .iter()
+                .find_map(|select_item| match select_item {
+                    SelectItem::ExprWithAlias { expr, alias } if alias.value == ident.value => {
+                        Some(expr.clone())
+                    }
+                    _ => None,
+                })
+                .unwrap_or(expr.clone()),
+            _ => expr.clone(),
+        }
+    }
+
+    fn find_aggregates(&self, expr: &Expr, aggregates: &mut Vec<Aggregate>) -> Result<()> {
+        match expr {
+            Expr::Function(func) => {
+                if let Some(agg_func) = AggregateFunction::from_name(&func.name.to_string()) {
+                    let aggregate = self.build_aggregate(agg_func, func, expr)?;
+                    if !aggregates.contains(&aggregate) {
+                        aggregates.push(aggregate);
+                    }
+                } else if let FunctionArguments::List(arg_list) = &func.args {
+                    for arg in &arg_list.args {
+                        if let FunctionArg::Unnamed(FunctionArgExpr::Expr(arg_expr)) = arg {
+                            self.find_aggregates(arg_expr, aggregates)?;
+                        }
+                    }
+                }
+            }
+            Expr::BinaryOp { left, right, .. } => {
+                self.find_aggregates(left, aggregates)?;
+                self.find_aggregates(right, aggregates)?;
+            }
+            Expr::UnaryOp { expr, .. } | Expr::Nested(expr) | Expr::Cast { expr, .. } => {
+                self.find_aggregates(expr, aggregates)?;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn build_aggregate(
+        &self,
+        agg_func: AggregateFunction,
+        func: &Function,
+        expr: &Expr,
+    ) -> Result<Aggregate> {
+        let arg_list = match &func.args {
+            FunctionArguments::List(arg_list)
+                if arg_list.duplicate_treatment.is_none() && arg_list.clauses.is_empty() =>
+            {
+                arg_list
+            }
+            _ => return Err(PlanError::NotImplemented(format!("aggregate: {}", func)).into()),
+        };
+        if func.filter.is_some() || func.over.is_some() || !func.within_group.is_empty() {
+            return Err(PlanError::NotImplemented(format!("aggregate: {}", func)).into());
+        }
+
+        let arg = match &arg_list.args[..] {
+            [FunctionArg::Unnamed(FunctionArgExpr::Wildcard)] => {
+                if agg_func != AggregateFunction::Count {
+                    return Err(PlanError::InvalidAggregate(format!(
+                        "only count accepts *: {}",
+                        func
+                    ))
+                    .into());
+                }
+                None
+            }
+            [FunctionArg::Unnamed(FunctionArgExpr::Expr(arg_expr))] => {
+                let mut nested_aggregates: Vec<Aggregate> = Vec::new();
+                self.find_aggregates(arg_expr, &mut nested_aggregates)?;
+                if !nested_aggregates.is_empty() {
+                    return Err(PlanError::InvalidAggregate(format!(
+                        "aggregate function calls cannot be nested: {}",
+                        func
+                    ))
+                    .into());
+                }
+                Some(arg_expr.clone())
+            }
+            _ => {
+                return Err(PlanError::InvalidAggregate(format!(
+                    "expected exactly one argument: {}",
+                    func
+                ))
+                .into())
+            }
+        };
+
+        Ok(Aggregate {
+            func: agg_func,
+            arg,
+            expr: expr.clone(),
+        })
+    }
+
+    // Checks that the columns referenced by the select items, where
+    // clause, group by and join conditions exist in the schemas of the
+    // select's table sources. A qualified column only binds to the table
+    // source with that name or alias while an unqualified column binds to
+    // any of them, so it must only exist in one. The check is skipped when
+    // the schema of any of the table sources is unknown.
+    fn check_column_references(&self, select: &Select) -> Result<()> {
+        let table_schemas = match &self.table_schemas {
+            Some(table_schemas) => table_schemas,
+            None => return Ok(()),
+        };
+
+        let mut relations: Vec<(Vec<String>, SchemaRef)> = Vec::new();
+        let mut exprs: Vec<&Expr> = Vec::new();
+        for table_with_joins in &select.from {
+            let mut table_factors = vec![&table_with_joins.relation];
+            for join in &table_with_joins.joins {
+                table_factors.push(&join.relation);
+                if let JoinOperator::Inner(JoinConstraint::On(expr))
+                | JoinOperator::LeftOuter(JoinConstraint::On(expr)) = &join.join_operator
+                {
+                    exprs.push(expr);
+                }
+            }
+            for table_factor in table_factors {
+                let table_source = self.build_select_from_relation(table_factor)?;
+                match table_schemas.get_schema(&table_source) {
+                    Some(schema) => relations.push((self.relation_names(&table_source), schema)),
+                    None => return Ok(()),
+                }
+            }
+        }
+
+        let mut select_aliases: Vec<&str> = Vec::new();
+        for select_item in &select.projection {
+            match select_item {
+                SelectItem::UnnamedExpr(expr) => exprs.push(expr),
+                SelectItem::ExprWithAlias { expr, alias } => {
+                    exprs.push(expr);
+                    select_aliases.push(alias.value.as_str());
+                }
+                _ => (),
+            }
+        }
+        if let Some(selection) = &select.selection {
+            exprs.push(selection);
+        }
+        let mut group_by_exprs: Vec<&Expr> = Vec::new();
+        if let GroupByExpr::Expressions(group_by, _) = &select.group_by {
+            group_by_exprs.extend(group_by);
+        }
+
+        let mut columns: Vec<&Expr> = Vec::new();
+        for expr in exprs {
+            self.find_column_references(expr, &mut columns);
+        }
+        let mut group_by_columns: Vec<&Expr> = Vec::new();
+        for expr in group_by_exprs {
+            self.find_column_references(expr, &mut group_by_columns);
+        }
+        // the group by can reference the select item aliases
+        group_by_columns.retain(|column| match column {
+            Expr::Identifier(ident) => !select_aliases.contains(&ident.value.as_str()),
+            _ => true,
+        });
+        columns.extend(group_by_columns);
+
+        for column in columns {
+            let matching_relations: Vec<&(Vec<String>, SchemaRef)> = match column {
+                Expr::Identifier(ident) => relations
+                    .iter()
+                    .filter(|(_, schema)| schema.column_with_name(&ident.value).is_some())
+                    .collect(),
+                Expr::CompoundIdentifier(idents) if idents.len() == 2 => relations
+                    .iter()
+                    .filter(|(names, schema)| {
+                        names.contains(&idents[0].value)
+                            && schema.column_with_name(&idents[1].value).is_some()
+                    })
+                    .collect(),
+                _ => continue,
+            };
+            if matching_relations.len() > 1 {
+                return Err(PlanError::AmbiguousColumn {
+                    name: column.to_string(),
+                    tables: matching_relations
+                        .iter()
+                        .map(|(names, _)| names.join(", "))
+                        .collect(),
+                }
+                .into());
+            }
+            if matching_relations.is_empty() {
+                let mut available: Vec<String> = Vec::new();
+                for (_, schema) in &relations {
+                    for field in schema.fields() {
+                        if !available.contains(field.name()) {
+                            available.push(field.name().clone());
+                        }
+                    }
+                }
+                return Err(PlanError::UnknownColumn {
+                    name: column.to_string(),
+                    available,
+                }
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn find_column_references<'a>(&self, expr: &'a Expr, columns: &mut Vec<&'a Expr>) {
+        match expr {
+            Expr::Identifier(_) | Expr::CompoundIdentifier(_) => columns.push(expr),
+            Expr::Function(func) => {
+                if let FunctionArguments::List(arg_list) = &func.args {
+                    for arg in &arg_list.args {
+                        if let FunctionArg::Unnamed(FunctionArgExpr::Expr(arg_expr)) = arg {
+                            self.find_column_references(arg_expr, columns);
+                        }
+                    }
+                }
+            }
+            Expr::BinaryOp { left, right, .. } => {
+                self.find_column_references(left, columns);
+                self.find_column_references(right, columns);
+            }
+            Expr::UnaryOp { expr, .. }
+            | Expr::Nested(expr)
+            | Expr::Cast { expr, .. }
+            | Expr::IsNull(expr)
+            | Expr::IsNotNull(expr) => self.find_column_references(expr, columns),
+            Expr::Between {
+                expr, low, high, ..
+            } => {
+                self.find_column_references(expr, columns);
+                self.find_column_references(low, columns);
+                self.find_column_references(high, columns);
+            }
+            Expr::InList { expr, list, .. } => {
+                self.find_column_references(expr, columns);
+                for item in list {
+                    self.find_column_references(item, columns);
+                }
+            }
+            Expr::Like { expr, pattern, .. } | Expr::ILike { expr, pattern, .. } => {
+                self.find_column_references(expr, columns);
+                self.find_column_references(pattern, columns);
+            }
+            _ => (),
+        }
+    }
+
+    // Each join gets its own stage with the left relation (or the previous
+    // join) as the first inbound node and the right relation as the second.
+    // Returns the stage of the last join.
+    fn build_select_joins(
+        &mut self,
+        logical_plan: &mut LogicalPlan,
+        from: &[TableWithJoins],
+        table_sources_stage: &Stage,
+    ) -> Result<Stage> {
+        let table_with_joins = match from {
+            [table_with_joins] => table_with_joins,
+            _ => {
+                return Err(PlanError::NotImplemented(
+                    "joins combined with multiple from relations".to_string(),
+                )
+                .into())
+            }
+        };
+
+        let left_node = self.build_select_from_relation(&table_with_joins.relation)?;
+        let mut left_names = self.relation_names(&left_node);
+        let mut left_node_id = logical_plan.add_node(left_node, table_sources_stage.clone());
+        let mut join_stage = table_sources_stage.clone();
+
+        for join in &table_with_joins.joins {
+            let (join_type, constraint) = match &join.join_operator {
+                JoinOperator::Inner(constraint) => (JoinType::Inner, constraint),
+                JoinOperator::LeftOuter(constraint) => (JoinType::Left, constraint),
+                _ => return Err(PlanError::NotImplemented(format!("join: {}", join)).into()),
+            };
+            let on_expr = match constraint {
+                JoinConstraint::On(expr) => expr,
+                _ => {
+                    return Err(
+                        PlanError::NotImplemented(format!("join constraint: {}", join)).into(),
+                    )
+                }
+            };
+
+            let right_node = self.build_select_from_relation(&join.relation)?;
+            let right_names = self.relation_names(&right_node);
+            let right_node_id = logical_plan.add_node(right_node, table_sources_stage.clone());
+
+            let mut left_keys: Vec<Expr> = Vec::new();
+            let mut right_keys: Vec<Expr> = Vec::new();
+            for condition in self.split_conjunction(on_expr) {
+                let (left_key, right_key) =
+                    self.build_join_keys(condition, &left_names, &right_names)?;
+                left_keys.push(left_key);
+                right_keys.push(right_key);
+            }
+
+            join_stage = Stage::new(StageType::Join, self.create_stage_id(), false);
+            let join_node_id = logical_plan.add_node(
+                LogicalPlanNodeType::Join {
+                    join_type,
+                    left_keys,
+                    right_keys,
+                },
+                join_stage.clone(),
+            );
+            logical_plan.connect(left_node_id, join_node_id);
+            logical_plan.connect(right_node_id, join_node_id);
+
+            left_node_id = join_node_id;
+            left_names.extend(right_names);
+        }
+
+        Ok(join_stage)
+    }
+
+    fn split_conjunction<'a>(&self, expr: &'a Expr) -> Vec<&'a Expr> {
+        match expr {
+            Expr::BinaryOp {
+                left,
+                op: BinaryOperator::And,
+                right,
+            } => {
+                let mut exprs = self.split_conjunction(left);
+                exprs.extend(self.split_conjunction(right));
+                exprs
+            }
+            Expr::Nested(expr) => self.split_conjunction(expr),
+            _ => vec![expr],
+        }
+    }
+
+    // Join conditions must be equalities between an expression on the left
+    // relations and one on the right relation. Columns must be qualified
+    // with the relation's alias (or table name) so each side of the
+    // equality can be assigned to a relation.
+    fn build_join_keys(
+        &self,
+        condition: &Expr,
+        left_names: &[String],
+        right_names: &[String],
+    ) -> Result<(Expr, Expr)> {
+        let (left, right) = match condition {
+            Expr::BinaryOp {
+                left,
+                op: BinaryOperator::Eq,
+                right,
+            } => (left.as_ref(), right.as_ref()),
+            _ => {
+                return Err(PlanError::InvalidJoinCondition(format!(
+                    "only equality conditions are supported: {}",
+                    condition
+                ))
+                .into())
+            }
+        };
+
+        let left_qualifiers = self.find_column_qualifiers(left)?;
+        let right_qualifiers = self.find_column_qualifiers(right)?;
+        let references = |qualifiers: &Vec<String>, names: &[String]| -> bool {
+            !qualifiers.is_empty() && qualifiers.iter().all(|name| names.contains(name))
+        };
+
+        if references(&left_qualifiers, left_names) && references(&right_qualifiers, right_names) {
+            Ok((left.clone(), right.clone()))
+        } else if references(&left_qualifiers, right_names)
+            && references(&right_qualifiers, left_names)
+        {
+            Ok((right.clone(), left.clone()))
+        } else {
+            Err(PlanError::InvalidJoinCondition(format!(
+                "each side must reference columns from one side of the join: {}",
+                condition
+            ))
+            .into())
+        }
+    }
+
+    fn find_column_qualifiers(&self, expr: &Expr) -> Result<Vec<String>> {
+        match expr {
+            Expr::CompoundIdentifier(idents) if idents.len() == 2 => {
+                Ok(vec![idents[0].value.clone()])
+            }
+            Expr::Identifier(ident) => Err(PlanError::InvalidJoinCondition(format!(
+                "column must be qualified with a table name or alias: {}",
+                ident
+            ))
+            .into()),
+            Expr::BinaryOp { left, right, .. } => {
+                let mut qualifiers = self.find_column_qualifiers(left)?;
+                qualifiers.extend(self.find_column_qualifiers(right)?);
+                Ok(qualifiers)
+            }
+            Expr::UnaryOp { expr, .. } | Expr::Nested(expr) => self.find_column_qualifiers(expr),
+            Expr::Value(_) => Ok(Vec::new()),
+            _ => Err(PlanError::NotImplemented(format!("join key: {}", expr)).into()),
+        }
+    }
+
+    fn relation_names(&self, node: &LogicalPlanNodeType) -> Vec<String> {
+        match node {
+            LogicalPlanNodeType::Table {
+                alias: Some(alias), ..
+            }
+            | LogicalPlanNodeType::TableFunc {
+                alias: Some(alias), ..
+            } => vec![alias.clone()],
+            LogicalPlanNodeType::Table { alias: None, name } => vec![name.clone()],
+            _ => Vec::new(),
+        }
+    }
+
     fn build_select_from(&self, from: &Vec<TableWithJoins>) -> Result<Vec<LogicalPlanNodeType>> {
         let mut nodes: Vec<LogicalPlanNodeType> = Vec::new();
         for table_with_join in from {
@@ -351,10 +1431,68 @@ impl LogicalPlanner {
             TableFactor::Table {
                 name, alias, args, ..
             } => Ok(self.table_relation_plan_node(name, alias, args)?),
+            TableFactor::Derived {
+                subquery, alias, ..
+            } => match subquery.body.as_ref() {
+                SetExpr::Values(values) => Ok(self.values_relation_plan_node(values, alias)),
+                _ => Err(PlanError::NotImplemented("from subquery".to_string()).into()),
+            },
             _ => Err(PlanError::NotImplemented("from relation".to_string()).into()),
         }
     }
 
+    // Inline rows are read by the values table func. Both
+    // `values((1, 'a'), (2, 'b'))` and `(values (1, 'a'), (2, 'b'))`
+    // are parsed as values; the first is a single row of tuples. The
+    // column names of the alias are passed as the columns arg.
+    fn values_relation_plan_node(
+        &self,
+        values: &Values,
+        alias: &Option<TableAlias>,
+    ) -> LogicalPlanNodeType {
+        let rows: Vec<Expr> = match values.rows.as_slice() {
+            [row]
+                if row
+                    .iter()
+                    .all(|expr| matches!(expr, Expr::Tuple(_) | Expr::Nested(_))) =>
+            {
+                row.iter()
+                    .map(|expr| match expr {
+                        Expr::Nested(expr) => Expr::Tuple(vec![expr.as_ref().clone()]),
+                        expr => expr.clone(),
+                    })
+                    .collect()
+            }
+            rows => rows.iter().map(|row| Expr::Tuple(row.clone())).collect(),
+        };
+
+        let mut args: Vec<FunctionArg> = rows
+            .into_iter()
+            .map(|row| FunctionArg::Unnamed(FunctionArgExpr::Expr(row)))
+            .collect();
+        if let Some(alias) = alias {
+            if !alias.columns.is_empty() {
+                args.push(FunctionArg::Named {
+                    name: Ident::new("columns"),
+                    arg: FunctionArgExpr::Expr(Expr::Tuple(
+                        alias
+                            .columns
+                            .iter()
+                            .map(|column| Expr::Identifier(column.clone()))
+                            .collect(),
+                    )),
+                    operator: FunctionArgOperator::RightArrow,
+                });
+            }
+        }
+
+        LogicalPlanNodeType::TableFunc {
+            alias: alias.as_ref().map(|alias| alias.name.value.clone()),
+            name: "values".to_string(),
+            args,
+        }
+    }
+
     fn table_relation_plan_node(
         &self,
         name: &ObjectName,
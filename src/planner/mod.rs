@@ -5,8 +5,12 @@ mod test_logical_planner;
 #[cfg(test)]
 mod test_physical_planner;
 
-pub use logical_planner::{LogicalPlan, LogicalPlanner};
+pub use logical_planner::{
+    Aggregate, AggregateFunction, EstimatedStats, JoinType, LogicalPlan, LogicalPlanNodeType,
+    LogicalPlanner, SortExpr, TableSchemas,
+};
 pub use physical_planner::{
-    DataFormat, Operator, OperatorCompute, OperatorTask, OperatorType, PhysicalPlan,
-    PhysicalPlanner,
+    DataFormat, ExchangeDistribution, Operator, OperatorCompute, OperatorTask, OperatorType,
+    ParquetCompression, ParquetWriterConfig, PhysicalPlan, PhysicalPlanner, Pipeline,
+    PlannerConfig,
 };
@@ -1,11 +1,21 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use sqlparser::ast::{Expr, FunctionArg, SelectItem};
+use sqlparser::ast::{
+    BinaryOperator, Expr, FunctionArg, FunctionArgExpr, FunctionArguments, SelectItem,
+    UnaryOperator, Value,
+};
 use thiserror::Error;
 
 use crate::planner::logical_planner::{LogicalPlan, LogicalPlanNode};
 
-use super::logical_planner::LogicalPlanNodeType;
+use super::logical_planner::{Aggregate, EstimatedStats, JoinType, LogicalPlanNodeType, SortExpr};
+
+// rows per record sent between operators
+const ROWS_PER_RECORD: usize = 10_000;
+// rows a producer can process for each cpu
+const ROWS_PER_CPU: usize = 10_000_000;
+const MAX_PRODUCER_CPU_IN_THOUSANDTHS: usize = 4000;
+const MAX_PRODUCER_MEMORY_IN_MIB: usize = 16 * 1024;
 
 #[derive(Error, Debug)]
 pub enum PhysicalPlanError {
@@ -19,17 +29,50 @@ pub enum PhysicalPlanError {
     ExpectedAllInboundNodesToHaveOperatorsAlready,
     #[error("not implemented: {0}")]
     NotImplemented(String),
+    #[error("unknown data format: {0}")]
+    UnknownDataFormat(String),
+    #[error("unknown parquet compression: {0}")]
+    UnknownParquetCompression(String),
+    #[error(
+        "consumer {1} of hash partitioned exchange {0} has {2} instances but needs one for each of the {3} partitions"
+    )]
+    HashPartitionConsumerInstancesMismatch(String, String, usize, usize),
 }
 
 #[derive(Clone, Debug, PartialEq, PartialOrd, Ord, Eq, Serialize, Deserialize)]
 pub enum DataFormat {
     Parquet,
+    Csv,
+    JsonLines,
 }
 
 impl DataFormat {
     fn name(&self) -> &str {
         match self {
             Self::Parquet => "Parquet",
+            Self::Csv => "Csv",
+            Self::JsonLines => "JsonLines",
+        }
+    }
+
+    pub fn file_extension(&self) -> &str {
+        match self {
+            Self::Parquet => "parquet",
+            Self::Csv => "csv",
+            Self::JsonLines => "jsonl",
+        }
+    }
+}
+
+impl std::str::FromStr for DataFormat {
+    type Err = PhysicalPlanError;
+
+    fn from_str(s: &str) -> Result<DataFormat, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "parquet" => Ok(Self::Parquet),
+            "csv" => Ok(Self::Csv),
+            "jsonl" | "json" => Ok(Self::JsonLines),
+            _ => Err(PhysicalPlanError::UnknownDataFormat(s.to_string())),
         }
     }
 }
@@ -40,6 +83,44 @@ impl std::fmt::Display for DataFormat {
     }
 }
 
+#[derive(Clone, Debug, PartialEq, PartialOrd, Ord, Eq, Serialize, Deserialize)]
+pub enum ParquetCompression {
+    Snappy,
+    Zstd,
+    Uncompressed,
+}
+
+impl std::str::FromStr for ParquetCompression {
+    type Err = PhysicalPlanError;
+
+    fn from_str(s: &str) -> Result<ParquetCompression, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "snappy" => Ok(Self::Snappy),
+            "zstd" => Ok(Self::Zstd),
+            "none" | "uncompressed" => Ok(Self::Uncompressed),
+            _ => Err(PhysicalPlanError::UnknownParquetCompression(s.to_string())),
+        }
+    }
+}
+
+// Properties of the parquet files written when materializing results.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Ord, Eq, Serialize, Deserialize)]
+pub struct ParquetWriterConfig {
+    pub row_group_size: usize,
+    pub compression: ParquetCompression,
+    pub data_page_size: usize,
+}
+
+impl Default for ParquetWriterConfig {
+    fn default() -> ParquetWriterConfig {
+        ParquetWriterConfig {
+            row_group_size: 1024 * 1024,
+            compression: ParquetCompression::Snappy,
+            data_page_size: 1024 * 1024,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, PartialOrd, Ord, Eq, Serialize, Deserialize)]
 pub enum OperatorTask {
     // table source stage
@@ -47,6 +128,11 @@ pub enum OperatorTask {
         alias: Option<String>,
         func_name: String,
         args: Vec<FunctionArg>,
+        // the columns the query reads; None when every column is needed
+        projection: Option<Vec<String>>,
+        // the parts of the downstream filter the source can use to skip
+        // data; the filter still applies the whole predicate
+        predicate: Option<Expr>,
         max_rows_per_batch: usize,
     },
     Table {
@@ -54,14 +140,49 @@ pub enum OperatorTask {
         name: String,
         max_rows_per_batch: usize,
     },
+    // join stage
+    HashJoin {
+        join_type: JoinType,
+        left_keys: Vec<Expr>,
+        right_keys: Vec<Expr>,
+    },
     // filter stage
     Filter {
         expr: Expr,
     },
+    // aggregate stage
+    Aggregate {
+        group_by: Vec<Expr>,
+        aggregates: Vec<Aggregate>,
+    },
+    // distinct stage
+    Distinct {
+        keys: Vec<Expr>,
+    },
+    // sort stage
+    Sort {
+        exprs: Vec<SortExpr>,
+    },
+    // limit stage
+    Limit {
+        limit: Option<u64>,
+        offset: Option<u64>,
+    },
+    // union stage
+    Union {
+        distinct: bool,
+        left_fields: Vec<SelectItem>,
+        right_fields: Vec<SelectItem>,
+    },
     // materialize stage
     MaterializeFiles {
         data_format: DataFormat,
+        parquet_writer: ParquetWriterConfig,
+        target_file_rows: usize,
+        partition_by: Vec<String>,
+        max_open_partitions: usize,
         fields: Vec<SelectItem>,
+        connection: String,
     },
 }
 
@@ -70,7 +191,13 @@ impl OperatorTask {
         match self {
             Self::TableFunc { .. } => "TableFunc",
             Self::Table { .. } => "Table",
+            Self::HashJoin { .. } => "HashJoin",
             Self::Filter { .. } => "Filter",
+            Self::Aggregate { .. } => "Aggregate",
+            Self::Distinct { .. } => "Distinct",
+            Self::Sort { .. } => "Sort",
+            Self::Limit { .. } => "Limit",
+            Self::Union { .. } => "Union",
             Self::MaterializeFiles { .. } => "MaterializeFiles",
         }
     }
@@ -101,6 +228,30 @@ pub enum OperatorType {
         // pull based: a producer will request data from the exchange
         outbound_producer_ids: Vec<String>,
         inbound_producer_ids: Vec<String>,
+        // records are given to a single consumer at a time in the order
+        // the producer sent them; unordered delivery is faster
+        #[serde(default)]
+        ordered: bool,
+        #[serde(default)]
+        distribution: ExchangeDistribution,
+    },
+}
+
+#[derive(Clone, Debug, Default, PartialEq, PartialOrd, Ord, Eq, Serialize, Deserialize)]
+pub enum ExchangeDistribution {
+    // each record is given to a single instance of each consumer
+    #[default]
+    RoundRobin,
+    // each record is given to every instance of each consumer and is
+    // complete once all of them processed it
+    Broadcast {
+        consumer_instances: usize,
+    },
+    // each row is given to the instance of each consumer which owns the
+    // partition the row's key columns hash to
+    HashPartition {
+        keys: Vec<String>,
+        num_partitions: usize,
     },
 }
 
@@ -135,7 +286,7 @@ pub struct Operator {
     pub compute: OperatorCompute,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize)]
 pub struct Pipeline {
     pub id: String,
     operators: Vec<Operator>,
@@ -182,7 +333,7 @@ impl Pipeline {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize)]
 pub struct PhysicalPlan {
     pipelines: Vec<Pipeline>,
 }
@@ -206,6 +357,13 @@ impl PhysicalPlan {
         &self.pipelines
     }
 
+    // A pretty printed dump of the pipelines and their operators. The
+    // operator and pipeline ids are derived from the logical plan so the
+    // same query always produces the same output.
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
     pub fn get_operator(&self, pipeline_id: String, operator_id: String) -> Option<&Operator> {
         for pipeline in self.get_pipelines_ref() {
             if pipeline.id != pipeline_id {
@@ -219,23 +377,96 @@ impl PhysicalPlan {
         }
         None
     }
+
+    // Each instance of a consumer of a hash partitioned exchange owns one
+    // of the partitions so the consumer needs an instance per partition.
+    pub fn validate(&self) -> Result<()> {
+        for pipeline in self.get_pipelines_ref() {
+            for op in pipeline.get_operators_ref() {
+                let (outbound_producer_ids, num_partitions) = match &op.operator_type {
+                    OperatorType::Exchange {
+                        outbound_producer_ids,
+                        distribution: ExchangeDistribution::HashPartition { num_partitions, .. },
+                        ..
+                    } => (outbound_producer_ids, *num_partitions),
+                    _ => continue,
+                };
+                for consumer_id in outbound_producer_ids {
+                    let consumer = match self.get_operator(pipeline.id.clone(), consumer_id.clone())
+                    {
+                        Some(consumer) => consumer,
+                        None => continue,
+                    };
+                    if consumer.compute.instances != num_partitions {
+                        return Err(PhysicalPlanError::HashPartitionConsumerInstancesMismatch(
+                            op.id.clone(),
+                            consumer.id.clone(),
+                            consumer.compute.instances,
+                            num_partitions,
+                        )
+                        .into());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlannerConfig {
+    // instances of each producer whose task can run in parallel; tasks
+    // which need to see every record always run as a single instance
+    pub default_producer_instances: usize,
+    // format of the files written when materializing the query results
+    pub output_data_format: DataFormat,
+    pub parquet_writer: ParquetWriterConfig,
+    // rows written to each materialized file; smaller records are
+    // combined until there are enough rows
+    pub target_file_rows: usize,
+    // columns used to partition the materialized files into hive style
+    // directories and the max partitions buffered at once
+    pub partition_by: Vec<String>,
+    pub max_open_partitions: usize,
+    // name of the registered storage connection the results are
+    // written to
+    pub results_connection: String,
+}
+
+impl Default for PlannerConfig {
+    fn default() -> PlannerConfig {
+        PlannerConfig {
+            default_producer_instances: 1,
+            output_data_format: DataFormat::Parquet,
+            parquet_writer: ParquetWriterConfig::default(),
+            target_file_rows: 1024 * 1024,
+            partition_by: Vec::new(),
+            max_open_partitions: 64,
+            results_connection: "default".to_string(),
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct PhysicalPlanner {
     logical_plan: LogicalPlan,
+    config: PlannerConfig,
     pipeline_idx: usize,
     operator_idx: usize,
     max_build_iterations: usize,
 }
 
 impl PhysicalPlanner {
-    pub fn new(logical_plan: LogicalPlan) -> PhysicalPlanner {
+    pub fn new(logical_plan: LogicalPlan, config: PlannerConfig) -> PhysicalPlanner {
+        // each node is visited at most twice; once to push its inbound
+        // nodes and once to build its operators
+        let max_build_iterations = std::cmp::max(10, 2 * logical_plan.get_all_node_ids().len());
         return PhysicalPlanner {
             logical_plan,
+            config,
             pipeline_idx: 0,
             operator_idx: 0,
-            max_build_iterations: 10,
+            max_build_iterations,
         };
     }
 
@@ -295,13 +526,207 @@ impl PhysicalPlanner {
 
         let mut physical_plan = PhysicalPlan::new();
         physical_plan.add_pipeline(pipeline.clone());
+        physical_plan.validate()?;
         Ok(physical_plan)
     }
 
+    // Sizes a producer from the estimated stats of its logical plan
+    // node. Each task type has a minimum memory which the estimate
+    // can only increase.
+    pub fn estimate_compute(&self, lpn: &LogicalPlanNode) -> Result<OperatorCompute> {
+        let stats = self.logical_plan.get_estimated_stats(lpn.id)?;
+        let inbound_stats = self
+            .logical_plan
+            .get_inbound_nodes(lpn.id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|inbound_idx| self.logical_plan.get_estimated_stats(inbound_idx))
+            .collect::<Result<Vec<EstimatedStats>>>()?;
+
+        let (min_memory_in_mib, working_set_in_bytes) = match &lpn.node {
+            // the whole build side is held in a hash table
+            LogicalPlanNodeType::Join { .. } => (
+                2048,
+                2 * inbound_stats.get(1).map_or(0, |build| build.total_bytes()),
+            ),
+            LogicalPlanNodeType::Aggregate { .. }
+            | LogicalPlanNodeType::Distinct { .. }
+            | LogicalPlanNodeType::Union { distinct: true, .. } => (1024, 2 * stats.total_bytes()),
+            // the sort spills to storage so this only reduces the number
+            // of sorted runs
+            LogicalPlanNodeType::Sort { .. } => (
+                1024,
+                inbound_stats.first().map_or(0, |input| input.total_bytes()),
+            ),
+            // streaming tasks only hold a record or two at a time
+            _ => {
+                let row_bytes = inbound_stats
+                    .iter()
+                    .map(|input| input.row_bytes)
+                    .chain(std::iter::once(stats.row_bytes))
+                    .max()
+                    .unwrap_or(0);
+                (512, 2 * ROWS_PER_RECORD * row_bytes)
+            }
+        };
+        let memory_in_mib = (working_set_in_bytes.div_ceil(1024 * 1024))
+            .clamp(min_memory_in_mib, MAX_PRODUCER_MEMORY_IN_MIB);
+
+        let input_rows: usize = if inbound_stats.is_empty() {
+            stats.rows
+        } else {
+            inbound_stats.iter().map(|input| input.rows).sum()
+        };
+        let cpu_in_thousandths =
+            (1000 + 1000 * (input_rows / ROWS_PER_CPU)).min(MAX_PRODUCER_CPU_IN_THOUSANDTHS);
+
+        // the exchange hands each record to a single instance of a
+        // producer so only tasks that process records independently
+        // can have more than one instance; the values and
+        // generate_series table funcs each produce all of their rows and
+        // sorted records are only kept in order by a single instance
+        let instances = match &lpn.node {
+            _ if self.reads_ordered_records(lpn.id) => 1,
+            LogicalPlanNodeType::TableFunc { name, .. }
+                if name != "values" && name != "generate_series" =>
+            {
+                std::cmp::max(1, self.config.default_producer_instances)
+            }
+            LogicalPlanNodeType::Filter { .. } | LogicalPlanNodeType::Materialize { .. } => {
+                std::cmp::max(1, self.config.default_producer_instances)
+            }
+            _ => 1,
+        };
+
+        Ok(OperatorCompute {
+            instances,
+            cpu_in_thousandths,
+            memory_in_mib,
+        })
+    }
+
+    // Finds the columns referenced by the nodes downstream of a table
+    // source so the source only reads those columns. Names are matched
+    // without their table qualifier which can include columns of other
+    // sources; the sources ignore columns they don't have. Returns None
+    // when every column could be needed.
+    pub fn find_projection(&self, plan_node_id: usize) -> Option<Vec<String>> {
+        let mut columns: Vec<String> = Vec::new();
+        let mut visited: Vec<usize> = Vec::new();
+        let mut node_ids = self
+            .logical_plan
+            .get_outbound_nodes(plan_node_id)
+            .unwrap_or_default();
+        while let Some(node_id) = node_ids.pop() {
+            if visited.contains(&node_id) {
+                continue;
+            }
+            visited.push(node_id);
+
+            let node = self.logical_plan.get_node(node_id)?;
+            let found = match &node.node {
+                LogicalPlanNodeType::TableFunc { .. }
+                | LogicalPlanNodeType::Table { .. }
+                | LogicalPlanNodeType::Limit { .. } => true,
+                LogicalPlanNodeType::Filter { expr } => find_expr_columns(expr, &mut columns),
+                LogicalPlanNodeType::Join {
+                    left_keys,
+                    right_keys,
+                    ..
+                } => left_keys
+                    .iter()
+                    .chain(right_keys.iter())
+                    .all(|expr| find_expr_columns(expr, &mut columns)),
+                LogicalPlanNodeType::Aggregate {
+                    group_by,
+                    aggregates,
+                } => {
+                    group_by
+                        .iter()
+                        .all(|expr| find_expr_columns(expr, &mut columns))
+                        && aggregates
+                            .iter()
+                            .all(|agg| find_expr_columns(&agg.expr, &mut columns))
+                }
+                // distinct without keys compares every column
+                LogicalPlanNodeType::Distinct { keys } => {
+                    !keys.is_empty()
+                        && keys
+                            .iter()
+                            .all(|expr| find_expr_columns(expr, &mut columns))
+                }
+                LogicalPlanNodeType::Sort { exprs } => exprs
+                    .iter()
+                    .all(|sort_expr| find_expr_columns(&sort_expr.expr, &mut columns)),
+                LogicalPlanNodeType::Union {
+                    left_fields,
+                    right_fields,
+                    ..
+                } => left_fields
+                    .iter()
+                    .chain(right_fields.iter())
+                    .all(|field| find_select_item_columns(field, &mut columns)),
+                LogicalPlanNodeType::Materialize { fields } => fields
+                    .iter()
+                    .all(|field| find_select_item_columns(field, &mut columns)),
+            };
+            if !found {
+                return None;
+            }
+
+            node_ids.extend(
+                self.logical_plan
+                    .get_outbound_nodes(node_id)
+                    .unwrap_or_default(),
+            );
+        }
+
+        columns.sort();
+        columns.dedup();
+        Some(columns)
+    }
+
+    // Finds the comparisons between a column and a literal which are
+    // and-ed together in the filter reading the table source. Returns
+    // None when the source isn't only read by a filter or none of the
+    // comparisons can be pushed down.
+    pub fn find_pushdown_predicate(&self, plan_node_id: usize) -> Option<Expr> {
+        let filter_node_id = match self
+            .logical_plan
+            .get_outbound_nodes(plan_node_id)?
+            .as_slice()
+        {
+            [node_id] => *node_id,
+            _ => return None,
+        };
+        let filter_expr = match self.logical_plan.get_node(filter_node_id)?.node {
+            LogicalPlanNodeType::Filter { expr } => expr,
+            _ => return None,
+        };
+
+        let mut conjuncts: Vec<&Expr> = Vec::new();
+        split_conjuncts(&filter_expr, &mut conjuncts);
+        conjuncts
+            .into_iter()
+            .filter(|expr| is_pushable_comparison(expr))
+            .cloned()
+            .reduce(|left, right| Expr::BinaryOp {
+                left: Box::new(left),
+                op: BinaryOperator::And,
+                right: Box::new(right),
+            })
+    }
+
     fn build_operators(&mut self, lpn: &LogicalPlanNode) -> Result<Vec<Operator>> {
         match lpn.node {
             LogicalPlanNodeType::Materialize { .. } => self.build_materialize_operators(lpn),
             LogicalPlanNodeType::Filter { .. } => self.build_filter_operators(lpn),
+            LogicalPlanNodeType::Join { .. } => self.build_join_operators(lpn),
+            LogicalPlanNodeType::Aggregate { .. } => self.build_aggregate_operators(lpn),
+            LogicalPlanNodeType::Distinct { .. } => self.build_distinct_operators(lpn),
+            LogicalPlanNodeType::Sort { .. } => self.build_sort_operators(lpn),
+            LogicalPlanNodeType::Limit { .. } => self.build_limit_operators(lpn),
+            LogicalPlanNodeType::Union { .. } => self.build_union_operators(lpn),
             LogicalPlanNodeType::TableFunc { .. } => self.build_table_func_operators(lpn),
             _ => Err(PhysicalPlanError::NotImplemented(format!(
                 "LogicalPlanNodeType isn't implemented to build resources: {:?}",
@@ -320,6 +745,8 @@ impl PhysicalPlanner {
                 alias,
                 func_name: name,
                 args,
+                projection: self.find_projection(lpn.id),
+                predicate: self.find_pushdown_predicate(lpn.id),
                 max_rows_per_batch: 10_000, // TODO: - determine how to set this
             },
             _ => {
@@ -342,11 +769,7 @@ impl PhysicalPlanner {
                 outbound_exchange_id: self.new_operator_id(lpn.id, "exchange"),
                 inbound_exchange_ids: Vec::new(),
             },
-            compute: OperatorCompute {
-                instances: 1,
-                cpu_in_thousandths: 1000,
-                memory_in_mib: 512,
-            },
+            compute: self.estimate_compute(lpn)?,
         };
         let exchange = Operator {
             id: self.new_operator_id(lpn.id, "exchange"),
@@ -355,6 +778,8 @@ impl PhysicalPlanner {
                 task: op_task.clone(),
                 outbound_producer_ids: self.get_outbound_operators(lpn, "producer")?,
                 inbound_producer_ids: vec![producer.id.clone()],
+                ordered: false,
+                distribution: self.exchange_distribution(lpn)?,
             },
             compute: OperatorCompute {
                 instances: 1,
@@ -396,19 +821,340 @@ impl PhysicalPlanner {
                 outbound_exchange_id: self.new_operator_id(lpn.id, "exchange"),
                 inbound_exchange_ids: self.get_inbound_operators(&lpn, "exchange")?,
             },
+            compute: self.estimate_compute(lpn)?,
+        };
+        let exchange = Operator {
+            id: self.new_operator_id(lpn.id, "exchange"),
+            plan_id: lpn.id,
+            operator_type: OperatorType::Exchange {
+                task: op_task.clone(),
+                outbound_producer_ids: self.get_outbound_operators(&lpn, "producer")?,
+                inbound_producer_ids: vec![producer.id.clone()],
+                ordered: self.produces_ordered_records(lpn.id),
+                distribution: self.exchange_distribution(lpn)?,
+            },
             compute: OperatorCompute {
                 instances: 1,
-                cpu_in_thousandths: 1000,
-                memory_in_mib: 512,
+                cpu_in_thousandths: 200,
+                memory_in_mib: 128,
+            },
+        };
+
+        operators.push(producer);
+        operators.push(exchange);
+
+        Ok(operators)
+    }
+
+    // The hash join producer reads the left (probe) side from the first
+    // inbound exchange and the right (build) side from the second. The
+    // whole build side is held in memory so the producer requests more
+    // memory than the other producers and limits the build side to it.
+    pub(crate) fn build_join_operators(&mut self, lpn: &LogicalPlanNode) -> Result<Vec<Operator>> {
+        let op_task = match lpn.node.clone() {
+            LogicalPlanNodeType::Join {
+                join_type,
+                left_keys,
+                right_keys,
+            } => OperatorTask::HashJoin {
+                join_type,
+                left_keys,
+                right_keys,
+            },
+            _ => {
+                return Err(
+                    PhysicalPlanError::UnableToBuildOperatorForLogicalPlanNodeType("join", "join")
+                        .into(),
+                );
+            }
+        };
+
+        let mut operators: Vec<Operator> = Vec::new();
+
+        let producer = Operator {
+            id: self.new_operator_id(lpn.id, "producer"),
+            plan_id: lpn.id,
+            operator_type: OperatorType::Producer {
+                task: op_task.clone(),
+                outbound_exchange_id: self.new_operator_id(lpn.id, "exchange"),
+                inbound_exchange_ids: self.get_inbound_operators(lpn, "exchange")?,
             },
+            compute: self.estimate_compute(lpn)?,
         };
         let exchange = Operator {
             id: self.new_operator_id(lpn.id, "exchange"),
             plan_id: lpn.id,
             operator_type: OperatorType::Exchange {
                 task: op_task.clone(),
-                outbound_producer_ids: self.get_outbound_operators(&lpn, "producer")?,
+                outbound_producer_ids: self.get_outbound_operators(lpn, "producer")?,
+                inbound_producer_ids: vec![producer.id.clone()],
+                ordered: false,
+                distribution: self.exchange_distribution(lpn)?,
+            },
+            compute: OperatorCompute {
+                instances: 1,
+                cpu_in_thousandths: 200,
+                memory_in_mib: 128,
+            },
+        };
+
+        operators.push(producer);
+        operators.push(exchange);
+
+        Ok(operators)
+    }
+
+    // The aggregate producer holds the accumulators of every group in
+    // memory so it requests more memory than the other producers and
+    // fails if the groups grow beyond it.
+    pub(crate) fn build_aggregate_operators(
+        &mut self,
+        lpn: &LogicalPlanNode,
+    ) -> Result<Vec<Operator>> {
+        let op_task = match lpn.node.clone() {
+            LogicalPlanNodeType::Aggregate {
+                group_by,
+                aggregates,
+            } => OperatorTask::Aggregate {
+                group_by,
+                aggregates,
+            },
+            _ => {
+                return Err(
+                    PhysicalPlanError::UnableToBuildOperatorForLogicalPlanNodeType(
+                        "aggregate",
+                        "aggregate",
+                    )
+                    .into(),
+                );
+            }
+        };
+
+        let mut operators: Vec<Operator> = Vec::new();
+
+        let producer = Operator {
+            id: self.new_operator_id(lpn.id, "producer"),
+            plan_id: lpn.id,
+            operator_type: OperatorType::Producer {
+                task: op_task.clone(),
+                outbound_exchange_id: self.new_operator_id(lpn.id, "exchange"),
+                inbound_exchange_ids: self.get_inbound_operators(lpn, "exchange")?,
+            },
+            compute: self.estimate_compute(lpn)?,
+        };
+        let exchange = Operator {
+            id: self.new_operator_id(lpn.id, "exchange"),
+            plan_id: lpn.id,
+            operator_type: OperatorType::Exchange {
+                task: op_task.clone(),
+                outbound_producer_ids: self.get_outbound_operators(lpn, "producer")?,
+                inbound_producer_ids: vec![producer.id.clone()],
+                ordered: false,
+                distribution: self.exchange_distribution(lpn)?,
+            },
+            compute: OperatorCompute {
+                instances: 1,
+                cpu_in_thousandths: 200,
+                memory_in_mib: 128,
+            },
+        };
+
+        operators.push(producer);
+        operators.push(exchange);
+
+        Ok(operators)
+    }
+
+    pub(crate) fn build_distinct_operators(
+        &mut self,
+        lpn: &LogicalPlanNode,
+    ) -> Result<Vec<Operator>> {
+        let op_task = match lpn.node.clone() {
+            LogicalPlanNodeType::Distinct { keys } => OperatorTask::Distinct { keys },
+            _ => {
+                return Err(
+                    PhysicalPlanError::UnableToBuildOperatorForLogicalPlanNodeType(
+                        "distinct", "distinct",
+                    )
+                    .into(),
+                );
+            }
+        };
+
+        let mut operators: Vec<Operator> = Vec::new();
+
+        let producer = Operator {
+            id: self.new_operator_id(lpn.id, "producer"),
+            plan_id: lpn.id,
+            operator_type: OperatorType::Producer {
+                task: op_task.clone(),
+                outbound_exchange_id: self.new_operator_id(lpn.id, "exchange"),
+                inbound_exchange_ids: self.get_inbound_operators(lpn, "exchange")?,
+            },
+            compute: self.estimate_compute(lpn)?,
+        };
+        let exchange = Operator {
+            id: self.new_operator_id(lpn.id, "exchange"),
+            plan_id: lpn.id,
+            operator_type: OperatorType::Exchange {
+                task: op_task.clone(),
+                outbound_producer_ids: self.get_outbound_operators(lpn, "producer")?,
                 inbound_producer_ids: vec![producer.id.clone()],
+                ordered: false,
+                distribution: self.exchange_distribution(lpn)?,
+            },
+            compute: OperatorCompute {
+                instances: 1,
+                cpu_in_thousandths: 200,
+                memory_in_mib: 128,
+            },
+        };
+
+        operators.push(producer);
+        operators.push(exchange);
+
+        Ok(operators)
+    }
+
+    pub(crate) fn build_sort_operators(&mut self, lpn: &LogicalPlanNode) -> Result<Vec<Operator>> {
+        let op_task = match lpn.node.clone() {
+            LogicalPlanNodeType::Sort { exprs } => OperatorTask::Sort { exprs },
+            _ => {
+                return Err(
+                    PhysicalPlanError::UnableToBuildOperatorForLogicalPlanNodeType("sort", "sort")
+                        .into(),
+                );
+            }
+        };
+
+        let mut operators: Vec<Operator> = Vec::new();
+
+        let producer = Operator {
+            id: self.new_operator_id(lpn.id, "producer"),
+            plan_id: lpn.id,
+            operator_type: OperatorType::Producer {
+                task: op_task.clone(),
+                outbound_exchange_id: self.new_operator_id(lpn.id, "exchange"),
+                inbound_exchange_ids: self.get_inbound_operators(lpn, "exchange")?,
+            },
+            compute: self.estimate_compute(lpn)?,
+        };
+        let exchange = Operator {
+            id: self.new_operator_id(lpn.id, "exchange"),
+            plan_id: lpn.id,
+            operator_type: OperatorType::Exchange {
+                task: op_task.clone(),
+                outbound_producer_ids: self.get_outbound_operators(lpn, "producer")?,
+                inbound_producer_ids: vec![producer.id.clone()],
+                ordered: true,
+                distribution: self.exchange_distribution(lpn)?,
+            },
+            compute: OperatorCompute {
+                instances: 1,
+                cpu_in_thousandths: 200,
+                memory_in_mib: 128,
+            },
+        };
+
+        operators.push(producer);
+        operators.push(exchange);
+
+        Ok(operators)
+    }
+
+    pub(crate) fn build_limit_operators(&mut self, lpn: &LogicalPlanNode) -> Result<Vec<Operator>> {
+        let op_task = match lpn.node.clone() {
+            LogicalPlanNodeType::Limit { limit, offset } => OperatorTask::Limit { limit, offset },
+            _ => {
+                return Err(
+                    PhysicalPlanError::UnableToBuildOperatorForLogicalPlanNodeType(
+                        "limit", "limit",
+                    )
+                    .into(),
+                );
+            }
+        };
+
+        let mut operators: Vec<Operator> = Vec::new();
+
+        let producer = Operator {
+            id: self.new_operator_id(lpn.id, "producer"),
+            plan_id: lpn.id,
+            operator_type: OperatorType::Producer {
+                task: op_task.clone(),
+                outbound_exchange_id: self.new_operator_id(lpn.id, "exchange"),
+                inbound_exchange_ids: self.get_inbound_operators(lpn, "exchange")?,
+            },
+            compute: self.estimate_compute(lpn)?,
+        };
+        let exchange = Operator {
+            id: self.new_operator_id(lpn.id, "exchange"),
+            plan_id: lpn.id,
+            operator_type: OperatorType::Exchange {
+                task: op_task.clone(),
+                outbound_producer_ids: self.get_outbound_operators(lpn, "producer")?,
+                inbound_producer_ids: vec![producer.id.clone()],
+                ordered: self.produces_ordered_records(lpn.id),
+                distribution: self.exchange_distribution(lpn)?,
+            },
+            compute: OperatorCompute {
+                instances: 1,
+                cpu_in_thousandths: 200,
+                memory_in_mib: 128,
+            },
+        };
+
+        operators.push(producer);
+        operators.push(exchange);
+
+        Ok(operators)
+    }
+
+    // The union producer reads every record from the first inbound
+    // exchange before reading from the second so the column names of
+    // the left side are used for the output.
+    pub(crate) fn build_union_operators(&mut self, lpn: &LogicalPlanNode) -> Result<Vec<Operator>> {
+        let op_task = match lpn.node.clone() {
+            LogicalPlanNodeType::Union {
+                distinct,
+                left_fields,
+                right_fields,
+            } => OperatorTask::Union {
+                distinct,
+                left_fields,
+                right_fields,
+            },
+            _ => {
+                return Err(
+                    PhysicalPlanError::UnableToBuildOperatorForLogicalPlanNodeType(
+                        "union", "union",
+                    )
+                    .into(),
+                );
+            }
+        };
+
+        let mut operators: Vec<Operator> = Vec::new();
+
+        let producer = Operator {
+            id: self.new_operator_id(lpn.id, "producer"),
+            plan_id: lpn.id,
+            operator_type: OperatorType::Producer {
+                task: op_task.clone(),
+                outbound_exchange_id: self.new_operator_id(lpn.id, "exchange"),
+                inbound_exchange_ids: self.get_inbound_operators(lpn, "exchange")?,
+            },
+            compute: self.estimate_compute(lpn)?,
+        };
+        let exchange = Operator {
+            id: self.new_operator_id(lpn.id, "exchange"),
+            plan_id: lpn.id,
+            operator_type: OperatorType::Exchange {
+                task: op_task.clone(),
+                outbound_producer_ids: self.get_outbound_operators(lpn, "producer")?,
+                inbound_producer_ids: vec![producer.id.clone()],
+                ordered: false,
+                distribution: self.exchange_distribution(lpn)?,
             },
             compute: OperatorCompute {
                 instances: 1,
@@ -441,8 +1187,13 @@ impl PhysicalPlanner {
         };
 
         let op_task = OperatorTask::MaterializeFiles {
-            data_format: DataFormat::Parquet,
+            data_format: self.config.output_data_format.clone(),
+            parquet_writer: self.config.parquet_writer.clone(),
+            target_file_rows: self.config.target_file_rows,
+            partition_by: self.config.partition_by.clone(),
+            max_open_partitions: self.config.max_open_partitions,
             fields,
+            connection: self.config.results_connection.clone(),
         };
         let mut operators: Vec<Operator> = Vec::new();
 
@@ -454,11 +1205,7 @@ impl PhysicalPlanner {
                 outbound_exchange_id: self.new_operator_id(lpn.id, "exchange"),
                 inbound_exchange_ids: self.get_inbound_operators(lpn, "exchange")?,
             },
-            compute: OperatorCompute {
-                instances: 1,
-                cpu_in_thousandths: 1000,
-                memory_in_mib: 512,
-            },
+            compute: self.estimate_compute(lpn)?,
         };
         let exchange = Operator {
             id: self.new_operator_id(lpn.id, "exchange"),
@@ -467,6 +1214,8 @@ impl PhysicalPlanner {
                 task: op_task.clone(),
                 outbound_producer_ids: self.get_outbound_operators(lpn, "producer")?,
                 inbound_producer_ids: vec![producer.id.clone()],
+                ordered: false,
+                distribution: self.exchange_distribution(lpn)?,
             },
             compute: OperatorCompute {
                 instances: 1,
@@ -481,6 +1230,56 @@ impl PhysicalPlanner {
         Ok(operators)
     }
 
+    // A sort produces ordered records and the limit and filter keep
+    // the order of the records they read. The order only holds when
+    // the records are read by a single instance from an ordered
+    // exchange.
+    fn produces_ordered_records(&self, plan_node_id: usize) -> bool {
+        match self.logical_plan.get_node(plan_node_id).map(|lpn| lpn.node) {
+            Some(LogicalPlanNodeType::Sort { .. }) => true,
+            Some(LogicalPlanNodeType::Limit { .. }) | Some(LogicalPlanNodeType::Filter { .. }) => {
+                self.reads_ordered_records(plan_node_id)
+            }
+            _ => false,
+        }
+    }
+
+    fn reads_ordered_records(&self, plan_node_id: usize) -> bool {
+        self.logical_plan
+            .get_inbound_nodes(plan_node_id)
+            .unwrap_or_default()
+            .into_iter()
+            .any(|inbound_node_id| self.produces_ordered_records(inbound_node_id))
+    }
+
+    // Every instance of a join builds the hash table from the whole right
+    // (build) side so the exchange of the build side broadcasts its records.
+    fn exchange_distribution(&self, lpn: &LogicalPlanNode) -> Result<ExchangeDistribution> {
+        let outbound_lpn_ids = self
+            .logical_plan
+            .get_outbound_nodes(lpn.id)
+            .unwrap_or_default();
+        for out_lpn_id in outbound_lpn_ids {
+            let out_lpn = match self.logical_plan.get_node(out_lpn_id) {
+                Some(out_lpn) => out_lpn,
+                None => continue,
+            };
+            if !matches!(out_lpn.node, LogicalPlanNodeType::Join { .. }) {
+                continue;
+            }
+            let inbound_lpn_ids = self
+                .logical_plan
+                .get_inbound_nodes(out_lpn_id)
+                .unwrap_or_default();
+            if inbound_lpn_ids.get(1) == Some(&lpn.id) {
+                return Ok(ExchangeDistribution::Broadcast {
+                    consumer_instances: self.estimate_compute(&out_lpn)?.instances,
+                });
+            }
+        }
+        Ok(ExchangeDistribution::RoundRobin)
+    }
+
     fn get_outbound_operators(
         &self,
         lpn: &LogicalPlanNode,
@@ -522,3 +1321,148 @@ impl PhysicalPlanner {
         sid
     }
 }
+
+//////////////////////////////////////////////////////
+// Column References
+
+fn find_select_item_columns(item: &SelectItem, columns: &mut Vec<String>) -> bool {
+    match item {
+        SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => {
+            find_expr_columns(expr, columns)
+        }
+        SelectItem::Wildcard(_) | SelectItem::QualifiedWildcard(..) => false,
+    }
+}
+
+// Adds the names of the columns referenced by the expression. Returns
+// false for expressions it doesn't know how to search since they could
+// reference any column.
+fn find_expr_columns(expr: &Expr, columns: &mut Vec<String>) -> bool {
+    match expr {
+        Expr::Identifier(ident) => {
+            columns.push(ident.value.clone());
+            true
+        }
+        Expr::CompoundIdentifier(idents) => match idents.last() {
+            Some(ident) => {
+                columns.push(ident.value.clone());
+                true
+            }
+            None => false,
+        },
+        Expr::Value(_) | Expr::TypedString { .. } => true,
+        Expr::Function(func) => match &func.args {
+            FunctionArguments::None => true,
+            FunctionArguments::List(arg_list) => arg_list.args.iter().all(|arg| match arg {
+                FunctionArg::Unnamed(FunctionArgExpr::Expr(arg_expr))
+                | FunctionArg::Named {
+                    arg: FunctionArgExpr::Expr(arg_expr),
+                    ..
+                } => find_expr_columns(arg_expr, columns),
+                // count(*) doesn't need any of the columns
+                FunctionArg::Unnamed(FunctionArgExpr::Wildcard) => true,
+                _ => false,
+            }),
+            FunctionArguments::Subquery(_) => false,
+        },
+        Expr::BinaryOp { left, right, .. } => {
+            find_expr_columns(left, columns) && find_expr_columns(right, columns)
+        }
+        Expr::UnaryOp { expr, .. }
+        | Expr::Nested(expr)
+        | Expr::Cast { expr, .. }
+        | Expr::IsNull(expr)
+        | Expr::IsNotNull(expr)
+        | Expr::IsTrue(expr)
+        | Expr::IsNotTrue(expr)
+        | Expr::IsFalse(expr)
+        | Expr::IsNotFalse(expr) => find_expr_columns(expr, columns),
+        Expr::Between {
+            expr, low, high, ..
+        } => {
+            find_expr_columns(expr, columns)
+                && find_expr_columns(low, columns)
+                && find_expr_columns(high, columns)
+        }
+        Expr::InList { expr, list, .. } => {
+            find_expr_columns(expr, columns)
+                && list.iter().all(|item| find_expr_columns(item, columns))
+        }
+        Expr::Like { expr, pattern, .. } | Expr::ILike { expr, pattern, .. } => {
+            find_expr_columns(expr, columns) && find_expr_columns(pattern, columns)
+        }
+        Expr::Case {
+            operand,
+            conditions,
+            results,
+            else_result,
+        } => {
+            operand
+                .iter()
+                .chain(else_result.iter())
+                .all(|expr| find_expr_columns(expr, columns))
+                && conditions
+                    .iter()
+                    .chain(results.iter())
+                    .all(|expr| find_expr_columns(expr, columns))
+        }
+        _ => false,
+    }
+}
+
+//////////////////////////////////////////////////////
+// Predicate Pushdown
+
+fn split_conjuncts<'a>(expr: &'a Expr, conjuncts: &mut Vec<&'a Expr>) {
+    match expr {
+        Expr::BinaryOp {
+            left,
+            op: BinaryOperator::And,
+            right,
+        } => {
+            split_conjuncts(left, conjuncts);
+            split_conjuncts(right, conjuncts);
+        }
+        Expr::Nested(expr) => split_conjuncts(expr, conjuncts),
+        _ => conjuncts.push(expr),
+    }
+}
+
+fn is_column(expr: &Expr) -> bool {
+    matches!(expr, Expr::Identifier(_) | Expr::CompoundIdentifier(_))
+}
+
+fn is_literal(expr: &Expr) -> bool {
+    match expr {
+        Expr::Value(Value::Number(..)) | Expr::Value(Value::SingleQuotedString(_)) => true,
+        Expr::UnaryOp {
+            op: UnaryOperator::Minus,
+            expr,
+        } => matches!(expr.as_ref(), Expr::Value(Value::Number(..))),
+        _ => false,
+    }
+}
+
+// a column compared to a literal or a column between two literals
+fn is_pushable_comparison(expr: &Expr) -> bool {
+    match expr {
+        Expr::BinaryOp { left, op, right } => {
+            matches!(
+                op,
+                BinaryOperator::Eq
+                    | BinaryOperator::NotEq
+                    | BinaryOperator::Lt
+                    | BinaryOperator::LtEq
+                    | BinaryOperator::Gt
+                    | BinaryOperator::GtEq
+            ) && ((is_column(left) && is_literal(right)) || (is_literal(left) && is_column(right)))
+        }
+        Expr::Between {
+            expr,
+            negated: false,
+            low,
+            high,
+        } => is_column(expr) && is_literal(low) && is_literal(high),
+        _ => false,
+    }
+}
@@ -1,10 +1,33 @@
+use std::sync::Arc;
+
 use anyhow::Result;
+use arrow::datatypes::{DataType, Field, Schema};
 use sqlparser::ast::{
     BinaryOperator, Expr, FunctionArg, FunctionArgExpr, FunctionArgOperator, Ident, SelectItem,
     Value, WildcardAdditionalOptions,
 };
 
-use super::logical_planner::{LogicalPlan, LogicalPlanNodeType, LogicalPlanner, Stage, StageType};
+use sqlparser::dialect::GenericDialect;
+use sqlparser::parser::Parser;
+
+use super::logical_planner::{
+    Aggregate, AggregateFunction, JoinType, LogicalPlan, LogicalPlanNodeType, LogicalPlanner,
+    PlanError, SortExpr, Stage, StageType, TableSchemas,
+};
+
+fn parse_expr(sql: &str) -> Expr {
+    Parser::new(&GenericDialect {})
+        .try_with_sql(sql)
+        .and_then(|mut parser| parser.parse_expr())
+        .unwrap()
+}
+
+fn parse_select_items(sql: &str) -> Vec<SelectItem> {
+    Parser::new(&GenericDialect {})
+        .try_with_sql(sql)
+        .and_then(|mut parser| parser.parse_projection())
+        .unwrap()
+}
 
 #[test]
 fn test_simple_logical_plans() -> Result<()> {
@@ -147,6 +170,381 @@ fn test_simple_logical_plans() -> Result<()> {
 
                 lp.connect_stages(table_source_stage.clone(), materialize_stage.clone());
 
+                Some(lp)
+            }),
+        },
+        TestCase {
+            case_name: "select-string-concat".to_string(),
+            query: "select first || ' ' || last from people".to_string(),
+            expected_plan: Box::new(|| -> Option<LogicalPlan> {
+                let mut lp = LogicalPlan::new();
+
+                let table_source_stage = Stage::new(StageType::TableSource, 0, false);
+                let materialize_stage = Stage::new(StageType::Materialize, 2, true);
+
+                lp.add_node(
+                    LogicalPlanNodeType::Table {
+                        alias: None,
+                        name: "people".to_string(),
+                    },
+                    table_source_stage.clone(),
+                );
+                // || is left associative
+                lp.add_node(
+                    LogicalPlanNodeType::Materialize {
+                        fields: vec![SelectItem::UnnamedExpr(Expr::BinaryOp {
+                            left: Box::new(Expr::BinaryOp {
+                                left: Box::new(Expr::Identifier(Ident {
+                                    value: "first".to_string(),
+                                    quote_style: None,
+                                })),
+                                op: BinaryOperator::StringConcat,
+                                right: Box::new(Expr::Value(Value::SingleQuotedString(
+                                    " ".to_string(),
+                                ))),
+                            }),
+                            op: BinaryOperator::StringConcat,
+                            right: Box::new(Expr::Identifier(Ident {
+                                value: "last".to_string(),
+                                quote_style: None,
+                            })),
+                        })],
+                    },
+                    materialize_stage.clone(),
+                );
+
+                lp.connect_stages(table_source_stage.clone(), materialize_stage.clone());
+
+                Some(lp)
+            }),
+        },
+        TestCase {
+            case_name: "select-group-by-alias-with-aggregates".to_string(),
+            query: "select a + 1 as k, count(*), sum(b) / count(b) from t where b > 0 group by k"
+                .to_string(),
+            expected_plan: Box::new(|| -> Option<LogicalPlan> {
+                let mut lp = LogicalPlan::new();
+
+                let table_source_stage = Stage::new(StageType::TableSource, 0, false);
+                let filter_stage = Stage::new(StageType::Filter, 1, false);
+                let aggregate_stage = Stage::new(StageType::Aggregate, 3, false);
+                let materialize_stage = Stage::new(StageType::Materialize, 2, true);
+
+                lp.add_node(
+                    LogicalPlanNodeType::Table {
+                        alias: None,
+                        name: "t".to_string(),
+                    },
+                    table_source_stage.clone(),
+                );
+                lp.add_node(
+                    LogicalPlanNodeType::Filter {
+                        expr: parse_expr("b > 0"),
+                    },
+                    filter_stage.clone(),
+                );
+                lp.add_node(
+                    LogicalPlanNodeType::Aggregate {
+                        group_by: vec![parse_expr("a + 1")],
+                        aggregates: vec![
+                            Aggregate {
+                                func: AggregateFunction::Count,
+                                arg: None,
+                                expr: parse_expr("count(*)"),
+                            },
+                            Aggregate {
+                                func: AggregateFunction::Sum,
+                                arg: Some(parse_expr("b")),
+                                expr: parse_expr("sum(b)"),
+                            },
+                            Aggregate {
+                                func: AggregateFunction::Count,
+                                arg: Some(parse_expr("b")),
+                                expr: parse_expr("count(b)"),
+                            },
+                        ],
+                    },
+                    aggregate_stage.clone(),
+                );
+                lp.add_node(
+                    LogicalPlanNodeType::Materialize {
+                        fields: parse_select_items("a + 1 as k, count(*), sum(b) / count(b)"),
+                    },
+                    materialize_stage.clone(),
+                );
+
+                lp.connect_stages(table_source_stage.clone(), filter_stage.clone());
+                lp.connect_stages(filter_stage.clone(), aggregate_stage.clone());
+                lp.connect_stages(aggregate_stage.clone(), materialize_stage.clone());
+
+                Some(lp)
+            }),
+        },
+        TestCase {
+            case_name: "select-aggregate-without-group-by".to_string(),
+            query: "select max(b) from t".to_string(),
+            expected_plan: Box::new(|| -> Option<LogicalPlan> {
+                let mut lp = LogicalPlan::new();
+
+                let table_source_stage = Stage::new(StageType::TableSource, 0, false);
+                let aggregate_stage = Stage::new(StageType::Aggregate, 3, false);
+                let materialize_stage = Stage::new(StageType::Materialize, 2, true);
+
+                lp.add_node(
+                    LogicalPlanNodeType::Table {
+                        alias: None,
+                        name: "t".to_string(),
+                    },
+                    table_source_stage.clone(),
+                );
+                lp.add_node(
+                    LogicalPlanNodeType::Aggregate {
+                        group_by: vec![],
+                        aggregates: vec![Aggregate {
+                            func: AggregateFunction::Max,
+                            arg: Some(parse_expr("b")),
+                            expr: parse_expr("max(b)"),
+                        }],
+                    },
+                    aggregate_stage.clone(),
+                );
+                lp.add_node(
+                    LogicalPlanNodeType::Materialize {
+                        fields: parse_select_items("max(b)"),
+                    },
+                    materialize_stage.clone(),
+                );
+
+                lp.connect_stages(table_source_stage.clone(), aggregate_stage.clone());
+                lp.connect_stages(aggregate_stage.clone(), materialize_stage.clone());
+
+                Some(lp)
+            }),
+        },
+        TestCase {
+            case_name: "select-order-by".to_string(),
+            query:
+                "select a, b + 1 as c from t order by a desc, c nulls first, b asc nulls last, d"
+                    .to_string(),
+            expected_plan: Box::new(|| -> Option<LogicalPlan> {
+                let mut lp = LogicalPlan::new();
+
+                let table_source_stage = Stage::new(StageType::TableSource, 0, false);
+                let sort_stage = Stage::new(StageType::Sort, 3, false);
+                let materialize_stage = Stage::new(StageType::Materialize, 2, true);
+
+                lp.add_node(
+                    LogicalPlanNodeType::Table {
+                        alias: None,
+                        name: "t".to_string(),
+                    },
+                    table_source_stage.clone(),
+                );
+                lp.add_node(
+                    LogicalPlanNodeType::Sort {
+                        exprs: vec![
+                            SortExpr {
+                                expr: parse_expr("a"),
+                                asc: false,
+                                nulls_first: true,
+                            },
+                            SortExpr {
+                                expr: parse_expr("b + 1"),
+                                asc: true,
+                                nulls_first: true,
+                            },
+                            SortExpr {
+                                expr: parse_expr("b"),
+                                asc: true,
+                                nulls_first: false,
+                            },
+                            SortExpr {
+                                expr: parse_expr("d"),
+                                asc: true,
+                                nulls_first: false,
+                            },
+                        ],
+                    },
+                    sort_stage.clone(),
+                );
+                lp.add_node(
+                    LogicalPlanNodeType::Materialize {
+                        fields: parse_select_items("a, b + 1 as c"),
+                    },
+                    materialize_stage.clone(),
+                );
+
+                lp.connect_stages(table_source_stage.clone(), sort_stage.clone());
+                lp.connect_stages(sort_stage.clone(), materialize_stage.clone());
+
+                Some(lp)
+            }),
+        },
+        TestCase {
+            case_name: "select-order-by-limit-offset".to_string(),
+            query: "select * from t order by a limit 10 offset 5".to_string(),
+            expected_plan: Box::new(|| -> Option<LogicalPlan> {
+                let mut lp = LogicalPlan::new();
+
+                let table_source_stage = Stage::new(StageType::TableSource, 0, false);
+                let sort_stage = Stage::new(StageType::Sort, 3, false);
+                let limit_stage = Stage::new(StageType::Limit, 4, false);
+                let materialize_stage = Stage::new(StageType::Materialize, 2, true);
+
+                lp.add_node(
+                    LogicalPlanNodeType::Table {
+                        alias: None,
+                        name: "t".to_string(),
+                    },
+                    table_source_stage.clone(),
+                );
+                lp.add_node(
+                    LogicalPlanNodeType::Sort {
+                        exprs: vec![SortExpr {
+                            expr: parse_expr("a"),
+                            asc: true,
+                            nulls_first: false,
+                        }],
+                    },
+                    sort_stage.clone(),
+                );
+                lp.add_node(
+                    LogicalPlanNodeType::Limit {
+                        limit: Some(10),
+                        offset: Some(5),
+                    },
+                    limit_stage.clone(),
+                );
+                lp.add_node(
+                    LogicalPlanNodeType::Materialize {
+                        fields: parse_select_items("*"),
+                    },
+                    materialize_stage.clone(),
+                );
+
+                lp.connect_stages(table_source_stage.clone(), sort_stage.clone());
+                lp.connect_stages(sort_stage.clone(), limit_stage.clone());
+                lp.connect_stages(limit_stage.clone(), materialize_stage.clone());
+
+                Some(lp)
+            }),
+        },
+        TestCase {
+            case_name: "select-offset-without-limit".to_string(),
+            query: "select * from t offset 3".to_string(),
+            expected_plan: Box::new(|| -> Option<LogicalPlan> {
+                let mut lp = LogicalPlan::new();
+
+                let table_source_stage = Stage::new(StageType::TableSource, 0, false);
+                let limit_stage = Stage::new(StageType::Limit, 3, false);
+                let materialize_stage = Stage::new(StageType::Materialize, 2, true);
+
+                lp.add_node(
+                    LogicalPlanNodeType::Table {
+                        alias: None,
+                        name: "t".to_string(),
+                    },
+                    table_source_stage.clone(),
+                );
+                lp.add_node(
+                    LogicalPlanNodeType::Limit {
+                        limit: None,
+                        offset: Some(3),
+                    },
+                    limit_stage.clone(),
+                );
+                lp.add_node(
+                    LogicalPlanNodeType::Materialize {
+                        fields: parse_select_items("*"),
+                    },
+                    materialize_stage.clone(),
+                );
+
+                lp.connect_stages(table_source_stage.clone(), limit_stage.clone());
+                lp.connect_stages(limit_stage.clone(), materialize_stage.clone());
+
+                Some(lp)
+            }),
+        },
+        TestCase {
+            case_name: "select-distinct-order-by".to_string(),
+            query: "select distinct region, a + 1 as b from t order by region".to_string(),
+            expected_plan: Box::new(|| -> Option<LogicalPlan> {
+                let mut lp = LogicalPlan::new();
+
+                let table_source_stage = Stage::new(StageType::TableSource, 0, false);
+                let distinct_stage = Stage::new(StageType::Distinct, 3, false);
+                let sort_stage = Stage::new(StageType::Sort, 4, false);
+                let materialize_stage = Stage::new(StageType::Materialize, 2, true);
+
+                lp.add_node(
+                    LogicalPlanNodeType::Table {
+                        alias: None,
+                        name: "t".to_string(),
+                    },
+                    table_source_stage.clone(),
+                );
+                lp.add_node(
+                    LogicalPlanNodeType::Distinct {
+                        keys: vec![parse_expr("region"), parse_expr("a + 1")],
+                    },
+                    distinct_stage.clone(),
+                );
+                lp.add_node(
+                    LogicalPlanNodeType::Sort {
+                        exprs: vec![SortExpr {
+                            expr: parse_expr("region"),
+                            asc: true,
+                            nulls_first: false,
+                        }],
+                    },
+                    sort_stage.clone(),
+                );
+                lp.add_node(
+                    LogicalPlanNodeType::Materialize {
+                        fields: parse_select_items("region, a + 1 as b"),
+                    },
+                    materialize_stage.clone(),
+                );
+
+                lp.connect_stages(table_source_stage.clone(), distinct_stage.clone());
+                lp.connect_stages(distinct_stage.clone(), sort_stage.clone());
+                lp.connect_stages(sort_stage.clone(), materialize_stage.clone());
+
+                Some(lp)
+            }),
+        },
+        TestCase {
+            case_name: "select-distinct-wildcard".to_string(),
+            query: "select distinct * from t".to_string(),
+            expected_plan: Box::new(|| -> Option<LogicalPlan> {
+                let mut lp = LogicalPlan::new();
+
+                let table_source_stage = Stage::new(StageType::TableSource, 0, false);
+                let distinct_stage = Stage::new(StageType::Distinct, 3, false);
+                let materialize_stage = Stage::new(StageType::Materialize, 2, true);
+
+                lp.add_node(
+                    LogicalPlanNodeType::Table {
+                        alias: None,
+                        name: "t".to_string(),
+                    },
+                    table_source_stage.clone(),
+                );
+                lp.add_node(
+                    LogicalPlanNodeType::Distinct { keys: Vec::new() },
+                    distinct_stage.clone(),
+                );
+                lp.add_node(
+                    LogicalPlanNodeType::Materialize {
+                        fields: parse_select_items("*"),
+                    },
+                    materialize_stage.clone(),
+                );
+
+                lp.connect_stages(table_source_stage.clone(), distinct_stage.clone());
+                lp.connect_stages(distinct_stage.clone(), materialize_stage.clone());
+
                 Some(lp)
             }),
         },
@@ -167,3 +565,538 @@ fn test_simple_logical_plans() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_invalid_aggregates_are_rejected() -> Result<()> {
+    let queries = vec![
+        "select sum(*) from t",
+        "select sum(count(b)) from t",
+        "select sum(a, b) from t",
+        "select a from t group by count(a)",
+    ];
+
+    for query in queries {
+        println!("query: {}", query);
+        let mut planner = LogicalPlanner::new(query.to_string());
+        assert!(planner.build().is_err());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_order_by_without_by_is_an_error() -> Result<()> {
+    let mut planner = LogicalPlanner::new("select a from t order a desc".to_string());
+    let err = planner.build().unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<PlanError>(),
+        Some(PlanError::ExpectedByAfterKeyword(keyword)) if keyword == "ORDER"
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_invalid_limit_and_offset_are_rejected() -> Result<()> {
+    let queries = vec![
+        "select * from t limit -1",
+        "select * from t limit 1.5",
+        "select * from t limit 'ten'",
+        "select * from t limit 10 offset -2",
+    ];
+
+    for query in queries {
+        println!("query: {}", query);
+        let mut planner = LogicalPlanner::new(query.to_string());
+        let err = planner.build().unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<PlanError>(),
+            Some(PlanError::InvalidNumber(_))
+        ));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_malformed_where_clauses_are_rejected() -> Result<()> {
+    let queries = vec![
+        "select * from t where (a > 1",
+        "select * from t where a > 1)",
+        "select * from t where",
+        "select * from t where a >",
+        "select * from t where a in ()",
+        "select * from t where a in (1, 2",
+    ];
+
+    for query in queries {
+        println!("query: {}", query);
+        let mut planner = LogicalPlanner::new(query.to_string());
+        assert!(planner.build().is_err());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_semicolons_inside_string_literals() -> Result<()> {
+    let mut planner =
+        LogicalPlanner::new("select * from t where note = 'a;b' and name = 'it''s';".to_string());
+    let plan = planner.build()?;
+
+    let mut expected_plan = LogicalPlan::new();
+    let table_source_stage = Stage::new(StageType::TableSource, 0, false);
+    let filter_stage = Stage::new(StageType::Filter, 1, false);
+    let materialize_stage = Stage::new(StageType::Materialize, 2, true);
+    expected_plan.add_node(
+        LogicalPlanNodeType::Table {
+            alias: None,
+            name: "t".to_string(),
+        },
+        table_source_stage.clone(),
+    );
+    expected_plan.add_node(
+        LogicalPlanNodeType::Filter {
+            expr: Expr::BinaryOp {
+                left: Box::new(parse_expr("note = 'a;b'")),
+                op: BinaryOperator::And,
+                right: Box::new(Expr::BinaryOp {
+                    left: Box::new(Expr::Identifier(Ident::new("name"))),
+                    op: BinaryOperator::Eq,
+                    right: Box::new(Expr::Value(Value::SingleQuotedString("it's".to_string()))),
+                }),
+            },
+        },
+        filter_stage.clone(),
+    );
+    expected_plan.add_node(
+        LogicalPlanNodeType::Materialize {
+            fields: parse_select_items("*"),
+        },
+        materialize_stage.clone(),
+    );
+    expected_plan.connect_stages(table_source_stage, filter_stage.clone());
+    expected_plan.connect_stages(filter_stage, materialize_stage);
+
+    assert_eq!(plan, expected_plan);
+
+    Ok(())
+}
+
+#[test]
+fn test_between_binds_its_own_and() -> Result<()> {
+    let mut planner =
+        LogicalPlanner::new("select * from t where a between 1 and 5 and b = 1".to_string());
+    let plan = planner.build()?;
+
+    let filter_expr = plan
+        .get_all_nodes()
+        .into_iter()
+        .find_map(|node| match node.node {
+            LogicalPlanNodeType::Filter { expr } => Some(expr),
+            _ => None,
+        })
+        .expect("expected a filter node");
+
+    let expected_expr = Expr::BinaryOp {
+        left: Box::new(Expr::Between {
+            expr: Box::new(Expr::Identifier(Ident::new("a"))),
+            negated: false,
+            low: Box::new(Expr::Value(Value::Number("1".to_string(), false))),
+            high: Box::new(Expr::Value(Value::Number("5".to_string(), false))),
+        }),
+        op: BinaryOperator::And,
+        right: Box::new(parse_expr("b = 1")),
+    };
+    assert_eq!(filter_expr, expected_expr);
+
+    Ok(())
+}
+
+#[test]
+fn test_join_plans() -> Result<()> {
+    struct TestCase {
+        case_name: String,
+        query: String,
+        join_type: JoinType,
+        left_keys: Vec<Expr>,
+        right_keys: Vec<Expr>,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            case_name: "inner-join".to_string(),
+            query: "select * from read_files('a') as a join read_files('b') as b on a.id = b.id"
+                .to_string(),
+            join_type: JoinType::Inner,
+            left_keys: vec![parse_expr("a.id")],
+            right_keys: vec![parse_expr("b.id")],
+        },
+        TestCase {
+            case_name: "left-join-with-swapped-and-multiple-keys".to_string(),
+            query: "select * from read_files('a') as a left join read_files('b') as b \
+                on b.id = a.id and a.day = b.day + 1"
+                .to_string(),
+            join_type: JoinType::Left,
+            left_keys: vec![parse_expr("a.id"), parse_expr("a.day")],
+            right_keys: vec![parse_expr("b.id"), parse_expr("b.day + 1")],
+        },
+    ];
+
+    for test_case in test_cases {
+        println!("test case: {}", test_case.case_name);
+
+        let mut planner = LogicalPlanner::new(test_case.query);
+        let plan = planner.build()?;
+
+        let mut expected_plan = LogicalPlan::new();
+        let table_source_stage = Stage::new(StageType::TableSource, 0, false);
+        let materialize_stage = Stage::new(StageType::Materialize, 2, true);
+        let join_stage = Stage::new(StageType::Join, 3, false);
+        for name in ["a", "b"] {
+            expected_plan.add_node(
+                LogicalPlanNodeType::TableFunc {
+                    alias: Some(name.to_string()),
+                    name: "read_files".to_string(),
+                    args: vec![FunctionArg::Unnamed(FunctionArgExpr::Expr(Expr::Value(
+                        Value::SingleQuotedString(name.to_string()),
+                    )))],
+                },
+                table_source_stage.clone(),
+            );
+        }
+        let join_node = expected_plan.add_node(
+            LogicalPlanNodeType::Join {
+                join_type: test_case.join_type,
+                left_keys: test_case.left_keys,
+                right_keys: test_case.right_keys,
+            },
+            join_stage.clone(),
+        );
+        expected_plan.connect(0, join_node);
+        expected_plan.connect(1, join_node);
+        expected_plan.add_node(
+            LogicalPlanNodeType::Materialize {
+                fields: parse_select_items("*"),
+            },
+            materialize_stage.clone(),
+        );
+        expected_plan.connect_stages(join_stage, materialize_stage);
+
+        assert_eq!(plan, expected_plan);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_invalid_join_conditions_are_rejected() -> Result<()> {
+    let queries = vec![
+        "select * from t1 join t2 on id = t2.id",
+        "select * from t1 join t2 on t1.id > t2.id",
+        "select * from t1 join t2 on t1.id = t1.other_id",
+        "select * from t1 join t2 on t1.id = t3.id",
+        "select * from t1 join t2 on t1.id = t2.id or t1.a = t2.a",
+    ];
+
+    for query in queries {
+        println!("query: {}", query);
+        let mut planner = LogicalPlanner::new(query.to_string());
+        let err = planner.build().unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<PlanError>(),
+            Some(PlanError::InvalidJoinCondition(_))
+        ));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_union_plans() -> Result<()> {
+    struct TestCase {
+        case_name: String,
+        query: String,
+        distinct: bool,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            case_name: "union-all".to_string(),
+            query: "select id, size from read_files('a') as a \
+                union all select id, name as size from read_files('b') as b where id > 1"
+                .to_string(),
+            distinct: false,
+        },
+        TestCase {
+            case_name: "union".to_string(),
+            query: "select id, size from read_files('a') as a \
+                union select id, name as size from read_files('b') as b where id > 1"
+                .to_string(),
+            distinct: true,
+        },
+    ];
+
+    for test_case in test_cases {
+        println!("test case: {}", test_case.case_name);
+
+        let mut planner = LogicalPlanner::new(test_case.query);
+        let plan = planner.build()?;
+
+        // each side of the union has its own table source and filter stages
+        let mut expected_plan = LogicalPlan::new();
+        let left_table_source_stage = Stage::new(StageType::TableSource, 0, false);
+        let right_table_source_stage = Stage::new(StageType::TableSource, 2, false);
+        let right_filter_stage = Stage::new(StageType::Filter, 3, false);
+        let union_stage = Stage::new(StageType::Union, 4, false);
+        let materialize_stage = Stage::new(StageType::Materialize, 5, true);
+        let mut table_nodes: Vec<usize> = Vec::new();
+        for (name, stage) in [
+            ("a", left_table_source_stage.clone()),
+            ("b", right_table_source_stage.clone()),
+        ] {
+            table_nodes.push(expected_plan.add_node(
+                LogicalPlanNodeType::TableFunc {
+                    alias: Some(name.to_string()),
+                    name: "read_files".to_string(),
+                    args: vec![FunctionArg::Unnamed(FunctionArgExpr::Expr(Expr::Value(
+                        Value::SingleQuotedString(name.to_string()),
+                    )))],
+                },
+                stage,
+            ));
+        }
+        let filter_node = expected_plan.add_node(
+            LogicalPlanNodeType::Filter {
+                expr: parse_expr("id > 1"),
+            },
+            right_filter_stage,
+        );
+        expected_plan.connect(table_nodes[1], filter_node);
+        let union_node = expected_plan.add_node(
+            LogicalPlanNodeType::Union {
+                distinct: test_case.distinct,
+                left_fields: parse_select_items("id, size"),
+                right_fields: parse_select_items("id, name as size"),
+            },
+            union_stage.clone(),
+        );
+        expected_plan.connect(table_nodes[0], union_node);
+        expected_plan.connect(filter_node, union_node);
+        expected_plan.add_node(
+            LogicalPlanNodeType::Materialize {
+                fields: parse_select_items("*"),
+            },
+            materialize_stage.clone(),
+        );
+        expected_plan.connect_stages(union_stage, materialize_stage);
+
+        assert_eq!(plan, expected_plan);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_mismatched_union_columns_are_rejected() -> Result<()> {
+    struct TestCase {
+        query: &'static str,
+        column_idx: usize,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            query: "select id, size from t1 union select id from t2",
+            column_idx: 1,
+        },
+        TestCase {
+            query: "select id from t1 union all select id, size from t2",
+            column_idx: 1,
+        },
+        TestCase {
+            query: "select id, 'small', 1 from t1 union select id, 'large', 'one' from t2",
+            column_idx: 2,
+        },
+    ];
+
+    for test_case in test_cases {
+        println!("query: {}", test_case.query);
+        let mut planner = LogicalPlanner::new(test_case.query.to_string());
+        let err = planner.build().unwrap_err();
+        match err.downcast_ref::<PlanError>() {
+            Some(PlanError::UnionColumnMismatch(column_idx)) => {
+                assert_eq!(*column_idx, test_case.column_idx)
+            }
+            _ => panic!("unexpected error: {}", err),
+        }
+    }
+
+    // wildcards and nulls can't be checked
+    for query in [
+        "select * from t1 union select id from t2",
+        "select id, null from t1 union select id, 'large' from t2",
+    ] {
+        LogicalPlanner::new(query.to_string()).build()?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_column_references_are_checked_against_table_schemas() -> Result<()> {
+    struct TestCase {
+        query: &'static str,
+        unknown_column: Option<&'static str>,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            query: "select id, size from read_files('a') as a where a.id > 1",
+            unknown_column: None,
+        },
+        TestCase {
+            query: "select id % 2 as parity, count(*) from read_files('a') as a group by parity",
+            unknown_column: None,
+        },
+        TestCase {
+            query: "select idd from read_files('a') as a",
+            unknown_column: Some("idd"),
+        },
+        TestCase {
+            query: "select id from read_files('a') as a where lower(name) = 'x'",
+            unknown_column: Some("name"),
+        },
+        TestCase {
+            query: "select id from read_files('a') as a where b.id = 1",
+            unknown_column: Some("b.id"),
+        },
+        TestCase {
+            query: "select id from read_files('a') as a group by a.size, name",
+            unknown_column: Some("name"),
+        },
+        TestCase {
+            query: "select a.id from read_files('a') as a join read_files('b') as b \
+                on a.id = b.missing",
+            unknown_column: None,
+        },
+    ];
+
+    let mut table_schemas = TableSchemas::new();
+    table_schemas.add_schema(
+        LogicalPlanNodeType::TableFunc {
+            alias: Some("a".to_string()),
+            name: "read_files".to_string(),
+            args: vec![FunctionArg::Unnamed(FunctionArgExpr::Expr(Expr::Value(
+                Value::SingleQuotedString("a".to_string()),
+            )))],
+        },
+        Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("size", DataType::Utf8, true),
+        ])),
+    );
+
+    for test_case in test_cases {
+        println!("query: {}", test_case.query);
+        let mut planner = LogicalPlanner::new(test_case.query.to_string());
+        planner.set_table_schemas(table_schemas.clone());
+        match (planner.build(), test_case.unknown_column) {
+            (Ok(_), None) => (),
+            (Err(err), Some(unknown_column)) => match err.downcast_ref::<PlanError>() {
+                Some(PlanError::UnknownColumn { name, available }) => {
+                    assert_eq!(name, unknown_column);
+                    assert_eq!(available, &vec!["id".to_string(), "size".to_string()]);
+                }
+                _ => panic!("unexpected error: {}", err),
+            },
+            (res, _) => panic!("unexpected result: {:?}", res.map(|_| ())),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_column_references_resolve_table_aliases() -> Result<()> {
+    struct TestCase {
+        query: &'static str,
+        ambiguous_column: Option<&'static str>,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            query: "select t.id, id from read_files('x') as t where t.size = 'small' and id > 1",
+            ambiguous_column: None,
+        },
+        TestCase {
+            query: "select t1.id from read_files('x') as t1 \
+                union all select t2.id from read_files('x') as t2 where size = 'small'",
+            ambiguous_column: None,
+        },
+        TestCase {
+            query: "select t1.id from read_files('x') as t1 \
+                join read_files('x') as t2 on t1.id = t2.id",
+            ambiguous_column: None,
+        },
+        TestCase {
+            query: "select size from read_files('x') as t1 \
+                join read_files('x') as t2 on t1.id = t2.id",
+            ambiguous_column: Some("size"),
+        },
+    ];
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("size", DataType::Utf8, true),
+    ]));
+    let mut table_schemas = TableSchemas::new();
+    for alias in ["t", "t1", "t2"] {
+        table_schemas.add_schema(
+            LogicalPlanNodeType::TableFunc {
+                alias: Some(alias.to_string()),
+                name: "read_files".to_string(),
+                args: vec![FunctionArg::Unnamed(FunctionArgExpr::Expr(Expr::Value(
+                    Value::SingleQuotedString("x".to_string()),
+                )))],
+            },
+            schema.clone(),
+        );
+    }
+
+    for test_case in test_cases {
+        println!("query: {}", test_case.query);
+        let mut planner = LogicalPlanner::new(test_case.query.to_string());
+        planner.set_table_schemas(table_schemas.clone());
+        match (planner.build(), test_case.ambiguous_column) {
+            (Ok(_), None) => (),
+            (Err(err), Some(ambiguous_column)) => match err.downcast_ref::<PlanError>() {
+                Some(PlanError::AmbiguousColumn { name, tables }) => {
+                    assert_eq!(name, ambiguous_column);
+                    assert_eq!(tables, &vec!["t1".to_string(), "t2".to_string()]);
+                }
+                _ => panic!("unexpected error: {}", err),
+            },
+            (res, _) => panic!("unexpected result: {:?}", res.map(|_| ())),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_explain_plans_the_inner_query() -> Result<()> {
+    let query = "select id, size from read_files('a') where id > 1";
+
+    let mut planner = LogicalPlanner::new(query.to_string());
+    let plan = planner.build()?;
+    assert!(!planner.is_explain());
+
+    let mut explain_planner = LogicalPlanner::new(format!("explain {}", query));
+    let explain_plan = explain_planner.build()?;
+    assert!(explain_planner.is_explain());
+    assert_eq!(plan, explain_plan);
+
+    let mut analyze_planner = LogicalPlanner::new(format!("explain analyze {}", query));
+    assert!(analyze_planner.build().is_err());
+
+    Ok(())
+}
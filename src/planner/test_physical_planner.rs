@@ -1,14 +1,15 @@
 use anyhow::{Error, Result};
 use sqlparser::ast::{Expr, SelectItem, Value, WildcardAdditionalOptions};
+use sqlparser::dialect::GenericDialect;
+use sqlparser::parser::Parser;
 
-use crate::planner::logical_planner::{LogicalPlan, LogicalPlanner};
-use crate::planner::physical_planner::{
-    DataFormat, Operator, OperatorCompute, OperatorTask, OperatorType, PhysicalPlan,
-    PhysicalPlanner, Pipeline,
+use super::physical_planner::PhysicalPlanError;
+use crate::planner::{
+    DataFormat, ExchangeDistribution, JoinType, LogicalPlan, LogicalPlanNodeType, LogicalPlanner,
+    Operator, OperatorCompute, OperatorTask, OperatorType, ParquetWriterConfig, PhysicalPlan,
+    PhysicalPlanner, Pipeline, PlannerConfig,
 };
 
-use super::logical_planner::LogicalPlanNodeType;
-
 #[test]
 fn test_simple_physical_plans() -> Result<()> {
     struct TestCase {
@@ -17,39 +18,249 @@ fn test_simple_physical_plans() -> Result<()> {
         plan_matchs_expected: Box<dyn Fn(&LogicalPlan, &PhysicalPlan) -> Result<()>>,
     }
 
-    let test_cases = vec![TestCase {
-        case_name: "select-with-filter-and-table-func".to_string(),
-        logical_plan: Box::new(|| -> Result<LogicalPlan> {
-            let query = "select * from read_files('data/path/*.parquet') where size = 'medium'";
-            let res = LogicalPlanner::new(query.to_string()).build()?;
-            Ok(res)
-        }),
-        plan_matchs_expected: Box::new(|lp, pp| -> Result<()> {
-            let plan_node_ids = &lp.get_all_node_ids();
-            let mut pipelines = pp.get_pipelines();
-
-            assert_eq!(1, pipelines.len());
-
-            let query_pipeline = &pipelines.remove(0);
-
-            // ensure all plan nodes have a corresponding physical operator
-            for plan_node_id in plan_node_ids {
-                if !query_pipeline.has_operators_for_plan_id(plan_node_id.clone()) {
-                    return Err(Error::msg(format!(
-                        "plan_node_id {} is missing physical operators",
-                        plan_node_id
-                    )));
+    let test_cases = vec![
+        TestCase {
+            case_name: "select-with-filter-and-table-func".to_string(),
+            logical_plan: Box::new(|| -> Result<LogicalPlan> {
+                let query = "select * from read_files('data/path/*.parquet') where size = 'medium'";
+                let res = LogicalPlanner::new(query.to_string()).build()?;
+                Ok(res)
+            }),
+            plan_matchs_expected: Box::new(|lp, pp| -> Result<()> {
+                let plan_node_ids = &lp.get_all_node_ids();
+                let mut pipelines = pp.get_pipelines();
+
+                assert_eq!(1, pipelines.len());
+
+                let query_pipeline = &pipelines.remove(0);
+
+                // ensure all plan nodes have a corresponding physical operator
+                for plan_node_id in plan_node_ids {
+                    if !query_pipeline.has_operators_for_plan_id(plan_node_id.clone()) {
+                        return Err(Error::msg(format!(
+                            "plan_node_id {} is missing physical operators",
+                            plan_node_id
+                        )));
+                    }
                 }
-            }
 
-            Ok(())
-        }),
-    }];
+                Ok(())
+            }),
+        },
+        TestCase {
+            case_name: "select-with-group-by".to_string(),
+            logical_plan: Box::new(|| -> Result<LogicalPlan> {
+                let query =
+                    "select size, count(*) from read_files('data/path/*.parquet') group by size";
+                let res = LogicalPlanner::new(query.to_string()).build()?;
+                Ok(res)
+            }),
+            plan_matchs_expected: Box::new(|lp, pp| -> Result<()> {
+                let plan_node_ids = &lp.get_all_node_ids();
+                let mut pipelines = pp.get_pipelines();
+
+                assert_eq!(1, pipelines.len());
+
+                let query_pipeline = &pipelines.remove(0);
+
+                // ensure all plan nodes have a corresponding physical operator
+                for plan_node_id in plan_node_ids {
+                    if !query_pipeline.has_operators_for_plan_id(plan_node_id.clone()) {
+                        return Err(Error::msg(format!(
+                            "plan_node_id {} is missing physical operators",
+                            plan_node_id
+                        )));
+                    }
+                }
+
+                // the aggregate is computed by its own producer
+                assert!(query_pipeline.get_operators().iter().any(|op| matches!(
+                    op.operator_type,
+                    OperatorType::Producer {
+                        task: OperatorTask::Aggregate { .. },
+                        ..
+                    }
+                )));
+
+                Ok(())
+            }),
+        },
+        TestCase {
+            case_name: "select-with-order-by".to_string(),
+            logical_plan: Box::new(|| -> Result<LogicalPlan> {
+                let query =
+                    "select id, size from read_files('data/path/*.parquet') order by size desc, id";
+                let res = LogicalPlanner::new(query.to_string()).build()?;
+                Ok(res)
+            }),
+            plan_matchs_expected: Box::new(|lp, pp| -> Result<()> {
+                let plan_node_ids = &lp.get_all_node_ids();
+                let mut pipelines = pp.get_pipelines();
+
+                assert_eq!(1, pipelines.len());
+
+                let query_pipeline = &pipelines.remove(0);
+
+                // ensure all plan nodes have a corresponding physical operator
+                for plan_node_id in plan_node_ids {
+                    if !query_pipeline.has_operators_for_plan_id(plan_node_id.clone()) {
+                        return Err(Error::msg(format!(
+                            "plan_node_id {} is missing physical operators",
+                            plan_node_id
+                        )));
+                    }
+                }
+
+                // the sort is computed by its own producer
+                assert!(query_pipeline.get_operators().iter().any(|op| matches!(
+                    op.operator_type,
+                    OperatorType::Producer {
+                        task: OperatorTask::Sort { .. },
+                        ..
+                    }
+                )));
+
+                Ok(())
+            }),
+        },
+        TestCase {
+            case_name: "select-with-limit".to_string(),
+            logical_plan: Box::new(|| -> Result<LogicalPlan> {
+                let query =
+                    "select id, size from read_files('data/path/*.parquet') limit 10 offset 5";
+                let res = LogicalPlanner::new(query.to_string()).build()?;
+                Ok(res)
+            }),
+            plan_matchs_expected: Box::new(|lp, pp| -> Result<()> {
+                let plan_node_ids = &lp.get_all_node_ids();
+                let mut pipelines = pp.get_pipelines();
+
+                assert_eq!(1, pipelines.len());
+
+                let query_pipeline = &pipelines.remove(0);
+
+                // ensure all plan nodes have a corresponding physical operator
+                for plan_node_id in plan_node_ids {
+                    if !query_pipeline.has_operators_for_plan_id(plan_node_id.clone()) {
+                        return Err(Error::msg(format!(
+                            "plan_node_id {} is missing physical operators",
+                            plan_node_id
+                        )));
+                    }
+                }
+
+                // the limit is applied by its own producer
+                assert!(query_pipeline.get_operators().iter().any(|op| matches!(
+                    op.operator_type,
+                    OperatorType::Producer {
+                        task: OperatorTask::Limit { .. },
+                        ..
+                    }
+                )));
+
+                Ok(())
+            }),
+        },
+        TestCase {
+            case_name: "select-with-union".to_string(),
+            logical_plan: Box::new(|| -> Result<LogicalPlan> {
+                let query = "select id, size from read_files('data/a/*.parquet') \
+                    union all select id, size from read_files('data/b/*.parquet')";
+                let res = LogicalPlanner::new(query.to_string()).build()?;
+                Ok(res)
+            }),
+            plan_matchs_expected: Box::new(|lp, pp| -> Result<()> {
+                let plan_node_ids = &lp.get_all_node_ids();
+                let mut pipelines = pp.get_pipelines();
+
+                assert_eq!(1, pipelines.len());
+
+                let query_pipeline = &pipelines.remove(0);
+
+                // ensure all plan nodes have a corresponding physical operator
+                for plan_node_id in plan_node_ids {
+                    if !query_pipeline.has_operators_for_plan_id(plan_node_id.clone()) {
+                        return Err(Error::msg(format!(
+                            "plan_node_id {} is missing physical operators",
+                            plan_node_id
+                        )));
+                    }
+                }
+
+                // both scans feed the union producer, left side first
+                let producers: Vec<(OperatorTask, Vec<String>)> = query_pipeline
+                    .get_operators()
+                    .into_iter()
+                    .filter_map(|op| match op.operator_type {
+                        OperatorType::Producer {
+                            task,
+                            inbound_exchange_ids,
+                            ..
+                        } => Some((task, inbound_exchange_ids)),
+                        _ => None,
+                    })
+                    .collect();
+                let table_func_exchange_ids: Vec<String> = query_pipeline
+                    .get_operators()
+                    .into_iter()
+                    .filter(|op| {
+                        matches!(
+                            op.operator_type,
+                            OperatorType::Exchange {
+                                task: OperatorTask::TableFunc { .. },
+                                ..
+                            }
+                        )
+                    })
+                    .map(|op| (op.plan_id, op.id))
+                    .collect::<std::collections::BTreeMap<usize, String>>()
+                    .into_values()
+                    .collect();
+                assert_eq!(table_func_exchange_ids.len(), 2);
+
+                let union_inbound_ids: Vec<&Vec<String>> = producers
+                    .iter()
+                    .filter(|(task, _)| {
+                        matches!(
+                            task,
+                            OperatorTask::Union {
+                                distinct: false,
+                                ..
+                            }
+                        )
+                    })
+                    .map(|(_, inbound_ids)| inbound_ids)
+                    .collect();
+                assert_eq!(union_inbound_ids, vec![&table_func_exchange_ids]);
+
+                // and the union feeds the single output
+                let materialize_inbound_ids: Vec<&Vec<String>> = producers
+                    .iter()
+                    .filter(|(task, _)| matches!(task, OperatorTask::MaterializeFiles { .. }))
+                    .map(|(_, inbound_ids)| inbound_ids)
+                    .collect();
+                assert_eq!(materialize_inbound_ids.len(), 1);
+                assert_eq!(materialize_inbound_ids[0].len(), 1);
+                assert!(query_pipeline.get_operators().iter().any(|op| {
+                    op.id == materialize_inbound_ids[0][0]
+                        && matches!(
+                            op.operator_type,
+                            OperatorType::Exchange {
+                                task: OperatorTask::Union { .. },
+                                ..
+                            }
+                        )
+                }));
+
+                Ok(())
+            }),
+        },
+    ];
 
     for test_case in test_cases {
         println!("test case: {}", test_case.case_name);
         let lp = &(test_case.logical_plan)()?;
-        let mut planner = PhysicalPlanner::new(lp.clone());
+        let mut planner = PhysicalPlanner::new(lp.clone(), PlannerConfig::default());
         let pp = &planner.build()?;
 
         (test_case.plan_matchs_expected)(lp, pp)?;
@@ -96,6 +307,8 @@ fn test_build_materialize_operators() -> Result<()> {
                 materialize_node.id.clone()
             )],
             inbound_producer_ids: vec![format!("operator_p{}_producer", filter_node.id.clone())],
+            ordered: false,
+            distribution: ExchangeDistribution::RoundRobin,
         },
         compute: OperatorCompute {
             instances: 1,
@@ -105,13 +318,17 @@ fn test_build_materialize_operators() -> Result<()> {
     };
     pipeline.add_operator(filter_exchange.clone());
 
-    let mut physical_planner = PhysicalPlanner::new(logical_plan);
+    let mut physical_planner = PhysicalPlanner::new(logical_plan, PlannerConfig::default());
     let mut operations = physical_planner.build_materialize_operators(&materialize_node)?;
 
     assert_eq!(2, operations.len());
 
     let ref expected_task_type = OperatorTask::MaterializeFiles {
         data_format: DataFormat::Parquet,
+        parquet_writer: ParquetWriterConfig::default(),
+        target_file_rows: 1024 * 1024,
+        partition_by: vec![],
+        max_open_partitions: 64,
         fields: vec![SelectItem::Wildcard(WildcardAdditionalOptions {
             opt_except: None,
             opt_ilike: None,
@@ -119,6 +336,7 @@ fn test_build_materialize_operators() -> Result<()> {
             opt_exclude: None,
             opt_replace: None,
         })],
+        connection: "default".to_string(),
     };
     let expected_producer = Operator {
         id: format!("operator_p{}_producer", materialize_node.id),
@@ -141,6 +359,8 @@ fn test_build_materialize_operators() -> Result<()> {
             task: expected_task_type.clone(),
             outbound_producer_ids: vec![],
             inbound_producer_ids: vec![expected_producer.id.clone()],
+            ordered: false,
+            distribution: ExchangeDistribution::RoundRobin,
         },
         compute: OperatorCompute {
             instances: 1,
@@ -157,3 +377,596 @@ fn test_build_materialize_operators() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_build_join_operators() -> Result<()> {
+    let query = "select * from read_files('a') as a left join read_files('b') as b on a.id = b.id \
+        where a.size = 'medium'";
+    let logical_plan = LogicalPlanner::new(query.to_string()).build()?;
+    let physical_plan =
+        PhysicalPlanner::new(logical_plan.clone(), PlannerConfig::default()).build()?;
+
+    let pipeline = &physical_plan.get_pipelines()[0];
+    for plan_node_id in logical_plan.get_all_node_ids() {
+        assert!(pipeline.has_operators_for_plan_id(plan_node_id));
+    }
+
+    let join_node = logical_plan
+        .get_all_nodes()
+        .into_iter()
+        .find(|item| matches!(item.node, LogicalPlanNodeType::Join { .. }))
+        .ok_or(Error::msg("unable to find join node"))?;
+    let join_producer = pipeline
+        .get_operators()
+        .into_iter()
+        .find(|item| item.id == format!("operator_p{}_producer", join_node.id))
+        .ok_or(Error::msg("unable to find join producer"))?;
+
+    match join_producer.operator_type {
+        OperatorType::Producer {
+            task: OperatorTask::HashJoin { join_type, .. },
+            inbound_exchange_ids,
+            ..
+        } => {
+            assert_eq!(join_type, JoinType::Left);
+            // the left relation is probed and the right relation is built
+            assert_eq!(
+                inbound_exchange_ids,
+                vec![
+                    "operator_p0_exchange".to_string(),
+                    "operator_p1_exchange".to_string()
+                ]
+            );
+        }
+        _ => return Err(Error::msg("expected a hash join producer")),
+    }
+    assert!(join_producer.compute.memory_in_mib > 512);
+
+    // only the build side is broadcast to the join instances
+    for (exchange_id, expected_distribution) in [
+        ("operator_p0_exchange", ExchangeDistribution::RoundRobin),
+        (
+            "operator_p1_exchange",
+            ExchangeDistribution::Broadcast {
+                consumer_instances: join_producer.compute.instances,
+            },
+        ),
+    ] {
+        let exchange = pipeline
+            .get_operators()
+            .into_iter()
+            .find(|item| item.id == exchange_id)
+            .ok_or(Error::msg("unable to find exchange"))?;
+        match exchange.operator_type {
+            OperatorType::Exchange { distribution, .. } => {
+                assert_eq!(distribution, expected_distribution);
+            }
+            _ => return Err(Error::msg("expected an exchange")),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_estimate_compute() -> Result<()> {
+    let query = "select size, count(*) from read_files('a') as a join read_files('b') as b \
+        on a.id = b.id where a.id > 10 group by size order by size limit 5 offset 2";
+    let logical_plan = LogicalPlanner::new(query.to_string()).build()?;
+    let physical_plan =
+        PhysicalPlanner::new(logical_plan.clone(), PlannerConfig::default()).build()?;
+    let planner = PhysicalPlanner::new(logical_plan.clone(), PlannerConfig::default());
+    let pipeline = &physical_plan.get_pipelines()[0];
+
+    for node in logical_plan.get_all_nodes() {
+        let stats = logical_plan.get_estimated_stats(node.id)?;
+        match &node.node {
+            LogicalPlanNodeType::TableFunc { .. } => {
+                assert_eq!(stats.rows, 1_000_000);
+            }
+            LogicalPlanNodeType::Join { .. } => {
+                assert_eq!(stats.rows, 1_000_000);
+                assert_eq!(stats.row_bytes, 256);
+            }
+            LogicalPlanNodeType::Filter { .. } => {
+                assert_eq!(stats.rows, 500_000);
+            }
+            LogicalPlanNodeType::Aggregate { .. } => {
+                assert_eq!(stats.rows, 50_000);
+                assert_eq!(stats.row_bytes, 32);
+            }
+            LogicalPlanNodeType::Limit { .. } => {
+                assert_eq!(stats.rows, 5);
+            }
+            _ => (),
+        }
+
+        // the producers are sized by the estimates
+        let compute = planner.estimate_compute(&node)?;
+        let producer = pipeline
+            .get_operators()
+            .into_iter()
+            .find(|item| item.id == format!("operator_p{}_producer", node.id))
+            .ok_or(Error::msg("unable to find producer"))?;
+        assert_eq!(producer.compute, compute);
+
+        let min_memory_in_mib = match &node.node {
+            LogicalPlanNodeType::Join { .. } => 2048,
+            LogicalPlanNodeType::Aggregate { .. } | LogicalPlanNodeType::Sort { .. } => 1024,
+            _ => 512,
+        };
+        assert!(compute.memory_in_mib >= min_memory_in_mib);
+        assert!(compute.cpu_in_thousandths >= 1000);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_producer_instances_are_configurable() -> Result<()> {
+    let query = "select size, count(*) from read_files('data/path/*.parquet') \
+        where size != 'small' group by size";
+    let logical_plan = LogicalPlanner::new(query.to_string()).build()?;
+    let config = PlannerConfig {
+        default_producer_instances: 3,
+        ..PlannerConfig::default()
+    };
+    let physical_plan = PhysicalPlanner::new(logical_plan, config).build()?;
+
+    let pipeline = &physical_plan.get_pipelines()[0];
+    for op in pipeline.get_operators() {
+        let expected_instances = match &op.operator_type {
+            OperatorType::Producer {
+                task: OperatorTask::TableFunc { .. },
+                ..
+            }
+            | OperatorType::Producer {
+                task: OperatorTask::Filter { .. },
+                ..
+            }
+            | OperatorType::Producer {
+                task: OperatorTask::MaterializeFiles { .. },
+                ..
+            } => 3,
+            // the aggregate needs every record and the exchanges
+            // fan the records out to the producer instances
+            _ => 1,
+        };
+        assert_eq!(
+            op.compute.instances, expected_instances,
+            "operator {}",
+            op.id
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_hash_partition_consumers_need_an_instance_per_partition() -> Result<()> {
+    let query = "select * from read_files('data/path/*.parquet') where size = 'medium'";
+    let logical_plan = LogicalPlanner::new(query.to_string()).build()?;
+    let physical_plan = PhysicalPlanner::new(logical_plan, PlannerConfig::default()).build()?;
+    let pipeline = &physical_plan.get_pipelines()[0];
+
+    let partitioned_plan = |consumer_instances: usize| -> PhysicalPlan {
+        let mut partitioned_pipeline = Pipeline::new(pipeline.id.clone());
+        for mut op in pipeline.get_operators() {
+            match &mut op.operator_type {
+                OperatorType::Exchange {
+                    task: OperatorTask::TableFunc { .. },
+                    distribution,
+                    ..
+                } => {
+                    *distribution = ExchangeDistribution::HashPartition {
+                        keys: vec!["size".to_string()],
+                        num_partitions: 4,
+                    };
+                }
+                OperatorType::Producer {
+                    task: OperatorTask::Filter { .. },
+                    ..
+                } => {
+                    op.compute.instances = consumer_instances;
+                }
+                _ => {}
+            }
+            partitioned_pipeline.add_operator(op);
+        }
+        let mut partitioned_plan = PhysicalPlan::new();
+        partitioned_plan.add_pipeline(partitioned_pipeline);
+        partitioned_plan
+    };
+
+    assert!(partitioned_plan(4).validate().is_ok());
+
+    let err = partitioned_plan(2)
+        .validate()
+        .expect_err("the consumer has fewer instances than partitions");
+    assert!(matches!(
+        err.downcast_ref::<PhysicalPlanError>(),
+        Some(PhysicalPlanError::HashPartitionConsumerInstancesMismatch(
+            _,
+            _,
+            2,
+            4
+        ))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_values_is_read_by_a_single_producer() -> Result<()> {
+    let query = "select * from values((1, 'a'), (2.5, 'b')) as t(id, name)";
+    let logical_plan = LogicalPlanner::new(query.to_string()).build()?;
+    let config = PlannerConfig {
+        default_producer_instances: 3,
+        ..PlannerConfig::default()
+    };
+    let physical_plan = PhysicalPlanner::new(logical_plan, config).build()?;
+
+    let pipelines = physical_plan.get_pipelines();
+    assert_eq!(pipelines.len(), 1);
+
+    let table_func_ops: Vec<Operator> = pipelines[0]
+        .get_operators()
+        .into_iter()
+        .filter(|op| {
+            matches!(
+                op.operator_type,
+                OperatorType::Producer {
+                    task: OperatorTask::TableFunc { .. },
+                    ..
+                }
+            )
+        })
+        .collect();
+    assert_eq!(table_func_ops.len(), 1);
+
+    let op = &table_func_ops[0];
+    assert_eq!(op.compute.instances, 1);
+    match &op.operator_type {
+        OperatorType::Producer {
+            task:
+                OperatorTask::TableFunc {
+                    alias,
+                    func_name,
+                    args,
+                    ..
+                },
+            ..
+        } => {
+            assert_eq!(alias, &Some("t".to_string()));
+            assert_eq!(func_name, "values");
+            assert_eq!(
+                args.iter()
+                    .map(|arg| arg.to_string())
+                    .collect::<Vec<String>>(),
+                vec!["(1, 'a')", "(2.5, 'b')", "columns => (id, name)"]
+            );
+        }
+        _ => return Err(Error::msg("expected a table func producer")),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_table_func_projection() -> Result<()> {
+    struct TestCase {
+        query: String,
+        expected_projections: Vec<Option<Vec<String>>>,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            query: "select id from read_files('data/*.parquet') where size != 'small'".to_string(),
+            expected_projections: vec![Some(vec!["id".to_string(), "size".to_string()])],
+        },
+        TestCase {
+            query: "select * from read_files('data/*.parquet') where size != 'small'".to_string(),
+            expected_projections: vec![None],
+        },
+        TestCase {
+            query: "select size, count(*) from read_files('data/*.parquet') group by size"
+                .to_string(),
+            expected_projections: vec![Some(vec!["size".to_string()])],
+        },
+        TestCase {
+            query: "select count(*) from read_files('data/*.parquet')".to_string(),
+            expected_projections: vec![Some(vec![])],
+        },
+        TestCase {
+            query: "select a.id, b.name from read_files('a/*.parquet') as a \
+                join read_files('b/*.parquet') as b on a.id = b.id"
+                .to_string(),
+            expected_projections: vec![
+                Some(vec!["id".to_string(), "name".to_string()]),
+                Some(vec!["id".to_string(), "name".to_string()]),
+            ],
+        },
+    ];
+
+    for test_case in test_cases {
+        println!("test case: {}", test_case.query);
+
+        let logical_plan = LogicalPlanner::new(test_case.query.clone()).build()?;
+        let physical_plan = PhysicalPlanner::new(logical_plan, PlannerConfig::default()).build()?;
+
+        let projections: Vec<Option<Vec<String>>> = physical_plan.get_pipelines()[0]
+            .get_operators()
+            .into_iter()
+            .filter_map(|op| match op.operator_type {
+                OperatorType::Producer {
+                    task: OperatorTask::TableFunc { projection, .. },
+                    ..
+                } => Some(projection),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(projections, test_case.expected_projections);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_table_func_pushdown_predicate() -> Result<()> {
+    struct TestCase {
+        query: String,
+        expected_predicate: Option<String>,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            query: "select id from read_files('data/*.parquet') \
+                where id > 2 and size like 's%' and 10 >= id"
+                .to_string(),
+            expected_predicate: Some("id > 2 AND 10 >= id".to_string()),
+        },
+        TestCase {
+            query: "select id from read_files('data/*.parquet') \
+                where id between 1 and 5 and size = 'small'"
+                .to_string(),
+            expected_predicate: Some("id BETWEEN 1 AND 5 AND size = 'small'".to_string()),
+        },
+        TestCase {
+            query: "select id from read_files('data/*.parquet') where id > 2 or id < 0".to_string(),
+            expected_predicate: None,
+        },
+        TestCase {
+            query: "select id from read_files('data/*.parquet')".to_string(),
+            expected_predicate: None,
+        },
+    ];
+
+    for test_case in test_cases {
+        println!("test case: {}", test_case.query);
+
+        let logical_plan = LogicalPlanner::new(test_case.query.clone()).build()?;
+        let physical_plan = PhysicalPlanner::new(logical_plan, PlannerConfig::default()).build()?;
+        let operators = physical_plan.get_pipelines()[0].get_operators();
+
+        let predicate = operators.iter().find_map(|op| match &op.operator_type {
+            OperatorType::Producer {
+                task: OperatorTask::TableFunc { predicate, .. },
+                ..
+            } => Some(predicate.clone()),
+            _ => None,
+        });
+        let expected_predicate = match &test_case.expected_predicate {
+            Some(expr) => Some(
+                Parser::new(&GenericDialect {})
+                    .try_with_sql(expr)?
+                    .parse_expr()?,
+            ),
+            None => None,
+        };
+        assert_eq!(predicate, Some(expected_predicate));
+
+        // the filter still applies the whole where clause
+        for op in operators {
+            if let OperatorType::Producer {
+                task: OperatorTask::Filter { expr },
+                ..
+            } = &op.operator_type
+            {
+                assert!(test_case.query.ends_with(&expr.to_string().to_lowercase()));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_physical_plan_to_json() -> Result<()> {
+    let query = "select id from read_files('data/*.parquet')";
+    let lp = LogicalPlanner::new(query.to_string()).build()?;
+    let pp = PhysicalPlanner::new(lp, PlannerConfig::default()).build()?;
+
+    let expected = include_str!("snapshots/physical_plan_read_files.json");
+    assert_eq!(pp.to_json()?, expected.trim_end());
+
+    Ok(())
+}
+
+#[test]
+fn test_output_data_format_is_configurable() -> Result<()> {
+    struct TestCase {
+        format_name: String,
+        expected_data_format: DataFormat,
+    }
+
+    let test_cases = vec![
+        TestCase {
+            format_name: "parquet".to_string(),
+            expected_data_format: DataFormat::Parquet,
+        },
+        TestCase {
+            format_name: "CSV".to_string(),
+            expected_data_format: DataFormat::Csv,
+        },
+    ];
+
+    for test_case in test_cases {
+        println!("test case: {}", test_case.format_name);
+
+        let query = "select id from read_files('data/*.parquet')";
+        let lp = LogicalPlanner::new(query.to_string()).build()?;
+        let config = PlannerConfig {
+            output_data_format: test_case.format_name.parse::<DataFormat>()?,
+            ..PlannerConfig::default()
+        };
+        let pp = PhysicalPlanner::new(lp, config).build()?;
+
+        let data_formats: Vec<DataFormat> = pp.get_pipelines()[0]
+            .get_operators()
+            .iter()
+            .filter_map(|op| match &op.operator_type {
+                OperatorType::Producer {
+                    task: OperatorTask::MaterializeFiles { data_format, .. },
+                    ..
+                } => Some(data_format.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(data_formats, vec![test_case.expected_data_format]);
+    }
+
+    assert!("orc".parse::<DataFormat>().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_exchanges_after_a_sort_are_ordered() -> Result<()> {
+    let query = "select id from read_files('data/*.parquet') where id > 3 order by id limit 10";
+    let logical_plan = LogicalPlanner::new(query.to_string()).build()?;
+    let physical_plan = PhysicalPlanner::new(logical_plan, PlannerConfig::default()).build()?;
+
+    let mut exchanges: Vec<(String, bool)> = physical_plan
+        .get_pipelines()
+        .iter()
+        .flat_map(|pipeline| pipeline.get_operators())
+        .filter_map(|op| match op.operator_type {
+            OperatorType::Exchange { task, ordered, .. } => {
+                Some((task.name().to_string(), ordered))
+            }
+            _ => None,
+        })
+        .collect();
+    exchanges.sort();
+    assert_eq!(
+        exchanges,
+        vec![
+            ("Filter".to_string(), false),
+            ("Limit".to_string(), true),
+            ("MaterializeFiles".to_string(), false),
+            ("Sort".to_string(), true),
+            ("TableFunc".to_string(), false),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_sorted_records_are_read_by_a_single_producer() -> Result<()> {
+    let queries = vec![
+        "select id from read_files('data/*.parquet') where id > 3 order by id",
+        "select id from read_files('data/*.parquet') where id > 3 order by id limit 10",
+    ];
+    for query in queries {
+        let logical_plan = LogicalPlanner::new(query.to_string()).build()?;
+        let config = PlannerConfig {
+            default_producer_instances: 3,
+            ..PlannerConfig::default()
+        };
+        let physical_plan = PhysicalPlanner::new(logical_plan, config).build()?;
+
+        let mut producers: Vec<(String, usize)> = physical_plan
+            .get_pipelines()
+            .iter()
+            .flat_map(|pipeline| pipeline.get_operators())
+            .filter_map(|op| match op.operator_type {
+                OperatorType::Producer { task, .. } => {
+                    Some((task.name().to_string(), op.compute.instances))
+                }
+                _ => None,
+            })
+            .collect();
+        producers.sort();
+
+        // the filter runs before the sort so it can still fan out
+        let mut expected = vec![
+            ("Filter".to_string(), 3),
+            ("MaterializeFiles".to_string(), 1),
+            ("Sort".to_string(), 1),
+            ("TableFunc".to_string(), 3),
+        ];
+        if query.contains("limit") {
+            expected.insert(1, ("Limit".to_string(), 1));
+        }
+        assert_eq!(producers, expected, "query: {}", query);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_physical_plan_operators_reference_each_other() -> Result<()> {
+    let query = "select a.size, count(*) from read_files('a') as a \
+        join read_files('b') as b on a.id = b.id group by a.size order by a.size";
+    let lp = LogicalPlanner::new(query.to_string()).build()?;
+    let pp = PhysicalPlanner::new(lp, PlannerConfig::default()).build()?;
+
+    let pipelines: &Vec<Pipeline> = pp.get_pipelines_ref();
+    assert_eq!(pipelines.len(), 1);
+
+    for pipeline in pipelines {
+        let operators: Vec<&Operator> = pipeline.get_operators_ref();
+        assert!(!operators.is_empty());
+
+        let find_operator = |op_id: &String| -> Result<&Operator> {
+            pp.get_operator(pipeline.id.clone(), op_id.clone())
+                .ok_or(Error::msg(format!("operator {} not found", op_id)))
+        };
+        for op in operators {
+            assert_eq!(find_operator(&op.id)?, op);
+            match &op.operator_type {
+                OperatorType::Producer {
+                    outbound_exchange_id,
+                    inbound_exchange_ids,
+                    ..
+                } => {
+                    assert!(matches!(
+                        find_operator(outbound_exchange_id)?.operator_type,
+                        OperatorType::Exchange { .. }
+                    ));
+                    for exchange_id in inbound_exchange_ids {
+                        assert!(matches!(
+                            find_operator(exchange_id)?.operator_type,
+                            OperatorType::Exchange { .. }
+                        ));
+                    }
+                }
+                OperatorType::Exchange {
+                    outbound_producer_ids,
+                    inbound_producer_ids,
+                    ..
+                } => {
+                    for producer_id in outbound_producer_ids.iter().chain(inbound_producer_ids) {
+                        assert!(matches!(
+                            find_operator(producer_id)?.operator_type,
+                            OperatorType::Producer { .. }
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
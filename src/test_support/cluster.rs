@@ -0,0 +1,193 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use arrow::array::RecordBatch;
+use thiserror::Error;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tracing::error;
+
+use crate::client::AsyncQueryClient;
+use crate::handlers::message_handler::messages;
+use crate::handlers::operator_handler::operators::ConnectionRegistry;
+use crate::handlers::operator_handler::TotalOperatorCompute;
+use crate::planner::PlannerConfig;
+use crate::worker::{QueryWorker, QueryWorkerConfig};
+
+const DEFAULT_QUERY_TIMEOUT: Duration = Duration::from_secs(30);
+const WORKER_START_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Error)]
+pub enum TestClusterError {
+    #[error("a test cluster needs at least one worker")]
+    NoWorkers,
+    #[error("worker at {0} didn't start listening")]
+    WorkerDidNotStart(String),
+    #[error("query was not created: {0:?}")]
+    QueryNotCreated(messages::query::RunQueryResp),
+}
+
+// Builds a cluster of in-process workers for end-to-end tests. The
+// workers share an in-memory default storage connection.
+pub struct TestClusterBuilder {
+    num_workers: usize,
+    allowed_compute: TotalOperatorCompute,
+    planner_config: PlannerConfig,
+    query_timeout: Duration,
+}
+
+impl TestClusterBuilder {
+    pub fn new() -> TestClusterBuilder {
+        TestClusterBuilder {
+            num_workers: 1,
+            allowed_compute: TotalOperatorCompute {
+                instances: 10,
+                memory_in_mib: 1 << 12,
+                cpu_in_thousandths: 4_000,
+            },
+            planner_config: PlannerConfig::default(),
+            query_timeout: DEFAULT_QUERY_TIMEOUT,
+        }
+    }
+
+    pub fn set_num_workers(&mut self, num_workers: usize) -> &Self {
+        self.num_workers = num_workers;
+        self
+    }
+
+    // The compute each worker can give to operator instances.
+    pub fn set_allowed_compute(&mut self, allowed_compute: TotalOperatorCompute) -> &Self {
+        self.allowed_compute = allowed_compute;
+        self
+    }
+
+    pub fn set_planner_config(&mut self, planner_config: PlannerConfig) -> &Self {
+        self.planner_config = planner_config;
+        self
+    }
+
+    // Queries which haven't finished within the timeout are failed.
+    pub fn set_query_timeout(&mut self, query_timeout: Duration) -> &Self {
+        self.query_timeout = query_timeout;
+        self
+    }
+
+    // Each worker connects to the workers started before it. Returns
+    // once every worker is listening.
+    pub async fn build(&self) -> Result<TestCluster> {
+        if self.num_workers == 0 {
+            return Err(TestClusterError::NoWorkers.into());
+        }
+
+        let mut conn_reg = ConnectionRegistry::new();
+        conn_reg.register_memory("default".to_string());
+
+        let mut cts: Vec<CancellationToken> = Vec::new();
+        let tt = TaskTracker::new();
+        let mut addresses: Vec<String> = Vec::new();
+        for _ in 0..self.num_workers {
+            let address = unused_address()?;
+            let mut config = QueryWorkerConfig::new(
+                address.clone(),
+                addresses.clone(),
+                self.allowed_compute.clone(),
+                conn_reg.clone(),
+            );
+            config.set_planner_config(self.planner_config.clone());
+
+            let mut worker = QueryWorker::new(config);
+            cts.push(worker.cancelation_token());
+            tt.spawn(async move {
+                if let Err(err) = worker.run().await {
+                    error!("test cluster worker failed: {:?}", err);
+                }
+            });
+            wait_for_listener(&address).await?;
+            addresses.push(address);
+        }
+
+        Ok(TestCluster {
+            addresses,
+            conn_reg,
+            query_timeout: self.query_timeout,
+            cts,
+            tt,
+        })
+    }
+}
+
+pub struct TestCluster {
+    addresses: Vec<String>,
+    conn_reg: ConnectionRegistry,
+    query_timeout: Duration,
+    cts: Vec<CancellationToken>,
+    tt: TaskTracker,
+}
+
+impl TestCluster {
+    pub fn addresses(&self) -> &Vec<String> {
+        &self.addresses
+    }
+
+    // The storage shared by the workers.
+    pub fn conn_reg(&self) -> &ConnectionRegistry {
+        &self.conn_reg
+    }
+
+    // A client of the first worker.
+    pub fn client(&self) -> AsyncQueryClient {
+        let mut client = AsyncQueryClient::new(self.addresses[0].clone());
+        client.set_query_timeout(self.query_timeout);
+        client
+    }
+
+    // Runs the query and waits for all of its results.
+    pub async fn run_query(&self, query: &str) -> Result<Vec<RecordBatch>> {
+        let (_, records) = self.run_query_with_id(query).await?;
+        Ok(records)
+    }
+
+    // The query id can be used to look up the metrics of the query.
+    pub async fn run_query_with_id(&self, query: &str) -> Result<(u128, Vec<RecordBatch>)> {
+        let client = self.client();
+        let query_id = match client.run_query(query.to_string()).await? {
+            messages::query::RunQueryResp::Created { query_id } => query_id,
+            resp => {
+                return Err(TestClusterError::QueryNotCreated(resp).into());
+            }
+        };
+        client.wait_for_query_results(query_id).await?;
+        let records = client
+            .collect_records(query_id)
+            .await?
+            .iter()
+            .map(|record| record.as_ref().clone())
+            .collect();
+        Ok((query_id, records))
+    }
+
+    // Cancels the workers and waits for them to stop.
+    pub async fn shutdown(self) {
+        for ct in self.cts.iter() {
+            ct.cancel();
+        }
+        self.tt.close();
+        self.tt.wait().await;
+    }
+}
+
+fn unused_address() -> Result<String> {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    Ok(listener.local_addr()?.to_string())
+}
+
+async fn wait_for_listener(address: &str) -> Result<()> {
+    let started_at = tokio::time::Instant::now();
+    while started_at.elapsed() < WORKER_START_TIMEOUT {
+        if tokio::net::TcpStream::connect(address).await.is_ok() {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    Err(TestClusterError::WorkerDidNotStart(address.to_string()).into())
+}
@@ -0,0 +1,6 @@
+mod cluster;
+
+pub use cluster::{TestCluster, TestClusterBuilder, TestClusterError};
+
+#[cfg(test)]
+mod test_cluster;
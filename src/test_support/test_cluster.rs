@@ -0,0 +1,84 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use arrow::array::Int64Array;
+
+use crate::handlers::message_handler::messages;
+
+use super::TestClusterBuilder;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_generate_series_through_the_cluster() -> Result<()> {
+    let mut builder = TestClusterBuilder::new();
+    builder.set_num_workers(2);
+    let cluster = builder.build().await?;
+
+    let records = cluster
+        .run_query("select * from generate_series(1,5,1)")
+        .await?;
+
+    let mut values: Vec<i64> = Vec::new();
+    for record in records.iter() {
+        let column = record
+            .column_by_name("generate_series")
+            .expect("expected the generate_series column");
+        let column = column
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .expect("expected an int64 column");
+        values.extend(column.values().iter());
+    }
+    values.sort();
+    assert_eq!(values, vec![1, 2, 3, 4, 5]);
+
+    cluster.shutdown().await;
+
+    Ok(())
+}
+
+// The limit completing stops the generate_series part way through
+// instead of waiting for it to produce the whole series.
+#[tokio::test(flavor = "multi_thread")]
+async fn test_limit_stops_the_generate_series() -> Result<()> {
+    let mut builder = TestClusterBuilder::new();
+    builder.set_num_workers(2);
+    let cluster = builder.build().await?;
+
+    let (query_id, records) = cluster
+        .run_query_with_id("select * from generate_series(1,1000000,1) limit 10")
+        .await?;
+    let num_rows: usize = records.iter().map(|record| record.num_rows()).sum();
+    assert_eq!(num_rows, 10);
+
+    // the stopped task reports its metrics as it closes, which can be
+    // after the query is complete
+    let client = cluster.client();
+    let mut records_out: Option<u64> = None;
+    for _ in 0..100 {
+        if let messages::query::GetQueryMetricsResp::Metrics(stats) =
+            client.get_query_metrics(query_id).await?
+        {
+            records_out = stats
+                .iter()
+                .find(|op_in_stats| op_in_stats.operator_id == "operator_p0_producer")
+                .and_then(|op_in_stats| op_in_stats.metrics)
+                .map(|metrics| metrics.records_out);
+        }
+        if records_out.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    let records_out = records_out.expect("expected the generate_series metrics");
+    assert!(records_out >= 10);
+    assert!(
+        records_out < 500_000,
+        "generate_series produced {} records",
+        records_out
+    );
+
+    cluster.shutdown().await;
+
+    Ok(())
+}
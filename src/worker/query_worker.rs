@@ -1,4 +1,5 @@
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Result;
 use tokio_util::sync::CancellationToken;
@@ -6,17 +7,38 @@ use tokio_util::task::TaskTracker;
 use tracing::info;
 use uuid::Uuid;
 
-use crate::handlers::message_handler::{ConnectionPoolHandler, MessageRegistry};
+use crate::handlers::message_handler::{
+    ConnectionPoolHandler, Heartbeat, MessageRegistry, Tls, TlsConfig,
+    DEFAULT_MAX_REASSEMBLY_BYTES, DEFAULT_REASSEMBLY_TIMEOUT,
+};
 use crate::handlers::message_router_handler::MessageRouterHandler;
 use crate::handlers::operator_handler::operators;
-use crate::handlers::operator_handler::{OperatorHandler, TotalOperatorCompute};
-use crate::handlers::query_handler::QueryHandler;
+use crate::handlers::operator_handler::{
+    OperatorHandler, RecordHeartbeat, TotalOperatorCompute, DEFAULT_EXCHANGE_MAX_BUFFERED_BYTES,
+    DEFAULT_IDENTIFY_EXCHANGE_MAX_ATTEMPTS,
+};
+use crate::handlers::query_data_handler::QueryDataHandler;
+use crate::handlers::query_handler::{QueryHandler, QueryStore, SchedulingPolicy};
+use crate::planner::PlannerConfig;
+
+const CONNECTION_STATS_LOG_INTERVAL: Duration = Duration::from_secs(60);
 
 pub struct QueryWorkerConfig {
     address: String,
     connect_to_addresses: Vec<String>,
     allowed_compute: TotalOperatorCompute,
-    conn_reg: Arc<operators::ConnectionRegistry>,
+    conn_reg: operators::ConnectionRegistry,
+    auth_token: Option<String>,
+    planner_config: PlannerConfig,
+    max_reassembly_bytes: usize,
+    tls_config: Option<TlsConfig>,
+    heartbeat: Heartbeat,
+    record_heartbeat: RecordHeartbeat,
+    exchange_max_buffered_bytes: usize,
+    identify_exchange_max_attempts: u32,
+    nan_policy: operators::NanPolicy,
+    scheduling_policy: SchedulingPolicy,
+    persist_query_state: bool,
 }
 
 impl QueryWorkerConfig {
@@ -24,15 +46,109 @@ impl QueryWorkerConfig {
         address: String,
         connect_to_addresses: Vec<String>,
         allowed_compute: TotalOperatorCompute,
-        conn_reg: operators::ConnectionRegistry,
+        mut conn_reg: operators::ConnectionRegistry,
     ) -> QueryWorkerConfig {
+        // develop against local storage when no storage is configured
+        if conn_reg.find_connection("default").is_none() {
+            conn_reg.register_fs(
+                "default".to_string(),
+                operators::DEFAULT_FS_ROOT.to_string(),
+            );
+        }
+
         QueryWorkerConfig {
             address,
             connect_to_addresses,
             allowed_compute,
-            conn_reg: Arc::new(conn_reg),
+            conn_reg,
+            auth_token: None,
+            planner_config: PlannerConfig::default(),
+            max_reassembly_bytes: DEFAULT_MAX_REASSEMBLY_BYTES,
+            tls_config: None,
+            heartbeat: Heartbeat::default(),
+            record_heartbeat: RecordHeartbeat::default(),
+            exchange_max_buffered_bytes: DEFAULT_EXCHANGE_MAX_BUFFERED_BYTES,
+            identify_exchange_max_attempts: DEFAULT_IDENTIFY_EXCHANGE_MAX_ATTEMPTS,
+            nan_policy: operators::NanPolicy::default(),
+            scheduling_policy: SchedulingPolicy::default(),
+            persist_query_state: false,
         }
     }
+
+    // Store the tables and query results in the bucket instead of the
+    // default connection given to the config.
+    pub fn set_s3_config(&mut self, s3_config: operators::S3Config) -> &Self {
+        self.conn_reg.register_s3("default".to_string(), s3_config);
+        self
+    }
+
+    pub fn set_auth_token(&mut self, token: String) -> &Self {
+        self.auth_token = Some(token);
+        self
+    }
+
+    pub fn set_scheduling_policy(&mut self, scheduling_policy: SchedulingPolicy) -> &Self {
+        self.scheduling_policy = scheduling_policy;
+        self
+    }
+
+    // Write the state of each query to the default storage connection so
+    // the queries can be restored after a restart.
+    pub fn set_persist_query_state(&mut self, persist_query_state: bool) -> &Self {
+        self.persist_query_state = persist_query_state;
+        self
+    }
+
+    pub fn set_planner_config(&mut self, planner_config: PlannerConfig) -> &Self {
+        self.planner_config = planner_config;
+        self
+    }
+
+    pub fn set_max_reassembly_bytes(&mut self, max_reassembly_bytes: usize) -> &Self {
+        self.max_reassembly_bytes = max_reassembly_bytes;
+        self
+    }
+
+    // Connections to and from the worker use tls when set; otherwise
+    // they are plaintext.
+    pub fn set_tls_config(&mut self, tls_config: TlsConfig) -> &Self {
+        self.tls_config = Some(tls_config);
+        self
+    }
+
+    pub fn set_heartbeat(&mut self, interval: Duration, timeout: Duration) -> &Self {
+        self.heartbeat = Heartbeat { interval, timeout };
+        self
+    }
+
+    // Tasks send a heartbeat for the records they're processing every
+    // interval and the exchanges give a record to another consumer after
+    // the timeout passes without one.
+    pub fn set_record_heartbeat(&mut self, interval: Duration, timeout: Duration) -> &Self {
+        self.record_heartbeat = RecordHeartbeat { interval, timeout };
+        self
+    }
+
+    // The exchanges write the records they buffer past this many bytes to
+    // the default storage connection until a consumer requests them.
+    pub fn set_exchange_max_buffered_bytes(&mut self, max_buffered_bytes: usize) -> &Self {
+        self.exchange_max_buffered_bytes = max_buffered_bytes;
+        self
+    }
+
+    // The tasks give up on finding the worker running an exchange after
+    // this many attempts.
+    pub fn set_identify_exchange_max_attempts(&mut self, max_attempts: u32) -> &Self {
+        self.identify_exchange_max_attempts = max_attempts;
+        self
+    }
+
+    // Filters, sorts and min/max compare NaN float values with this
+    // policy. Every worker in a cluster should use the same policy.
+    pub fn set_nan_policy(&mut self, nan_policy: operators::NanPolicy) -> &Self {
+        self.nan_policy = nan_policy;
+        self
+    }
 }
 
 pub struct QueryWorker {
@@ -58,14 +174,27 @@ impl QueryWorker {
         runtime.block_on(self.async_main())
     }
 
+    // Runs the worker on the current runtime until it's cancelled, for
+    // example to run several workers in one process.
+    pub async fn run(&mut self) -> Result<()> {
+        self.async_main().await
+    }
+
+    pub fn cancelation_token(&self) -> CancellationToken {
+        self.cancelation_token.clone()
+    }
+
     async fn async_main(&mut self) -> Result<()> {
         info!("worker_id: {}", self.worker_id);
 
         let tt = TaskTracker::new();
 
-        let msg_reg = Arc::new(MessageRegistry::new());
+        let mut msg_reg = MessageRegistry::new();
+        msg_reg
+            .set_fragment_reassembly(self.config.max_reassembly_bytes, DEFAULT_REASSEMBLY_TIMEOUT);
+        let msg_reg = Arc::new(msg_reg);
         let op_reg = Arc::new(operators::build_default_operator_task_registry()?);
-        let conn_reg = self.config.conn_reg.clone();
+        let conn_reg = Arc::new(self.config.conn_reg.clone());
 
         // Connect Pool and Router ////////////////////////
         let (mut connection_pool_handler, connection_msg_pipe) = ConnectionPoolHandler::new(
@@ -74,13 +203,41 @@ impl QueryWorker {
             self.config.connect_to_addresses.clone(),
             msg_reg.clone(),
         );
+        if let Some(tls_config) = &self.config.tls_config {
+            connection_pool_handler.set_tls(Tls::new(tls_config)?);
+        }
+        connection_pool_handler.set_heartbeat(self.config.heartbeat);
+        connection_pool_handler.set_auth_token(self.config.auth_token.clone());
+        let connection_stats = connection_pool_handler.stats();
 
         let (mut message_router, message_router_state) =
             MessageRouterHandler::new(self.worker_id.clone(), connection_msg_pipe, msg_reg.clone());
+        message_router.set_auth_token(self.config.auth_token.clone());
 
         // add internal subscribers
-        let mut query_handler =
-            QueryHandler::new(message_router_state.clone(), msg_reg.clone()).await;
+        let query_store = if self.config.persist_query_state {
+            Some(QueryStore::new(conn_reg.get_operator("default")?))
+        } else {
+            None
+        };
+        let mut query_handler = QueryHandler::new(
+            message_router_state.clone(),
+            msg_reg.clone(),
+            conn_reg.clone(),
+            self.config.planner_config.clone(),
+            query_store,
+        )
+        .await;
+        query_handler.set_scheduling_policy(self.config.scheduling_policy);
+
+        let mut query_data_handler = QueryDataHandler::new(
+            message_router_state.clone(),
+            msg_reg.clone(),
+            conn_reg.clone(),
+        )
+        .await;
+        query_data_handler
+            .set_results_connection(self.config.planner_config.results_connection.clone());
 
         let mut operator_handler = OperatorHandler::new(
             message_router_state.clone(),
@@ -90,6 +247,11 @@ impl QueryWorker {
             self.config.allowed_compute.clone(),
         )
         .await;
+        operator_handler.set_record_heartbeat(self.config.record_heartbeat);
+        operator_handler.set_exchange_max_buffered_bytes(self.config.exchange_max_buffered_bytes);
+        operator_handler
+            .set_identify_exchange_max_attempts(self.config.identify_exchange_max_attempts);
+        operator_handler.set_nan_policy(self.config.nan_policy);
 
         let ct = self.cancelation_token.clone();
         tt.spawn(async move {
@@ -98,6 +260,38 @@ impl QueryWorker {
             }
         });
 
+        let connection_stats_ct = self.cancelation_token.clone();
+        let stats_conn_reg = conn_reg.clone();
+        tt.spawn(async move {
+            let mut interval = tokio::time::interval(CONNECTION_STATS_LOG_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {
+                        let stats = connection_stats.snapshot();
+                        info!(
+                            active_connections = stats.active_connections,
+                            bytes_read = stats.bytes_read,
+                            messages_parsed = stats.messages_parsed,
+                            parse_errors = stats.parse_errors,
+                            "connection stats",
+                        );
+                        if let Some(stats) = stats_conn_reg.read_cache_stats() {
+                            info!(
+                                hits = stats.hits,
+                                misses = stats.misses,
+                                entries = stats.entries,
+                                bytes = stats.bytes,
+                                "read cache stats",
+                            );
+                        }
+                    }
+                    _ = connection_stats_ct.cancelled() => {
+                        break;
+                    }
+                }
+            }
+        });
+
         let message_router_ct = self.cancelation_token.clone();
         tt.spawn(async move {
             if let Err(err) = message_router.async_main(message_router_ct).await {
@@ -112,6 +306,13 @@ impl QueryWorker {
             }
         });
 
+        let query_data_handler_ct = self.cancelation_token.clone();
+        tt.spawn(async move {
+            if let Err(err) = query_data_handler.async_main(query_data_handler_ct).await {
+                info!("error: {}", err);
+            }
+        });
+
         let operator_handler_ct = self.cancelation_token.clone();
         tt.spawn(async move {
             if let Err(err) = operator_handler.async_main(operator_handler_ct).await {